@@ -0,0 +1,40 @@
+/* Applications run from the area `memory.x` keeps at the top of RAM - see
+   `src/app.rs` in the firmware */
+MEMORY
+{
+  APP : ORIGIN = 0x20007000, LENGTH = 4K
+}
+
+EXTERN(APP_HEADER);
+
+SECTIONS
+{
+  /* The header must come first, as that's where the firmware looks */
+  .text :
+  {
+    KEEP(*(.app_header));
+    *(.text .text.*);
+  } > APP
+
+  .rodata : ALIGN(4)
+  {
+    *(.rodata .rodata.*);
+  } > APP
+
+  /* The whole image is loaded into RAM, so .data needs no copying */
+  .data : ALIGN(4)
+  {
+    *(.data .data.*);
+  } > APP
+
+  /* The firmware zeroes the area before loading, so .bss needs no clearing */
+  .bss : ALIGN(4)
+  {
+    *(.bss .bss.*);
+  } > APP
+
+  /DISCARD/ :
+  {
+    *(.ARM.exidx.*);
+  }
+}
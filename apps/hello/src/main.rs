@@ -0,0 +1,91 @@
+//! A sample application for the `apps` example.
+//!
+//! It says hello, draws a box round the screen, plays a scale and waits
+//! for a key, which it hands back to the firmware. It's built on its own
+//! and loaded over XMODEM:
+//!
+//! ```
+//! $ cargo build --release
+//! $ arm-none-eabi-objcopy -O binary target/thumbv7em-none-eabihf/release/hello-app hello.bin
+//! ```
+
+#![no_std]
+#![no_main]
+
+// We only want the definitions, not the firmware's half
+#[allow(dead_code)]
+#[path = "../../../src/app.rs"]
+mod app;
+
+use app::{Api, Header};
+use core::panic::PanicInfo;
+
+const WIDTH: u32 = 400;
+const HEIGHT: u32 = 300;
+
+/// C major, in Hz
+const SCALE: [u32; 8] = [262, 294, 330, 349, 392, 440, 494, 523];
+
+/// What the firmware looks for. `app.x` puts it at the start of the image.
+#[link_section = ".app_header"]
+#[no_mangle]
+pub static APP_HEADER: Header = Header {
+    magic: app::MAGIC,
+    version: 1,
+    entry,
+};
+
+extern "C" fn entry(api: &'static Api) -> i32 {
+    print(api, "Hello from an application!\n");
+
+    for x in 0..WIDTH {
+        (api.plot)(x, 0, true);
+        (api.plot)(x, HEIGHT - 1, true);
+    }
+    for y in 0..HEIGHT {
+        (api.plot)(0, y, true);
+        (api.plot)(WIDTH - 1, y, true);
+    }
+
+    let start = (api.get_time)();
+    for &note in SCALE.iter() {
+        (api.play_note)(note, 200);
+    }
+    print(api, "That took ");
+    print_number(api, (api.get_time)().wrapping_sub(start));
+    print(api, " ms. Press a key...\n");
+
+    loop {
+        let key = (api.read_key)();
+        if key >= 0 {
+            return key;
+        }
+    }
+}
+
+fn print(api: &Api, s: &str) {
+    for byte in s.bytes() {
+        (api.print_char)(byte);
+    }
+}
+
+fn print_number(api: &Api, mut value: u32) {
+    let mut digits = [0u8; 10];
+    let mut len = 0;
+    loop {
+        digits[len] = b'0' + (value % 10) as u8;
+        len += 1;
+        value /= 10;
+        if value == 0 {
+            break;
+        }
+    }
+    for &digit in digits[0..len].iter().rev() {
+        (api.print_char)(digit);
+    }
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    loop {}
+}
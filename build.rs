@@ -4,14 +4,29 @@ use std::io::Write;
 use std::path::PathBuf;
 
 fn main() {
-    // Put the linker script somewhere the linker can find it
+    // Put the linker script for our chip somewhere the linker can find it
+    let memory: &[u8] = if env::var_os("CARGO_FEATURE_TM4C129").is_some() {
+        include_bytes!("memory-tm4c129.x")
+    } else if env::var_os("CARGO_FEATURE_STM32F407").is_some() {
+        include_bytes!("memory-stm32f407.x")
+    } else {
+        include_bytes!("memory.x")
+    };
     let out = &PathBuf::from(env::var_os("OUT_DIR").unwrap());
     File::create(out.join("memory.x"))
         .unwrap()
-        .write_all(include_bytes!("memory.x"))
+        .write_all(memory)
         .unwrap();
     println!("cargo:rustc-link-search={}", out.display());
 
+    // defmt keeps its format strings in a section of their own, which
+    // needs its own linker script. It's only there with `--features defmt`.
+    if env::var_os("CARGO_FEATURE_DEFMT").is_some() {
+        println!("cargo:rustc-link-arg=-Tdefmt.x");
+    }
+
     println!("cargo:rerun-if-changed=build.rs");
     println!("cargo:rerun-if-changed=memory.x");
+    println!("cargo:rerun-if-changed=memory-tm4c129.x");
+    println!("cargo:rerun-if-changed=memory-stm32f407.x");
 }
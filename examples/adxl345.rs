@@ -0,0 +1,158 @@
+//! Tap, double tap and free-fall detection with an ADXL345 accelerometer.
+//!
+//! SCL is PB2 (I2C0SCL), SDA is PB3 (I2C0SDA) and the ADXL345's INT1 pin
+//! goes to PE4.
+//!
+//! The ADXL345 does the detection itself and raises INT1. That edge fires
+//! the GPIO Port E interrupt, which just flags that something happened;
+//! the main loop then asks the ADXL345 what it was, prints it on UART0
+//! (115200 bps) and flashes an LED - blue for a tap, green for a double tap
+//! and red for free-fall.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board, gpio_port)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+#[cfg(feature = "tm4c123")]
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+#[cfg(feature = "tm4c129")]
+#[macro_use(interrupt)]
+extern crate tm4c129x;
+
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
+use demo::adxl345::{self, Adxl345};
+use demo::config;
+use demo::hal;
+use demo::i2c::I2c;
+use embedded_hal::digital::OutputPin;
+use embedded_hal::prelude::*;
+use hal::delay::Delay;
+use hal::gpio::GpioExt;
+use hal::sysctl;
+use hal::time::U32Ext;
+use rt::ExceptionFrame;
+
+/// INT1 is on PE4.
+const INT1_PIN: u32 = 1 << 4;
+
+/// How long each LED flash lasts.
+const FLASH_MS: u32 = 250;
+
+/// How often we go round the main loop.
+const POLL_MS: u32 = 10;
+
+/// Set by the GPIO interrupt, cleared by the main loop.
+static EVENT_PENDING: AtomicBool = AtomicBool::new(false);
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::I2c0);
+
+    let porte = gpio_port!(p, E).split(&board.power_control);
+
+    // I2C0SCL and I2C0SDA
+    config::I2C0.connect();
+    let bus = I2c::i2c0(p.I2C0, 400_000_u32.hz(), &board.clocks);
+
+    let mut accel = Adxl345::new(bus, adxl345::DEFAULT_ADDRESS).unwrap();
+    // 3g taps lasting under 10ms, with 50-300ms between double taps
+    accel.configure_tap(3_000, 10_000, 50, 300).unwrap();
+    // Under 0.4g on all axes for 100ms
+    accel.configure_free_fall(400, 100).unwrap();
+    let events = adxl345::INT_SINGLE_TAP | adxl345::INT_DOUBLE_TAP | adxl345::INT_FREE_FALL;
+    accel.enable_interrupts(events).unwrap();
+
+    // INT1 is active high. Interrupt on the rising edge.
+    let _int1 = porte.pe4.into_floating_input();
+    let gpio = config::Port::E.registers();
+    gpio.im.modify(|r, w| unsafe { w.bits(r.bits() & !INT1_PIN) });
+    gpio.is.modify(|r, w| unsafe { w.bits(r.bits() & !INT1_PIN) });
+    gpio.ibe.modify(|r, w| unsafe { w.bits(r.bits() & !INT1_PIN) });
+    gpio.iev.modify(|r, w| unsafe { w.bits(r.bits() | INT1_PIN) });
+    gpio.icr.write(|w| unsafe { w.bits(INT1_PIN) });
+    gpio.im.modify(|r, w| unsafe { w.bits(r.bits() | INT1_PIN) });
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(hal::Interrupt::GPIOE);
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+
+    writeln!(board.tx, "ADXL345 tap and free-fall demo").unwrap();
+
+    // Anything that happened before we were listening would hold INT1 high
+    // and we'd never see an edge, so clear it out.
+    accel.interrupt_source().unwrap();
+
+    let mut red_ms = 0;
+    let mut blue_ms = 0;
+    let mut green_ms = 0;
+
+    loop {
+        if EVENT_PENDING.swap(false, Ordering::SeqCst) {
+            let source = accel.interrupt_source().unwrap();
+            // A double tap also sets the single tap bit
+            if source & adxl345::INT_DOUBLE_TAP != 0 {
+                writeln!(board.tx, "Double tap").unwrap();
+                green_ms = FLASH_MS;
+            } else if source & adxl345::INT_SINGLE_TAP != 0 {
+                writeln!(board.tx, "Tap").unwrap();
+                blue_ms = FLASH_MS;
+            }
+            if source & adxl345::INT_FREE_FALL != 0 {
+                writeln!(board.tx, "Free-fall!").unwrap();
+                red_ms = FLASH_MS;
+            }
+        }
+
+        flash(&mut board.led_red, &mut red_ms);
+        flash(&mut board.led_blue, &mut blue_ms);
+        flash(&mut board.led_green, &mut green_ms);
+        d.delay_ms(POLL_MS);
+    }
+}
+
+/// Keep an LED lit while its timer runs down.
+fn flash<P>(led: &mut P, remaining_ms: &mut u32)
+where
+    P: OutputPin,
+{
+    if *remaining_ms > 0 {
+        led.set_high();
+        *remaining_ms = remaining_ms.saturating_sub(POLL_MS);
+    } else {
+        led.set_low();
+    }
+}
+
+interrupt!(GPIOE, gpioe_isr);
+
+fn gpioe_isr() {
+    let gpio = config::Port::E.registers();
+    gpio.icr.write(|w| unsafe { w.bits(INT1_PIN) });
+    EVENT_PENDING.store(true, Ordering::SeqCst);
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -1,78 +1,68 @@
 //! How to use the heap and a dynamic memory allocator
 //!
-//! To compile this example you'll need to build the collections crate as part
-//! of the Xargo sysroot. To do that change the Xargo.toml file to look like
-//! this:
-//!
-//! ``` text
-//! [dependencies.core]
-//! stage = 0
-//!
-//! [dependencies.collections] # NEW
-//! stage = 0
-//!
-//! [dependencies.compiler_builtins]
-//! stage = 1
-//! ```
-//!
-//! This example depends on the alloc-cortex-m crate so you'll have to add it
-//! to your Cargo.toml:
-//!
-//! ``` text
-//! # or edit the Cargo.toml file manually
-//! $ cargo add alloc-cortex-m
-//! ```
+//! This example depends on the alloc-cortex-m crate, and using the heap
+//! still needs a nightly compiler, so build this with `--features heap`.
 //!
 //! ---
 
-#![feature(collections)]
-#![feature(used)]
+#![feature(alloc)]
+#![feature(alloc_error_handler)]
+#![no_main]
 #![no_std]
 
-// This is the allocator crate; you can use a different one
-extern crate alloc_cortex_m;
 #[macro_use]
-extern crate collections;
-extern crate cortex_m;
-extern crate cortex_m_rt;
-extern crate cortex_m_semihosting;
+extern crate alloc;
+extern crate alloc_cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate cortex_m_semihosting as sh;
+extern crate panic_halt;
 
+use core::alloc::Layout;
 use core::fmt::Write;
 
-use cortex_m::asm;
-use cortex_m_semihosting::hio;
+use alloc_cortex_m::CortexMHeap;
+use rt::ExceptionFrame;
+use sh::hio;
 
-fn main() {
-    // Initialize the allocator
-    unsafe {
-        extern "C" {
-            // Start of the heap
-            static mut _sheap: usize;
-        }
+// this is the allocator the application will use
+#[global_allocator]
+static ALLOCATOR: CortexMHeap = CortexMHeap::empty();
 
-        // Size of the heap in words (1 word = 4 bytes)
-        // NOTE The bigger the heap the greater the chance to run into a stack
-        // overflow (collision between the stack and the heap)
-        const SIZE: isize = 256;
+// Size of the heap in bytes
+// NOTE The bigger the heap the greater the chance to run into a stack
+// overflow (collision between the stack and the heap)
+const HEAP_SIZE: usize = 1024;
 
-        // End of the heap
-        let _eheap = (&mut _sheap as *mut _).offset(SIZE);
+entry!(main);
 
-        alloc_cortex_m::init(&mut _sheap, _eheap);
-    }
+fn main() -> ! {
+    // Initialize the allocator BEFORE you use it
+    unsafe { ALLOCATOR.init(rt::heap_start() as usize, HEAP_SIZE) }
 
     // Growable array allocated on the heap
     let xs = vec![0, 1, 2];
 
     let mut stdout = hio::hstdout().unwrap();
     writeln!(stdout, "{:?}", xs).unwrap();
+
+    loop {}
+}
+
+// define what happens in an Out Of Memory (OOM) condition
+#[alloc_error_handler]
+fn alloc_error(_layout: Layout) -> ! {
+    loop {}
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
 }
 
-// As we are not using interrupts, we just register a dummy catch all handler
-#[link_section = ".vector_table.interrupts"]
-#[used]
-static INTERRUPTS: [extern "C" fn(); 240] = [default_handler; 240];
+exception!(*, default_handler);
 
-extern "C" fn default_handler() {
-    asm::bkpt();
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
 }
@@ -0,0 +1,225 @@
+//! Show where an analog thumbstick is, and calibrate it.
+//!
+//! See `demo::analog_joystick` for the wiring - X on PD0, Y on PD1 and the
+//! button on PD6. The VGA output is the same as `hello_vga` (HSYNC on PB6,
+//! VSYNC on PC4 and green on PB7), with a dot for the stick and the
+//! direction events on the status bar. The console is on UART0 at 115200
+//! bps. Commands:
+//!
+//! * `calibrate` - let go of the stick first, then go round the edges
+//! * `done` - finish calibrating, and save it in the EEPROM
+//! * `raw` - what the ADC says
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use cortex_m::interrupt;
+use cortex_m::peripheral::syst::SystClkSource;
+use demo::adc::Adc;
+use demo::analog_joystick::{AnalogJoystick, Calibration};
+use demo::board::Board;
+use demo::console::Console;
+use demo::eeprom::Eeprom;
+use demo::graphics::{Canvas, Colour, VGA_HEIGHT, VGA_WIDTH};
+use demo::joystick;
+use demo::status_bar;
+use demo::text::Buffer;
+use demo::vga;
+use embedded_hal::prelude::*;
+use menu::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::sysctl;
+
+/// The box the dot moves around in: room for the dot to go 100 pixels
+/// each way, and a gap at the edges.
+const BOX_SIZE: usize = 200 + DOT + 4;
+const BOX_X: usize = (VGA_WIDTH - BOX_SIZE) / 2;
+const BOX_Y: usize = status_bar::HEIGHT + ((VGA_HEIGHT - status_bar::HEIGHT - BOX_SIZE) / 2);
+
+/// Size of the dot.
+const DOT: usize = 4;
+
+/// SysTick's byte in the SCB's SHPR registers.
+const SHPR_SYSTICK: usize = 11;
+
+const CALIBRATE_ITEM: Item = Item {
+    item_type: ItemType::Callback(calibrate_callback),
+    command: "calibrate",
+    help: Some("let go of the stick, then go round the edges"),
+};
+
+const DONE_ITEM: Item = Item {
+    item_type: ItemType::Callback(done_callback),
+    command: "done",
+    help: Some("finish calibrating and save it"),
+};
+
+const RAW_ITEM: Item = Item {
+    item_type: ItemType::Callback(raw_callback),
+    command: "raw",
+    help: Some("show what the ADC says"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[&CALIBRATE_ITEM, &DONE_ITEM, &RAW_ITEM],
+    entry: None,
+    exit: None,
+};
+
+/// Sampled by SysTick.
+static mut STICK: Option<AnalogJoystick> = None;
+/// Only touched from the main loop.
+static mut EEPROM: Option<Eeprom> = None;
+
+/// Run a closure against the stick, with SysTick kept out.
+fn with_stick<F, T>(f: F) -> Option<T>
+where
+    F: FnOnce(&mut AnalogJoystick) -> T,
+{
+    interrupt::free(|_| unsafe { STICK.as_mut() }.map(f))
+}
+
+fn calibrate_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    with_stick(|s| s.start_calibration());
+    writeln!(Console, "Go round the edges a few times, then type 'done'").unwrap();
+}
+
+fn done_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    let calibration = match with_stick(|s| s.finish_calibration()) {
+        Some(Some(calibration)) => calibration,
+        _ => {
+            writeln!(Console, "That didn't look right. Try 'calibrate' again.").unwrap();
+            return;
+        }
+    };
+    writeln!(Console, "{:?}", calibration).unwrap();
+    match unsafe { EEPROM.as_mut() }.map(|e| calibration.save(e)) {
+        Some(Ok(())) => writeln!(Console, "Saved").unwrap(),
+        Some(Err(e)) => writeln!(Console, "Can't save: {:?}", e).unwrap(),
+        None => writeln!(Console, "No EEPROM").unwrap(),
+    }
+}
+
+fn raw_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    if let Some(raw) = with_stick(|s| s.raw()) {
+        writeln!(Console, "X {} Y {}", raw[0], raw[1]).unwrap();
+    }
+}
+
+/// The top left of the dot for a stick position.
+fn dot(position: (i16, i16)) -> (usize, usize) {
+    (
+        BOX_X + 2 + (100 + position.0) as usize,
+        BOX_Y + 2 + (100 + position.1) as usize,
+    )
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::Adc0);
+    board.enable(sysctl::Domain::Eeprom);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    let calibration = match Eeprom::new(p.EEPROM) {
+        Ok(mut eeprom) => {
+            let calibration = Calibration::load(&mut eeprom);
+            unsafe { EEPROM = Some(eeprom) };
+            calibration
+        }
+        Err(e) => {
+            writeln!(board.tx, "EEPROM failed: {:?}", e).unwrap();
+            Calibration::DEFAULT
+        }
+    };
+    let stick = AnalogJoystick::new(Adc::adc0(p.ADC0), calibration);
+    unsafe { STICK = Some(stick) };
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+
+    // Sample the stick every millisecond. Below the VGA timers, so the
+    // picture stays put.
+    unsafe { cp.SCB.shpr[SHPR_SYSTICK].write(0x40) };
+    let mut syst = cp.SYST;
+    syst.set_clock_source(SystClkSource::Core);
+    syst.set_reload((board.clocks.sysclk.0 / 1000) - 1);
+    syst.enable_counter();
+    syst.enable_interrupt();
+
+    let fb = vga::framebuffer();
+    fb.clear(Colour::BLACK);
+    status_bar::draw(fb, "Analog joystick", "");
+    fb.draw_rect(BOX_X, BOX_Y, BOX_SIZE, BOX_SIZE, Colour::WHITE);
+    let mut last = dot((0, 0));
+    fb.fill_rect(last.0, last.1, DOT, DOT, Colour::WHITE);
+
+    writeln!(board.tx, "Analog joystick. 'help' for help.").unwrap();
+
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+    loop {
+        if let Ok(ch) = board.rx.read() {
+            r.input_byte(ch);
+        }
+
+        while let Some(event) = joystick::next_event() {
+            let mut text = Buffer::new();
+            write!(text, "{:?}", event).unwrap();
+            status_bar::draw(fb, "Analog joystick", text.as_str());
+        }
+
+        if let Some(position) = with_stick(|s| s.position()) {
+            let now = dot(position);
+            if now != last {
+                fb.fill_rect(last.0, last.1, DOT, DOT, Colour::BLACK);
+                fb.fill_rect(now.0, now.1, DOT, DOT, Colour::WHITE);
+                last = now;
+            }
+        }
+    }
+}
+
+exception!(SysTick, sys_tick);
+
+fn sys_tick() {
+    if let Some(stick) = unsafe { STICK.as_mut() } {
+        stick.sample();
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,91 @@
+//! Drives a string of APA102 "DotStar" LEDs.
+//!
+//! Clock is PA2 (SSI0Clk) and data is PA5 (SSI0Tx). SW1 dims the string and
+//! SW2 brightens it, using the APA102's 5-bit global brightness.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+
+use core::fmt::Write;
+use demo::apa102::{Apa102, Rgb, MAX_BRIGHTNESS};
+use demo::config;
+use demo::hal;
+use embedded_hal::prelude::*;
+use hal::delay::Delay;
+use hal::sysctl;
+use rt::ExceptionFrame;
+
+const NUM_LEDS: usize = 60;
+
+/// How many LEDs in the comet's tail.
+const TAIL: usize = 8;
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Ssi0);
+
+    // SSI0Clk and SSI0Tx
+    config::SSI0.connect();
+
+    let mut leds = Apa102::new(p.SSI0);
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+
+    let mut colours = [Rgb::default(); NUM_LEDS];
+    let mut brightness = MAX_BRIGHTNESS / 2;
+    let mut position = 0;
+    let mut hue: u8 = 0;
+
+    writeln!(board.tx, "APA102 demo - SW1/SW2 change brightness").unwrap();
+
+    loop {
+        // A comet with a fading tail, slowly changing colour as it goes
+        let head = Rgb::wheel(hue);
+        for (i, colour) in colours.iter_mut().enumerate() {
+            let behind = (position + NUM_LEDS - i) % NUM_LEDS;
+            *colour = if behind < TAIL {
+                head.scale((255 >> behind) as u8)
+            } else {
+                Rgb::default()
+            };
+        }
+        leds.write(&colours, brightness);
+
+        position = (position + 1) % NUM_LEDS;
+        hue = hue.wrapping_add(1);
+
+        if board.sw1.is_pressed() && brightness > 0 {
+            brightness -= 1;
+            writeln!(board.tx, "Brightness {}/{}", brightness, MAX_BRIGHTNESS).unwrap();
+        } else if board.sw2.is_pressed() && brightness < MAX_BRIGHTNESS {
+            brightness += 1;
+            writeln!(board.tx, "Brightness {}/{}", brightness, MAX_BRIGHTNESS).unwrap();
+        }
+
+        d.delay_ms(30u32);
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,299 @@
+//! Run applications that were built separately from the firmware.
+//!
+//! Build an application (like `apps/hello`) against `demo::app`, turn it
+//! into a flat binary and send it over UART0 (115200 bps) with XMODEM. It
+//! goes into the application area at the top of RAM, and `run` calls it
+//! with a table of functions for the console, the VGA screen (HSYNC on PB6,
+//! VSYNC on PC4 and green on PB7, as `hello_vga`), a square wave on PB4
+//! (M0PWM2) for a piezo sounder and a millisecond clock. Nothing needs
+//! reflashing when the application changes.
+//!
+//! Commands:
+//!
+//! * `load` - receive an application with XMODEM
+//! * `run` - run it
+//! * `info` - show what's loaded
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use demo::app::{self, Api};
+use demo::board::Board;
+use demo::console::Console;
+use demo::graphics::{Canvas, Colour};
+use demo::vga;
+use demo::xmodem::{self, Xmodem};
+use embedded_hal::prelude::*;
+use menu::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::delay::Delay;
+use tm4c123x_hal::gpio::GpioExt;
+use tm4c123x_hal::sysctl;
+
+/// PWM0 runs at 80 MHz / 64.
+const PWM_CLOCK_HZ: u32 = 80_000_000 / 64;
+
+/// The 16-bit PWM counter can't go any lower than this.
+const MIN_HZ: u32 = 20;
+const MAX_HZ: u32 = 20_000;
+
+/// M0PWM2 in PWMENABLE.
+const PWM_OUTPUT: u32 = 1 << 2;
+
+/// RCC.USEPWMDIV, and RCC.PWMDIV set to /64.
+const RCC_PWMDIV_64: u32 = (1 << 20) | (0x7 << 17);
+
+/// Timer1 ticks per millisecond.
+const TICKS_PER_MS: u32 = 80_000;
+
+// GPTMCFG, GPTMTAMR, GPTMCTL
+const CFG_32_BIT: u32 = 0x0;
+const TAMR_PERIODIC: u32 = 0x2;
+const CTL_TAEN: u32 = 1 << 0;
+
+static API: Api = Api {
+    version: app::VERSION,
+    print_char,
+    read_key,
+    plot,
+    play_note,
+    get_time,
+};
+
+/// Counted up by Timer1A.
+static mut MILLISECONDS: u32 = 0;
+
+/// Set by `load`. The main loop does the loading, as it has the UART.
+static mut LOAD: bool = false;
+
+/// How much we loaded.
+static mut LOADED: usize = 0;
+
+const LOAD_ITEM: Item = Item {
+    item_type: ItemType::Callback(load_callback),
+    command: "load",
+    help: Some("receive an application with XMODEM"),
+};
+
+const RUN_ITEM: Item = Item {
+    item_type: ItemType::Callback(run_callback),
+    command: "run",
+    help: Some("run the application"),
+};
+
+const INFO_ITEM: Item = Item {
+    item_type: ItemType::Callback(info_callback),
+    command: "info",
+    help: Some("show what's loaded"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[&LOAD_ITEM, &RUN_ITEM, &INFO_ITEM],
+    entry: None,
+    exit: None,
+};
+
+extern "C" fn print_char(ch: u8) {
+    if ch == b'\n' {
+        Console.write_byte(b'\r');
+    }
+    Console.write_byte(ch);
+}
+
+extern "C" fn read_key() -> i32 {
+    match Console.read_byte() {
+        Some(byte) => byte as i32,
+        None => -1,
+    }
+}
+
+extern "C" fn plot(x: u32, y: u32, on: bool) {
+    let colour = if on { Colour::WHITE } else { Colour::BLACK };
+    vga::framebuffer().draw_point(x as usize, y as usize, colour);
+}
+
+extern "C" fn play_note(hz: u32, ms: u32) {
+    let pwm = unsafe { &*tm4c123x::PWM0::ptr() };
+    if hz != 0 {
+        let period = PWM_CLOCK_HZ / hz.max(MIN_HZ).min(MAX_HZ);
+        pwm._1_load.write(|w| unsafe { w.bits(period - 1) });
+        pwm._1_cmpa.write(|w| unsafe { w.bits((period / 2) - 1) });
+        pwm.enable
+            .modify(|r, w| unsafe { w.bits(r.bits() | PWM_OUTPUT) });
+    }
+    let start = get_time();
+    while get_time().wrapping_sub(start) < ms {}
+    pwm.enable
+        .modify(|r, w| unsafe { w.bits(r.bits() & !PWM_OUTPUT) });
+}
+
+extern "C" fn get_time() -> u32 {
+    unsafe { core::ptr::read_volatile(&MILLISECONDS) }
+}
+
+fn load_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    unsafe { LOAD = true };
+    writeln!(Console, "Send the application now").unwrap();
+}
+
+fn run_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    match app::run(&API) {
+        Ok(result) => writeln!(Console, "\nIt returned {}", result).unwrap(),
+        Err(e) => writeln!(Console, "Can't run it: {:?}", e).unwrap(),
+    }
+}
+
+fn info_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    writeln!(
+        Console,
+        "Application area 0x{:08x}..0x{:08x}",
+        app::APP_START,
+        app::APP_START + app::APP_LEN
+    ).unwrap();
+    writeln!(Console, "{} bytes loaded", unsafe { LOADED }).unwrap();
+    match app::header() {
+        Ok(header) => writeln!(Console, "Needs API version {}", header.version).unwrap(),
+        Err(e) => writeln!(Console, "{:?}", e).unwrap(),
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Timer1);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::Pwm0);
+
+    let mut portb = p.GPIO_PORTB.split(&board.power_control);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    // M0PWM2
+    let _beeper = portb.pb4.into_af4(&mut portb.control);
+
+    // Slow the PWM clock down, then set up generator 1 for a square wave.
+    // `play_note` sets the period and turns the output on.
+    let sysctl_regs = unsafe { &*tm4c123x::SYSCTL::ptr() };
+    sysctl_regs
+        .rcc
+        .modify(|r, w| unsafe { w.bits(r.bits() | RCC_PWMDIV_64) });
+    let pwm = p.PWM0;
+    pwm._1_ctl.write(|w| unsafe { w.bits(0) });
+    // ACTCMPAD = drive high, ACTLOAD = drive low
+    pwm._1_gena.write(|w| unsafe { w.bits((0x3 << 6) | (0x2 << 2)) });
+    pwm._1_ctl.write(|w| unsafe { w.bits(1) });
+
+    // Timer1A interrupts every millisecond
+    let timer = p.TIMER1;
+    timer.ctl.write(|w| unsafe { w.bits(0) });
+    timer.cfg.write(|w| unsafe { w.bits(CFG_32_BIT) });
+    timer.tamr.write(|w| unsafe { w.bits(TAMR_PERIODIC) });
+    timer.tailr.write(|w| unsafe { w.bits(TICKS_PER_MS - 1) });
+    timer.imr.modify(|_, w| w.tatoim().set_bit());
+    timer.icr.write(|w| w.tatocint().set_bit());
+    timer.ctl.write(|w| unsafe { w.bits(CTL_TAEN) });
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER1A);
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+
+    vga::framebuffer().clear(Colour::BLACK);
+
+    writeln!(
+        board.tx,
+        "Application host - API version {}, {} bytes at 0x{:08x}",
+        app::VERSION,
+        app::APP_LEN,
+        app::APP_START
+    ).unwrap();
+
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+
+    loop {
+        while let Ok(ch) = board.rx.read() {
+            r.input_byte(ch);
+        }
+
+        if !unsafe { LOAD } {
+            continue;
+        }
+        unsafe {
+            LOAD = false;
+            LOADED = 0;
+        }
+        // Zero the lot, so .bss starts out clear
+        let area = unsafe { app::area() };
+        for byte in area.iter_mut() {
+            *byte = 0;
+        }
+        let mut len = 0;
+        let result = Xmodem::new(r.output, &mut board.rx, &mut d).receive(|block| {
+            if len + block.len() > app::APP_LEN {
+                return false;
+            }
+            area[len..len + block.len()].copy_from_slice(block);
+            len += block.len();
+            true
+        });
+        // Give the terminal emulator a moment to tidy up
+        d.delay_ms(500u32);
+        match result {
+            Ok(_) => {
+                // XMODEM pads with SUB, which .bss doesn't want
+                while len > 0 && area[len - 1] == xmodem::SUB {
+                    len -= 1;
+                    area[len] = 0;
+                }
+                unsafe { LOADED = len };
+                writeln!(Console, "\nLoaded {} bytes", len).unwrap();
+            }
+            Err(e) => writeln!(Console, "\nLoad failed: {:?}", e).unwrap(),
+        }
+        r.prompt();
+    }
+}
+
+interrupt!(TIMER1A, timer1a_isr);
+
+fn timer1a_isr() {
+    let timer = unsafe { &*tm4c123x::TIMER1::ptr() };
+    timer.icr.write(|w| w.tatocint().set_bit());
+    unsafe { MILLISECONDS = MILLISECONDS.wrapping_add(1) };
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,166 @@
+//! Three tasks at once, written with `await!` - see `demo::executor`.
+//!
+//! * Anything typed on UART0 (115200 bps) is echoed back.
+//! * The red LED blinks twice a second.
+//! * Pressing SW1 prints how many times it's been pressed.
+//!
+//! Each task is a generator that reads like a blocking loop. Between them, the
+//! executor sleeps until an interrupt: SysTick for the delays, UART0 for the
+//! echo and GPIO Port F (Port J on the TM4C1294) for the button. The UART0
+//! handler masks its interrupt and leaves the byte in the FIFO for the task to
+//! collect; the GPIO handler clears its interrupt and sets a flag for the task
+//! to find. Either way, the handlers stay tiny and the work happens in the
+//! tasks.
+
+#![feature(generators, generator_trait)]
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use]
+extern crate demo;
+extern crate embedded_hal;
+#[macro_use]
+extern crate nb;
+extern crate panic_halt;
+#[cfg(feature = "tm4c123")]
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+#[cfg(feature = "tm4c129")]
+#[macro_use(interrupt)]
+extern crate tm4c129x;
+
+use core::fmt::Write;
+use core::ops::Generator;
+use core::sync::atomic::{AtomicBool, Ordering};
+use demo::config::{Pin, Port};
+use demo::console::Console;
+use demo::executor::{self, Future, Poll};
+use demo::hal;
+use embedded_hal::prelude::*;
+use rt::ExceptionFrame;
+
+/// SW1 is on PF4.
+#[cfg(feature = "tm4c123")]
+const SW1: Pin = Pin { port: Port::F, bit: 4 };
+/// USR_SW1 is on PJ0.
+#[cfg(feature = "tm4c129")]
+const SW1: Pin = Pin { port: Port::J, bit: 0 };
+const SW1_PIN: u32 = 1 << SW1.bit;
+
+/// How long the red LED spends on, and off.
+const BLINK_MS: u32 = 250;
+
+/// Ignore the switch for this long after a press, while it bounces.
+const DEBOUNCE_MS: u32 = 50;
+
+/// Set by the GPIO interrupt, cleared by `ButtonPress`.
+static SW1_PRESSED: AtomicBool = AtomicBool::new(false);
+
+/// Ready when SW1 has been pressed.
+struct ButtonPress;
+
+impl Future for ButtonPress {
+    type Output = ();
+
+    fn poll(&mut self) -> Poll<()> {
+        if SW1_PRESSED.swap(false, Ordering::SeqCst) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+
+    // SW1 pulls its pin low. Interrupt on the falling edge.
+    let gpio = SW1.port.registers();
+    gpio.im.modify(|r, w| unsafe { w.bits(r.bits() & !SW1_PIN) });
+    gpio.is.modify(|r, w| unsafe { w.bits(r.bits() & !SW1_PIN) });
+    gpio.ibe.modify(|r, w| unsafe { w.bits(r.bits() & !SW1_PIN) });
+    gpio.iev.modify(|r, w| unsafe { w.bits(r.bits() & !SW1_PIN) });
+    gpio.icr.write(|w| unsafe { w.bits(SW1_PIN) });
+    gpio.im.modify(|r, w| unsafe { w.bits(r.bits() | SW1_PIN) });
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(hal::Interrupt::UART0);
+    #[cfg(feature = "tm4c123")]
+    nvic.enable(hal::Interrupt::GPIOF);
+    #[cfg(feature = "tm4c129")]
+    nvic.enable(hal::Interrupt::GPIOJ);
+
+    executor::start(cp.SYST, &board.clocks);
+
+    writeln!(board.tx, "async demo - type something, or press SW1").unwrap();
+
+    // Each task gets its own part of the board
+    let mut tx = board.tx;
+    let mut led_red = board.led_red;
+
+    let mut echo = move || loop {
+        let byte = await!(executor::uart0_read());
+        block!(tx.write(byte)).unwrap();
+        if byte == b'\r' {
+            block!(tx.write(b'\n')).unwrap();
+        }
+    };
+
+    let mut blink = move || loop {
+        led_red.on();
+        await!(executor::delay_ms(BLINK_MS));
+        led_red.off();
+        await!(executor::delay_ms(BLINK_MS));
+    };
+
+    let mut button = || {
+        let mut presses = 0;
+        loop {
+            await!(ButtonPress);
+            presses += 1;
+            writeln!(Console, "\nSW1 pressed {} times", presses).unwrap();
+            await!(executor::delay_ms(DEBOUNCE_MS));
+            // Forget any bounces
+            SW1_PRESSED.store(false, Ordering::SeqCst);
+        }
+    };
+
+    let tasks: &mut [&mut Generator<Yield = (), Return = ()>] =
+        &mut [&mut echo, &mut blink, &mut button];
+    executor::run(tasks);
+}
+
+exception!(SysTick, executor::tick);
+
+#[cfg(feature = "tm4c123")]
+interrupt!(GPIOF, sw1_isr);
+#[cfg(feature = "tm4c129")]
+interrupt!(GPIOJ, sw1_isr);
+
+fn sw1_isr() {
+    let gpio = SW1.port.registers();
+    gpio.icr.write(|w| unsafe { w.bits(SW1_PIN) });
+    SW1_PRESSED.store(true, Ordering::SeqCst);
+}
+
+interrupt!(UART0, executor::uart0_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
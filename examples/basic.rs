@@ -0,0 +1,260 @@
+//! A home computer - Tiny BASIC on the VGA screen.
+//!
+//! Type on UART0 (115200 bps); everything appears on the VGA screen
+//! (HSYNC on PB6, VSYNC on PC4 and green on PB7, as `hello_vga`). See
+//! `demo::basic` for the language. For example:
+//!
+//! ```text
+//! 10 CLS
+//! 20 FOR I = 0 TO 299 STEP 10
+//! 30 LINE 0, I, 399, 299 - I
+//! 40 NEXT I
+//! 50 SOUND 440, 200
+//! RUN
+//! ```
+//!
+//! `PLOT` and `LINE` draw on the same 400 x 300 screen as the text.
+//! `SOUND` puts a square wave on PB4 (M0PWM2), with `demo::audio` - add a
+//! piezo sounder, or a small speaker and a transistor. Errors beep too.
+//! `SAVE` puts the program in the on-chip EEPROM, and `LOAD` gets it back,
+//! so there's room for 2 KiB of program there.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+extern crate vga_framebuffer as fb;
+
+use core::fmt::{self, Write};
+use demo::adc::{self, Adc};
+use demo::audio;
+use demo::basic::{Basic, Host};
+use demo::board::Board;
+use demo::eeprom::{self, Eeprom};
+use demo::graphics::{Canvas, Colour};
+use demo::random;
+use demo::vga;
+use embedded_hal::prelude::*;
+use rt::ExceptionFrame;
+use tm4c123x::UART0;
+use tm4c123x_hal::delay::Delay;
+use tm4c123x_hal::serial::Rx;
+use tm4c123x_hal::sysctl;
+
+/// Marks a saved program in the EEPROM. Then comes the length in bytes,
+/// then the program.
+const MAGIC: u32 = 0x4241_5343;
+const ADDR_MAGIC: usize = 0;
+const ADDR_PROGRAM: usize = 2;
+
+const CTRL_C: u8 = 0x03;
+
+/// The longest line you can type.
+const MAX_LINE: usize = 80;
+
+/// Everything the interpreter can get at.
+struct Machine<'a, W: 'a> {
+    text: &'a mut W,
+    /// How to clear `text`, which also homes the cursor
+    clear: fn(&mut W),
+    rx: Rx<UART0>,
+    delay: Delay,
+    eeprom: Eeprom,
+}
+
+impl<'a, W> Write for Machine<'a, W>
+where
+    W: Write,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.text.write_str(s)
+    }
+}
+
+impl<'a, W> Host for Machine<'a, W>
+where
+    W: Write,
+{
+    fn read_byte(&mut self) -> Option<u8> {
+        self.rx.read().ok()
+    }
+
+    fn read_line(&mut self, buffer: &mut [u8]) -> Option<usize> {
+        let mut len = 0;
+        loop {
+            let byte = match self.rx.read() {
+                Ok(byte) => byte,
+                Err(_) => continue,
+            };
+            match byte {
+                b'\r' => {
+                    writeln!(self.text).unwrap();
+                    return Some(len);
+                }
+                CTRL_C => {
+                    writeln!(self.text).unwrap();
+                    return None;
+                }
+                0x08 | 0x7F if len > 0 => {
+                    len -= 1;
+                    self.text.write_str("\x08 \x08").unwrap();
+                }
+                0x20...0x7E if len < buffer.len() => {
+                    buffer[len] = byte;
+                    len += 1;
+                    self.text.write_char(byte as char).unwrap();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn cls(&mut self) {
+        (self.clear)(self.text);
+    }
+
+    fn plot(&mut self, x: i32, y: i32, on: bool) {
+        if x >= 0 && y >= 0 {
+            let colour = if on { Colour::WHITE } else { Colour::BLACK };
+            vga::framebuffer().draw_point(x as usize, y as usize, colour);
+        }
+    }
+
+    fn line(&mut self, start: (i32, i32), end: (i32, i32), on: bool) {
+        let colour = if on { Colour::WHITE } else { Colour::BLACK };
+        vga::framebuffer().draw_line(
+            (start.0 as isize, start.1 as isize),
+            (end.0 as isize, end.1 as isize),
+            colour,
+        );
+    }
+
+    fn sound(&mut self, hz: u32, ms: u32) {
+        if hz != 0 {
+            audio::beep(hz, ms);
+        }
+        self.delay.delay_ms(ms);
+    }
+
+    fn alert(&mut self) {
+        audio::error();
+    }
+
+    fn pause(&mut self, ms: u32) {
+        self.delay.delay_ms(ms);
+    }
+
+    fn save(&mut self, program: &[u8]) -> bool {
+        if program.len() > (eeprom::WORDS - ADDR_PROGRAM) * 4 {
+            return false;
+        }
+        let mut result = self.eeprom.write(ADDR_MAGIC, &[0, program.len() as u32]);
+        for (i, bytes) in program.chunks(4).enumerate() {
+            let mut word = 0;
+            for (j, byte) in bytes.iter().enumerate() {
+                word |= u32::from(*byte) << (j * 8);
+            }
+            result = result.and_then(|_| self.eeprom.write(ADDR_PROGRAM + i, &[word]));
+        }
+        // The magic number goes in last, so half a program never loads
+        result
+            .and_then(|_| self.eeprom.write(ADDR_MAGIC, &[MAGIC]))
+            .is_ok()
+    }
+
+    fn load(&mut self, program: &mut [u8]) -> Option<usize> {
+        let mut header = [0u32; 2];
+        self.eeprom.read(ADDR_MAGIC, &mut header).ok()?;
+        let len = header[1] as usize;
+        if header[0] != MAGIC || len > program.len() {
+            return None;
+        }
+        for (i, bytes) in program[0..len].chunks_mut(4).enumerate() {
+            let mut word = [0u32];
+            self.eeprom.read(ADDR_PROGRAM + i, &mut word).ok()?;
+            for (j, byte) in bytes.iter_mut().enumerate() {
+                *byte = (word[0] >> (j * 8)) as u8;
+            }
+        }
+        Some(len)
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::Eeprom);
+    board.enable(sysctl::Domain::Pwm0);
+    board.enable(sysctl::Domain::Timer5);
+    board.enable(sysctl::Domain::Adc0);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+
+    audio::init(p.PWM0, p.TIMER5, &board.clocks);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER5A);
+
+    let mut c = fb::TextFrameBuffer::new(vga::framebuffer());
+    c.clear();
+
+    let mut machine = Machine {
+        text: &mut c,
+        clear: fb::TextFrameBuffer::clear,
+        rx: board.rx,
+        delay: Delay::new(cp.SYST, &board.clocks),
+        eeprom: Eeprom::new(p.EEPROM).unwrap(),
+    };
+
+    // So RND is different every time
+    let mut adc = Adc::adc0(p.ADC0);
+    random::gather(|| adc.read(adc::TEMPERATURE));
+
+    let mut basic = Basic::new();
+
+    writeln!(board.tx, "Tiny BASIC - see the VGA screen").unwrap();
+    writeln!(machine, "Tiny BASIC").unwrap();
+    writeln!(machine, "{} bytes free", basic.free()).unwrap();
+
+    loop {
+        writeln!(machine, "Ready").unwrap();
+        let mut line = [0u8; MAX_LINE];
+        if let Some(len) = machine.read_line(&mut line) {
+            // read_line only gives us ASCII
+            let text = core::str::from_utf8(&line[0..len]).unwrap();
+            basic.enter(&mut machine, text);
+        }
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+interrupt!(TIMER5A, audio::timer5a_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
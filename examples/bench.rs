@@ -0,0 +1,180 @@
+//! Times the VGA drawing code with the DWT cycle counter.
+//!
+//! The VGA output is the same as `hello_vga` (HSYNC on PB6, VSYNC on PC4
+//! and green on PB7). Commands are on UART0 at 115200 bps:
+//!
+//! * `bench` - run all the benchmarks
+//! * `bench <name>` - run just one of them
+//!
+//! The benchmarks are:
+//!
+//! * `clear` - clear the text console
+//! * `fill` - fill a 100 x 100 pixel rectangle, a point at a time
+//! * `text` - draw ten 40 character lines with the graphics font
+//! * `scroll` - write 40 lines to the text console, so it scrolls
+//!
+//! The VGA interrupts keep running, so the figures include the time spent
+//! drawing the screen - which is what you'll get in real life, too.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use]
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+extern crate vga_framebuffer as fb;
+
+use core::fmt::Write;
+use demo::board::Board;
+use demo::console::Console;
+use demo::font;
+use demo::graphics::{Canvas, Colour};
+use demo::profile::{self, Cycles};
+use demo::vga;
+use embedded_hal::prelude::*;
+use menu::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::sysctl::{self, Clocks};
+
+/// A benchmark: a name, and what to time.
+struct Bench {
+    name: &'static str,
+    run: fn(),
+}
+
+static BENCHES: [Bench; 4] = [
+    Bench {
+        name: "clear",
+        run: bench_clear,
+    },
+    Bench {
+        name: "fill",
+        run: bench_fill,
+    },
+    Bench {
+        name: "text",
+        run: bench_text,
+    },
+    Bench {
+        name: "scroll",
+        run: bench_scroll,
+    },
+];
+
+/// For converting cycles to microseconds.
+static mut CLOCKS: Option<Clocks> = None;
+
+const BENCH_ITEM: Item = Item {
+    item_type: ItemType::Callback(bench_callback),
+    command: "bench",
+    help: Some("[<name>] - run the benchmarks (clear, fill, text, scroll)"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[&BENCH_ITEM],
+    entry: None,
+    exit: None,
+};
+
+fn bench_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let clocks = unsafe { CLOCKS.as_ref().unwrap() };
+    let only = input.split_whitespace().nth(1);
+    let mut ran = false;
+    for bench in BENCHES.iter() {
+        if only.map_or(true, |name| name == bench.name) {
+            let (_, cycles) = profile!{ (bench.run)() };
+            writeln!(Console, "{:<8}{}", bench.name, Cycles(cycles, clocks)).unwrap();
+            ran = true;
+        }
+    }
+    if !ran {
+        writeln!(Console, "No such benchmark").unwrap();
+    }
+}
+
+fn bench_clear() {
+    fb::TextFrameBuffer::new(vga::framebuffer()).clear();
+}
+
+fn bench_fill() {
+    vga::framebuffer().fill_rect(150, 100, 100, 100, Colour::WHITE);
+}
+
+fn bench_text() {
+    let screen = vga::framebuffer();
+    for row in 0..10 {
+        screen.draw_str(
+            0,
+            row * font::HEIGHT,
+            "The quick brown fox jumps over the lazy",
+            Colour::WHITE,
+            Colour::BLACK,
+        );
+    }
+}
+
+fn bench_scroll() {
+    let mut c = fb::TextFrameBuffer::new(vga::framebuffer());
+    for line in 0..40 {
+        writeln!(c, "Line {}", line).unwrap();
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let mut cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+    unsafe { CLOCKS = Some(board.clocks) };
+
+    profile::init(&mut cp.DCB, &mut cp.DWT);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+
+    let mut c = fb::TextFrameBuffer::new(vga::framebuffer());
+    c.clear();
+    writeln!(c, "VGA benchmarks - see UART0").unwrap();
+
+    writeln!(board.tx, "VGA benchmarks. Type 'bench'.").unwrap();
+
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+
+    loop {
+        while let Ok(ch) = board.rx.read() {
+            r.input_byte(ch);
+        }
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,82 @@
+//! Logs temperature, pressure and humidity from a BME280 once a second.
+//!
+//! SCL is PB2 (I2C0SCL) and SDA is PB3 (I2C0SDA). Most breakout boards tie
+//! SDO low, giving address 0x76. The readings go to UART0 at 115200 bps.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+
+use core::fmt::Write;
+use demo::bme280::{self, Bme280};
+use demo::config;
+use demo::hal;
+use demo::i2c::I2c;
+use embedded_hal::prelude::*;
+use hal::delay::Delay;
+use hal::sysctl;
+use hal::time::U32Ext;
+use rt::ExceptionFrame;
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::I2c0);
+
+    // I2C0SCL and I2C0SDA
+    config::I2C0.connect();
+    let bus = I2c::i2c0(p.I2C0, 100_000_u32.hz(), &board.clocks);
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+
+    let mut sensor = Bme280::new(bus, bme280::DEFAULT_ADDRESS).unwrap();
+
+    writeln!(board.tx, "BME280 logger").unwrap();
+
+    // The first reading isn't ready until the first conversion is done
+    d.delay_ms(100u32);
+
+    let mut seconds = 0u32;
+    loop {
+        let m = sensor.read().unwrap();
+        let sign = if m.temperature < 0 { "-" } else { "" };
+        let t = m.temperature.abs();
+        writeln!(
+            board.tx,
+            "{:6}s  {}{}.{:02} C  {}.{:02} hPa  {}.{:01} %RH",
+            seconds,
+            sign,
+            t / 100,
+            t % 100,
+            m.pressure / 100,
+            m.pressure % 100,
+            m.humidity / 1000,
+            (m.humidity % 1000) / 100
+        ).unwrap();
+        seconds += 1;
+        d.delay_ms(1_000u32);
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
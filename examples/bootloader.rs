@@ -0,0 +1,182 @@
+//! A bootloader that updates the application over UART0 with XMODEM.
+//!
+//! The flash is split up as described in `demo::image`. Link this with
+//! `FLASH : ORIGIN = 0x00000000, LENGTH = 15K` in `memory.x`, and your
+//! application with `ORIGIN = 0x00004000, LENGTH = 112K`. On the TM4C1294
+//! that's `LENGTH = 48K` in `memory-tm4c129.x`, and `ORIGIN = 0x00010000,
+//! LENGTH = 480K` for the application.
+//!
+//! At reset, we check the application's CRC and jump to it - unless it's
+//! bad (in which case we say why), or SW1 is held down. Then we wait for a
+//! new image on UART0 (115200 bps), sent with XMODEM. An image is the
+//! application's binary with a 16 byte header on the front - a magic
+//! number, the length and a CRC-32, all little-endian, then a zero word.
+//! For example:
+//!
+//! ```
+//! arm-none-eabi-objcopy -O binary app app.bin
+//! python3 -c "import sys,struct,zlib; d=open(sys.argv[1],'rb').read(); \
+//!   sys.stdout.buffer.write(struct.pack('<4I',0x544F4F42,len(d),zlib.crc32(d),0)+d)" \
+//!   app.bin > app.img
+//! ```
+//!
+//! We only copy the new image over the old one once it's all arrived and
+//! its CRC checks out, so if the transfer fails you get the old one back.
+//! The header goes in last, so if the power goes mid-copy we notice the
+//! application is bad next time and copy it again.
+
+#![feature(asm)]
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+
+use core::fmt::Write;
+use demo::flash::{self, Flash, PAGE_SIZE};
+use demo::hal;
+use demo::image::{APP_HEADER, APP_START, HEADER_LEN, MAGIC, SLOT_SIZE, STAGING};
+use demo::image::{self, Header};
+use demo::xmodem::Xmodem;
+use embedded_hal::prelude::*;
+use hal::delay::Delay;
+use rt::ExceptionFrame;
+
+/// How many words we copy at a time - a page on the TM4C123, and a
+/// sixteenth of one on the TM4C1294.
+const BLOCK_WORDS: usize = 256;
+
+/// Copy the staged image over the application, header last.
+fn install(flash: &mut Flash, header: &Header) -> Result<(), flash::Error> {
+    flash.erase(APP_HEADER)?;
+    let mut words = [0u32; BLOCK_WORDS];
+    let source = STAGING + HEADER_LEN;
+    for page in 0..(header.len + PAGE_SIZE - 1) / PAGE_SIZE {
+        let offset = page * PAGE_SIZE;
+        flash.erase(APP_START + offset)?;
+        for block in 0..PAGE_SIZE / (4 * BLOCK_WORDS) {
+            let offset = offset + (block * 4 * BLOCK_WORDS);
+            for (i, word) in words.iter_mut().enumerate() {
+                *word = flash::read_word(source + offset + (i * 4));
+            }
+            flash.write(APP_START + offset, &words)?;
+        }
+    }
+    if flash::crc32(APP_START..APP_START + header.len) != header.crc {
+        return Err(flash::Error::Verify);
+    }
+    flash.write(APP_HEADER, &[MAGIC, header.len as u32, header.crc, 0])
+}
+
+/// Point the vector table at the application and jump to its reset
+/// handler, on its stack.
+fn boot(scb: &cortex_m::peripheral::SCB) -> ! {
+    let stack = flash::read_word(APP_START);
+    let reset = flash::read_word(APP_START + 4);
+    unsafe {
+        scb.vtor.write(APP_START as u32);
+        asm!("msr msp, $0\n bx $1" :: "r"(stack), "r"(reset) :: "volatile");
+    }
+    loop {}
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+
+    let app = image::check_application();
+    if app.is_ok() && !board.sw1.is_pressed() {
+        boot(&cp.SCB);
+    }
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+    let mut flash = Flash::new(p.FLASH_CTRL);
+
+    writeln!(board.tx, "Bootloader").unwrap();
+    if let Err(e) = app {
+        writeln!(board.tx, "Application check failed: {:?}", e).unwrap();
+    }
+
+    // A good staged image but a bad application means we were cut off
+    // part way through copying it
+    if app.is_err() {
+        if let Ok(staged) = image::check_staged() {
+            writeln!(board.tx, "Finishing the last update").unwrap();
+            if install(&mut flash, &staged).is_ok() {
+                boot(&cp.SCB);
+            }
+        }
+    }
+
+    loop {
+        writeln!(board.tx, "Send the new image with XMODEM").unwrap();
+        let mut words = [0u32; BLOCK_WORDS];
+        let mut offset = 0;
+        let result = Xmodem::new(&mut board.tx, &mut board.rx, &mut d).receive(|block| {
+            if offset + block.len() > SLOT_SIZE {
+                return false;
+            }
+            // Erase any pages that start inside this block
+            let mut page = ((offset + PAGE_SIZE - 1) / PAGE_SIZE) * PAGE_SIZE;
+            while page < offset + block.len() {
+                if flash.erase(STAGING + page).is_err() {
+                    return false;
+                }
+                page += PAGE_SIZE;
+            }
+            for (word, bytes) in words.iter_mut().zip(block.chunks(4)) {
+                *word = u32::from(bytes[0]) | (u32::from(bytes[1]) << 8)
+                    | (u32::from(bytes[2]) << 16)
+                    | (u32::from(bytes[3]) << 24);
+            }
+            let count = block.len() / 4;
+            let ok = flash.write(STAGING + offset, &words[0..count]).is_ok();
+            offset += block.len();
+            ok
+        });
+        // Give the terminal emulator a moment to tidy up
+        d.delay_ms(500u32);
+
+        match result.map(|_| image::check_staged()) {
+            Ok(Ok(staged)) => {
+                writeln!(board.tx, "\nGot {} bytes, installing", staged.len).unwrap();
+                match install(&mut flash, &staged) {
+                    Ok(_) => {
+                        writeln!(board.tx, "Done").unwrap();
+                        boot(&cp.SCB);
+                    }
+                    Err(e) => writeln!(board.tx, "Install failed: {:?}", e).unwrap(),
+                }
+            }
+            Ok(Err(e)) => writeln!(board.tx, "\nThat's not a good image: {:?}", e).unwrap(),
+            Err(e) => writeln!(board.tx, "\nTransfer failed: {:?}", e).unwrap(),
+        }
+
+        // The old application is still there, if there was one
+        if image::check_application().is_ok() {
+            writeln!(board.tx, "Starting the old application").unwrap();
+            boot(&cp.SCB);
+        }
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
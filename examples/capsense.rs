@@ -0,0 +1,118 @@
+//! Four capacitive touch buttons.
+//!
+//! Each pad (a coin, or a square of copper tape) goes to one of PE0, PE1,
+//! PE2 and PE3, and through a 2M2 resistor to PE5. Put some tape or paper
+//! over the pads so you're not touching the metal.
+//!
+//! Touching the first three pads lights the red, blue and green LEDs.
+//! Every touch and release is printed on UART0 (115200 bps). Press `r` to
+//! see the raw readings, or `c` to recalibrate (keep your hands off).
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board, gpio_port)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+
+use core::fmt::Write;
+use demo::capsense::CapSense;
+use demo::config;
+use demo::hal;
+use demo::pac;
+use embedded_hal::prelude::*;
+use hal::delay::Delay;
+use hal::gpio::GpioExt;
+use hal::sysctl;
+use rt::ExceptionFrame;
+
+/// The send pin, PE5.
+const SEND_PIN: u8 = 5;
+
+/// The pads, PE0 to PE3.
+const PAD_PINS: [u8; 4] = [0, 1, 2, 3];
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Timer1);
+
+    // We just want port E powered up - CapSense drives the pins itself
+    let _porte = gpio_port!(p, E).split(&board.power_control);
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+
+    let mut pads = CapSense::new(
+        config::Port::E.registers(),
+        SEND_PIN,
+        &PAD_PINS,
+        unsafe { &*pac::TIMER1::ptr() },
+    );
+
+    writeln!(board.tx, "Capacitive touch demo. Calibrating...").unwrap();
+    pads.calibrate();
+    writeln!(board.tx, "Ready").unwrap();
+
+    let mut last = 0;
+    loop {
+        let touched = pads.update();
+
+        let changed = touched ^ last;
+        for i in 0..pads.count() {
+            if changed & (1 << i) != 0 {
+                if touched & (1 << i) != 0 {
+                    writeln!(board.tx, "Pad {} touched", i).unwrap();
+                } else {
+                    writeln!(board.tx, "Pad {} released", i).unwrap();
+                }
+            }
+        }
+        last = touched;
+
+        board.led_red.set(pads.is_touched(0));
+        board.led_blue.set(pads.is_touched(1));
+        board.led_green.set(pads.is_touched(2));
+
+        while let Ok(ch) = board.rx.read() {
+            match ch {
+                b'r' => for i in 0..pads.count() {
+                    writeln!(
+                        board.tx,
+                        "Pad {}: {} (baseline {})",
+                        i,
+                        pads.reading(i),
+                        pads.baseline(i)
+                    ).unwrap();
+                },
+                b'c' => {
+                    writeln!(board.tx, "Calibrating...").unwrap();
+                    pads.calibrate();
+                    last = 0;
+                }
+                _ => {}
+            }
+        }
+
+        d.delay_ms(20u32);
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,501 @@
+//! CHIP-8 games on the VGA screen.
+//!
+//! The 64 x 32 display is blown up six times, in the middle of the VGA
+//! output (HSYNC on PB6, VSYNC on PC4 and green on PB7, as `hello_vga`).
+//! The buzzer is a square wave on PB4 (M0PWM2) - add a piezo sounder, or a
+//! small speaker and a transistor.
+//!
+//! The hex keypad is on the left of the keyboard of whatever terminal is on
+//! UART0 (115200 bps):
+//!
+//! ```text
+//! 1 2 3 C        1 2 3 4
+//! 4 5 6 D   ->   Q W E R
+//! 7 8 9 E        A S D F
+//! A 0 B F        Z X C V
+//! ```
+//!
+//! A serial line only tells us when a key goes down, so each one is held
+//! for a tenth of a second, or for as long as the terminal keeps repeating
+//! it.
+//!
+//! We start with a little built-in program: move the ball about with 2, 4,
+//! 6 and 8 (`W`, `Q`, `E` and `S`). Press Ctrl-O to load a ROM from an SD
+//! card - type its path from the root directory, or just press Enter to
+//! see what's there - or Ctrl-U to send one with XMODEM instead. Ctrl-R
+//! starts the current one again.
+//!
+//! The card is on SSI0 - SCK is PA2 (SSI0Clk), MISO is PA4 (SSI0Rx), MOSI is
+//! PA5 (SSI0Tx) and chip select is PA3, as in `sd_files`.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use demo::adc::{self, Adc};
+use demo::board::Board;
+use demo::chip8::{self, Chip8};
+use demo::config;
+use demo::fat::{Dir, Volume};
+use demo::graphics::{Canvas, Colour};
+use demo::random;
+use demo::sdcard::SdCard;
+use demo::spi::Spi;
+use demo::vga;
+use demo::xmodem::Xmodem;
+use embedded_hal::prelude::*;
+use embedded_hal::spi::MODE_0;
+use rt::ExceptionFrame;
+use tm4c123x_hal::delay::Delay;
+use tm4c123x_hal::gpio::gpioa::PA3;
+use tm4c123x_hal::gpio::{GpioExt, Output, PushPull};
+use tm4c123x_hal::sysctl::{self, Clocks};
+use tm4c123x_hal::time::U32Ext;
+
+/// Each CHIP-8 pixel is this many VGA pixels square.
+const SCALE: usize = 6;
+
+/// Where the CHIP-8 display goes on the 400 x 300 screen.
+const LEFT: usize = (400 - (chip8::WIDTH * SCALE)) / 2;
+const TOP: usize = (300 - (chip8::HEIGHT * SCALE)) / 2;
+
+/// Timer1 ticks per 60 Hz frame.
+const TICKS_PER_FRAME: u32 = 80_000_000 / 60;
+
+/// Instructions per frame - about 600 a second.
+const STEPS_PER_FRAME: usize = 10;
+
+/// How long a key stays down after we hear about it.
+const HOLD_FRAMES: u8 = 6;
+
+/// The buzzer's pitch. PWM0 runs at 80 MHz / 64.
+const BUZZER_PERIOD: u32 = 80_000_000 / 64 / 440;
+
+/// M0PWM2 in PWMENABLE.
+const PWM_OUTPUT: u32 = 1 << 2;
+
+/// RCC.USEPWMDIV, and RCC.PWMDIV set to /64.
+const RCC_PWMDIV_64: u32 = (1 << 20) | (0x7 << 17);
+
+// GPTMCFG, GPTMTAMR, GPTMCTL
+const CFG_32_BIT: u32 = 0x0;
+const TAMR_PERIODIC: u32 = 0x2;
+const CTL_TAEN: u32 = 1 << 0;
+
+const CTRL_C: u8 = 0x03;
+const CTRL_O: u8 = 0x0F;
+const CTRL_R: u8 = 0x12;
+const CTRL_U: u8 = 0x15;
+
+/// The longest path Ctrl-O takes.
+const MAX_PATH: usize = 64;
+
+/// Cards start at 400 kHz or less.
+const INIT_HZ: u32 = 400_000;
+
+/// And then most can go this fast.
+const FAST_HZ: u32 = 20_000_000;
+
+type Card = SdCard<Spi<tm4c123x::SSI0>, PA3<Output<PushPull>>>;
+
+/// The biggest ROM that fits.
+const ROM_LEN: usize = chip8::MEMORY_LEN - chip8::PROGRAM_START;
+
+/// Draws a ball, waits for a key, rubs the ball out, moves it and goes
+/// round again.
+const BALL: [u8; 44] = [
+    0x6A, 0x1C, // 200: V A = 28
+    0x6B, 0x0E, // 202: V B = 14
+    0xA2, 0x28, // 204: I = 228
+    0xDA, 0xB4, // 206: draw 4 rows at V A, V B
+    0xF0, 0x0A, // 208: V 0 = key
+    0xDA, 0xB4, // 20A: rub out
+    0x30, 0x02, // 20C: skip if V 0 = 2
+    0x12, 0x12, // 20E: goto 212
+    0x7B, 0xFF, // 210: V B -= 1
+    0x30, 0x08, // 212: skip if V 0 = 8
+    0x12, 0x18, // 214: goto 218
+    0x7B, 0x01, // 216: V B += 1
+    0x30, 0x04, // 218: skip if V 0 = 4
+    0x12, 0x1E, // 21A: goto 21E
+    0x7A, 0xFF, // 21C: V A -= 1
+    0x30, 0x06, // 21E: skip if V 0 = 6
+    0x12, 0x24, // 220: goto 224
+    0x7A, 0x01, // 222: V A += 1
+    0xDA, 0xB4, // 224: draw
+    0x12, 0x08, // 226: goto 208
+    0x60, 0xF0, 0xF0, 0x60, // 228: the ball
+];
+
+static mut CHIP8: Option<Chip8> = None;
+static mut ROM: [u8; ROM_LEN] = [0; ROM_LEN];
+
+/// Timer1A counts down, so turn it round.
+fn now() -> u32 {
+    let timer = unsafe { &*tm4c123x::TIMER1::ptr() };
+    !timer.tav.read().bits()
+}
+
+/// Which keypad key (if any) a typed character is.
+fn keypad(byte: u8) -> Option<u8> {
+    let key = match byte.to_ascii_lowercase() {
+        b'1' => 0x1,
+        b'2' => 0x2,
+        b'3' => 0x3,
+        b'4' => 0xC,
+        b'q' => 0x4,
+        b'w' => 0x5,
+        b'e' => 0x6,
+        b'r' => 0xD,
+        b'a' => 0x7,
+        b's' => 0x8,
+        b'd' => 0x9,
+        b'f' => 0xE,
+        b'z' => 0xA,
+        b'x' => 0x0,
+        b'c' => 0xB,
+        b'v' => 0xF,
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// Copy any pixels that have changed onto the screen. `drawn` is what we
+/// put there last time.
+fn redraw(chip8: &Chip8, drawn: &mut [u64; chip8::HEIGHT]) {
+    let fb = vga::framebuffer();
+    for y in 0..chip8::HEIGHT {
+        for x in 0..chip8::WIDTH {
+            let bit = 1 << x;
+            let lit = chip8.pixel(x, y);
+            if lit != (drawn[y] & bit != 0) {
+                let colour = if lit { Colour::WHITE } else { Colour::BLACK };
+                fb.fill_rect(LEFT + x * SCALE, TOP + y * SCALE, SCALE, SCALE, colour);
+                drawn[y] ^= bit;
+            }
+        }
+    }
+}
+
+/// Wake the card up and find its filesystem. If there isn't one, you get
+/// the card back to try again later.
+fn mount<T>(
+    mut card: Card,
+    delay: &mut Delay,
+    tx: &mut T,
+    clocks: &Clocks,
+) -> Result<Volume<Card>, Card>
+where
+    T: Write,
+{
+    card.spi().set_frequency(INIT_HZ.hz(), clocks);
+    if let Err(e) = card.init(delay) {
+        writeln!(tx, "No card ({:?})", e).unwrap();
+        return Err(card);
+    }
+    card.spi().set_frequency(FAST_HZ.hz(), clocks);
+    Volume::mount(card).map_err(|(card, e)| {
+        writeln!(tx, "{}", e).unwrap();
+        card
+    })
+}
+
+/// Read a line from the terminal, with echo. Gives `None` if Ctrl-C or
+/// Escape is pressed instead.
+fn read_line<'a, R, T>(rx: &mut R, tx: &mut T, buffer: &'a mut [u8]) -> Option<&'a str>
+where
+    R: embedded_hal::serial::Read<u8>,
+    T: Write,
+{
+    let mut len = 0;
+    loop {
+        match rx.read() {
+            Ok(b'\r') | Ok(b'\n') => {
+                writeln!(tx).unwrap();
+                // We only let ASCII in
+                return Some(core::str::from_utf8(&buffer[0..len]).unwrap());
+            }
+            Ok(CTRL_C) | Ok(0x1B) => {
+                writeln!(tx).unwrap();
+                return None;
+            }
+            Ok(0x08) | Ok(0x7F) if len > 0 => {
+                len -= 1;
+                write!(tx, "\x08 \x08").unwrap();
+            }
+            Ok(byte @ 0x20...0x7E) if len < buffer.len() => {
+                buffer[len] = byte;
+                len += 1;
+                write!(tx, "{}", byte as char).unwrap();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// List the files in the root directory.
+fn list<T>(volume: &mut Volume<Card>, tx: &mut T)
+where
+    T: Write,
+{
+    let mut entries = volume.entries(Dir::root());
+    loop {
+        match volume.next_entry(&mut entries) {
+            Ok(Some(entry)) => {
+                if !entry.is_dir() {
+                    writeln!(tx, "{} ({} bytes)", entry.name, entry.size).unwrap();
+                }
+            }
+            Ok(None) => break,
+            Err(e) => {
+                writeln!(tx, "{}", e).unwrap();
+                break;
+            }
+        }
+    }
+}
+
+/// Read a ROM from the card, returning how long it is.
+fn load_rom<T>(
+    volume: &mut Volume<Card>,
+    path: &str,
+    rom: &mut [u8],
+    tx: &mut T,
+) -> Option<usize>
+where
+    T: Write,
+{
+    let mut file = match volume.open(Dir::root(), path) {
+        Ok(file) => file,
+        Err(e) => {
+            writeln!(tx, "{}: {}", path, e).unwrap();
+            return None;
+        }
+    };
+    if file.size() as usize > rom.len() {
+        writeln!(tx, "{} is too big ({} bytes at most)", path, rom.len()).unwrap();
+        return None;
+    }
+    match volume.read(&mut file, rom) {
+        Ok(len) => Some(len),
+        Err(e) => {
+            writeln!(tx, "{}: {}", path, e).unwrap();
+            None
+        }
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Timer1);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::Pwm0);
+    board.enable(sysctl::Domain::Adc0);
+    board.enable(sysctl::Domain::Ssi0);
+
+    let mut portb = p.GPIO_PORTB.split(&board.power_control);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+
+    // M0PWM2
+    let _buzzer = portb.pb4.into_af4(&mut portb.control);
+
+    // Slow the PWM clock down, then set up generator 1 for a square wave.
+    // We just turn the output on and off.
+    let sysctl_regs = unsafe { &*tm4c123x::SYSCTL::ptr() };
+    sysctl_regs
+        .rcc
+        .modify(|r, w| unsafe { w.bits(r.bits() | RCC_PWMDIV_64) });
+    let pwm = p.PWM0;
+    pwm._1_ctl.write(|w| unsafe { w.bits(0) });
+    pwm._1_load.write(|w| unsafe { w.bits(BUZZER_PERIOD - 1) });
+    pwm._1_cmpa.write(|w| unsafe { w.bits((BUZZER_PERIOD / 2) - 1) });
+    // ACTCMPAD = drive high, ACTLOAD = drive low
+    pwm._1_gena.write(|w| unsafe { w.bits((0x3 << 6) | (0x2 << 2)) });
+    pwm._1_ctl.write(|w| unsafe { w.bits(1) });
+
+    // Timer1A, 32-bit periodic, free running from 0xFFFF_FFFF
+    let timer = p.TIMER1;
+    timer.ctl.write(|w| unsafe { w.bits(0) });
+    timer.cfg.write(|w| unsafe { w.bits(CFG_32_BIT) });
+    timer.tamr.write(|w| unsafe { w.bits(TAMR_PERIODIC) });
+    timer.tailr.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+    timer.ctl.write(|w| unsafe { w.bits(CTL_TAEN) });
+
+    // A different game every time
+    let mut adc = Adc::adc0(p.ADC0);
+    random::gather(|| adc.read(adc::TEMPERATURE));
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+
+    let fb = vga::framebuffer();
+    fb.clear(Colour::BLACK);
+    fb.draw_str(LEFT, TOP - 24, "CHIP-8", Colour::WHITE, Colour::BLACK);
+    fb.draw_rect(
+        LEFT - 2,
+        TOP - 2,
+        (chip8::WIDTH * SCALE) + 4,
+        (chip8::HEIGHT * SCALE) + 4,
+        Colour::WHITE,
+    );
+
+    let rom = unsafe { &mut ROM };
+    rom[0..BALL.len()].copy_from_slice(&BALL);
+    let mut rom_len = BALL.len();
+
+    // It's a bit big for the stack
+    let chip8 = unsafe {
+        CHIP8 = Some(Chip8::new());
+        CHIP8.as_mut().unwrap()
+    };
+    chip8.load(&rom[0..rom_len]).unwrap();
+
+    writeln!(
+        board.tx,
+        "CHIP-8 - Ctrl-O to load a ROM, Ctrl-U to upload one, Ctrl-R to restart"
+    ).unwrap();
+
+    // SSI0Clk, SSI0Rx and SSI0Tx
+    config::SSI0.connect();
+    let spi = Spi::ssi0(p.SSI0, MODE_0, INIT_HZ.hz(), &board.clocks);
+    let card = SdCard::new(spi, board.porta.pa3.into_push_pull_output());
+    let mut storage = mount(card, &mut d, &mut board.tx, &board.clocks);
+
+    let mut drawn = [0u64; chip8::HEIGHT];
+    let mut held = [0u8; 16];
+    let mut running = true;
+    let mut frame = now();
+    loop {
+        while now().wrapping_sub(frame) < TICKS_PER_FRAME {}
+        frame = frame.wrapping_add(TICKS_PER_FRAME);
+
+        while let Ok(byte) = board.rx.read() {
+            match byte {
+                CTRL_U => {
+                    writeln!(board.tx, "Send the ROM with XMODEM").unwrap();
+                    let mut len = 0;
+                    let result = Xmodem::new(&mut board.tx, &mut board.rx, &mut d).receive(|block| {
+                        // XMODEM pads the last block, which does no harm
+                        // after the program
+                        if len + block.len() > ROM_LEN {
+                            return false;
+                        }
+                        rom[len..len + block.len()].copy_from_slice(block);
+                        len += block.len();
+                        true
+                    });
+                    // Give the terminal emulator a moment to tidy up
+                    d.delay_ms(500u32);
+                    match result {
+                        Ok(_) => {
+                            writeln!(board.tx, "\nGot {} bytes", len).unwrap();
+                            rom_len = len;
+                        }
+                        Err(e) => writeln!(board.tx, "\nFailed: {:?}", e).unwrap(),
+                    }
+                    // The old ROM is gone either way
+                    chip8.load(&rom[0..rom_len]).unwrap();
+                    running = true;
+                    frame = now();
+                }
+                CTRL_O => {
+                    // Have another go, in case it's gone in since
+                    storage = match storage {
+                        Ok(volume) => Ok(volume),
+                        Err(card) => mount(card, &mut d, &mut board.tx, &board.clocks),
+                    };
+                    if let Ok(ref mut volume) = storage {
+                        write!(board.tx, "ROM (Enter to list, Esc to give up): ").unwrap();
+                        let mut buffer = [0u8; MAX_PATH];
+                        match read_line(&mut board.rx, &mut board.tx, &mut buffer) {
+                            Some("") => list(volume, &mut board.tx),
+                            Some(path) => {
+                                if let Some(len) = load_rom(volume, path, rom, &mut board.tx) {
+                                    writeln!(board.tx, "Got {} bytes", len).unwrap();
+                                    rom_len = len;
+                                    chip8.load(&rom[0..rom_len]).unwrap();
+                                    running = true;
+                                }
+                            }
+                            None => {}
+                        }
+                    }
+                    frame = now();
+                }
+                CTRL_R => {
+                    chip8.load(&rom[0..rom_len]).unwrap();
+                    running = true;
+                }
+                _ => {
+                    if let Some(key) = keypad(byte) {
+                        chip8.set_key(key, true);
+                        held[key as usize] = HOLD_FRAMES;
+                    }
+                }
+            }
+        }
+
+        for (key, count) in held.iter_mut().enumerate() {
+            if *count > 0 {
+                *count -= 1;
+                if *count == 0 {
+                    chip8.set_key(key as u8, false);
+                }
+            }
+        }
+
+        if running {
+            chip8.seed(now());
+            chip8.tick();
+            for _ in 0..STEPS_PER_FRAME {
+                if let Err(e) = chip8.step() {
+                    writeln!(board.tx, "Stopped: {:?}", e).unwrap();
+                    running = false;
+                    break;
+                }
+            }
+        }
+
+        let bits = if running && chip8.is_beeping() { PWM_OUTPUT } else { 0 };
+        pwm.enable.modify(|r, w| unsafe { w.bits((r.bits() & !PWM_OUTPUT) | bits) });
+
+        if chip8.take_dirty() {
+            redraw(chip8, &mut drawn);
+        }
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
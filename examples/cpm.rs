@@ -0,0 +1,862 @@
+//! CP/M programs on an emulated Z80.
+//!
+//! The Z80 gets 24 KiB of RAM - all we can spare - laid out like a 24K CP/M
+//! 2.2 system, with programs loaded at 0100 and the BDOS at 5C06. Rather
+//! than running the real CCP, BDOS and BIOS, we catch calls to them and do
+//! the work here, with the console on UART0 (115200 bps). That leaves no
+//! room for the VGA framebuffer, so there's no screen.
+//!
+//! Drives A: to D: are disk images on an SD card, called `A.DSK` to `D.DSK`
+//! in its root directory. They're 8" single density, like the CP/M 2.2
+//! manual's sample BIOS - 77 tracks of 26 128-byte sectors, with two system
+//! tracks, a 64 entry directory and sectors skewed by six (cpmtools calls
+//! it `ibm-3740`). The card is on SSI0 - SCK is PA2 (SSI0Clk), MISO is PA4
+//! (SSI0Rx), MOSI is PA5 (SSI0Tx) and chip select is PA3, as in `sd_files` -
+//! and is only looked at when we start, so press reset after changing it.
+//!
+//! The BIOS's disk calls read and write the images, and the disk parameter
+//! tables are where `SELDSK` says. But the BDOS file functions still say
+//! there's nothing there, as we don't have a BDOS to keep track of files,
+//! so only programs that go to the BIOS themselves see the disks. Still,
+//! anything that sticks to the console (BASIC interpreters with no `SAVE`,
+//! games, benchmarks) runs fine. At the `A>` prompt:
+//!
+//! * `LOAD` - send a `.COM` file with XMODEM
+//! * `GO [args]` - run it, with the arguments in the FCBs and the command
+//!   tail as usual
+//! * `DIR [d:]` - list the files on a disk
+//! * `[d:]NAME [args]` - run `NAME.COM` from a disk
+//!
+//! When a program finishes we say how fast the Z80 went, not counting time
+//! spent waiting for the console. On an 80 MHz Cortex-M4 it's a fair bit
+//! quicker than the 4 MHz original.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+
+use core::fmt::Write;
+use demo::config;
+use demo::console::Console;
+use demo::fat::{Dir, File, Volume};
+use demo::hal;
+use demo::pac;
+use demo::sdcard::SdCard;
+use demo::spi::Spi;
+use demo::xmodem::Xmodem;
+use demo::z80::{self, Bus, Cpu};
+use embedded_hal::prelude::*;
+use embedded_hal::spi::MODE_0;
+use hal::delay::Delay;
+use hal::gpio::gpioa::PA3;
+use hal::gpio::{Output, PushPull};
+use hal::sysctl::{self, Clocks};
+use hal::time::U32Ext;
+use rt::ExceptionFrame;
+
+const RAM_LEN: usize = 0x6000;
+
+/// Where programs are loaded and run.
+const TPA: u16 = 0x0100;
+
+/// The BDOS entry point, which is also the top of the TPA.
+const BDOS: u16 = 0x5C06;
+
+/// The BIOS jump table.
+const BIOS: u16 = 0x5E00;
+const BIOS_ENTRIES: u16 = 17;
+
+/// The disk tables, after the jump table: the sector skew, the disk
+/// parameter block, the directory buffer, a disk parameter header for each
+/// drive, and then each drive's check and allocation vectors.
+const XLT: u16 = BIOS + 0x40;
+const DPB: u16 = XLT + 32;
+const DIRBUF: u16 = DPB + 16;
+const DPH: u16 = DIRBUF + 128;
+const DPH_LEN: u16 = 16;
+const VECTORS: u16 = DPH + (DPH_LEN * MAX_DRIVES as u16);
+const CSV_LEN: u16 = 16;
+const ALV_LEN: u16 = 31;
+
+/// A: to D:
+const MAX_DRIVES: usize = 4;
+
+// The disk images
+const TRACKS: u16 = 77;
+const SECTORS: u16 = 26;
+const SECTOR_LEN: usize = 128;
+const SYSTEM_TRACKS: u16 = 2;
+
+/// Where each logical sector is on the track, counting from 1.
+const SKEW: [u8; 26] = [
+    1, 7, 13, 19, 25, 5, 11, 17, 23, 3, 9, 15, 21, 2, 8, 14, 20, 26, 6, 12, 18, 24, 4, 10, 16, 22,
+];
+
+/// SPT, BSH, BLM, EXM, DSM, DRM, AL0, AL1, CKS and OFF, for 1 KiB blocks
+/// and 64 directory entries.
+const DISK_PARAMETERS: [u8; 15] = [26, 0, 3, 7, 0, 242, 0, 63, 0, 0xC0, 0, 16, 0, 2, 0];
+
+/// Records in a block, and in the directory (which is the first two
+/// blocks).
+const BLOCK_RECORDS: usize = 8;
+const DIR_RECORDS: usize = 16;
+const DIR_ENTRY_LEN: usize = 32;
+
+/// Cards start at 400 kHz or less.
+const INIT_HZ: u32 = 400_000;
+
+/// And then most can go this fast.
+const FAST_HZ: u32 = 20_000_000;
+
+// Page zero
+const FCB1: usize = 0x005C;
+const FCB2: usize = 0x006C;
+const FCB_LEN: usize = 16;
+const COMMAND_TAIL: usize = 0x0080;
+
+const JP: u8 = 0xC3;
+const RET: u8 = 0xC9;
+
+const CTRL_C: u8 = 0x03;
+/// End of file, for the paper tape reader we don't have.
+const CTRL_Z: u8 = 0x1A;
+
+/// The longest line the CCP takes.
+const MAX_LINE: usize = 127;
+
+/// Timer1 ticks per microsecond.
+const TICKS_PER_US: u64 = 80;
+
+// GPTMCFG, GPTMTAMR, GPTMCTL
+const CFG_32_BIT: u32 = 0x0;
+const TAMR_PERIODIC: u32 = 0x2;
+const CTL_TAEN: u32 = 1 << 0;
+
+static mut RAM: [u8; RAM_LEN] = [0; RAM_LEN];
+
+/// The Z80's memory. It has no I/O devices.
+struct Memory<'a> {
+    ram: &'a mut [u8; RAM_LEN],
+}
+
+impl<'a> z80::Bus for Memory<'a> {
+    fn read(&mut self, address: u16) -> u8 {
+        self.ram.get(address as usize).cloned().unwrap_or(0xFF)
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        if let Some(byte) = self.ram.get_mut(address as usize) {
+            *byte = value;
+        }
+    }
+
+    fn input(&mut self, _port: u16) -> u8 {
+        0xFF
+    }
+
+    fn output(&mut self, _port: u16, _value: u8) {}
+}
+
+/// The console, which can look at a key without taking it (for the
+/// console status calls).
+struct Terminal {
+    pending: Option<u8>,
+}
+
+impl Terminal {
+    fn is_ready(&mut self) -> bool {
+        if self.pending.is_none() {
+            self.pending = Console.read_byte();
+        }
+        self.pending.is_some()
+    }
+
+    fn try_read(&mut self) -> Option<u8> {
+        self.is_ready();
+        self.pending.take()
+    }
+
+    fn read(&mut self) -> u8 {
+        loop {
+            if let Some(byte) = self.try_read() {
+                return byte;
+            }
+        }
+    }
+
+    fn write(&mut self, byte: u8) {
+        Console.write_byte(byte);
+    }
+
+    /// Read a line with echo. Returns `None` if the line starts with a
+    /// Ctrl-C.
+    fn read_line(&mut self, buffer: &mut [u8]) -> Option<usize> {
+        let mut len = 0;
+        loop {
+            match self.read() {
+                b'\r' | b'\n' => {
+                    self.write(b'\r');
+                    return Some(len);
+                }
+                CTRL_C if len == 0 => return None,
+                0x08 | 0x7F if len > 0 => {
+                    len -= 1;
+                    for &byte in b"\x08 \x08" {
+                        self.write(byte);
+                    }
+                }
+                byte @ 0x20...0x7E if len < buffer.len() => {
+                    buffer[len] = byte;
+                    len += 1;
+                    self.write(byte);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+type Card = SdCard<Spi<pac::SSI0>, PA3<Output<PushPull>>>;
+
+/// The disk images, and the card they're on.
+struct Drives {
+    volume: Option<Volume<Card>>,
+    images: [Option<File>; MAX_DRIVES],
+}
+
+impl Drives {
+    fn has(&self, drive: usize) -> bool {
+        self.images.get(drive).map_or(false, |image| image.is_some())
+    }
+
+    /// Go to a sector (numbered from 1, like the BIOS does) in an image.
+    fn find(
+        &mut self,
+        drive: usize,
+        track: u16,
+        sector: u16,
+    ) -> Option<(&mut Volume<Card>, &mut File)> {
+        if sector == 0 || sector > SECTORS || track >= TRACKS {
+            return None;
+        }
+        let sectors = (u32::from(track) * u32::from(SECTORS)) + u32::from(sector - 1);
+        let position = sectors * SECTOR_LEN as u32;
+        let volume = self.volume.as_mut()?;
+        let file = self.images.get_mut(drive)?.as_mut()?;
+        if position >= file.size() {
+            return None;
+        }
+        file.seek(position);
+        Some((volume, file))
+    }
+
+    fn read_sector(&mut self, drive: usize, track: u16, sector: u16, buffer: &mut [u8]) -> bool {
+        match self.find(drive, track, sector) {
+            Some((volume, file)) => volume.read(file, buffer).ok() == Some(SECTOR_LEN),
+            None => false,
+        }
+    }
+
+    fn write_sector(&mut self, drive: usize, track: u16, sector: u16, buffer: &[u8]) -> bool {
+        match self.find(drive, track, sector) {
+            Some((volume, file)) => volume.write(file, buffer).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Read a 128 byte record, counting from the start of the directory.
+    fn read_record(&mut self, drive: usize, record: usize, buffer: &mut [u8]) -> bool {
+        let track = SYSTEM_TRACKS + (record / SECTORS as usize) as u16;
+        let sector = u16::from(SKEW[record % SECTORS as usize]);
+        self.read_sector(drive, track, sector, buffer)
+    }
+
+    /// Get everything that's been written onto the card.
+    fn flush(&mut self) {
+        if let Some(ref mut volume) = self.volume {
+            for image in self.images.iter_mut() {
+                if let Some(ref mut file) = *image {
+                    let _ = volume.sync(file);
+                }
+            }
+        }
+    }
+}
+
+/// Where the BIOS disk calls are up to.
+struct Disk {
+    drive: usize,
+    track: u16,
+    sector: u16,
+    dma: u16,
+}
+
+/// What to do after a call to the BDOS or BIOS.
+enum Next {
+    Return,
+    WarmBoot,
+}
+
+/// The BDOS functions, with C saying which, and DE or E as the argument.
+/// The answer goes back in HL, and in A and B as well.
+fn bdos(cpu: &mut Cpu, memory: &mut Memory, terminal: &mut Terminal) -> Next {
+    let e = cpu.de as u8;
+    let result: u16 = match cpu.bc as u8 {
+        0 => return Next::WarmBoot,
+        1 => {
+            let byte = terminal.read();
+            terminal.write(byte);
+            byte as u16
+        }
+        2 => {
+            terminal.write(e);
+            0
+        }
+        6 => match e {
+            0xFF => terminal.try_read().unwrap_or(0) as u16,
+            0xFE => if terminal.is_ready() { 0xFF } else { 0 },
+            _ => {
+                terminal.write(e);
+                0
+            }
+        },
+        9 => {
+            let mut address = cpu.de;
+            loop {
+                let byte = memory.read(address);
+                if byte == b'$' {
+                    break;
+                }
+                terminal.write(byte);
+                address = address.wrapping_add(1);
+            }
+            0
+        }
+        10 => {
+            // The first byte says how much room there is; we fill in the
+            // second with how much we used
+            let mut line = [0u8; 255];
+            let max = memory.read(cpu.de) as usize;
+            match terminal.read_line(&mut line[0..max]) {
+                Some(len) => {
+                    memory.write(cpu.de.wrapping_add(1), len as u8);
+                    for (i, &byte) in line[0..len].iter().enumerate() {
+                        memory.write(cpu.de.wrapping_add(2 + i as u16), byte);
+                    }
+                }
+                None => return Next::WarmBoot,
+            }
+            0
+        }
+        11 => if terminal.is_ready() { 0xFF } else { 0 },
+        // Version 2.2
+        12 => 0x0022,
+        // Reset the disks, get the current drive (A:) and the user number
+        13 | 25 | 32 => 0,
+        // Select a drive: we pretend A: is there
+        14 => if e == 0 { 0 } else { 0xFF },
+        // The file functions: no such file
+        _ => 0xFF,
+    };
+    cpu.hl = result;
+    cpu.a = result as u8;
+    cpu.bc = (result & 0xFF00) | (cpu.bc & 0x00FF);
+    Next::Return
+}
+
+/// The BIOS entry points, numbered from zero (cold boot).
+fn bios(
+    entry: u16,
+    cpu: &mut Cpu,
+    memory: &mut Memory,
+    terminal: &mut Terminal,
+    drives: &mut Drives,
+    disk: &mut Disk,
+) -> Next {
+    match entry {
+        0 | 1 => return Next::WarmBoot,
+        2 => cpu.a = if terminal.is_ready() { 0xFF } else { 0 },
+        3 => cpu.a = terminal.read(),
+        4 => terminal.write(cpu.bc as u8),
+        // The reader, with nothing in it
+        7 => cpu.a = CTRL_Z,
+        8 => disk.track = 0,
+        9 => {
+            disk.drive = cpu.bc as u8 as usize;
+            cpu.hl = if drives.has(disk.drive) {
+                DPH + (disk.drive as u16 * DPH_LEN)
+            } else {
+                0
+            };
+        }
+        10 => disk.track = cpu.bc,
+        11 => disk.sector = cpu.bc,
+        12 => disk.dma = cpu.bc,
+        13 => {
+            let mut buffer = [0u8; SECTOR_LEN];
+            let ok = drives.read_sector(disk.drive, disk.track, disk.sector, &mut buffer);
+            if ok {
+                for (i, &byte) in buffer.iter().enumerate() {
+                    memory.write(disk.dma.wrapping_add(i as u16), byte);
+                }
+            }
+            cpu.a = if ok { 0 } else { 1 };
+        }
+        14 => {
+            let mut buffer = [0u8; SECTOR_LEN];
+            for (i, byte) in buffer.iter_mut().enumerate() {
+                *byte = memory.read(disk.dma.wrapping_add(i as u16));
+            }
+            let ok = drives.write_sector(disk.drive, disk.track, disk.sector, &buffer);
+            cpu.a = if ok { 0 } else { 1 };
+        }
+        15 => cpu.a = 0,
+        16 => {
+            cpu.hl = if cpu.de == 0 {
+                cpu.bc
+            } else {
+                u16::from(memory.read(cpu.de.wrapping_add(cpu.bc)))
+            };
+        }
+        // The printer and punch
+        _ => {}
+    }
+    Next::Return
+}
+
+/// Do what RET would do.
+fn ret(cpu: &mut Cpu, memory: &mut Memory) {
+    let low = memory.read(cpu.sp) as u16;
+    let high = memory.read(cpu.sp.wrapping_add(1)) as u16;
+    cpu.sp = cpu.sp.wrapping_add(2);
+    cpu.pc = (high << 8) | low;
+}
+
+/// Set up page zero and the BIOS jump table, as a warm boot would.
+fn warm_boot(memory: &mut Memory) {
+    let ram = &mut memory.ram;
+    let wboot = BIOS + 3;
+    ram[0..3].copy_from_slice(&[JP, wboot as u8, (wboot >> 8) as u8]);
+    // IOBYTE and current drive
+    ram[3] = 0;
+    ram[4] = 0;
+    ram[5..8].copy_from_slice(&[JP, BDOS as u8, (BDOS >> 8) as u8]);
+    ram[BDOS as usize] = RET;
+    // Each BIOS entry jumps to itself, so we catch it however it's called
+    for entry in 0..BIOS_ENTRIES {
+        let address = BIOS + (entry * 3);
+        let start = address as usize;
+        ram[start..start + 3].copy_from_slice(&[JP, address as u8, (address >> 8) as u8]);
+    }
+    disk_tables(&mut ram[..]);
+}
+
+fn write_word(ram: &mut [u8], address: u16, value: u16) {
+    let address = address as usize;
+    ram[address] = value as u8;
+    ram[address + 1] = (value >> 8) as u8;
+}
+
+/// Set up the skew table, the disk parameter block and a disk parameter
+/// header for each drive.
+fn disk_tables(ram: &mut [u8]) {
+    ram[XLT as usize..XLT as usize + SKEW.len()].copy_from_slice(&SKEW);
+    ram[DPB as usize..DPB as usize + DISK_PARAMETERS.len()].copy_from_slice(&DISK_PARAMETERS);
+    for drive in 0..MAX_DRIVES as u16 {
+        let dph = DPH + (drive * DPH_LEN);
+        let csv = VECTORS + (drive * (CSV_LEN + ALV_LEN));
+        for byte in ram[dph as usize..(dph + DPH_LEN) as usize].iter_mut() {
+            *byte = 0;
+        }
+        write_word(ram, dph, XLT);
+        write_word(ram, dph + 8, DIRBUF);
+        write_word(ram, dph + 10, DPB);
+        write_word(ram, dph + 12, csv);
+        write_word(ram, dph + 14, csv + CSV_LEN);
+    }
+}
+
+/// Go through user 0's directory entries on `drive`, until `f` says
+/// that's the one. Returns false if it never did.
+fn scan<F>(drives: &mut Drives, drive: usize, mut f: F) -> bool
+where
+    F: FnMut(&[u8]) -> bool,
+{
+    let mut record = [0u8; SECTOR_LEN];
+    for index in 0..DIR_RECORDS {
+        if !drives.read_record(drive, index, &mut record) {
+            return false;
+        }
+        for entry in record.chunks(DIR_ENTRY_LEN) {
+            if entry[0] == 0 && f(entry) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// List the files on a drive, four to a line, as CP/M does.
+fn dir(drives: &mut Drives, drive: usize) {
+    if !drives.has(drive) {
+        writeln!(Console, "No disk in {}:", (b'A' + drive as u8) as char).unwrap();
+        return;
+    }
+    let mut count = 0;
+    scan(drives, drive, |entry| {
+        // The first extent of each file
+        if entry[12] == 0 && entry[14] == 0 {
+            if count % 4 == 0 {
+                write!(Console, "\n{}", (b'A' + drive as u8) as char).unwrap();
+            }
+            write!(Console, ": ").unwrap();
+            for (i, &byte) in entry[1..12].iter().enumerate() {
+                if i == 8 {
+                    Console.write_byte(b' ');
+                }
+                Console.write_byte(byte & 0x7F);
+            }
+            Console.write_byte(b' ');
+            count += 1;
+        }
+        false
+    });
+    if count == 0 {
+        write!(Console, "No file").unwrap();
+    }
+    writeln!(Console).unwrap();
+}
+
+/// Load the file in `fcb` into the TPA.
+fn load_file(
+    drives: &mut Drives,
+    memory: &mut Memory,
+    fcb: &[u8],
+) -> Result<(), &'static str> {
+    let drive = if fcb[0] == 0 { 0 } else { fcb[0] as usize - 1 };
+    if !drives.has(drive) {
+        return Err("no disk");
+    }
+    let name = &fcb[1..12];
+    let mut address = TPA as usize;
+    let mut extent = 0;
+    loop {
+        let mut found = [0u8; DIR_ENTRY_LEN];
+        let hit = scan(drives, drive, |entry| {
+            let matches = entry[12] == extent && entry[14] == 0
+                && entry[1..12].iter().zip(name).all(|(&a, &b)| a & 0x7F == b);
+            if matches {
+                found.copy_from_slice(entry);
+            }
+            matches
+        });
+        if !hit {
+            if extent == 0 {
+                return Err("not found");
+            }
+            break;
+        }
+        let records = found[15] as usize;
+        let mut buffer = [0u8; SECTOR_LEN];
+        for record in 0..records {
+            let block = found[16 + (record / BLOCK_RECORDS)] as usize;
+            if address + SECTOR_LEN > BDOS as usize {
+                return Err("too big");
+            }
+            let index = (block * BLOCK_RECORDS) + (record % BLOCK_RECORDS);
+            if !drives.read_record(drive, index, &mut buffer) {
+                return Err("disk error");
+            }
+            memory.ram[address..address + SECTOR_LEN].copy_from_slice(&buffer);
+            address += SECTOR_LEN;
+        }
+        // A full extent might have another after it
+        if records < 128 {
+            break;
+        }
+        extent += 1;
+    }
+    Ok(())
+}
+
+/// Fill in an FCB from a file name, like `B:NAME.TYP`.
+fn make_fcb(fcb: &mut [u8], name: &[u8]) {
+    for byte in fcb.iter_mut() {
+        *byte = 0;
+    }
+    for byte in fcb[1..12].iter_mut() {
+        *byte = b' ';
+    }
+    let mut name = name;
+    if name.len() >= 2 && name[1] == b':' {
+        fcb[0] = (name[0] & 0x1F).saturating_sub(b'A' & 0x1F) + 1;
+        name = &name[2..];
+    }
+    let mut parts = name.splitn(2, |&b| b == b'.');
+    for (dest, &src) in fcb[1..9].iter_mut().zip(parts.next().unwrap_or(&[])) {
+        *dest = src;
+    }
+    for (dest, &src) in fcb[9..12].iter_mut().zip(parts.next().unwrap_or(&[])) {
+        *dest = src;
+    }
+}
+
+/// Set up the FCBs and the command tail from the arguments.
+fn set_arguments(memory: &mut Memory, args: &[u8]) {
+    let ram = &mut memory.ram;
+    let mut words = args.split(|&b| b == b' ').filter(|w| !w.is_empty());
+    make_fcb(&mut ram[FCB1..FCB1 + FCB_LEN], words.next().unwrap_or(&[]));
+    make_fcb(&mut ram[FCB2..FCB2 + FCB_LEN], words.next().unwrap_or(&[]));
+    // The tail keeps its leading space
+    let tail = &mut ram[COMMAND_TAIL..COMMAND_TAIL + 128];
+    let len = if args.is_empty() { 0 } else { args.len() + 1 };
+    tail[0] = len as u8;
+    tail[1] = b' ';
+    tail[2..2 + args.len()].copy_from_slice(args);
+}
+
+/// Timer1A counts down, so turn it round.
+fn now() -> u32 {
+    let timer = unsafe { &*pac::TIMER1::ptr() };
+    !timer.tav.read().bits()
+}
+
+/// Run the program in the TPA until it warm boots (or halts, as there are
+/// no interrupts to wake it). Returns how many T-states ran and how many
+/// Timer1 ticks they took.
+fn run(
+    cpu: &mut Cpu,
+    memory: &mut Memory,
+    terminal: &mut Terminal,
+    drives: &mut Drives,
+) -> (u64, u64) {
+    let mut disk = Disk {
+        drive: 0,
+        track: 0,
+        sector: 1,
+        dma: COMMAND_TAIL as u16,
+    };
+    let mut t_states = 0u64;
+    let mut ticks = 0u64;
+    let mut last = now();
+    loop {
+        let next = if cpu.pc == BDOS {
+            ticks += now().wrapping_sub(last) as u64;
+            let next = bdos(cpu, memory, terminal);
+            last = now();
+            next
+        } else if cpu.pc >= BIOS && cpu.pc < BIOS + (BIOS_ENTRIES * 3) && (cpu.pc - BIOS) % 3 == 0
+        {
+            ticks += now().wrapping_sub(last) as u64;
+            let next = bios((cpu.pc - BIOS) / 3, cpu, memory, terminal, drives, &mut disk);
+            last = now();
+            next
+        } else if cpu.halted {
+            writeln!(Console, "\nHalted at {:04X}", cpu.pc.wrapping_sub(1)).unwrap();
+            Next::WarmBoot
+        } else {
+            t_states += cpu.step(memory) as u64;
+            continue;
+        };
+        match next {
+            Next::Return => ret(cpu, memory),
+            Next::WarmBoot => break,
+        }
+    }
+    ticks += now().wrapping_sub(last) as u64;
+    (t_states, ticks)
+}
+
+/// Run what's in the TPA, then say how fast it went.
+fn go(
+    cpu: &mut Cpu,
+    memory: &mut Memory,
+    terminal: &mut Terminal,
+    drives: &mut Drives,
+    args: &[u8],
+) {
+    set_arguments(memory, args);
+    cpu.reset();
+    cpu.pc = TPA;
+    // Programs may simply RET to go back to the CCP
+    cpu.sp = BDOS - 6;
+    memory.write(cpu.sp, 0);
+    memory.write(cpu.sp + 1, 0);
+    let (t_states, ticks) = run(cpu, memory, terminal, drives);
+    drives.flush();
+    let us = ticks / TICKS_PER_US;
+    if us > 0 {
+        let khz = (t_states * 1000) / us;
+        writeln!(
+            Console,
+            "\n{} T-states in {} ms: {}.{:03} MHz",
+            t_states,
+            us / 1000,
+            khz / 1000,
+            khz % 1000
+        ).unwrap();
+    }
+}
+
+/// Wake the card up and open the disk images on it.
+fn mount(mut card: Card, delay: &mut Delay, clocks: &Clocks) -> Drives {
+    let mut drives = Drives {
+        volume: None,
+        images: [None, None, None, None],
+    };
+    card.spi().set_frequency(INIT_HZ.hz(), clocks);
+    if let Err(e) = card.init(delay) {
+        writeln!(Console, "No card ({:?})", e).unwrap();
+        return drives;
+    }
+    card.spi().set_frequency(FAST_HZ.hz(), clocks);
+    let mut volume = match Volume::mount(card) {
+        Ok(volume) => volume,
+        Err((_card, e)) => {
+            writeln!(Console, "{}", e).unwrap();
+            return drives;
+        }
+    };
+    for (drive, image) in drives.images.iter_mut().enumerate() {
+        let name = [b'A' + drive as u8, b'.', b'D', b'S', b'K'];
+        // We only make ASCII
+        let name = core::str::from_utf8(&name).unwrap();
+        if let Ok(file) = volume.open(Dir::root(), name) {
+            writeln!(Console, "{}: is {} ({} KiB)", &name[0..1], name, file.size() / 1024).unwrap();
+            *image = Some(file);
+        }
+    }
+    drives.volume = Some(volume);
+    drives
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Timer1);
+    board.enable(sysctl::Domain::Ssi0);
+
+    // Timer1A, 32-bit periodic, free running from 0xFFFF_FFFF
+    let timer = p.TIMER1;
+    timer.ctl.write(|w| unsafe { w.bits(0) });
+    timer.cfg.write(|w| unsafe { w.bits(CFG_32_BIT) });
+    timer.tamr.write(|w| unsafe { w.bits(TAMR_PERIODIC) });
+    timer.tailr.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+    timer.ctl.write(|w| unsafe { w.bits(CTL_TAEN) });
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+
+    let mut memory = Memory {
+        ram: unsafe { &mut RAM },
+    };
+    let mut terminal = Terminal { pending: None };
+    let mut cpu = Cpu::new();
+    let mut loaded = false;
+
+    writeln!(board.tx, "{}K CP/M 2.2 (emulated Z80)", RAM_LEN / 1024).unwrap();
+
+    // SSI0Clk, SSI0Rx and SSI0Tx
+    config::SSI0.connect();
+    let porta = board.porta;
+    let spi = Spi::ssi0(p.SSI0, MODE_0, INIT_HZ.hz(), &board.clocks);
+    let card = SdCard::new(spi, porta.pa3.into_push_pull_output());
+    let mut drives = mount(card, &mut d, &board.clocks);
+
+    loop {
+        warm_boot(&mut memory);
+        write!(board.tx, "\nA>").unwrap();
+        let mut line = [0u8; MAX_LINE];
+        let len = match terminal.read_line(&mut line) {
+            Some(len) => len,
+            None => continue,
+        };
+        writeln!(board.tx).unwrap();
+        for byte in line[0..len].iter_mut() {
+            *byte = byte.to_ascii_uppercase();
+        }
+        let line = &line[0..len];
+        let (command, args) = match line.iter().position(|&b| b == b' ') {
+            Some(space) => (&line[0..space], &line[space + 1..]),
+            None => (line, &line[len..]),
+        };
+
+        match command {
+            b"" => {}
+            b"DIR" => {
+                let mut fcb = [0u8; FCB_LEN];
+                make_fcb(&mut fcb, args);
+                dir(&mut drives, (fcb[0] as usize).saturating_sub(1));
+            }
+            b"LOAD" => {
+                writeln!(board.tx, "Send the .COM file with XMODEM").unwrap();
+                let mut address = TPA as usize;
+                let result = {
+                    let ram = &mut memory.ram;
+                    Xmodem::new(&mut board.tx, &mut board.rx, &mut d).receive(|block| {
+                        if address + block.len() > BDOS as usize {
+                            return false;
+                        }
+                        ram[address..address + block.len()].copy_from_slice(block);
+                        address += block.len();
+                        true
+                    })
+                };
+                // Give the terminal emulator a moment to tidy up
+                d.delay_ms(500u32);
+                match result {
+                    Ok(_) => {
+                        let pages = (address - TPA as usize) / 256;
+                        writeln!(board.tx, "\n{} pages", pages).unwrap();
+                        loaded = true;
+                    }
+                    Err(e) => writeln!(board.tx, "\nFailed: {:?}", e).unwrap(),
+                }
+            }
+            b"GO" if loaded => go(&mut cpu, &mut memory, &mut terminal, &mut drives, args),
+            b"GO" => writeln!(board.tx, "Nothing loaded").unwrap(),
+            _ => {
+                // Look for a .COM file, as the CCP does
+                let mut fcb = [0u8; FCB_LEN];
+                make_fcb(&mut fcb, command);
+                fcb[9..12].copy_from_slice(b"COM");
+                match load_file(&mut drives, &mut memory, &fcb) {
+                    Ok(()) => {
+                        loaded = true;
+                        go(&mut cpu, &mut memory, &mut terminal, &mut drives, args);
+                    }
+                    Err(e) => {
+                        // We only let ASCII in
+                        let name = core::str::from_utf8(command).unwrap();
+                        match e {
+                            "not found" | "no disk" => writeln!(board.tx, "{}?", name).unwrap(),
+                            _ => writeln!(board.tx, "{}: {}", name, e).unwrap(),
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
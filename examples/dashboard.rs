@@ -0,0 +1,219 @@
+//! A multimeter-style dashboard: four analog inputs, the chip temperature
+//! and a supply voltage, as big readouts and bar graphs on the VGA screen.
+//!
+//! The inputs are AIN0..AIN3 (PE3, PE2, PE1 and PE0), 0 to 3.3V. The
+//! supply goes to AIN8 (PE5) through a 10k/10k divider, so it can be up to
+//! 6.6V - the LaunchPad's 5V rail, say. Each reading is the average of 16
+//! samples, and the screen updates ten times a second.
+//!
+//! The VGA output is the same as `hello_vga` (HSYNC on PB6, VSYNC on PC4
+//! and green on PB7). A good first project, if you've just wired it up:
+//! put a pot across 3.3V and ground, with the wiper on PE3.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use demo::adc::{self, Adc};
+use demo::board::Board;
+use demo::font;
+use demo::graphics::{Canvas, Colour, VGA_WIDTH};
+use demo::status_bar;
+use demo::text::Buffer;
+use demo::vga;
+use embedded_hal::prelude::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::delay::Delay;
+use tm4c123x_hal::sysctl;
+use tm4c123x_hal::tm4c123x::ADC0;
+
+/// Time between updates.
+const UPDATE_MS: u32 = 100;
+
+/// Samples averaged for each reading.
+const AVERAGE: u32 = 16;
+
+/// The supply is divided by this much before it gets to the pin.
+const SUPPLY_DIVIDER: u32 = 2;
+
+/// Where each row goes.
+const TOP: usize = status_bar::HEIGHT + 8;
+const ROW_HEIGHT: usize = 46;
+/// How big the readouts are.
+const SCALE: usize = 2;
+const VALUE_X: usize = 88;
+/// Room for "-12.3C" or "3.300V", with one to spare.
+const VALUE_WIDTH: usize = 7 * font::WIDTH * SCALE;
+const BAR_X: usize = VALUE_X + VALUE_WIDTH + 12;
+const BAR_WIDTH: usize = VGA_WIDTH - BAR_X - 10;
+const BAR_HEIGHT: usize = 16;
+
+/// What a row shows.
+#[derive(Clone, Copy)]
+enum Reading {
+    /// An analog input, in mV
+    Input(u8),
+    /// The on-chip sensor, in tenths of a degree, shown on a 0-50C bar
+    Temperature,
+    /// The divided-down supply on AIN8, in mV
+    Supply,
+}
+
+struct Row {
+    label: &'static str,
+    reading: Reading,
+}
+
+const ROWS: [Row; 6] = [
+    Row {
+        label: "AIN0 PE3",
+        reading: Reading::Input(0),
+    },
+    Row {
+        label: "AIN1 PE2",
+        reading: Reading::Input(1),
+    },
+    Row {
+        label: "AIN2 PE1",
+        reading: Reading::Input(2),
+    },
+    Row {
+        label: "AIN3 PE0",
+        reading: Reading::Input(3),
+    },
+    Row {
+        label: "Chip temp",
+        reading: Reading::Temperature,
+    },
+    Row {
+        label: "Supply PE5",
+        reading: Reading::Supply,
+    },
+];
+
+/// The supply's analog input.
+const SUPPLY_CHANNEL: u8 = 8;
+
+/// The average of a few samples.
+fn read_average(adc: &mut Adc<ADC0>, channel: u8) -> u16 {
+    let total: u32 = (0..AVERAGE).map(|_| u32::from(adc.read(channel))).sum();
+    (total / AVERAGE) as u16
+}
+
+/// Take a reading, and format it. Returns how full the bar should be, in
+/// thousandths.
+fn measure(adc: &mut Adc<ADC0>, reading: Reading, text: &mut Buffer) -> u32 {
+    match reading {
+        Reading::Input(channel) => {
+            let mv = adc::millivolts(read_average(adc, channel));
+            write!(text, "{}.{:03}V", mv / 1000, mv % 1000).unwrap();
+            (mv * 1000) / adc::VREF_MV
+        }
+        Reading::Temperature => {
+            let tenths = adc::temperature(read_average(adc, adc::TEMPERATURE));
+            let sign = if tenths < 0 { "-" } else { "" };
+            write!(text, "{}{}.{}C", sign, tenths.abs() / 10, tenths.abs() % 10).unwrap();
+            (tenths.max(0).min(500) * 2) as u32
+        }
+        Reading::Supply => {
+            let mv = adc::millivolts(read_average(adc, SUPPLY_CHANNEL)) * SUPPLY_DIVIDER;
+            write!(text, "{}.{:03}V", mv / 1000, mv % 1000).unwrap();
+            (mv * 1000) / (adc::VREF_MV * SUPPLY_DIVIDER)
+        }
+    }
+}
+
+/// Draw the parts of a row that don't change.
+fn draw_frame<C>(canvas: &mut C, y: usize, row: &Row)
+where
+    C: Canvas,
+{
+    canvas.draw_str(0, y + 8, row.label, Colour::WHITE, Colour::BLACK);
+    canvas.draw_rect(BAR_X, y + 8, BAR_WIDTH, BAR_HEIGHT, Colour::WHITE);
+}
+
+/// Draw a reading, and its bar `fill` thousandths full.
+fn draw_reading<C>(canvas: &mut C, y: usize, text: &str, fill: u32)
+where
+    C: Canvas,
+{
+    canvas.fill_rect(VALUE_X, y, VALUE_WIDTH, font::HEIGHT * SCALE, Colour::BLACK);
+    canvas.draw_str_scaled(VALUE_X, y, text, SCALE, Colour::WHITE, Colour::BLACK);
+
+    let inside = BAR_WIDTH - 4;
+    let lit = (inside * fill.min(1000) as usize) / 1000;
+    let bar_y = y + 10;
+    canvas.fill_rect(BAR_X + 2, bar_y, lit, BAR_HEIGHT - 4, Colour::WHITE);
+    canvas.fill_rect(BAR_X + 2 + lit, bar_y, inside - lit, BAR_HEIGHT - 4, Colour::BLACK);
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::Adc0);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    demo::config::Port::E.enable();
+    for row in ROWS.iter() {
+        if let Reading::Input(channel) = row.reading {
+            adc::configure_pin(channel);
+        }
+    }
+    adc::configure_pin(SUPPLY_CHANNEL);
+    let mut adc = Adc::adc0(p.ADC0);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+
+    let fb = vga::framebuffer();
+    fb.clear(Colour::BLACK);
+    status_bar::draw(fb, "Dashboard", "10 Hz");
+    for (i, row) in ROWS.iter().enumerate() {
+        draw_frame(fb, TOP + (i * ROW_HEIGHT), row);
+    }
+
+    writeln!(board.tx, "Dashboard running").unwrap();
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+    loop {
+        for (i, row) in ROWS.iter().enumerate() {
+            let mut text = Buffer::new();
+            let fill = measure(&mut adc, row.reading, &mut text);
+            draw_reading(vga::framebuffer(), TOP + (i * ROW_HEIGHT), text.as_str(), fill);
+        }
+        d.delay_ms(UPDATE_MS);
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,494 @@
+//! Logs analog readings to an SD card, as CSV with the time on each line.
+//!
+//! Every `interval` seconds we read the configured ADC channels - AIN0 to
+//! AIN11 in millivolts, and the on-chip temperature sensor in degrees C -
+//! and stamp them with the time from the hibernation module's real-time
+//! clock, which keeps going through a reset. The line goes out on UART0
+//! (115200 bps) straight away, so you can capture the log from a terminal
+//! with no card at all, and is added to `/LOG.CSV` on the card:
+//!
+//! ```text
+//! time,ain0_mv,temp_c
+//! 2018-06-01 12:00:00,1650,24.3
+//! 2018-06-01 12:00:10,1652,24.3
+//! ```
+//!
+//! Lines are kept in RAM and written out every `FLUSH_SECONDS` (or when
+//! half of `PENDING_LEN` is waiting). The file's size is only changed once
+//! they've all gone, so a card pulled out part way through a flush still
+//! has the file as it was, and the lines go again when it's back. While
+//! the card is out (or has no FAT filesystem) we try it again every
+//! `RETRY_SECONDS`. Lines that didn't fit in RAM meanwhile are counted,
+//! and a `#` comment line says how many went missing. Everything else we
+//! say on the console starts with `#` too, so the CSV can be picked out.
+//!
+//! The card is on SSI0 - SCK is PA2 (SSI0Clk), MISO is PA4 (SSI0Rx), MOSI is
+//! PA5 (SSI0Tx) and chip select is PA3 - as in `sd_files`.
+//!
+//! Commands:
+//!
+//! * `channels <n|temp>...` - which channels to log (up to four)
+//! * `interval <seconds>` - how often
+//! * `date <YYYY-MM-DD>` and `time <HH:MM:SS>` - set the clock
+//! * `start` and `stop` - start and stop logging (stopping flushes)
+//! * `flush` - write out what's waiting, e.g. before taking the card out
+//! * `status` - say what's happening
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+
+use core::fmt::Write;
+use demo::adc::{self, Adc};
+use demo::config::{self, Port};
+use demo::console::Console;
+use demo::datetime::DateTime;
+use demo::fat::{Dir, File, Volume};
+use demo::hal;
+use demo::hib::Rtc;
+use demo::pac;
+use demo::sdcard::SdCard;
+use demo::spi::Spi;
+use demo::text::Buffer;
+use embedded_hal::prelude::*;
+use embedded_hal::spi::MODE_0;
+use hal::delay::Delay;
+use hal::gpio::gpioa::PA3;
+use hal::gpio::{Output, PushPull};
+use hal::sysctl::{self, Clocks};
+use hal::time::U32Ext;
+use menu::*;
+use rt::ExceptionFrame;
+
+/// Where the log goes.
+const LOG_PATH: &str = "/LOG.CSV";
+
+/// Cards start at 400 kHz or less.
+const INIT_HZ: u32 = 400_000;
+
+/// And then most can go this fast.
+const FAST_HZ: u32 = 20_000_000;
+
+/// How many channels a line can have.
+const MAX_CHANNELS: usize = 4;
+
+/// How often lines are written to the card.
+const FLUSH_SECONDS: u32 = 30;
+
+/// How often we look for a card that isn't there.
+const RETRY_SECONDS: u32 = 5;
+
+/// How much can wait for the card. At 40 bytes a line, that's about a
+/// hundred lines - a quarter of an hour, at the default interval.
+const PENDING_LEN: usize = 4096;
+
+/// How long we sleep each time round the main loop.
+const POLL_MS: u32 = 10;
+
+type Card = SdCard<Spi<pac::SSI0>, PA3<Output<PushPull>>>;
+
+/// How far we've got with the card.
+enum Storage {
+    /// Not answering, or no filesystem we can use
+    Absent(Card),
+    /// Mounted, with the log open
+    Ready(Volume<Card>, File),
+}
+
+/// What to log (`adc::TEMPERATURE` for the temperature sensor).
+static mut CHANNELS: [Option<u8>; MAX_CHANNELS] = [Some(0), Some(adc::TEMPERATURE), None, None];
+
+static mut INTERVAL_SECONDS: u32 = 10;
+
+static mut LOGGING: bool = true;
+
+/// The clock, as the main loop last read it, so `date` and `time` can
+/// change just one half.
+static mut NOW: u32 = 0;
+
+/// Requests for the main loop, which has the clock and the card.
+static mut SET_CLOCK: Option<u32> = None;
+static mut NEW_HEADER: bool = true;
+static mut FLUSH_NOW: bool = false;
+static mut SHOW_STATUS: bool = false;
+
+const CHANNELS_ITEM: Item = Item {
+    item_type: ItemType::Callback(channels_callback),
+    command: "channels",
+    help: Some("<n|temp>... - which channels to log"),
+};
+
+const INTERVAL_ITEM: Item = Item {
+    item_type: ItemType::Callback(interval_callback),
+    command: "interval",
+    help: Some("<seconds> - how often to log"),
+};
+
+const DATE_ITEM: Item = Item {
+    item_type: ItemType::Callback(date_callback),
+    command: "date",
+    help: Some("<YYYY-MM-DD> - set the date"),
+};
+
+const TIME_ITEM: Item = Item {
+    item_type: ItemType::Callback(time_callback),
+    command: "time",
+    help: Some("<HH:MM:SS> - set the time"),
+};
+
+const START_ITEM: Item = Item {
+    item_type: ItemType::Callback(start_callback),
+    command: "start",
+    help: Some("start logging"),
+};
+
+const STOP_ITEM: Item = Item {
+    item_type: ItemType::Callback(stop_callback),
+    command: "stop",
+    help: Some("stop logging, and flush"),
+};
+
+const FLUSH_ITEM: Item = Item {
+    item_type: ItemType::Callback(flush_callback),
+    command: "flush",
+    help: Some("write out what's waiting"),
+};
+
+const STATUS_ITEM: Item = Item {
+    item_type: ItemType::Callback(status_callback),
+    command: "status",
+    help: Some("say what's happening"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[
+        &CHANNELS_ITEM,
+        &INTERVAL_ITEM,
+        &DATE_ITEM,
+        &TIME_ITEM,
+        &START_ITEM,
+        &STOP_ITEM,
+        &FLUSH_ITEM,
+        &STATUS_ITEM,
+    ],
+    entry: None,
+    exit: None,
+};
+
+fn channels_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let mut channels = [None; MAX_CHANNELS];
+    let mut count = 0;
+    for word in input.split_whitespace().skip(1) {
+        let channel = match word.parse::<u8>() {
+            Ok(n) if n <= 11 => n,
+            _ if word == "temp" => adc::TEMPERATURE,
+            _ => {
+                writeln!(Console, "# AIN0 to AIN11, or temp").unwrap();
+                return;
+            }
+        };
+        if count == MAX_CHANNELS {
+            writeln!(Console, "# No more than {} channels", MAX_CHANNELS).unwrap();
+            return;
+        }
+        channels[count] = Some(channel);
+        count += 1;
+    }
+    if count == 0 {
+        writeln!(Console, "# Usage: channels <n|temp>...").unwrap();
+        return;
+    }
+    for &channel in channels.iter().filter_map(|c| c.as_ref()) {
+        adc::configure_pin(channel);
+    }
+    unsafe {
+        CHANNELS = channels;
+        NEW_HEADER = true;
+    }
+}
+
+fn interval_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match input.split_whitespace().nth(1).map(|s| s.parse::<u32>()) {
+        Some(Ok(seconds)) if seconds > 0 => unsafe { INTERVAL_SECONDS = seconds },
+        _ => writeln!(Console, "# Usage: interval <seconds>").unwrap(),
+    }
+}
+
+fn date_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let mut now = DateTime::from_unix(unsafe { NOW });
+    match input.split_whitespace().nth(1).map(|s| now.set_date(s)) {
+        Some(Ok(())) => unsafe { SET_CLOCK = Some(now.to_unix()) },
+        _ => writeln!(Console, "# Usage: date <YYYY-MM-DD>").unwrap(),
+    }
+}
+
+fn time_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let mut now = DateTime::from_unix(unsafe { NOW });
+    match input.split_whitespace().nth(1).map(|s| now.set_time(s)) {
+        Some(Ok(())) => unsafe { SET_CLOCK = Some(now.to_unix()) },
+        _ => writeln!(Console, "# Usage: time <HH:MM:SS>").unwrap(),
+    }
+}
+
+fn start_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    unsafe {
+        if !LOGGING {
+            LOGGING = true;
+            NEW_HEADER = true;
+        }
+    }
+}
+
+fn stop_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    unsafe {
+        LOGGING = false;
+        FLUSH_NOW = true;
+    }
+}
+
+fn flush_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    unsafe { FLUSH_NOW = true };
+}
+
+fn status_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    unsafe { SHOW_STATUS = true };
+}
+
+/// The column names, for the top of the file (and after a change).
+fn header(line: &mut Buffer) {
+    write!(line, "time").unwrap();
+    for &channel in unsafe { CHANNELS.iter() }.filter_map(|c| c.as_ref()) {
+        if channel == adc::TEMPERATURE {
+            write!(line, ",temp_c").unwrap();
+        } else {
+            write!(line, ",ain{}_mv", channel).unwrap();
+        }
+    }
+    writeln!(line).unwrap();
+}
+
+/// Read every channel, and make a line of them.
+fn sample(line: &mut Buffer, adc: &mut Adc<pac::ADC0>, now: u32) {
+    write!(line, "{}", DateTime::from_unix(now)).unwrap();
+    for &channel in unsafe { CHANNELS.iter() }.filter_map(|c| c.as_ref()) {
+        let reading = adc.read(channel);
+        if channel == adc::TEMPERATURE {
+            let tenths = adc::temperature(reading);
+            let sign = if tenths < 0 { "-" } else { "" };
+            write!(line, ",{}{}.{}", sign, tenths.abs() / 10, tenths.abs() % 10).unwrap();
+        } else {
+            write!(line, ",{}", adc::millivolts(reading)).unwrap();
+        }
+    }
+    writeln!(line).unwrap();
+}
+
+/// Send a line to the console, and keep it for the card if there's room.
+/// Once there's been no room, the next line to go in says so.
+fn record(line: &Buffer, pending: &mut Buffer<&mut [u8]>, lost: &mut u32) {
+    write!(Console, "{}", line.as_str()).unwrap();
+    if *lost > 0 && pending.is_empty() {
+        writeln!(pending, "# {} lines lost while the card was out", lost).unwrap();
+        *lost = 0;
+    }
+    if pending.capacity() - pending.len() >= line.len() {
+        pending.write_str(line.as_str()).unwrap();
+    } else {
+        *lost += 1;
+    }
+}
+
+/// Wake the card up, mount it and open the log.
+fn open_log(mut card: Card, delay: &mut Delay, clocks: &Clocks) -> Storage {
+    card.spi().set_frequency(INIT_HZ.hz(), clocks);
+    if card.init(delay).is_err() {
+        return Storage::Absent(card);
+    }
+    card.spi().set_frequency(FAST_HZ.hz(), clocks);
+    let mut volume = match Volume::mount(card) {
+        Ok(volume) => volume,
+        Err((card, e)) => {
+            writeln!(Console, "# {}", e).unwrap();
+            return Storage::Absent(card);
+        }
+    };
+    match volume.append(Dir::root(), LOG_PATH) {
+        Ok(file) => {
+            writeln!(Console, "# Logging to {} ({} bytes)", LOG_PATH, file.size()).unwrap();
+            Storage::Ready(volume, file)
+        }
+        Err(e) => {
+            writeln!(Console, "# Can't open {}: {}", LOG_PATH, e).unwrap();
+            Storage::Absent(volume.free())
+        }
+    }
+}
+
+/// Write what's waiting to the card, and update the file's size. If the
+/// card's gone, it all stays waiting.
+fn flush(storage: Storage, pending: &mut Buffer<&mut [u8]>, now: u32) -> Storage {
+    let (mut volume, mut file) = match storage {
+        Storage::Ready(volume, file) => (volume, file),
+        absent => return absent,
+    };
+    volume.set_time(DateTime::from_unix(now));
+    let result = match volume.write(&mut file, pending.as_bytes()) {
+        Ok(()) => volume.sync(&mut file),
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(()) => {
+            pending.clear();
+            Storage::Ready(volume, file)
+        }
+        Err(e) => {
+            // What we wrote is past the size the card knows about, so it
+            // all goes again next time
+            writeln!(Console, "# {} - keeping {} bytes for later", e, pending.len()).unwrap();
+            Storage::Absent(volume.free())
+        }
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Ssi0);
+    board.enable(sysctl::Domain::Adc0);
+
+    // No reset here, or we'd stop the clock
+    sysctl::control_power(
+        &board.power_control,
+        sysctl::Domain::Hibernation,
+        sysctl::RunMode::Run,
+        sysctl::PowerState::On,
+    );
+    let mut rtc = Rtc::new(p.HIB);
+
+    // AIN0 to AIN11 are all on these
+    Port::B.enable();
+    Port::D.enable();
+    Port::E.enable();
+    for &channel in unsafe { CHANNELS.iter() }.filter_map(|c| c.as_ref()) {
+        adc::configure_pin(channel);
+    }
+    let mut adc = Adc::adc0(p.ADC0);
+
+    // SSI0Clk, SSI0Rx and SSI0Tx
+    config::SSI0.connect();
+    let porta = board.porta;
+    let spi = Spi::ssi0(p.SSI0, MODE_0, INIT_HZ.hz(), &board.clocks);
+    let card = SdCard::new(spi, porta.pa3.into_push_pull_output());
+    let mut delay = Delay::new(cp.SYST, &board.clocks);
+
+    writeln!(board.tx, "# Data logger, it's {}", DateTime::from_unix(rtc.seconds())).unwrap();
+    let mut storage = open_log(card, &mut delay, &board.clocks);
+
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+
+    let mut pending_bytes = [0u8; PENDING_LEN];
+    let mut pending = Buffer::from_storage(&mut pending_bytes[..]);
+    let mut lost = 0u32;
+    let mut was_ready = true;
+    let start = rtc.seconds();
+    let mut last_sample = start.wrapping_sub(unsafe { INTERVAL_SECONDS });
+    let mut last_flush = start;
+    let mut last_retry = start;
+
+    loop {
+        while let Ok(ch) = board.rx.read() {
+            r.input_byte(ch);
+        }
+
+        if let Some(unix) = unsafe { SET_CLOCK.take() } {
+            rtc.set_seconds(unix);
+            writeln!(Console, "# It's now {}", DateTime::from_unix(unix)).unwrap();
+            last_sample = unix.wrapping_sub(unsafe { INTERVAL_SECONDS });
+            last_flush = unix;
+            last_retry = unix;
+        }
+        let now = rtc.seconds();
+        unsafe { NOW = now };
+
+        if unsafe { core::mem::replace(&mut NEW_HEADER, false) } {
+            let mut line = Buffer::new();
+            header(&mut line);
+            record(&line, &mut pending, &mut lost);
+        }
+        // Going by the gap, rather than the next time, copes with the
+        // clock going backwards
+        if unsafe { LOGGING } && now.wrapping_sub(last_sample) >= unsafe { INTERVAL_SECONDS } {
+            last_sample = now;
+            let mut line = Buffer::new();
+            sample(&mut line, &mut adc, now);
+            record(&line, &mut pending, &mut lost);
+        }
+
+        let absent = match storage {
+            Storage::Absent(_) => true,
+            Storage::Ready(..) => false,
+        };
+        if absent && now.wrapping_sub(last_retry) >= RETRY_SECONDS {
+            last_retry = now;
+            storage = match storage {
+                Storage::Absent(card) => open_log(card, &mut delay, &board.clocks),
+                ready => ready,
+            };
+        }
+        let flush_now = unsafe { core::mem::replace(&mut FLUSH_NOW, false) };
+        let due = now.wrapping_sub(last_flush) >= FLUSH_SECONDS;
+        if flush_now || (!pending.is_empty() && (due || pending.len() > PENDING_LEN / 2)) {
+            last_flush = now;
+            storage = flush(storage, &mut pending, now);
+        }
+
+        let ready = match storage {
+            Storage::Ready(..) => true,
+            Storage::Absent(_) => false,
+        };
+        if ready != was_ready {
+            if !ready {
+                writeln!(Console, "# No card - trying every {} s", RETRY_SECONDS).unwrap();
+            }
+            was_ready = ready;
+        }
+        if unsafe { core::mem::replace(&mut SHOW_STATUS, false) } {
+            writeln!(
+                Console,
+                "# {}, every {} s, card {}, {} bytes waiting, {} lines lost",
+                if unsafe { LOGGING } { "Logging" } else { "Stopped" },
+                unsafe { INTERVAL_SECONDS },
+                if ready { "ready" } else { "missing" },
+                pending.len(),
+                lost
+            ).unwrap();
+        }
+        delay.delay_ms(POLL_MS);
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,291 @@
+//! Closed-loop speed control of a DC motor with a quadrature encoder.
+//!
+//! * PWM to the H-bridge enable is PA6 (M1PWM2), at 20 kHz
+//! * H-bridge IN1 / IN2 are PE1 / PE2
+//! * Encoder A / B are PC5 (PhA1) / PC6 (PhB1), read by QEI1
+//!
+//! Timer1A runs the PID loop at 1 kHz. The console is on UART0 at 115200 bps.
+//!
+//! Commands:
+//!
+//! * `rpm <n>` - set the target speed (negative for reverse)
+//! * `kp <n>`, `ki <n>`, `kd <n>` - set a gain, in thousandths
+//! * `status` - show target, actual speed, output and gains
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use cortex_m::interrupt;
+use demo::board::Board;
+use demo::console::Console;
+use demo::pid::Pid;
+use embedded_hal::prelude::*;
+use menu::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::gpio::GpioExt;
+use tm4c123x_hal::sysctl;
+
+/// How often we run the control loop.
+const LOOP_HZ: u32 = 1_000;
+
+/// System clock.
+const SYSCLK_HZ: u32 = 80_000_000;
+
+/// PWM period in system clocks (20 kHz).
+const PWM_PERIOD: u32 = SYSCLK_HZ / 20_000;
+
+/// Encoder counts per revolution of the output shaft (we count all four
+/// edges, so this is four times the encoder's lines per revolution).
+const COUNTS_PER_REV: i32 = 4 * 334;
+
+/// Mask for the direction pins, PE1 and PE2.
+const DIR_MASK: u32 = 0x06;
+const DIR_FORWARD: u32 = 0x02;
+const DIR_REVERSE: u32 = 0x04;
+
+struct Control {
+    pid: Pid,
+    /// Target speed in encoder counts per second
+    target: i32,
+    /// Measured speed in counts per second, times 256
+    speed_q8: i32,
+    last_pos: u32,
+    output: i32,
+}
+
+static mut CONTROL: Option<Control> = None;
+
+const RPM_ITEM: Item = Item {
+    item_type: ItemType::Callback(rpm_callback),
+    command: "rpm",
+    help: Some("<n> - set the target speed"),
+};
+
+const KP_ITEM: Item = Item {
+    item_type: ItemType::Callback(gain_callback),
+    command: "kp",
+    help: Some("<n> - set proportional gain (x1000)"),
+};
+
+const KI_ITEM: Item = Item {
+    item_type: ItemType::Callback(gain_callback),
+    command: "ki",
+    help: Some("<n> - set integral gain (x1000)"),
+};
+
+const KD_ITEM: Item = Item {
+    item_type: ItemType::Callback(gain_callback),
+    command: "kd",
+    help: Some("<n> - set derivative gain (x1000)"),
+};
+
+const STATUS_ITEM: Item = Item {
+    item_type: ItemType::Callback(status_callback),
+    command: "status",
+    help: Some("show the loop state"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[&RPM_ITEM, &KP_ITEM, &KI_ITEM, &KD_ITEM, &STATUS_ITEM],
+    entry: None,
+    exit: None,
+};
+
+/// Parse the first argument after the command.
+fn argument<T>(input: &str) -> Option<T>
+where
+    T: core::str::FromStr,
+{
+    input.split_whitespace().nth(1).and_then(|s| s.parse::<T>().ok())
+}
+
+/// Run a closure against the control state with interrupts masked.
+fn with_control<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut Control) -> R,
+{
+    interrupt::free(|_| f(unsafe { CONTROL.as_mut().unwrap() }))
+}
+
+fn rpm_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match argument::<i32>(input) {
+        Some(rpm) => with_control(|c| c.target = (rpm * COUNTS_PER_REV) / 60),
+        None => writeln!(Console, "Usage: rpm <n>").unwrap(),
+    }
+}
+
+fn gain_callback(_menu: &Menu, item: &Item, input: &str) {
+    match argument::<i32>(input) {
+        Some(gain) => with_control(|c| {
+            match item.command {
+                "kp" => c.pid.kp = gain,
+                "ki" => c.pid.ki = gain,
+                _ => c.pid.kd = gain,
+            }
+            c.pid.reset();
+        }),
+        None => writeln!(Console, "Usage: {} <n>", item.command).unwrap(),
+    }
+}
+
+fn status_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    let (target, speed, output, kp, ki, kd) = with_control(|c| {
+        (
+            c.target,
+            c.speed_q8 >> 8,
+            c.output,
+            c.pid.kp,
+            c.pid.ki,
+            c.pid.kd,
+        )
+    });
+    writeln!(
+        Console,
+        "Target {} rpm, actual {} rpm, output {}/{}",
+        (target * 60) / COUNTS_PER_REV,
+        (speed * 60) / COUNTS_PER_REV,
+        output,
+        PWM_PERIOD
+    ).unwrap();
+    writeln!(Console, "Kp {} Ki {} Kd {} (x1000)", kp, ki, kd).unwrap();
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer1);
+    board.enable(sysctl::Domain::Pwm1);
+    board.enable(sysctl::Domain::Qei1);
+
+    let mut portc = p.GPIO_PORTC.split(&board.power_control);
+    let porte = p.GPIO_PORTE.split(&board.power_control);
+
+    // M1PWM2
+    let mut porta = board.porta;
+    let _pwm = porta.pa6.into_af5(&mut porta.control);
+    // PhA1 and PhB1
+    let _pha = portc.pc5.into_af6(&mut portc.control);
+    let _phb = portc.pc6.into_af6(&mut portc.control);
+    // Direction pins, driven by the ISR
+    let _in1 = porte.pe1.into_push_pull_output();
+    let _in2 = porte.pe2.into_push_pull_output();
+
+    // PWM1 generator 1, count-down mode. Output A goes high at LOAD and low
+    // on a CMPA match, so CMPA sets the off time.
+    let pwm = p.PWM1;
+    pwm._1_ctl.write(|w| unsafe { w.bits(0) });
+    pwm._1_load.write(|w| unsafe { w.bits(PWM_PERIOD - 1) });
+    pwm._1_cmpa.write(|w| unsafe { w.bits(PWM_PERIOD - 1) });
+    // ACTCMPAD = drive low, ACTLOAD = drive high
+    pwm._1_gena.write(|w| unsafe { w.bits((0x2 << 6) | (0x3 << 2)) });
+    pwm._1_ctl.write(|w| unsafe { w.bits(1) });
+    pwm.enable.modify(|r, w| unsafe { w.bits(r.bits() | (1 << 2)) });
+
+    // QEI1, counting both edges of both channels
+    let qei = p.QEI1;
+    qei.maxpos.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+    qei.ctl.write(|w| unsafe { w.bits((1 << 3) | 1) });
+
+    unsafe {
+        CONTROL = Some(Control {
+            pid: Pid::new(2_000, 200, 0, PWM_PERIOD as i32),
+            target: 0,
+            speed_q8: 0,
+            last_pos: 0,
+            output: 0,
+        });
+    }
+
+    // Timer1A, 32-bit periodic at LOOP_HZ
+    let timer = p.TIMER1;
+    timer.ctl.modify(|_, w| w.taen().clear_bit());
+    timer.cfg.write(|w| unsafe { w.bits(0) });
+    timer.tamr.modify(|_, w| w.tamr().period());
+    timer
+        .tailr
+        .write(|w| unsafe { w.bits(SYSCLK_HZ / LOOP_HZ - 1) });
+    timer.imr.modify(|_, w| w.tatoim().set_bit());
+    timer.icr.write(|w| w.tatocint().set_bit());
+    timer.ctl.modify(|_, w| w.taen().set_bit());
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER1A);
+
+    writeln!(board.tx, "DC motor PID demo").unwrap();
+
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+
+    loop {
+        if let Ok(ch) = board.rx.read() {
+            r.input_byte(ch);
+        }
+    }
+}
+
+interrupt!(TIMER1A, timer1a_isr);
+
+fn timer1a_isr() {
+    let timer = unsafe { &*tm4c123x::TIMER1::ptr() };
+    timer.icr.write(|w| w.tatocint().set_bit());
+
+    let c = match unsafe { CONTROL.as_mut() } {
+        Some(c) => c,
+        None => return,
+    };
+
+    // Speed from the change in position, low-pass filtered
+    let qei = unsafe { &*tm4c123x::QEI1::ptr() };
+    let pos = qei.pos.read().bits();
+    let delta = pos.wrapping_sub(c.last_pos) as i32;
+    c.last_pos = pos;
+    let instant_q8 = (delta * LOOP_HZ as i32) << 8;
+    c.speed_q8 += (instant_q8 - c.speed_q8) / 8;
+
+    c.output = c.pid.update(c.target, c.speed_q8 >> 8);
+
+    // Sign picks the direction, magnitude sets the duty cycle
+    let gpio = unsafe { &*tm4c123x::GPIO_PORTE::ptr() };
+    let dir = if c.output > 0 {
+        DIR_FORWARD
+    } else if c.output < 0 {
+        DIR_REVERSE
+    } else {
+        0
+    };
+    gpio.data
+        .modify(|r, w| unsafe { w.bits((r.bits() & !DIR_MASK) | dir) });
+    let duty = (c.output.abs() as u32).min(PWM_PERIOD - 1);
+    let pwm = unsafe { &*tm4c123x::PWM1::ptr() };
+    pwm._1_cmpa
+        .write(|w| unsafe { w.bits(PWM_PERIOD - 1 - duty) });
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
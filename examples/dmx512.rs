@@ -0,0 +1,267 @@
+//! DMX512 lighting control.
+//!
+//! Wire a MAX3485 (or similar) with DI to PB1 (U1Tx) and DE and /RE
+//! together to PB5. A and B go to pins 3 and 2 of the XLR, with the
+//! ground on pin 1. Don't forget a 120R terminator at the far end of the
+//! chain.
+//!
+//! A DMX frame is a break (the line held low for at least 88us), a 'mark
+//! after break' (high for at least 8us), then 513 slots at 250 kbaud, 8N2.
+//! The first slot is the start code (0 for dimmer levels) and the other
+//! 512 are the channel levels. We send frames back to back, which works
+//! out at about 44 a second. The console is on UART0 at 115200 bps.
+//!
+//! Commands:
+//!
+//! * `set <channel> <level>` - set a channel (1..512) to a level (0..255)
+//! * `all <level>` - set every channel
+//! * `show` - list the channels that aren't zero
+//! * `chase <n>` - chase a light along the first n channels (0 to stop)
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board, gpio_port)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+#[macro_use]
+extern crate nb;
+extern crate panic_halt;
+
+use core::fmt::Write;
+use demo::console::Console;
+use demo::hal;
+use demo::pac;
+use embedded_hal::prelude::*;
+use hal::delay::Delay;
+use hal::gpio::GpioExt;
+use hal::serial::{NewlineMode, Serial};
+use hal::time::U32Ext;
+use menu::*;
+use rt::ExceptionFrame;
+
+/// Channels in a DMX universe.
+const CHANNELS: usize = 512;
+
+/// The start code for ordinary dimmer data.
+const START_CODE: u8 = 0;
+
+/// The spec says at least 88us of break and 8us of mark after break. Some
+/// older fixtures like a bit more.
+const BREAK_US: u32 = 100;
+const MARK_AFTER_BREAK_US: u32 = 12;
+
+/// How many frames each step of the chase lasts (about a fifth of a
+/// second).
+const CHASE_FRAMES: u32 = 9;
+
+// UARTCTL, UARTLCRH, UARTFR
+const CTL_UARTEN: u32 = 1 << 0;
+const LCRH_BRK: u32 = 1 << 0;
+const LCRH_STP2: u32 = 1 << 3;
+const FR_BUSY: u32 = 1 << 3;
+
+/// Channel levels. Index 0 is channel 1.
+static mut LEVELS: [u8; CHANNELS] = [0; CHANNELS];
+
+/// How many channels the chase runs over. Zero means no chase.
+static mut CHASE_LEN: usize = 0;
+
+const SET_ITEM: Item = Item {
+    item_type: ItemType::Callback(set_callback),
+    command: "set",
+    help: Some("<channel> <level> - set a channel"),
+};
+
+const ALL_ITEM: Item = Item {
+    item_type: ItemType::Callback(all_callback),
+    command: "all",
+    help: Some("<level> - set every channel"),
+};
+
+const SHOW_ITEM: Item = Item {
+    item_type: ItemType::Callback(show_callback),
+    command: "show",
+    help: Some("list the channels that are on"),
+};
+
+const CHASE_ITEM: Item = Item {
+    item_type: ItemType::Callback(chase_callback),
+    command: "chase",
+    help: Some("<n> - chase along the first n channels (0 to stop)"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[&SET_ITEM, &ALL_ITEM, &SHOW_ITEM, &CHASE_ITEM],
+    entry: None,
+    exit: None,
+};
+
+/// Parse the nth argument after the command.
+fn argument<T>(input: &str, n: usize) -> Option<T>
+where
+    T: core::str::FromStr,
+{
+    input
+        .split_whitespace()
+        .nth(n + 1)
+        .and_then(|s| s.parse::<T>().ok())
+}
+
+fn levels() -> &'static mut [u8; CHANNELS] {
+    // There are no interrupts, so the callbacks and the main loop can't
+    // get in each other's way.
+    unsafe { &mut LEVELS }
+}
+
+fn set_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match (argument::<usize>(input, 0), argument::<u8>(input, 1)) {
+        (Some(channel), Some(level)) if channel >= 1 && channel <= CHANNELS => {
+            levels()[channel - 1] = level;
+        }
+        _ => writeln!(Console, "Usage: set <1..512> <0..255>").unwrap(),
+    }
+}
+
+fn all_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match argument::<u8>(input, 0) {
+        Some(level) => for l in levels().iter_mut() {
+            *l = level;
+        },
+        None => writeln!(Console, "Usage: all <0..255>").unwrap(),
+    }
+}
+
+fn show_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    let mut any = false;
+    for (i, level) in levels().iter().enumerate() {
+        if *level != 0 {
+            writeln!(Console, "{:3}: {}", i + 1, level).unwrap();
+            any = true;
+        }
+    }
+    if !any {
+        writeln!(Console, "All channels are off").unwrap();
+    }
+    let chase = unsafe { CHASE_LEN };
+    if chase != 0 {
+        writeln!(Console, "Chasing over {} channels", chase).unwrap();
+    }
+}
+
+fn chase_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match argument::<usize>(input, 0) {
+        Some(n) if n <= CHANNELS => {
+            unsafe { CHASE_LEN = n };
+            for l in levels()[0..n].iter_mut() {
+                *l = 0;
+            }
+        }
+        _ => writeln!(Console, "Usage: chase <0..512>").unwrap(),
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+
+    let mut portb = gpio_port!(p, B).split(&board.power_control);
+
+    // The DMX side. We only transmit, so U1Rx is left alone.
+    let uart1 = Serial::uart1(
+        p.UART1,
+        portb.pb1.into_af1(&mut portb.control),
+        portb.pb0.into_af1(&mut portb.control),
+        (),
+        (),
+        250_000_u32.bps(),
+        NewlineMode::Binary,
+        &board.clocks,
+        &board.power_control,
+    );
+    let (mut dmx, _) = uart1.split();
+
+    // DMX wants two stop bits, which the HAL doesn't do, so we change the
+    // line control with the UART turned off.
+    let regs = unsafe { &*pac::UART1::ptr() };
+    regs.ctl
+        .modify(|r, w| unsafe { w.bits(r.bits() & !CTL_UARTEN) });
+    regs.lcrh
+        .modify(|r, w| unsafe { w.bits(r.bits() | LCRH_STP2) });
+    regs.ctl.modify(|r, w| unsafe { w.bits(r.bits() | CTL_UARTEN) });
+
+    // We're the only transmitter on the bus, and the break has to be
+    // driven too, so the transceiver stays on all the time.
+    let mut de = portb.pb5.into_push_pull_output();
+    de.set_high();
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+
+    writeln!(board.tx, "DMX512 controller").unwrap();
+
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+
+    let mut frame = [0u8; CHANNELS];
+    let mut frames = 0u32;
+    let mut chase_pos = 0;
+
+    loop {
+        // Move the chase along
+        let chase_len = unsafe { CHASE_LEN };
+        if chase_len != 0 {
+            if frames % CHASE_FRAMES == 0 {
+                let l = levels();
+                l[chase_pos % chase_len] = 0;
+                chase_pos = (chase_pos + 1) % chase_len;
+                l[chase_pos] = 255;
+            }
+        } else {
+            chase_pos = 0;
+        }
+
+        // Take a copy, so a command can't change the levels halfway
+        // through a frame
+        frame.copy_from_slice(levels());
+
+        // Let the last frame finish before we break
+        while regs.fr.read().bits() & FR_BUSY != 0 {}
+        regs.lcrh.modify(|r, w| unsafe { w.bits(r.bits() | LCRH_BRK) });
+        d.delay_us(BREAK_US);
+        regs.lcrh
+            .modify(|r, w| unsafe { w.bits(r.bits() & !LCRH_BRK) });
+        d.delay_us(MARK_AFTER_BREAK_US);
+
+        block!(dmx.write(START_CODE)).unwrap();
+        for level in frame.iter() {
+            block!(dmx.write(*level)).unwrap();
+            // Keep an eye on the console, as a frame takes 23ms and the
+            // FIFO only holds 16 characters
+            if let Ok(ch) = board.rx.read() {
+                r.input_byte(ch);
+            }
+        }
+        frames = frames.wrapping_add(1);
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,229 @@
+//! Keeps time with a DS3231 real time clock module on I2C.
+//!
+//! SCL is PB2 (I2C0SCL) and SDA is PB3 (I2C0SDA). The VGA output is the
+//! same as `hello_vga`: HSYNC on PB6, VSYNC on PC4 and green on PB7.
+//!
+//! The DS3231 has its own battery and crystal, so this is an alternative to
+//! fitting the 32.768 kHz crystal for the hibernation module. The time is
+//! shown in the status bar at the top of the VGA screen, re-read from the
+//! RTC every 100ms so it never drifts from it.
+//!
+//! Commands, on UART0 at 115200 bps:
+//!
+//! * `date [YYYY-MM-DD]` - show or set the date
+//! * `time [HH:MM:SS]` - show or set the time
+//! * `temp` - show the DS3231's die temperature
+//! * `stack` - show the most stack used so far
+//!
+//! The stack figure is on the left of the status bar too, updated once a
+//! minute.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+extern crate vga_framebuffer as fb;
+
+use core::fmt::Write;
+use demo::board::Board;
+use demo::console::Console;
+use demo::datetime::DateTime;
+use demo::ds3231::Ds3231;
+use demo::i2c::{self, I2c};
+use demo::reset;
+use demo::stack;
+use demo::status_bar;
+use demo::text::Buffer;
+use demo::vga;
+use embedded_hal::prelude::*;
+use menu::*;
+use rt::ExceptionFrame;
+use tm4c123x::I2C0;
+use tm4c123x_hal::delay::Delay;
+use tm4c123x_hal::gpio::GpioExt;
+use tm4c123x_hal::sysctl;
+use tm4c123x_hal::time::U32Ext;
+
+/// How often we re-read the RTC.
+const POLL_MS: u32 = 100;
+
+static mut RTC: Option<Ds3231<I2c<I2C0>>> = None;
+
+const DATE_ITEM: Item = Item {
+    item_type: ItemType::Callback(date_callback),
+    command: "date",
+    help: Some("[YYYY-MM-DD] - show or set the date"),
+};
+
+const TIME_ITEM: Item = Item {
+    item_type: ItemType::Callback(time_callback),
+    command: "time",
+    help: Some("[HH:MM:SS] - show or set the time"),
+};
+
+const TEMP_ITEM: Item = Item {
+    item_type: ItemType::Callback(temp_callback),
+    command: "temp",
+    help: Some("show the RTC temperature"),
+};
+
+const STACK_ITEM: Item = Item {
+    item_type: ItemType::Callback(stack_callback),
+    command: "stack",
+    help: Some("show the most stack used so far"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[&DATE_ITEM, &TIME_ITEM, &TEMP_ITEM, &STACK_ITEM],
+    entry: None,
+    exit: None,
+};
+
+fn rtc() -> &'static mut Ds3231<I2c<I2C0>> {
+    unsafe { RTC.as_mut().unwrap() }
+}
+
+fn date_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let mut now = rtc().get().unwrap();
+    if let Some(arg) = input.split_whitespace().nth(1) {
+        if now.set_date(arg).is_err() {
+            writeln!(Console, "Usage: date [YYYY-MM-DD]").unwrap();
+            return;
+        }
+        rtc().set(&now).unwrap();
+    }
+    writeln!(Console, "{}", now).unwrap();
+}
+
+fn time_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let mut now = rtc().get().unwrap();
+    if let Some(arg) = input.split_whitespace().nth(1) {
+        if now.set_time(arg).is_err() {
+            writeln!(Console, "Usage: time [HH:MM:SS]").unwrap();
+            return;
+        }
+        rtc().set(&now).unwrap();
+    }
+    writeln!(Console, "{}", now).unwrap();
+}
+
+fn temp_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    let quarters = rtc().temperature().unwrap();
+    writeln!(
+        Console,
+        "{}.{:02} C",
+        quarters / 4,
+        (quarters.abs() % 4) * 25
+    ).unwrap();
+}
+
+fn stack_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    writeln!(
+        Console,
+        "Stack: {} of {} bytes used at worst",
+        stack::used(),
+        stack::size()
+    ).unwrap();
+}
+
+entry!(main);
+
+fn main() -> ! {
+    // Before anything else goes on the stack
+    stack::paint();
+    let cause = reset::cause();
+
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::I2c0);
+
+    let mut portb = p.GPIO_PORTB.split(&board.power_control);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+
+    // I2C0SCL and I2C0SDA
+    let _scl = portb.pb2.into_af3(&mut portb.control);
+    let _sda = portb.pb3.into_af3(&mut portb.control);
+    i2c::open_drain(unsafe { &*tm4c123x::GPIO_PORTB::ptr() }, 3);
+    let bus = I2c::i2c0(p.I2C0, 100_000_u32.hz(), &board.clocks);
+
+    let mut rtc = Ds3231::new(bus).unwrap();
+    if rtc.lost_power().unwrap() {
+        writeln!(board.tx, "RTC lost power - set it with `date` and `time`").unwrap();
+        rtc.set(&DateTime::zero()).unwrap();
+    }
+    unsafe {
+        RTC = Some(rtc);
+    }
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+
+    // Leave the top row for the status bar
+    let mut c = fb::TextFrameBuffer::new(vga::framebuffer());
+    c.clear();
+    writeln!(c, "\nDS3231 clock demo").unwrap();
+    writeln!(c, "Reset by: {}", cause).unwrap();
+    if cause.is_brown_out() {
+        writeln!(c, "Check the power supply!").unwrap();
+    }
+
+    writeln!(board.tx, "DS3231 clock demo - it is {}", rtc().get().unwrap()).unwrap();
+
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+
+    let mut last_shown = DateTime::zero();
+    let mut stack_used = Buffer::new();
+    loop {
+        while let Ok(ch) = board.rx.read() {
+            r.input_byte(ch);
+        }
+
+        let now = rtc().get().unwrap();
+        if now != last_shown {
+            let mut clock = Buffer::new();
+            write!(clock, "{}", now).unwrap();
+            if stack_used.is_empty() || now.minutes != last_shown.minutes {
+                stack_used.clear();
+                write!(stack_used, "Monotron - stack {}", stack::used()).unwrap();
+            }
+            status_bar::draw(vga::framebuffer(), stack_used.as_str(), clock.as_str());
+            last_shown = now;
+        }
+
+        d.delay_ms(POLL_MS);
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,205 @@
+//! Joins a WiFi network with an ESP8266 and POSTs ADC readings to a web
+//! server.
+//!
+//! The ESP8266 (an ESP-01 is fine) needs the standard AT firmware, running
+//! at 115200 bps. Connect its TX to PB0 (U1Rx), its RX to PB1 (U1Tx) and
+//! give it a decent 3.3V supply - it takes more current than the LaunchPad
+//! regulator likes. CH_PD must be pulled high.
+//!
+//! A pot on PE3 (AIN0) gives us something to report, along with the chip
+//! temperature. Every ten seconds we send them as a form to `PATH` on
+//! `HOST`, and print the status line of the reply on UART0 (115200 bps).
+//! Change the constants below to suit your network and server - something
+//! like `python3 -m http.server` won't do, as it doesn't take POSTs, but a
+//! few lines of Flask will.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board, gpio_port)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+
+use core::fmt::Write;
+use cortex_m::asm;
+use demo::adc::{self, Adc};
+use demo::esp8266::{self, Esp8266, Protocol};
+use demo::hal;
+use demo::text::Buffer;
+use hal::delay::Delay;
+use hal::gpio::GpioExt;
+use hal::serial::{NewlineMode, Serial};
+use hal::sysctl;
+use hal::time::U32Ext;
+use rt::ExceptionFrame;
+
+/// The network to join.
+const SSID: &str = "my-network";
+const PASSWORD: &str = "my-password";
+
+/// Where to send the readings.
+const HOST: &str = "192.168.1.10";
+const PORT: u16 = 8000;
+const PATH: &str = "/readings";
+
+/// How often to send them.
+const INTERVAL_MS: u32 = 10_000;
+
+/// How long the server gets to answer.
+const REPLY_TIMEOUT_MS: u32 = 5_000;
+
+/// The channel the pot is on.
+const POT_CHANNEL: u8 = 0;
+
+/// Send one lot of readings, and return the first line of the reply.
+fn post<TX, RX, D>(
+    esp: &mut Esp8266<TX, RX, D>,
+    millivolts: u32,
+    temperature: i32,
+    reply: &mut [u8],
+) -> Result<usize, esp8266::Error>
+where
+    TX: embedded_hal::serial::Write<u8>,
+    RX: embedded_hal::serial::Read<u8>,
+    D: embedded_hal::blocking::delay::DelayUs<u32>,
+{
+    let mut body = Buffer::from_storage([0u8; 256]);
+    let sign = if temperature < 0 { "-" } else { "" };
+    write!(
+        body,
+        "pot_mv={}&temperature={}{}.{}",
+        millivolts,
+        sign,
+        temperature.abs() / 10,
+        temperature.abs() % 10
+    ).unwrap();
+
+    let mut request = Buffer::from_storage([0u8; 256]);
+    write!(
+        request,
+        "POST {} HTTP/1.1\r\n\
+         Host: {}\r\n\
+         Content-Type: application/x-www-form-urlencoded\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n",
+        PATH,
+        HOST,
+        body.len()
+    ).unwrap();
+
+    esp.connect(Protocol::Tcp, HOST, PORT)?;
+    esp.send(request.as_bytes())?;
+    esp.send(body.as_bytes())?;
+
+    // We only want the status line, but we read it all so the module isn't
+    // left with data for us
+    let mut len = 0;
+    let mut first_line = true;
+    let mut chunk = [0u8; 64];
+    loop {
+        let n = match esp.receive(&mut chunk, REPLY_TIMEOUT_MS) {
+            Ok(0) | Err(esp8266::Error::Closed) => break,
+            Ok(n) => n,
+            Err(e) => return Err(e),
+        };
+        for byte in &chunk[0..n] {
+            if *byte == b'\r' || *byte == b'\n' {
+                first_line = false;
+            } else if first_line && len < reply.len() {
+                reply[len] = *byte;
+                len += 1;
+            }
+        }
+    }
+    esp.close()?;
+    Ok(len)
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Adc0);
+
+    let mut portb = gpio_port!(p, B).split(&board.power_control);
+    let _porte = gpio_port!(p, E).split(&board.power_control);
+
+    // The ESP8266
+    let uart1 = Serial::uart1(
+        p.UART1,
+        portb.pb1.into_af1(&mut portb.control),
+        portb.pb0.into_af1(&mut portb.control),
+        (),
+        (),
+        115200_u32.bps(),
+        NewlineMode::Binary,
+        &board.clocks,
+        &board.power_control,
+    );
+    let (tx1, rx1) = uart1.split();
+
+    adc::configure_pin(POT_CHANNEL);
+    let mut adc = Adc::adc0(p.ADC0);
+
+    let d = Delay::new(cp.SYST, &board.clocks);
+
+    writeln!(board.tx, "ESP8266 HTTP POST demo").unwrap();
+
+    let mut esp = match Esp8266::new(tx1, rx1, d) {
+        Ok(esp) => esp,
+        Err(e) => {
+            writeln!(board.tx, "ESP8266 not responding: {:?}", e).unwrap();
+            loop {
+                asm::wfi();
+            }
+        }
+    };
+
+    writeln!(board.tx, "Joining {}...", SSID).unwrap();
+    while let Err(e) = esp.join(SSID, PASSWORD) {
+        writeln!(board.tx, "Failed ({:?}), retrying", e).unwrap();
+    }
+    let mut ip = [0u8; 16];
+    match esp.local_ip(&mut ip) {
+        Ok(ip) => writeln!(board.tx, "Joined. Our IP is {}", ip).unwrap(),
+        Err(e) => writeln!(board.tx, "Joined, but no IP? ({:?})", e).unwrap(),
+    }
+
+    let mut reply = [0u8; 64];
+    loop {
+        let millivolts = adc::millivolts(adc.read(POT_CHANNEL));
+        let temperature = adc::temperature(adc.read(adc::TEMPERATURE));
+        writeln!(board.tx, "Sending pot={}mV temperature={}", millivolts, temperature).unwrap();
+        match post(&mut esp, millivolts, temperature, &mut reply) {
+            Ok(len) => writeln!(
+                board.tx,
+                "Got: {}",
+                core::str::from_utf8(&reply[0..len]).unwrap_or("?")
+            ).unwrap(),
+            Err(e) => {
+                writeln!(board.tx, "Failed: {:?}", e).unwrap();
+                let _ = esp.close();
+            }
+        }
+        esp.wait(INTERVAL_MS).unwrap();
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
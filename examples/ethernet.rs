@@ -0,0 +1,193 @@
+//! Puts the LaunchPad on Ethernet with an ENC28J60 module and smoltcp.
+//!
+//! We have a fixed IP address (change `IP_ADDRESS` to suit your network),
+//! answer pings and serve a little status page on port 80 showing our
+//! uptime and the ADC readings. Try `ping 192.168.1.50` and then point a
+//! browser at it.
+//!
+//! Wire the module to SSI0: SCK to PA2, SO to PA4 (SSI0Rx), SI to PA5
+//! (SSI0Tx) and CS to PA3. Tie RESET high. The module needs 3.3V, and
+//! rather a lot of it - about 180mA - so don't power it from a GPIO pin.
+//! Pots (or anything else) on PE3 (AIN0) and PE2 (AIN1) show up on the page,
+//! along with the chip temperature. Progress is printed on UART0 (115200
+//! bps).
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board, gpio_port)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+extern crate smoltcp;
+
+use core::fmt::Write;
+use demo::adc::{self, Adc};
+use demo::config;
+use demo::enc28j60::Enc28j60;
+use demo::hal;
+use demo::pac;
+use demo::spi::Spi;
+use demo::text::Buffer;
+use embedded_hal::prelude::*;
+use embedded_hal::spi::MODE_0;
+use hal::delay::Delay;
+use hal::gpio::GpioExt;
+use hal::sysctl;
+use hal::time::U32Ext;
+use rt::ExceptionFrame;
+use smoltcp::iface::{EthernetInterfaceBuilder, NeighborCache};
+use smoltcp::socket::{SocketSet, TcpSocket, TcpSocketBuffer};
+use smoltcp::time::Instant;
+use smoltcp::wire::{EthernetAddress, IpAddress, IpCidr};
+
+/// A locally administered address, so it can't clash with a real card.
+const MAC_ADDRESS: [u8; 6] = [0x02, 0x00, 0x00, 0x12, 0x34, 0x56];
+
+/// Who we are.
+const IP_ADDRESS: [u8; 4] = [192, 168, 1, 50];
+const PREFIX_LEN: u8 = 24;
+
+const HTTP_PORT: u16 = 80;
+
+/// How long we sleep each time round the main loop.
+const POLL_MS: u32 = 1;
+
+/// Build the status page, headers and all.
+fn status_page(page: &mut Buffer<[u8; 512]>, uptime_ms: u32, adc: &mut Adc<pac::ADC0>) {
+    let temperature = adc::temperature(adc.read(adc::TEMPERATURE));
+    let sign = if temperature < 0 { "-" } else { "" };
+    write!(
+        page,
+        "HTTP/1.0 200 OK\r\n\
+         Content-Type: text/html\r\n\
+         Connection: close\r\n\r\n\
+         <html><head><title>LaunchPad</title></head><body>\
+         <h1>Stellaris LaunchPad</h1>\
+         <p>Up for {}s</p>\
+         <p>AIN0: {} mV<br>AIN1: {} mV<br>Temperature: {}{}.{} C</p>\
+         </body></html>\r\n",
+        uptime_ms / 1000,
+        adc::millivolts(adc.read(0)),
+        adc::millivolts(adc.read(1)),
+        sign,
+        temperature.abs() / 10,
+        temperature.abs() % 10
+    ).unwrap();
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Ssi0);
+    board.enable(sysctl::Domain::Adc0);
+
+    let _porte = gpio_port!(p, E).split(&board.power_control);
+
+    // SSI0Clk, SSI0Rx and SSI0Tx
+    config::SSI0.connect();
+    let porta = board.porta;
+    // The errata say older chips want at least 8 MHz
+    let spi = Spi::ssi0(p.SSI0, MODE_0, 10_000_000_u32.hz(), &board.clocks);
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+
+    adc::configure_pin(0);
+    adc::configure_pin(1);
+    let mut adc = Adc::adc0(p.ADC0);
+
+    writeln!(board.tx, "ENC28J60 + smoltcp demo").unwrap();
+
+    let eth = Enc28j60::new(spi, porta.pa3.into_push_pull_output(), &mut d, MAC_ADDRESS).unwrap();
+
+    let mut neighbor_storage = [None; 8];
+    let mut ip_addrs = [IpCidr::new(
+        IpAddress::v4(IP_ADDRESS[0], IP_ADDRESS[1], IP_ADDRESS[2], IP_ADDRESS[3]),
+        PREFIX_LEN,
+    )];
+    let mut iface = EthernetInterfaceBuilder::new(eth)
+        .ethernet_addr(EthernetAddress(MAC_ADDRESS))
+        .neighbor_cache(NeighborCache::new(&mut neighbor_storage[..]))
+        .ip_addrs(&mut ip_addrs[..])
+        .finalize();
+
+    let mut rx_storage = [0u8; 512];
+    let mut tx_storage = [0u8; 1024];
+    let http_socket = TcpSocket::new(
+        TcpSocketBuffer::new(&mut rx_storage[..]),
+        TcpSocketBuffer::new(&mut tx_storage[..]),
+    );
+    let mut socket_storage = [None];
+    let mut sockets = SocketSet::new(&mut socket_storage[..]);
+    let http = sockets.add(http_socket);
+
+    writeln!(
+        board.tx,
+        "Listening on {}:{}",
+        iface.ip_addrs()[0].address(),
+        HTTP_PORT
+    ).unwrap();
+
+    // This ignores the time spent doing things, so it runs a little slow
+    let mut uptime_ms = 0u32;
+    let mut link_up = false;
+    let mut page = Buffer::from_storage([0u8; 512]);
+
+    loop {
+        // Pings are answered in here
+        match iface.poll(&mut sockets, Instant::from_millis(i64::from(uptime_ms))) {
+            Ok(_) | Err(smoltcp::Error::Unrecognized) => {}
+            Err(e) => writeln!(board.tx, "Poll error: {}", e).unwrap(),
+        }
+
+        {
+            let mut socket = sockets.get::<TcpSocket>(http);
+            if !socket.is_open() {
+                socket.listen(HTTP_PORT).unwrap();
+            }
+            if socket.may_recv() {
+                // We don't care what they asked for, only that they asked
+                let got = socket.recv(|data| (data.len(), data.len())).unwrap_or(0);
+                if got > 0 && socket.can_send() {
+                    page.clear();
+                    status_page(&mut page, uptime_ms, &mut adc);
+                    socket.send_slice(page.as_bytes()).unwrap();
+                    socket.close();
+                }
+            } else if socket.may_send() {
+                socket.close();
+            }
+        }
+
+        // Check the cable every second or so
+        if uptime_ms % 1000 == 0 {
+            let now_up = iface.device_mut().is_link_up().unwrap_or(false);
+            if now_up != link_up {
+                link_up = now_up;
+                writeln!(board.tx, "Link {}", if link_up { "up" } else { "down" }).unwrap();
+            }
+        }
+
+        d.delay_ms(POLL_MS);
+        uptime_ms = uptime_ms.wrapping_add(POLL_MS);
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,441 @@
+//! Makes the LaunchPad a Firmata board, so a PC can drive its pins with
+//! pyFirmata, Johnny-Five or anything else that speaks the protocol.
+//!
+//! It's on UART0 at 57600 bps (what StandardFirmata uses, so what host
+//! libraries expect), through the debug USB port. The pins are:
+//!
+//! | Pins   | Where               | Modes                                    |
+//! |--------|---------------------|------------------------------------------|
+//! | 0-7    | PB0-PB7             | input, output, pull-up                   |
+//! | 8-10   | PF1-PF3 (the LED)   | input, output, PWM, pull-up              |
+//! | 11, 12 | PF4, PF0 (SW1, SW2) | input, pull-up                           |
+//! | 13-16  | PE3-PE0 (A0-A3)     | input, output, pull-up, analog (12 bits) |
+//!
+//! PB6 and PB7 are joined to PD0 and PD1 on the LaunchPad, so leave those
+//! alone. PWM is 20 kHz from PWM1, so `analogWrite` on pins 8 to 10 dims
+//! the red, blue and green LED. Analog readings go out every 19 ms, unless
+//! the host asks for something else.
+//!
+//! From Python, with pyFirmata:
+//!
+//! ```text
+//! board = pyfirmata.Board("/dev/ttyACM0", layout=None)
+//! board.get_pin("d:9:p").write(0.5)   # the LED goes half-blue
+//! ```
+//!
+//! ---
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+extern crate tm4c123x_hal;
+
+use demo::adc::{self, Adc};
+use demo::board::Board;
+use demo::config::{Pin, Port};
+use demo::fault;
+use demo::firmata::{self, Message, Parser, ANALOG, INPUT, NONE, OUTPUT, PULLUP, PWM};
+use demo::scheduler::{self, Task};
+use embedded_hal::prelude::*;
+use tm4c123x_hal::sysctl;
+use tm4c123x_hal::tm4c123x::{ADC0, PWM1};
+
+/// What we tell the host we are.
+const FIRMWARE: &str = "hal-demos";
+
+/// PWM period in system clocks (20 kHz).
+const PWM_PERIOD: u32 = 80_000_000 / 20_000;
+/// M1PWMn's alternate function.
+const AF_PWM: u32 = 5;
+
+/// Analog readings go out this often, until the host says otherwise.
+const DEFAULT_INTERVAL_MS: u32 = 19;
+/// Any faster and the UART can't keep up.
+const MIN_INTERVAL_MS: u32 = 10;
+
+/// What a pin can do, and the resolution of each, for the capability
+/// response.
+const DIGITAL: &[(u8, u8)] = &[(INPUT, 1), (OUTPUT, 1), (PULLUP, 1)];
+const LED: &[(u8, u8)] = &[(INPUT, 1), (OUTPUT, 1), (PWM, 8), (PULLUP, 1)];
+const SWITCH: &[(u8, u8)] = &[(INPUT, 1), (PULLUP, 1)];
+const ANALOG_IN: &[(u8, u8)] = &[(INPUT, 1), (OUTPUT, 1), (PULLUP, 1), (ANALOG, 12)];
+
+/// One of our pins, as the host sees it.
+struct PinInfo {
+    pin: Pin,
+    modes: &'static [(u8, u8)],
+    /// Its number as an analog input
+    channel: Option<u8>,
+    /// Which M1PWMn it is
+    pwm: Option<u8>,
+}
+
+macro_rules! pin {
+    ($port:ident, $bit:expr, $modes:expr, $channel:expr, $pwm:expr) => {
+        PinInfo {
+            pin: Pin {
+                port: Port::$port,
+                bit: $bit,
+            },
+            modes: $modes,
+            channel: $channel,
+            pwm: $pwm,
+        }
+    };
+}
+
+const PIN_COUNT: usize = 17;
+
+const PINS: [PinInfo; PIN_COUNT] = [
+    pin!(B, 0, DIGITAL, None, None),
+    pin!(B, 1, DIGITAL, None, None),
+    pin!(B, 2, DIGITAL, None, None),
+    pin!(B, 3, DIGITAL, None, None),
+    pin!(B, 4, DIGITAL, None, None),
+    pin!(B, 5, DIGITAL, None, None),
+    pin!(B, 6, DIGITAL, None, None),
+    pin!(B, 7, DIGITAL, None, None),
+    pin!(F, 1, LED, None, Some(5)),
+    pin!(F, 2, LED, None, Some(6)),
+    pin!(F, 3, LED, None, Some(7)),
+    pin!(F, 4, SWITCH, None, None),
+    pin!(F, 0, SWITCH, None, None),
+    pin!(E, 3, ANALOG_IN, Some(0), None),
+    pin!(E, 2, ANALOG_IN, Some(1), None),
+    pin!(E, 1, ANALOG_IN, Some(2), None),
+    pin!(E, 0, ANALOG_IN, Some(3), None),
+];
+
+/// Ports of eight pins, as Firmata counts them.
+const PORTS: usize = (PIN_COUNT + 7) / 8;
+
+/// How many analog inputs there are.
+const CHANNELS: usize = 4;
+
+struct Io {
+    board: Board,
+    adc: Adc<ADC0>,
+    pwm: PWM1,
+    modes: [u8; PIN_COUNT],
+    /// What each output or PWM pin was last set to
+    values: [u16; PIN_COUNT],
+    /// One bit for each analog input we send readings of
+    report_analog: u8,
+    /// One bit for each port we send changes of
+    report_digital: u8,
+    /// What we last sent for each port
+    last_ports: [u8; PORTS],
+    interval_ms: u32,
+    next_analog_ms: u32,
+}
+
+struct Context {
+    parser: Parser,
+    io: Io,
+}
+
+static TASKS: [Task<Context>; 2] = [
+    Task {
+        name: "host",
+        period_ms: 0,
+        run: host,
+    },
+    Task {
+        name: "report",
+        period_ms: 1,
+        run: report,
+    },
+];
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.set_baud(firmata::BAUD_RATE);
+    board.enable(sysctl::Domain::Adc0);
+    board.enable(sysctl::Domain::Pwm1);
+    Port::B.enable();
+    Port::E.enable();
+
+    // PWM1 generators 2 (output B is M1PWM5) and 3 (M1PWM6 and 7), in
+    // count-down mode. The outputs go high at LOAD and low on a compare
+    // match, so the compare value sets the off time.
+    let pwm = p.PWM1;
+    pwm._2_ctl.write(|w| unsafe { w.bits(0) });
+    pwm._3_ctl.write(|w| unsafe { w.bits(0) });
+    pwm._2_load.write(|w| unsafe { w.bits(PWM_PERIOD - 1) });
+    pwm._3_load.write(|w| unsafe { w.bits(PWM_PERIOD - 1) });
+    // ACTCMPBD = drive low, ACTLOAD = drive high
+    pwm._2_genb.write(|w| unsafe { w.bits((0x2 << 10) | (0x3 << 2)) });
+    pwm._3_genb.write(|w| unsafe { w.bits((0x2 << 10) | (0x3 << 2)) });
+    // ACTCMPAD = drive low, ACTLOAD = drive high
+    pwm._3_gena.write(|w| unsafe { w.bits((0x2 << 6) | (0x3 << 2)) });
+    pwm._2_ctl.write(|w| unsafe { w.bits(1) });
+    pwm._3_ctl.write(|w| unsafe { w.bits(1) });
+    pwm.enable.modify(|r, w| unsafe { w.bits(r.bits() | (0x7 << 5)) });
+
+    let mut io = Io {
+        board,
+        adc: Adc::adc0(p.ADC0),
+        pwm,
+        modes: [INPUT; PIN_COUNT],
+        values: [0; PIN_COUNT],
+        report_analog: 0,
+        report_digital: 0,
+        last_ports: [0; PORTS],
+        interval_ms: DEFAULT_INTERVAL_MS,
+        next_analog_ms: 0,
+    };
+    io.reset();
+    // What StandardFirmata says when it starts
+    firmata::send_version(&mut io.board.tx).unwrap();
+    firmata::send_firmware(&mut io.board.tx, FIRMWARE).unwrap();
+
+    let clocks = io.board.clocks;
+    let mut context = Context {
+        parser: Parser::new(),
+        io,
+    };
+    scheduler::start(cp.SYST, &clocks, &TASKS).run(&mut context);
+}
+
+/// Act on anything the host sent.
+fn host(c: &mut Context) {
+    while let Ok(byte) = c.io.board.rx.read() {
+        if let Some(message) = c.parser.input(byte) {
+            c.io.handle(message);
+        }
+    }
+}
+
+/// Send the inputs the host is watching.
+fn report(c: &mut Context) {
+    c.io.report_digital();
+    let now = scheduler::now();
+    if (now.wrapping_sub(c.io.next_analog_ms) as i32) >= 0 {
+        c.io.next_analog_ms = now.wrapping_add(c.io.interval_ms);
+        c.io.report_analog();
+    }
+}
+
+impl Io {
+    /// Back to how we started: outputs low, switches pulled up, analog
+    /// inputs reading, and nothing being reported.
+    fn reset(&mut self) {
+        for (number, info) in PINS.iter().enumerate() {
+            let mode = if info.channel.is_some() {
+                ANALOG
+            } else if info.modes.iter().any(|&(mode, _)| mode == OUTPUT) {
+                OUTPUT
+            } else {
+                PULLUP
+            };
+            self.set_mode(number, mode);
+        }
+        self.report_analog = 0;
+        self.report_digital = 0;
+        self.interval_ms = DEFAULT_INTERVAL_MS;
+    }
+
+    fn handle(&mut self, message: Message) {
+        match message {
+            Message::SetPinMode { pin, mode } => {
+                self.set_mode(pin as usize, mode);
+            }
+            Message::SetPin { pin, high } => self.write(pin as usize, u16::from(high)),
+            Message::DigitalPort { port, value } => {
+                for bit in 0..8 {
+                    let pin = (port as usize * 8) + bit;
+                    if pin < PINS.len() && self.modes[pin] == OUTPUT {
+                        self.write(pin, u16::from((value >> bit) & 1));
+                    }
+                }
+            }
+            Message::AnalogWrite { pin, value } => self.write(pin as usize, value),
+            Message::ReportAnalog { channel, on } if (channel as usize) < CHANNELS => {
+                set_bit(&mut self.report_analog, channel, on);
+            }
+            Message::ReportDigital { port, on } if (port as usize) < PORTS => {
+                set_bit(&mut self.report_digital, port, on);
+                // Send it straight away, whether it's changed or not
+                self.last_ports[port as usize] = !self.read_port(port as usize);
+            }
+            Message::ReportVersion => firmata::send_version(&mut self.board.tx).unwrap(),
+            Message::SystemReset => self.reset(),
+            Message::Sysex { command, data } => self.sysex(command, data),
+            _ => {}
+        }
+    }
+
+    fn sysex(&mut self, command: u8, data: &[u8]) {
+        let tx = &mut self.board.tx;
+        match command {
+            firmata::REPORT_FIRMWARE => firmata::send_firmware(tx, FIRMWARE).unwrap(),
+            firmata::CAPABILITY_QUERY => {
+                let mut reply = [0u8; PIN_COUNT * 9];
+                let mut len = 0;
+                for info in PINS.iter() {
+                    for &(mode, resolution) in info.modes {
+                        reply[len] = mode;
+                        reply[len + 1] = resolution;
+                        len += 2;
+                    }
+                    reply[len] = NONE;
+                    len += 1;
+                }
+                firmata::send_sysex(tx, firmata::CAPABILITY_RESPONSE, &reply[0..len]).unwrap();
+            }
+            firmata::ANALOG_MAPPING_QUERY => {
+                let mut reply = [NONE; PIN_COUNT];
+                for (slot, info) in reply.iter_mut().zip(PINS.iter()) {
+                    if let Some(channel) = info.channel {
+                        *slot = channel;
+                    }
+                }
+                firmata::send_sysex(tx, firmata::ANALOG_MAPPING_RESPONSE, &reply).unwrap();
+            }
+            firmata::PIN_STATE_QUERY if !data.is_empty() && (data[0] as usize) < PINS.len() => {
+                let pin = data[0] as usize;
+                let value = self.values[pin];
+                let reply = [
+                    data[0],
+                    self.modes[pin],
+                    (value & 0x7F) as u8,
+                    (value >> 7) as u8,
+                ];
+                firmata::send_sysex(tx, firmata::PIN_STATE_RESPONSE, &reply).unwrap();
+            }
+            firmata::SAMPLING_INTERVAL if data.len() >= 2 => {
+                let ms = u32::from(data[0]) | (u32::from(data[1]) << 7);
+                self.interval_ms = ms.max(MIN_INTERVAL_MS);
+            }
+            _ => {}
+        }
+    }
+
+    /// Change a pin's mode, if it has that mode.
+    fn set_mode(&mut self, number: usize, mode: u8) {
+        let info = match PINS.get(number) {
+            Some(info) => info,
+            None => return,
+        };
+        if !info.modes.iter().any(|&(m, _)| m == mode) {
+            return;
+        }
+        let pin = info.pin;
+        let port = pin.port.registers();
+        let mask = 1 << pin.bit;
+        // Undo whatever the last mode did
+        port.amsel.modify(|r, w| unsafe { w.bits(r.bits() & !mask) });
+        port.pur.modify(|r, w| unsafe { w.bits(r.bits() & !mask) });
+        match (mode, info.channel, info.pwm) {
+            (OUTPUT, _, _) => pin.into_output(),
+            (PWM, _, Some(_)) => pin.into_af(AF_PWM),
+            (ANALOG, Some(channel), _) => adc::configure_pin(channel),
+            (PULLUP, _, _) => {
+                pin.into_input();
+                port.pur.modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+            }
+            _ => pin.into_input(),
+        }
+        self.modes[number] = mode;
+        self.values[number] = 0;
+        if mode == OUTPUT {
+            pin.set(false);
+        } else if mode == PWM {
+            self.set_duty(number, 0);
+        }
+    }
+
+    /// Drive an output pin, or set a PWM pin's duty cycle (0 to 255).
+    fn write(&mut self, number: usize, value: u16) {
+        match self.modes.get(number) {
+            Some(&OUTPUT) => PINS[number].pin.set(value != 0),
+            Some(&PWM) => self.set_duty(number, value.min(255)),
+            _ => return,
+        }
+        self.values[number] = value;
+    }
+
+    fn set_duty(&mut self, number: usize, value: u16) {
+        let duty = ((u32::from(value) * PWM_PERIOD) / 255).min(PWM_PERIOD - 1);
+        let compare = PWM_PERIOD - 1 - duty;
+        match PINS[number].pwm {
+            Some(5) => self.pwm._2_cmpb.write(|w| unsafe { w.bits(compare) }),
+            Some(6) => self.pwm._3_cmpa.write(|w| unsafe { w.bits(compare) }),
+            Some(7) => self.pwm._3_cmpb.write(|w| unsafe { w.bits(compare) }),
+            _ => {}
+        }
+    }
+
+    /// The inputs in a port, a bit for each pin. Pins that aren't inputs
+    /// read as zero.
+    fn read_port(&self, port: usize) -> u8 {
+        let mut value = 0;
+        for bit in 0..8 {
+            let number = (port * 8) + bit;
+            if number < PINS.len()
+                && (self.modes[number] == INPUT || self.modes[number] == PULLUP)
+                && PINS[number].pin.is_high()
+            {
+                value |= 1 << bit;
+            }
+        }
+        value
+    }
+
+    fn report_digital(&mut self) {
+        for port in 0..PORTS {
+            if self.report_digital & (1 << port) == 0 {
+                continue;
+            }
+            let value = self.read_port(port);
+            if value != self.last_ports[port] {
+                self.last_ports[port] = value;
+                firmata::send_digital_port(&mut self.board.tx, port as u8, value).unwrap();
+            }
+        }
+    }
+
+    fn report_analog(&mut self) {
+        for (number, info) in PINS.iter().enumerate() {
+            let channel = match info.channel {
+                Some(channel) => channel,
+                None => continue,
+            };
+            if self.report_analog & (1 << channel) == 0 || self.modes[number] != ANALOG {
+                continue;
+            }
+            let sample = self.adc.read(channel);
+            firmata::send_analog(&mut self.board.tx, channel, sample).unwrap();
+        }
+    }
+}
+
+fn set_bit(bits: &mut u8, bit: u8, on: bool) {
+    if on {
+        *bits |= 1 << bit;
+    } else {
+        *bits &= !(1 << bit);
+    }
+}
+
+exception!(SysTick, scheduler::tick);
+
+// Print what went wrong on UART0 and flash the red LED
+exception!(HardFault, fault::hard_fault);
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
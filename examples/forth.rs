@@ -0,0 +1,230 @@
+//! An interactive Forth, for poking at the hardware.
+//!
+//! Talk to it on UART0 (115200 bps). As well as the standard words (see
+//! `demo::forth`), there are some for the board:
+//!
+//! * `led ( n -- )` - the RGB LED, with red, blue and green as bits 0 to 2
+//! * `sw1 ( -- flag )` - true while SW1 is pressed
+//! * `ticks ( -- n )` - a free-running count at 80 MHz, from Timer1
+//! * `ms ( n -- )` - wait a while
+//! * `cls ( -- )` - clear the VGA screen
+//! * `plot ( x y -- )` and `unplot ( x y -- )` - set and clear pixels
+//! * `line ( x1 y1 x2 y2 -- )` - draw a line
+//!
+//! The VGA output is the same as `hello_vga`: HSYNC on PB6, VSYNC on PC4
+//! and green on PB7. For example:
+//!
+//! ```text
+//! : flash 8 0 do i 7 and led 100 ms loop 0 led ;
+//! flash
+//! : box 0 0 399 0 line 399 0 399 299 line 399 299 0 299 line 0 299 0 0 line ;
+//! cls box
+//! $400253FC @ .
+//! ```
+//!
+//! Ctrl-C stops a runaway word.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use demo::board::Board;
+use demo::console::Console;
+use demo::forth::{self, Forth, Stack};
+use demo::graphics::{Canvas, Colour};
+use demo::vga;
+use embedded_hal::prelude::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::sysctl;
+
+/// The dictionary, in 32-bit cells.
+const DICTIONARY_CELLS: usize = 2048;
+
+/// Timer1 ticks per millisecond.
+const TICKS_PER_MS: u32 = 80_000;
+
+/// The RGB LED is PF1 to PF3.
+const LED_MASK: u32 = 0b1110;
+
+/// SW1 is PF4, pulled up.
+const SW1: u32 = 1 << 4;
+
+// GPTMCFG, GPTMTAMR, GPTMCTL
+const CFG_32_BIT: u32 = 0x0;
+const TAMR_PERIODIC: u32 = 0x2;
+const CTL_TAEN: u32 = 1 << 0;
+
+const CTRL_C: u8 = 0x03;
+
+/// The longest line you can type.
+const MAX_LINE: usize = 80;
+
+static mut DICTIONARY: [u32; DICTIONARY_CELLS] = [0; DICTIONARY_CELLS];
+
+fn led(s: &mut Stack) -> Result<(), forth::Error> {
+    let value = (s.pop()? as u32) << 1;
+    let gpio = unsafe { &*tm4c123x::GPIO_PORTF::ptr() };
+    gpio.data
+        .modify(|r, w| unsafe { w.bits((r.bits() & !LED_MASK) | (value & LED_MASK)) });
+    Ok(())
+}
+
+fn sw1(s: &mut Stack) -> Result<(), forth::Error> {
+    let gpio = unsafe { &*tm4c123x::GPIO_PORTF::ptr() };
+    let pressed = gpio.data.read().bits() & SW1 == 0;
+    s.push(if pressed { -1 } else { 0 })
+}
+
+/// Timer1A counts down, so turn it round.
+fn now() -> u32 {
+    let timer = unsafe { &*tm4c123x::TIMER1::ptr() };
+    !timer.tav.read().bits()
+}
+
+fn ticks(s: &mut Stack) -> Result<(), forth::Error> {
+    s.push(now() as i32)
+}
+
+fn ms(s: &mut Stack) -> Result<(), forth::Error> {
+    let delay = s.pop()?.max(0) as u32;
+    for _ in 0..delay {
+        let start = now();
+        while now().wrapping_sub(start) < TICKS_PER_MS {}
+    }
+    Ok(())
+}
+
+fn cls(_s: &mut Stack) -> Result<(), forth::Error> {
+    vga::framebuffer().clear(Colour::BLACK);
+    Ok(())
+}
+
+fn plot(s: &mut Stack) -> Result<(), forth::Error> {
+    draw_point(s, Colour::WHITE)
+}
+
+fn unplot(s: &mut Stack) -> Result<(), forth::Error> {
+    draw_point(s, Colour::BLACK)
+}
+
+fn draw_point(s: &mut Stack, colour: Colour) -> Result<(), forth::Error> {
+    let y = s.pop()?;
+    let x = s.pop()?;
+    if x >= 0 && y >= 0 {
+        vga::framebuffer().draw_point(x as usize, y as usize, colour);
+    }
+    Ok(())
+}
+
+fn line(s: &mut Stack) -> Result<(), forth::Error> {
+    let y2 = s.pop()? as isize;
+    let x2 = s.pop()? as isize;
+    let y1 = s.pop()? as isize;
+    let x1 = s.pop()? as isize;
+    vga::framebuffer().draw_line((x1, y1), (x2, y2), Colour::WHITE);
+    Ok(())
+}
+
+/// Lets Ctrl-C out of an endless loop.
+fn interrupted() -> bool {
+    Console.read_byte() == Some(CTRL_C)
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Timer1);
+    board.enable(sysctl::Domain::Ssi2);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+
+    // `board` has set up the LED and SW1 pins, and the natives drive them
+    // through the data register directly
+
+    // Timer1A, 32-bit periodic, free running from 0xFFFF_FFFF
+    let timer = p.TIMER1;
+    timer.ctl.write(|w| unsafe { w.bits(0) });
+    timer.cfg.write(|w| unsafe { w.bits(CFG_32_BIT) });
+    timer.tamr.write(|w| unsafe { w.bits(TAMR_PERIODIC) });
+    timer.tailr.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+    timer.ctl.write(|w| unsafe { w.bits(CTL_TAEN) });
+
+    let mut f = Forth::new(unsafe { &mut DICTIONARY }).unwrap();
+    f.define("led", led).unwrap();
+    f.define("sw1", sw1).unwrap();
+    f.define("ticks", ticks).unwrap();
+    f.define("ms", ms).unwrap();
+    f.define("cls", cls).unwrap();
+    f.define("plot", plot).unwrap();
+    f.define("unplot", unplot).unwrap();
+    f.define("line", line).unwrap();
+    f.stop = Some(interrupted);
+
+    writeln!(board.tx, "Forth - {} cells free", f.free()).unwrap();
+
+    let mut buffer = [0u8; MAX_LINE];
+    let mut len = 0;
+    loop {
+        let byte = match board.rx.read() {
+            Ok(byte) => byte,
+            Err(_) => continue,
+        };
+        match byte {
+            b'\r' => {
+                write!(Console, " ").unwrap();
+                // We only let ASCII in
+                let text = core::str::from_utf8(&buffer[0..len]).unwrap();
+                match f.interpret(text, &mut Console) {
+                    Ok(_) if f.is_compiling() => writeln!(Console, " compiled").unwrap(),
+                    Ok(_) => writeln!(Console, " ok").unwrap(),
+                    Err(e) => writeln!(Console, " ? {:?}", e).unwrap(),
+                }
+                len = 0;
+            }
+            0x08 | 0x7F if len > 0 => {
+                len -= 1;
+                write!(Console, "\x08 \x08").unwrap();
+            }
+            0x20...0x7E if len < MAX_LINE => {
+                buffer[len] = byte;
+                len += 1;
+                Console.write_byte(byte);
+            }
+            _ => {}
+        }
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,291 @@
+//! A bench frequency counter, with a big readout on the VGA screen.
+//!
+//! The input is PC6 (WT1CCP0), 3.3V logic, up to about 20 MHz. Wide Timer
+//! 1A counts its rising edges, and every gate period Timer1A interrupts and
+//! we see how far the count has gone. So there's no dead time between
+//! readings and the resolution is one count per gate - 10 Hz with a 100ms
+//! gate, 0.1 Hz with 10s. The counter stops when it reaches its match
+//! value, so once it's half way there we set it back to zero, which might
+//! lose an edge every few minutes.
+//!
+//! The VGA output is the same as `hello_vga` (HSYNC on PB6, VSYNC on PC4
+//! and green on PB7). The console is on UART0 at 115200 bps. Commands:
+//!
+//! * `gate <100|1000|10000>` - set the gate time, in milliseconds
+//! * `reset` - clear the min/max statistics
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use demo::board::Board;
+use demo::config::{Pin, Port};
+use demo::console::Console;
+use demo::font;
+use demo::graphics::{Canvas, Colour, VGA_WIDTH};
+use demo::status_bar;
+use demo::text::Buffer;
+use demo::vga;
+use embedded_hal::prelude::*;
+use menu::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::sysctl;
+
+/// The signal to count.
+const INPUT: Pin = Pin {
+    port: Port::C,
+    bit: 6,
+};
+
+/// Timer clocks per millisecond.
+const CLOCKS_PER_MS: u32 = 80_000;
+
+/// The gate times we offer, in milliseconds.
+const GATES_MS: [usize; 3] = [100, 1000, 10_000];
+
+/// How big the readout is, and where it goes.
+const SCALE: usize = 3;
+const READOUT_Y: usize = 80;
+/// The statistics go under the readout.
+const STATS_Y: usize = READOUT_Y + (font::HEIGHT * SCALE) + 32;
+
+// GPTMCFG
+const CFG_SPLIT: u32 = 0x4;
+
+// GPTMTAMR: capture, edge-count, counting up
+const TAMR_CAPTURE: u32 = 0x3;
+const TAMR_CDIR: u32 = 1 << 4;
+
+// GPTMCTL, rising edges
+const CTL_TAEN: u32 = 1 << 0;
+
+/// When we put the edge counter back to zero.
+const HALF_WAY: u32 = 0x8000_0000;
+
+/// The edge count at the last gate.
+static LAST_COUNT: AtomicUsize = AtomicUsize::new(0);
+/// Edges in the last gate period.
+static EDGES: AtomicUsize = AtomicUsize::new(0);
+/// `EDGES` has a new value.
+static READY: AtomicBool = AtomicBool::new(false);
+
+static GATE_MS: AtomicUsize = AtomicUsize::new(1000);
+static RESET_STATS: AtomicBool = AtomicBool::new(true);
+
+const GATE_ITEM: Item = Item {
+    item_type: ItemType::Callback(gate_callback),
+    command: "gate",
+    help: Some("<100|1000|10000> - set the gate time in ms"),
+};
+
+const RESET_ITEM: Item = Item {
+    item_type: ItemType::Callback(reset_callback),
+    command: "reset",
+    help: Some("clear the min/max statistics"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[&GATE_ITEM, &RESET_ITEM],
+    entry: None,
+    exit: None,
+};
+
+fn gate_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let gate = input
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse::<usize>().ok());
+    match gate {
+        Some(ms) if GATES_MS.contains(&ms) => {
+            GATE_MS.store(ms, Ordering::Relaxed);
+            set_gate(ms as u32);
+            RESET_STATS.store(true, Ordering::Relaxed);
+        }
+        _ => writeln!(Console, "Usage: gate <100|1000|10000>").unwrap(),
+    }
+}
+
+fn reset_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    RESET_STATS.store(true, Ordering::Relaxed);
+}
+
+/// Restart Timer1A with a new gate period. 10 seconds is 800 million
+/// clocks, which still fits in 32 bits.
+fn set_gate(ms: u32) {
+    let timer = unsafe { &*tm4c123x_hal::tm4c123x::TIMER1::ptr() };
+    timer.ctl.modify(|_, w| w.taen().clear_bit());
+    timer.tailr.write(|w| unsafe { w.bits((ms * CLOCKS_PER_MS) - 1) });
+    timer.tav.write(|w| unsafe { w.bits((ms * CLOCKS_PER_MS) - 1) });
+    // Start counting the new gate from here
+    let counter = unsafe { &*tm4c123x_hal::tm4c123x::WTIMER1::ptr() };
+    LAST_COUNT.store(counter.tar.read().bits() as usize, Ordering::Relaxed);
+    READY.store(false, Ordering::Relaxed);
+    timer.ctl.modify(|_, w| w.taen().set_bit());
+}
+
+/// Write a frequency in millihertz as Hz, with as many decimal places as
+/// the gate time gives us.
+fn write_hz<W>(w: &mut W, millihertz: u64, gate_ms: usize) -> core::fmt::Result
+where
+    W: Write,
+{
+    let hz = millihertz / 1000;
+    match gate_ms {
+        10_000 => write!(w, "{}.{} Hz", hz, (millihertz % 1000) / 100),
+        _ => write!(w, "{} Hz", hz),
+    }
+}
+
+/// Draw a line of text centred across the screen, blanking the rest of the
+/// line so shorter text doesn't leave bits of longer text behind.
+fn draw_centred<C>(canvas: &mut C, y: usize, s: &str, scale: usize)
+where
+    C: Canvas,
+{
+    let char_width = font::WIDTH * scale;
+    let width = (s.len() * char_width).min(VGA_WIDTH);
+    let x = (VGA_WIDTH - width) / 2;
+    canvas.fill_rect(0, y, VGA_WIDTH, font::HEIGHT * scale, Colour::BLACK);
+    canvas.draw_str_scaled(x, y, s, scale, Colour::WHITE, Colour::BLACK);
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::Timer1);
+    board.enable(sysctl::Domain::WideTimer1);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    // Wide Timer 1A, as a free-running 32-bit edge counter
+    INPUT.into_af(7);
+    let counter = p.WTIMER1;
+    counter.ctl.write(|w| unsafe { w.bits(0) });
+    counter.cfg.write(|w| unsafe { w.bits(CFG_SPLIT) });
+    counter
+        .tamr
+        .write(|w| unsafe { w.bits(TAMR_CAPTURE | TAMR_CDIR) });
+    counter.tamatchr.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+    counter.ctl.write(|w| unsafe { w.bits(CTL_TAEN) });
+
+    // Timer1A, 32-bit periodic, interrupting at the end of each gate
+    let timer = p.TIMER1;
+    timer.ctl.modify(|_, w| w.taen().clear_bit());
+    timer.cfg.write(|w| unsafe { w.bits(0) });
+    timer.tamr.modify(|_, w| w.tamr().period());
+    timer.imr.modify(|_, w| w.tatoim().set_bit());
+    timer.icr.write(|w| w.tatocint().set_bit());
+    set_gate(GATE_MS.load(Ordering::Relaxed) as u32);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+    // Below the VGA timers, so the picture stays put
+    unsafe { nvic.set_priority(tm4c123x_hal::Interrupt::TIMER1A, 0x40) };
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER1A);
+
+    let fb = vga::framebuffer();
+    fb.clear(Colour::BLACK);
+    status_bar::draw(fb, "Frequency counter", "PC6");
+    draw_centred(fb, READOUT_Y, "----", SCALE);
+
+    writeln!(board.tx, "Frequency counter. Input on PC6. Try 'gate 100'.").unwrap();
+
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+
+    let mut min = u64::max_value();
+    let mut max = 0;
+
+    loop {
+        while let Ok(ch) = board.rx.read() {
+            r.input_byte(ch);
+        }
+        if !READY.swap(false, Ordering::Relaxed) {
+            continue;
+        }
+
+        let gate_ms = GATE_MS.load(Ordering::Relaxed);
+        let edges = EDGES.load(Ordering::Relaxed) as u64;
+        let millihertz = (edges * 1_000_000) / gate_ms as u64;
+        if RESET_STATS.swap(false, Ordering::Relaxed) {
+            min = millihertz;
+            max = millihertz;
+        }
+        min = min.min(millihertz);
+        max = max.max(millihertz);
+
+        let fb = vga::framebuffer();
+        let mut line = Buffer::new();
+        write_hz(&mut line, millihertz, gate_ms).unwrap();
+        draw_centred(fb, READOUT_Y, line.as_str(), SCALE);
+
+        let mut line = Buffer::new();
+        write!(line, "Min ").unwrap();
+        write_hz(&mut line, min, gate_ms).unwrap();
+        draw_centred(fb, STATS_Y, line.as_str(), 1);
+
+        let mut line = Buffer::new();
+        write!(line, "Max ").unwrap();
+        write_hz(&mut line, max, gate_ms).unwrap();
+        draw_centred(fb, STATS_Y + font::HEIGHT, line.as_str(), 1);
+
+        let mut line = Buffer::new();
+        write!(line, "Gate {} ms", gate_ms).unwrap();
+        status_bar::draw(fb, "Frequency counter", line.as_str());
+    }
+}
+
+interrupt!(TIMER1A, timer1a_isr);
+
+fn timer1a_isr() {
+    let timer = unsafe { &*tm4c123x_hal::tm4c123x::TIMER1::ptr() };
+    timer.icr.write(|w| w.tatocint().set_bit());
+    let counter = unsafe { &*tm4c123x_hal::tm4c123x::WTIMER1::ptr() };
+    let count = counter.tar.read().bits();
+    let last = LAST_COUNT.load(Ordering::Relaxed) as u32;
+    EDGES.store(count.wrapping_sub(last) as usize, Ordering::Relaxed);
+    if count >= HALF_WAY {
+        counter.ctl.write(|w| unsafe { w.bits(0) });
+        counter.tav.write(|w| unsafe { w.bits(0) });
+        counter.ctl.write(|w| unsafe { w.bits(CTL_TAEN) });
+        LAST_COUNT.store(0, Ordering::Relaxed);
+    } else {
+        LAST_COUNT.store(count as usize, Ordering::Relaxed);
+    }
+    READY.store(true, Ordering::Relaxed);
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
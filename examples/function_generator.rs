@@ -0,0 +1,241 @@
+//! A function generator: sine, square, triangle and sawtooth waves, from
+//! 1 Hz to 10 kHz.
+//!
+//! The output is PWM on T1CCP0 - PB4, or PD2 on the TM4C1294 - at 312.5 kHz
+//! (468.75 kHz on the TM4C1294) with 8-bit resolution.
+//! Timer2A interrupts at 40 kHz, takes the next sample from a
+//! `demo::wavetable::Oscillator` and sets the duty cycle. Put it through a
+//! low-pass filter - 1k and 10nF, twice over for a cleaner signal - and
+//! you get 0 to 3.3V, centred on 1.65V at full amplitude.
+//!
+//! The console is on UART0 at 115200 bps. Commands:
+//!
+//! * `wave <sine|square|triangle|saw>` - set the waveform
+//! * `freq <hz>` - set the frequency
+//! * `amp <percent>` - set the amplitude
+//! * `status` - show the settings
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+#[cfg(feature = "tm4c123")]
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+#[cfg(feature = "tm4c129")]
+#[macro_use(interrupt)]
+extern crate tm4c129x;
+
+use core::fmt::Write;
+use cortex_m::interrupt;
+use demo::board::SYSCLK_HZ;
+use demo::config::{Pin, Port};
+use demo::console::Console;
+use demo::hal;
+use demo::pac;
+use demo::wavetable::{Oscillator, Waveform};
+use embedded_hal::prelude::*;
+use hal::sysctl;
+use menu::*;
+use rt::ExceptionFrame;
+
+/// T1CCP0, and its alternate function.
+#[cfg(feature = "tm4c123")]
+const OUTPUT: Pin = Pin { port: Port::B, bit: 4 };
+#[cfg(feature = "tm4c123")]
+const OUTPUT_FUNCTION: u32 = 7;
+#[cfg(feature = "tm4c129")]
+const OUTPUT: Pin = Pin { port: Port::D, bit: 2 };
+#[cfg(feature = "tm4c129")]
+const OUTPUT_FUNCTION: u32 = 3;
+
+/// How often Timer2A asks for a sample.
+const SAMPLE_RATE_HZ: u32 = 40_000;
+
+/// Anything faster is a handful of samples per cycle.
+const MAX_FREQUENCY_HZ: u32 = 10_000;
+
+/// The PWM counts from here down to zero, so this is full scale.
+const PWM_TOP: u32 = 255;
+
+// GPTMCFG, GPTMTnMR
+const CFG_16_BIT: u32 = 0x4;
+/// Periodic, PWM, and only change the match value at the end of a cycle
+const MR_PWM: u32 = 0x2 | (1 << 3) | (1 << 10);
+
+static mut OSCILLATOR: Option<Oscillator> = None;
+
+const WAVE_ITEM: Item = Item {
+    item_type: ItemType::Callback(wave_callback),
+    command: "wave",
+    help: Some("<sine|square|triangle|saw> - set the waveform"),
+};
+
+const FREQ_ITEM: Item = Item {
+    item_type: ItemType::Callback(freq_callback),
+    command: "freq",
+    help: Some("<hz> - set the frequency"),
+};
+
+const AMP_ITEM: Item = Item {
+    item_type: ItemType::Callback(amp_callback),
+    command: "amp",
+    help: Some("<percent> - set the amplitude"),
+};
+
+const STATUS_ITEM: Item = Item {
+    item_type: ItemType::Callback(status_callback),
+    command: "status",
+    help: Some("show the settings"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[&WAVE_ITEM, &FREQ_ITEM, &AMP_ITEM, &STATUS_ITEM],
+    entry: None,
+    exit: None,
+};
+
+/// Parse the first argument after the command.
+fn argument<T>(input: &str) -> Option<T>
+where
+    T: core::str::FromStr,
+{
+    input.split_whitespace().nth(1).and_then(|s| s.parse::<T>().ok())
+}
+
+/// Run a closure against the oscillator with the sample interrupt masked.
+fn with_oscillator<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut Oscillator) -> R,
+{
+    interrupt::free(|_| f(unsafe { OSCILLATOR.as_mut().unwrap() }))
+}
+
+fn wave_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let waveform = match input.split_whitespace().nth(1) {
+        Some("sine") => Waveform::Sine,
+        Some("square") => Waveform::Square,
+        Some("triangle") => Waveform::Triangle,
+        Some("saw") => Waveform::Sawtooth,
+        _ => {
+            writeln!(Console, "Usage: wave <sine|square|triangle|saw>").unwrap();
+            return;
+        }
+    };
+    with_oscillator(|o| o.waveform = waveform);
+}
+
+fn freq_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match argument::<u32>(input) {
+        Some(hz @ 1...MAX_FREQUENCY_HZ) => with_oscillator(|o| o.set_frequency(hz)),
+        _ => writeln!(Console, "Usage: freq <1..{}>", MAX_FREQUENCY_HZ).unwrap(),
+    }
+}
+
+fn amp_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match argument::<u32>(input) {
+        Some(percent @ 0...100) => {
+            let volume = ((percent * 255) / 100) as u8;
+            with_oscillator(|o| o.volume = volume)
+        }
+        _ => writeln!(Console, "Usage: amp <0..100>").unwrap(),
+    }
+}
+
+fn status_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    let (waveform, hz, volume) = with_oscillator(|o| (o.waveform, o.frequency(), o.volume));
+    writeln!(
+        Console,
+        "{:?} at {} Hz, {}%",
+        waveform,
+        hz,
+        (u32::from(volume) * 100) / 255
+    ).unwrap();
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Timer1);
+    board.enable(sysctl::Domain::Timer2);
+
+    let mut oscillator = Oscillator::new(Waveform::Sine, SAMPLE_RATE_HZ);
+    oscillator.set_frequency(1000);
+    oscillator.volume = 255;
+    unsafe { OSCILLATOR = Some(oscillator) };
+
+    // T1CCP0. The counter reloads at PWM_TOP, taking the output high, and
+    // it goes low again at the match value.
+    OUTPUT.into_af(OUTPUT_FUNCTION);
+    let pwm = p.TIMER1;
+    pwm.ctl.write(|w| unsafe { w.bits(0) });
+    pwm.cfg.write(|w| unsafe { w.bits(CFG_16_BIT) });
+    pwm.tamr.write(|w| unsafe { w.bits(MR_PWM) });
+    pwm.tailr.write(|w| unsafe { w.bits(PWM_TOP) });
+    pwm.tamatchr.write(|w| unsafe { w.bits(PWM_TOP / 2) });
+    pwm.ctl.modify(|_, w| w.taen().set_bit());
+
+    // Timer2A, 32-bit periodic, interrupting on timeout
+    let timer = p.TIMER2;
+    timer.ctl.modify(|_, w| w.taen().clear_bit());
+    timer.cfg.write(|w| unsafe { w.bits(0) });
+    timer.tamr.modify(|_, w| w.tamr().period());
+    timer
+        .tailr
+        .write(|w| unsafe { w.bits(SYSCLK_HZ / SAMPLE_RATE_HZ - 1) });
+    timer.imr.modify(|_, w| w.tatoim().set_bit());
+    timer.icr.write(|w| w.tatocint().set_bit());
+    timer.ctl.modify(|_, w| w.taen().set_bit());
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(hal::Interrupt::TIMER2A);
+
+    writeln!(board.tx, "Function generator. Try 'wave triangle'.").unwrap();
+
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+
+    loop {
+        if let Ok(ch) = board.rx.read() {
+            r.input_byte(ch);
+        }
+    }
+}
+
+interrupt!(TIMER2A, timer2a_isr);
+
+fn timer2a_isr() {
+    let timer = unsafe { &*pac::TIMER2::ptr() };
+    timer.icr.write(|w| w.tatocint().set_bit());
+    if let Some(oscillator) = unsafe { OSCILLATOR.as_mut() } {
+        // -32768..32767 to 0..255, and the output is high for
+        // PWM_TOP - match clocks
+        let level = ((i32::from(oscillator.next_sample()) + 32768) >> 8) as u32;
+        let pwm = unsafe { &*pac::TIMER1::ptr() };
+        pwm.tamatchr.write(|w| unsafe { w.bits(PWM_TOP - level) });
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,254 @@
+//! Shows where a serial GPS module thinks we are.
+//!
+//! The GPS TX goes to PB0 (U1Rx), and its RX to PB1 (U1Tx) although we
+//! never send it anything. Most modules (like the u-blox NEO-6M boards)
+//! default to 9600 bps. The VGA output is the same as `hello_vga`: HSYNC on
+//! PB6, VSYNC on PC4 and green on PB7.
+//!
+//! We pick the GGA and RMC sentences out of the stream and lay out the fix
+//! status, position, altitude, speed and satellite count on the VGA
+//! screen, with the UTC time in the status bar. A summary of each fix goes
+//! to UART0 (115200 bps) as well.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use demo::board::Board;
+use demo::font;
+use demo::graphics::{Canvas, Colour};
+use demo::nmea::{self, Gga, Rmc, Sentence};
+use demo::status_bar;
+use demo::text::Buffer;
+use demo::vga;
+use embedded_hal::prelude::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::gpio::GpioExt;
+use tm4c123x_hal::serial::{NewlineMode, Serial};
+use tm4c123x_hal::sysctl;
+use tm4c123x_hal::time::U32Ext;
+
+/// The GPS baud rate.
+const GPS_BAUD: u32 = 9600;
+
+/// Where the labels and values go.
+const LEFT: usize = 2 * font::WIDTH;
+const VALUE_X: usize = LEFT + (12 * font::WIDTH);
+const FIRST_ROW: usize = status_bar::HEIGHT + font::HEIGHT;
+const ROW_HEIGHT: usize = font::HEIGHT + (font::HEIGHT / 2);
+
+/// Values are padded to this many characters, to rub out the old ones.
+const VALUE_WIDTH: usize = 30;
+
+const LABELS: [&str; 8] = [
+    "Fix", "Satellites", "Latitude", "Longitude", "Altitude", "Speed", "Course", "Date",
+];
+
+/// Print a fixed point number with the given number of decimal places.
+fn write_fixed<W: Write>(w: &mut W, value: i32, places: u32) {
+    let scale = 10i32.pow(places);
+    let sign = if value < 0 { "-" } else { "" };
+    let value = value.abs();
+    write!(
+        w,
+        "{}{}.{:0width$}",
+        sign,
+        value / scale,
+        value % scale,
+        width = places as usize
+    ).unwrap();
+}
+
+/// Write a value next to its label, padded to rub out what was there.
+fn draw_value<C: Canvas>(canvas: &mut C, row: usize, text: &Buffer) {
+    let y = FIRST_ROW + (row * ROW_HEIGHT);
+    canvas.draw_str(VALUE_X, y, text.as_str(), Colour::WHITE, Colour::BLACK);
+    for col in text.len()..VALUE_WIDTH {
+        canvas.draw_char(
+            VALUE_X + (col * font::WIDTH),
+            y,
+            b' ',
+            Colour::WHITE,
+            Colour::BLACK,
+        );
+    }
+}
+
+fn draw_labels<C: Canvas>(canvas: &mut C) {
+    for (row, label) in LABELS.iter().enumerate() {
+        let y = FIRST_ROW + (row * ROW_HEIGHT);
+        canvas.draw_str(LEFT, y, label, Colour::WHITE, Colour::BLACK);
+    }
+}
+
+/// Fill in the values we get from GGA.
+fn draw_gga<C: Canvas>(canvas: &mut C, gga: &Gga) {
+    let mut text = Buffer::new();
+    match gga.quality {
+        0 => write!(text, "None"),
+        1 => write!(text, "GPS"),
+        2 => write!(text, "Differential GPS"),
+        q => write!(text, "Type {}", q),
+    }.unwrap();
+    if let Some(hdop) = gga.hdop {
+        write!(text, ", HDOP ").unwrap();
+        write_fixed(&mut text, hdop, 2);
+    }
+    draw_value(canvas, 0, &text);
+
+    let mut text = Buffer::new();
+    write!(text, "{}", gga.satellites).unwrap();
+    draw_value(canvas, 1, &text);
+
+    let (mut lat, mut lon) = (Buffer::new(), Buffer::new());
+    if let Some(pos) = gga.position {
+        write_fixed(&mut lat, pos.latitude.abs(), 6);
+        write!(lat, " {}", if pos.latitude < 0 { 'S' } else { 'N' }).unwrap();
+        write_fixed(&mut lon, pos.longitude.abs(), 6);
+        write!(lon, " {}", if pos.longitude < 0 { 'W' } else { 'E' }).unwrap();
+    } else {
+        write!(lat, "-").unwrap();
+        write!(lon, "-").unwrap();
+    }
+    draw_value(canvas, 2, &lat);
+    draw_value(canvas, 3, &lon);
+
+    let mut text = Buffer::new();
+    match gga.altitude {
+        Some(alt) => {
+            write_fixed(&mut text, alt, 1);
+            write!(text, " m").unwrap();
+        }
+        None => write!(text, "-").unwrap(),
+    }
+    draw_value(canvas, 4, &text);
+}
+
+/// Fill in the values we get from RMC.
+fn draw_rmc<C: Canvas>(canvas: &mut C, rmc: &Rmc) {
+    let mut text = Buffer::new();
+    match rmc.speed {
+        Some(knots) => {
+            // Thousandths of a knot to tenths of a km/h
+            write_fixed(&mut text, (knots * 1852) / 100_000, 1);
+            write!(text, " km/h").unwrap();
+        }
+        None => write!(text, "-").unwrap(),
+    }
+    draw_value(canvas, 5, &text);
+
+    let mut text = Buffer::new();
+    match rmc.course {
+        Some(course) => {
+            write_fixed(&mut text, course / 10, 1);
+            write!(text, " deg").unwrap();
+        }
+        None => write!(text, "-").unwrap(),
+    }
+    draw_value(canvas, 6, &text);
+
+    let mut text = Buffer::new();
+    match rmc.datetime {
+        Some(dt) => write!(text, "{:04}-{:02}-{:02}", dt.year, dt.month, dt.day).unwrap(),
+        None => write!(text, "-").unwrap(),
+    }
+    draw_value(canvas, 7, &text);
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+
+    let mut portb = p.GPIO_PORTB.split(&board.power_control);
+
+    // The GPS
+    let uart1 = Serial::uart1(
+        p.UART1,
+        portb.pb1.into_af1(&mut portb.control),
+        portb.pb0.into_af1(&mut portb.control),
+        (),
+        (),
+        GPS_BAUD.bps(),
+        NewlineMode::Binary,
+        &board.clocks,
+        &board.power_control,
+    );
+    let (_gps_tx, mut gps_rx) = uart1.split();
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+
+    writeln!(board.tx, "NMEA GPS receiver").unwrap();
+
+    let fb = vga::framebuffer();
+    fb.clear(Colour::BLACK);
+    status_bar::draw(fb, "GPS", "Waiting for data");
+    draw_labels(fb);
+
+    let mut parser = nmea::Parser::new();
+
+    loop {
+        let byte = match gps_rx.read() {
+            Ok(byte) => byte,
+            Err(_) => continue,
+        };
+        // Redrawing can take long enough for the UART FIFO to overflow, so
+        // we may lose the sentence after this one. The checksum catches
+        // that, and there will be a fresh fix along in a second.
+        match parser.input(byte) {
+            Some(Sentence::Gga(gga)) => {
+                draw_gga(fb, &gga);
+                let mut clock = Buffer::new();
+                match gga.time {
+                    Some(time) => write!(clock, "{} UTC", time).unwrap(),
+                    None => write!(clock, "No time").unwrap(),
+                }
+                status_bar::draw(fb, "GPS", clock.as_str());
+                match gga.position {
+                    Some(pos) if gga.quality != 0 => writeln!(
+                        board.tx,
+                        "{} with {} satellites",
+                        pos, gga.satellites
+                    ).unwrap(),
+                    _ => writeln!(board.tx, "No fix ({} satellites)", gga.satellites).unwrap(),
+                }
+            }
+            Some(Sentence::Rmc(rmc)) => draw_rmc(fb, &rmc),
+            None => {}
+        }
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,110 @@
+//! Extra outputs from daisy-chained 74HC595 shift registers.
+//!
+//! SRCLK is PA2 (SSI0Clk), SER is PA5 (SSI0Tx) and RCLK is PA3. Put an LED
+//! on each of the sixteen outputs of two chained '595s.
+//!
+//! The `blink` function only knows about `OutputPin`, and gets used for
+//! both the on-board red LED and an expander output.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+
+use core::fmt::Write;
+use demo::config;
+use demo::hal;
+use demo::hc595::ShiftRegister;
+use demo::spi::Spi;
+use embedded_hal::digital::OutputPin;
+use embedded_hal::prelude::*;
+use embedded_hal::spi::MODE_0;
+use hal::delay::Delay;
+use hal::sysctl;
+use hal::time::U32Ext;
+use rt::ExceptionFrame;
+
+const CHIPS: usize = 2;
+
+/// Toggle any output pin.
+fn blink<P>(pin: &mut P)
+where
+    P: OutputPin,
+{
+    if pin.is_high() {
+        pin.set_low();
+    } else {
+        pin.set_high();
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Ssi0);
+
+    // SSI0Clk and SSI0Tx
+    config::SSI0.connect();
+    let porta = board.porta;
+    let spi = Spi::ssi0(p.SSI0, MODE_0, 10_000_000_u32.hz(), &board.clocks);
+    let expander = ShiftRegister::new(spi, porta.pa3.into_push_pull_output(), CHIPS).unwrap();
+
+    // The last output on the chain gets treated like any other GPIO
+    let mut last = expander.pin(expander.len() - 1);
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+
+    writeln!(board.tx, "74HC595 demo - {} extra outputs", expander.len()).unwrap();
+
+    // A Knight Rider style scanner over all but the last output
+    let scan_len = expander.len() - 1;
+    let mut position = 0;
+    let mut forwards = true;
+    let mut ticks = 0;
+    loop {
+        let keep = expander.get_all() & (1 << scan_len);
+        expander.set_all(keep | (1 << position)).unwrap();
+        if forwards {
+            position += 1;
+            if position == scan_len - 1 {
+                forwards = false;
+            }
+        } else {
+            position -= 1;
+            if position == 0 {
+                forwards = true;
+            }
+        }
+
+        ticks += 1;
+        if ticks == 10 {
+            ticks = 0;
+            blink(&mut board.led_red);
+            blink(&mut last);
+        }
+
+        d.delay_ms(50u32);
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
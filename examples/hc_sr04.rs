@@ -0,0 +1,303 @@
+//! Measures distance with an HC-SR04 ultrasonic rangefinder.
+//!
+//! TRIG is PB4 (T1CCP0) and ECHO is PB2 (T3CCP0). The HC-SR04 runs on 5V
+//! and so does its ECHO output. PB2 is 5V tolerant, but a 1k/2k divider
+//! doesn't hurt. The VGA output is the same as `hello_vga`: HSYNC on PB6,
+//! VSYNC on PC4 and green on PB7.
+//!
+//! Timer1A runs in PWM mode and emits a 10us pulse on TRIG every 60ms, with
+//! no help from the CPU. Timer3A runs in edge-time mode and captures the
+//! time of both edges of the ECHO pulse; its interrupt works out the pulse
+//! width. The main loop converts that to millimetres, throws away the odd
+//! wild reading by taking the median of the last few, prints the result on
+//! UART0 (115200 bps) and draws it as a bar graph on the VGA screen.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use demo::board::Board;
+use demo::graphics::{Canvas, Colour};
+use demo::status_bar;
+use demo::text::Buffer;
+use demo::vga;
+use rt::ExceptionFrame;
+use tm4c123x_hal::gpio::GpioExt;
+use tm4c123x_hal::sysctl;
+
+/// Timer clocks per microsecond.
+const CLOCKS_PER_US: u32 = 80;
+
+/// Time between measurements. The datasheet asks for at least 60ms so the
+/// last echo has died away.
+const PERIOD_US: u32 = 60_000;
+
+/// Length of the trigger pulse.
+const TRIGGER_US: u32 = 10;
+
+/// With nothing in range, the HC-SR04 gives up and sends a pulse of about
+/// 38ms. Anything this long or longer is 'no echo'.
+const NO_ECHO_US: u32 = 30_000;
+
+/// Readings outside this range (in mm) are nonsense.
+const MIN_MM: u32 = 20;
+const MAX_MM: u32 = 4_000;
+
+/// How many readings we take the median of.
+const FILTER_LEN: usize = 5;
+
+/// The timers are 16-bit with an 8-bit prescaler on top.
+const TIMER_MASK: u32 = 0x00FF_FFFF;
+
+// GPTMCFG
+const CFG_16_BIT: u32 = 0x4;
+
+// GPTMTAMR
+const TAMR_CAPTURE: u32 = 0x3;
+const TAMR_PERIODIC: u32 = 0x2;
+const TAMR_CMR: u32 = 1 << 2;
+const TAMR_AMS: u32 = 1 << 3;
+
+// GPTMCTL
+const CTL_TAEN: u32 = 1 << 0;
+const CTL_TAEVENT_BOTH: u32 = 0x3 << 2;
+
+// GPTMIMR, GPTMICR
+const INT_CAE: u32 = 1 << 2;
+
+/// ECHO is on PB2.
+const ECHO_PIN: u32 = 1 << 2;
+
+/// Where the bar graph goes.
+const BAR_TOP: usize = 64;
+const BAR_HEIGHT: usize = 48;
+
+/// Set by the Timer3A interrupt when `ECHO_CLOCKS` has a new value.
+static ECHO_READY: AtomicBool = AtomicBool::new(false);
+
+/// Width of the last echo pulse, in timer clocks.
+static ECHO_CLOCKS: AtomicUsize = AtomicUsize::new(0);
+
+/// When the echo pulse started. Only the interrupt touches this.
+static mut RISING_EDGE: u32 = 0;
+
+/// The last few readings, so we can take the median.
+struct Filter {
+    readings: [u32; FILTER_LEN],
+    next: usize,
+    count: usize,
+}
+
+impl Filter {
+    fn new() -> Filter {
+        Filter {
+            readings: [0; FILTER_LEN],
+            next: 0,
+            count: 0,
+        }
+    }
+
+    fn push(&mut self, mm: u32) {
+        self.readings[self.next] = mm;
+        self.next = (self.next + 1) % FILTER_LEN;
+        self.count = (self.count + 1).min(FILTER_LEN);
+    }
+
+    /// The middle of the readings we have. A single bad reading (a stray
+    /// echo, or a missed one) can't drag this around like it would an
+    /// average.
+    fn median(&self) -> Option<u32> {
+        if self.count == 0 {
+            return None;
+        }
+        let mut sorted = self.readings;
+        let sorted = &mut sorted[0..self.count];
+        // Insertion sort - it's five numbers
+        for i in 1..sorted.len() {
+            let mut j = i;
+            while j > 0 && sorted[j - 1] > sorted[j] {
+                sorted.swap(j - 1, j);
+                j -= 1;
+            }
+        }
+        Some(sorted[sorted.len() / 2])
+    }
+}
+
+/// Sound does about 343 m/s, and it has to get there and back.
+fn echo_to_mm(us: u32) -> u32 {
+    (us * 343) / 2_000
+}
+
+/// Draw the bar, scaled so `MAX_MM` fills the screen.
+fn draw_bar<C: Canvas>(canvas: &mut C, mm: u32) {
+    let width = canvas.width();
+    let len = (mm.min(MAX_MM) as usize * width) / MAX_MM as usize;
+    canvas.fill_rect(0, BAR_TOP, len, BAR_HEIGHT, Colour::WHITE);
+    canvas.fill_rect(len, BAR_TOP, width - len, BAR_HEIGHT, Colour::BLACK);
+}
+
+/// A tick and a label every 500mm under the bar.
+fn draw_scale<C: Canvas>(canvas: &mut C) {
+    let width = canvas.width();
+    let y = BAR_TOP + BAR_HEIGHT + 4;
+    canvas.draw_line(
+        (0, y as isize),
+        ((width - 1) as isize, y as isize),
+        Colour::WHITE,
+    );
+    for step in 0..(MAX_MM / 500) {
+        let x = (step as usize * 500 * width) / MAX_MM as usize;
+        canvas.draw_line(
+            (x as isize, y as isize),
+            (x as isize, (y + 4) as isize),
+            Colour::WHITE,
+        );
+        let mut label = Buffer::new();
+        write!(label, "{}", step * 500).unwrap();
+        canvas.draw_str(x, y + 8, label.as_str(), Colour::WHITE, Colour::BLACK);
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Timer1);
+    board.enable(sysctl::Domain::Timer3);
+    board.enable(sysctl::Domain::Ssi2);
+
+    let mut portb = p.GPIO_PORTB.split(&board.power_control);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    // T1CCP0 and T3CCP0
+    let _trig = portb.pb4.into_af7(&mut portb.control);
+    let _echo = portb.pb2.into_af7(&mut portb.control);
+
+    // Timer3A counts down from the top in edge-time mode, grabbing the
+    // count on both edges of ECHO.
+    let echo_timer = p.TIMER3;
+    echo_timer.ctl.write(|w| unsafe { w.bits(0) });
+    echo_timer.cfg.write(|w| unsafe { w.bits(CFG_16_BIT) });
+    echo_timer
+        .tamr
+        .write(|w| unsafe { w.bits(TAMR_CAPTURE | TAMR_CMR) });
+    echo_timer.tailr.write(|w| unsafe { w.bits(0xFFFF) });
+    echo_timer.tapr.write(|w| unsafe { w.bits(0xFF) });
+    echo_timer.ctl.write(|w| unsafe { w.bits(CTL_TAEVENT_BOTH) });
+    echo_timer.icr.write(|w| unsafe { w.bits(INT_CAE) });
+    echo_timer.imr.write(|w| unsafe { w.bits(INT_CAE) });
+    echo_timer
+        .ctl
+        .write(|w| unsafe { w.bits(CTL_TAEVENT_BOTH | CTL_TAEN) });
+
+    // Timer1A in PWM mode. The output goes high when the count reloads
+    // and low when it hits the match value, which we set just below the
+    // top. The prescaler registers hold bits 23:16 of each value.
+    let load = (PERIOD_US * CLOCKS_PER_US) - 1;
+    let matched = load - (TRIGGER_US * CLOCKS_PER_US);
+    let trig_timer = p.TIMER1;
+    trig_timer.ctl.write(|w| unsafe { w.bits(0) });
+    trig_timer.cfg.write(|w| unsafe { w.bits(CFG_16_BIT) });
+    trig_timer
+        .tamr
+        .write(|w| unsafe { w.bits(TAMR_PERIODIC | TAMR_AMS) });
+    trig_timer.tailr.write(|w| unsafe { w.bits(load & 0xFFFF) });
+    trig_timer.tapr.write(|w| unsafe { w.bits(load >> 16) });
+    trig_timer
+        .tamatchr
+        .write(|w| unsafe { w.bits(matched & 0xFFFF) });
+    trig_timer.tapmr.write(|w| unsafe { w.bits(matched >> 16) });
+    trig_timer.ctl.write(|w| unsafe { w.bits(CTL_TAEN) });
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER3A);
+
+    writeln!(board.tx, "HC-SR04 rangefinder").unwrap();
+
+    let fb = vga::framebuffer();
+    fb.clear(Colour::BLACK);
+    status_bar::draw(fb, "Rangefinder", "");
+    draw_scale(fb);
+
+    let mut filter = Filter::new();
+
+    loop {
+        if !ECHO_READY.swap(false, Ordering::SeqCst) {
+            continue;
+        }
+        let us = ECHO_CLOCKS.load(Ordering::SeqCst) as u32 / CLOCKS_PER_US;
+        let mm = echo_to_mm(us);
+        if us >= NO_ECHO_US {
+            writeln!(board.tx, "No echo").unwrap();
+            status_bar::draw(fb, "Rangefinder", "Out of range");
+            draw_bar(fb, 0);
+            continue;
+        }
+        if mm < MIN_MM || mm > MAX_MM {
+            writeln!(board.tx, "Ignoring {} mm", mm).unwrap();
+            continue;
+        }
+        filter.push(mm);
+        if let Some(median) = filter.median() {
+            writeln!(board.tx, "{} mm (raw {} mm)", median, mm).unwrap();
+            let mut text = Buffer::new();
+            write!(text, "{} mm", median).unwrap();
+            status_bar::draw(fb, "Rangefinder", text.as_str());
+            draw_bar(fb, median);
+        }
+    }
+}
+
+interrupt!(TIMER3A, timer3a_isr);
+
+/// An edge on ECHO. If the pin is now high, the pulse has just started;
+/// otherwise it has just finished.
+fn timer3a_isr() {
+    let timer = unsafe { &*tm4c123x::TIMER3::ptr() };
+    let gpio = unsafe { &*tm4c123x::GPIO_PORTB::ptr() };
+    timer.icr.write(|w| unsafe { w.bits(INT_CAE) });
+    let now = timer.tar.read().bits() & TIMER_MASK;
+    if gpio.data.read().bits() & ECHO_PIN != 0 {
+        unsafe { RISING_EDGE = now };
+    } else {
+        // Counting down, so the start is the bigger number (unless we
+        // wrapped in between)
+        let width = unsafe { RISING_EDGE }.wrapping_sub(now) & TIMER_MASK;
+        ECHO_CLOCKS.store(width as usize, Ordering::SeqCst);
+        ECHO_READY.store(true, Ordering::SeqCst);
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,139 @@
+//! Drives an HD44780 character LCD in 4-bit mode.
+//!
+//! D4..D7 are PE0..PE3, RS is PA2, RW is PA3 and E is PA4.
+//!
+//! The top line shows the uptime with a custom 'heartbeat' character. The
+//! bottom line mirrors whatever you are typing on the UART0 console, at
+//! 115200 bps, and keeps the last line you entered once you press Enter.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board, gpio_port)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+
+use core::fmt::Write;
+use demo::hal;
+use demo::hd44780::{Geometry, Hd44780};
+use demo::text::Buffer;
+use embedded_hal::prelude::*;
+use hal::delay::Delay;
+use hal::gpio::GpioExt;
+use rt::ExceptionFrame;
+
+const GEOMETRY: Geometry = Geometry::_16x2;
+
+/// Custom character 0 - a filled heart
+const HEART_FULL: [u8; 8] = [
+    0b00000, 0b01010, 0b11111, 0b11111, 0b11111, 0b01110, 0b00100, 0b00000,
+];
+
+/// Custom character 1 - a hollow heart
+const HEART_EMPTY: [u8; 8] = [
+    0b00000, 0b01010, 0b10101, 0b10001, 0b10001, 0b01010, 0b00100, 0b00000,
+];
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+
+    let porte = gpio_port!(p, E).split(&board.power_control);
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+
+    let data = (
+        porte.pe0.into_push_pull_output(),
+        porte.pe1.into_push_pull_output(),
+        porte.pe2.into_push_pull_output(),
+        porte.pe3.into_push_pull_output(),
+    );
+    let mut porta = board.porta;
+    let mut lcd = Hd44780::new(
+        data,
+        porta.pa2.into_push_pull_output(),
+        porta.pa3.into_push_pull_output(),
+        porta.pa4.into_push_pull_output(),
+        &mut d,
+        GEOMETRY,
+    );
+    lcd.define_char(0, &HEART_FULL);
+    lcd.define_char(1, &HEART_EMPTY);
+
+    writeln!(board.tx, "HD44780 demo - type something!").unwrap();
+    write!(lcd, "Hello, LCD!").unwrap();
+
+    let mut typing = Buffer::from_storage([0u8; 32]);
+    let mut status = Buffer::from_storage([0u8; 32]);
+    let mut ticks = 0u32;
+    let mut seconds = 0u32;
+    let mut dirty = true;
+
+    loop {
+        while let Ok(ch) = board.rx.read() {
+            match ch {
+                b'\r' | b'\n' => {
+                    board.tx.write_str("\n").unwrap();
+                    // Keep showing what was entered until they type again
+                    typing.clear();
+                    continue;
+                }
+                0x08 | 0x7F => {
+                    typing.pop();
+                    board.tx.write_str("\x08 \x08").unwrap();
+                }
+                0x20...0x7E => {
+                    // Anything past the end of the buffer is dropped
+                    let _ = typing.write_char(ch as char);
+                    board.tx.write_char(ch as char).unwrap();
+                }
+                _ => {}
+            }
+            lcd.write_row(1, typing.as_str());
+        }
+
+        d.delay_ms(10u32);
+        ticks += 1;
+        if ticks == 100 {
+            ticks = 0;
+            seconds += 1;
+            dirty = true;
+        }
+
+        if dirty {
+            dirty = false;
+            status.clear();
+            write!(
+                status,
+                "Up {:02}:{:02}:{:02} ",
+                seconds / 3600,
+                (seconds / 60) % 60,
+                seconds % 60
+            ).unwrap();
+            lcd.write_row(0, status.as_str());
+            // Heartbeat in the top-right corner
+            lcd.set_position(GEOMETRY.cols() - 1, 0);
+            lcd.write_byte(if seconds % 2 == 0 { 0 } else { 1 });
+        }
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,283 @@
+//! Using `Vec`, `String` and `Box`, with the VGA console watching the heap.
+//!
+//! The heap is the 8 KiB just above the statics (the stack comes down from
+//! the top of RAM to meet it). The allocator is `alloc-cortex-m`, wrapped so
+//! we can count how much is in use. VGA is as in `hello_vga` (HSYNC on PB6,
+//! VSYNC on PC4 and green on PB7) and the screen shows the heap usage after
+//! every command. Commands are typed on UART0 at 115200 bps:
+//!
+//! * `vec <n>` - build a `Vec` of the first `n` squares
+//! * `rev <words..>` - build a `String` with the words backwards
+//! * `box` - put a 512 byte buffer in a `Box`
+//! * `keep <n>` - allocate `n` bytes and keep them
+//! * `free` - drop everything `keep` kept
+//! * `heap` - show the heap usage
+//!
+//! `keep` enough and you'll run out. The out-of-memory handler says so on
+//! the screen (and UART0) and stops.
+//!
+//! Build this with `--features heap`.
+
+#![feature(alloc)]
+#![feature(alloc_error_handler)]
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate alloc;
+extern crate alloc_cortex_m;
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+extern crate vga_framebuffer as fb;
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc_cortex_m::CortexMHeap;
+use core::alloc::{GlobalAlloc, Layout};
+use core::fmt::Write;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use demo::board::Board;
+use demo::console::Console;
+use demo::vga;
+use embedded_hal::prelude::*;
+use menu::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::sysctl;
+
+/// Size of the heap in bytes. The bigger it is, the less room the stack
+/// has before it runs into it.
+const HEAP_SIZE: usize = 8 * 1024;
+
+/// `CortexMHeap`, plus a count of the bytes in use.
+struct Heap {
+    inner: CortexMHeap,
+    used: AtomicUsize,
+}
+
+#[global_allocator]
+static HEAP: Heap = Heap {
+    inner: CortexMHeap::empty(),
+    used: AtomicUsize::new(0),
+};
+
+/// Everything `keep` has kept.
+static mut KEPT: Option<Vec<Vec<u8>>> = None;
+
+const VEC_ITEM: Item = Item {
+    item_type: ItemType::Callback(vec_callback),
+    command: "vec",
+    help: Some("<n> - build a Vec of n squares"),
+};
+
+const REV_ITEM: Item = Item {
+    item_type: ItemType::Callback(rev_callback),
+    command: "rev",
+    help: Some("<words..> - build a String, backwards"),
+};
+
+const BOX_ITEM: Item = Item {
+    item_type: ItemType::Callback(box_callback),
+    command: "box",
+    help: Some("box up 512 bytes"),
+};
+
+const KEEP_ITEM: Item = Item {
+    item_type: ItemType::Callback(keep_callback),
+    command: "keep",
+    help: Some("<n> - allocate n bytes and keep them"),
+};
+
+const FREE_ITEM: Item = Item {
+    item_type: ItemType::Callback(free_callback),
+    command: "free",
+    help: Some("drop everything kept"),
+};
+
+const HEAP_ITEM: Item = Item {
+    item_type: ItemType::Callback(heap_callback),
+    command: "heap",
+    help: Some("show the heap usage"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[
+        &VEC_ITEM,
+        &REV_ITEM,
+        &BOX_ITEM,
+        &KEEP_ITEM,
+        &FREE_ITEM,
+        &HEAP_ITEM,
+    ],
+    entry: None,
+    exit: None,
+};
+
+unsafe impl GlobalAlloc for Heap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc(layout);
+        if !ptr.is_null() {
+            self.used.fetch_add(layout.size(), Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.dealloc(ptr, layout);
+        self.used.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+/// Bytes in use on the heap.
+fn heap_used() -> usize {
+    HEAP.used.load(Ordering::Relaxed)
+}
+
+/// Parse the first argument after the command.
+fn argument<T>(input: &str) -> Option<T>
+where
+    T: core::str::FromStr,
+{
+    input.split_whitespace().nth(1).and_then(|s| s.parse::<T>().ok())
+}
+
+fn vec_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match argument::<u32>(input) {
+        Some(n) => {
+            let squares: Vec<u32> = (0..n).map(|x| x * x).collect();
+            let sum: u32 = squares.iter().sum();
+            writeln!(
+                Console,
+                "{} squares (capacity {}), adding up to {}",
+                squares.len(),
+                squares.capacity(),
+                sum
+            ).unwrap();
+            if squares.len() <= 16 {
+                writeln!(Console, "{:?}", squares).unwrap();
+            }
+        }
+        None => writeln!(Console, "Usage: vec <n>").unwrap(),
+    }
+}
+
+fn rev_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let mut s = String::new();
+    for word in input.split_whitespace().skip(1).collect::<Vec<_>>().iter().rev() {
+        if !s.is_empty() {
+            s.push(' ');
+        }
+        s.push_str(word);
+    }
+    writeln!(Console, "{:?} ({} bytes)", s, s.len()).unwrap();
+}
+
+fn box_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    let buffer = Box::new([0xA5u8; 512]);
+    writeln!(
+        Console,
+        "Boxed {} bytes at {:p}, {} bytes in use",
+        buffer.len(),
+        &*buffer,
+        heap_used()
+    ).unwrap();
+}
+
+fn keep_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match argument::<usize>(input) {
+        Some(n) => {
+            let kept = unsafe { KEPT.get_or_insert_with(Vec::new) };
+            kept.push(vec![0u8; n]);
+            writeln!(Console, "Keeping {} blocks", kept.len()).unwrap();
+        }
+        None => writeln!(Console, "Usage: keep <n>").unwrap(),
+    }
+}
+
+fn free_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    unsafe { KEPT = None };
+}
+
+fn heap_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    writeln!(Console, "{} of {} bytes in use", heap_used(), HEAP_SIZE).unwrap();
+}
+
+entry!(main);
+
+fn main() -> ! {
+    // Set the heap up before anything uses it
+    unsafe { HEAP.inner.init(rt::heap_start() as usize, HEAP_SIZE) };
+
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+
+    let mut c = fb::TextFrameBuffer::new(vga::framebuffer());
+    c.clear();
+    writeln!(c, "Heap demo - {} bytes of heap", HEAP_SIZE).unwrap();
+
+    writeln!(board.tx, "Heap demo. Type 'help'.").unwrap();
+
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+
+    let mut last_used = None;
+    loop {
+        while let Ok(ch) = board.rx.read() {
+            r.input_byte(ch);
+        }
+        let used = heap_used();
+        if last_used != Some(used) {
+            writeln!(c, "Heap: {:5} of {} bytes in use", used, HEAP_SIZE).unwrap();
+            last_used = Some(used);
+        }
+    }
+}
+
+#[alloc_error_handler]
+fn alloc_error(layout: Layout) -> ! {
+    let mut c = fb::TextFrameBuffer::new(vga::framebuffer());
+    writeln!(
+        c,
+        "Out of memory! Wanted {} bytes with {} of {} in use",
+        layout.size(),
+        heap_used(),
+        HEAP_SIZE
+    ).unwrap();
+    writeln!(Console, "Out of memory! Wanted {} bytes", layout.size()).unwrap();
+    // Leave the interrupts on, so the screen stays up
+    loop {}
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
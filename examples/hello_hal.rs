@@ -1,120 +1,143 @@
-//! Prints "Hello, world!" on the OpenOCD console using semihosting
+//! Prints "Hello, world!" on the OpenOCD console using semihosting, or on
+//! UART0 in release builds so it runs without a debugger (see
+//! `demo::logger`). With `--features defmt` it goes over RTT with `defmt`
+//! instead (see `demo::defmt_log`).
+//!
+//! Then, using `demo::scheduler`, it blinks the red LED once a second,
+//! shows the state of SW2 on the blue and green LEDs, and reports anything
+//! that arrives on UART0 - each as its own task, rather than one loop that
+//! stops for a second at a time.
 //!
 //! ---
 
-#![feature(used)]
 #![no_std]
+#![no_main]
 
 extern crate cortex_m;
-extern crate cortex_m_rt;
-extern crate cortex_m_semihosting;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[cfg(feature = "defmt")]
+extern crate defmt;
+#[macro_use(board, gpio_port)]
+extern crate demo;
 extern crate embedded_hal;
-extern crate tm4c123x_hal;
+#[cfg(not(feature = "defmt"))]
+#[macro_use]
+extern crate log;
+extern crate panic_halt;
 
 use core::fmt::Write;
-use cortex_m::asm;
-use cortex_m_semihosting::hio;
+#[cfg(feature = "defmt")]
+use defmt::info;
+use demo::board::Board;
+use demo::fault;
+use demo::hal;
+use demo::image;
+#[cfg(not(feature = "defmt"))]
+use demo::logger;
+use demo::reset;
+use demo::scheduler::{self, Task};
 use embedded_hal::prelude::*;
-use tm4c123x_hal::gpio::GpioExt;
-use tm4c123x_hal::serial::{NewlineMode, Serial};
-use tm4c123x_hal::sysctl::{self, chip_id, SysctlExt};
-use tm4c123x_hal::time::U32Ext;
-use tm4c123x_hal::delay::Delay;
-
-fn main() {
-    let mut itm = hio::hstdout().unwrap();
-    writeln!(itm, "Hello, world!").unwrap();
-
-    let p = tm4c123x_hal::Peripherals::take().unwrap();
-    let core_p = tm4c123x_hal::CorePeripherals::take().unwrap();
-    let mut sc = p.SYSCTL.constrain();
-
-    // This will run you at 8MHz using the internal oscillator
-    // sc.clock_setup.oscillator = sysctl::Oscillator::Main(
-    //     sysctl::CrystalFrequency::_16mhz,
-    //     sysctl::SystemClock::UseOscillator(sysctl::Divider::_2),
-    // );
-
-    // This runs you at 66.67MHz using the 16MHz crystal and the PLL
-    sc.clock_setup.oscillator = sysctl::Oscillator::Main(
-        sysctl::CrystalFrequency::_16mhz,
-        sysctl::SystemClock::UsePll(sysctl::PllOutputFrequency::_80_00mhz),
-    );
-
-    let clocks = sc.clock_setup.freeze();
-    let mut porta = p.GPIO_PORTA.split(&sc.power_control);
-    let mut portf = p.GPIO_PORTF.split(&sc.power_control);
-
-    // Activate UART
-    let uart = Serial::uart0(
-        p.UART0,
-        porta.pa1.into_af1(&mut porta.control),
-        porta.pa0.into_af1(&mut porta.control),
-        (),
-        (),
-        115200_u32.bps(),
-        NewlineMode::SwapLFtoCRLF,
-        &clocks,
-        &sc.power_control,
-    );
-    // Print chip info
-    let (mut tx, mut rx) = uart.split();
-    writeln!(tx, "Chip: {:?}", chip_id::get()).unwrap();
+use hal::gpio::GpioExt;
+use hal::serial::{NewlineMode, Serial};
+use hal::sysctl::chip_id;
+use hal::time::U32Ext;
+
+static TASKS: [Task<Board>; 3] = [
+    Task {
+        name: "blink",
+        period_ms: 1000,
+        run: blink,
+    },
+    Task {
+        name: "switch",
+        period_ms: 10,
+        run: switch,
+    },
+    Task {
+        name: "uart",
+        period_ms: 0,
+        run: uart,
+    },
+];
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let core_p = hal::CorePeripherals::take().unwrap();
+    let mut board = board!(p);
+
+    #[cfg(not(feature = "defmt"))]
+    logger::init(logger::Output::Semihosting, log::LevelFilter::Info);
+    info!("Hello, world!");
+
+    // Print chip info, and why we're starting up
+    writeln!(board.tx, "Chip: {:?}", chip_id::get()).unwrap();
+    writeln!(board.tx, "Reset by: {}", reset::cause()).unwrap();
+    match image::check_application() {
+        Ok(header) => writeln!(board.tx, "Image: {} bytes, CRC OK", header.len).unwrap(),
+        // Expected, unless the bootloader installed us
+        Err(e) => writeln!(board.tx, "Image: {:?}", e).unwrap(),
+    }
 
     // This will activate UART1 with H/W flow control
     // TODO: Test with FTDI TTL USB cable.
-    let mut portb = p.GPIO_PORTB.split(&sc.power_control);
-    let mut portc = p.GPIO_PORTC.split(&sc.power_control);
+    let mut portb = gpio_port!(p, B).split(&board.power_control);
+    #[cfg(feature = "tm4c123")]
+    let (rts, cts) = {
+        let mut portc = gpio_port!(p, C).split(&board.power_control);
+        (
+            portc.pc4.into_af8(&mut portc.control),
+            portc.pc5.into_af8(&mut portc.control),
+        )
+    };
+    // U1RTS and U1CTS are on PN0 and PN1 - two of the LEDs - so the
+    // Connected LaunchPad goes without flow control
+    #[cfg(feature = "tm4c129")]
+    let (rts, cts) = ((), ());
     let _uart1 = Serial::uart1(
         p.UART1,
         portb.pb1.into_af1(&mut portb.control),
         portb.pb0.into_af1(&mut portb.control),
-        portc.pc4.into_af8(&mut portc.control),
-        portc.pc5.into_af8(&mut portc.control),
+        rts,
+        cts,
         115200_u32.bps(),
         NewlineMode::SwapLFtoCRLF,
-        &clocks,
-        &sc.power_control,
+        &board.clocks,
+        &board.power_control,
     );
 
-    // turn on LED here
-    let mut led_red = portf.pf1.into_push_pull_output();
-    let mut led_blue = portf.pf2.into_push_pull_output();
-    let mut led_green = portf.pf3.into_push_pull_output();
-    led_red.set_low();
-    led_blue.set_low();
-    led_green.set_low();
-    let _sw1 = portf.pf4.into_pull_up_input();
-    let sw2 = portf.pf0.unlock(&mut portf.control).into_pull_up_input();
-
-    let mut d = Delay::new(core_p.SYST, &clocks);
-
-    loop {
-        if sw2.is_low() {
-            led_blue.set_high();
-            led_green.set_low();
-        } else {
-            led_blue.set_low();
-            led_green.set_high();
-        }
-        if let Ok(ch) = rx.read() {
-            writeln!(tx, "Read 0x{:02x} from the UART", ch).unwrap();
-        }
-
-        if led_red.is_high() {
-            led_red.set_low();
-        } else {
-            led_red.set_high();
-        }
-        d.delay_ms(1000u32);
+    let clocks = board.clocks;
+    scheduler::start(core_p.SYST, &clocks, &TASKS).run(&mut board);
+}
+
+/// Toggle the red LED.
+fn blink(board: &mut Board) {
+    board.led_red.toggle();
+}
+
+/// Blue while SW2 is pressed, green otherwise.
+fn switch(board: &mut Board) {
+    let pressed = board.sw2.is_pressed();
+    board.led_blue.set(pressed);
+    board.led_green.set(!pressed);
+}
+
+/// Say what came in on the UART.
+fn uart(board: &mut Board) {
+    while let Ok(ch) = board.rx.read() {
+        writeln!(board.tx, "Read 0x{:02x} from the UART", ch).unwrap();
     }
 }
 
-// As we are not using interrupts, we just register a dummy catch all handler
-#[link_section = ".vector_table.interrupts"]
-#[used]
-static INTERRUPTS: [extern "C" fn(); 240] = [default_handler; 240];
+exception!(SysTick, scheduler::tick);
+
+// Print what went wrong on UART0 and flash the red LED
+exception!(HardFault, fault::hard_fault);
+
+exception!(*, default_handler);
 
-extern "C" fn default_handler() {
-    asm::bkpt();
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
 }
@@ -0,0 +1,182 @@
+//! How many times can you press SW1 in five seconds?
+//!
+//! The best eight go in a high score table, and a count of the games
+//! played goes after them, both kept in the EEPROM with `demo::records`.
+//! Pull the plug while it's saving and the last table is still there when
+//! it comes back.
+//!
+//! It's all on the console (UART0 at 115200 bps), and the LaunchPad's own
+//! buttons: press SW1 to start, and then as fast as you can. Hold SW2
+//! during reset to wipe the table.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board)]
+extern crate demo;
+extern crate embedded_hal;
+#[macro_use]
+extern crate nb;
+extern crate panic_halt;
+
+use core::fmt::Write;
+use cortex_m::asm;
+use demo::eeprom::Eeprom;
+use demo::hal;
+use demo::records::{self, HighScores, Record};
+use embedded_hal::prelude::*;
+use hal::sysctl;
+use rt::ExceptionFrame;
+
+/// The high score table, at the bottom of the EEPROM.
+const SCORES: Record = Record {
+    address: 0,
+    capacity: records::HIGH_SCORES_LEN,
+};
+
+/// How many games have been played, after the scores (which take 32
+/// words).
+const GAMES: Record = Record {
+    address: 32,
+    capacity: 4,
+};
+
+/// How long a game lasts.
+const GAME_MS: u32 = 5000;
+
+/// How long SW1 must stay put before we believe it.
+const DEBOUNCE_MS: u32 = 5;
+
+/// Clock cycles in a millisecond, at 80 MHz.
+const CYCLES_PER_MS: u32 = 80_000;
+
+/// Follows a bouncy switch, one millisecond at a time.
+struct Debounce {
+    pressed: bool,
+    /// How long the switch has said otherwise
+    changing_ms: u32,
+}
+
+impl Debounce {
+    /// Look at the switch again. Says true when it's just been pressed.
+    fn update(&mut self, pressed: bool) -> bool {
+        if pressed == self.pressed {
+            self.changing_ms = 0;
+            return false;
+        }
+        self.changing_ms += 1;
+        if self.changing_ms < DEBOUNCE_MS {
+            return false;
+        }
+        self.pressed = pressed;
+        self.changing_ms = 0;
+        pressed
+    }
+}
+
+fn show_table(w: &mut Write, table: &HighScores) {
+    writeln!(w, "\nHigh scores:").unwrap();
+    for (i, line) in table.scores.iter().enumerate() {
+        let initials = core::str::from_utf8(&line.initials).unwrap_or("???");
+        writeln!(w, "{}. {} {:3}", i + 1, initials, line.score).unwrap();
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Eeprom);
+
+    let mut eeprom = match Eeprom::new(p.EEPROM) {
+        Ok(eeprom) => eeprom,
+        Err(e) => panic!("EEPROM failed: {:?}", e),
+    };
+
+    if board.sw2.is_pressed() {
+        SCORES.erase(&mut eeprom).unwrap();
+        GAMES.erase(&mut eeprom).unwrap();
+        writeln!(board.tx, "High scores wiped").unwrap();
+    }
+
+    let mut table = HighScores::load(&SCORES, &mut eeprom);
+    let mut bytes = [0u8; 4];
+    // Nothing saved yet is no games played
+    let _ = GAMES.load(&mut eeprom, &mut bytes);
+    let mut games = (0..4).fold(0u32, |n, i| n | (u32::from(bytes[i]) << (i * 8)));
+
+    let mut sw1 = Debounce {
+        pressed: false,
+        changing_ms: 0,
+    };
+    loop {
+        show_table(&mut board.tx, &table);
+        writeln!(board.tx, "\n{} games played. Press SW1 to start.", games).unwrap();
+        while !sw1.update(board.sw1.is_pressed()) {
+            asm::delay(CYCLES_PER_MS);
+        }
+        for count in (1..4).rev() {
+            writeln!(board.tx, "{}...", count).unwrap();
+            asm::delay(CYCLES_PER_MS * 1000);
+        }
+        writeln!(board.tx, "Go!").unwrap();
+
+        board.led_green.on();
+        let mut score = 0;
+        for _ in 0..GAME_MS {
+            if sw1.update(board.sw1.is_pressed()) {
+                score += 1;
+                board.led_red.toggle();
+            }
+            asm::delay(CYCLES_PER_MS);
+        }
+        board.led_green.off();
+        board.led_red.off();
+        writeln!(board.tx, "Stop! {} presses.", score).unwrap();
+
+        games += 1;
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = (games >> (i * 8)) as u8;
+        }
+        if let Err(e) = GAMES.save(&mut eeprom, &bytes) {
+            writeln!(board.tx, "Couldn't save the games played: {:?}", e).unwrap();
+        }
+
+        if !table.qualifies(score) {
+            continue;
+        }
+        write!(board.tx, "A high score! Your initials? ").unwrap();
+        let mut initials = [b' '; records::INITIALS];
+        for initial in initials.iter_mut() {
+            let byte = block!(board.rx.read()).unwrap();
+            *initial = match byte {
+                b'a'...b'z' => byte - b'a' + b'A',
+                b'A'...b'Z' | b'0'...b'9' => byte,
+                _ => b'?',
+            };
+            block!(board.tx.write(*initial)).unwrap();
+        }
+        writeln!(board.tx).unwrap();
+        table.insert(initials, score);
+        if let Err(e) = table.save(&SCORES, &mut eeprom) {
+            writeln!(board.tx, "Couldn't save the table: {:?}", e).unwrap();
+        }
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
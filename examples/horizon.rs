@@ -0,0 +1,184 @@
+//! An artificial horizon, using an MPU-6050 IMU.
+//!
+//! SCL is PB2 (I2C0SCL) and SDA is PB3 (I2C0SDA). The VGA output is the
+//! same as `hello_vga`: HSYNC on PB6, VSYNC on PC4 and green on PB7.
+//!
+//! At 100 Hz we read the accelerometer and gyro, and fuse them with a
+//! complementary filter: the gyro is good over short periods but drifts,
+//! while the accelerometer is noisy but right on average. The resulting
+//! pitch and roll tilt and move a horizon line on the VGA screen, and are
+//! shown in the status bar.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use demo::board::Board;
+use demo::graphics::{self, Canvas, Colour};
+use demo::i2c::{self, I2c};
+use demo::mpu6050::{self, Mpu6050};
+use demo::status_bar;
+use demo::text::Buffer;
+use demo::trig;
+use demo::vga;
+use rt::ExceptionFrame;
+use tm4c123x_hal::gpio::GpioExt;
+use tm4c123x_hal::sysctl;
+use tm4c123x_hal::time::U32Ext;
+
+/// How often we run the filter.
+const RATE_HZ: u32 = 100;
+
+/// System clock.
+const SYSCLK_HZ: u32 = 80_000_000;
+
+/// How much we trust the gyro, out of 1000. The rest comes from the
+/// accelerometer.
+const GYRO_WEIGHT: i32 = 980;
+
+/// Pixels the horizon moves per degree of pitch.
+const PIXELS_PER_DEGREE: i32 = 3;
+
+/// Half the length of the horizon line.
+const HALF_LENGTH: i32 = 150;
+
+/// Work out where the ends of the horizon line go for the given attitude,
+/// in thousandths of a degree.
+fn horizon(pitch: i32, roll: i32) -> ((isize, isize), (isize, isize)) {
+    let centre_x = (graphics::VGA_WIDTH / 2) as i32;
+    let centre_y = (graphics::VGA_HEIGHT / 2) as i32 + (pitch * PIXELS_PER_DEGREE) / 1000;
+    // Convert to binary degrees. The horizon tilts the opposite way to us.
+    let angle = ((-roll as i64 * 65536) / 360_000) as u16;
+    let dx = (i32::from(trig::cos(angle)) * HALF_LENGTH) >> 15;
+    let dy = (i32::from(trig::sin(angle)) * HALF_LENGTH) >> 15;
+    (
+        ((centre_x - dx) as isize, (centre_y - dy) as isize),
+        ((centre_x + dx) as isize, (centre_y + dy) as isize),
+    )
+}
+
+/// Print thousandths of a degree as degrees with one decimal place.
+fn write_degrees<W: Write>(w: &mut W, mdeg: i32) {
+    let sign = if mdeg < 0 { "-" } else { "" };
+    let mdeg = mdeg.abs();
+    write!(w, "{}{}.{}", sign, mdeg / 1000, (mdeg % 1000) / 100).unwrap();
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Timer1);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::I2c0);
+
+    let mut portb = p.GPIO_PORTB.split(&board.power_control);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+
+    // I2C0SCL and I2C0SDA. We need 400 kHz to read 14 bytes in good time.
+    let _scl = portb.pb2.into_af3(&mut portb.control);
+    let _sda = portb.pb3.into_af3(&mut portb.control);
+    i2c::open_drain(unsafe { &*tm4c123x::GPIO_PORTB::ptr() }, 3);
+    let bus = I2c::i2c0(p.I2C0, 400_000_u32.hz(), &board.clocks);
+
+    // Sample at 1 kHz / (1 + 9) = 100 Hz
+    let mut imu = Mpu6050::new(bus, mpu6050::DEFAULT_ADDRESS, 9).unwrap();
+
+    // Timer1A, 32-bit periodic. We poll the timeout flag rather than take
+    // an interrupt, so the I2C traffic stays in the main loop.
+    let timer = p.TIMER1;
+    timer.ctl.modify(|_, w| w.taen().clear_bit());
+    timer.cfg.write(|w| unsafe { w.bits(0) });
+    timer.tamr.modify(|_, w| w.tamr().period());
+    timer
+        .tailr
+        .write(|w| unsafe { w.bits(SYSCLK_HZ / RATE_HZ - 1) });
+    timer.icr.write(|w| w.tatocint().set_bit());
+    timer.ctl.modify(|_, w| w.taen().set_bit());
+
+    writeln!(board.tx, "MPU-6050 artificial horizon").unwrap();
+
+    let fb = vga::framebuffer();
+    fb.clear(Colour::BLACK);
+
+    // Both in thousandths of a degree
+    let mut pitch = 0i32;
+    let mut roll = 0i32;
+    let mut line = horizon(pitch, roll);
+    let mut ticks = 0u32;
+
+    loop {
+        while timer.ris.read().tatoris().bit_is_clear() {}
+        timer.icr.write(|w| w.tatocint().set_bit());
+
+        let r = imu.read().unwrap();
+        let (ax, ay, az) = (
+            i32::from(r.accel[0]),
+            i32::from(r.accel[1]),
+            i32::from(r.accel[2]),
+        );
+
+        // What the accelerometer thinks, assuming the only acceleration
+        // is gravity
+        let ay_az = trig::isqrt(((ay * ay) + (az * az)) as u32) as i32;
+        let accel_pitch = trig::atan2(-ax, ay_az);
+        let accel_roll = trig::atan2(ay, az);
+
+        // How far the gyro says we've turned since last time
+        let gyro_scale = mpu6050::GYRO_LSB_PER_DPS * RATE_HZ as i32;
+        let d_pitch = (i32::from(r.gyro[1]) * 1000) / gyro_scale;
+        let d_roll = (i32::from(r.gyro[0]) * 1000) / gyro_scale;
+
+        pitch = ((GYRO_WEIGHT * (pitch + d_pitch)) + ((1000 - GYRO_WEIGHT) * accel_pitch)) / 1000;
+        roll = ((GYRO_WEIGHT * (roll + d_roll)) + ((1000 - GYRO_WEIGHT) * accel_roll)) / 1000;
+
+        // Redraw at 25 Hz - no point going faster than the eye can see
+        ticks += 1;
+        if ticks % 4 == 0 {
+            fb.draw_line(line.0, line.1, Colour::BLACK);
+            line = horizon(pitch, roll);
+            fb.draw_line(line.0, line.1, Colour::WHITE);
+
+            let mut text = Buffer::new();
+            write!(text, "Pitch ").unwrap();
+            write_degrees(&mut text, pitch);
+            write!(text, "  Roll ").unwrap();
+            write_degrees(&mut text, roll);
+            status_bar::draw(fb, "Horizon", text.as_str());
+        }
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,215 @@
+//! A stereo tone generator on an I2S DAC, with the VGA screen running.
+//!
+//! See `demo::i2s` for the wiring - BCLK on PA2, DIN on PA5 and LRCLK on
+//! PB2, to a MAX98357B or similar. The VGA output is the same as
+//! `hello_vga` (HSYNC on PB6, VSYNC on PC4 and green on PB7). The console is
+//! on UART0 at 115200 bps. Commands:
+//!
+//! * `left <hz>` and `right <hz>` - set each side's frequency
+//! * `wave <sine|square|triangle|saw|noise>` - set both sides' waveform
+//! * `vol <0..255>` - set both sides' volume
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use cortex_m::interrupt;
+use demo::board::Board;
+use demo::console::Console;
+use demo::graphics::Colour;
+use demo::i2s;
+use demo::status_bar;
+use demo::text::Buffer;
+use demo::udma;
+use demo::vga;
+use demo::wavetable::{Oscillator, Waveform};
+use embedded_hal::prelude::*;
+use menu::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::sysctl;
+
+/// What we ask for. See `demo::i2s` for what we get.
+const SAMPLE_RATE_HZ: u32 = 48_000;
+
+const LEFT_ITEM: Item = Item {
+    item_type: ItemType::Callback(left_callback),
+    command: "left",
+    help: Some("<hz> - set the left frequency"),
+};
+
+const RIGHT_ITEM: Item = Item {
+    item_type: ItemType::Callback(right_callback),
+    command: "right",
+    help: Some("<hz> - set the right frequency"),
+};
+
+const WAVE_ITEM: Item = Item {
+    item_type: ItemType::Callback(wave_callback),
+    command: "wave",
+    help: Some("<sine|square|triangle|saw|noise> - set the waveform"),
+};
+
+const VOL_ITEM: Item = Item {
+    item_type: ItemType::Callback(vol_callback),
+    command: "vol",
+    help: Some("<0..255> - set the volume"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[&LEFT_ITEM, &RIGHT_ITEM, &WAVE_ITEM, &VOL_ITEM],
+    entry: None,
+    exit: None,
+};
+
+/// Left and right.
+static mut OSCILLATORS: Option<[Oscillator; 2]> = None;
+
+/// Run a closure against both oscillators, with the DAC's interrupt masked.
+fn with_oscillators<F>(f: F)
+where
+    F: FnOnce(&mut [Oscillator; 2]),
+{
+    interrupt::free(|_| {
+        if let Some(oscillators) = unsafe { OSCILLATORS.as_mut() } {
+            f(oscillators);
+        }
+    });
+}
+
+/// The first argument after the command.
+fn argument<T>(input: &str) -> Option<T>
+where
+    T: core::str::FromStr,
+{
+    input.split_whitespace().nth(1).and_then(|s| s.parse::<T>().ok())
+}
+
+fn left_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match argument::<u32>(input) {
+        Some(hz) => with_oscillators(|o| o[0].set_frequency(hz)),
+        None => writeln!(Console, "Usage: left <hz>").unwrap(),
+    }
+}
+
+fn right_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match argument::<u32>(input) {
+        Some(hz) => with_oscillators(|o| o[1].set_frequency(hz)),
+        None => writeln!(Console, "Usage: right <hz>").unwrap(),
+    }
+}
+
+fn wave_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let waveform = match input.split_whitespace().nth(1) {
+        Some("sine") => Waveform::Sine,
+        Some("square") => Waveform::Square,
+        Some("triangle") => Waveform::Triangle,
+        Some("saw") => Waveform::Sawtooth,
+        Some("noise") => Waveform::Noise,
+        _ => {
+            writeln!(Console, "Usage: wave <sine|square|triangle|saw|noise>").unwrap();
+            return;
+        }
+    };
+    with_oscillators(|o| {
+        o[0].waveform = waveform;
+        o[1].waveform = waveform;
+    });
+}
+
+fn vol_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match argument::<u8>(input) {
+        Some(volume) => with_oscillators(|o| {
+            o[0].volume = volume;
+            o[1].volume = volume;
+        }),
+        None => writeln!(Console, "Usage: vol <0..255>").unwrap(),
+    }
+}
+
+/// Make the next buffer's worth. An `i2s::Source`.
+fn fill(buffer: &mut [i16]) {
+    if let Some(oscillators) = unsafe { OSCILLATORS.as_mut() } {
+        for frame in buffer.chunks_mut(2) {
+            frame[0] = oscillators[0].next_sample();
+            frame[1] = oscillators[1].next_sample();
+        }
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::Ssi0);
+    board.enable(sysctl::Domain::Timer3);
+    board.enable(sysctl::Domain::MicroDma);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    // The oscillators need to know the real rate, not the one we asked for
+    let divider = i2s::divider(SAMPLE_RATE_HZ, &board.clocks);
+    let rate = i2s::actual_rate(divider, &board.clocks);
+    let mut left = Oscillator::new(Waveform::Sine, rate);
+    left.set_frequency(440);
+    let mut right = Oscillator::new(Waveform::Sine, rate);
+    right.set_frequency(660);
+    unsafe { OSCILLATORS = Some([left, right]) };
+
+    udma::init();
+    i2s::start(p.SSI0, p.TIMER3, SAMPLE_RATE_HZ, &board.clocks, fill);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+    // Below the VGA timers, so the picture stays put
+    unsafe { nvic.set_priority(tm4c123x_hal::Interrupt::SSI0, 0x40) };
+    nvic.enable(tm4c123x_hal::Interrupt::SSI0);
+
+    let mut text = Buffer::new();
+    write!(text, "{} Hz, 16-bit", rate).unwrap();
+    vga::framebuffer().clear(Colour::BLACK);
+    status_bar::draw(vga::framebuffer(), "I2S DAC", text.as_str());
+
+    writeln!(board.tx, "I2S at {} Hz (divider {})", rate, divider).unwrap();
+
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+    loop {
+        if let Ok(ch) = board.rx.read() {
+            r.input_byte(ch);
+        }
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+interrupt!(SSI0, i2s::ssi0_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,124 @@
+//! Drives an ILI9341 320x240 SPI TFT, using uDMA for the pixel data.
+//!
+//! SCK is PA2 (SSI0Clk), MOSI is PA5 (SSI0Tx), CS is PA3, DC is PA6 and RST
+//! is PA7.
+//!
+//! The drawing code only uses the `Canvas` trait, so the same `draw_demo`
+//! function would work on the VGA framebuffer or the Nokia 5110 LCD.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+
+use core::fmt::Write;
+use demo::config;
+use demo::graphics::{Canvas, Colour};
+use demo::hal;
+use demo::ili9341::Ili9341;
+use demo::spi::Spi;
+use demo::udma;
+use embedded_hal::prelude::*;
+use embedded_hal::spi::MODE_0;
+use hal::delay::Delay;
+use hal::sysctl;
+use hal::time::U32Ext;
+use rt::ExceptionFrame;
+
+/// Size of the gradient sprite we blit around.
+const SPRITE_SIZE: usize = 32;
+
+/// Draw a test pattern on anything that implements `Canvas`.
+fn draw_demo<C>(canvas: &mut C, frame: u32)
+where
+    C: Canvas,
+{
+    let (w, h) = (canvas.width() as isize, canvas.height() as isize);
+    let colours = [
+        Colour::RED,
+        Colour::GREEN,
+        Colour::BLUE,
+        Colour::YELLOW,
+        Colour::CYAN,
+        Colour::MAGENTA,
+    ];
+    let colour = colours[(frame as usize) % colours.len()];
+    // A fan of lines from the top-left corner
+    for i in 0..16 {
+        canvas.draw_line((0, 0), ((w * i) / 16, h - 1), colour);
+    }
+    let (width, height) = (canvas.width(), canvas.height());
+    canvas.draw_rect(0, 0, width, height, Colour::WHITE);
+    canvas.draw_str(8, 8, "Hello, Monotron!", Colour::WHITE, Colour::BLACK);
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Ssi0);
+    board.enable(sysctl::Domain::MicroDma);
+
+    // SSI0Clk and SSI0Tx
+    config::SSI0.connect();
+    let porta = board.porta;
+    let spi = Spi::ssi0(p.SSI0, MODE_0, 20_000_000_u32.hz(), &board.clocks);
+
+    udma::init();
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+    let mut rst = porta.pa7.into_push_pull_output();
+    let mut tft = Ili9341::new(
+        spi,
+        porta.pa6.into_push_pull_output(),
+        porta.pa3.into_push_pull_output(),
+        &mut rst,
+        &mut d,
+    ).unwrap();
+
+    writeln!(board.tx, "ILI9341 demo").unwrap();
+
+    // A red/blue gradient sprite
+    let mut sprite = [0u16; SPRITE_SIZE * SPRITE_SIZE];
+    for y in 0..SPRITE_SIZE {
+        for x in 0..SPRITE_SIZE {
+            let c = Colour::from_rgb((x * 8) as u8, 0, (y * 8) as u8);
+            sprite[y * SPRITE_SIZE + x] = c.0;
+        }
+    }
+
+    let mut frame = 0;
+    loop {
+        tft.clear(Colour::BLACK);
+        draw_demo(&mut tft, frame);
+        // Bounce the sprite along the bottom
+        let max_x = demo::ili9341::WIDTH - SPRITE_SIZE;
+        let pos = (frame as usize * 16) % (2 * max_x);
+        let x = if pos > max_x { 2 * max_x - pos } else { pos };
+        let y = demo::ili9341::HEIGHT - SPRITE_SIZE - 8;
+        tft.blit(x, y, SPRITE_SIZE, &sprite).unwrap();
+        frame = frame.wrapping_add(1);
+        d.delay_ms(500u32);
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
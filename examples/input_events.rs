@@ -0,0 +1,187 @@
+//! Everything you can plug in, as one stream of `demo::input` events.
+//!
+//! Attach any of these (or none - the console always works):
+//!
+//! * a PS/2 keyboard, clock on PD2 and data on PD3 (see `demo::keyboard`)
+//! * a PS/2 mouse, clock on PC6 and data on PC7 (see `demo::mouse`)
+//! * a DB9 joystick on PE0..PE4 (see `demo::joystick`)
+//! * a rotary encoder on PD6 and PD7, button on PE5 (see `demo::rotary`)
+//!
+//! Every event is listed on the VGA screen (HSYNC on PB6, VSYNC on PC4 and
+//! green on PB7, as in `hello_vga`) and in full on the console (UART0 at
+//! 115200 bps). Keys typed on the console, arrows and all, come out as key
+//! events too.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use cortex_m::asm;
+use cortex_m::peripheral::syst::SystClkSource;
+use demo::board::Board;
+use demo::font;
+use demo::graphics::{Canvas, Colour, VGA_HEIGHT, VGA_WIDTH};
+use demo::input::{self, InputEvent};
+use demo::joystick;
+use demo::keyboard::{self, Settings};
+use demo::mouse;
+use demo::rotary::Rotary;
+use demo::status_bar;
+use demo::text::Buffer;
+use demo::vga;
+use embedded_hal::prelude::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::sysctl;
+
+const ROWS: usize = (VGA_HEIGHT - status_bar::HEIGHT) / font::HEIGHT;
+
+/// How long a lone ESC from the console waits to become the Escape key.
+const ESCAPE_MS: usize = 50;
+
+/// SysTick's byte in the SCB's SHPR registers.
+const SHPR_SYSTICK: usize = 11;
+
+/// Milliseconds, counted by SysTick.
+static TICKS: AtomicUsize = AtomicUsize::new(0);
+
+/// A short description of an event, to fit on the screen.
+fn describe(event: &InputEvent, text: &mut Buffer) -> core::fmt::Result {
+    match *event {
+        InputEvent::KeyDown(key) if key.repeat => write!(text, "key repeat {:?}", key.key),
+        InputEvent::KeyDown(key) => write!(text, "key down {:?}", key.key),
+        InputEvent::KeyUp(key) => write!(text, "key up {:?}", key.key),
+        InputEvent::Joystick(event) => write!(text, "joystick {:?}", event),
+        InputEvent::Mouse { x, y, buttons } => write!(
+            text,
+            "mouse {},{} {}{}{}",
+            x,
+            y,
+            if buttons.left { "L" } else { "-" },
+            if buttons.middle { "M" } else { "-" },
+            if buttons.right { "R" } else { "-" }
+        ),
+        InputEvent::Rotary(event) => write!(text, "rotary {:?}", event),
+        InputEvent::Touch { pad, touched } => write!(
+            text,
+            "pad {} {}",
+            pad,
+            if touched { "touched" } else { "let go" }
+        ),
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::Qei0);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+    // Below the VGA timers, so the picture stays put
+    unsafe {
+        nvic.set_priority(tm4c123x_hal::Interrupt::GPIOC, 0x40);
+        nvic.set_priority(tm4c123x_hal::Interrupt::GPIOD, 0x40);
+    }
+    nvic.enable(tm4c123x_hal::Interrupt::GPIOC);
+    nvic.enable(tm4c123x_hal::Interrupt::GPIOD);
+
+    unsafe { cp.SCB.shpr[SHPR_SYSTICK].write(0x40) };
+    let mut syst = cp.SYST;
+    syst.set_clock_source(SystClkSource::Core);
+    syst.set_reload((board.clocks.sysclk.0 / 1000) - 1);
+    syst.enable_counter();
+    syst.enable_interrupt();
+
+    // Let the mouse (and the keyboard) finish their self-tests
+    asm::delay(board.clocks.sysclk.0 / 2);
+    mouse::init(&board.clocks);
+    let mut keyboard = keyboard::init(&board.clocks, Settings::DEFAULT);
+    joystick::init();
+    // Takes PD6 and PD7 from the keyboard's port, which it doesn't mind
+    let mut rotary = Rotary::new(p.QEI0);
+
+    let fb = vga::framebuffer();
+    fb.clear(Colour::BLACK);
+    status_bar::draw(fb, "Input events", "");
+
+    writeln!(board.tx, "Input events. Press things.").unwrap();
+
+    let mut keys = input::Decoder::new();
+    let mut row = 0;
+    let mut last_tick = 0;
+    let mut last_byte = 0;
+    loop {
+        let now = TICKS.load(Ordering::Relaxed);
+        if let Ok(byte) = board.rx.read() {
+            keys.input(byte);
+            last_byte = now;
+        } else if now.wrapping_sub(last_byte) >= ESCAPE_MS {
+            keys.timeout();
+        }
+        input::keyboard(&mut keyboard);
+        input::joystick();
+        input::mouse();
+        if now != last_tick {
+            last_tick = now;
+            input::rotary(&mut rotary);
+        }
+
+        while let Some(event) = input::next() {
+            writeln!(board.tx, "{:?}", event).unwrap();
+            let mut text = Buffer::new();
+            // Too long is cut short, which is fine
+            let _ = describe(&event, &mut text);
+            let y = status_bar::HEIGHT + (row * font::HEIGHT);
+            fb.fill_rect(0, y, VGA_WIDTH, font::HEIGHT, Colour::BLACK);
+            fb.draw_str(0, y, text.as_str(), Colour::WHITE, Colour::BLACK);
+            row = (row + 1) % ROWS;
+            // A blank line under the newest, so you can see where it is
+            let y = status_bar::HEIGHT + (row * font::HEIGHT);
+            fb.fill_rect(0, y, VGA_WIDTH, font::HEIGHT, Colour::BLACK);
+        }
+    }
+}
+
+exception!(SysTick, sys_tick);
+
+fn sys_tick() {
+    TICKS.fetch_add(1, Ordering::Relaxed);
+    joystick::sample();
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+interrupt!(GPIOC, mouse::gpioc_isr);
+interrupt!(GPIOD, keyboard::gpiod_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
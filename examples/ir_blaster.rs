@@ -0,0 +1,212 @@
+//! An infrared remote control, sending NEC codes.
+//!
+//! Drive an IR LED from PB6 (M0PWM0) through a transistor and a suitable
+//! resistor - the GPIO can't supply enough current on its own. On the
+//! TM4C1294, M0PWM0 is PF0 (which is also LED D4). Point it at
+//! a TV, or at the `ir_remote` example running on another LaunchPad.
+//!
+//! PWM0 generates the 38 kHz carrier all the time, and we gate it on and
+//! off with the PWM output enable. Timer1A runs in one-shot mode for the
+//! length of each mark and space, and its interrupt moves on to the next.
+//! The console is on UART0 at 115200 bps.
+//!
+//! Commands:
+//!
+//! * `irsend <addr> <cmd>` - send a code (numbers can be decimal or 0x hex)
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+#[cfg(feature = "tm4c123")]
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+#[cfg(feature = "tm4c129")]
+#[macro_use(interrupt)]
+extern crate tm4c129x;
+
+use core::fmt::Write;
+use cortex_m::interrupt;
+use demo::board::SYSCLK_HZ;
+use demo::config::{Pin, Port};
+use demo::console::Console;
+use demo::hal;
+use demo::nec::{self, Pulses};
+use demo::pac;
+use embedded_hal::prelude::*;
+use hal::sysctl;
+use menu::*;
+use rt::ExceptionFrame;
+
+/// Timer clocks per microsecond.
+const CLOCKS_PER_US: u32 = SYSCLK_HZ / 1_000_000;
+
+/// Carrier period in clocks (38 kHz).
+const CARRIER_PERIOD: u32 = SYSCLK_HZ / 38_000;
+
+/// M0PWM0, and its alternate function.
+#[cfg(feature = "tm4c123")]
+const LED: Pin = Pin { port: Port::B, bit: 6 };
+#[cfg(feature = "tm4c123")]
+const LED_FUNCTION: u32 = 4;
+#[cfg(feature = "tm4c129")]
+const LED: Pin = Pin { port: Port::F, bit: 0 };
+#[cfg(feature = "tm4c129")]
+const LED_FUNCTION: u32 = 6;
+
+/// M0PWM0 in PWMENABLE.
+const PWM_OUTPUT: u32 = 1 << 0;
+
+// GPTMCFG, GPTMTAMR, GPTMCTL
+const CFG_32_BIT: u32 = 0x0;
+const TAMR_ONE_SHOT: u32 = 0x1;
+const CTL_TAEN: u32 = 1 << 0;
+
+/// What's left of the frame we're sending.
+static mut PULSES: Option<Pulses> = None;
+
+const IRSEND_ITEM: Item = Item {
+    item_type: ItemType::Callback(irsend_callback),
+    command: "irsend",
+    help: Some("<addr> <cmd> - send an NEC code"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[&IRSEND_ITEM],
+    entry: None,
+    exit: None,
+};
+
+/// Parse a number, which might be hex with a `0x` on the front.
+fn parse_number(s: &str) -> Option<u32> {
+    if s.starts_with("0x") || s.starts_with("0X") {
+        u32::from_str_radix(&s[2..], 16).ok()
+    } else {
+        s.parse::<u32>().ok()
+    }
+}
+
+fn irsend_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let mut args = input.split_whitespace().skip(1).map(parse_number);
+    match (args.next(), args.next()) {
+        (Some(Some(address)), Some(Some(command))) if address <= 0xFFFF && command <= 0xFF => {
+            let started = interrupt::free(|_| {
+                if unsafe { PULSES.is_some() } {
+                    return false;
+                }
+                let bits = nec::encode(address as u16, command as u8);
+                unsafe { PULSES = Some(nec::pulses(bits)) };
+                next_pulse();
+                true
+            });
+            if !started {
+                writeln!(Console, "Still sending the last one").unwrap();
+            }
+        }
+        _ => writeln!(Console, "Usage: irsend <addr> <cmd>").unwrap(),
+    }
+}
+
+/// Start the next mark or space, or stop if the frame is done. Call with
+/// interrupts off.
+fn next_pulse() {
+    let pwm = unsafe { &*pac::PWM0::ptr() };
+    let timer = unsafe { &*pac::TIMER1::ptr() };
+    match unsafe { PULSES.as_mut() }.and_then(|p| p.next()) {
+        Some((mark, us)) => {
+            if mark {
+                pwm.enable
+                    .modify(|r, w| unsafe { w.bits(r.bits() | PWM_OUTPUT) });
+            } else {
+                pwm.enable
+                    .modify(|r, w| unsafe { w.bits(r.bits() & !PWM_OUTPUT) });
+            }
+            timer
+                .tailr
+                .write(|w| unsafe { w.bits(us * CLOCKS_PER_US - 1) });
+            timer.ctl.write(|w| unsafe { w.bits(CTL_TAEN) });
+        }
+        None => {
+            pwm.enable
+                .modify(|r, w| unsafe { w.bits(r.bits() & !PWM_OUTPUT) });
+            unsafe { PULSES = None };
+        }
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Timer1);
+    board.enable(sysctl::Domain::Pwm0);
+
+    // M0PWM0
+    LED.into_af(LED_FUNCTION);
+
+    // PWM0 generator 0, count-down mode, at 38 kHz with a one third duty
+    // cycle (the LED gets a rest, and receivers don't mind). The output
+    // stays disabled until we want a mark.
+    let pwm = p.PWM0;
+    pwm._0_ctl.write(|w| unsafe { w.bits(0) });
+    pwm._0_load.write(|w| unsafe { w.bits(CARRIER_PERIOD - 1) });
+    pwm._0_cmpa
+        .write(|w| unsafe { w.bits((CARRIER_PERIOD / 3) - 1) });
+    // ACTCMPAD = drive high, ACTLOAD = drive low
+    pwm._0_gena.write(|w| unsafe { w.bits((0x3 << 6) | (0x2 << 2)) });
+    pwm._0_ctl.write(|w| unsafe { w.bits(1) });
+
+    // Timer1A, 32-bit one-shot, interrupting on timeout
+    let timer = p.TIMER1;
+    timer.ctl.write(|w| unsafe { w.bits(0) });
+    timer.cfg.write(|w| unsafe { w.bits(CFG_32_BIT) });
+    timer.tamr.write(|w| unsafe { w.bits(TAMR_ONE_SHOT) });
+    timer.imr.modify(|_, w| w.tatoim().set_bit());
+    timer.icr.write(|w| w.tatocint().set_bit());
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(hal::Interrupt::TIMER1A);
+
+    writeln!(board.tx, "NEC IR blaster").unwrap();
+
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+
+    loop {
+        if let Ok(ch) = board.rx.read() {
+            r.input_byte(ch);
+        }
+    }
+}
+
+interrupt!(TIMER1A, timer1a_isr);
+
+/// The current mark or space is over.
+fn timer1a_isr() {
+    let timer = unsafe { &*pac::TIMER1::ptr() };
+    timer.icr.write(|w| w.tatocint().set_bit());
+    next_pulse();
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
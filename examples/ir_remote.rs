@@ -0,0 +1,274 @@
+//! Decodes an NEC infrared remote, and uses it to drive the on-screen menu.
+//!
+//! The OUT pin of a TSOP38238 (or similar 38 kHz receiver module) goes to
+//! PB2 (T3CCP0). The VGA output is the same as `hello_vga`: HSYNC on PB6,
+//! VSYNC on PC4 and green on PB7.
+//!
+//! Timer3A captures the time of every edge from the receiver, and its
+//! interrupt feeds the mark and space lengths to the NEC decoder. Every
+//! code received is printed on UART0 (115200 bps), so you can find out
+//! what your remote sends. The keys in `KEYMAP` (which suit the common
+//! 21-key 'Car MP3' remote - edit it for yours) type into the menu on the
+//! VGA screen. The keyboard works too.
+//!
+//! Commands:
+//!
+//! * `1` - toggle the red LED
+//! * `2` - toggle the blue LED
+//! * `3` - toggle the green LED
+//! * `0` - all LEDs off
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+extern crate vga_framebuffer as fb;
+
+use core::fmt::Write;
+use cortex_m::interrupt;
+use demo::board::Board;
+use demo::nec::{Decoder, Event};
+use demo::vga;
+use embedded_hal::prelude::*;
+use menu::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::gpio::GpioExt;
+use tm4c123x_hal::sysctl;
+
+/// Timer clocks per microsecond.
+const CLOCKS_PER_US: u32 = 80;
+
+/// The timer is 16-bit with an 8-bit prescaler on top.
+const TIMER_MASK: u32 = 0x00FF_FFFF;
+
+// GPTMCFG, GPTMTAMR, GPTMCTL, GPTMIMR/GPTMICR
+const CFG_16_BIT: u32 = 0x4;
+const TAMR_CAPTURE: u32 = 0x3;
+const TAMR_CMR: u32 = 1 << 2;
+const CTL_TAEN: u32 = 1 << 0;
+const CTL_TAEVENT_BOTH: u32 = 0x3 << 2;
+const INT_CAE: u32 = 1 << 2;
+
+/// The receiver is on PB2.
+const IR_PIN: u32 = 1 << 2;
+
+/// The LEDs are PF1 (red), PF2 (blue) and PF3 (green).
+const LED_RED: u32 = 1 << 1;
+const LED_BLUE: u32 = 1 << 2;
+const LED_GREEN: u32 = 1 << 3;
+
+/// Which remote buttons type what.
+const KEYMAP: [(u8, &[u8]); 14] = [
+    (0x16, b"0"),
+    (0x0C, b"1"),
+    (0x18, b"2"),
+    (0x5E, b"3"),
+    (0x08, b"4"),
+    (0x1C, b"5"),
+    (0x5A, b"6"),
+    (0x42, b"7"),
+    (0x52, b"8"),
+    (0x4A, b"9"),
+    // PLAY/PAUSE is Enter
+    (0x43, b"\n"),
+    // PREV is Backspace
+    (0x44, b"\x08"),
+    // CH shows the help
+    (0x46, b"help\n"),
+    // CH- goes back up a menu
+    (0x45, b"exit\n"),
+];
+
+/// The latest event from the decoder, waiting for the main loop.
+static mut EVENT: Option<Event> = None;
+
+/// Only the interrupt touches these.
+static mut DECODER: Option<Decoder> = None;
+static mut LAST_EDGE: u32 = 0;
+
+const RED_ITEM: Item = Item {
+    item_type: ItemType::Callback(red_callback),
+    command: "1",
+    help: Some("toggle the red LED"),
+};
+
+const BLUE_ITEM: Item = Item {
+    item_type: ItemType::Callback(blue_callback),
+    command: "2",
+    help: Some("toggle the blue LED"),
+};
+
+const GREEN_ITEM: Item = Item {
+    item_type: ItemType::Callback(green_callback),
+    command: "3",
+    help: Some("toggle the green LED"),
+};
+
+const OFF_ITEM: Item = Item {
+    item_type: ItemType::Callback(off_callback),
+    command: "0",
+    help: Some("all LEDs off"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[&RED_ITEM, &BLUE_ITEM, &GREEN_ITEM, &OFF_ITEM],
+    entry: None,
+    exit: None,
+};
+
+fn toggle_leds(mask: u32) {
+    let gpio = unsafe { &*tm4c123x::GPIO_PORTF::ptr() };
+    gpio.data.modify(|r, w| unsafe { w.bits(r.bits() ^ mask) });
+}
+
+fn red_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    toggle_leds(LED_RED);
+}
+
+fn blue_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    toggle_leds(LED_BLUE);
+}
+
+fn green_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    toggle_leds(LED_GREEN);
+}
+
+fn off_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    let gpio = unsafe { &*tm4c123x::GPIO_PORTF::ptr() };
+    let all = LED_RED | LED_BLUE | LED_GREEN;
+    gpio.data.modify(|r, w| unsafe { w.bits(r.bits() & !all) });
+}
+
+/// What should this button type?
+fn keys(command: u8) -> Option<&'static [u8]> {
+    KEYMAP
+        .iter()
+        .find(|&&(code, _)| code == command)
+        .map(|&(_, keys)| keys)
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Timer3);
+    board.enable(sysctl::Domain::Ssi2);
+
+    let mut portb = p.GPIO_PORTB.split(&board.power_control);
+
+    // `board` has set up the LED pins, and the callbacks drive them
+    // through the data register directly
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    unsafe {
+        DECODER = Some(Decoder::new());
+    }
+
+    // T3CCP0. Timer3A counts down from the top in edge-time mode,
+    // grabbing the count on every edge.
+    let _ir = portb.pb2.into_af7(&mut portb.control);
+    let timer = p.TIMER3;
+    timer.ctl.write(|w| unsafe { w.bits(0) });
+    timer.cfg.write(|w| unsafe { w.bits(CFG_16_BIT) });
+    timer
+        .tamr
+        .write(|w| unsafe { w.bits(TAMR_CAPTURE | TAMR_CMR) });
+    timer.tailr.write(|w| unsafe { w.bits(0xFFFF) });
+    timer.tapr.write(|w| unsafe { w.bits(0xFF) });
+    timer.ctl.write(|w| unsafe { w.bits(CTL_TAEVENT_BOTH) });
+    timer.icr.write(|w| unsafe { w.bits(INT_CAE) });
+    timer.imr.write(|w| unsafe { w.bits(INT_CAE) });
+    timer
+        .ctl
+        .write(|w| unsafe { w.bits(CTL_TAEVENT_BOTH | CTL_TAEN) });
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER3A);
+
+    writeln!(board.tx, "NEC IR remote decoder").unwrap();
+
+    let mut c = fb::TextFrameBuffer::new(vga::framebuffer());
+    c.clear();
+    writeln!(c, "IR remote menu - press CH for help").unwrap();
+
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut c);
+
+    loop {
+        while let Ok(ch) = board.rx.read() {
+            r.output.write_char(ch as char).unwrap();
+            r.input_byte(ch);
+        }
+
+        match interrupt::free(|_| unsafe { EVENT.take() }) {
+            Some(Event::Command { address, command }) => {
+                writeln!(
+                    board.tx,
+                    "Address 0x{:04X}, command 0x{:02X}",
+                    address, command
+                ).unwrap();
+                if let Some(keys) = keys(command) {
+                    for &ch in keys {
+                        r.output.write_char(ch as char).unwrap();
+                        r.input_byte(ch);
+                    }
+                }
+            }
+            // Held buttons don't auto-repeat in a menu
+            Some(Event::Repeat) => writeln!(board.tx, "Repeat").unwrap(),
+            None => {}
+        }
+    }
+}
+
+interrupt!(TIMER3A, timer3a_isr);
+
+/// An edge from the receiver. It idles high and goes low for a mark, so if
+/// the pin is high now, a mark has just finished.
+fn timer3a_isr() {
+    let timer = unsafe { &*tm4c123x::TIMER3::ptr() };
+    let gpio = unsafe { &*tm4c123x::GPIO_PORTB::ptr() };
+    timer.icr.write(|w| unsafe { w.bits(INT_CAE) });
+    let now = timer.tar.read().bits() & TIMER_MASK;
+    let mark = gpio.data.read().bits() & IR_PIN != 0;
+    // Counting down, so the last edge is the bigger number
+    let us = (unsafe { LAST_EDGE }.wrapping_sub(now) & TIMER_MASK) / CLOCKS_PER_US;
+    unsafe { LAST_EDGE = now };
+    if let Some(decoder) = unsafe { DECODER.as_mut() } {
+        if let Some(event) = decoder.pulse(mark, us) {
+            unsafe { EVENT = Some(event) };
+        }
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
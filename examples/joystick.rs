@@ -0,0 +1,138 @@
+//! Drive a square around the VGA screen with a DB9 joystick.
+//!
+//! See `demo::joystick` for the wiring - up, down, left, right and fire on
+//! PE0..PE4. The VGA output is the same as `hello_vga` (HSYNC on PB6, VSYNC
+//! on PC4 and green on PB7). Fire leaves a trail behind the square, or
+//! stops leaving one. Every event goes to the console (UART0 at 115200
+//! bps) as well as the status bar.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use cortex_m::peripheral::syst::SystClkSource;
+use demo::board::Board;
+use demo::graphics::{Canvas, Colour, VGA_HEIGHT, VGA_WIDTH};
+use demo::joystick::{self, Button, Event};
+use demo::status_bar;
+use demo::text::Buffer;
+use demo::vga;
+use rt::ExceptionFrame;
+use tm4c123x_hal::sysctl;
+
+/// Size of the square.
+const SIZE: usize = 8;
+
+/// Milliseconds between steps while a direction is held.
+const STEP_MS: usize = 10;
+
+/// SysTick's byte in the SCB's SHPR registers.
+const SHPR_SYSTICK: usize = 11;
+
+/// Milliseconds, counted by SysTick.
+static TICKS: AtomicUsize = AtomicUsize::new(0);
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+
+    vga::init(p.TIMER0, p.SSI2);
+    joystick::init();
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+
+    // Sample the joystick every millisecond. Below the VGA timers, so the
+    // picture stays put.
+    unsafe { cp.SCB.shpr[SHPR_SYSTICK].write(0x40) };
+    let mut syst = cp.SYST;
+    syst.set_clock_source(SystClkSource::Core);
+    syst.set_reload((board.clocks.sysclk.0 / 1000) - 1);
+    syst.enable_counter();
+    syst.enable_interrupt();
+
+    let fb = vga::framebuffer();
+    fb.clear(Colour::BLACK);
+    status_bar::draw(fb, "DB9 joystick", "");
+
+    let (mut x, mut y) = (VGA_WIDTH / 2, VGA_HEIGHT / 2);
+    let mut trail = false;
+    let mut last_step = 0;
+    fb.fill_rect(x, y, SIZE, SIZE, Colour::WHITE);
+
+    loop {
+        while let Some(event) = joystick::next_event() {
+            if event == Event::Pressed(Button::Fire) {
+                trail = !trail;
+            }
+            let mut text = Buffer::new();
+            write!(text, "{:?}", event).unwrap();
+            status_bar::draw(fb, "DB9 joystick", text.as_str());
+            writeln!(board.tx, "{:?}", event).unwrap();
+        }
+
+        let now = TICKS.load(Ordering::Relaxed);
+        if now.wrapping_sub(last_step) < STEP_MS {
+            continue;
+        }
+        last_step = now;
+        let (old_x, old_y) = (x, y);
+        if joystick::is_down(Button::Left) {
+            x = x.saturating_sub(1);
+        }
+        if joystick::is_down(Button::Right) {
+            x = (x + 1).min(VGA_WIDTH - SIZE);
+        }
+        if joystick::is_down(Button::Up) {
+            y = y.saturating_sub(1).max(status_bar::HEIGHT);
+        }
+        if joystick::is_down(Button::Down) {
+            y = (y + 1).min(VGA_HEIGHT - SIZE);
+        }
+        if (x, y) != (old_x, old_y) {
+            if !trail {
+                fb.fill_rect(old_x, old_y, SIZE, SIZE, Colour::BLACK);
+            }
+            fb.fill_rect(x, y, SIZE, SIZE, Colour::WHITE);
+        }
+    }
+}
+
+exception!(SysTick, sys_tick);
+
+fn sys_tick() {
+    TICKS.fetch_add(1, Ordering::Relaxed);
+    joystick::sample();
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,279 @@
+//! Type on a PS/2 keyboard, and see it on the VGA screen.
+//!
+//! See `demo::keyboard` for the wiring - clock on PD2 and data on PD3. The
+//! VGA output is the same as `hello_vga` (HSYNC on PB6, VSYNC on PC4 and
+//! green on PB7). Whatever you type goes to the screen and to the menu, as
+//! does anything typed on the console (UART0 at 115200 bps). Commands:
+//!
+//! * `layout <us|uk|de>` - what's printed on the keys
+//! * `delay <ms>` - how long a key is held before it repeats
+//! * `rate <cps>` - how fast it repeats
+//! * `save` - keep the settings in the EEPROM for next time
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use demo::board::Board;
+use demo::console::Console;
+use demo::eeprom::Eeprom;
+use demo::font;
+use demo::graphics::{Canvas, Colour, VGA_HEIGHT, VGA_WIDTH};
+use demo::keyboard::{self, Key, KeyEvent, Keyboard, Layout, Settings};
+use demo::status_bar;
+use demo::text::Buffer;
+use demo::vga;
+use embedded_hal::prelude::*;
+use menu::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::sysctl;
+
+const COLS: usize = VGA_WIDTH / font::WIDTH;
+const ROWS: usize = (VGA_HEIGHT - status_bar::HEIGHT) / font::HEIGHT;
+
+const LAYOUT_ITEM: Item = Item {
+    item_type: ItemType::Callback(layout_callback),
+    command: "layout",
+    help: Some("<us|uk|de> - set the keyboard layout"),
+};
+
+const DELAY_ITEM: Item = Item {
+    item_type: ItemType::Callback(delay_callback),
+    command: "delay",
+    help: Some("<ms> - set the delay before a key repeats"),
+};
+
+const RATE_ITEM: Item = Item {
+    item_type: ItemType::Callback(rate_callback),
+    command: "rate",
+    help: Some("<cps> - set how fast a key repeats"),
+};
+
+const SAVE_ITEM: Item = Item {
+    item_type: ItemType::Callback(save_callback),
+    command: "save",
+    help: Some("keep the settings in the EEPROM"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[&LAYOUT_ITEM, &DELAY_ITEM, &RATE_ITEM, &SAVE_ITEM],
+    entry: None,
+    exit: None,
+};
+
+/// For the menu callbacks. Only touched from the main loop.
+static mut KEYBOARD: Option<Keyboard> = None;
+static mut EEPROM: Option<Eeprom> = None;
+
+/// Argument `n` after the command.
+fn argument<T>(input: &str, n: usize) -> Option<T>
+where
+    T: core::str::FromStr,
+{
+    input.split_whitespace().nth(n).and_then(|s| s.parse::<T>().ok())
+}
+
+/// Change the settings, if there's a keyboard.
+fn change<F>(f: F)
+where
+    F: FnOnce(&mut Settings),
+{
+    if let Some(keyboard) = unsafe { KEYBOARD.as_mut() } {
+        let mut settings = keyboard.settings();
+        f(&mut settings);
+        keyboard.set_settings(settings);
+        if !keyboard.is_connected() {
+            writeln!(Console, "The keyboard didn't answer").unwrap();
+        }
+    }
+    show();
+}
+
+/// Show the settings on the status bar.
+fn show() {
+    let mut text = Buffer::new();
+    if let Some(keyboard) = unsafe { KEYBOARD.as_ref() } {
+        let settings = keyboard.settings();
+        let caps = if keyboard.modifiers().caps_lock {
+            " CAPS"
+        } else {
+            ""
+        };
+        write!(
+            text,
+            "{} {}ms {}cps{}",
+            settings.layout.name(),
+            settings.delay_ms,
+            settings.rate_cps,
+            caps
+        ).unwrap();
+    }
+    status_bar::draw(vga::framebuffer(), "PS/2 keyboard", text.as_str());
+}
+
+fn layout_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match input.split_whitespace().nth(1).and_then(Layout::from_name) {
+        Some(layout) => change(|s| s.layout = layout),
+        None => writeln!(Console, "Usage: layout <us|uk|de>").unwrap(),
+    }
+}
+
+fn delay_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match argument::<u16>(input, 1) {
+        Some(ms) => change(|s| s.delay_ms = ms),
+        None => writeln!(Console, "Usage: delay <ms>").unwrap(),
+    }
+}
+
+fn rate_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match argument::<u8>(input, 1) {
+        Some(cps) => change(|s| s.rate_cps = cps),
+        None => writeln!(Console, "Usage: rate <cps>").unwrap(),
+    }
+}
+
+fn save_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    let keyboard = unsafe { KEYBOARD.as_ref() };
+    let eeprom = unsafe { EEPROM.as_mut() };
+    match (keyboard, eeprom) {
+        (Some(keyboard), Some(eeprom)) => match keyboard.settings().save(eeprom) {
+            Ok(()) => writeln!(Console, "Saved").unwrap(),
+            Err(e) => writeln!(Console, "Can't save: {:?}", e).unwrap(),
+        },
+        _ => writeln!(Console, "No EEPROM").unwrap(),
+    }
+}
+
+/// A very small terminal on the rest of the screen.
+struct Screen {
+    col: usize,
+    row: usize,
+}
+
+impl Screen {
+    fn new() -> Screen {
+        Screen { col: 0, row: 0 }
+    }
+
+    fn key(&mut self, event: &KeyEvent) {
+        let fb = vga::framebuffer();
+        let (x, y) = (self.col * font::WIDTH, status_bar::HEIGHT + (self.row * font::HEIGHT));
+        match event.key {
+            Key::Enter => self.newline(),
+            Key::Backspace if self.col > 0 => {
+                self.col -= 1;
+                fb.draw_char(x - font::WIDTH, y, b' ', Colour::WHITE, Colour::BLACK);
+            }
+            // The font only does ASCII for certain
+            Key::Char(c) => {
+                let byte = if c.is_ascii() { c as u8 } else { b'?' };
+                fb.draw_char(x, y, byte, Colour::WHITE, Colour::BLACK);
+                self.col += 1;
+                if self.col == COLS {
+                    self.newline();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn newline(&mut self) {
+        self.col = 0;
+        self.row = (self.row + 1) % ROWS;
+        let fb = vga::framebuffer();
+        let y = status_bar::HEIGHT + (self.row * font::HEIGHT);
+        fb.fill_rect(0, y, VGA_WIDTH, font::HEIGHT, Colour::BLACK);
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::Eeprom);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+    // Below the VGA timers, so the picture stays put
+    unsafe { nvic.set_priority(tm4c123x_hal::Interrupt::GPIOD, 0x40) };
+    nvic.enable(tm4c123x_hal::Interrupt::GPIOD);
+
+    let settings = match Eeprom::new(p.EEPROM) {
+        Ok(mut eeprom) => {
+            let settings = Settings::load(&mut eeprom);
+            unsafe { EEPROM = Some(eeprom) };
+            settings
+        }
+        Err(e) => {
+            writeln!(board.tx, "EEPROM failed: {:?}", e).unwrap();
+            Settings::DEFAULT
+        }
+    };
+    let keyboard = keyboard::init(&board.clocks, settings);
+    if !keyboard.is_connected() {
+        writeln!(board.tx, "No keyboard?").unwrap();
+    }
+    unsafe { KEYBOARD = Some(keyboard) };
+
+    vga::framebuffer().clear(Colour::BLACK);
+    show();
+
+    writeln!(board.tx, "Type away. 'help' for help.").unwrap();
+
+    let mut screen = Screen::new();
+    let mut caps_lock = false;
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+    loop {
+        let event = unsafe { KEYBOARD.as_mut() }.and_then(|k| k.poll());
+        if let Some(event) = event {
+            screen.key(&event);
+            if let Some(byte) = event.ascii() {
+                r.input_byte(byte);
+            }
+            if event.modifiers.caps_lock != caps_lock {
+                caps_lock = event.modifiers.caps_lock;
+                show();
+            }
+        }
+        if let Ok(ch) = board.rx.read() {
+            r.input_byte(ch);
+        }
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+interrupt!(GPIOD, keyboard::gpiod_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
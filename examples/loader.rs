@@ -0,0 +1,229 @@
+//! A little development monitor - load programs into RAM and run them.
+//!
+//! Type `loadhex` (or `loadsrec`) on UART0 (115200 bps) and then send an
+//! Intel HEX (or Motorola S-record) file as plain text (with picocom,
+//! Ctrl-A Ctrl-S and `ascii-xfr`, or just paste it). The records must fall
+//! inside the load area, whose address is printed at start-up, so link
+//! your program to run from there. Every good record gets a `.`; a bad one
+//! stops the load. Ctrl-C gives up.
+//!
+//! `run` then calls the file's start address (or whatever address you give
+//! it), as an `extern "C" fn()`. If the program returns, we come back here.
+//!
+//! Commands:
+//!
+//! * `loadhex` - receive an Intel HEX file
+//! * `loadsrec` - receive an S-record file
+//! * `run [addr]` - call the loaded code (addresses in hex)
+//! * `info` - show the load area and what's in it
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+
+use core::fmt::Write;
+use demo::console::Console;
+use demo::hal;
+use demo::loader::{self, Parser, Region};
+use embedded_hal::prelude::*;
+use menu::*;
+use rt::ExceptionFrame;
+
+/// The load area, in words so it's aligned.
+const LOAD_WORDS: usize = 4 * 1024;
+
+/// The longest line we accept (a full record is 521 characters).
+const MAX_LINE: usize = 524;
+
+const CTRL_C: u8 = 0x03;
+
+static mut LOAD_AREA: [u32; LOAD_WORDS] = [0; LOAD_WORDS];
+
+/// Set by `loadhex` and `loadsrec`. The main loop does the loading, as it has the UART.
+static mut LOAD: bool = false;
+
+/// What we loaded, and where it said to start.
+static mut LOADED: usize = 0;
+static mut START: Option<u32> = None;
+
+const LOADHEX_ITEM: Item = Item {
+    item_type: ItemType::Callback(loadhex_callback),
+    command: "loadhex",
+    help: Some("receive an Intel HEX file"),
+};
+
+// Either sort of file will do, really
+const LOADSREC_ITEM: Item = Item {
+    item_type: ItemType::Callback(loadhex_callback),
+    command: "loadsrec",
+    help: Some("receive a Motorola S-record file"),
+};
+
+const RUN_ITEM: Item = Item {
+    item_type: ItemType::Callback(run_callback),
+    command: "run",
+    help: Some("[addr] - run the loaded code"),
+};
+
+const INFO_ITEM: Item = Item {
+    item_type: ItemType::Callback(info_callback),
+    command: "info",
+    help: Some("show the load area"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[&LOADHEX_ITEM, &LOADSREC_ITEM, &RUN_ITEM, &INFO_ITEM],
+    entry: None,
+    exit: None,
+};
+
+fn load_area() -> &'static mut [u8] {
+    unsafe {
+        core::slice::from_raw_parts_mut(LOAD_AREA.as_mut_ptr() as *mut u8, LOAD_WORDS * 4)
+    }
+}
+
+fn load_address() -> u32 {
+    unsafe { LOAD_AREA.as_ptr() as u32 }
+}
+
+fn loadhex_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    unsafe { LOAD = true };
+    writeln!(Console, "Send the file now (Ctrl-C to give up)").unwrap();
+}
+
+fn run_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let address = match input.split_whitespace().nth(1) {
+        Some(arg) => match u32::from_str_radix(arg.trim_left_matches("0x"), 16) {
+            Ok(address) => address,
+            Err(_) => {
+                writeln!(Console, "Usage: run [addr]").unwrap();
+                return;
+            }
+        },
+        None => match unsafe { START } {
+            Some(address) => address,
+            None => {
+                writeln!(Console, "No start address - give one").unwrap();
+                return;
+            }
+        },
+    };
+    writeln!(Console, "Running 0x{:08x}", address).unwrap();
+    // It's Thumb code, so set the bottom bit
+    let code: extern "C" fn() = unsafe { core::mem::transmute((address | 1) as usize) };
+    code();
+    writeln!(Console, "\nIt came back").unwrap();
+}
+
+fn info_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    writeln!(
+        Console,
+        "Load area 0x{:08x}..0x{:08x}",
+        load_address(),
+        load_address() + (LOAD_WORDS as u32 * 4)
+    ).unwrap();
+    writeln!(Console, "{} bytes loaded", unsafe { LOADED }).unwrap();
+    if let Some(start) = unsafe { START } {
+        writeln!(Console, "Start address 0x{:08x}", start).unwrap();
+    }
+}
+
+/// Read records until the end one, or something goes wrong.
+/// Gives `None` if the user gave up.
+fn load<RX>(rx: &mut RX) -> Result<Option<Region<'static>>, loader::Error>
+where
+    RX: embedded_hal::serial::Read<u8>,
+{
+    let mut parser = Parser::new();
+    let mut region = Region::new(load_area(), load_address());
+    let mut line = [0u8; MAX_LINE];
+    let mut len = 0;
+    loop {
+        let byte = match rx.read() {
+            Ok(byte) => byte,
+            Err(_) => continue,
+        };
+        match byte {
+            CTRL_C => return Ok(None),
+            b'\r' | b'\n' if len == 0 => {}
+            b'\r' | b'\n' => {
+                let text =
+                    core::str::from_utf8(&line[0..len]).map_err(|_| loader::Error::Syntax)?;
+                len = 0;
+                if region.apply(parser.parse(text)?)? {
+                    return Ok(Some(region));
+                }
+                write!(Console, ".").unwrap();
+            }
+            _ if len < MAX_LINE => {
+                line[len] = byte;
+                len += 1;
+            }
+            _ => return Err(loader::Error::Syntax),
+        }
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+
+    let mut board = board!(p);
+
+    writeln!(
+        board.tx,
+        "Loader - {} byte load area at 0x{:08x}",
+        LOAD_WORDS * 4,
+        load_address()
+    ).unwrap();
+
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+
+    loop {
+        while let Ok(ch) = board.rx.read() {
+            r.input_byte(ch);
+        }
+
+        if unsafe { LOAD } {
+            unsafe {
+                LOAD = false;
+                LOADED = 0;
+                START = None;
+            }
+            match load(&mut board.rx) {
+                Ok(Some(region)) => unsafe {
+                    LOADED = region.loaded;
+                    START = region.start;
+                    writeln!(Console, "\nLoaded {} bytes", LOADED).unwrap();
+                },
+                Ok(None) => writeln!(Console, "\nGave up").unwrap(),
+                Err(e) => writeln!(Console, "\nLoad failed: {:?}", e).unwrap(),
+            }
+            r.prompt();
+        }
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,195 @@
+//! An eight channel logic analyser, for sigrok (PulseView) or OLS.
+//!
+//! The channels are PB0..PB7, or PK0..PK7 on the TM4C1294 (whose Port B
+//! only has six pins). Connect UART0 (the debug USB port) to the
+//! host software as an 'Openbench Logic Sniffer' at 115200 bps - the
+//! protocol (`demo::sump`) is binary, so nothing else can be on the UART.
+//!
+//! Timer1 sets the sample rate and the CPU copies each sample into SRAM, so
+//! we top out at 1 MHz, with room for 16K samples. One trigger stage is
+//! supported: capture starts (well, the post-trigger part of it does) when
+//! the masked channels match the value. Sending anything while we're
+//! waiting for a trigger gives up on the capture.
+//!
+//! The uDMA could take samples from a timer trigger without the CPU, and a
+//! lot faster, but then the trigger has to be checked after the fact -
+//! left as an exercise.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board)]
+extern crate demo;
+extern crate embedded_hal;
+#[macro_use]
+extern crate nb;
+extern crate panic_halt;
+
+use demo::board::SYSCLK_HZ;
+use demo::config::{GpioRegisters, Pin, Port};
+use demo::hal;
+use demo::pac::{self, timer0};
+use demo::sump::{self, Command, Metadata, Parser};
+use embedded_hal::prelude::*;
+use hal::sysctl;
+use rt::ExceptionFrame;
+
+/// How many samples we keep. Must be a power of two.
+const SAMPLES: usize = 16 * 1024;
+
+/// The port the channels are on. They're all eight pins of it.
+#[cfg(feature = "tm4c123")]
+const CHANNELS: Port = Port::B;
+#[cfg(feature = "tm4c129")]
+const CHANNELS: Port = Port::K;
+
+/// The fastest we can go.
+const MAX_SAMPLE_RATE: u32 = 1_000_000;
+
+static mut BUFFER: [u8; SAMPLES] = [0; SAMPLES];
+
+/// What the host has asked for.
+struct Settings {
+    divider: u32,
+    read: usize,
+    delay: usize,
+    trigger_mask: u8,
+    trigger_value: u8,
+}
+
+/// Wait for the next tick of Timer1A, and take a sample.
+fn sample(timer: &timer0::RegisterBlock, gpio: &GpioRegisters) -> u8 {
+    while timer.ris.read().tatoris().bit_is_clear() {}
+    timer.icr.write(|w| w.tatocint().set_bit());
+    gpio.data.read().bits() as u8
+}
+
+/// Run a capture. Returns where the newest sample is, or `None` if the
+/// host interrupted us.
+fn capture(settings: &Settings, buffer: &mut [u8; SAMPLES]) -> Option<usize> {
+    let timer = unsafe { &*pac::TIMER1::ptr() };
+    let gpio = CHANNELS.registers();
+    let uart = unsafe { &*pac::UART0::ptr() };
+
+    let rate = sump::sample_rate(settings.divider).min(MAX_SAMPLE_RATE);
+    timer.ctl.modify(|_, w| w.taen().clear_bit());
+    timer.tailr.write(|w| unsafe { w.bits(SYSCLK_HZ / rate - 1) });
+    timer.icr.write(|w| w.tatocint().set_bit());
+    timer.ctl.modify(|_, w| w.taen().set_bit());
+
+    let read = settings.read.min(SAMPLES);
+    let mut index = 0;
+    let mut remaining = read;
+    if settings.trigger_mask != 0 {
+        // Fill the ring until the trigger, which is the first of the
+        // post-trigger samples
+        loop {
+            let s = sample(timer, gpio);
+            buffer[index] = s;
+            index = (index + 1) & (SAMPLES - 1);
+            if s & settings.trigger_mask == settings.trigger_value {
+                break;
+            }
+            if uart.fr.read().rxfe().bit_is_clear() {
+                timer.ctl.modify(|_, w| w.taen().clear_bit());
+                return None;
+            }
+        }
+        remaining = settings.delay.min(read) - 1;
+    }
+    for _ in 0..remaining {
+        buffer[index] = sample(timer, gpio);
+        index = (index + 1) & (SAMPLES - 1);
+    }
+
+    timer.ctl.modify(|_, w| w.taen().clear_bit());
+    Some(index)
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+
+    // The protocol is binary. That's fine, as only `write!` turns `\n`
+    // into `\r\n` - bytes sent with `write` go as they are.
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Timer1);
+
+    // The channels
+    for bit in 0..8 {
+        Pin { port: CHANNELS, bit }.into_input();
+    }
+
+    // Timer1A, 32-bit periodic. We poll it rather than take interrupts.
+    let timer = p.TIMER1;
+    timer.ctl.modify(|_, w| w.taen().clear_bit());
+    timer.cfg.write(|w| unsafe { w.bits(0) });
+    timer.tamr.modify(|_, w| w.tamr().period());
+
+    let metadata = Metadata {
+        name: "TM4C123 LaunchPad",
+        probes: 8,
+        sample_memory: SAMPLES as u32,
+        max_sample_rate: MAX_SAMPLE_RATE,
+    };
+    let mut settings = Settings {
+        divider: sump::CLOCK_HZ / MAX_SAMPLE_RATE - 1,
+        read: SAMPLES,
+        delay: SAMPLES,
+        trigger_mask: 0,
+        trigger_value: 0,
+    };
+    let mut parser = Parser::new();
+
+    loop {
+        let command = match board.rx.read() {
+            Ok(byte) => parser.input(byte),
+            Err(_) => None,
+        };
+        match command {
+            Some(Command::Id) => {
+                for &byte in sump::ID.iter() {
+                    block!(board.tx.write(byte)).unwrap();
+                }
+            }
+            Some(Command::Metadata) => metadata.send(|byte| block!(board.tx.write(byte)).unwrap()),
+            Some(Command::Divider(divider)) => settings.divider = divider,
+            Some(Command::Counts { read, delay }) => {
+                settings.read = read;
+                settings.delay = delay;
+            }
+            Some(Command::TriggerMask { stage: 0, mask }) => settings.trigger_mask = mask as u8,
+            Some(Command::TriggerValue { stage: 0, value }) => {
+                settings.trigger_value = value as u8;
+            }
+            Some(Command::Run) => {
+                let buffer = unsafe { &mut BUFFER };
+                if let Some(end) = capture(&settings, buffer) {
+                    // Newest first
+                    for i in 1..=settings.read.min(SAMPLES) {
+                        let byte = buffer[end.wrapping_sub(i) & (SAMPLES - 1)];
+                        block!(board.tx.write(byte)).unwrap();
+                    }
+                }
+            }
+            Some(Command::Reset) => settings.trigger_mask = 0,
+            _ => {}
+        }
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,225 @@
+//! Sends and receives LoRa telemetry with an SX1276 module (e.g. RFM95W).
+//!
+//! SCK is PA2 (SSI0Clk), MISO is PA4 (SSI0Rx), MOSI is PA5 (SSI0Tx), NSS is
+//! PA3 and RESET is PA6. The analog input we report is AIN0 on PE3.
+//!
+//! Every ten seconds we send a frame with our uptime and the ADC reading.
+//! In between we listen, and print anything we hear (from another board
+//! running the same demo) with its RSSI and SNR. The console is on UART0 at
+//! 115200 bps.
+//!
+//! Commands:
+//!
+//! * `sf <7..12>` - set the spreading factor (both ends must match)
+//! * `send` - send a frame now
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board, gpio_port)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+
+use core::fmt::Write;
+use demo::config;
+use demo::console::Console;
+use demo::hal;
+use demo::pac;
+use demo::spi::Spi;
+use demo::sx127x::{self, Sx127x};
+use demo::text::Buffer;
+use embedded_hal::prelude::*;
+use embedded_hal::spi::MODE_0;
+use hal::delay::Delay;
+use hal::gpio::GpioExt;
+use hal::sysctl;
+use hal::time::U32Ext;
+use menu::*;
+use rt::ExceptionFrame;
+
+/// Change this to 433 or 868 MHz to suit your module and your local rules.
+const FREQUENCY_HZ: u32 = 915_000_000;
+
+/// Milliseconds between telemetry frames.
+const SEND_INTERVAL_MS: u32 = 10_000;
+
+/// How long we sleep each time round the main loop.
+const POLL_MS: u32 = 10;
+
+/// AIN0 is PE3.
+const AIN0_PIN: u32 = 1 << 3;
+
+static mut NEW_SPREADING_FACTOR: Option<u8> = None;
+
+static mut SEND_NOW: bool = false;
+
+const SF_ITEM: Item = Item {
+    item_type: ItemType::Callback(sf_callback),
+    command: "sf",
+    help: Some("<7..12> - set the spreading factor"),
+};
+
+const SEND_ITEM: Item = Item {
+    item_type: ItemType::Callback(send_callback),
+    command: "send",
+    help: Some("send a telemetry frame now"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[&SF_ITEM, &SEND_ITEM],
+    entry: None,
+    exit: None,
+};
+
+fn sf_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let sf = input.split_whitespace().nth(1).and_then(|s| s.parse::<u8>().ok());
+    match sf {
+        Some(sf) if sf >= sx127x::MIN_SPREADING_FACTOR && sf <= sx127x::MAX_SPREADING_FACTOR => {
+            unsafe { NEW_SPREADING_FACTOR = Some(sf) };
+        }
+        _ => writeln!(Console, "Usage: sf <7..12>").unwrap(),
+    }
+}
+
+fn send_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    unsafe { SEND_NOW = true };
+}
+
+/// Set up ADC0 sample sequencer 3 to take a single sample of AIN0 when
+/// asked.
+fn adc_init() {
+    let gpio = config::Port::E.registers();
+    gpio.afsel.modify(|r, w| unsafe { w.bits(r.bits() | AIN0_PIN) });
+    gpio.den.modify(|r, w| unsafe { w.bits(r.bits() & !AIN0_PIN) });
+    gpio.amsel.modify(|r, w| unsafe { w.bits(r.bits() | AIN0_PIN) });
+
+    let adc = unsafe { &*pac::ADC0::ptr() };
+    adc.actss.modify(|r, w| unsafe { w.bits(r.bits() & !(1 << 3)) });
+    // Triggered by software
+    adc.emux.modify(|r, w| unsafe { w.bits(r.bits() & !(0xF << 12)) });
+    adc.ssmux3.write(|w| unsafe { w.bits(0) });
+    // END0 and IE0
+    adc.ssctl3.write(|w| unsafe { w.bits(0x6) });
+    adc.actss.modify(|r, w| unsafe { w.bits(r.bits() | (1 << 3)) });
+}
+
+/// Take one 12-bit sample of AIN0.
+fn adc_read() -> u16 {
+    let adc = unsafe { &*pac::ADC0::ptr() };
+    adc.pssi.write(|w| unsafe { w.bits(1 << 3) });
+    while adc.ris.read().bits() & (1 << 3) == 0 {}
+    let sample = adc.ssfifo3.read().bits() as u16 & 0xFFF;
+    adc.isc.write(|w| unsafe { w.bits(1 << 3) });
+    sample
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Ssi0);
+    board.enable(sysctl::Domain::Adc0);
+
+    let _porte = gpio_port!(p, E).split(&board.power_control);
+
+    // SSI0Clk, SSI0Rx and SSI0Tx
+    config::SSI0.connect();
+    let porta = board.porta;
+    let spi = Spi::ssi0(p.SSI0, MODE_0, 4_000_000_u32.hz(), &board.clocks);
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+
+    let mut rst = porta.pa6.into_push_pull_output();
+    let mut radio = Sx127x::new(
+        spi,
+        porta.pa3.into_push_pull_output(),
+        &mut rst,
+        &mut d,
+        FREQUENCY_HZ,
+    ).unwrap();
+
+    adc_init();
+
+    writeln!(board.tx, "LoRa telemetry demo, SF{}", radio.spreading_factor()).unwrap();
+
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+
+    // This ignores the time spent sending, so it runs a little slow
+    let mut uptime_ms = 0u32;
+    let mut last_send_ms = 0u32;
+    let mut sequence = 0u32;
+
+    loop {
+        while let Ok(ch) = board.rx.read() {
+            r.input_byte(ch);
+        }
+
+        if let Some(sf) = unsafe { NEW_SPREADING_FACTOR.take() } {
+            radio.set_spreading_factor(sf).unwrap();
+            writeln!(Console, "Now using SF{}", radio.spreading_factor()).unwrap();
+        }
+
+        let send_now = unsafe { core::mem::replace(&mut SEND_NOW, false) };
+        if send_now || uptime_ms.wrapping_sub(last_send_ms) >= SEND_INTERVAL_MS {
+            last_send_ms = uptime_ms;
+            let mut frame = Buffer::from_storage([0u8; sx127x::MAX_PAYLOAD]);
+            write!(
+                frame,
+                "#{} up {}s adc {}",
+                sequence,
+                uptime_ms / 1000,
+                adc_read()
+            ).unwrap();
+            radio.send(frame.as_bytes()).unwrap();
+            writeln!(Console, "Sent frame {}", sequence).unwrap();
+            sequence = sequence.wrapping_add(1);
+        }
+
+        match radio.receive() {
+            Ok(Some(packet)) => {
+                write!(
+                    Console,
+                    "RSSI {} dBm, SNR {} dB: ",
+                    packet.rssi,
+                    packet.snr_quarter_db / 4
+                ).unwrap();
+                for &byte in packet.payload() {
+                    let ch = match byte {
+                        0x20...0x7E => byte as char,
+                        _ => '.',
+                    };
+                    Console.write_char(ch).unwrap();
+                }
+                writeln!(Console, "").unwrap();
+            }
+            Ok(None) => {}
+            Err(sx127x::Error::BadCrc) => writeln!(Console, "Bad CRC").unwrap(),
+            Err(e) => panic!("Radio error: {:?}", e),
+        }
+
+        d.delay_ms(POLL_MS);
+        uptime_ms = uptime_ms.wrapping_add(POLL_MS);
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
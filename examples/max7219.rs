@@ -0,0 +1,140 @@
+//! Scrolls text across a chain of MAX7219 8x8 LED matrices.
+//!
+//! CLK is PA2 (SSI0Clk), DIN is PA5 (SSI0Tx) and CS is PA3.
+//!
+//! Type a line on UART0 (115200 bps) and press Enter to change the message.
+//! The characters come from the VGA font, squashed to 8 pixels high.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+
+use core::fmt::Write;
+use demo::config;
+use demo::font;
+use demo::hal;
+use demo::max7219::{self, Max7219};
+use demo::spi::Spi;
+use embedded_hal::prelude::*;
+use embedded_hal::spi::MODE_0;
+use hal::delay::Delay;
+use hal::sysctl;
+use hal::time::U32Ext;
+use rt::ExceptionFrame;
+
+/// How many modules in the chain.
+const DEVICES: usize = 4;
+
+/// Longest message we can scroll.
+const MAX_MESSAGE: usize = 64;
+
+/// Milliseconds per column scrolled.
+const SCROLL_MS: u32 = 40;
+
+/// Get column `col` of the message, where the message is padded with a
+/// screen's worth of blank columns so it scrolls in from the right.
+fn message_column(message: &[u8], col: usize) -> u8 {
+    let col = match col.checked_sub(DEVICES * 8) {
+        Some(c) => c,
+        None => return 0,
+    };
+    match message.get(col / font::WIDTH) {
+        Some(ch) => max7219::font_column(*ch, col % font::WIDTH),
+        None => 0,
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Ssi0);
+
+    // SSI0Clk and SSI0Tx
+    config::SSI0.connect();
+    let porta = board.porta;
+    let spi = Spi::ssi0(p.SSI0, MODE_0, 1_000_000_u32.hz(), &board.clocks);
+    let mut matrix = Max7219::new(spi, porta.pa3.into_push_pull_output(), DEVICES).unwrap();
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+
+    let mut message = [0u8; MAX_MESSAGE];
+    let mut message_len = 0;
+    for (dest, src) in message.iter_mut().zip(b"Hello, Monotron! ".iter()) {
+        *dest = *src;
+        message_len += 1;
+    }
+    let mut incoming = [0u8; MAX_MESSAGE];
+    let mut incoming_len = 0;
+
+    writeln!(board.tx, "MAX7219 scroller - type a message and press Enter").unwrap();
+
+    let mut offset = 0;
+    loop {
+        while let Ok(ch) = board.rx.read() {
+            match ch {
+                b'\r' | b'\n' => {
+                    board.tx.write_str("\n").unwrap();
+                    if incoming_len > 0 {
+                        message[0..incoming_len].copy_from_slice(&incoming[0..incoming_len]);
+                        message_len = incoming_len;
+                        incoming_len = 0;
+                        offset = 0;
+                    }
+                }
+                0x08 | 0x7F if incoming_len > 0 => {
+                    incoming_len -= 1;
+                    board.tx.write_str("\x08 \x08").unwrap();
+                }
+                0x20...0x7E if incoming_len < MAX_MESSAGE => {
+                    incoming[incoming_len] = ch;
+                    incoming_len += 1;
+                    board.tx.write_char(ch as char).unwrap();
+                }
+                _ => {}
+            }
+        }
+
+        let text = &message[0..message_len];
+        for row in 0..8 {
+            let mut data = [0u8; DEVICES];
+            for (device, byte) in data.iter_mut().enumerate() {
+                for bit in 0..8 {
+                    let col = offset + device * 8 + bit;
+                    if message_column(text, col) & (1 << row) != 0 {
+                        *byte |= 0x80 >> bit;
+                    }
+                }
+            }
+            matrix.write_row(row as u8, &data).unwrap();
+        }
+
+        offset += 1;
+        if offset > (DEVICES * 8) + (message_len * font::WIDTH) {
+            offset = 0;
+        }
+        d.delay_ms(SCROLL_MS);
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
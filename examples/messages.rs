@@ -0,0 +1,269 @@
+//! Two LaunchPads, both running this, swapping `demo::messages` over
+//! UART1.
+//!
+//! Wire PB1 (U1Tx) on each board to PB0 (U1Rx) on the other, and join the
+//! grounds. Each board sends its temperature, AIN0 (PE3) and switches once
+//! a second, and prints what the other one sent on UART0 (115200 bps).
+//!
+//! * SW1 - change the colour of the other board's LED
+//! * SW2 - make the other board send ten times a second, or back to once
+//!
+//! Hold SW2 during reset and the board claims to speak the next version
+//! of the messages. The other board then ignores what it sends, and says
+//! so (once), rather than trying to read it.
+//!
+//! Build with `--features messages`.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board, gpio_port)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+
+use core::fmt::Write;
+use demo::adc::{self, Adc};
+use demo::board::Board;
+use demo::cobs::{self, Input};
+use demo::fault;
+use demo::hal;
+use demo::messages::{self, Body, Command, Header, Sensors};
+use demo::pac;
+use demo::scheduler::{self, Task};
+use embedded_hal::prelude::*;
+use hal::gpio::GpioExt;
+use hal::serial::{NewlineMode, Rx, Serial, Tx};
+use hal::sysctl;
+use hal::time::U32Ext;
+use pac::{ADC0, UART1};
+
+const LINK_BAUD: u32 = 115_200;
+
+/// How often to send `Sensors`: slowly, or quickly after SW2.
+const SLOW_MS: u32 = 1000;
+const FAST_MS: u32 = 100;
+
+struct Context {
+    board: Board,
+    adc: Adc<ADC0>,
+    link_tx: Tx<UART1>,
+    link_rx: Rx<UART1>,
+    decoder: cobs::Decoder,
+    /// What we put in our headers
+    version: u16,
+    sequence: u16,
+    interval_ms: u32,
+    next_sensors_ms: u32,
+    /// Which colour we last asked the other board for
+    colour: u8,
+    /// Which interval we last asked the other board for
+    fast: bool,
+    sw1_was: bool,
+    sw2_was: bool,
+    /// The other board's version, if it isn't ours and we've said so
+    mismatch: Option<u16>,
+}
+
+static TASKS: [Task<Context>; 3] = [
+    Task {
+        name: "link",
+        period_ms: 0,
+        run: link,
+    },
+    Task {
+        name: "sensors",
+        period_ms: 1,
+        run: sensors,
+    },
+    Task {
+        name: "switches",
+        period_ms: 20,
+        run: switches,
+    },
+];
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Adc0);
+    demo::config::Port::E.enable();
+    adc::configure_pin(0);
+
+    let mut portb = gpio_port!(p, B).split(&board.power_control);
+    let uart1 = Serial::uart1(
+        p.UART1,
+        portb.pb1.into_af1(&mut portb.control),
+        portb.pb0.into_af1(&mut portb.control),
+        (),
+        (),
+        LINK_BAUD.bps(),
+        NewlineMode::Binary,
+        &board.clocks,
+        &board.power_control,
+    );
+    let (link_tx, link_rx) = uart1.split();
+
+    let version = if board.sw2.is_pressed() {
+        messages::VERSION + 1
+    } else {
+        messages::VERSION
+    };
+    writeln!(board.tx, "Messages, version {}", version).unwrap();
+
+    let clocks = board.clocks;
+    let mut context = Context {
+        board,
+        adc: Adc::adc0(p.ADC0),
+        link_tx,
+        link_rx,
+        decoder: cobs::Decoder::new(),
+        version,
+        sequence: 0,
+        interval_ms: SLOW_MS,
+        next_sensors_ms: 0,
+        colour: 0,
+        fast: false,
+        sw1_was: false,
+        // So holding it down at reset isn't a press
+        sw2_was: true,
+        mismatch: None,
+    };
+    send(&mut context, Body::Hello);
+    scheduler::start(cp.SYST, &clocks, &TASKS).run(&mut context);
+}
+
+/// Send a message, and give its sequence number.
+fn send(c: &mut Context, body: Body) -> u16 {
+    let header = Header {
+        version: c.version,
+        sequence: c.sequence,
+    };
+    c.sequence = c.sequence.wrapping_add(1);
+    let mut buffer = [0u8; messages::MAX_MESSAGE];
+    let len = messages::encode(&header, &body, &mut buffer).unwrap();
+    cobs::send(&mut c.link_tx, &buffer[0..len]).unwrap();
+    header.sequence
+}
+
+/// Deal with whatever the other board sent.
+fn link(c: &mut Context) {
+    while let Ok(byte) = c.link_rx.read() {
+        let message = match c.decoder.input(byte) {
+            Input::Packet(packet) => messages::decode(packet),
+            // There's nothing but packets on this line
+            _ => continue,
+        };
+        match message {
+            Ok((header, body)) => received(c, header, body),
+            Err(messages::Error::Version(theirs)) => {
+                if c.mismatch != Some(theirs) {
+                    c.mismatch = Some(theirs);
+                    let ours = c.version;
+                    writeln!(
+                        c.board.tx,
+                        "The other board speaks version {} and we speak {}, so we're ignoring it",
+                        theirs,
+                        ours
+                    ).unwrap();
+                    // So it finds out what we speak, too
+                    send(c, Body::Hello);
+                }
+            }
+            Err(e) => writeln!(c.board.tx, "Bad message: {:?}", e).unwrap(),
+        }
+    }
+}
+
+fn received(c: &mut Context, header: Header, body: Body) {
+    c.mismatch = None;
+    match body {
+        Body::Hello => writeln!(c.board.tx, "Hello from the other board").unwrap(),
+        Body::Sensors(s) => writeln!(
+            c.board.tx,
+            "#{}: up {} ms, {}{}.{}C, AIN0 {} mV, SW1 {}, SW2 {}",
+            header.sequence,
+            s.uptime_ms,
+            if s.temperature < 0 { "-" } else { "" },
+            (s.temperature / 10).abs(),
+            (s.temperature % 10).abs(),
+            s.ain0_mv,
+            if s.sw1 { "down" } else { "up" },
+            if s.sw2 { "down" } else { "up" }
+        ).unwrap(),
+        Body::Command(command) => {
+            match command {
+                Command::SetLed { red, green, blue } => {
+                    c.board.led_red.set(red);
+                    c.board.led_green.set(green);
+                    c.board.led_blue.set(blue);
+                }
+                Command::SetInterval(ms) => c.interval_ms = ms,
+            }
+            writeln!(c.board.tx, "#{}: {:?}", header.sequence, command).unwrap();
+            send(c, Body::Ack(header.sequence));
+        }
+        Body::Ack(sequence) => writeln!(c.board.tx, "Command #{} done", sequence).unwrap(),
+    }
+}
+
+/// Send a reading when it's due.
+fn sensors(c: &mut Context) {
+    let now = scheduler::now();
+    if (now.wrapping_sub(c.next_sensors_ms) as i32) < 0 {
+        return;
+    }
+    c.next_sensors_ms = now.wrapping_add(c.interval_ms);
+    let sensors = Sensors {
+        uptime_ms: now,
+        temperature: adc::temperature(c.adc.read(adc::TEMPERATURE)) as i16,
+        ain0_mv: adc::millivolts(c.adc.read(0)) as u16,
+        sw1: c.board.sw1.is_pressed(),
+        sw2: c.board.sw2.is_pressed(),
+    };
+    send(c, Body::Sensors(sensors));
+}
+
+/// Send a command when a switch goes down. Checking every 20 ms is slow
+/// enough that they don't bounce.
+fn switches(c: &mut Context) {
+    let sw1 = c.board.sw1.is_pressed();
+    let sw2 = c.board.sw2.is_pressed();
+    if sw1 && !c.sw1_was {
+        c.colour = (c.colour + 1) % 8;
+        let colour = c.colour;
+        let command = Command::SetLed {
+            red: colour & 1 != 0,
+            green: colour & 2 != 0,
+            blue: colour & 4 != 0,
+        };
+        let sequence = send(c, Body::Command(command));
+        writeln!(c.board.tx, "Sent #{}: {:?}", sequence, command).unwrap();
+    }
+    if sw2 && !c.sw2_was {
+        c.fast = !c.fast;
+        let command = Command::SetInterval(if c.fast { FAST_MS } else { SLOW_MS });
+        let sequence = send(c, Body::Command(command));
+        writeln!(c.board.tx, "Sent #{}: {:?}", sequence, command).unwrap();
+    }
+    c.sw1_was = sw1;
+    c.sw2_was = sw2;
+}
+
+exception!(SysTick, scheduler::tick);
+
+// Print what went wrong on UART0 and flash the red LED
+exception!(HardFault, fault::hard_fault);
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
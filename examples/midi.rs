@@ -0,0 +1,342 @@
+//! A MIDI sound module and controller.
+//!
+//! MIDI IN goes through the usual 6N138 opto-isolator to PB0 (U1Rx), and
+//! PB1 (U1Tx) drives MIDI OUT through a pair of 220R resistors (one to
+//! 3.3V). Incoming Note On and Note Off messages, on any channel, play up
+//! to three notes at once. Pressing SW1 and SW2 sends middle C and the E
+//! above it on MIDI OUT, and plays them locally as well.
+//!
+//! The three channels are square waves from timers in PWM mode - Timer1A
+//! on PB4, Timer1B on PB5 and Timer3A on PB2 (PD2, PD3 and PD4 on the
+//! TM4C1294). Mix them through a 1k resistor each into an amplifier (not
+//! straight into a speaker). Every note is logged on UART0 (115200 bps).
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board, gpio_port)]
+extern crate demo;
+extern crate embedded_hal;
+#[macro_use]
+extern crate nb;
+extern crate panic_halt;
+
+use core::fmt::Write;
+use demo::board::SYSCLK_HZ;
+use demo::config::{Pin, Port};
+use demo::hal;
+use demo::midi::{self, Message};
+use demo::pac::{self, timer0};
+use embedded_hal::prelude::*;
+use hal::gpio::GpioExt;
+use hal::serial::{NewlineMode, Serial};
+use hal::sysctl;
+use hal::time::U32Ext;
+use rt::ExceptionFrame;
+
+/// How many notes we can play at once.
+const VOICES: usize = 3;
+
+/// Timer clocks per cycle at 80 MHz for MIDI notes 0..11 (C-1 to B-1).
+/// Each octave up halves the period. The lowest fits in the 24 bits a
+/// 16-bit timer plus prescaler gives us, even at 120 MHz.
+const PERIODS: [u32; 12] = [
+    9_784_976, 9_235_788, 8_717_423, 8_228_152, 7_766_341, 7_330_450, 6_919_023, 6_530_688,
+    6_164_149, 5_818_182, 5_491_632, 5_183_411,
+];
+
+/// The notes the buttons play, and on which channel.
+const SW1_NOTE: u8 = midi::MIDDLE_C;
+const SW2_NOTE: u8 = midi::MIDDLE_C + 4;
+const OUT_CHANNEL: u8 = 0;
+const OUT_VELOCITY: u8 = 100;
+
+/// How often we look at the buttons (every 5ms). Bounces shorter than
+/// this get missed, which is what we want.
+const BUTTON_POLL_CLOCKS: u32 = SYSCLK_HZ / 200;
+
+/// T1CCP0, T1CCP1 and T3CCP0, and their alternate function.
+#[cfg(feature = "tm4c123")]
+const VOICE_PINS: [Pin; VOICES] = [
+    Pin { port: Port::B, bit: 4 },
+    Pin { port: Port::B, bit: 5 },
+    Pin { port: Port::B, bit: 2 },
+];
+#[cfg(feature = "tm4c123")]
+const VOICE_FUNCTION: u32 = 7;
+#[cfg(feature = "tm4c129")]
+const VOICE_PINS: [Pin; VOICES] = [
+    Pin { port: Port::D, bit: 2 },
+    Pin { port: Port::D, bit: 3 },
+    Pin { port: Port::D, bit: 4 },
+];
+#[cfg(feature = "tm4c129")]
+const VOICE_FUNCTION: u32 = 3;
+
+// GPTMCFG, GPTMTnMR, GPTMCTL
+const CFG_16_BIT: u32 = 0x4;
+const CFG_32_BIT: u32 = 0x0;
+const MR_PWM: u32 = 0x2 | (1 << 3);
+const MR_PERIODIC: u32 = 0x2;
+const CTL_TAEN: u32 = 1 << 0;
+const CTL_TBEN: u32 = 1 << 8;
+
+/// One square wave output, on one half of a timer.
+struct Voice {
+    timer: &'static timer0::RegisterBlock,
+    b_half: bool,
+    note: Option<u8>,
+    /// When the note started, so we can steal the oldest voice.
+    started: u32,
+}
+
+impl Voice {
+    fn new(timer: &'static timer0::RegisterBlock, b_half: bool) -> Voice {
+        if b_half {
+            timer.tbmr.write(|w| unsafe { w.bits(MR_PWM) });
+        } else {
+            timer.tamr.write(|w| unsafe { w.bits(MR_PWM) });
+        }
+        Voice {
+            timer,
+            b_half,
+            note: None,
+            started: 0,
+        }
+    }
+
+    fn play(&mut self, note: u8, now: u32) {
+        // Count from the top, with the output high for the first half of
+        // the cycle. The prescale registers hold bits 23:16.
+        let period = (PERIODS[(note % 12) as usize] / 80) * (SYSCLK_HZ / 1_000_000);
+        let load = (period >> (note / 12)) - 1;
+        let matched = load / 2;
+        let t = self.timer;
+        if self.b_half {
+            t.ctl.modify(|r, w| unsafe { w.bits(r.bits() & !CTL_TBEN) });
+            t.tbilr.write(|w| unsafe { w.bits(load & 0xFFFF) });
+            t.tbpr.write(|w| unsafe { w.bits(load >> 16) });
+            t.tbmatchr.write(|w| unsafe { w.bits(matched & 0xFFFF) });
+            t.tbpmr.write(|w| unsafe { w.bits(matched >> 16) });
+            t.ctl.modify(|r, w| unsafe { w.bits(r.bits() | CTL_TBEN) });
+        } else {
+            t.ctl.modify(|r, w| unsafe { w.bits(r.bits() & !CTL_TAEN) });
+            t.tailr.write(|w| unsafe { w.bits(load & 0xFFFF) });
+            t.tapr.write(|w| unsafe { w.bits(load >> 16) });
+            t.tamatchr.write(|w| unsafe { w.bits(matched & 0xFFFF) });
+            t.tapmr.write(|w| unsafe { w.bits(matched >> 16) });
+            t.ctl.modify(|r, w| unsafe { w.bits(r.bits() | CTL_TAEN) });
+        }
+        self.note = Some(note);
+        self.started = now;
+    }
+
+    fn stop(&mut self) {
+        let bit = if self.b_half { CTL_TBEN } else { CTL_TAEN };
+        self.timer
+            .ctl
+            .modify(|r, w| unsafe { w.bits(r.bits() & !bit) });
+        self.note = None;
+    }
+}
+
+/// Hands out voices to notes.
+struct Synth {
+    voices: [Voice; VOICES],
+    /// Counts notes, to tell which is oldest.
+    clock: u32,
+}
+
+impl Synth {
+    /// Start a note, returning which voice got it. If every voice is busy,
+    /// the one that's been playing longest gets cut off.
+    fn note_on(&mut self, note: u8) -> usize {
+        self.clock = self.clock.wrapping_add(1);
+        let mut chosen = 0;
+        for (i, v) in self.voices.iter().enumerate() {
+            if v.note.is_none() || v.note == Some(note) {
+                chosen = i;
+                break;
+            }
+            if v.started < self.voices[chosen].started {
+                chosen = i;
+            }
+        }
+        self.voices[chosen].play(note, self.clock);
+        chosen
+    }
+
+    fn note_off(&mut self, note: u8) {
+        for v in self.voices.iter_mut() {
+            if v.note == Some(note) {
+                v.stop();
+            }
+        }
+    }
+}
+
+fn log_note<W: Write>(w: &mut W, message: &Message, voice: Option<usize>) {
+    match *message {
+        Message::NoteOn {
+            channel,
+            note,
+            velocity,
+        } => {
+            let (name, octave) = midi::note_name(note);
+            write!(
+                w,
+                "On  {}{} ch {} vel {}",
+                name,
+                octave,
+                channel + 1,
+                velocity
+            ).unwrap();
+        }
+        Message::NoteOff { channel, note } => {
+            let (name, octave) = midi::note_name(note);
+            write!(w, "Off {}{} ch {}", name, octave, channel + 1).unwrap();
+        }
+    }
+    match voice {
+        Some(v) => writeln!(w, " -> voice {}", v).unwrap(),
+        None => writeln!(w).unwrap(),
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Timer1);
+    board.enable(sysctl::Domain::Timer2);
+    board.enable(sysctl::Domain::Timer3);
+
+    let mut portb = gpio_port!(p, B).split(&board.power_control);
+
+    // MIDI IN and OUT
+    let uart1 = Serial::uart1(
+        p.UART1,
+        portb.pb1.into_af1(&mut portb.control),
+        portb.pb0.into_af1(&mut portb.control),
+        (),
+        (),
+        midi::BAUD_RATE.bps(),
+        NewlineMode::Binary,
+        &board.clocks,
+        &board.power_control,
+    );
+    let (mut midi_out, mut midi_in) = uart1.split();
+
+    for pin in VOICE_PINS.iter() {
+        pin.into_af(VOICE_FUNCTION);
+    }
+    let timer1 = unsafe { &*pac::TIMER1::ptr() };
+    let timer3 = unsafe { &*pac::TIMER3::ptr() };
+    timer1.ctl.write(|w| unsafe { w.bits(0) });
+    timer1.cfg.write(|w| unsafe { w.bits(CFG_16_BIT) });
+    timer3.ctl.write(|w| unsafe { w.bits(0) });
+    timer3.cfg.write(|w| unsafe { w.bits(CFG_16_BIT) });
+    let mut synth = Synth {
+        voices: [
+            Voice::new(timer1, false),
+            Voice::new(timer1, true),
+            Voice::new(timer3, false),
+        ],
+        clock: 0,
+    };
+
+    // Timer2A paces the button polling
+    let ticker = p.TIMER2;
+    ticker.ctl.write(|w| unsafe { w.bits(0) });
+    ticker.cfg.write(|w| unsafe { w.bits(CFG_32_BIT) });
+    ticker.tamr.write(|w| unsafe { w.bits(MR_PERIODIC) });
+    ticker
+        .tailr
+        .write(|w| unsafe { w.bits(BUTTON_POLL_CLOCKS - 1) });
+    ticker.icr.write(|w| w.tatocint().set_bit());
+    ticker.ctl.write(|w| unsafe { w.bits(CTL_TAEN) });
+
+    writeln!(board.tx, "MIDI demo").unwrap();
+
+    let mut parser = midi::Parser::new();
+    let mut sw1_down = false;
+    let mut sw2_down = false;
+
+    loop {
+        if let Ok(byte) = midi_in.read() {
+            if let Some(message) = parser.input(byte) {
+                let voice = match message {
+                    Message::NoteOn { note, .. } => Some(synth.note_on(note)),
+                    Message::NoteOff { note, .. } => {
+                        synth.note_off(note);
+                        None
+                    }
+                };
+                log_note(&mut board.tx, &message, voice);
+            }
+        }
+
+        if ticker.ris.read().tatoris().bit_is_clear() {
+            continue;
+        }
+        ticker.icr.write(|w| w.tatocint().set_bit());
+
+        // The buttons pull down when pressed
+        if board.sw1.is_pressed() != sw1_down {
+            sw1_down = !sw1_down;
+            button(sw1_down, SW1_NOTE, &mut synth, &mut midi_out, &mut board.tx);
+        }
+        if board.sw2.is_pressed() != sw2_down {
+            sw2_down = !sw2_down;
+            button(sw2_down, SW2_NOTE, &mut synth, &mut midi_out, &mut board.tx);
+        }
+    }
+}
+
+/// A button has gone down or up. Send the note, and play it too.
+fn button<TX, W>(pressed: bool, note: u8, synth: &mut Synth, out: &mut TX, log: &mut W)
+where
+    TX: embedded_hal::serial::Write<u8>,
+    TX::Error: core::fmt::Debug,
+    W: Write,
+{
+    let message = if pressed {
+        Message::NoteOn {
+            channel: OUT_CHANNEL,
+            note,
+            velocity: OUT_VELOCITY,
+        }
+    } else {
+        Message::NoteOff {
+            channel: OUT_CHANNEL,
+            note,
+        }
+    };
+    for byte in message.to_bytes().iter() {
+        block!(out.write(*byte)).unwrap();
+    }
+    let voice = if pressed {
+        Some(synth.note_on(note))
+    } else {
+        synth.note_off(note);
+        None
+    };
+    log_note(log, &message, voice);
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,218 @@
+//! Makes the LaunchPad a Modbus RTU slave on an RS-485 bus.
+//!
+//! Wire a MAX3485 as for the `rs485_modbus` example: DI to PB1 (U1Tx), RO to
+//! PB0 (U1Rx) and DE and /RE together to PB5. We're slave `SLAVE` at 9600
+//! bps, 8N1, with these registers (any of the read functions will do):
+//!
+//! | Address | Contents                                         |
+//! |---------|--------------------------------------------------|
+//! | 0       | LEDs: bit 0 red, bit 1 blue, bit 2 green (R/W)   |
+//! | 1       | AIN0 (PE3) in millivolts                         |
+//! | 2       | AIN1 (PE2) in millivolts                         |
+//! | 3       | Chip temperature, tenths of a degree C (signed)  |
+//! | 4       | Uptime in seconds, top 16 bits                   |
+//! | 5       | Uptime in seconds, bottom 16 bits                |
+//!
+//! Try it from a PC with a USB RS-485 dongle and `mbpoll -a 1 -b 9600 -P
+//! none -r 1 -c 6 /dev/ttyUSB0` (note mbpoll counts registers from 1).
+//!
+//! Timer2A measures the gaps between bytes: each byte restarts it, and when
+//! it runs out the frame is over. Timer3A counts the seconds. Requests are
+//! logged on UART0 (115200 bps).
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board, gpio_port)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+
+use core::fmt::Write;
+use demo::adc::{self, Adc};
+use demo::board::SYSCLK_HZ;
+use demo::hal;
+use demo::modbus::{self, Registers};
+use demo::pac;
+use demo::rs485::Rs485;
+use embedded_hal::prelude::*;
+use hal::gpio::GpioExt;
+use hal::serial::{NewlineMode, Serial};
+use hal::sysctl;
+use hal::time::U32Ext;
+use rt::ExceptionFrame;
+
+/// Our address on the bus.
+const SLAVE: u8 = 1;
+
+const BAUD_RATE: u32 = 9600;
+
+const CFG_32_BIT: u32 = 0x0;
+const MR_ONE_SHOT: u32 = 0x1;
+const MR_PERIODIC: u32 = 0x2;
+const CTL_TAEN: u32 = 1 << 0;
+
+const REG_LEDS: u16 = 0;
+const REG_AIN0: u16 = 1;
+const REG_AIN1: u16 = 2;
+const REG_TEMPERATURE: u16 = 3;
+const REG_UPTIME_HIGH: u16 = 4;
+const REG_UPTIME_LOW: u16 = 5;
+
+const LED_RED: u16 = 1 << 0;
+const LED_BLUE: u16 = 1 << 1;
+const LED_GREEN: u16 = 1 << 2;
+
+/// What we show the bus.
+struct Slave {
+    leds: u16,
+    adc: Adc<pac::ADC0>,
+    uptime: u32,
+}
+
+impl Registers for Slave {
+    fn read(&mut self, address: u16) -> Option<u16> {
+        match address {
+            REG_LEDS => Some(self.leds),
+            REG_AIN0 => Some(adc::millivolts(self.adc.read(0)) as u16),
+            REG_AIN1 => Some(adc::millivolts(self.adc.read(1)) as u16),
+            REG_TEMPERATURE => Some(adc::temperature(self.adc.read(adc::TEMPERATURE)) as u16),
+            REG_UPTIME_HIGH => Some((self.uptime >> 16) as u16),
+            REG_UPTIME_LOW => Some(self.uptime as u16),
+            _ => None,
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u16) -> bool {
+        match address {
+            REG_LEDS => {
+                self.leds = value & (LED_RED | LED_BLUE | LED_GREEN);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Adc0);
+    board.enable(sysctl::Domain::Timer2);
+    board.enable(sysctl::Domain::Timer3);
+
+    let mut portb = gpio_port!(p, B).split(&board.power_control);
+    let _porte = gpio_port!(p, E).split(&board.power_control);
+
+    // The RS-485 side
+    let uart1 = Serial::uart1(
+        p.UART1,
+        portb.pb1.into_af1(&mut portb.control),
+        portb.pb0.into_af1(&mut portb.control),
+        (),
+        (),
+        BAUD_RATE.bps(),
+        NewlineMode::Binary,
+        &board.clocks,
+        &board.power_control,
+    );
+    let (tx1, mut rx1) = uart1.split();
+    let de = portb.pb5.into_push_pull_output();
+    let mut bus = Rs485::new(tx1, de, unsafe { &*pac::UART1::ptr() });
+
+    adc::configure_pin(0);
+    adc::configure_pin(1);
+    let mut slave = Slave {
+        leds: 0,
+        adc: Adc::adc0(p.ADC0),
+        uptime: 0,
+    };
+
+    // Timer2A runs out when the line has been quiet long enough. Writing
+    // the load register restarts the count straight away.
+    let silence_clocks = (SYSCLK_HZ / 1_000_000) * modbus::silent_interval_us(BAUD_RATE);
+    let silence = p.TIMER2;
+    silence.ctl.write(|w| unsafe { w.bits(0) });
+    silence.cfg.write(|w| unsafe { w.bits(CFG_32_BIT) });
+    silence.tamr.write(|w| unsafe { w.bits(MR_ONE_SHOT) });
+
+    // Timer3A ticks once a second
+    let ticker = p.TIMER3;
+    ticker.ctl.write(|w| unsafe { w.bits(0) });
+    ticker.cfg.write(|w| unsafe { w.bits(CFG_32_BIT) });
+    ticker.tamr.write(|w| unsafe { w.bits(MR_PERIODIC) });
+    ticker.tailr.write(|w| unsafe { w.bits(SYSCLK_HZ - 1) });
+    ticker.icr.write(|w| w.tatocint().set_bit());
+    ticker.ctl.write(|w| unsafe { w.bits(CTL_TAEN) });
+
+    writeln!(board.tx, "Modbus RTU slave {} at {} bps", SLAVE, BAUD_RATE).unwrap();
+
+    let mut request = [0u8; modbus::MAX_FRAME];
+    let mut reply = [0u8; modbus::MAX_FRAME];
+    let mut len = 0;
+    // Set if the frame was too long, so we ignore all of it
+    let mut overflow = false;
+
+    loop {
+        while let Ok(byte) = rx1.read() {
+            if len < request.len() {
+                request[len] = byte;
+                len += 1;
+            } else {
+                overflow = true;
+            }
+            silence.tailr.write(|w| unsafe { w.bits(silence_clocks) });
+            silence.icr.write(|w| w.tatocint().set_bit());
+            silence.ctl.write(|w| unsafe { w.bits(CTL_TAEN) });
+        }
+
+        if (len > 0 || overflow) && silence.ris.read().tatoris().bit_is_set() {
+            silence.icr.write(|w| w.tatocint().set_bit());
+            if overflow {
+                writeln!(board.tx, "Frame too long").unwrap();
+            } else if !modbus::crc_ok(&request[0..len]) {
+                writeln!(board.tx, "Bad frame ({} bytes)", len).unwrap();
+            } else {
+                let reply_len = modbus::serve(SLAVE, &request[0..len], &mut slave, &mut reply);
+                if reply_len > 0 {
+                    bus.send(&reply[0..reply_len]).unwrap();
+                    write!(board.tx, "Slave {} function {}", request[0], request[1]).unwrap();
+                    if reply[1] & modbus::EXCEPTION != 0 {
+                        writeln!(board.tx, " - exception {}", reply[2]).unwrap();
+                    } else {
+                        writeln!(board.tx, " - OK").unwrap();
+                    }
+                }
+            }
+            len = 0;
+            overflow = false;
+        }
+
+        if ticker.ris.read().tatoris().bit_is_set() {
+            ticker.icr.write(|w| w.tatocint().set_bit());
+            slave.uptime = slave.uptime.wrapping_add(1);
+        }
+
+        board.led_red.set(slave.leds & LED_RED != 0);
+        board.led_blue.set(slave.leds & LED_BLUE != 0);
+        board.led_green.set(slave.leds & LED_GREEN != 0);
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
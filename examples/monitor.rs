@@ -0,0 +1,666 @@
+//! A machine-code monitor, like the ones 1980s home computers had.
+//!
+//! Type commands on UART0 (115200 bps) and the answers appear on the VGA
+//! screen (HSYNC on PB6, VSYNC on PC4 and green on PB7, as `hello_vga`).
+//! All numbers are in hex.
+//!
+//! Commands:
+//!
+//! * `mem <addr> [len]` - dump some memory
+//! * `peek <addr>` - read a word
+//! * `poke <addr> <byte>...` - write some bytes
+//! * `pokew <addr> <word>` - write a word
+//! * `list [addr] [count]` - disassemble some Thumb code
+//! * `go <addr>` - call some code
+//! * `regs` - show the registers from the last breakpoint
+//! * `cont` - carry on after a breakpoint
+//! * `hexedit <ram|flash|eeprom> [addr]` - page through memory, and change it
+//! * `hexedit file <path> [offset]` - the same, for a file on the SD card
+//!
+//! A `bkpt` instruction stops the program and drops you back into the
+//! monitor, with the registers saved, until you type `cont`. For example,
+//! using the scratch area whose address is shown at start-up:
+//!
+//! ```text
+//! pokew 20000100 4770be00
+//! go 20000100
+//! ```
+//!
+//! That's `bkpt #0` then `bx lr`. We catch the breakpoint with the
+//! DebugMonitor exception, so it won't work with a debugger attached -
+//! that gets it first.
+//!
+//! Reading or writing memory that isn't there gives you a HardFault, just
+//! like the real thing.
+//!
+//! `hexedit` takes over the screen with `demo::hexedit`, driven by the
+//! arrow keys on your terminal, until you press Escape. EEPROM addresses
+//! count bytes from the start of the EEPROM, and file offsets from the
+//! start of the file. The SD card is on SSI0 - SCK is PA2 (SSI0Clk), MISO is
+//! PA4 (SSI0Rx), MOSI is PA5 (SSI0Tx) and chip select is PA3, as in
+//! `sd_files` - and is only looked at when we start. Paths start from its
+//! root directory, and files can be changed but not made any longer.
+
+#![feature(asm)]
+#![feature(naked_functions)]
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+extern crate vga_framebuffer as fb;
+
+use core::fmt::{self, Write};
+use demo::board::Board;
+use demo::config;
+use demo::console::Console;
+use demo::eeprom::Eeprom;
+use demo::fat::{Dir, Volume};
+use demo::graphics::{Canvas, Colour};
+use demo::hexedit::{EepromBytes, FileBytes, HexEditor, Memory, Ram, Rom};
+use demo::input::{self, InputEvent};
+use demo::sdcard::SdCard;
+use demo::spi::Spi;
+use demo::thumb;
+use demo::tui::Response;
+use demo::vga;
+use embedded_hal::prelude::*;
+use embedded_hal::spi::MODE_0;
+use menu::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::delay::Delay;
+use tm4c123x_hal::gpio::gpioa::PA3;
+use tm4c123x_hal::gpio::{Output, PushPull};
+use tm4c123x_hal::sysctl::{self, Clocks};
+use tm4c123x_hal::time::U32Ext;
+use cortex_m::asm;
+
+/// Somewhere to put your own code, in words so it's aligned.
+const SCRATCH_WORDS: usize = 256;
+
+/// How much `mem` shows if you don't say.
+const DUMP_LEN: u32 = 0x40;
+
+/// How many instructions `list` shows if you don't say.
+const LIST_COUNT: u32 = 16;
+
+/// Clock cycles in a millisecond, at 80 MHz.
+const CYCLES_PER_MS: u32 = 80_000;
+
+/// How long a lone ESC from the terminal waits to become the Escape key.
+const ESCAPE_MS: usize = 50;
+
+/// DEMCR.MON_EN routes `bkpt` to the DebugMonitor exception.
+const DEMCR_MON_EN: u32 = 1 << 16;
+
+/// DebugMonitor's slot in SHPR (exception 12, less 4).
+const SHPR_DEBUG_MONITOR: usize = 8;
+
+/// The lowest priority (the TM4C123 has three priority bits), so the VGA
+/// interrupts carry on while we're stopped.
+const LOWEST_PRIORITY: u8 = 0xE0;
+
+/// EXC_RETURN bit 4 is clear if the FPU state was stacked too.
+const EXC_RETURN_NO_FPU: u32 = 1 << 4;
+
+/// xPSR bit 9 says the hardware added a word to align the stack.
+const XPSR_STACK_ALIGN: u32 = 1 << 9;
+
+/// Cards start at 400 kHz or less.
+const INIT_HZ: u32 = 400_000;
+
+/// And then most can go this fast.
+const FAST_HZ: u32 = 20_000_000;
+
+type Card = SdCard<Spi<tm4c123x::SSI0>, PA3<Output<PushPull>>>;
+
+static mut SCRATCH: [u32; SCRATCH_WORDS] = [0; SCRATCH_WORDS];
+
+/// The text console, so callbacks and the break handler can use it.
+static mut SCREEN: Option<*mut Write> = None;
+
+/// Set while we're sat in a breakpoint.
+static mut STOPPED: bool = false;
+
+/// The registers from the last breakpoint.
+static mut REGS: Option<Registers> = None;
+
+/// Where `list` carries on from.
+static mut LIST_NEXT: u32 = 0;
+
+/// For `hexedit eeprom`, if it came up.
+static mut EEPROM: Option<Eeprom> = None;
+
+/// For `hexedit file`, if there's a card with a filesystem on it.
+static mut VOLUME: Option<Volume<Card>> = None;
+
+struct Registers {
+    /// r0 to r12
+    r: [u32; 13],
+    sp: u32,
+    lr: u32,
+    pc: u32,
+    xpsr: u32,
+}
+
+/// Writes to the VGA screen.
+struct Screen;
+
+impl Write for Screen {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match unsafe { SCREEN } {
+            Some(screen) => unsafe { (*screen).write_str(s) },
+            None => Ok(()),
+        }
+    }
+}
+
+const MEM_ITEM: Item = Item {
+    item_type: ItemType::Callback(mem_callback),
+    command: "mem",
+    help: Some("<addr> [len] - dump memory"),
+};
+
+const PEEK_ITEM: Item = Item {
+    item_type: ItemType::Callback(peek_callback),
+    command: "peek",
+    help: Some("<addr> - read a word"),
+};
+
+const POKE_ITEM: Item = Item {
+    item_type: ItemType::Callback(poke_callback),
+    command: "poke",
+    help: Some("<addr> <byte>... - write bytes"),
+};
+
+const POKEW_ITEM: Item = Item {
+    item_type: ItemType::Callback(pokew_callback),
+    command: "pokew",
+    help: Some("<addr> <word> - write a word"),
+};
+
+const LIST_ITEM: Item = Item {
+    item_type: ItemType::Callback(list_callback),
+    command: "list",
+    help: Some("[addr] [count] - disassemble"),
+};
+
+const GO_ITEM: Item = Item {
+    item_type: ItemType::Callback(go_callback),
+    command: "go",
+    help: Some("<addr> - call some code"),
+};
+
+const REGS_ITEM: Item = Item {
+    item_type: ItemType::Callback(regs_callback),
+    command: "regs",
+    help: Some("show the registers"),
+};
+
+const CONT_ITEM: Item = Item {
+    item_type: ItemType::Callback(cont_callback),
+    command: "cont",
+    help: Some("carry on after a breakpoint"),
+};
+
+const HEXEDIT_ITEM: Item = Item {
+    item_type: ItemType::Callback(hexedit_callback),
+    command: "hexedit",
+    help: Some("<ram|flash|eeprom> [addr] | file <path> [offset] - edit"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[
+        &MEM_ITEM,
+        &PEEK_ITEM,
+        &POKE_ITEM,
+        &POKEW_ITEM,
+        &LIST_ITEM,
+        &GO_ITEM,
+        &REGS_ITEM,
+        &CONT_ITEM,
+        &HEXEDIT_ITEM,
+    ],
+    entry: None,
+    exit: None,
+};
+
+/// The `n`th word of the command, as hex.
+fn hex_arg(input: &str, n: usize) -> Option<u32> {
+    input
+        .split_whitespace()
+        .nth(n)
+        .and_then(|arg| u32::from_str_radix(arg.trim_left_matches("0x"), 16).ok())
+}
+
+fn read_u8(address: u32) -> u8 {
+    unsafe { core::ptr::read_volatile(address as *const u8) }
+}
+
+fn read_u16(address: u32) -> u16 {
+    unsafe { core::ptr::read_volatile(address as *const u16) }
+}
+
+fn read_u32(address: u32) -> u32 {
+    unsafe { core::ptr::read_volatile(address as *const u32) }
+}
+
+fn mem_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let address = match hex_arg(input, 1) {
+        Some(address) => address,
+        None => {
+            writeln!(Screen, "Usage: mem <addr> [len]").unwrap();
+            return;
+        }
+    };
+    let len = hex_arg(input, 2).unwrap_or(DUMP_LEN);
+    // Eight bytes a line, to fit on the screen
+    let rows = ((address & 7) + len + 7) / 8;
+    for row in 0..rows {
+        let start = (address & !7).wrapping_add(row * 8);
+        write!(Screen, "{:08x}:", start).unwrap();
+        for i in 0..8 {
+            write!(Screen, " {:02x}", read_u8(start.wrapping_add(i))).unwrap();
+        }
+        write!(Screen, " ").unwrap();
+        for i in 0..8 {
+            let ch = match read_u8(start.wrapping_add(i)) {
+                byte @ 0x20...0x7E => byte as char,
+                _ => '.',
+            };
+            write!(Screen, "{}", ch).unwrap();
+        }
+        writeln!(Screen).unwrap();
+    }
+}
+
+fn peek_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match hex_arg(input, 1) {
+        Some(address) => {
+            let address = address & !3;
+            let value = read_u32(address);
+            writeln!(Screen, "{:08x}: {:08x} ({})", address, value, value).unwrap();
+        }
+        None => writeln!(Screen, "Usage: peek <addr>").unwrap(),
+    }
+}
+
+fn poke_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let address = match hex_arg(input, 1) {
+        Some(address) => address,
+        None => {
+            writeln!(Screen, "Usage: poke <addr> <byte>...").unwrap();
+            return;
+        }
+    };
+    let mut count = 0;
+    for arg in input.split_whitespace().skip(2) {
+        match u8::from_str_radix(arg, 16) {
+            Ok(byte) => unsafe {
+                core::ptr::write_volatile(address.wrapping_add(count) as *mut u8, byte);
+                count += 1;
+            },
+            Err(_) => {
+                writeln!(Screen, "Bad byte {}", arg).unwrap();
+                break;
+            }
+        }
+    }
+    writeln!(Screen, "{} bytes written", count).unwrap();
+}
+
+fn pokew_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match (hex_arg(input, 1), hex_arg(input, 2)) {
+        (Some(address), Some(value)) => {
+            let address = address & !3;
+            unsafe { core::ptr::write_volatile(address as *mut u32, value) };
+            writeln!(Screen, "{:08x}: {:08x}", address, read_u32(address)).unwrap();
+        }
+        _ => writeln!(Screen, "Usage: pokew <addr> <word>").unwrap(),
+    }
+}
+
+fn list_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let mut address = hex_arg(input, 1).unwrap_or(unsafe { LIST_NEXT }) & !1;
+    let count = hex_arg(input, 2).unwrap_or(LIST_COUNT);
+    for _ in 0..count {
+        let first = read_u16(address);
+        let len = thumb::length(first);
+        let second = if len == 4 {
+            read_u16(address.wrapping_add(2))
+        } else {
+            0
+        };
+        if len == 4 {
+            write!(Screen, "{:08x}: {:04x} {:04x}  ", address, first, second).unwrap();
+        } else {
+            write!(Screen, "{:08x}: {:04x}       ", address, first).unwrap();
+        }
+        thumb::disassemble(&mut Screen, address, first, second).unwrap();
+        writeln!(Screen).unwrap();
+        address = address.wrapping_add(len);
+    }
+    unsafe { LIST_NEXT = address };
+}
+
+fn go_callback(_menu: &Menu, _item: &Item, input: &str) {
+    if unsafe { STOPPED } {
+        // Another breakpoint in there would be a HardFault
+        writeln!(Screen, "Stopped - cont first").unwrap();
+        return;
+    }
+    let address = match hex_arg(input, 1) {
+        Some(address) => address,
+        None => {
+            writeln!(Screen, "Usage: go <addr>").unwrap();
+            return;
+        }
+    };
+    writeln!(Screen, "Calling 0x{:08x}", address).unwrap();
+    // It's Thumb code, so set the bottom bit
+    let code: extern "C" fn() = unsafe { core::mem::transmute((address | 1) as usize) };
+    code();
+    writeln!(Screen, "It came back").unwrap();
+}
+
+fn regs_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    match unsafe { REGS.as_ref() } {
+        Some(regs) => show_registers(regs),
+        None => writeln!(Screen, "No breakpoint yet").unwrap(),
+    }
+}
+
+fn cont_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    if unsafe { STOPPED } {
+        unsafe { STOPPED = false };
+    } else {
+        writeln!(Screen, "Not stopped").unwrap();
+    }
+}
+
+fn hexedit_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let mut ram = Ram;
+    let mut rom = Rom;
+    let mut eeprom_bytes;
+    let mut file_bytes;
+    let kind = input.split_whitespace().nth(1);
+    let memory: &mut Memory = match kind {
+        Some("ram") => &mut ram,
+        Some("flash") => &mut rom,
+        Some("file") => {
+            let path = match input.split_whitespace().nth(2) {
+                Some(path) => path,
+                None => {
+                    writeln!(Screen, "Usage: hexedit file <path> [offset]").unwrap();
+                    return;
+                }
+            };
+            let volume = match unsafe { VOLUME.as_mut() } {
+                Some(volume) => volume,
+                None => {
+                    writeln!(Screen, "No SD card").unwrap();
+                    return;
+                }
+            };
+            match volume.open(Dir::root(), path) {
+                Ok(file) => {
+                    file_bytes = FileBytes::new(volume, file, path);
+                    &mut file_bytes
+                }
+                Err(e) => {
+                    writeln!(Screen, "{}: {}", path, e).unwrap();
+                    return;
+                }
+            }
+        }
+        Some("eeprom") => match unsafe { EEPROM.as_mut() } {
+            Some(eeprom) => {
+                eeprom_bytes = EepromBytes::new(eeprom);
+                &mut eeprom_bytes
+            }
+            None => {
+                writeln!(Screen, "No EEPROM").unwrap();
+                return;
+            }
+        },
+        _ => {
+            writeln!(Screen, "Usage: hexedit <ram|flash|eeprom> [addr]").unwrap();
+            return;
+        }
+    };
+    let offset = hex_arg(input, if kind == Some("file") { 3 } else { 2 })
+        .map(|address| (address as usize).wrapping_sub(memory.start()))
+        .unwrap_or(0);
+    if offset >= memory.size() {
+        writeln!(Screen, "That's not in there").unwrap();
+        return;
+    }
+
+    let fb = vga::framebuffer();
+    fb.clear(Colour::BLACK);
+    let mut editor = HexEditor::new(memory, offset);
+    editor.draw(fb, memory);
+
+    // The main loop's waiting for us, so we read the UART ourselves
+    let mut keys = input::Decoder::new();
+    let mut idle = 0;
+    loop {
+        match Console.read_byte() {
+            Some(byte) => {
+                keys.input(byte);
+                idle = 0;
+            }
+            None => {
+                asm::delay(CYCLES_PER_MS);
+                idle += 1;
+                if idle == ESCAPE_MS {
+                    keys.timeout();
+                }
+            }
+        }
+        while let Some(event) = input::next() {
+            if let InputEvent::KeyDown(event) = event {
+                if editor.key(memory, event.key) == Response::Cancelled {
+                    // The text console carries on below what it had
+                    fb.clear(Colour::BLACK);
+                    return;
+                }
+                editor.draw(fb, memory);
+            }
+        }
+    }
+}
+
+/// Wake the card up and find its filesystem, for `hexedit file`.
+fn mount(mut card: Card, delay: &mut Delay, clocks: &Clocks) {
+    card.spi().set_frequency(INIT_HZ.hz(), clocks);
+    if card.init(delay).is_err() {
+        return;
+    }
+    card.spi().set_frequency(FAST_HZ.hz(), clocks);
+    match Volume::mount(card) {
+        Ok(volume) => {
+            writeln!(
+                Screen,
+                "SD card, {} KiB of {}",
+                volume.size_kib(),
+                volume.fat_name()
+            ).unwrap();
+            unsafe { VOLUME = Some(volume) };
+        }
+        Err((_card, e)) => writeln!(Screen, "SD card: {}", e).unwrap(),
+    }
+}
+
+fn show_registers(regs: &Registers) {
+    for (i, value) in regs.r.iter().enumerate() {
+        let name_width = if i < 10 { 2 } else { 1 };
+        write!(Screen, "r{}{:w$}{:08x}", i, "", value, w = name_width).unwrap();
+        if i % 3 == 2 {
+            writeln!(Screen).unwrap();
+        } else {
+            write!(Screen, " ").unwrap();
+        }
+    }
+    writeln!(Screen, "sp  {:08x}", regs.sp).unwrap();
+    writeln!(Screen, "lr  {:08x} pc  {:08x} psr {:08x}", regs.lr, regs.pc, regs.xpsr).unwrap();
+}
+
+/// Catches `bkpt`. We define this ourselves, rather than with `exception!`,
+/// as we need the stack pointer before any Rust code moves it. We hand
+/// `break_handler` the stacked registers, r4-r11 (which the hardware doesn't
+/// stack) and EXC_RETURN, keeping the stack 8-byte aligned.
+#[naked]
+#[no_mangle]
+pub unsafe extern "C" fn DebugMonitor() {
+    asm!("
+        tst lr, #4
+        ite eq
+        mrseq r0, msp
+        mrsne r0, psp
+        mov r2, lr
+        push {r4-r11}
+        mov r1, sp
+        push {r2, lr}
+        bl break_handler
+        pop {r2, lr}
+        pop {r4-r11}
+        bx lr
+    " :::: "volatile");
+}
+
+/// Sit in the monitor until someone types `cont`. `frame` is r0-r3, r12,
+/// lr, pc and xPSR, as the hardware stacked them.
+#[no_mangle]
+pub extern "C" fn break_handler(frame: &mut [u32; 8], saved: &[u32; 8], exc_return: u32) {
+    let pc = frame[6];
+    let mut r = [0u32; 13];
+    r[0..4].copy_from_slice(&frame[0..4]);
+    r[4..12].copy_from_slice(saved);
+    r[12] = frame[4];
+    // Work out where the stack was before the exception
+    let mut sp = &*frame as *const [u32; 8] as u32 + 32;
+    if exc_return & EXC_RETURN_NO_FPU == 0 {
+        sp += 72;
+    }
+    if frame[7] & XPSR_STACK_ALIGN != 0 {
+        sp += 4;
+    }
+    let regs = Registers {
+        r,
+        sp,
+        lr: frame[5],
+        pc,
+        xpsr: frame[7],
+    };
+    writeln!(Screen, "\nBreak at 0x{:08x}", pc).unwrap();
+    show_registers(&regs);
+    unsafe {
+        REGS = Some(regs);
+        LIST_NEXT = pc;
+        STOPPED = true;
+    }
+
+    // The main loop's stuck in whatever hit the breakpoint, so we read the
+    // UART ourselves
+    let mut buffer = [0u8; 64];
+    let mut screen = Screen;
+    {
+        let mut runner = Runner::new(&ROOT_MENU, &mut buffer, &mut screen);
+        while unsafe { STOPPED } {
+            if let Some(ch) = Console.read_byte() {
+                runner.input_byte(ch);
+            }
+        }
+    }
+
+    // Step over the breakpoint, or we'd come straight back
+    if read_u16(pc) & 0xFF00 == 0xBE00 {
+        frame[6] = pc + 2;
+    }
+    writeln!(Screen, "Continuing").unwrap();
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::Eeprom);
+    board.enable(sysctl::Domain::Ssi0);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+
+    // Send breakpoints to DebugMonitor, which the VGA interrupts can
+    // pre-empt
+    unsafe {
+        cp.SCB.shpr[SHPR_DEBUG_MONITOR].write(LOWEST_PRIORITY);
+        cp.DCB.demcr.modify(|w| w | DEMCR_MON_EN);
+    }
+
+    let mut c = fb::TextFrameBuffer::new(vga::framebuffer());
+    c.clear();
+    unsafe {
+        SCREEN = Some(&mut c as &mut Write as *mut Write);
+        LIST_NEXT = SCRATCH.as_ptr() as u32;
+        EEPROM = Eeprom::new(p.EEPROM).ok();
+    }
+
+    writeln!(board.tx, "Monitor - see the VGA screen").unwrap();
+    writeln!(Screen, "Monitor").unwrap();
+    writeln!(
+        Screen,
+        "Scratch area 0x{:08x}, {} bytes",
+        unsafe { SCRATCH.as_ptr() as u32 },
+        SCRATCH_WORDS * 4
+    ).unwrap();
+
+    // SSI0Clk, SSI0Rx and SSI0Tx
+    config::SSI0.connect();
+    let spi = Spi::ssi0(p.SSI0, MODE_0, INIT_HZ.hz(), &board.clocks);
+    let card = SdCard::new(spi, board.porta.pa3.into_push_pull_output());
+    let mut delay = Delay::new(cp.SYST, &board.clocks);
+    mount(card, &mut delay, &board.clocks);
+
+    let mut buffer = [0u8; 64];
+    let mut screen = Screen;
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut screen);
+
+    loop {
+        while let Ok(ch) = board.rx.read() {
+            r.input_byte(ch);
+        }
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
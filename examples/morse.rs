@@ -0,0 +1,264 @@
+//! A Morse code beacon.
+//!
+//! The blue LED (PF2, or D3 on PF4 on the TM4C1294) flashes the code. For
+//! sound as well, put a piezo sounder (or a small speaker and a transistor)
+//! on M0PWM0 - PB6, or PF0 on the TM4C1294 - which carries a 700 Hz tone
+//! while the key is down. Timer1A interrupts once per unit and works
+//! through the message. The console is on UART0 at 115200 bps.
+//!
+//! Commands:
+//!
+//! * `cw <text> [wpm]` - send the text, at 15 words per minute unless told
+//!   otherwise
+//! * `beep <on|off>` - turn the tone on or off
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+#[cfg(feature = "tm4c123")]
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+#[cfg(feature = "tm4c129")]
+#[macro_use(interrupt)]
+extern crate tm4c129x;
+
+use core::fmt::Write;
+use cortex_m::interrupt;
+use demo::board::SYSCLK_HZ;
+use demo::config::{Pin, Port};
+use demo::console::Console;
+use demo::hal;
+use demo::morse::{self, Key, Keyer};
+use demo::pac;
+use embedded_hal::prelude::*;
+use hal::sysctl;
+use menu::*;
+use rt::ExceptionFrame;
+
+/// The tone, in Hz.
+const TONE_HZ: u32 = 700;
+
+/// PWM0 counts down with a /64 prescaler, as 700 Hz is too slow for a
+/// 16-bit count at 80 MHz (let alone 120 MHz).
+const TONE_PERIOD: u32 = SYSCLK_HZ / 64 / TONE_HZ;
+
+/// Speed when the command doesn't give one.
+const DEFAULT_WPM: u32 = 15;
+
+/// The blue LED.
+#[cfg(feature = "tm4c123")]
+const LED: Pin = Pin { port: Port::F, bit: 2 };
+#[cfg(feature = "tm4c129")]
+const LED: Pin = Pin { port: Port::F, bit: 4 };
+
+/// M0PWM0, and its alternate function.
+#[cfg(feature = "tm4c123")]
+const BEEPER: Pin = Pin { port: Port::B, bit: 6 };
+#[cfg(feature = "tm4c123")]
+const BEEPER_FUNCTION: u32 = 4;
+#[cfg(feature = "tm4c129")]
+const BEEPER: Pin = Pin { port: Port::F, bit: 0 };
+#[cfg(feature = "tm4c129")]
+const BEEPER_FUNCTION: u32 = 6;
+
+/// M0PWM0 in PWMENABLE.
+const PWM_OUTPUT: u32 = 1 << 0;
+
+/// RCC.USEPWMDIV, and RCC.PWMDIV set to /64.
+#[cfg(feature = "tm4c123")]
+const RCC_PWMDIV_64: u32 = (1 << 20) | (0x7 << 17);
+/// PWMCC.USEPWM, and PWMCC.PWMDIV set to /64.
+#[cfg(feature = "tm4c129")]
+const PWMCC_PWMDIV_64: u32 = (1 << 8) | 0x5;
+
+// GPTMCFG, GPTMTAMR, GPTMCTL
+const CFG_32_BIT: u32 = 0x0;
+const TAMR_PERIODIC: u32 = 0x2;
+const CTL_TAEN: u32 = 1 << 0;
+
+/// Turns the LED, and maybe the tone, on and off.
+struct Sounder {
+    beep: bool,
+}
+
+impl Key for Sounder {
+    fn set(&mut self, down: bool) {
+        let pwm = unsafe { &*pac::PWM0::ptr() };
+        LED.set(down);
+        if down {
+            if self.beep {
+                pwm.enable
+                    .modify(|r, w| unsafe { w.bits(r.bits() | PWM_OUTPUT) });
+            }
+        } else {
+            pwm.enable
+                .modify(|r, w| unsafe { w.bits(r.bits() & !PWM_OUTPUT) });
+        }
+    }
+}
+
+/// The message being sent, if any.
+static mut KEYER: Option<Keyer> = None;
+
+static mut SOUNDER: Sounder = Sounder { beep: true };
+
+const CW_ITEM: Item = Item {
+    item_type: ItemType::Callback(cw_callback),
+    command: "cw",
+    help: Some("<text> [wpm] - send some Morse"),
+};
+
+const BEEP_ITEM: Item = Item {
+    item_type: ItemType::Callback(beep_callback),
+    command: "beep",
+    help: Some("<on|off> - turn the tone on or off"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[&CW_ITEM, &BEEP_ITEM],
+    entry: None,
+    exit: None,
+};
+
+fn cw_callback(_menu: &Menu, _item: &Item, input: &str) {
+    // Everything after the command is the text, unless the last word is a
+    // number, in which case that's the speed
+    let input = input.trim();
+    let text = match input.find(' ') {
+        Some(idx) => input[idx..].trim(),
+        None => {
+            writeln!(Console, "Usage: cw <text> [wpm]").unwrap();
+            return;
+        }
+    };
+    let (text, wpm) = match text.rfind(' ') {
+        Some(idx) => match text[idx..].trim().parse::<u32>() {
+            Ok(wpm) if wpm > 0 => (text[..idx].trim(), wpm),
+            _ => (text, DEFAULT_WPM),
+        },
+        None => (text, DEFAULT_WPM),
+    };
+    if text.len() > morse::MAX_TEXT {
+        writeln!(Console, "Only sending the first {} characters", morse::MAX_TEXT).unwrap();
+    }
+
+    let unit_clocks = morse::unit_ms(wpm) * (SYSCLK_HZ / 1000);
+    let timer = unsafe { &*pac::TIMER1::ptr() };
+    interrupt::free(|_| {
+        timer.ctl.write(|w| unsafe { w.bits(0) });
+        unsafe {
+            SOUNDER.set(false);
+            KEYER = Some(Keyer::new(text));
+        }
+        timer.tailr.write(|w| unsafe { w.bits(unit_clocks - 1) });
+        timer.ctl.write(|w| unsafe { w.bits(CTL_TAEN) });
+    });
+    writeln!(Console, "Sending at {} wpm", wpm).unwrap();
+}
+
+fn beep_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let beep = match input.split_whitespace().nth(1) {
+        Some("on") => true,
+        Some("off") => false,
+        _ => {
+            writeln!(Console, "Usage: beep <on|off>").unwrap();
+            return;
+        }
+    };
+    interrupt::free(|_| unsafe { SOUNDER.beep = beep });
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Timer1);
+    board.enable(sysctl::Domain::Pwm0);
+
+    // `board` has set up the blue LED, and the Sounder drives it through
+    // the data register directly
+
+    BEEPER.into_af(BEEPER_FUNCTION);
+
+    // Slow the PWM clock down, then set up generator 0 for a square wave.
+    // The output stays disabled until the key goes down.
+    let pwm = p.PWM0;
+    #[cfg(feature = "tm4c123")]
+    {
+        let sysctl_regs = unsafe { &*pac::SYSCTL::ptr() };
+        sysctl_regs
+            .rcc
+            .modify(|r, w| unsafe { w.bits(r.bits() | RCC_PWMDIV_64) });
+    }
+    #[cfg(feature = "tm4c129")]
+    pwm.cc.write(|w| unsafe { w.bits(PWMCC_PWMDIV_64) });
+    pwm._0_ctl.write(|w| unsafe { w.bits(0) });
+    pwm._0_load.write(|w| unsafe { w.bits(TONE_PERIOD - 1) });
+    pwm._0_cmpa.write(|w| unsafe { w.bits((TONE_PERIOD / 2) - 1) });
+    // ACTCMPAD = drive high, ACTLOAD = drive low
+    pwm._0_gena.write(|w| unsafe { w.bits((0x3 << 6) | (0x2 << 2)) });
+    pwm._0_ctl.write(|w| unsafe { w.bits(1) });
+
+    // Timer1A, 32-bit periodic, interrupting on timeout. The cw command
+    // sets the period and starts it.
+    let timer = p.TIMER1;
+    timer.ctl.write(|w| unsafe { w.bits(0) });
+    timer.cfg.write(|w| unsafe { w.bits(CFG_32_BIT) });
+    timer.tamr.write(|w| unsafe { w.bits(TAMR_PERIODIC) });
+    timer.imr.modify(|_, w| w.tatoim().set_bit());
+    timer.icr.write(|w| w.tatocint().set_bit());
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(hal::Interrupt::TIMER1A);
+
+    writeln!(board.tx, "Morse beacon").unwrap();
+
+    let mut buffer = [0u8; 80];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+
+    loop {
+        if let Ok(ch) = board.rx.read() {
+            r.input_byte(ch);
+        }
+    }
+}
+
+interrupt!(TIMER1A, timer1a_isr);
+
+/// One unit has gone by.
+fn timer1a_isr() {
+    let timer = unsafe { &*pac::TIMER1::ptr() };
+    timer.icr.write(|w| w.tatocint().set_bit());
+    let busy = match unsafe { KEYER.as_mut() } {
+        Some(keyer) => keyer.tick(unsafe { &mut SOUNDER }),
+        None => false,
+    };
+    if !busy {
+        timer.ctl.write(|w| unsafe { w.bits(0) });
+        unsafe { KEYER = None };
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
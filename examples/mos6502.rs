@@ -0,0 +1,342 @@
+//! A little 6502 home computer on the VGA screen.
+//!
+//! An emulated 6502 runs at about 1 MHz, with this memory map:
+//!
+//! ```text
+//! $0000 - $1FFF   8 KiB of RAM
+//! $8000 - $84FF   Screen: 18 rows of 64 bytes, of which the first 50
+//!                 columns are visible
+//! $9000 - $9FFF   Character ROM: sixteen rows of pixels per character
+//! $A000           Keyboard: the last key, with bit 7 set until you read
+//!                 or write $A010
+//! $FF00 - $FFFF   Firmware
+//! ```
+//!
+//! The firmware is a typewriter - whatever you type on UART0 (115200 bps)
+//! appears on the VGA screen (HSYNC on PB6, VSYNC on PC4 and green on PB7,
+//! as `hello_vga`). Its routine at $FF5F clears the screen. There's an IRQ
+//! every frame (60 Hz), if you clear the I flag.
+//!
+//! Press Ctrl-U to send a program with XMODEM. It's loaded at $0200 and run
+//! from there. Ctrl-R resets the machine, which leaves RAM alone.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use demo::board::Board;
+use demo::font;
+use demo::graphics::{Canvas, Colour};
+use demo::mos6502::{Bus, Cpu};
+use demo::vga;
+use demo::xmodem::Xmodem;
+use embedded_hal::prelude::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::delay::Delay;
+use tm4c123x_hal::sysctl;
+
+const RAM_LEN: usize = 0x2000;
+
+const SCREEN_START: u16 = 0x8000;
+const SCREEN_END: u16 = 0x84FF;
+const SCREEN_LEN: usize = (SCREEN_END - SCREEN_START) as usize + 1;
+/// Bytes per row of the screen.
+const SCREEN_STRIDE: usize = 64;
+const COLUMNS: usize = 50;
+const ROWS: usize = 18;
+
+const CHARACTER_ROM_START: u16 = 0x9000;
+const CHARACTER_ROM_END: u16 = 0x9FFF;
+
+const KEYBOARD: u16 = 0xA000;
+const KEYBOARD_STROBE: u16 = 0xA010;
+
+const FIRMWARE_START: u16 = 0xFF00;
+const VECTORS_START: u16 = 0xFFFA;
+
+/// Where uploaded programs go.
+const LOAD_ADDRESS: usize = 0x0200;
+
+/// Timer1 ticks per 60 Hz frame.
+const TICKS_PER_FRAME: u32 = 80_000_000 / 60;
+
+/// 1 MHz, near enough.
+const CYCLES_PER_FRAME: u32 = 1_000_000 / 60;
+
+// GPTMCFG, GPTMTAMR, GPTMCTL
+const CFG_32_BIT: u32 = 0x0;
+const TAMR_PERIODIC: u32 = 0x2;
+const CTL_TAEN: u32 = 1 << 0;
+
+const CTRL_R: u8 = 0x12;
+const CTRL_U: u8 = 0x15;
+
+// The firmware. The cursor is a pointer at $00.
+//
+//   FF00  A2 FF     reset:   LDX    #$FF
+//   FF02  9A                 TXS
+//   FF03  D8                 CLD
+//   FF04  20 5F FF           JSR    cls
+//   FF07  AD 00 A0  loop:    LDA    KBD
+//   FF0A  10 FB              BPL    loop
+//   FF0C  8D 10 A0           STA    KBDSTRB
+//   FF0F  29 7F              AND    #$7F
+//   FF11  C9 0D              CMP    #$0D
+//   FF13  F0 1A              BEQ    cr
+//   FF15  C9 08              CMP    #$08
+//   FF17  F0 35              BEQ    bs
+//   FF19  C9 7F              CMP    #$7F
+//   FF1B  F0 31              BEQ    bs
+//   FF1D  C9 0C              CMP    #$0C
+//   FF1F  F0 27              BEQ    clr
+//   FF21  A0 00              LDY    #$00
+//   FF23  91 00              STA    (CUR),Y
+//   FF25  E6 00              INC    CUR
+//   FF27  A5 00              LDA    CUR
+//   FF29  29 3F              AND    #$3F
+//   FF2B  C9 32              CMP    #50
+//   FF2D  D0 D8              BNE    loop
+//   FF2F  A5 00     cr:      LDA    CUR
+//   FF31  09 3F              ORA    #$3F
+//   FF33  18                 CLC
+//   FF34  69 01              ADC    #$01
+//   FF36  85 00              STA    CUR
+//   FF38  90 02              BCC    chk
+//   FF3A  E6 01              INC    CUR+1
+//   FF3C  A5 01     chk:     LDA    CUR+1
+//   FF3E  C9 84              CMP    #$84
+//   FF40  D0 C5              BNE    loop
+//   FF42  A5 00              LDA    CUR
+//   FF44  C9 80              CMP    #$80
+//   FF46  D0 BF              BNE    loop
+//   FF48  20 5F FF  clr:     JSR    cls
+//   FF4B  4C 07 FF           JMP    loop
+//   FF4E  A5 00     bs:      LDA    CUR
+//   FF50  29 3F              AND    #$3F
+//   FF52  F0 B3              BEQ    loop
+//   FF54  C6 00              DEC    CUR
+//   FF56  A9 20              LDA    #$20
+//   FF58  A0 00              LDY    #$00
+//   FF5A  91 00              STA    (CUR),Y
+//   FF5C  4C 07 FF           JMP    loop
+//   FF5F  A9 00     cls:     LDA    #$00
+//   FF61  85 00              STA    CUR
+//   FF63  A9 80              LDA    #$80
+//   FF65  85 01              STA    CUR+1
+//   FF67  A9 20              LDA    #$20
+//   FF69  A0 00              LDY    #$00
+//   FF6B  91 00     clsl:    STA    (CUR),Y
+//   FF6D  C8                 INY
+//   FF6E  D0 FB              BNE    clsl
+//   FF70  E6 01              INC    CUR+1
+//   FF72  A6 01              LDX    CUR+1
+//   FF74  E0 85              CPX    #$85
+//   FF76  D0 F3              BNE    clsl
+//   FF78  A9 80              LDA    #$80
+//   FF7A  85 01              STA    CUR+1
+//   FF7C  60                 RTS
+//   FF7D  40        irq:     RTI
+const FIRMWARE: [u8; 126] = [
+    0xA2, 0xFF, 0x9A, 0xD8, 0x20, 0x5F, 0xFF, 0xAD, 0x00, 0xA0, 0x10, 0xFB,
+    0x8D, 0x10, 0xA0, 0x29, 0x7F, 0xC9, 0x0D, 0xF0, 0x1A, 0xC9, 0x08, 0xF0,
+    0x35, 0xC9, 0x7F, 0xF0, 0x31, 0xC9, 0x0C, 0xF0, 0x27, 0xA0, 0x00, 0x91,
+    0x00, 0xE6, 0x00, 0xA5, 0x00, 0x29, 0x3F, 0xC9, 0x32, 0xD0, 0xD8, 0xA5,
+    0x00, 0x09, 0x3F, 0x18, 0x69, 0x01, 0x85, 0x00, 0x90, 0x02, 0xE6, 0x01,
+    0xA5, 0x01, 0xC9, 0x84, 0xD0, 0xC5, 0xA5, 0x00, 0xC9, 0x80, 0xD0, 0xBF,
+    0x20, 0x5F, 0xFF, 0x4C, 0x07, 0xFF, 0xA5, 0x00, 0x29, 0x3F, 0xF0, 0xB3,
+    0xC6, 0x00, 0xA9, 0x20, 0xA0, 0x00, 0x91, 0x00, 0x4C, 0x07, 0xFF, 0xA9,
+    0x00, 0x85, 0x00, 0xA9, 0x80, 0x85, 0x01, 0xA9, 0x20, 0xA0, 0x00, 0x91,
+    0x00, 0xC8, 0xD0, 0xFB, 0xE6, 0x01, 0xA6, 0x01, 0xE0, 0x85, 0xD0, 0xF3,
+    0xA9, 0x80, 0x85, 0x01, 0x60, 0x40,
+];
+
+/// NMI, reset and IRQ, which all go to the firmware.
+const VECTORS: [u8; 6] = [0x7D, 0xFF, 0x00, 0xFF, 0x7D, 0xFF];
+
+static mut RAM: [u8; RAM_LEN] = [0; RAM_LEN];
+static mut SCREEN: [u8; SCREEN_LEN] = [0; SCREEN_LEN];
+
+/// Everything the 6502 can see.
+struct Machine<'a> {
+    ram: &'a mut [u8; RAM_LEN],
+    screen: &'a mut [u8; SCREEN_LEN],
+    /// The last key, with bit 7 set if it hasn't been read yet.
+    key: u8,
+}
+
+impl<'a> Bus for Machine<'a> {
+    fn read(&mut self, address: u16) -> u8 {
+        match address {
+            0...0x1FFF => self.ram[address as usize],
+            SCREEN_START...SCREEN_END => self.screen[(address - SCREEN_START) as usize],
+            CHARACTER_ROM_START...CHARACTER_ROM_END => {
+                let offset = (address - CHARACTER_ROM_START) as usize;
+                font::glyph((offset / font::HEIGHT) as u8)[offset % font::HEIGHT]
+            }
+            KEYBOARD => self.key,
+            KEYBOARD_STROBE => {
+                self.key &= 0x7F;
+                0
+            }
+            VECTORS_START...0xFFFF => VECTORS[(address - VECTORS_START) as usize],
+            FIRMWARE_START...0xFFFF => {
+                let offset = (address - FIRMWARE_START) as usize;
+                FIRMWARE.get(offset).cloned().unwrap_or(0xFF)
+            }
+            // Nothing there
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        match address {
+            0...0x1FFF => self.ram[address as usize] = value,
+            SCREEN_START...SCREEN_END => {
+                let offset = (address - SCREEN_START) as usize;
+                self.screen[offset] = value;
+                let (row, column) = (offset / SCREEN_STRIDE, offset % SCREEN_STRIDE);
+                if row < ROWS && column < COLUMNS {
+                    vga::framebuffer().draw_char(
+                        column * font::WIDTH,
+                        row * font::HEIGHT,
+                        value,
+                        Colour::WHITE,
+                        Colour::BLACK,
+                    );
+                }
+            }
+            KEYBOARD_STROBE => self.key &= 0x7F,
+            // ROM, or nothing at all
+            _ => {}
+        }
+    }
+}
+
+/// Timer1A counts down, so turn it round.
+fn now() -> u32 {
+    let timer = unsafe { &*tm4c123x::TIMER1::ptr() };
+    !timer.tav.read().bits()
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Timer1);
+    board.enable(sysctl::Domain::Ssi2);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+
+    // Timer1A, 32-bit periodic, free running from 0xFFFF_FFFF
+    let timer = p.TIMER1;
+    timer.ctl.write(|w| unsafe { w.bits(0) });
+    timer.cfg.write(|w| unsafe { w.bits(CFG_32_BIT) });
+    timer.tamr.write(|w| unsafe { w.bits(TAMR_PERIODIC) });
+    timer.tailr.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+    timer.ctl.write(|w| unsafe { w.bits(CTL_TAEN) });
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+
+    vga::framebuffer().clear(Colour::BLACK);
+
+    let mut machine = Machine {
+        ram: unsafe { &mut RAM },
+        screen: unsafe { &mut SCREEN },
+        key: 0,
+    };
+    let mut cpu = Cpu::new();
+    cpu.reset(&mut machine);
+
+    writeln!(board.tx, "6502 - Ctrl-U to upload a program, Ctrl-R to reset").unwrap();
+
+    let mut running = true;
+    let mut frame = now();
+    loop {
+        while now().wrapping_sub(frame) < TICKS_PER_FRAME {}
+        frame = frame.wrapping_add(TICKS_PER_FRAME);
+
+        while let Ok(byte) = board.rx.read() {
+            match byte {
+                CTRL_U => {
+                    writeln!(board.tx, "Send the program with XMODEM").unwrap();
+                    let mut address = LOAD_ADDRESS;
+                    let result = {
+                        let ram = &mut machine.ram;
+                        Xmodem::new(&mut board.tx, &mut board.rx, &mut d).receive(|block| {
+                            if address + block.len() > RAM_LEN {
+                                return false;
+                            }
+                            ram[address..address + block.len()].copy_from_slice(block);
+                            address += block.len();
+                            true
+                        })
+                    };
+                    // Give the terminal emulator a moment to tidy up
+                    d.delay_ms(500u32);
+                    match result {
+                        Ok(_) => {
+                            writeln!(board.tx, "\nGot {} bytes", address - LOAD_ADDRESS).unwrap();
+                            cpu.reset(&mut machine);
+                            cpu.pc = LOAD_ADDRESS as u16;
+                            running = true;
+                        }
+                        Err(e) => writeln!(board.tx, "\nFailed: {:?}", e).unwrap(),
+                    }
+                    frame = now();
+                }
+                CTRL_R => {
+                    cpu.reset(&mut machine);
+                    running = true;
+                }
+                // Backspace is more use to 6502 programs than delete
+                0x7F => machine.key = 0x08 | 0x80,
+                _ => machine.key = byte | 0x80,
+            }
+        }
+
+        if running {
+            let start = cpu.cycles;
+            while cpu.cycles.wrapping_sub(start) < CYCLES_PER_FRAME {
+                if let Err(e) = cpu.step(&mut machine) {
+                    writeln!(board.tx, "Stopped: {:?}", e).unwrap();
+                    running = false;
+                    break;
+                }
+            }
+            cpu.irq(&mut machine);
+        }
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
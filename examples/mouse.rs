@@ -0,0 +1,188 @@
+//! Paint squares on the VGA screen with a PS/2 mouse.
+//!
+//! See `demo::mouse` for the wiring - clock on PC6 and data on PC7. The VGA
+//! output is the same as `hello_vga` (HSYNC on PB6, VSYNC on PC4 and green
+//! on PB7). The left button fills the square under the pointer and the
+//! right button clears it; press `c` on the console (UART0 at 115200 bps)
+//! to clear the lot.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use cortex_m::asm;
+use demo::board::Board;
+use demo::graphics::{Canvas, Colour, VGA_HEIGHT, VGA_WIDTH};
+use demo::mouse::{self, Buttons};
+use demo::pointer::{Over, Pointer};
+use demo::status_bar;
+use demo::text::Buffer;
+use demo::vga;
+use embedded_hal::prelude::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::sysctl;
+
+/// Size of a square, in pixels.
+const CELL: usize = 8;
+const COLS: usize = VGA_WIDTH / CELL;
+const ROWS: usize = (VGA_HEIGHT - status_bar::HEIGHT) / CELL;
+
+/// Which squares are filled.
+static mut CELLS: [[bool; COLS]; ROWS] = [[false; COLS]; ROWS];
+
+/// The square at (x, y), if there is one.
+fn cell_at(x: usize, y: usize) -> Option<(usize, usize)> {
+    if y < status_bar::HEIGHT {
+        return None;
+    }
+    let (col, row) = (x / CELL, (y - status_bar::HEIGHT) / CELL);
+    if col < COLS && row < ROWS {
+        Some((col, row))
+    } else {
+        None
+    }
+}
+
+/// What's under the pointer. A `pointer::Background`.
+fn background(x: usize, y: usize) -> bool {
+    match cell_at(x, y) {
+        Some((col, row)) => unsafe { CELLS[row][col] },
+        None => false,
+    }
+}
+
+/// Fill or clear one square.
+fn paint<C>(canvas: &mut C, col: usize, row: usize, filled: bool)
+where
+    C: Canvas,
+{
+    unsafe { CELLS[row][col] = filled };
+    let colour = if filled { Colour::WHITE } else { Colour::BLACK };
+    canvas.fill_rect(
+        col * CELL,
+        status_bar::HEIGHT + (row * CELL),
+        CELL,
+        CELL,
+        colour,
+    );
+}
+
+/// Show where the mouse is and what it's pressing.
+fn show<C>(canvas: &mut C, x: usize, y: usize, buttons: Buttons)
+where
+    C: Canvas,
+{
+    let mut text = Buffer::new();
+    write!(
+        text,
+        "{},{} {}{}{}",
+        x,
+        y,
+        if buttons.left { 'L' } else { '-' },
+        if buttons.middle { 'M' } else { '-' },
+        if buttons.right { 'R' } else { '-' }
+    ).unwrap();
+    let left = if mouse::is_connected() {
+        "PS/2 mouse"
+    } else {
+        "No mouse"
+    };
+    status_bar::draw(canvas, left, text.as_str());
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+    // Below the VGA timers, so the picture stays put
+    unsafe { nvic.set_priority(tm4c123x_hal::Interrupt::GPIOC, 0x40) };
+    nvic.enable(tm4c123x_hal::Interrupt::GPIOC);
+
+    // Let the mouse finish its self-test
+    asm::delay(board.clocks.sysclk.0 / 2);
+    mouse::init(&board.clocks);
+
+    let fb = vga::framebuffer();
+    fb.clear(Colour::BLACK);
+    let (x, y) = mouse::position();
+    let y = y.max(status_bar::HEIGHT);
+    let mut pointer = Pointer::new(x, y, background);
+    pointer.show(fb);
+    let mut last = (x, y, mouse::buttons(), mouse::is_connected());
+    show(fb, x, y, last.2);
+
+    writeln!(board.tx, "Left button paints, right button clears, 'c' clears all").unwrap();
+
+    loop {
+        if let Ok(b'c') = board.rx.read() {
+            pointer.hide(fb);
+            for row in 0..ROWS {
+                for col in 0..COLS {
+                    paint(fb, col, row, false);
+                }
+            }
+            pointer.show(fb);
+        }
+
+        // The pointer can't go over the status bar, as we don't know
+        // what's under it there
+        let (x, y) = mouse::position();
+        let y = y.max(status_bar::HEIGHT);
+        let buttons = mouse::buttons();
+        pointer.move_to(fb, x, y);
+        if buttons.left != buttons.right {
+            if let Some((col, row)) = cell_at(x, y) {
+                if background(x, y) != buttons.left {
+                    let mut canvas = Over {
+                        pointer: &pointer,
+                        canvas: &mut *fb,
+                    };
+                    paint(&mut canvas, col, row, buttons.left);
+                }
+            }
+        }
+
+        let now = (x, y, buttons, mouse::is_connected());
+        if now != last {
+            show(fb, x, y, buttons);
+            last = now;
+        }
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+interrupt!(GPIOC, mouse::gpioc_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
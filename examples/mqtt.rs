@@ -0,0 +1,263 @@
+//! Publishes sensor readings to an MQTT broker over an ESP8266, and lets
+//! the broker control the LED.
+//!
+//! Wire up the ESP8266 as for the `esp8266` example (PB0/PB1 at 115200
+//! bps) and set the constants below to suit your network and broker. Every
+//! ten seconds we publish the pot on PE3 (AIN0) in millivolts to
+//! `launchpad/pot` and the chip temperature in tenths of a degree to
+//! `launchpad/temperature`. Publish `red`, `green`, `blue` or `off` to
+//! `launchpad/led` to change the LED. With Mosquitto:
+//!
+//! ```
+//! mosquitto_sub -h 192.168.1.10 -t 'launchpad/#' -v
+//! mosquitto_pub -h 192.168.1.10 -t launchpad/led -m blue
+//! ```
+//!
+//! Timer2A counts seconds for us. Progress is printed on UART0 (115200
+//! bps).
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board, gpio_port)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+
+use core::fmt::Write;
+use cortex_m::asm;
+use demo::adc::{self, Adc};
+use demo::board::SYSCLK_HZ;
+use demo::esp8266::{self, Esp8266, Protocol};
+use demo::hal;
+use demo::mqtt::{self, Packet};
+use demo::text::Buffer;
+use hal::delay::Delay;
+use hal::gpio::GpioExt;
+use hal::serial::{NewlineMode, Serial};
+use hal::sysctl;
+use hal::time::U32Ext;
+use rt::ExceptionFrame;
+
+/// The network to join.
+const SSID: &str = "my-network";
+const PASSWORD: &str = "my-password";
+
+/// The broker.
+const BROKER: &str = "192.168.1.10";
+const BROKER_PORT: u16 = 1883;
+const CLIENT_ID: &str = "launchpad";
+
+const POT_TOPIC: &str = "launchpad/pot";
+const TEMPERATURE_TOPIC: &str = "launchpad/temperature";
+const LED_TOPIC: &str = "launchpad/led";
+
+/// The broker hangs up if it hears nothing for one and a half times this.
+const KEEP_ALIVE_SECS: u16 = 60;
+
+/// How often to publish the readings.
+const PUBLISH_SECS: u32 = 10;
+
+/// How long each wait for data from the broker lasts.
+const POLL_MS: u32 = 100;
+
+/// The channel the pot is on.
+const POT_CHANNEL: u8 = 0;
+
+const CFG_32_BIT: u32 = 0x0;
+const MR_PERIODIC: u32 = 0x2;
+const CTL_TAEN: u32 = 1 << 0;
+
+/// Open a connection to the broker, log in and subscribe to the LED topic.
+fn connect<TX, RX, D>(esp: &mut Esp8266<TX, RX, D>) -> Result<(), esp8266::Error>
+where
+    TX: embedded_hal::serial::Write<u8>,
+    RX: embedded_hal::serial::Read<u8>,
+    D: embedded_hal::blocking::delay::DelayUs<u32>,
+{
+    let mut packet = [0u8; mqtt::MAX_PACKET];
+    esp.connect(Protocol::Tcp, BROKER, BROKER_PORT)?;
+    let len = mqtt::connect(&mut packet, CLIENT_ID, KEEP_ALIVE_SECS, None).unwrap();
+    esp.send(&packet[0..len])?;
+    // The SUBSCRIBE can follow straight on - the broker deals with them in
+    // order, and the CONNACK turns up in the main loop
+    let len = mqtt::subscribe(&mut packet, 1, LED_TOPIC).unwrap();
+    esp.send(&packet[0..len])
+}
+
+/// Publish a number.
+fn publish_value<TX, RX, D>(
+    esp: &mut Esp8266<TX, RX, D>,
+    topic: &str,
+    value: i32,
+) -> Result<(), esp8266::Error>
+where
+    TX: embedded_hal::serial::Write<u8>,
+    RX: embedded_hal::serial::Read<u8>,
+    D: embedded_hal::blocking::delay::DelayUs<u32>,
+{
+    let mut text = Buffer::from_storage([0u8; 16]);
+    write!(text, "{}", value).unwrap();
+    let mut packet = [0u8; mqtt::MAX_PACKET];
+    let len = mqtt::publish(&mut packet, topic, text.as_bytes(), false).unwrap();
+    esp.send(&packet[0..len])
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Adc0);
+    board.enable(sysctl::Domain::Timer2);
+
+    let mut portb = gpio_port!(p, B).split(&board.power_control);
+    let _porte = gpio_port!(p, E).split(&board.power_control);
+
+    // The ESP8266
+    let uart1 = Serial::uart1(
+        p.UART1,
+        portb.pb1.into_af1(&mut portb.control),
+        portb.pb0.into_af1(&mut portb.control),
+        (),
+        (),
+        115200_u32.bps(),
+        NewlineMode::Binary,
+        &board.clocks,
+        &board.power_control,
+    );
+    let (tx1, rx1) = uart1.split();
+
+    adc::configure_pin(POT_CHANNEL);
+    let mut adc = Adc::adc0(p.ADC0);
+
+    // Timer2A ticks once a second
+    let ticker = p.TIMER2;
+    ticker.ctl.write(|w| unsafe { w.bits(0) });
+    ticker.cfg.write(|w| unsafe { w.bits(CFG_32_BIT) });
+    ticker.tamr.write(|w| unsafe { w.bits(MR_PERIODIC) });
+    ticker.tailr.write(|w| unsafe { w.bits(SYSCLK_HZ - 1) });
+    ticker.icr.write(|w| w.tatocint().set_bit());
+    ticker.ctl.write(|w| unsafe { w.bits(CTL_TAEN) });
+
+    let d = Delay::new(cp.SYST, &board.clocks);
+
+    writeln!(board.tx, "MQTT demo").unwrap();
+
+    let mut esp = match Esp8266::new(tx1, rx1, d) {
+        Ok(esp) => esp,
+        Err(e) => {
+            writeln!(board.tx, "ESP8266 not responding: {:?}", e).unwrap();
+            loop {
+                asm::wfi();
+            }
+        }
+    };
+
+    writeln!(board.tx, "Joining {}...", SSID).unwrap();
+    while let Err(e) = esp.join(SSID, PASSWORD) {
+        writeln!(board.tx, "Failed ({:?}), retrying", e).unwrap();
+    }
+
+    let mut parser = mqtt::Parser::new();
+    let mut chunk = [0u8; 64];
+    let mut seconds = 0u32;
+    let mut last_publish = 0u32;
+    let mut last_sent = 0u32;
+
+    loop {
+        if !esp.is_connected() {
+            writeln!(board.tx, "Connecting to {}...", BROKER).unwrap();
+            parser = mqtt::Parser::new();
+            if let Err(e) = connect(&mut esp) {
+                writeln!(board.tx, "Failed: {:?}", e).unwrap();
+                let _ = esp.close();
+                esp.wait(5_000).unwrap();
+                continue;
+            }
+            last_sent = seconds;
+        }
+
+        let len = match esp.receive(&mut chunk, POLL_MS) {
+            Ok(len) => len,
+            Err(esp8266::Error::Closed) => {
+                writeln!(board.tx, "Broker hung up").unwrap();
+                continue;
+            }
+            Err(e) => {
+                writeln!(board.tx, "Receive failed: {:?}", e).unwrap();
+                0
+            }
+        };
+        for &byte in &chunk[0..len] {
+            match parser.input(byte) {
+                Some(Packet::ConnAck { code: 0, .. }) => writeln!(board.tx, "Connected").unwrap(),
+                Some(Packet::ConnAck { code, .. }) => {
+                    writeln!(board.tx, "Broker refused us ({})", code).unwrap()
+                }
+                Some(Packet::SubAck { granted, .. }) if granted & 0x80 == 0 => {
+                    writeln!(board.tx, "Subscribed to {}", LED_TOPIC).unwrap()
+                }
+                Some(Packet::SubAck { .. }) => writeln!(board.tx, "Subscribe refused").unwrap(),
+                Some(Packet::Publish { topic, payload }) if topic == LED_TOPIC => {
+                    let (r, g, b) = match payload {
+                        b"red" => (true, false, false),
+                        b"green" => (false, true, false),
+                        b"blue" => (false, false, true),
+                        _ => (false, false, false),
+                    };
+                    board.led_red.set(r);
+                    board.led_green.set(g);
+                    board.led_blue.set(b);
+                    writeln!(
+                        board.tx,
+                        "LED {}",
+                        core::str::from_utf8(payload).unwrap_or("?")
+                    ).unwrap();
+                }
+                Some(_) | None => {}
+            }
+        }
+
+        if ticker.ris.read().tatoris().bit_is_set() {
+            ticker.icr.write(|w| w.tatocint().set_bit());
+            seconds = seconds.wrapping_add(1);
+        }
+
+        if seconds.wrapping_sub(last_publish) >= PUBLISH_SECS {
+            last_publish = seconds;
+            let millivolts = adc::millivolts(adc.read(POT_CHANNEL)) as i32;
+            let temperature = adc::temperature(adc.read(adc::TEMPERATURE));
+            let result = publish_value(&mut esp, POT_TOPIC, millivolts)
+                .and_then(|_| publish_value(&mut esp, TEMPERATURE_TOPIC, temperature));
+            match result {
+                Ok(_) => last_sent = seconds,
+                Err(e) => writeln!(board.tx, "Publish failed: {:?}", e).unwrap(),
+            }
+        }
+
+        // Half the keep-alive time is plenty of margin
+        if seconds.wrapping_sub(last_sent) >= u32::from(KEEP_ALIVE_SECS / 2)
+            && esp.send(&mqtt::ping_request()).is_ok()
+        {
+            last_sent = seconds;
+        }
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,243 @@
+//! Ping-pong between two LaunchPads with nRF24L01+ radios.
+//!
+//! SCK is PA2 (SSI0Clk), MISO is PA4 (SSI0Rx), MOSI is PA5 (SSI0Tx), CSN is
+//! PA3 and CE is PA6. Power the module from 3.3V, not 5V.
+//!
+//! Both boards start as responders, sending back whatever they receive.
+//! Press `p` on the console (UART0, 115200 bps) of one board to make it the
+//! pinger. It sends a numbered packet every 100ms and times the reply. Press
+//! `r` to go back to being a responder, or `s` for the link statistics.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+
+use core::fmt::Write;
+use demo::config;
+use demo::hal;
+use demo::nrf24::{Nrf24, TxResult, PAYLOAD_SIZE};
+use demo::spi::Spi;
+use embedded_hal::blocking::spi::Transfer;
+use embedded_hal::digital::OutputPin;
+use embedded_hal::prelude::*;
+use embedded_hal::spi::MODE_0;
+use hal::delay::Delay;
+use hal::sysctl;
+use hal::time::U32Ext;
+use rt::ExceptionFrame;
+
+/// Both ends use this address.
+const ADDRESS: [u8; 5] = [b'M', b'o', b'n', b'o', b'1'];
+
+/// Milliseconds between pings.
+const PING_INTERVAL_MS: u32 = 100;
+
+/// How long we wait for a pong, in 100us steps.
+const PONG_TIMEOUT: u32 = 500;
+
+/// Print the statistics every this many pings.
+const REPORT_EVERY: u32 = 50;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Ping,
+    Respond,
+}
+
+#[derive(Default)]
+struct Stats {
+    sent: u32,
+    acked: u32,
+    lost: u32,
+    retries: u32,
+    pongs: u32,
+    timeouts: u32,
+    strong: u32,
+    /// Sum of round trip times, in 100us units
+    rtt_total: u32,
+    rtt_min: u32,
+    rtt_max: u32,
+}
+
+impl Stats {
+    fn new() -> Stats {
+        Stats {
+            rtt_min: u32::max_value(),
+            ..Default::default()
+        }
+    }
+
+    fn print<W>(&self, w: &mut W)
+    where
+        W: Write,
+    {
+        let percent = |n: u32| if self.sent == 0 { 0 } else { (n * 100) / self.sent };
+        writeln!(
+            w,
+            "Sent {} acked {} ({}%) lost {} retries {}",
+            self.sent,
+            self.acked,
+            percent(self.acked),
+            self.lost,
+            self.retries
+        ).unwrap();
+        writeln!(
+            w,
+            "Pongs {} ({}%) timeouts {} strong signal {}",
+            self.pongs,
+            percent(self.pongs),
+            self.timeouts,
+            self.strong
+        ).unwrap();
+        if self.pongs > 0 {
+            let avg = self.rtt_total / self.pongs;
+            writeln!(
+                w,
+                "RTT min {}.{} avg {}.{} max {}.{} ms",
+                self.rtt_min / 10,
+                self.rtt_min % 10,
+                avg / 10,
+                avg % 10,
+                self.rtt_max / 10,
+                self.rtt_max % 10
+            ).unwrap();
+        }
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Ssi0);
+
+    // SSI0Clk, SSI0Rx and SSI0Tx
+    config::SSI0.connect();
+    let porta = board.porta;
+    let spi = Spi::ssi0(p.SSI0, MODE_0, 4_000_000_u32.hz(), &board.clocks);
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+
+    let mut radio = Nrf24::new(
+        spi,
+        porta.pa3.into_push_pull_output(),
+        porta.pa6.into_push_pull_output(),
+        &mut d,
+    ).unwrap();
+    radio.set_address(&ADDRESS).unwrap();
+    radio.listen().unwrap();
+
+    writeln!(board.tx, "nRF24L01+ ping-pong - p to ping, r to respond, s for stats").unwrap();
+
+    let mut role = Role::Respond;
+    let mut stats = Stats::new();
+    let mut packet = [0u8; PAYLOAD_SIZE];
+
+    loop {
+        while let Ok(ch) = board.rx.read() {
+            match ch {
+                b'p' if role != Role::Ping => {
+                    writeln!(board.tx, "Pinging").unwrap();
+                    role = Role::Ping;
+                    stats = Stats::new();
+                }
+                b'r' if role != Role::Respond => {
+                    writeln!(board.tx, "Responding").unwrap();
+                    role = Role::Respond;
+                    radio.listen().unwrap();
+                }
+                b's' => stats.print(&mut board.tx),
+                _ => {}
+            }
+        }
+
+        match role {
+            Role::Respond => {
+                if radio.receive(&mut packet).unwrap() {
+                    // Send it straight back, then carry on listening
+                    radio.send(&packet).unwrap();
+                    radio.listen().unwrap();
+                }
+            }
+            Role::Ping => {
+                ping(&mut radio, &mut d, &mut stats);
+                if stats.sent % REPORT_EVERY == 0 {
+                    stats.print(&mut board.tx);
+                }
+                d.delay_ms(PING_INTERVAL_MS);
+            }
+        }
+    }
+}
+
+/// Send one numbered ping and wait for it to come back.
+fn ping<SPI, CSN, CE, E>(radio: &mut Nrf24<SPI, CSN, CE>, d: &mut Delay, stats: &mut Stats)
+where
+    SPI: Transfer<u8, Error = E>,
+    CSN: OutputPin,
+    CE: OutputPin,
+    E: core::fmt::Debug,
+{
+    let mut packet = [0u8; PAYLOAD_SIZE];
+    let seq = stats.sent;
+    packet[0..4].copy_from_slice(&[
+        (seq >> 24) as u8,
+        (seq >> 16) as u8,
+        (seq >> 8) as u8,
+        seq as u8,
+    ]);
+    stats.sent += 1;
+    match radio.send(&packet).unwrap() {
+        TxResult::Acked(retries) => {
+            stats.acked += 1;
+            stats.retries += u32::from(retries);
+        }
+        TxResult::Lost => {
+            stats.lost += 1;
+            return;
+        }
+    }
+
+    radio.listen().unwrap();
+    let mut reply = [0u8; PAYLOAD_SIZE];
+    for rtt in 0..PONG_TIMEOUT {
+        if radio.receive(&mut reply).unwrap() {
+            if reply[0..4] == packet[0..4] {
+                stats.pongs += 1;
+                stats.rtt_total += rtt;
+                stats.rtt_min = stats.rtt_min.min(rtt);
+                stats.rtt_max = stats.rtt_max.max(rtt);
+                if radio.strong_signal().unwrap() {
+                    stats.strong += 1;
+                }
+                radio.standby().unwrap();
+                return;
+            }
+        }
+        d.delay_us(100u32);
+    }
+    stats.timeouts += 1;
+    radio.standby().unwrap();
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
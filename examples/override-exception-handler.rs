@@ -2,46 +2,57 @@
 //!
 //! You can override an exception handler using the [`exception!`][1] macro.
 //!
-//! [1]: https://docs.rs/cortex-m-rt/0.3.2/cortex_m_rt/macro.exception.html
+//! [1]: https://docs.rs/cortex-m-rt/0.5.3/cortex_m_rt/macro.exception.html
 //!
-//! The default exception handler can be overridden using the
-//! [`default_handler!`][2] macro
-//!
-//! [2]: https://docs.rs/cortex-m-rt/0.3.2/cortex_m_rt/macro.default_handler.html
+//! The default exception handler, which catches everything you haven't
+//! overridden, is set with `exception!(*, ..)`.
 //!
 //! ---
 
-#![feature(used)]
+#![no_main]
 #![no_std]
 
 extern crate cortex_m;
-#[macro_use(exception)]
-extern crate cortex_m_rt;
-
-use core::ptr;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate panic_halt;
 
 use cortex_m::asm;
+use cortex_m::peripheral::syst::SystClkSource;
+use cortex_m::Peripherals;
+use rt::ExceptionFrame;
 
-fn main() {
-    unsafe {
-        // Invalid memory access
-        ptr::read_volatile(0x2FFF_FFFF as *const u32);
-    }
-}
+entry!(main);
 
-exception!(HARD_FAULT, handler);
+fn main() -> ! {
+    let p = Peripherals::take().unwrap();
+    let mut syst = p.SYST;
 
-fn handler() {
-    // You'll hit this breakpoint rather than the one in cortex-m-rt
-    asm::bkpt()
+    // Configures the system timer to trigger a SysTick exception every
+    // million cycles
+    syst.set_clock_source(SystClkSource::Core);
+    syst.set_reload(1_000_000);
+    syst.enable_counter();
+    syst.enable_interrupt();
+
+    loop {}
 }
 
-// As we are not using interrupts, we just register a dummy catch all handler
-#[allow(dead_code)]
-#[used]
-#[link_section = ".vector_table.interrupts"]
-static INTERRUPTS: [extern "C" fn(); 240] = [default_handler; 240];
+exception!(SysTick, sys_tick);
 
-extern "C" fn default_handler() {
+fn sys_tick() {
+    // You'll hit this breakpoint every time the timer expires
     asm::bkpt();
 }
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -1,58 +1,47 @@
 //! Defining the panic handler
 //!
-//! The panic handler can be defined through the `panic_fmt` [language item][1].
-//! Make sure that the "abort-on-panic" feature of the cortex-m-rt crate is
-//! disabled to avoid redefining the language item.
-//!
-//! [1]: https://doc.rust-lang.org/unstable-book/language-features/lang-items.html
+//! The panic handler can be defined with the `#[panic_handler]` attribute.
+//! Don't link in a panic handler crate (like `panic-halt`) as well, or there
+//! will be two of them.
 //!
 //! ---
 
-#![feature(core_intrinsics)]
-#![feature(lang_items)]
-#![feature(used)]
+#![no_main]
 #![no_std]
 
-extern crate cortex_m;
-extern crate cortex_m_rt;
-extern crate cortex_m_semihosting;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate cortex_m_semihosting as sh;
 
 use core::fmt::Write;
-use core::intrinsics;
+use core::panic::PanicInfo;
+
+use rt::ExceptionFrame;
+use sh::hio;
 
-use cortex_m::asm;
-use cortex_m_semihosting::hio;
+entry!(main);
 
-fn main() {
+fn main() -> ! {
     panic!("Oops");
 }
 
-#[lang = "panic_fmt"]
-#[no_mangle]
-unsafe extern "C" fn rust_begin_unwind(
-    args: core::fmt::Arguments,
-    file: &'static str,
-    line: u32,
-    col: u32,
-) -> ! {
-    if let Ok(mut stdout) = hio::hstdout() {
-        write!(stdout, "panicked at '")
-            .and_then(|_| {
-                stdout
-                    .write_fmt(args)
-                    .and_then(|_| writeln!(stdout, "', {}:{}", file, line))
-            })
-            .ok();
-    }
+exception!(HardFault, hard_fault);
 
-    intrinsics::abort()
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
 }
 
-// As we are not using interrupts, we just register a dummy catch all handler
-#[link_section = ".vector_table.interrupts"]
-#[used]
-static INTERRUPTS: [extern "C" fn(); 240] = [default_handler; 240];
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    if let Ok(mut stdout) = hio::hstdout() {
+        writeln!(stdout, "{}", info).ok();
+    }
 
-extern "C" fn default_handler() {
-    asm::bkpt();
+    loop {}
 }
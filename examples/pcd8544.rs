@@ -0,0 +1,108 @@
+//! Drives a Nokia 5110 (PCD8544) 84x48 LCD over SPI.
+//!
+//! SCLK is PA2 (SSI0Clk), DIN is PA5 (SSI0Tx), CE is PA3, DC is PA6 and RST
+//! is PA7.
+//!
+//! Text is rendered using the VGA font, so the same strings look the same on
+//! both displays.
+
+#![no_std]
+#![no_main]
+
+extern crate bresenham;
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+
+use bresenham::Bresenham;
+use core::fmt::Write;
+use demo::config;
+use demo::hal;
+use demo::pcd8544::{self, Pcd8544, TextSize};
+use demo::spi::Spi;
+use embedded_hal::prelude::*;
+use embedded_hal::spi::MODE_0;
+use hal::delay::Delay;
+use hal::sysctl;
+use hal::time::U32Ext;
+use rt::ExceptionFrame;
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Ssi0);
+
+    // SSI0Clk and SSI0Tx
+    config::SSI0.connect();
+    let porta = board.porta;
+    let spi = Spi::ssi0(p.SSI0, MODE_0, 4_000_000_u32.hz(), &board.clocks);
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+
+    let mut rst = porta.pa7.into_push_pull_output();
+    let mut lcd = Pcd8544::new(
+        spi,
+        porta.pa6.into_push_pull_output(),
+        porta.pa3.into_push_pull_output(),
+        &mut rst,
+        &mut d,
+    ).unwrap();
+
+    writeln!(board.tx, "PCD8544 demo").unwrap();
+
+    // A line bouncing around the bottom half of the screen
+    let (mut x0, mut y0, mut dx0, mut dy0) = (0isize, 24isize, 1isize, 1isize);
+    let (mut x1, mut y1, mut dx1, mut dy1) = (83isize, 47isize, -2isize, -1isize);
+    let mut frame = 0u32;
+
+    loop {
+        lcd.clear();
+        lcd.set_text_size(TextSize::Tall);
+        write!(lcd, "Monotron").unwrap();
+        lcd.set_text_size(TextSize::Short);
+        write!(lcd, "\n\nFrame {}", frame).unwrap();
+
+        for (x, y) in Bresenham::new((x0, y0), (x1, y1)) {
+            lcd.set_pixel(x as usize, y as usize, true);
+        }
+        lcd.flush().unwrap();
+
+        bounce(&mut x0, &mut dx0, pcd8544::WIDTH as isize);
+        bounce(&mut y0, &mut dy0, pcd8544::HEIGHT as isize);
+        bounce(&mut x1, &mut dx1, pcd8544::WIDTH as isize);
+        bounce(&mut y1, &mut dy1, pcd8544::HEIGHT as isize);
+        frame = frame.wrapping_add(1);
+
+        d.delay_ms(40u32);
+    }
+}
+
+/// Move `pos` by `delta`, reversing if we hit either edge of the lower half
+/// of the screen.
+fn bounce(pos: &mut isize, delta: &mut isize, limit: isize) {
+    *pos += *delta;
+    if *pos < 24 || *pos >= limit {
+        *delta = -*delta;
+        *pos += 2 * *delta;
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,241 @@
+//! Watches a battery, and makes a fuss when it's getting flat.
+//!
+//! The battery goes to AIN8 (PE5) through a 10k/10k divider, as in the
+//! `dashboard` example, so it can be up to 6.6V - one LiPo cell, or four
+//! AA cells. `demo::power` keeps a rolling average, and when it drops
+//! below the threshold:
+//!
+//! * the screen says so, under the voltage
+//! * the red LED flashes (the green one is on while all is well)
+//! * a piezo sounder on PB4 beeps every `BEEP_EVERY_MS`
+//! * UART0 (115200 bps) gets a message
+//!
+//! It starts at `START_THRESHOLD_MV`, which suits a LiPo cell. Type `+` or
+//! `-` (without pressing Enter) to move it by 100 mV.
+//!
+//! The VGA output is the same as `hello_vga` (HSYNC on PB6, VSYNC on PC4
+//! and green on PB7).
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use demo::adc::{self, Adc};
+use demo::audio;
+use demo::board::Board;
+use demo::fault;
+use demo::font;
+use demo::graphics::{Canvas, Colour, VGA_WIDTH};
+use demo::power::{Divider, Event, Monitor};
+use demo::scheduler::{self, Task};
+use demo::status_bar;
+use demo::text::Buffer;
+use demo::vga;
+use embedded_hal::prelude::*;
+use tm4c123x_hal::sysctl;
+use tm4c123x_hal::tm4c123x::ADC0;
+
+/// The battery's analog input.
+const SUPPLY_CHANNEL: u8 = 8;
+
+const DIVIDER: Divider = Divider {
+    top: 10_000,
+    bottom: 10_000,
+};
+
+/// Where the threshold starts, and how far `+` and `-` move it.
+const START_THRESHOLD_MV: u32 = 3500;
+const STEP_MV: u32 = 100;
+const MIN_THRESHOLD_MV: u32 = 1000;
+const MAX_THRESHOLD_MV: u32 = 6000;
+
+/// How often to beep while the battery is low, and what with.
+const BEEP_EVERY_MS: u32 = 10_000;
+const BEEP_HZ: u32 = 2000;
+const BEEP_MS: u32 = 200;
+
+/// How big the readout is, and where things go.
+const SCALE: usize = 4;
+const READOUT_Y: usize = 96;
+const THRESHOLD_Y: usize = READOUT_Y + (font::HEIGHT * SCALE) + 24;
+const WARNING_Y: usize = THRESHOLD_Y + (font::HEIGHT * 2);
+
+struct Context {
+    board: Board,
+    adc: Adc<ADC0>,
+    monitor: Monitor,
+    /// When we last beeped
+    beep_ms: u32,
+    /// The red LED flashes while the battery is low
+    flash: bool,
+}
+
+static TASKS: [Task<Context>; 3] = [
+    Task {
+        name: "sample",
+        period_ms: 50,
+        run: sample,
+    },
+    Task {
+        name: "display",
+        period_ms: 500,
+        run: display,
+    },
+    Task {
+        name: "keys",
+        period_ms: 0,
+        run: keys,
+    },
+];
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::Adc0);
+    board.enable(sysctl::Domain::Pwm0);
+    board.enable(sysctl::Domain::Timer5);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    demo::config::Port::E.enable();
+    adc::configure_pin(SUPPLY_CHANNEL);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+
+    audio::init(p.PWM0, p.TIMER5, &board.clocks);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER5A);
+
+    vga::framebuffer().clear(Colour::BLACK);
+
+    writeln!(board.tx, "Power monitor. '+' and '-' move the threshold.").unwrap();
+
+    let clocks = board.clocks;
+    let mut context = Context {
+        board,
+        adc: Adc::adc0(p.ADC0),
+        monitor: Monitor::new(SUPPLY_CHANNEL, DIVIDER, START_THRESHOLD_MV),
+        beep_ms: 0,
+        flash: false,
+    };
+    display(&mut context);
+    scheduler::start(cp.SYST, &clocks, &TASKS).run(&mut context);
+}
+
+fn sample(c: &mut Context) {
+    let reading = c.adc.read(c.monitor.channel());
+    match c.monitor.sample(reading) {
+        Some(Event::Low(mv)) => {
+            writeln!(c.board.tx, "Battery low: {}.{:03}V", mv / 1000, mv % 1000).unwrap();
+            c.board.led_green.off();
+            c.beep_ms = scheduler::now();
+            audio::beep(BEEP_HZ, BEEP_MS);
+            display(c);
+        }
+        Some(Event::Recovered(mv)) => {
+            writeln!(c.board.tx, "Battery OK: {}.{:03}V", mv / 1000, mv % 1000).unwrap();
+            c.board.led_red.off();
+            display(c);
+        }
+        None => {}
+    }
+}
+
+/// Redraw the screen, and flash and beep if the battery is low.
+fn display(c: &mut Context) {
+    let low = c.monitor.is_low();
+    if low {
+        c.flash = !c.flash;
+        c.board.led_red.set(c.flash);
+        let now = scheduler::now();
+        if now.wrapping_sub(c.beep_ms) >= BEEP_EVERY_MS {
+            c.beep_ms = now;
+            audio::beep(BEEP_HZ, BEEP_MS);
+        }
+    } else {
+        c.board.led_green.on();
+    }
+
+    let fb = vga::framebuffer();
+    let mut text = Buffer::new();
+    match c.monitor.average_mv() {
+        Some(mv) => write!(text, "{}.{:02}V", mv / 1000, (mv % 1000) / 10).unwrap(),
+        None => write!(text, "-.--V").unwrap(),
+    }
+    status_bar::draw(fb, "Power monitor", text.as_str());
+    draw_centred(fb, READOUT_Y, text.as_str(), SCALE);
+
+    let threshold = c.monitor.threshold_mv();
+    let mut text = Buffer::new();
+    write!(
+        text,
+        "Alarm below {}.{:02}V",
+        threshold / 1000,
+        (threshold % 1000) / 10
+    ).unwrap();
+    draw_centred(fb, THRESHOLD_Y, text.as_str(), 1);
+    draw_centred(fb, WARNING_Y, if low { "BATTERY LOW" } else { "" }, 2);
+}
+
+/// `+` and `-` move the threshold.
+fn keys(c: &mut Context) {
+    while let Ok(ch) = c.board.rx.read() {
+        let threshold = c.monitor.threshold_mv();
+        let threshold = match ch {
+            b'+' => (threshold + STEP_MV).min(MAX_THRESHOLD_MV),
+            b'-' => (threshold - STEP_MV).max(MIN_THRESHOLD_MV),
+            _ => continue,
+        };
+        c.monitor.set_threshold_mv(threshold);
+        writeln!(
+            c.board.tx,
+            "Alarm below {}.{:03}V",
+            threshold / 1000,
+            threshold % 1000
+        ).unwrap();
+        display(c);
+    }
+}
+
+fn draw_centred<C>(canvas: &mut C, y: usize, s: &str, scale: usize)
+where
+    C: Canvas,
+{
+    let char_width = font::WIDTH * scale;
+    let width = (s.len() * char_width).min(VGA_WIDTH);
+    let x = (VGA_WIDTH - width) / 2;
+    canvas.fill_rect(0, y, VGA_WIDTH, font::HEIGHT * scale, Colour::BLACK);
+    canvas.draw_str_scaled(x, y, s, scale, Colour::WHITE, Colour::BLACK);
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+interrupt!(TIMER5A, audio::timer5a_isr);
+
+exception!(SysTick, scheduler::tick);
+
+// Print what went wrong on UART0 and flash the red LED
+exception!(HardFault, fault::hard_fault);
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
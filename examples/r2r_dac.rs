@@ -0,0 +1,143 @@
+//! Plays arpeggios on `demo::synth` through an R-2R ladder on Port D, with
+//! the VGA screen running.
+//!
+//! See `demo::dac` for the wiring (and the two resistors to take off the
+//! LaunchPad). The VGA output is the same as `hello_vga` (HSYNC on PB6,
+//! VSYNC on PC4 and green on PB7). uDMA moves every sample, so the only
+//! audio interrupt is one per 256 samples, to mix the next buffer; the
+//! status bar shows how many there have been.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use demo::board::Board;
+use demo::dac;
+use demo::graphics::Colour;
+use demo::midi;
+use demo::status_bar;
+use demo::synth::{self, Envelope};
+use demo::text::Buffer;
+use demo::udma;
+use demo::vga;
+use demo::wavetable::Waveform;
+use embedded_hal::prelude::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::delay::Delay;
+use tm4c123x_hal::sysctl;
+
+/// How long each note lasts.
+const NOTE_MS: u32 = 150;
+
+/// Semitones above the root for each step of the arpeggio.
+const ARPEGGIO: [u8; 4] = [0, 4, 7, 12];
+
+/// The roots, a bar each: C, A minor (ish), F and G.
+const ROOTS: [u8; 4] = [48, 45, 41, 43];
+
+/// A plucked sort of note.
+const PLUCK: Envelope = Envelope {
+    attack_ms: 2,
+    decay_ms: 120,
+    sustain: 64,
+    release_ms: 60,
+};
+
+/// Buffers mixed so far.
+static BUFFERS: AtomicUsize = AtomicUsize::new(0);
+
+/// Mix a buffer, and count it.
+fn fill(buffer: &mut [u8]) {
+    synth::fill(buffer);
+    BUFFERS.fetch_add(1, Ordering::Relaxed);
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::Timer3);
+    board.enable(sysctl::Domain::MicroDma);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    synth::init();
+    synth::with(|s| {
+        s.set_waveform(0, Waveform::Sawtooth);
+        s.set_envelope(0, PLUCK);
+        s.set_waveform(1, Waveform::Square);
+        s.set_volume(1, 128);
+    });
+    udma::init();
+    dac::start(p.TIMER3, synth::SAMPLE_RATE_HZ, &board.clocks, fill);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+    // Below the VGA timers, so the picture stays put
+    unsafe { nvic.set_priority(tm4c123x_hal::Interrupt::TIMER3A, 0x40) };
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER3A);
+
+    vga::framebuffer().clear(Colour::BLACK);
+
+    writeln!(board.tx, "Playing on the R-2R ladder").unwrap();
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+    loop {
+        for &root in ROOTS.iter() {
+            synth::with(|s| s.note_on(1, synth::note_millihertz(root - 12)));
+            for _ in 0..2 {
+                for &step in ARPEGGIO.iter() {
+                    let note = root + step;
+                    synth::with(|s| s.note_on(0, synth::note_millihertz(note)));
+
+                    let (name, octave) = midi::note_name(note);
+                    let mut text = Buffer::new();
+                    write!(
+                        text,
+                        "{}{} {} buffers",
+                        name,
+                        octave,
+                        BUFFERS.load(Ordering::Relaxed)
+                    ).unwrap();
+                    status_bar::draw(vga::framebuffer(), "R-2R DAC", text.as_str());
+
+                    d.delay_ms(NOTE_MS);
+                }
+            }
+            synth::with(|s| s.note_off(1));
+        }
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+interrupt!(TIMER3A, dac::timer3a_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
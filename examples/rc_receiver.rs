@@ -0,0 +1,582 @@
+//! Reads a hobby RC receiver and drives four servos (or ESCs) through a
+//! mixer.
+//!
+//! Power the receiver from 5V, and check its outputs are 3.3V logic - most
+//! are. The channels can come in three ways:
+//!
+//! * PPM - the receiver's PPM (or CPPM) output goes to PC4. This is the
+//!   default.
+//! * PWM - hold SW1 during reset, and wire six of the receiver's servo
+//!   outputs to PC4, PC5, PC6, PC7, PD2 and PD3, channel 1 first.
+//! * SBUS - hold SW2 during reset, and wire the receiver's SBUS output to
+//!   PB0 (U1Rx) through an inverter.
+//!
+//! For PPM and PWM, each pin is a wide timer in edge-time capture mode,
+//! and its interrupt feeds the decoder in `demo::rc`. SBUS is read from
+//! UART1 instead, but the frames come out the same.
+//!
+//! SBUS idles low, and the TM4C123's UARTs can't be told to expect that,
+//! so it has to be turned the right way up outside. An NPN transistor
+//! (2N3904, BC547 or similar) does it: SBUS to the base through 10k, the
+//! emitter to ground, and the collector to PB0 with 10k up to 3.3V. Any
+//! inverting logic gate running from 3.3V works too, and some receivers
+//! (many FrSky ones) have an uninverted SBUS pad that can go straight to
+//! PB0.
+//!
+//! The servos go on PB6, PB7, PB4 and PB5 (M0PWM0 to M0PWM3), at 50 Hz.
+//! PB6 and PB7 are joined to PD0 and PD1 on the LaunchPad, so leave those
+//! alone. SW2 picks the mixer:
+//!
+//! * pass-through - channels 1 to 4 go to outputs 1 to 4
+//! * elevon - for a flying wing, with aileron on channel 1 and elevator on
+//!   channel 2 (`AETR`, as most Mode 2 transmitters send)
+//! * V-tail - elevator and rudder (channel 4) on outputs 2 and 4
+//!
+//! If the frames stop for more than `FAILSAFE_MS`, the servo pulses stop
+//! too (most ESCs then shut off the motor), and the LED goes from green to
+//! red. An SBUS receiver that says it's in failsafe counts as no frames,
+//! so its own failsafe positions are never used. The channels and outputs
+//! are printed on UART0 (115200 bps) twice a second.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate nb;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use cortex_m::interrupt;
+use demo::board::Board;
+use demo::config::{Pin, Port};
+use demo::fault;
+use demo::rc::{self, Frame, Ppm, Pwm, Sbus};
+use demo::scheduler::{self, Task};
+use embedded_hal::prelude::*;
+use tm4c123x_hal::gpio::GpioExt;
+use tm4c123x_hal::serial::{NewlineMode, Rx, Serial};
+use tm4c123x_hal::sysctl;
+use tm4c123x_hal::time::U32Ext;
+use tm4c123x_hal::tm4c123x::{wtimer0, PWM0, UART1};
+
+/// Timer clocks per microsecond.
+const CLOCKS_PER_US: u32 = 80;
+
+// GPTMCFG, GPTMTnMR, GPTMCTL, GPTMIMR/GPTMICR
+const CFG_SPLIT: u32 = 0x4;
+const TMR_CAPTURE: u32 = 0x3;
+const TMR_CMR: u32 = 1 << 2;
+const TMR_CDIR: u32 = 1 << 4;
+const CTL_TAEN: u32 = 1 << 0;
+const CTL_TAEVENT_BOTH: u32 = 0x3 << 2;
+const CTL_TBEN: u32 = 1 << 8;
+const CTL_TBEVENT_BOTH: u32 = 0x3 << 10;
+const INT_CAE: u32 = 1 << 2;
+const INT_CBE: u32 = 1 << 10;
+
+const SBUS_BAUD: u32 = 100_000;
+
+// UARTCTL, UARTLCRH: SBUS is 8E2
+const CTL_UARTEN: u32 = 1 << 0;
+const LCRH_PEN: u32 = 1 << 1;
+const LCRH_EPS: u32 = 1 << 2;
+const LCRH_STP2: u32 = 1 << 3;
+
+/// WTnCCPn's alternate function.
+const AF_CAPTURE: u32 = 7;
+/// M0PWMn's alternate function.
+const AF_PWM: u32 = 4;
+/// RCC.USEPWMDIV, and RCC.PWMDIV set to /64, as in `demo::audio`.
+const RCC_PWMDIV_64: u32 = (1 << 20) | (0x7 << 17);
+/// 20 ms, in PWM clocks. There are 80 / 64 (so 5 / 4) to a microsecond.
+const PWM_PERIOD: u32 = 20_000 * 5 / 4;
+
+/// No frames for this long and we stop driving the servos.
+const FAILSAFE_MS: u32 = 100;
+
+const INPUT_COUNT: usize = 6;
+const OUTPUT_COUNT: usize = 4;
+
+/// Where the channels come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Ppm,
+    Pwm,
+    Sbus,
+}
+
+impl Mode {
+    fn name(self) -> &'static str {
+        match self {
+            Mode::Ppm => "PPM",
+            Mode::Pwm => "PWM",
+            Mode::Sbus => "SBUS",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Half {
+    A,
+    B,
+}
+
+/// A capture pin, and the wide timer behind it.
+struct Input {
+    pin: Pin,
+    timer: u8,
+    half: Half,
+}
+
+/// Channel 1 first. PPM only uses the first.
+static INPUTS: [Input; INPUT_COUNT] = [
+    Input {
+        pin: Pin { port: Port::C, bit: 4 },
+        timer: 0,
+        half: Half::A,
+    },
+    Input {
+        pin: Pin { port: Port::C, bit: 5 },
+        timer: 0,
+        half: Half::B,
+    },
+    Input {
+        pin: Pin { port: Port::C, bit: 6 },
+        timer: 1,
+        half: Half::A,
+    },
+    Input {
+        pin: Pin { port: Port::C, bit: 7 },
+        timer: 1,
+        half: Half::B,
+    },
+    Input {
+        pin: Pin { port: Port::D, bit: 2 },
+        timer: 3,
+        half: Half::A,
+    },
+    Input {
+        pin: Pin { port: Port::D, bit: 3 },
+        timer: 3,
+        half: Half::B,
+    },
+];
+
+/// M0PWM0 to M0PWM3.
+const OUTPUTS: [Pin; OUTPUT_COUNT] = [
+    Pin { port: Port::B, bit: 6 },
+    Pin { port: Port::B, bit: 7 },
+    Pin { port: Port::B, bit: 4 },
+    Pin { port: Port::B, bit: 5 },
+];
+
+/// For each output, how much of channels 1 to 4 goes to it (see
+/// `rc::mix`).
+struct Mixer {
+    name: &'static str,
+    weights: [[i16; 4]; OUTPUT_COUNT],
+}
+
+static MIXERS: [Mixer; 3] = [
+    Mixer {
+        name: "pass-through",
+        weights: [[100, 0, 0, 0], [0, 100, 0, 0], [0, 0, 100, 0], [0, 0, 0, 100]],
+    },
+    Mixer {
+        name: "elevon",
+        weights: [[50, 50, 0, 0], [-50, 50, 0, 0], [0, 0, 100, 0], [0, 0, 0, 100]],
+    },
+    Mixer {
+        name: "V-tail",
+        weights: [[100, 0, 0, 0], [0, 50, 0, 50], [0, 0, 100, 0], [0, 50, 0, -50]],
+    },
+];
+
+enum Decoder {
+    Ppm(Ppm),
+    Pwm(Pwm),
+}
+
+/// The latest PPM or PWM frame from the decoder, waiting for the `servos` task.
+static mut FRAME: Option<Frame> = None;
+
+/// Only the interrupts touch these.
+static mut DECODER: Option<Decoder> = None;
+static mut LAST_EDGE: [u32; INPUT_COUNT] = [0; INPUT_COUNT];
+
+struct Context {
+    board: Board,
+    pwm: PWM0,
+    mode: Mode,
+    /// Only for `Mode::Sbus`
+    sbus_rx: Option<Rx<UART1>>,
+    sbus: Sbus,
+    mixer: usize,
+    /// The last frame we had, and when
+    frame: Frame,
+    frame_ms: u32,
+    /// What we're sending the servos, in microseconds
+    outputs: [u16; OUTPUT_COUNT],
+    /// We've stopped the servos until the frames come back
+    lost: bool,
+    sw2_was: bool,
+}
+
+static TASKS: [Task<Context>; 3] = [
+    Task {
+        name: "servos",
+        // Often enough that the UART's FIFO can't fill up with SBUS
+        period_ms: 1,
+        run: servos,
+    },
+    Task {
+        name: "print",
+        period_ms: 500,
+        run: print,
+    },
+    Task {
+        name: "switches",
+        period_ms: 20,
+        run: switches,
+    },
+];
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::WideTimer0);
+    board.enable(sysctl::Domain::WideTimer1);
+    board.enable(sysctl::Domain::WideTimer3);
+    board.enable(sysctl::Domain::Pwm0);
+    Port::C.enable();
+    Port::D.enable();
+
+    let mode = if board.sw2.is_pressed() {
+        Mode::Sbus
+    } else if board.sw1.is_pressed() {
+        Mode::Pwm
+    } else {
+        Mode::Ppm
+    };
+
+    // Before the servo pins are set up, as this resets Port B
+    let sbus_rx = if mode == Mode::Sbus {
+        let mut portb = p.GPIO_PORTB.split(&board.power_control);
+        let uart1 = Serial::uart1(
+            p.UART1,
+            portb.pb1.into_af1(&mut portb.control),
+            portb.pb0.into_af1(&mut portb.control),
+            (),
+            (),
+            SBUS_BAUD.bps(),
+            NewlineMode::Binary,
+            &board.clocks,
+            &board.power_control,
+        );
+        // The HAL only does no parity and one stop bit, so we change the
+        // line control with the UART turned off.
+        let regs = unsafe { &*tm4c123x::UART1::ptr() };
+        regs.ctl
+            .modify(|r, w| unsafe { w.bits(r.bits() & !CTL_UARTEN) });
+        let line = LCRH_PEN | LCRH_EPS | LCRH_STP2;
+        regs.lcrh.modify(|r, w| unsafe { w.bits(r.bits() | line) });
+        regs.ctl.modify(|r, w| unsafe { w.bits(r.bits() | CTL_UARTEN) });
+        let (_, rx) = uart1.split();
+        Some(rx)
+    } else {
+        Port::B.enable();
+        start_capture(mode);
+        None
+    };
+
+    // PWM0 generators 0 and 1, counting down from the top of a 20 ms
+    // period. Each output goes high at its compare value and low again
+    // at the top, so the compare value is the pulse length.
+    let sysctl = unsafe { &*tm4c123x::SYSCTL::ptr() };
+    sysctl
+        .rcc
+        .modify(|r, w| unsafe { w.bits(r.bits() | RCC_PWMDIV_64) });
+    let pwm = p.PWM0;
+    pwm._0_ctl.write(|w| unsafe { w.bits(0) });
+    pwm._1_ctl.write(|w| unsafe { w.bits(0) });
+    pwm._0_load.write(|w| unsafe { w.bits(PWM_PERIOD - 1) });
+    pwm._1_load.write(|w| unsafe { w.bits(PWM_PERIOD - 1) });
+    // ACTCMPAD (or ACTCMPBD) = drive high, ACTLOAD = drive low
+    pwm._0_gena.write(|w| unsafe { w.bits((0x3 << 6) | (0x2 << 2)) });
+    pwm._0_genb.write(|w| unsafe { w.bits((0x3 << 10) | (0x2 << 2)) });
+    pwm._1_gena.write(|w| unsafe { w.bits((0x3 << 6) | (0x2 << 2)) });
+    pwm._1_genb.write(|w| unsafe { w.bits((0x3 << 10) | (0x2 << 2)) });
+    pwm._0_ctl.write(|w| unsafe { w.bits(1) });
+    pwm._1_ctl.write(|w| unsafe { w.bits(1) });
+    for pin in OUTPUTS.iter() {
+        pin.into_af(AF_PWM);
+    }
+
+    let mut nvic = cp.NVIC;
+    if mode != Mode::Sbus {
+        nvic.enable(tm4c123x_hal::Interrupt::WTIMER0A);
+    }
+    if mode == Mode::Pwm {
+        nvic.enable(tm4c123x_hal::Interrupt::WTIMER0B);
+        nvic.enable(tm4c123x_hal::Interrupt::WTIMER1A);
+        nvic.enable(tm4c123x_hal::Interrupt::WTIMER1B);
+        nvic.enable(tm4c123x_hal::Interrupt::WTIMER3A);
+        nvic.enable(tm4c123x_hal::Interrupt::WTIMER3B);
+    }
+
+    writeln!(
+        board.tx,
+        "RC receiver, {} in, {} mixer",
+        mode.name(),
+        MIXERS[0].name
+    ).unwrap();
+    board.led_red.on();
+
+    let clocks = board.clocks;
+    let mut context = Context {
+        board,
+        pwm,
+        mode,
+        sbus_rx,
+        sbus: Sbus::new(),
+        mixer: 0,
+        frame: Frame::new(),
+        frame_ms: 0,
+        outputs: [rc::CENTRE_US; OUTPUT_COUNT],
+        lost: true,
+        // So holding it down at reset isn't a press
+        sw2_was: true,
+    };
+    scheduler::start(cp.SYST, &clocks, &TASKS).run(&mut context);
+}
+
+/// Set up the wide timers for PPM or PWM, and the decoder their
+/// interrupts feed.
+fn start_capture(mode: Mode) {
+    let used = if mode == Mode::Ppm { 1 } else { INPUT_COUNT };
+    unsafe {
+        DECODER = Some(if mode == Mode::Ppm {
+            Decoder::Ppm(Ppm::new())
+        } else {
+            Decoder::Pwm(Pwm::new(INPUT_COUNT))
+        });
+    }
+
+    // Each timer half counts up, grabbing the count on every edge for PWM,
+    // or on rising edges for PPM. The time between one rising edge and the
+    // next is a PPM channel whichever way up the receiver sends it.
+    let events = if mode == Mode::Ppm {
+        0
+    } else {
+        CTL_TAEVENT_BOTH | CTL_TBEVENT_BOTH
+    };
+    for &number in &[0, 1, 3] {
+        let timer = timer(number);
+        timer.ctl.write(|w| unsafe { w.bits(0) });
+        timer.cfg.write(|w| unsafe { w.bits(CFG_SPLIT) });
+        let capture = TMR_CAPTURE | TMR_CMR | TMR_CDIR;
+        timer.tamr.write(|w| unsafe { w.bits(capture) });
+        timer.tbmr.write(|w| unsafe { w.bits(capture) });
+        timer.tailr.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+        timer.tbilr.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+        timer.icr.write(|w| unsafe { w.bits(INT_CAE | INT_CBE) });
+    }
+    for input in &INPUTS[0..used] {
+        input.pin.into_af(AF_CAPTURE);
+        let (mask, enable) = match input.half {
+            Half::A => (INT_CAE, CTL_TAEN),
+            Half::B => (INT_CBE, CTL_TBEN),
+        };
+        let timer = timer(input.timer);
+        timer
+            .imr
+            .modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+        timer
+            .ctl
+            .modify(|r, w| unsafe { w.bits(r.bits() | events | enable) });
+    }
+}
+
+fn timer(number: u8) -> &'static wtimer0::RegisterBlock {
+    unsafe {
+        match number {
+            0 => &*tm4c123x::WTIMER0::ptr(),
+            1 => &*tm4c123x::WTIMER1::ptr(),
+            _ => &*tm4c123x::WTIMER3::ptr(),
+        }
+    }
+}
+
+/// Mix the latest frame out to the servos, or stop them if the frames
+/// have dried up.
+fn servos(c: &mut Context) {
+    let now = scheduler::now();
+    let frame = match c.sbus_rx {
+        Some(ref mut rx) => read_sbus(rx, &mut c.sbus),
+        None => interrupt::free(|_| unsafe { FRAME.take() }),
+    };
+    match frame {
+        Some(frame) if !frame.failsafe => {
+            c.frame = frame;
+            c.frame_ms = now;
+            let weights = &MIXERS[c.mixer].weights;
+            for (output, weights) in c.outputs.iter_mut().zip(weights.iter()) {
+                *output = rc::mix(&frame, weights);
+            }
+            let outputs = c.outputs;
+            set_outputs(&c.pwm, &outputs);
+            if c.lost {
+                c.lost = false;
+                c.pwm.enable.modify(|r, w| unsafe { w.bits(r.bits() | 0xF) });
+                c.board.led_red.off();
+                c.board.led_green.on();
+                writeln!(c.board.tx, "Signal found").unwrap();
+            }
+        }
+        _ => {
+            if !c.lost && now.wrapping_sub(c.frame_ms) > FAILSAFE_MS {
+                c.lost = true;
+                c.pwm.enable.modify(|r, w| unsafe { w.bits(r.bits() & !0xF) });
+                c.board.led_green.off();
+                c.board.led_red.on();
+                writeln!(c.board.tx, "Signal lost").unwrap();
+            }
+        }
+    }
+}
+
+/// Everything UART1 has for us, keeping the newest frame.
+fn read_sbus(rx: &mut Rx<UART1>, sbus: &mut Sbus) -> Option<Frame> {
+    let mut frame = None;
+    loop {
+        match rx.read() {
+            Ok(byte) => {
+                if let Some(new) = sbus.input(byte) {
+                    frame = Some(new);
+                }
+            }
+            Err(nb::Error::WouldBlock) => return frame,
+            // A parity or framing error, so this frame is no good
+            Err(nb::Error::Other(_)) => sbus.reset(),
+        }
+    }
+}
+
+fn set_outputs(pwm: &PWM0, outputs: &[u16; OUTPUT_COUNT]) {
+    let compare = |us: u16| (u32::from(us) * 5) / 4;
+    pwm._0_cmpa.write(|w| unsafe { w.bits(compare(outputs[0])) });
+    pwm._0_cmpb.write(|w| unsafe { w.bits(compare(outputs[1])) });
+    pwm._1_cmpa.write(|w| unsafe { w.bits(compare(outputs[2])) });
+    pwm._1_cmpb.write(|w| unsafe { w.bits(compare(outputs[3])) });
+}
+
+fn print(c: &mut Context) {
+    if c.lost {
+        return;
+    }
+    let frame = c.frame;
+    let outputs = c.outputs;
+    let tx = &mut c.board.tx;
+    write!(tx, "{}", c.mode.name()).unwrap();
+    for us in frame.channels() {
+        write!(tx, " {}", us).unwrap();
+    }
+    write!(tx, " ->").unwrap();
+    for us in outputs.iter() {
+        write!(tx, " {}", us).unwrap();
+    }
+    if c.mode == Mode::Sbus {
+        write!(tx, " ({} frames lost)", c.sbus.lost_frames()).unwrap();
+    }
+    writeln!(tx).unwrap();
+}
+
+/// SW2 moves on to the next mixer.
+fn switches(c: &mut Context) {
+    let sw2 = c.board.sw2.is_pressed();
+    if sw2 && !c.sw2_was {
+        c.mixer = (c.mixer + 1) % MIXERS.len();
+        writeln!(c.board.tx, "{} mixer", MIXERS[c.mixer].name).unwrap();
+    }
+    c.sw2_was = sw2;
+}
+
+/// An edge on one of the inputs. For PWM, if the pin is low now, a pulse
+/// has just finished.
+fn capture(input: usize) {
+    let info = &INPUTS[input];
+    let timer = timer(info.timer);
+    let now = match info.half {
+        Half::A => {
+            timer.icr.write(|w| unsafe { w.bits(INT_CAE) });
+            timer.tar.read().bits()
+        }
+        Half::B => {
+            timer.icr.write(|w| unsafe { w.bits(INT_CBE) });
+            timer.tbr.read().bits()
+        }
+    };
+    let us = now.wrapping_sub(unsafe { LAST_EDGE[input] }) / CLOCKS_PER_US;
+    unsafe { LAST_EDGE[input] = now };
+    let frame = match unsafe { DECODER.as_mut() } {
+        Some(Decoder::Ppm(ppm)) => ppm.pulse(us),
+        Some(Decoder::Pwm(pwm)) if !info.pin.is_high() => pwm.pulse(input, us),
+        _ => None,
+    };
+    if frame.is_some() {
+        unsafe { FRAME = frame };
+    }
+}
+
+interrupt!(WTIMER0A, wtimer0a_isr);
+interrupt!(WTIMER0B, wtimer0b_isr);
+interrupt!(WTIMER1A, wtimer1a_isr);
+interrupt!(WTIMER1B, wtimer1b_isr);
+interrupt!(WTIMER3A, wtimer3a_isr);
+interrupt!(WTIMER3B, wtimer3b_isr);
+
+fn wtimer0a_isr() {
+    capture(0);
+}
+
+fn wtimer0b_isr() {
+    capture(1);
+}
+
+fn wtimer1a_isr() {
+    capture(2);
+}
+
+fn wtimer1b_isr() {
+    capture(3);
+}
+
+fn wtimer3a_isr() {
+    capture(4);
+}
+
+fn wtimer3b_isr() {
+    capture(5);
+}
+
+exception!(SysTick, scheduler::tick);
+
+// Print what went wrong on UART0 and flash the red LED
+exception!(HardFault, fault::hard_fault);
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
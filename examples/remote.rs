@@ -0,0 +1,254 @@
+//! Lets scripts on a PC drive the board with `demo::remote` packets, while
+//! you can still type at the menu on the same port.
+//!
+//! Everything is on UART0 at 115200 bps. Anything that isn't in a COBS
+//! frame goes to the menu (`stats`, and `clear` for the screen), so a
+//! terminal works as usual. The VGA output is the same as `hello_vga`
+//! (HSYNC on PB6, VSYNC on PC4 and green on PB7), for `FILL_RECT` and
+//! `DRAW_TEXT`.
+//!
+//! The host can read flash and RAM, read AIN0 (PE3), AIN1 (PE2) and the
+//! temperature sensor, and drive the LED (PF1 to PF3) and PB0 to PB3. From
+//! Python, with pyserial and the `cobs` package:
+//!
+//! ```text
+//! def request(port, command, tag, args=b""):
+//!     body = bytes([command, tag]) + args
+//!     packet = body + struct.pack("<H", binascii.crc_hqx(body, 0))
+//!     port.write(b"\0" + cobs.encode(packet) + b"\0")
+//!     port.read_until(b"\0")  # the zero before the reply
+//!     return cobs.decode(port.read_until(b"\0")[:-1])
+//!
+//! request(port, 0x02, 1, bytes([5, 1, 1]))  # PF1 on - the LED goes red
+//! ```
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use demo::adc::{self, Adc};
+use demo::board::Board;
+use demo::cobs::{self, Input};
+use demo::config::{Pin, Port};
+use demo::console::Console;
+use demo::font;
+use demo::graphics::{Canvas, Colour, VGA_HEIGHT, VGA_WIDTH};
+use demo::remote::{self, Target};
+use demo::vga;
+use embedded_hal::prelude::*;
+use menu::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::sysctl;
+use tm4c123x_hal::tm4c123x::ADC0;
+
+/// What the host can read.
+const FLASH: (u32, u32) = (0x0000_0000, 0x0004_0000);
+const RAM: (u32, u32) = (0x2000_0000, 0x2000_8000);
+
+/// The pins the host can drive. PB4 is the VGA's SSI2 clock.
+const PINS: [Pin; 7] = [
+    Pin { port: Port::B, bit: 0 },
+    Pin { port: Port::B, bit: 1 },
+    Pin { port: Port::B, bit: 2 },
+    Pin { port: Port::B, bit: 3 },
+    Pin { port: Port::F, bit: 1 },
+    Pin { port: Port::F, bit: 2 },
+    Pin { port: Port::F, bit: 3 },
+];
+
+/// Ports, as the protocol numbers them.
+const PORTS: [Port; 6] = [Port::A, Port::B, Port::C, Port::D, Port::E, Port::F];
+
+/// A reply, once it's been through COBS.
+const MAX_ENCODED: usize = remote::MAX_PACKET + 2;
+
+/// Requests we've answered.
+static SERVED: AtomicUsize = AtomicUsize::new(0);
+/// Packets that didn't pass the CRC check.
+static CORRUPT: AtomicUsize = AtomicUsize::new(0);
+
+const STATS_ITEM: Item = Item {
+    item_type: ItemType::Callback(stats_callback),
+    command: "stats",
+    help: Some("- count the packets"),
+};
+
+const CLEAR_ITEM: Item = Item {
+    item_type: ItemType::Callback(clear_callback),
+    command: "clear",
+    help: Some("- clear the screen"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[&STATS_ITEM, &CLEAR_ITEM],
+    entry: None,
+    exit: None,
+};
+
+/// What the host gets to play with.
+struct Remote {
+    adc: Adc<ADC0>,
+}
+
+impl Target for Remote {
+    fn read_memory(&mut self, address: u32, data: &mut [u8]) -> bool {
+        let end = match address.checked_add(data.len() as u32) {
+            Some(end) => end,
+            None => return false,
+        };
+        let allowed = [FLASH, RAM];
+        if !allowed.iter().any(|&(start, top)| address >= start && end <= top) {
+            return false;
+        }
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = unsafe { core::ptr::read_volatile((address as usize + i) as *const u8) };
+        }
+        true
+    }
+
+    fn set_gpio(&mut self, port: u8, pin: u8, high: bool) -> bool {
+        let wanted = match PORTS.get(port as usize) {
+            Some(&port) => Pin { port, bit: pin },
+            None => return false,
+        };
+        if !PINS.contains(&wanted) {
+            return false;
+        }
+        wanted.set(high);
+        true
+    }
+
+    fn read_adc(&mut self, channel: u8) -> Option<u16> {
+        match channel {
+            0 | 1 | adc::TEMPERATURE => Some(self.adc.read(channel)),
+            _ => None,
+        }
+    }
+
+    fn fill_rect(&mut self, x: u16, y: u16, width: u16, height: u16, colour: u16) -> bool {
+        let (x, y, width, height) = (x as usize, y as usize, width as usize, height as usize);
+        if x + width > VGA_WIDTH || y + height > VGA_HEIGHT {
+            return false;
+        }
+        vga::framebuffer().fill_rect(x, y, width, height, Colour(colour));
+        true
+    }
+
+    fn draw_text(&mut self, x: u16, y: u16, text: &[u8]) -> bool {
+        let (x, y) = (x as usize, y as usize);
+        if x + (text.len() * font::WIDTH) > VGA_WIDTH || y + font::HEIGHT > VGA_HEIGHT {
+            return false;
+        }
+        let fb = vga::framebuffer();
+        for (i, &ch) in text.iter().enumerate() {
+            fb.draw_char(x + (i * font::WIDTH), y, ch, Colour::WHITE, Colour::BLACK);
+        }
+        true
+    }
+}
+
+fn stats_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    writeln!(
+        Console,
+        "{} requests answered, {} corrupt",
+        SERVED.load(Ordering::Relaxed),
+        CORRUPT.load(Ordering::Relaxed)
+    ).unwrap();
+}
+
+fn clear_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    vga::framebuffer().clear(Colour::BLACK);
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::Adc0);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    Port::E.enable();
+    adc::configure_pin(0);
+    adc::configure_pin(1);
+    // The LED pins are outputs already
+    for pin in PINS.iter().filter(|pin| pin.port == Port::B) {
+        pin.into_output();
+    }
+    let mut target = Remote {
+        adc: Adc::adc0(p.ADC0),
+    };
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+
+    vga::framebuffer().clear(Colour::BLACK);
+
+    writeln!(board.tx, "Remote control, protocol version {}", remote::VERSION).unwrap();
+
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+
+    let mut decoder = cobs::Decoder::new();
+    let mut reply = [0u8; remote::MAX_PACKET];
+    let mut encoded = [0u8; MAX_ENCODED];
+
+    loop {
+        while let Ok(byte) = board.rx.read() {
+            match decoder.input(byte) {
+                Input::Text(byte) => r.input_byte(byte),
+                Input::Packet(request) => {
+                    let len = remote::serve(request, &mut target, &mut reply);
+                    if len == 0 {
+                        CORRUPT.fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    }
+                    SERVED.fetch_add(1, Ordering::Relaxed);
+                    // The Runner has the Tx, so this goes round it.
+                    // `write_byte` sends a `\n` as it is, which we need.
+                    let len = cobs::encode(&reply[0..len], &mut encoded);
+                    Console.write_byte(0);
+                    for &byte in &encoded[0..len] {
+                        Console.write_byte(byte);
+                    }
+                    Console.write_byte(0);
+                }
+                Input::Nothing => {}
+            }
+        }
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,343 @@
+//! Reads ISO14443A (MIFARE etc.) card UIDs with an MFRC522 'RC522' module.
+//!
+//! SCK is PA2 (SSI0Clk), MISO is PA4 (SSI0Rx), MOSI is PA5 (SSI0Tx), SDA is
+//! PA3 and RST is PA6. The VGA output is the same as `hello_vga`: HSYNC on
+//! PB6, VSYNC on PC4 and green on PB7.
+//!
+//! Every card we see is shown on the console (UART0, 115200 bps) and the
+//! VGA screen. If the UID is in the list of known cards, kept in the
+//! on-chip EEPROM, we 'open the door' by turning on the green LED (PF3) for
+//! a few seconds.
+//!
+//! Commands:
+//!
+//! * `add` - add the last card seen to the known list
+//! * `del` - remove the last card seen from the known list
+//! * `list` - show the known cards
+//! * `wipe` - forget all the known cards
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+extern crate vga_framebuffer as fb;
+
+use core::fmt::Write;
+use demo::board::Board;
+use demo::console::Console;
+use demo::eeprom::{self, Eeprom};
+use demo::mfrc522::{Mfrc522, Uid, MAX_UID};
+use demo::spi::Spi;
+use demo::vga;
+use embedded_hal::prelude::*;
+use embedded_hal::spi::MODE_0;
+use menu::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::delay::Delay;
+use tm4c123x_hal::sysctl;
+use tm4c123x_hal::time::U32Ext;
+
+/// How many cards we remember.
+const MAX_CARDS: usize = 32;
+
+/// Each card takes two words: the length, then the UID bytes.
+const WORDS_PER_CARD: usize = 2;
+
+/// Marks the EEPROM as holding a card list, rather than junk.
+const MAGIC: u32 = 0x5246_4944;
+
+/// EEPROM word addresses.
+const ADDR_MAGIC: usize = 0;
+const ADDR_COUNT: usize = 1;
+const ADDR_CARDS: usize = 2;
+
+/// How long the door stays open.
+const DOOR_OPEN_MS: u32 = 3_000;
+
+/// How often we poll for a card.
+const POLL_MS: u32 = 100;
+
+/// The known cards, mirrored in EEPROM.
+struct CardList {
+    eeprom: Eeprom,
+    count: usize,
+    cards: [Uid; MAX_CARDS],
+}
+
+impl CardList {
+    fn load(eeprom: Eeprom) -> CardList {
+        let mut list = CardList {
+            eeprom,
+            count: 0,
+            cards: [Uid {
+                len: 0,
+                bytes: [0u8; MAX_UID],
+            }; MAX_CARDS],
+        };
+        let mut header = [0u32; 2];
+        list.eeprom.read(ADDR_MAGIC, &mut header).unwrap();
+        if header[0] != MAGIC || header[1] as usize > MAX_CARDS {
+            // Blank (or garbage) EEPROM
+            list.save().unwrap();
+            return list;
+        }
+        list.count = header[1] as usize;
+        for i in 0..list.count {
+            let mut words = [0u32; WORDS_PER_CARD];
+            list.eeprom
+                .read(ADDR_CARDS + (i * WORDS_PER_CARD), &mut words)
+                .unwrap();
+            let len = (words[0] & 0xFF) as usize;
+            if len > MAX_UID {
+                // Garbage, so start again
+                list.count = 0;
+                list.save().unwrap();
+                return list;
+            }
+            let card = &mut list.cards[i];
+            card.len = len;
+            for (j, byte) in card.bytes.iter_mut().enumerate() {
+                *byte = (words[(j + 1) / 4] >> (((j + 1) % 4) * 8)) as u8;
+            }
+        }
+        list
+    }
+
+    fn save(&mut self) -> Result<(), eeprom::Error> {
+        for i in 0..self.count {
+            let card = &self.cards[i];
+            let mut words = [card.len as u32, 0];
+            for (j, byte) in card.bytes.iter().enumerate() {
+                words[(j + 1) / 4] |= u32::from(*byte) << (((j + 1) % 4) * 8);
+            }
+            self.eeprom
+                .write(ADDR_CARDS + (i * WORDS_PER_CARD), &words)?;
+        }
+        self.eeprom.write(ADDR_MAGIC, &[MAGIC, self.count as u32])
+    }
+
+    fn contains(&self, uid: &Uid) -> bool {
+        self.cards[0..self.count].iter().any(|c| c == uid)
+    }
+
+    fn add(&mut self, uid: &Uid) -> bool {
+        if self.contains(uid) || self.count == MAX_CARDS {
+            return false;
+        }
+        self.cards[self.count] = *uid;
+        self.count += 1;
+        self.save().unwrap();
+        true
+    }
+
+    fn remove(&mut self, uid: &Uid) -> bool {
+        match self.cards[0..self.count].iter().position(|c| c == uid) {
+            Some(idx) => {
+                self.cards[idx] = self.cards[self.count - 1];
+                self.count -= 1;
+                self.save().unwrap();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn wipe(&mut self) {
+        self.count = 0;
+        self.save().unwrap();
+    }
+}
+
+static mut CARDS: Option<CardList> = None;
+
+static mut LAST_UID: Option<Uid> = None;
+
+const ADD_ITEM: Item = Item {
+    item_type: ItemType::Callback(add_callback),
+    command: "add",
+    help: Some("add the last card seen"),
+};
+
+const DEL_ITEM: Item = Item {
+    item_type: ItemType::Callback(del_callback),
+    command: "del",
+    help: Some("remove the last card seen"),
+};
+
+const LIST_ITEM: Item = Item {
+    item_type: ItemType::Callback(list_callback),
+    command: "list",
+    help: Some("show the known cards"),
+};
+
+const WIPE_ITEM: Item = Item {
+    item_type: ItemType::Callback(wipe_callback),
+    command: "wipe",
+    help: Some("forget all the known cards"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[&ADD_ITEM, &DEL_ITEM, &LIST_ITEM, &WIPE_ITEM],
+    entry: None,
+    exit: None,
+};
+
+fn cards() -> &'static mut CardList {
+    unsafe { CARDS.as_mut().unwrap() }
+}
+
+fn add_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    match unsafe { LAST_UID } {
+        Some(uid) if cards().add(&uid) => writeln!(Console, "Added {}", UidFmt(&uid)).unwrap(),
+        Some(_) => writeln!(Console, "Already known, or the list is full").unwrap(),
+        None => writeln!(Console, "Show me a card first").unwrap(),
+    }
+}
+
+fn del_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    match unsafe { LAST_UID } {
+        Some(uid) if cards().remove(&uid) => {
+            writeln!(Console, "Removed {}", UidFmt(&uid)).unwrap()
+        }
+        Some(_) => writeln!(Console, "That card isn't known").unwrap(),
+        None => writeln!(Console, "Show me a card first").unwrap(),
+    }
+}
+
+fn list_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    let cards = cards();
+    writeln!(Console, "{} of {} cards known", cards.count, MAX_CARDS).unwrap();
+    for card in &cards.cards[0..cards.count] {
+        writeln!(Console, "  {}", UidFmt(card)).unwrap();
+    }
+}
+
+fn wipe_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    cards().wipe();
+    writeln!(Console, "All cards forgotten").unwrap();
+}
+
+/// Shows a UID as colon separated hex.
+struct UidFmt<'a>(&'a Uid);
+
+impl<'a> core::fmt::Display for UidFmt<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        for (i, byte) in self.0.as_bytes().iter().enumerate() {
+            if i != 0 {
+                write!(f, ":")?;
+            }
+            write!(f, "{:02X}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi0);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::Eeprom);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+
+    // SSI0Clk, SSI0Rx and SSI0Tx
+    let mut porta = board.porta;
+    let _sck = porta.pa2.into_af2(&mut porta.control);
+    let _miso = porta.pa4.into_af2(&mut porta.control);
+    let _mosi = porta.pa5.into_af2(&mut porta.control);
+    let spi = Spi::ssi0(p.SSI0, MODE_0, 4_000_000_u32.hz(), &board.clocks);
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+
+    let mut rst = porta.pa6.into_push_pull_output();
+    let cs = porta.pa3.into_push_pull_output();
+    let mut reader = Mfrc522::new(spi, cs, &mut rst, &mut d).unwrap();
+
+    let eeprom = Eeprom::new(p.EEPROM).unwrap();
+    unsafe {
+        CARDS = Some(CardList::load(eeprom));
+    }
+
+    let mut c = fb::TextFrameBuffer::new(vga::framebuffer());
+    c.clear();
+    writeln!(c, "RFID door entry").unwrap();
+    writeln!(c, "{} cards known", cards().count).unwrap();
+
+    writeln!(
+        board.tx,
+        "MFRC522 version 0x{:02X}, {} cards known",
+        reader.version().unwrap(),
+        cards().count
+    ).unwrap();
+
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+
+    let mut door_timer = 0;
+
+    loop {
+        while let Ok(ch) = board.rx.read() {
+            r.input_byte(ch);
+        }
+
+        match reader.poll() {
+            Ok(Some(uid)) => {
+                let known = cards().contains(&uid);
+                let verdict = if known { "welcome" } else { "unknown" };
+                writeln!(Console, "Card {} - {}", UidFmt(&uid), verdict).unwrap();
+                writeln!(c, "Card {} - {}", UidFmt(&uid), verdict).unwrap();
+                if known {
+                    board.led_green.on();
+                    door_timer = DOOR_OPEN_MS;
+                }
+                unsafe { LAST_UID = Some(uid) };
+            }
+            Ok(None) => {}
+            // Usually a card on the edge of the field - try again
+            Err(_) => {}
+        }
+
+        d.delay_ms(POLL_MS);
+        if door_timer > 0 {
+            door_timer = door_timer.saturating_sub(POLL_MS);
+            if door_timer == 0 {
+                board.led_green.off();
+            }
+        }
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
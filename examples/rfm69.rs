@@ -0,0 +1,143 @@
+//! A beacon and receiver pair using RFM69HW packet radios.
+//!
+//! SCK is PA2 (SSI0Clk), MISO is PA4 (SSI0Rx), MOSI is PA5 (SSI0Tx), NSS is
+//! PA3 and RESET is PA6. Power the module from 3.3V - the HW module can pull
+//! 130mA when transmitting, so don't skimp on decoupling.
+//!
+//! Both boards start as receivers, printing every packet they hear along
+//! with its RSSI. Press `b` on the console (UART0, 115200 bps) of one board
+//! to make it a beacon, sending a numbered packet every second. Press `r` to
+//! go back to receiving. Packets are AES encrypted, so both ends must be
+//! built with the same `KEY`.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+
+use core::fmt::Write;
+use demo::config;
+use demo::hal;
+use demo::rfm69::Rfm69;
+use demo::spi::Spi;
+use demo::text::Buffer;
+use embedded_hal::prelude::*;
+use embedded_hal::spi::MODE_0;
+use hal::delay::Delay;
+use hal::sysctl;
+use hal::time::U32Ext;
+use rt::ExceptionFrame;
+
+/// Change this to 433 or 868 MHz to suit your module and your local rules.
+const FREQUENCY_HZ: u32 = 915_000_000;
+
+/// The shared AES-128 key.
+const KEY: [u8; 16] = [
+    0x4D, 0x6F, 0x6E, 0x6F, 0x74, 0x72, 0x6F, 0x6E, 0x20, 0x52, 0x46, 0x4D, 0x36, 0x39, 0x48,
+    0x57,
+];
+
+/// Milliseconds between beacons.
+const BEACON_INTERVAL_MS: u32 = 1_000;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Beacon,
+    Receive,
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Ssi0);
+
+    // SSI0Clk, SSI0Rx and SSI0Tx
+    config::SSI0.connect();
+    let porta = board.porta;
+    let spi = Spi::ssi0(p.SSI0, MODE_0, 4_000_000_u32.hz(), &board.clocks);
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+
+    let mut rst = porta.pa6.into_push_pull_output();
+    let mut radio = Rfm69::new(
+        spi,
+        porta.pa3.into_push_pull_output(),
+        &mut rst,
+        &mut d,
+        FREQUENCY_HZ,
+    ).unwrap();
+    radio.set_aes_key(Some(&KEY)).unwrap();
+
+    writeln!(board.tx, "RFM69 demo - b for beacon, r for receiver").unwrap();
+
+    let mut role = Role::Receive;
+    let mut count = 0u32;
+    let mut elapsed_ms = 0;
+
+    loop {
+        while let Ok(ch) = board.rx.read() {
+            match ch {
+                b'b' if role != Role::Beacon => {
+                    writeln!(board.tx, "Beacon mode").unwrap();
+                    role = Role::Beacon;
+                    elapsed_ms = BEACON_INTERVAL_MS;
+                }
+                b'r' if role != Role::Receive => {
+                    writeln!(board.tx, "Receive mode").unwrap();
+                    role = Role::Receive;
+                }
+                _ => {}
+            }
+        }
+
+        match role {
+            Role::Beacon => {
+                if elapsed_ms >= BEACON_INTERVAL_MS {
+                    elapsed_ms = 0;
+                    let mut packet = Buffer::from_storage([0u8; 32]);
+                    write!(packet, "Monotron beacon {}", count).unwrap();
+                    radio.send(packet.as_bytes()).unwrap();
+                    writeln!(board.tx, "Sent beacon {}", count).unwrap();
+                    count = count.wrapping_add(1);
+                }
+                d.delay_ms(10u32);
+                elapsed_ms += 10;
+            }
+            Role::Receive => {
+                if let Some(packet) = radio.receive().unwrap() {
+                    write!(board.tx, "{} dBm: ", packet.rssi).unwrap();
+                    for &byte in packet.payload() {
+                        let ch = match byte {
+                            0x20...0x7E => byte as char,
+                            _ => '.',
+                        };
+                        board.tx.write_char(ch).unwrap();
+                    }
+                    writeln!(board.tx, "").unwrap();
+                }
+            }
+        }
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
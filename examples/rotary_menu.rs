@@ -0,0 +1,212 @@
+//! Drive a menu with nothing but a rotary encoder.
+//!
+//! See `demo::rotary` for the wiring - the encoder on PD6 and PD7, and its
+//! button on PE5. The VGA output is the same as `hello_vga` (HSYNC on PB6,
+//! VSYNC on PC4 and green on PB7). Turn the knob to move through the menu,
+//! and press it to pick an item; what the item says appears under the
+//! menu. The `led` sub-menu sets the LaunchPad's RGB LED.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate menu;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use cortex_m::peripheral::syst::SystClkSource;
+use demo::board::Board;
+use demo::console::Console;
+use demo::graphics::{Canvas, Colour};
+use demo::rotary::{self, Rotary};
+use demo::scroll_menu::ScrollMenu;
+use demo::status_bar;
+use demo::vga;
+use menu::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::sysctl;
+
+/// SysTick's byte in the SCB's SHPR registers.
+const SHPR_SYSTICK: usize = 11;
+
+const RED_ITEM: Item = Item {
+    item_type: ItemType::Callback(led_callback),
+    command: "red",
+    help: Some("light the red LED"),
+};
+
+const GREEN_ITEM: Item = Item {
+    item_type: ItemType::Callback(led_callback),
+    command: "green",
+    help: Some("light the green LED"),
+};
+
+const BLUE_ITEM: Item = Item {
+    item_type: ItemType::Callback(led_callback),
+    command: "blue",
+    help: Some("light the blue LED"),
+};
+
+const OFF_ITEM: Item = Item {
+    item_type: ItemType::Callback(led_callback),
+    command: "off",
+    help: Some("turn the LED off"),
+};
+
+const LED_MENU: Menu = Menu {
+    label: "led",
+    items: &[&RED_ITEM, &GREEN_ITEM, &BLUE_ITEM, &OFF_ITEM],
+    entry: None,
+    exit: None,
+};
+
+const LED_ITEM: Item = Item {
+    item_type: ItemType::Menu(&LED_MENU),
+    command: "led",
+    help: Some("set the RGB LED"),
+};
+
+const UPTIME_ITEM: Item = Item {
+    item_type: ItemType::Callback(uptime_callback),
+    command: "uptime",
+    help: Some("how long since reset"),
+};
+
+const COUNT_ITEM: Item = Item {
+    item_type: ItemType::Callback(count_callback),
+    command: "count",
+    help: Some("add one"),
+};
+
+const ABOUT_ITEM: Item = Item {
+    item_type: ItemType::Callback(about_callback),
+    command: "about",
+    help: Some("what this is"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[&LED_ITEM, &UPTIME_ITEM, &COUNT_ITEM, &ABOUT_ITEM],
+    entry: None,
+    exit: None,
+};
+
+/// Milliseconds, counted by SysTick.
+static TICKS: AtomicUsize = AtomicUsize::new(0);
+
+/// Which LED the menu wants lit: 0 for none, then red, green and blue.
+static LED: AtomicUsize = AtomicUsize::new(0);
+
+/// For `count`.
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+fn led_callback(_menu: &Menu, item: &Item, _input: &str) {
+    let led = match item.command {
+        "red" => 1,
+        "green" => 2,
+        "blue" => 3,
+        _ => 0,
+    };
+    LED.store(led, Ordering::Relaxed);
+    writeln!(Console, "LED {}", item.command).unwrap();
+}
+
+fn uptime_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    let ms = TICKS.load(Ordering::Relaxed);
+    writeln!(Console, "Up for {}.{:03} seconds", ms / 1000, ms % 1000).unwrap();
+}
+
+fn count_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    let count = COUNT.fetch_add(1, Ordering::Relaxed) + 1;
+    writeln!(Console, "Count is {}", count).unwrap();
+}
+
+fn about_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    writeln!(Console, "A menu driven by a rotary encoder.").unwrap();
+    writeln!(Console, "Turn to move, press to pick.").unwrap();
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::Qei0);
+
+    vga::init(p.TIMER0, p.SSI2);
+    let mut rotary = Rotary::new(p.QEI0);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+
+    // Below the VGA timers, so the picture stays put
+    unsafe { cp.SCB.shpr[SHPR_SYSTICK].write(0x40) };
+    let mut syst = cp.SYST;
+    syst.set_clock_source(SystClkSource::Core);
+    syst.set_reload((board.clocks.sysclk.0 / 1000) - 1);
+    syst.enable_counter();
+    syst.enable_interrupt();
+
+    let fb = vga::framebuffer();
+    fb.clear(Colour::BLACK);
+    status_bar::draw(fb, "Rotary menu", "");
+    let mut view = ScrollMenu::new(&ROOT_MENU);
+    view.draw(fb);
+
+    writeln!(board.tx, "Rotary menu. Turn the knob.").unwrap();
+
+    let mut last_tick = 0;
+    loop {
+        // Poll the encoder once a millisecond, so its button debounces
+        let now = TICKS.load(Ordering::Relaxed);
+        if now == last_tick {
+            continue;
+        }
+        last_tick = now;
+        match rotary.poll() {
+            Some(rotary::Event::Clockwise) => view.next(),
+            Some(rotary::Event::Anticlockwise) => view.previous(),
+            Some(rotary::Event::Press) => view.select(),
+            None => continue,
+        }
+        view.draw(fb);
+
+        let led = LED.load(Ordering::Relaxed);
+        board.led_red.set(led == 1);
+        board.led_green.set(led == 2);
+        board.led_blue.set(led == 3);
+    }
+}
+
+exception!(SysTick, sys_tick);
+
+fn sys_tick() {
+    TICKS.fetch_add(1, Ordering::Relaxed);
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,164 @@
+//! Talks to an RS-485 temperature and humidity sensor using Modbus RTU.
+//!
+//! This was tested with one of the cheap XY-MD02 (SHT20) sensors, which
+//! is slave 1 at 9600 bps and has the temperature and humidity (both in
+//! tenths) in input registers 1 and 2. Plenty of other sensors work the
+//! same way - change the constants to suit.
+//!
+//! Wire a MAX3485 (or similar 3.3V transceiver) with DI to PB1 (U1Tx), RO
+//! to PB0 (U1Rx) and DE and /RE together to PB5. A and B go to the sensor.
+//! Every two seconds we ask for a reading and print it on UART0 (115200
+//! bps).
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board, gpio_port)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+
+use core::fmt::Write;
+use demo::hal;
+use demo::modbus;
+use demo::pac;
+use demo::rs485::Rs485;
+use embedded_hal::prelude::*;
+use hal::delay::Delay;
+use hal::gpio::GpioExt;
+use hal::serial::{NewlineMode, Serial};
+use hal::time::U32Ext;
+use rt::ExceptionFrame;
+
+/// The sensor's address on the bus.
+const SLAVE: u8 = 1;
+
+/// The first register we want, and how many.
+const FIRST_REGISTER: u16 = 1;
+const REGISTER_COUNT: u16 = 2;
+
+/// Give up if the sensor hasn't started replying after this long.
+const REPLY_TIMEOUT_MS: u32 = 200;
+
+/// 3.5 characters is about 4ms at 9600 bps. Once the line has been quiet
+/// this long, the reply is over.
+const END_OF_FRAME_MS: u32 = 4;
+
+/// Wait for a reply to come in, returning how long it was (or 0 if nothing
+/// came).
+fn receive<RX, D>(rx: &mut RX, d: &mut D, buffer: &mut [u8]) -> usize
+where
+    RX: embedded_hal::serial::Read<u8>,
+    D: embedded_hal::blocking::delay::DelayMs<u32>,
+{
+    let mut len = 0;
+    let mut quiet_ms = 0;
+    loop {
+        let mut got_one = false;
+        while let Ok(byte) = rx.read() {
+            if len < buffer.len() {
+                buffer[len] = byte;
+                len += 1;
+            }
+            got_one = true;
+        }
+        if got_one {
+            quiet_ms = 0;
+        } else {
+            quiet_ms += 1;
+        }
+        if (len > 0 && quiet_ms >= END_OF_FRAME_MS) || quiet_ms >= REPLY_TIMEOUT_MS {
+            return len;
+        }
+        d.delay_ms(1);
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+
+    let mut portb = gpio_port!(p, B).split(&board.power_control);
+
+    // The RS-485 side
+    let uart1 = Serial::uart1(
+        p.UART1,
+        portb.pb1.into_af1(&mut portb.control),
+        portb.pb0.into_af1(&mut portb.control),
+        (),
+        (),
+        9600_u32.bps(),
+        NewlineMode::Binary,
+        &board.clocks,
+        &board.power_control,
+    );
+    let (tx1, mut rx1) = uart1.split();
+    let de = portb.pb5.into_push_pull_output();
+    let mut bus = Rs485::new(tx1, de, unsafe { &*pac::UART1::ptr() });
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+
+    writeln!(board.tx, "Modbus RTU over RS-485").unwrap();
+
+    let mut buffer = [0u8; modbus::MAX_FRAME];
+    loop {
+        let request = modbus::read_request(
+            SLAVE,
+            modbus::READ_INPUT_REGISTERS,
+            FIRST_REGISTER,
+            REGISTER_COUNT,
+        );
+        // Throw away anything left over from last time
+        while rx1.read().is_ok() {}
+        bus.send(&request).unwrap();
+
+        let len = receive(&mut rx1, &mut d, &mut buffer);
+        let reply = &buffer[0..len];
+        if len == 0 {
+            writeln!(board.tx, "No reply").unwrap();
+        } else if len < 5 || !modbus::crc_ok(reply) {
+            writeln!(board.tx, "Bad reply ({} bytes)", len).unwrap();
+        } else if reply[0] != SLAVE {
+            writeln!(board.tx, "Reply from slave {}?", reply[0]).unwrap();
+        } else if reply[1] == modbus::READ_INPUT_REGISTERS | modbus::EXCEPTION {
+            writeln!(board.tx, "Exception {}", reply[2]).unwrap();
+        } else if reply[2] as usize != 2 * REGISTER_COUNT as usize || len < 9 {
+            writeln!(board.tx, "Wrong length ({} bytes)", len).unwrap();
+        } else {
+            let temp = ((u16::from(reply[3]) << 8) | u16::from(reply[4])) as i16;
+            let humidity = (u16::from(reply[5]) << 8) | u16::from(reply[6]);
+            let sign = if temp < 0 { "-" } else { "" };
+            let temp = i32::from(temp).abs();
+            writeln!(
+                board.tx,
+                "Temperature {}{}.{} C, humidity {}.{} %",
+                sign,
+                temp / 10,
+                temp % 10,
+                humidity / 10,
+                humidity % 10
+            ).unwrap();
+        }
+
+        d.delay_ms(2000u32);
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
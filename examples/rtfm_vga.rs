@@ -0,0 +1,206 @@
+//! The `hello_vga` menu console, using RTFM instead of `static mut`.
+//!
+//! The VGA output is the same as `hello_vga` (HSYNC on PB6, VSYNC on PC4
+//! and green on PB7) and the menu is typed on UART0 at 115200 bps. Here,
+//! though, everything is a `cortex-m-rtfm` task:
+//!
+//! * TIMER0A and TIMER0B run at priority 3, so nothing can hold up a scan
+//!   line.
+//! * UART0 runs at priority 1, moving received bytes into `INPUT`.
+//! * `idle` takes bytes out of `INPUT` and feeds them to the menu.
+//!
+//! `INPUT` is shared between UART0 and `idle`, so `idle` has to `claim` it,
+//! which locks out UART0 (and only UART0) while it does. RTFM works that out
+//! at compile time from the priorities and refuses to build code that gets
+//! it wrong. The VGA timing tasks share nothing, so they're never locked
+//! out. The frame buffer itself still lives in `demo::vga`, as the
+//! framebuffer crate wants it `'static`.
+//!
+//! RTFM is optional and needs a nightly compiler, so build this with
+//! `--features rtfm`:
+//!
+//! ```
+//! $ cargo build --example rtfm_vga --features rtfm
+//! ```
+
+#![feature(proc_macro)]
+#![no_std]
+
+extern crate cortex_m;
+extern crate cortex_m_rtfm as rtfm;
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+extern crate tm4c123x_hal;
+extern crate vga_framebuffer as fb;
+
+use core::fmt::Write;
+use demo::board::Board;
+use demo::console::Console;
+use demo::vga;
+use embedded_hal::prelude::*;
+use menu::*;
+use rtfm::{app, Resource, Threshold};
+use tm4c123x_hal::serial::Rx;
+use tm4c123x_hal::sysctl;
+use tm4c123x_hal::tm4c123x::{self, UART0};
+
+/// How many received bytes `idle` can fall behind by.
+const INPUT_LEN: usize = 32;
+
+/// Bytes on their way from UART0 to `idle`.
+pub struct Input {
+    data: [u8; INPUT_LEN],
+    read: usize,
+    write: usize,
+}
+
+impl Input {
+    /// Add a byte. If `idle` is that far behind, drop it.
+    fn push(&mut self, byte: u8) {
+        let next = (self.write + 1) % INPUT_LEN;
+        if next != self.read {
+            self.data[self.write] = byte;
+            self.write = next;
+        }
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.read == self.write {
+            None
+        } else {
+            let byte = self.data[self.read];
+            self.read = (self.read + 1) % INPUT_LEN;
+            Some(byte)
+        }
+    }
+}
+
+app! {
+    device: tm4c123x,
+
+    resources: {
+        static INPUT: Input = Input {
+            data: [0; INPUT_LEN],
+            read: 0,
+            write: 0,
+        };
+        static RX: Rx<UART0>;
+    },
+
+    idle: {
+        resources: [INPUT],
+    },
+
+    tasks: {
+        TIMER0A: {
+            path: timer0a,
+            priority: 3,
+        },
+
+        TIMER0B: {
+            path: timer0b,
+            priority: 3,
+        },
+
+        UART0: {
+            path: uart0,
+            priority: 1,
+            resources: [INPUT, RX],
+        },
+    },
+}
+
+const HELLO_ITEM: Item = Item {
+    item_type: ItemType::Callback(hello_callback),
+    command: "hello",
+    help: Some("says hello on the UART"),
+};
+
+const CLEAR_ITEM: Item = Item {
+    item_type: ItemType::Callback(clear_callback),
+    command: "clear",
+    help: Some("clears the screen"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[&HELLO_ITEM, &CLEAR_ITEM],
+    entry: None,
+    exit: None,
+};
+
+fn hello_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    writeln!(Console, "Hello from idle").unwrap();
+}
+
+fn clear_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    fb::TextFrameBuffer::new(vga::framebuffer()).clear();
+}
+
+/// Runs with interrupts off, before any of the tasks. RTFM enables the
+/// task interrupts and sets their priorities once we return.
+fn init(p: init::Peripherals, _r: init::Resources) -> init::LateResources {
+    let mut board = Board::new(
+        p.device.SYSCTL,
+        p.device.GPIO_PORTA,
+        p.device.GPIO_PORTF,
+        p.device.UART0,
+    );
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+
+    // Interrupt when the RX FIFO fills past its trigger level, or when
+    // anything has sat in it for a while.
+    let uart_regs = unsafe { &*tm4c123x::UART0::ptr() };
+    uart_regs.im.modify(|_, w| {
+        w.rxim().set_bit();
+        w.rtim().set_bit();
+        w
+    });
+
+    vga::init(p.device.TIMER0, p.device.SSI2);
+
+    init::LateResources { RX: board.rx }
+}
+
+/// Runs the menu whenever no task is.
+fn idle(t: &mut Threshold, mut r: idle::Resources) -> ! {
+    let mut c = fb::TextFrameBuffer::new(vga::framebuffer());
+    c.clear();
+    writeln!(c, "Welcome to Monotron, with added RTFM...").unwrap();
+
+    let mut buffer = [0u8; 64];
+    let mut runner = Runner::new(&ROOT_MENU, &mut buffer, &mut c);
+
+    loop {
+        // Keep the lock short - UART0 is waiting
+        while let Some(ch) = r.INPUT.claim_mut(t, |input, _t| input.pop()) {
+            runner.output.write_char(ch as char).unwrap();
+            runner.input_byte(ch);
+        }
+        rtfm::wfi();
+    }
+}
+
+fn timer0a(_t: &mut Threshold, _r: TIMER0A::Resources) {
+    vga::timer0a_isr();
+}
+
+fn timer0b(_t: &mut Threshold, _r: TIMER0B::Resources) {
+    vga::timer0b_isr();
+}
+
+/// Move everything in the RX FIFO into `INPUT`.
+fn uart0(_t: &mut Threshold, mut r: UART0::Resources) {
+    let uart_regs = unsafe { &*tm4c123x::UART0::ptr() };
+    uart_regs.icr.write(|w| {
+        w.rxic().set_bit();
+        w.rtic().set_bit();
+        w
+    });
+    while let Ok(ch) = r.RX.read() {
+        r.INPUT.push(ch);
+    }
+}
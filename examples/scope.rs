@@ -0,0 +1,303 @@
+//! A one channel oscilloscope, on the VGA screen.
+//!
+//! The input is AIN0 (PE3), 0 to 3.3V. Timer1A triggers ADC0 at 40 samples
+//! per division, and the sequencer 3 interrupt collects them. Each sweep is
+//! 800 samples; we look for a rising edge through the trigger level in the
+//! first half, and draw the 400 samples from there (or from the start, if
+//! there isn't one - the status bar says which).
+//!
+//! The VGA output is the same as `hello_vga` (HSYNC on PB6, VSYNC on PC4
+//! and green on PB7). The controls are on UART0 at 115200 bps:
+//!
+//! * `time <us>` - microseconds per division (500 to 100000)
+//! * `volts <mV>` - millivolts per division (100, 200, 500 or 1000)
+//! * `trigger <mV>` - the trigger level
+//!
+//! One interrupt a sample is hard work with the VGA running too, hence the
+//! 500us/div limit.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use demo::adc::{self, Adc};
+use demo::console::Console;
+use demo::graphics::{Canvas, Colour, VGA_WIDTH};
+use demo::status_bar;
+use demo::text::Buffer;
+use demo::vga;
+use embedded_hal::prelude::*;
+use menu::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::sysctl;
+use tm4c123x_hal::tm4c123x::ADC0;
+
+/// The analog input.
+const CHANNEL: u8 = 0;
+
+/// Samples (and pixels) per division, across.
+const SAMPLES_PER_DIV: usize = 40;
+/// Pixels per division, up.
+const DIV_HEIGHT: usize = 32;
+/// Divisions up the screen.
+const Y_DIVS: usize = 8;
+/// The top of the graticule.
+const PLOT_TOP: usize = status_bar::HEIGHT + 8;
+/// The bottom of the graticule, which is 0V.
+const PLOT_BOTTOM: usize = PLOT_TOP + (Y_DIVS * DIV_HEIGHT);
+
+/// One sweep - twice the screen, so there's room to find a trigger.
+const CAPTURE_LEN: usize = 2 * VGA_WIDTH;
+
+/// Timer1A ticks at 80 MHz, so 2 ticks per microsecond per division.
+const TICKS_PER_US_DIV: u32 = 80 / SAMPLES_PER_DIV as u32;
+
+const MIN_US_PER_DIV: usize = 500;
+const MAX_US_PER_DIV: usize = 100_000;
+
+static mut ADC: Option<Adc<ADC0>> = None;
+static mut CAPTURE: [u16; CAPTURE_LEN] = [0; CAPTURE_LEN];
+/// How much of `CAPTURE` is full. The main loop sets it back to zero.
+static CAPTURED: AtomicUsize = AtomicUsize::new(0);
+
+static US_PER_DIV: AtomicUsize = AtomicUsize::new(1000);
+static MV_PER_DIV: AtomicUsize = AtomicUsize::new(500);
+static TRIGGER_MV: AtomicUsize = AtomicUsize::new(1650);
+
+const TIME_ITEM: Item = Item {
+    item_type: ItemType::Callback(time_callback),
+    command: "time",
+    help: Some("<us> - microseconds per division"),
+};
+
+const VOLTS_ITEM: Item = Item {
+    item_type: ItemType::Callback(volts_callback),
+    command: "volts",
+    help: Some("<mV> - millivolts per division"),
+};
+
+const TRIGGER_ITEM: Item = Item {
+    item_type: ItemType::Callback(trigger_callback),
+    command: "trigger",
+    help: Some("<mV> - trigger level"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[&TIME_ITEM, &VOLTS_ITEM, &TRIGGER_ITEM],
+    entry: None,
+    exit: None,
+};
+
+fn arg(input: &str) -> Option<usize> {
+    input
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse().ok())
+}
+
+fn time_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match arg(input) {
+        Some(us @ MIN_US_PER_DIV...MAX_US_PER_DIV) => US_PER_DIV.store(us, Ordering::Relaxed),
+        _ => writeln!(
+            Console,
+            "Usage: time <{}..{}>",
+            MIN_US_PER_DIV,
+            MAX_US_PER_DIV
+        ).unwrap(),
+    }
+}
+
+fn volts_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match arg(input) {
+        Some(mv) if [100, 200, 500, 1000].contains(&mv) => MV_PER_DIV.store(mv, Ordering::Relaxed),
+        _ => writeln!(Console, "Usage: volts <100|200|500|1000>").unwrap(),
+    }
+}
+
+fn trigger_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match arg(input) {
+        Some(mv) if mv <= adc::VREF_MV as usize => TRIGGER_MV.store(mv, Ordering::Relaxed),
+        _ => writeln!(Console, "Usage: trigger <0..{}>", adc::VREF_MV).unwrap(),
+    }
+}
+
+/// Start a sweep at the current timebase.
+fn arm() {
+    let timer = unsafe { &*tm4c123x_hal::tm4c123x::TIMER1::ptr() };
+    let ticks = US_PER_DIV.load(Ordering::Relaxed) as u32 * TICKS_PER_US_DIV;
+    timer.ctl.modify(|_, w| w.taen().clear_bit());
+    timer.tailr.write(|w| unsafe { w.bits(ticks - 1) });
+    CAPTURED.store(0, Ordering::Relaxed);
+    timer.ctl.modify(|_, w| w.taen().set_bit());
+}
+
+/// Where to start drawing, if there's a rising edge through `level`.
+fn find_trigger(samples: &[u16], level: u16) -> Option<usize> {
+    (1..VGA_WIDTH).find(|&i| samples[i - 1] < level && samples[i] >= level)
+}
+
+/// Where a sample goes on the screen.
+fn sample_to_y(sample: u16, mv_per_div: usize) -> usize {
+    let mv = adc::millivolts(sample) as usize;
+    let height = (mv * DIV_HEIGHT / mv_per_div).min(Y_DIVS * DIV_HEIGHT);
+    PLOT_BOTTOM - height
+}
+
+fn draw_graticule<C>(canvas: &mut C)
+where
+    C: Canvas,
+{
+    for div in 0..=Y_DIVS {
+        let y = PLOT_TOP + div * DIV_HEIGHT;
+        for x in (0..VGA_WIDTH).filter(|x| x % 4 == 0) {
+            canvas.draw_point(x, y, Colour::WHITE);
+        }
+    }
+    for div in 0..=(VGA_WIDTH / SAMPLES_PER_DIV) {
+        let x = (div * SAMPLES_PER_DIV).min(VGA_WIDTH - 1);
+        for y in (PLOT_TOP..PLOT_BOTTOM).filter(|y| y % 4 == 0) {
+            canvas.draw_point(x, y, Colour::WHITE);
+        }
+    }
+}
+
+fn draw_trace<C>(canvas: &mut C, trace: &[usize; VGA_WIDTH], colour: Colour)
+where
+    C: Canvas,
+{
+    for x in 1..VGA_WIDTH {
+        canvas.draw_line(
+            ((x - 1) as isize, trace[x - 1] as isize),
+            (x as isize, trace[x] as isize),
+            colour,
+        );
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = demo::board::Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::Timer1);
+    board.enable(sysctl::Domain::Adc0);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    // PE3
+    demo::config::Port::E.enable();
+    adc::configure_pin(CHANNEL);
+    let mut adc = Adc::adc0(p.ADC0);
+    adc.start_triggered(CHANNEL);
+    unsafe { ADC = Some(adc) };
+
+    // Timer1A, 32-bit periodic, triggering the ADC
+    let timer = p.TIMER1;
+    timer.ctl.modify(|_, w| w.taen().clear_bit());
+    timer.cfg.write(|w| unsafe { w.bits(0) });
+    timer.tamr.modify(|_, w| w.tamr().period());
+    timer.ctl.modify(|_, w| w.taote().set_bit());
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+    // Below the VGA timers, so the picture stays put
+    unsafe { nvic.set_priority(tm4c123x_hal::Interrupt::ADC0SS3, 0x40) };
+    nvic.enable(tm4c123x_hal::Interrupt::ADC0SS3);
+
+    vga::framebuffer().clear(Colour::BLACK);
+    draw_graticule(vga::framebuffer());
+
+    writeln!(board.tx, "Scope. Input on PE3. Try 'time 2000'.").unwrap();
+
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+
+    let mut trace = [PLOT_BOTTOM; VGA_WIDTH];
+    arm();
+
+    loop {
+        while let Ok(ch) = board.rx.read() {
+            r.input_byte(ch);
+        }
+        if CAPTURED.load(Ordering::Relaxed) < CAPTURE_LEN {
+            continue;
+        }
+
+        let samples = unsafe { &CAPTURE };
+        let mv_per_div = MV_PER_DIV.load(Ordering::Relaxed);
+        let trigger_mv = TRIGGER_MV.load(Ordering::Relaxed) as u32;
+        let level = (trigger_mv * u32::from(adc::MAX) / adc::VREF_MV) as u16;
+        let start = find_trigger(samples, level);
+
+        let fb = vga::framebuffer();
+        draw_trace(fb, &trace, Colour::BLACK);
+        for (x, y) in trace.iter_mut().enumerate() {
+            *y = sample_to_y(samples[start.unwrap_or(0) + x], mv_per_div);
+        }
+        draw_graticule(fb);
+        draw_trace(fb, &trace, Colour::WHITE);
+
+        let mut status = Buffer::new();
+        write!(
+            status,
+            "{}us {}mV {}mV {}",
+            US_PER_DIV.load(Ordering::Relaxed),
+            mv_per_div,
+            trigger_mv,
+            if start.is_some() { "TRIG" } else { "AUTO" }
+        ).unwrap();
+        status_bar::draw(fb, "Scope", status.as_str());
+
+        arm();
+    }
+}
+
+interrupt!(ADC0SS3, adc_isr);
+
+fn adc_isr() {
+    let adc = unsafe { ADC.as_mut().unwrap() };
+    while let Some(sample) = adc.take() {
+        let n = CAPTURED.load(Ordering::Relaxed);
+        if n < CAPTURE_LEN {
+            unsafe { CAPTURE[n] = sample };
+            CAPTURED.store(n + 1, Ordering::Relaxed);
+        } else {
+            // Full - stop until the main loop has drawn it
+            let timer = unsafe { &*tm4c123x_hal::tm4c123x::TIMER1::ptr() };
+            timer.ctl.modify(|_, w| w.taen().clear_bit());
+        }
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
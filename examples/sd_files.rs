@@ -0,0 +1,464 @@
+//! Look after the files on an SD card, from the console or a two-pane file
+//! manager on the VGA screen.
+//!
+//! The card is on SSI0 - SCK is PA2 (SSI0Clk), MISO is PA4 (SSI0Rx), MOSI is
+//! PA5 (SSI0Tx) and chip select is PA3 - with a 10k pull-up on MISO if your
+//! breakout doesn't have one. The VGA output is wired as for `hello_vga`.
+//! Type commands on UART0 at 115200 bps:
+//!
+//! * `mount` - start the card again, after swapping it
+//! * `ls [dir]` - list a directory
+//! * `cd [dir]` - change directory (to the root, if you don't say)
+//! * `cat <file>` - print a file
+//! * `cp <from> <to>` - copy a file (`to` can be a directory)
+//! * `rm <path>` - delete a file, or an empty directory
+//! * `mkdir <dir>` - make a directory
+//! * `fm` - the file manager, until you press Escape
+//!
+//! The file manager draws on the VGA screen, but takes its keys from the
+//! terminal, like `hexedit` in `monitor`. See `demo::file_manager` for the
+//! keys. Names are 8.3 (see `demo::fat`), and new files are all stamped
+//! 2000-01-01, as nothing here knows the time.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+
+use core::fmt::Write;
+use cortex_m::asm;
+use demo::config;
+use demo::console::Console;
+use demo::fat::{self, Dir, Path, Volume};
+use demo::file_manager::FileManager;
+use demo::graphics::{Canvas, Colour};
+use demo::hal;
+use demo::input::{self, InputEvent};
+use demo::pac;
+use demo::sdcard::SdCard;
+use demo::spi::Spi;
+use demo::status_bar;
+use demo::text::Buffer;
+use demo::tui::Response;
+use demo::vga;
+use embedded_hal::prelude::*;
+use embedded_hal::spi::MODE_0;
+use hal::delay::Delay;
+use hal::gpio::gpioa::PA3;
+use hal::gpio::{Output, PushPull};
+use hal::sysctl::{self, Clocks};
+use hal::time::U32Ext;
+use menu::*;
+use rt::ExceptionFrame;
+
+/// Cards start at 400 kHz or less.
+const INIT_HZ: u32 = 400_000;
+
+/// And then most can go this fast.
+const FAST_HZ: u32 = 20_000_000;
+
+/// Clock cycles in a millisecond, at 80 MHz.
+const CYCLES_PER_MS: u32 = 80_000;
+
+/// How long a lone ESC from the terminal waits to become the Escape key.
+const ESCAPE_MS: usize = 50;
+
+type Card = SdCard<Spi<pac::SSI0>, PA3<Output<PushPull>>>;
+
+/// The card, while it hasn't got a filesystem we can use.
+static mut CARD: Option<Card> = None;
+
+/// The card, once it's mounted.
+static mut VOLUME: Option<Volume<Card>> = None;
+
+/// The current directory.
+static mut CWD: Option<(Dir, Path)> = None;
+
+/// `mount` asks the main loop, which has the delay and the clocks.
+static mut MOUNT_NOW: bool = false;
+
+const MOUNT_ITEM: Item = Item {
+    item_type: ItemType::Callback(mount_callback),
+    command: "mount",
+    help: Some("start the card again"),
+};
+
+const LS_ITEM: Item = Item {
+    item_type: ItemType::Callback(ls_callback),
+    command: "ls",
+    help: Some("[dir] - list a directory"),
+};
+
+const CD_ITEM: Item = Item {
+    item_type: ItemType::Callback(cd_callback),
+    command: "cd",
+    help: Some("[dir] - change directory"),
+};
+
+const CAT_ITEM: Item = Item {
+    item_type: ItemType::Callback(cat_callback),
+    command: "cat",
+    help: Some("<file> - print a file"),
+};
+
+const CP_ITEM: Item = Item {
+    item_type: ItemType::Callback(cp_callback),
+    command: "cp",
+    help: Some("<from> <to> - copy a file"),
+};
+
+const RM_ITEM: Item = Item {
+    item_type: ItemType::Callback(rm_callback),
+    command: "rm",
+    help: Some("<path> - delete a file or empty directory"),
+};
+
+const MKDIR_ITEM: Item = Item {
+    item_type: ItemType::Callback(mkdir_callback),
+    command: "mkdir",
+    help: Some("<dir> - make a directory"),
+};
+
+const FM_ITEM: Item = Item {
+    item_type: ItemType::Callback(fm_callback),
+    command: "fm",
+    help: Some("the file manager, on the VGA screen"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[
+        &MOUNT_ITEM,
+        &LS_ITEM,
+        &CD_ITEM,
+        &CAT_ITEM,
+        &CP_ITEM,
+        &RM_ITEM,
+        &MKDIR_ITEM,
+        &FM_ITEM,
+    ],
+    entry: None,
+    exit: None,
+};
+
+/// The nth word after the command.
+fn argument(input: &str, n: usize) -> Option<&str> {
+    input.split_whitespace().nth(n + 1)
+}
+
+/// The volume and the current directory, or a complaint.
+fn volume() -> Option<(&'static mut Volume<Card>, &'static mut (Dir, Path))> {
+    match unsafe { (VOLUME.as_mut(), CWD.as_mut()) } {
+        (Some(volume), Some(cwd)) => Some((volume, cwd)),
+        _ => {
+            writeln!(Console, "No card - put one in and `mount`").unwrap();
+            None
+        }
+    }
+}
+
+fn mount_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    unsafe { MOUNT_NOW = true };
+}
+
+fn ls_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let (volume, cwd) = match volume() {
+        Some(v) => v,
+        None => return,
+    };
+    let dir = match volume.open_dir(cwd.0, argument(input, 0).unwrap_or(".")) {
+        Ok(dir) => dir,
+        Err(e) => {
+            writeln!(Console, "{}", e).unwrap();
+            return;
+        }
+    };
+    let mut entries = volume.entries(dir);
+    let mut count = 0;
+    loop {
+        match volume.next_entry(&mut entries) {
+            Ok(Some(entry)) => {
+                let mut name = Buffer::new();
+                write!(name, "{}", entry.name).unwrap();
+                if entry.is_dir() {
+                    write!(Console, "{:<12} {:>10}", name.as_str(), "<DIR>").unwrap();
+                } else {
+                    write!(Console, "{:<12} {:>10}", name.as_str(), entry.size).unwrap();
+                }
+                writeln!(Console, "  {}", entry.modified).unwrap();
+                count += 1;
+            }
+            Ok(None) => break,
+            Err(e) => {
+                writeln!(Console, "{}", e).unwrap();
+                return;
+            }
+        }
+    }
+    writeln!(Console, "{} entries", count).unwrap();
+}
+
+fn cd_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let (volume, cwd) = match volume() {
+        Some(v) => v,
+        None => return,
+    };
+    let path = argument(input, 0).unwrap_or("/");
+    match volume.open_dir(cwd.0, path) {
+        Ok(dir) => {
+            cwd.0 = dir;
+            cwd.1.change(path);
+        }
+        Err(e) => writeln!(Console, "{}", e).unwrap(),
+    }
+    writeln!(Console, "{}", cwd.1).unwrap();
+}
+
+fn cat_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let path = match argument(input, 0) {
+        Some(path) => path,
+        None => {
+            writeln!(Console, "Usage: cat <file>").unwrap();
+            return;
+        }
+    };
+    let (volume, cwd) = match volume() {
+        Some(v) => v,
+        None => return,
+    };
+    let mut file = match volume.open(cwd.0, path) {
+        Ok(file) => file,
+        Err(e) => {
+            writeln!(Console, "{}", e).unwrap();
+            return;
+        }
+    };
+    let mut buffer = [0u8; fat::BLOCK_LEN];
+    loop {
+        match volume.read(&mut file, &mut buffer) {
+            Ok(0) => break,
+            Ok(count) => {
+                for &byte in buffer[..count].iter() {
+                    if byte == b'\n' {
+                        Console.write_byte(b'\r');
+                    }
+                    Console.write_byte(byte);
+                }
+            }
+            Err(e) => {
+                writeln!(Console, "\n{}", e).unwrap();
+                return;
+            }
+        }
+    }
+    writeln!(Console).unwrap();
+}
+
+fn cp_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let (from, to) = match (argument(input, 0), argument(input, 1)) {
+        (Some(from), Some(to)) => (from, to),
+        _ => {
+            writeln!(Console, "Usage: cp <from> <to>").unwrap();
+            return;
+        }
+    };
+    let (volume, cwd) = match volume() {
+        Some(v) => v,
+        None => return,
+    };
+    // Into a directory, it keeps its name
+    let (to_dir, to_name) = match volume.open_dir(cwd.0, to) {
+        Ok(dir) => (dir, from.rsplit('/').next().unwrap_or(from)),
+        Err(_) => (cwd.0, to),
+    };
+    match volume.copy(cwd.0, from, to_dir, to_name) {
+        Ok(size) => writeln!(Console, "Copied {} bytes", size).unwrap(),
+        Err(e) => writeln!(Console, "{}", e).unwrap(),
+    }
+}
+
+fn rm_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let path = match argument(input, 0) {
+        Some(path) => path,
+        None => {
+            writeln!(Console, "Usage: rm <path>").unwrap();
+            return;
+        }
+    };
+    let (volume, cwd) = match volume() {
+        Some(v) => v,
+        None => return,
+    };
+    if let Err(e) = volume.delete(cwd.0, path) {
+        writeln!(Console, "{}", e).unwrap();
+    }
+}
+
+fn mkdir_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let path = match argument(input, 0) {
+        Some(path) => path,
+        None => {
+            writeln!(Console, "Usage: mkdir <dir>").unwrap();
+            return;
+        }
+    };
+    let (volume, cwd) = match volume() {
+        Some(v) => v,
+        None => return,
+    };
+    if let Err(e) = volume.make_dir(cwd.0, path) {
+        writeln!(Console, "{}", e).unwrap();
+    }
+}
+
+fn fm_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    let (volume, cwd) = match volume() {
+        Some(v) => v,
+        None => return,
+    };
+    writeln!(Console, "File manager on the VGA screen - Escape to leave").unwrap();
+    let fb = vga::framebuffer();
+    fb.clear(Colour::BLACK);
+    status_bar::draw(fb, "SD card", volume.fat_name());
+    let mut manager = FileManager::new(volume, cwd.0, &cwd.1);
+    manager.draw(fb);
+
+    // The main loop's waiting for us, so we read the UART ourselves
+    let mut keys = input::Decoder::new();
+    let mut idle = 0;
+    loop {
+        match Console.read_byte() {
+            Some(byte) => {
+                keys.input(byte);
+                idle = 0;
+            }
+            None => {
+                asm::delay(CYCLES_PER_MS);
+                idle += 1;
+                if idle == ESCAPE_MS {
+                    keys.timeout();
+                }
+            }
+        }
+        while let Some(event) = input::next() {
+            if let InputEvent::KeyDown(event) = event {
+                if manager.key(volume, event.key) == Response::Cancelled {
+                    draw_idle(volume);
+                    return;
+                }
+                manager.draw(fb);
+            }
+        }
+    }
+}
+
+/// What's on the VGA screen when the file manager isn't.
+fn draw_idle(volume: &Volume<Card>) {
+    let fb = vga::framebuffer();
+    fb.clear(Colour::BLACK);
+    status_bar::draw(fb, "SD card", volume.fat_name());
+    fb.draw_str(8, 40, "Type `fm` on the console", Colour::WHITE, Colour::BLACK);
+}
+
+/// Wake the card up and find its filesystem. Either way, it ends up in
+/// `VOLUME` or `CARD`.
+fn mount(mut card: Card, delay: &mut Delay, clocks: &Clocks) {
+    card.spi().set_frequency(INIT_HZ.hz(), clocks);
+    let card_type = match card.init(delay) {
+        Ok(card_type) => card_type,
+        Err(e) => {
+            writeln!(Console, "No card ({:?})", e).unwrap();
+            unsafe { CARD = Some(card) };
+            return;
+        }
+    };
+    card.spi().set_frequency(FAST_HZ.hz(), clocks);
+    match Volume::mount(card) {
+        Ok(volume) => {
+            writeln!(
+                Console,
+                "{:?} card, {} KiB of {}",
+                card_type,
+                volume.size_kib(),
+                volume.fat_name()
+            ).unwrap();
+            draw_idle(&volume);
+            unsafe {
+                VOLUME = Some(volume);
+                CWD = Some((Dir::root(), Path::root()));
+            }
+        }
+        Err((card, e)) => {
+            writeln!(Console, "{}", e).unwrap();
+            unsafe { CARD = Some(card) };
+        }
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::Ssi0);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(hal::Interrupt::TIMER0A);
+    nvic.enable(hal::Interrupt::TIMER0B);
+
+    // SSI0Clk, SSI0Rx and SSI0Tx
+    config::SSI0.connect();
+    let porta = board.porta;
+    let spi = Spi::ssi0(p.SSI0, MODE_0, INIT_HZ.hz(), &board.clocks);
+    let card = SdCard::new(spi, porta.pa3.into_push_pull_output());
+    let mut delay = Delay::new(cp.SYST, &board.clocks);
+
+    writeln!(board.tx, "SD card files - try `ls` or `fm`").unwrap();
+    mount(card, &mut delay, &board.clocks);
+
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+
+    loop {
+        while let Ok(ch) = board.rx.read() {
+            r.input_byte(ch);
+        }
+
+        if unsafe { core::mem::replace(&mut MOUNT_NOW, false) } {
+            let card = unsafe { VOLUME.take().map(Volume::free).or_else(|| CARD.take()) };
+            if let Some(card) = card {
+                mount(card, &mut delay, &board.clocks);
+            }
+        }
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
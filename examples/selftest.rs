@@ -0,0 +1,180 @@
+//! Checks the peripherals on a board, with loopback tests.
+//!
+//! Fit two jumpers first: PB1 to PB0 (UART1) and PE2 to PE3 (GPIO). The
+//! other tests use internal loopback, or need nothing at all. See
+//! `demo::selftest` for what each test does. The console is UART0 at
+//! 115200 bps.
+//!
+//! Commands:
+//!
+//! * `selftest` - run every test and print a pass/fail table
+//! * `clkout [<hz>|off]` - put a square wave from the system clock on PE4
+//!   (1 MHz by default), to check the PLL with a frequency counter
+//!
+//! The green LED comes on if everything passed, and the red one if
+//! anything didn't.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use demo::adc::Adc;
+use demo::board::{Board, Led};
+use demo::clkout::ClockOut;
+use demo::console::Console;
+use demo::eeprom::Eeprom;
+use demo::selftest::{self, Rig};
+use demo::spi::Spi;
+use embedded_hal::prelude::*;
+use embedded_hal::spi::MODE_0;
+use menu::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::gpio::gpiof::{PF1, PF3};
+use tm4c123x_hal::gpio::{GpioExt, Output, PushPull};
+use tm4c123x_hal::serial::{NewlineMode, Serial};
+use tm4c123x_hal::sysctl;
+use tm4c123x_hal::time::U32Ext;
+
+const SELFTEST_ITEM: Item = Item {
+    item_type: ItemType::Callback(selftest_callback),
+    command: "selftest",
+    help: Some("run the loopback tests"),
+};
+
+const CLKOUT_ITEM: Item = Item {
+    item_type: ItemType::Callback(clkout_callback),
+    command: "clkout",
+    help: Some("[<hz>|off] - put the system clock on PE4"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[&SELFTEST_ITEM, &CLKOUT_ITEM],
+    entry: None,
+    exit: None,
+};
+
+/// Everything the tests need, and the LEDs for the result.
+struct Context {
+    rig: Rig,
+    clkout: ClockOut,
+    led_red: Led<PF1<Output<PushPull>>>,
+    led_green: Led<PF3<Output<PushPull>>>,
+}
+
+static mut CONTEXT: Option<Context> = None;
+
+fn context() -> &'static mut Context {
+    unsafe { CONTEXT.as_mut().unwrap() }
+}
+
+fn selftest_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    let context = context();
+    let failed = selftest::run(&mut context.rig, &mut Console).unwrap();
+    context.led_green.set(failed == 0);
+    context.led_red.set(failed != 0);
+    if failed == 0 {
+        writeln!(Console, "All tests passed").unwrap();
+    } else {
+        writeln!(Console, "{} of {} tests failed", failed, selftest::TESTS.len()).unwrap();
+    }
+}
+
+fn clkout_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let clkout = &mut context().clkout;
+    let hz = match input.split_whitespace().nth(1) {
+        None => 1_000_000,
+        Some("off") => {
+            clkout.stop();
+            writeln!(Console, "Clock out off").unwrap();
+            return;
+        }
+        Some(arg) => match arg.parse() {
+            Ok(hz) => hz,
+            Err(_) => {
+                writeln!(Console, "Usage: clkout [<hz>|off]").unwrap();
+                return;
+            }
+        },
+    };
+    match clkout.set(hz) {
+        Ok(actual) => writeln!(Console, "Clock out on PE4: {} Hz", actual).unwrap(),
+        Err(e) => writeln!(Console, "Can do {} to {} Hz", e.min_hz, e.max_hz).unwrap(),
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Ssi1);
+    board.enable(sysctl::Domain::Can0);
+    board.enable(sysctl::Domain::Adc0);
+    board.enable(sysctl::Domain::Eeprom);
+    board.enable(sysctl::Domain::Pwm0);
+
+    let mut portb = p.GPIO_PORTB.split(&board.power_control);
+    let uart1 = Serial::uart1(
+        p.UART1,
+        portb.pb1.into_af1(&mut portb.control),
+        portb.pb0.into_af1(&mut portb.control),
+        (),
+        (),
+        115200_u32.bps(),
+        NewlineMode::Binary,
+        &board.clocks,
+        &board.power_control,
+    );
+    let (uart1_tx, uart1_rx) = uart1.split();
+
+    let rig = Rig {
+        uart1_tx,
+        uart1_rx,
+        ssi1: Spi::ssi1(p.SSI1, MODE_0, 1_000_000_u32.hz(), &board.clocks),
+        can0: p.CAN0,
+        adc0: Adc::adc0(p.ADC0),
+        eeprom: Eeprom::new(p.EEPROM).ok(),
+    };
+    unsafe {
+        CONTEXT = Some(Context {
+            rig,
+            clkout: ClockOut::new(p.PWM0, &board.clocks),
+            led_red: board.led_red,
+            led_green: board.led_green,
+        });
+    }
+
+    writeln!(board.tx, "Self test. Fit the jumpers, then type 'selftest'.").unwrap();
+
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+
+    loop {
+        while let Ok(ch) = board.rx.read() {
+            r.input_byte(ch);
+        }
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,396 @@
+//! The settings the demos share, kept in the EEPROM - see `demo::settings`.
+//!
+//! The VGA screen (HSYNC on PB6, VSYNC on PC4 and green on PB7, as in
+//! `hello_vga`) shows what they are. Change them with commands on the
+//! console (UART0, at the saved baud rate - 115200 bps to start with), or
+//! with `screen`, which puts a settings screen up on the VGA output to
+//! drive with the terminal's arrow keys. Commands:
+//!
+//! * `get [name]` - show a setting, or all of them
+//! * `set <name> <value>` - change one
+//! * `save` - keep them in the EEPROM for next time
+//! * `defaults` - go back to the defaults (until you save, or reset)
+//! * `screen` - change them on the settings screen
+//!
+//! A new baud rate takes effect at the next reset, so the terminal doesn't
+//! lose us half way through.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use cortex_m::asm;
+use demo::board::Board;
+use demo::config;
+use demo::console::Console;
+use demo::eeprom::Eeprom;
+use demo::graphics::{Canvas, Colour};
+use demo::input::{self, InputEvent};
+use demo::keyboard::{Key, Layout};
+use demo::settings::{self, Display, Settings};
+use demo::status_bar;
+use demo::text::Buffer;
+use demo::tui::{self, Attr, Button, Focus, Label, ListBox, Rect, Response, Widget};
+use demo::vga;
+use embedded_hal::prelude::*;
+use menu::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::sysctl;
+
+/// Clock cycles in a millisecond, at 80 MHz.
+const CYCLES_PER_MS: u32 = 80_000;
+
+/// How long a lone ESC from the terminal waits to become the Escape key.
+const ESCAPE_MS: usize = 50;
+
+// What the list boxes say, in the same order as the things they pick
+const BAUD_NAMES: [&str; 8] = [
+    "9600", "19200", "38400", "57600", "115200", "230400", "460800", "921600",
+];
+const LAYOUT_NAMES: [&str; 3] = ["us", "uk", "de"];
+const DISPLAY_NAMES: [&str; 2] = ["colour", "mono"];
+
+// Which widget is which, in `widgets!`
+const SAVE: usize = 3;
+const CANCEL: usize = 4;
+
+const GET_ITEM: Item = Item {
+    item_type: ItemType::Callback(get_callback),
+    command: "get",
+    help: Some("[name] - show a setting, or all of them"),
+};
+
+const SET_ITEM: Item = Item {
+    item_type: ItemType::Callback(set_callback),
+    command: "set",
+    help: Some("<name> <value> - change a setting"),
+};
+
+const SAVE_ITEM: Item = Item {
+    item_type: ItemType::Callback(save_callback),
+    command: "save",
+    help: Some("keep the settings in the EEPROM"),
+};
+
+const DEFAULTS_ITEM: Item = Item {
+    item_type: ItemType::Callback(defaults_callback),
+    command: "defaults",
+    help: Some("go back to the defaults"),
+};
+
+const SCREEN_ITEM: Item = Item {
+    item_type: ItemType::Callback(screen_callback),
+    command: "screen",
+    help: Some("change the settings on the VGA screen"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[
+        &GET_ITEM,
+        &SET_ITEM,
+        &SAVE_ITEM,
+        &DEFAULTS_ITEM,
+        &SCREEN_ITEM,
+    ],
+    entry: None,
+    exit: None,
+};
+
+/// The settings as they are now, saved or not.
+static mut SETTINGS: Settings = Settings::DEFAULT;
+
+/// Where they're saved, if it came up.
+static mut EEPROM: Option<Eeprom> = None;
+
+/// List the settings on the VGA screen.
+fn show() {
+    let fb = vga::framebuffer();
+    fb.clear(Colour::BLACK);
+    status_bar::draw(fb, "Settings", "");
+    let settings = unsafe { SETTINGS };
+    for (row, name) in settings::NAMES.iter().enumerate() {
+        let mut text = Buffer::new();
+        // Too long is cut short, which is fine
+        let _ = write!(text, "{:8} ", name);
+        let _ = settings.get(name, &mut text);
+        tui::text(fb, 2, 2 + row, 46, text.as_str(), Attr::Normal);
+    }
+}
+
+fn get_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let settings = unsafe { SETTINGS };
+    match input.split_whitespace().nth(1) {
+        Some(name) => match settings.get(name, &mut Console) {
+            Ok(()) => writeln!(Console).unwrap(),
+            Err(_) => writeln!(Console, "No setting called {}", name).unwrap(),
+        },
+        None => {
+            for name in settings::NAMES.iter() {
+                write!(Console, "{} = ", name).unwrap();
+                settings.get(name, &mut Console).unwrap();
+                writeln!(Console).unwrap();
+            }
+        }
+    }
+}
+
+fn set_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let mut args = input.split_whitespace().skip(1);
+    let name = match args.next() {
+        Some(name) => name,
+        None => {
+            writeln!(Console, "Usage: set <name> <value>").unwrap();
+            return;
+        }
+    };
+    // Nothing at all clears the startup program
+    let value = args.next().unwrap_or("");
+    match unsafe { SETTINGS.set(name, value) } {
+        Ok(()) => {
+            writeln!(Console, "{} = {}", name, value).unwrap();
+            show();
+        }
+        Err(settings::Error::UnknownName) => {
+            writeln!(Console, "No setting called {}", name).unwrap();
+        }
+        Err(_) => writeln!(Console, "{} can't be '{}'", name, value).unwrap(),
+    }
+}
+
+fn save_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    save();
+}
+
+fn defaults_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    unsafe { SETTINGS = Settings::DEFAULT };
+    writeln!(Console, "Back to the defaults - save to keep them").unwrap();
+    show();
+}
+
+fn screen_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    let settings = unsafe { SETTINGS };
+    let mut baud = ListBox::new(
+        Rect {
+            col: 2,
+            row: 2,
+            width: 12,
+            height: 6,
+        },
+        Some("Baud"),
+        &BAUD_NAMES,
+    );
+    baud.select(settings::BAUDS.iter().position(|&b| b == settings.baud).unwrap_or(0));
+    let mut layout = ListBox::new(
+        Rect {
+            col: 16,
+            row: 2,
+            width: 12,
+            height: 5,
+        },
+        Some("Keyboard"),
+        &LAYOUT_NAMES,
+    );
+    layout.select(Layout::ALL.iter().position(|&l| l == settings.layout).unwrap_or(0));
+    let mut display = ListBox::new(
+        Rect {
+            col: 30,
+            row: 2,
+            width: 12,
+            height: 4,
+        },
+        Some("Screen"),
+        &DISPLAY_NAMES,
+    );
+    display.select(Display::ALL.iter().position(|&d| d == settings.display).unwrap_or(0));
+    let mut save_button = Button {
+        col: 2,
+        row: 12,
+        label: "Save",
+    };
+    let mut cancel = Button {
+        col: 10,
+        row: 12,
+        label: "Cancel",
+    };
+    let mut startup_label = Label {
+        col: 2,
+        row: 9,
+        width: 18,
+        text: "Start-up program:",
+    };
+    let mut startup = Label {
+        col: 20,
+        row: 9,
+        width: settings::STARTUP_LEN,
+        text: if settings.startup().is_empty() {
+            "(none)"
+        } else {
+            settings.startup()
+        },
+    };
+    let mut help = Label {
+        col: 2,
+        row: 14,
+        width: 46,
+        text: "Tab to move, Enter to pick, Esc to leave",
+    };
+    let mut help_startup = Label {
+        col: 2,
+        row: 15,
+        width: 46,
+        text: "Change the start-up program with 'set startup'",
+    };
+
+    // Borrow all the widgets, in the order of the constants above
+    macro_rules! widgets {
+        () => {
+            [
+                &mut baud as &mut Widget,
+                &mut layout,
+                &mut display,
+                &mut save_button,
+                &mut cancel,
+                &mut startup_label,
+                &mut startup,
+                &mut help,
+                &mut help_startup,
+            ]
+        };
+    }
+
+    let fb = vga::framebuffer();
+    fb.clear(Colour::BLACK);
+    status_bar::draw(fb, "Settings", "");
+    let mut focus = Focus::new();
+    focus.draw(fb, &mut widgets!());
+
+    // The main loop's waiting for us, so we read the UART ourselves
+    let mut keys = input::Decoder::new();
+    let mut idle = 0;
+    'screen: loop {
+        match Console.read_byte() {
+            Some(byte) => {
+                keys.input(byte);
+                idle = 0;
+            }
+            None => {
+                asm::delay(CYCLES_PER_MS);
+                idle += 1;
+                if idle == ESCAPE_MS {
+                    keys.timeout();
+                }
+            }
+        }
+        while let Some(event) = input::next() {
+            let key = match event {
+                InputEvent::KeyDown(event) => event.key,
+                _ => continue,
+            };
+            if key == Key::Escape {
+                break 'screen;
+            }
+            let (index, response) = {
+                let mut widgets = widgets!();
+                let result = focus.key(&mut widgets, key);
+                focus.draw(fb, &mut widgets);
+                result
+            };
+            match (index, response) {
+                (SAVE, Response::Activated) => {
+                    unsafe {
+                        SETTINGS.baud = settings::BAUDS[baud.selected()];
+                        SETTINGS.layout = Layout::ALL[layout.selected()];
+                        SETTINGS.display = Display::ALL[display.selected()];
+                    }
+                    save();
+                    break 'screen;
+                }
+                (CANCEL, Response::Activated) => break 'screen,
+                _ => {}
+            }
+        }
+    }
+    show();
+}
+
+/// Write the settings to the EEPROM, and say how it went.
+fn save() {
+    let settings = unsafe { SETTINGS };
+    match unsafe { EEPROM.as_mut() } {
+        Some(eeprom) => match settings.save(eeprom) {
+            Ok(()) => writeln!(Console, "Saved").unwrap(),
+            Err(e) => writeln!(Console, "Can't save: {:?}", e).unwrap(),
+        },
+        None => writeln!(Console, "No EEPROM").unwrap(),
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::Eeprom);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+
+    match Eeprom::new(p.EEPROM) {
+        Ok(mut eeprom) => unsafe {
+            SETTINGS = Settings::load(&mut eeprom);
+            EEPROM = Some(eeprom);
+        },
+        Err(e) => writeln!(board.tx, "EEPROM failed: {:?}", e).unwrap(),
+    }
+
+    let baud = unsafe { SETTINGS.baud };
+    if baud != config::CONSOLE_BAUD {
+        writeln!(board.tx, "Switching to {} bps", baud).unwrap();
+        board.set_baud(baud);
+    }
+    show();
+
+    writeln!(board.tx, "Settings. 'help' for help.").unwrap();
+
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+    loop {
+        if let Ok(ch) = board.rx.read() {
+            r.input_byte(ch);
+        }
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
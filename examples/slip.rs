@@ -0,0 +1,102 @@
+//! Gives the LaunchPad an IP address over its USB serial port, using SLIP
+//! and smoltcp, and answers pings.
+//!
+//! There's no console in this one - UART0 is the network. On a Linux PC:
+//!
+//! ```
+//! sudo slattach -L -s 115200 -p slip /dev/ttyACM0 &
+//! sudo ip addr add 192.168.190.1 peer 192.168.190.2 dev sl0
+//! sudo ip link set sl0 up
+//! ping 192.168.190.2
+//! ```
+//!
+//! We poll the UART, so at 115200 bps there's only just time to empty the
+//! FIFO between polls. Big pings (`ping -s 200`) will get dropped now and
+//! then. The green LED flashes once a second so you can see we're alive.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+extern crate smoltcp;
+
+use demo::hal;
+use demo::slip::{self, Slip};
+use embedded_hal::prelude::*;
+use hal::delay::Delay;
+use rt::ExceptionFrame;
+use smoltcp::iface::{EthernetInterfaceBuilder, NeighborCache};
+use smoltcp::time::Instant;
+use smoltcp::wire::{EthernetAddress, IpAddress, IpCidr};
+
+/// Who we are. The host is the other end of the link.
+const IP_ADDRESS: [u8; 4] = [192, 168, 190, 2];
+
+/// Any packet for somewhere else on this 'network' goes to the host.
+const PREFIX_LEN: u8 = 24;
+
+/// How long we sleep each time round the main loop.
+const POLL_MS: u32 = 1;
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    // SLIP is binary. That's fine, as only `write!` turns `\n` into
+    // `\r\n` - the bytes `Slip` sends go as they are.
+    let mut board = board!(p);
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+
+    let mut neighbor_storage = [None; 4];
+    let mut ip_addrs = [IpCidr::new(
+        IpAddress::v4(IP_ADDRESS[0], IP_ADDRESS[1], IP_ADDRESS[2], IP_ADDRESS[3]),
+        PREFIX_LEN,
+    )];
+    let mut iface = EthernetInterfaceBuilder::new(Slip::new(board.tx, board.rx))
+        .ethernet_addr(EthernetAddress(slip::LOCAL_MAC))
+        .neighbor_cache(NeighborCache::new(&mut neighbor_storage[..]))
+        .ip_addrs(&mut ip_addrs[..])
+        .finalize();
+
+    // Pings are handled by the interface itself, so we need no sockets
+    let mut socket_storage: [Option<smoltcp::socket::SocketSetItem>; 1] = [None];
+    let mut sockets = smoltcp::socket::SocketSet::new(&mut socket_storage[..]);
+
+    // This ignores the time spent doing things, so it runs a little slow
+    let mut uptime_ms = 0u32;
+
+    loop {
+        // There's nobody to tell about errors, so carry on regardless
+        let _ = iface.poll(&mut sockets, Instant::from_millis(i64::from(uptime_ms)));
+
+        match uptime_ms % 1000 {
+            0 => board.led_green.on(),
+            50 => board.led_green.off(),
+            _ => {}
+        }
+
+        d.delay_ms(POLL_MS);
+        uptime_ms = uptime_ms.wrapping_add(POLL_MS);
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
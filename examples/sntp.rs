@@ -0,0 +1,361 @@
+//! Keeps the hibernation module's real-time clock in step with network time.
+//!
+//! We fetch the time from an SNTP server over an ESP8266 (wired as for the
+//! `esp8266` example, on PB0/PB1) at boot and then every hour. Each time, we
+//! see how far the RTC has wandered since the last sync and adjust its trim
+//! to match, so it keeps better time in between - and when the network is
+//! down. Set the constants below to suit your network.
+//!
+//! The time and the sync status go in the VGA status bar, with the details
+//! below. The VGA output is the same as `hello_vga`: HSYNC on PB6, VSYNC
+//! on PC4 and green on PB7. Progress is printed on UART0 (115200 bps) too.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use cortex_m::asm;
+use demo::board::Board;
+use demo::datetime::DateTime;
+use demo::esp8266::{self, Esp8266, Protocol};
+use demo::font;
+use demo::graphics::{Canvas, Colour};
+use demo::hib::{self, Rtc};
+use demo::sntp;
+use demo::status_bar;
+use demo::text::Buffer;
+use demo::vga;
+use rt::ExceptionFrame;
+use tm4c123x_hal::delay::Delay;
+use tm4c123x_hal::gpio::GpioExt;
+use tm4c123x_hal::serial::{NewlineMode, Serial};
+use tm4c123x_hal::sysctl;
+use tm4c123x_hal::time::U32Ext;
+
+/// The network to join.
+const SSID: &str = "my-network";
+const PASSWORD: &str = "my-password";
+
+/// Where to get the time.
+const SERVER: &str = "pool.ntp.org";
+
+/// How often to sync, and how soon to try again if it fails.
+const SYNC_SECS: u32 = 3600;
+const RETRY_SECS: u32 = 60;
+
+/// Only adjust the trim when the clock was at least this close, in
+/// milliseconds. Anything worse means the clock was never set, or someone
+/// has been fiddling with it.
+const MAX_TRIM_OFFSET_MS: i64 = 2_000;
+
+/// Don't trim by more than about 250 ppm.
+const MAX_TRIM_STEP: i64 = 512;
+
+/// How long to wait for the server.
+const REPLY_TIMEOUT_MS: u32 = 2_000;
+
+/// Where the labels and values go.
+const LEFT: usize = 2 * font::WIDTH;
+const VALUE_X: usize = LEFT + (12 * font::WIDTH);
+const FIRST_ROW: usize = status_bar::HEIGHT + font::HEIGHT;
+const ROW_HEIGHT: usize = font::HEIGHT + (font::HEIGHT / 2);
+
+/// Values are padded to this many characters, to rub out the old ones.
+const VALUE_WIDTH: usize = 30;
+
+const LABELS: [&str; 5] = ["Server", "Last sync", "Stratum", "Offset", "Trim"];
+
+/// How the last sync went.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Status {
+    NeverSynced,
+    Synced,
+    Failed,
+}
+
+/// What we remember between syncs.
+struct Sync {
+    status: Status,
+    /// When we last synced (successfully or not), by the RTC
+    last_attempt: u32,
+    /// When we last synced successfully, by the server
+    last_sync: Option<u32>,
+    stratum: u8,
+    /// How far out the RTC was, in milliseconds (positive is fast)
+    offset_ms: i64,
+}
+
+/// Ask the server what the time is.
+fn query<TX, RX, D>(esp: &mut Esp8266<TX, RX, D>) -> Result<sntp::Reply, esp8266::Error>
+where
+    TX: embedded_hal::serial::Write<u8>,
+    RX: embedded_hal::serial::Read<u8>,
+    D: embedded_hal::blocking::delay::DelayUs<u32>,
+{
+    esp.connect(Protocol::Udp, SERVER, sntp::PORT)?;
+    let mut packet = [0u8; sntp::PACKET_LEN];
+    let mut len = 0;
+    let mut result = esp.send(&sntp::request());
+    while result.is_ok() && len < packet.len() {
+        result = match esp.receive(&mut packet[len..], REPLY_TIMEOUT_MS) {
+            Ok(0) => Err(esp8266::Error::Timeout),
+            Ok(n) => {
+                len += n;
+                Ok(())
+            }
+            Err(e) => Err(e),
+        };
+    }
+    // Always close, even if it went wrong
+    esp.close()?;
+    result?;
+    sntp::parse(&packet[0..len]).ok_or(esp8266::Error::Failed)
+}
+
+/// Get the time from the server, work out how far out we were, adjust the
+/// trim and set the clock.
+fn sync<TX, RX, D>(esp: &mut Esp8266<TX, RX, D>, rtc: &mut Rtc, state: &mut Sync)
+where
+    TX: embedded_hal::serial::Write<u8>,
+    RX: embedded_hal::serial::Read<u8>,
+    D: embedded_hal::blocking::delay::DelayUs<u32>,
+{
+    state.last_attempt = rtc.seconds();
+    let reply = match query(esp) {
+        Ok(reply) => reply,
+        Err(_) => {
+            state.status = Status::Failed;
+            return;
+        }
+    };
+
+    let ticks_per_second = i64::from(hib::TICKS_PER_SECOND);
+    let (seconds, ticks) = rtc.now();
+    let rtc_ticks = (i64::from(seconds) * ticks_per_second) + i64::from(ticks);
+    // 2^32 fractions per second down to 2^15 ticks
+    let ntp_ticks = (i64::from(reply.unix) * ticks_per_second) + i64::from(reply.fraction >> 17);
+    let offset_ticks = rtc_ticks - ntp_ticks;
+    state.offset_ms = (offset_ticks * 1000) / ticks_per_second;
+
+    // We lined the clock up last time, so any offset now is drift. Spread
+    // the correction over each 64 second trim period.
+    if let Some(last_sync) = state.last_sync {
+        let elapsed = i64::from(reply.unix.wrapping_sub(last_sync));
+        if elapsed > 0 && state.offset_ms.abs() < MAX_TRIM_OFFSET_MS {
+            let step = ((offset_ticks * 64) / elapsed).max(-MAX_TRIM_STEP).min(MAX_TRIM_STEP);
+            let trim = i64::from(rtc.trim()) + step;
+            let nominal = i64::from(hib::NOMINAL_TRIM);
+            let trim = trim.max(nominal - MAX_TRIM_STEP).min(nominal + MAX_TRIM_STEP);
+            rtc.set_trim(trim as u16);
+        }
+    }
+
+    // Setting the clock zeroes the sub-seconds, so wait for the start of
+    // the next second and set that
+    let _ = esp.wait(1000 - reply.millis());
+    rtc.set_seconds(reply.unix.wrapping_add(1));
+
+    state.last_sync = Some(reply.unix);
+    state.stratum = reply.stratum;
+    state.status = Status::Synced;
+}
+
+/// Write a value next to its label, padded to rub out what was there.
+fn draw_value<C: Canvas>(canvas: &mut C, row: usize, text: &Buffer) {
+    let y = FIRST_ROW + (row * ROW_HEIGHT);
+    canvas.draw_str(VALUE_X, y, text.as_str(), Colour::WHITE, Colour::BLACK);
+    for col in text.len()..VALUE_WIDTH {
+        canvas.draw_char(
+            VALUE_X + (col * font::WIDTH),
+            y,
+            b' ',
+            Colour::WHITE,
+            Colour::BLACK,
+        );
+    }
+}
+
+fn draw_labels<C: Canvas>(canvas: &mut C) {
+    for (row, label) in LABELS.iter().enumerate() {
+        let y = FIRST_ROW + (row * ROW_HEIGHT);
+        canvas.draw_str(LEFT, y, label, Colour::WHITE, Colour::BLACK);
+    }
+}
+
+/// Fill in the details of the last sync.
+fn draw_sync<C: Canvas>(canvas: &mut C, state: &Sync, trim: u16) {
+    let mut text = Buffer::new();
+    write!(text, "{}", SERVER).unwrap();
+    draw_value(canvas, 0, &text);
+
+    let mut text = Buffer::new();
+    match state.last_sync {
+        Some(unix) => write!(text, "{} UTC", DateTime::from_unix(unix)).unwrap(),
+        None => write!(text, "Never").unwrap(),
+    }
+    draw_value(canvas, 1, &text);
+
+    let mut text = Buffer::new();
+    if state.last_sync.is_some() {
+        write!(text, "{}", state.stratum).unwrap();
+    }
+    draw_value(canvas, 2, &text);
+
+    let mut text = Buffer::new();
+    if state.last_sync.is_some() {
+        write!(text, "{} ms", state.offset_ms).unwrap();
+    }
+    draw_value(canvas, 3, &text);
+
+    let mut text = Buffer::new();
+    write!(
+        text,
+        "0x{:04x} ({:+})",
+        trim,
+        i32::from(trim) - i32::from(hib::NOMINAL_TRIM)
+    ).unwrap();
+    draw_value(canvas, 4, &text);
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+
+    // No reset here, or we'd stop the clock
+    sysctl::control_power(
+        &board.power_control,
+        sysctl::Domain::Hibernation,
+        sysctl::RunMode::Run,
+        sysctl::PowerState::On,
+    );
+
+    let mut portb = p.GPIO_PORTB.split(&board.power_control);
+
+    // The ESP8266
+    let uart1 = Serial::uart1(
+        p.UART1,
+        portb.pb1.into_af1(&mut portb.control),
+        portb.pb0.into_af1(&mut portb.control),
+        (),
+        (),
+        115200_u32.bps(),
+        NewlineMode::Binary,
+        &board.clocks,
+        &board.power_control,
+    );
+    let (tx1, rx1) = uart1.split();
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+
+    let mut rtc = Rtc::new(p.HIB);
+
+    writeln!(board.tx, "SNTP clock").unwrap();
+
+    let fb = vga::framebuffer();
+    fb.clear(Colour::BLACK);
+    status_bar::draw(fb, "SNTP clock", "Starting WiFi");
+    draw_labels(fb);
+
+    let d = Delay::new(cp.SYST, &board.clocks);
+    let mut esp = match Esp8266::new(tx1, rx1, d) {
+        Ok(esp) => esp,
+        Err(e) => {
+            writeln!(board.tx, "ESP8266 not responding: {:?}", e).unwrap();
+            status_bar::draw(fb, "SNTP clock", "No ESP8266");
+            loop {
+                asm::wfi();
+            }
+        }
+    };
+
+    status_bar::draw(fb, "SNTP clock", "Joining network");
+    while let Err(e) = esp.join(SSID, PASSWORD) {
+        writeln!(board.tx, "Failed to join {} ({:?}), retrying", SSID, e).unwrap();
+    }
+
+    let mut state = Sync {
+        status: Status::NeverSynced,
+        last_attempt: 0,
+        last_sync: None,
+        stratum: 0,
+        offset_ms: 0,
+    };
+    let mut shown = None;
+
+    loop {
+        let now = rtc.seconds();
+        let due = match state.status {
+            Status::NeverSynced => true,
+            Status::Synced => now.wrapping_sub(state.last_attempt) >= SYNC_SECS,
+            Status::Failed => now.wrapping_sub(state.last_attempt) >= RETRY_SECS,
+        };
+        if due {
+            status_bar::draw(fb, "Syncing...", SERVER);
+            sync(&mut esp, &mut rtc, &mut state);
+            match state.status {
+                Status::Synced => writeln!(
+                    board.tx,
+                    "Synced with {} (stratum {}), offset {} ms, trim 0x{:04x}",
+                    SERVER,
+                    state.stratum,
+                    state.offset_ms,
+                    rtc.trim()
+                ).unwrap(),
+                _ => writeln!(board.tx, "Sync failed").unwrap(),
+            }
+            draw_sync(fb, &state, rtc.trim());
+            shown = None;
+        }
+
+        let now = rtc.seconds();
+        if shown != Some(now) {
+            shown = Some(now);
+            let mut clock = Buffer::new();
+            write!(clock, "{} UTC", DateTime::from_unix(now)).unwrap();
+            let status = match state.status {
+                Status::NeverSynced => "Not synced",
+                Status::Synced => "NTP synced",
+                Status::Failed => "NTP failed",
+            };
+            status_bar::draw(fb, clock.as_str(), status);
+        }
+
+        // Keeps an eye on the ESP8266 while we wait
+        let _ = esp.wait(50);
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
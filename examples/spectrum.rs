@@ -0,0 +1,267 @@
+//! A spectrum analyser, on the VGA screen.
+//!
+//! The input is AIN0 (PE3), biased to the middle of the 0 to 3.3V range.
+//! Timer1A triggers ADC0 at 20 kHz and the sequencer 3 interrupt collects
+//! 512 samples, which go through a Hann window and `demo::fft` to give 256
+//! bins of 39 Hz each, from DC to 10 kHz.
+//!
+//! The top half of the screen is the current spectrum, as bars on a log
+//! scale (8 pixels per doubling). The bottom half is a spectrogram - each
+//! new spectrum goes in at the top and the old ones scroll down. We only
+//! have one colour, so louder bins are drawn as denser dither patterns.
+//! The status bar shows the loudest bin.
+//!
+//! The VGA output is the same as `hello_vga` (HSYNC on PB6, VSYNC on PC4
+//! and green on PB7).
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use demo::adc::{self, Adc};
+use demo::fft;
+use demo::graphics::{Canvas, Colour, VGA_HEIGHT, VGA_WIDTH};
+use demo::status_bar;
+use demo::text::Buffer;
+use demo::vga;
+use rt::ExceptionFrame;
+use tm4c123x_hal::sysctl;
+use tm4c123x_hal::tm4c123x::ADC0;
+
+/// The analog input.
+const CHANNEL: u8 = 0;
+
+const SYSCLK_HZ: u32 = 80_000_000;
+const SAMPLE_RATE_HZ: u32 = 20_000;
+
+/// Points in the FFT. 256 works too, with half the resolution.
+const POINTS: usize = 512;
+/// The useful half of the output.
+const BINS: usize = POINTS / 2;
+
+/// The left edge of the display, so it's in the middle of the screen.
+const LEFT: usize = (VGA_WIDTH - BINS) / 2;
+/// The top of the bars.
+const BARS_TOP: usize = status_bar::HEIGHT + 8;
+/// How tall a full scale bar is - 16 doublings at 8 pixels each.
+const BARS_HEIGHT: usize = 128;
+/// The top of the spectrogram.
+const HISTORY_TOP: usize = BARS_TOP + BARS_HEIGHT + 8;
+/// How many spectra the spectrogram shows.
+const HISTORY: usize = VGA_HEIGHT - HISTORY_TOP;
+
+/// A 4x4 ordered dither. A pixel is lit if its brightness (0..16) is more
+/// than the entry for its position.
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+static mut ADC: Option<Adc<ADC0>> = None;
+static mut CAPTURE: [u16; POINTS] = [0; POINTS];
+/// How much of `CAPTURE` is full. The main loop sets it back to zero.
+static CAPTURED: AtomicUsize = AtomicUsize::new(0);
+
+/// The spectra the spectrogram shows, one bit per bin, as a ring.
+struct History {
+    rows: [[u32; BINS / 32]; HISTORY],
+    newest: usize,
+}
+
+impl History {
+    /// Add a spectrum. Neighbouring frames use neighbouring lines of the
+    /// dither pattern, so it lines up as they scroll.
+    fn push(&mut self, levels: &[usize; BINS], frame: usize) {
+        self.newest = (self.newest + 1) % HISTORY;
+        let row = &mut self.rows[self.newest];
+        for (bin, &level) in levels.iter().enumerate() {
+            let lit = level / 8 > BAYER[frame % 4][bin % 4] as usize;
+            if lit {
+                row[bin / 32] |= 1 << (bin % 32);
+            } else {
+                row[bin / 32] &= !(1 << (bin % 32));
+            }
+        }
+    }
+
+    /// Draw the lot, newest at the top.
+    fn draw<C>(&self, canvas: &mut C)
+    where
+        C: Canvas,
+    {
+        for age in 0..HISTORY {
+            let row = &self.rows[(self.newest + HISTORY - age) % HISTORY];
+            for bin in 0..BINS {
+                let colour = if row[bin / 32] & (1 << (bin % 32)) != 0 {
+                    Colour::WHITE
+                } else {
+                    Colour::BLACK
+                };
+                canvas.draw_point(LEFT + bin, HISTORY_TOP + age, colour);
+            }
+        }
+    }
+}
+
+/// Start another capture.
+fn arm() {
+    CAPTURED.store(0, Ordering::Relaxed);
+    let timer = unsafe { &*tm4c123x_hal::tm4c123x::TIMER1::ptr() };
+    timer.ctl.modify(|_, w| w.taen().set_bit());
+}
+
+/// A magnitude on a log scale, 8 steps per doubling, 0..128.
+fn level(magnitude: u16) -> usize {
+    if magnitude == 0 {
+        return 0;
+    }
+    let bits = 16 - magnitude.leading_zeros() as usize;
+    // The three bits after the top one
+    let fraction = if bits > 4 {
+        usize::from(magnitude >> (bits - 4)) & 7
+    } else {
+        usize::from(magnitude << (4 - bits)) & 7
+    };
+    ((bits - 1) * 8) + fraction
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = demo::board::Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::Timer1);
+    board.enable(sysctl::Domain::Adc0);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    // PE3
+    demo::config::Port::E.enable();
+    adc::configure_pin(CHANNEL);
+    let mut adc = Adc::adc0(p.ADC0);
+    adc.start_triggered(CHANNEL);
+    unsafe { ADC = Some(adc) };
+
+    // Timer1A, 32-bit periodic, triggering the ADC
+    let timer = p.TIMER1;
+    timer.ctl.modify(|_, w| w.taen().clear_bit());
+    timer.cfg.write(|w| unsafe { w.bits(0) });
+    timer.tamr.modify(|_, w| w.tamr().period());
+    timer
+        .tailr
+        .write(|w| unsafe { w.bits((SYSCLK_HZ / SAMPLE_RATE_HZ) - 1) });
+    timer.ctl.modify(|_, w| w.taote().set_bit());
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+    // Below the VGA timers, so the picture stays put
+    unsafe { nvic.set_priority(tm4c123x_hal::Interrupt::ADC0SS3, 0x40) };
+    nvic.enable(tm4c123x_hal::Interrupt::ADC0SS3);
+
+    vga::framebuffer().clear(Colour::BLACK);
+    writeln!(
+        board.tx,
+        "Spectrum analyser. Input on PE3, {} Hz per bin.",
+        SAMPLE_RATE_HZ / POINTS as u32
+    ).unwrap();
+
+    let mut re = [0i16; POINTS];
+    let mut im = [0i16; POINTS];
+    let mut levels = [0usize; BINS];
+    let mut history = History {
+        rows: [[0; BINS / 32]; HISTORY],
+        newest: 0,
+    };
+    let mut frame = 0;
+
+    arm();
+
+    loop {
+        if CAPTURED.load(Ordering::Relaxed) < POINTS {
+            continue;
+        }
+
+        // Take out the bias, and scale 12 bits up to Q15
+        let samples = unsafe { &CAPTURE };
+        let mean = samples.iter().map(|&s| u32::from(s)).sum::<u32>() / POINTS as u32;
+        for (i, &sample) in samples.iter().enumerate() {
+            re[i] = ((i32::from(sample) - mean as i32) << 3) as i16;
+            im[i] = 0;
+        }
+        // We have what we need, so get the next lot going
+        arm();
+
+        fft::hann(&mut re);
+        fft::transform(&mut re, &mut im);
+
+        let fb = vga::framebuffer();
+        let mut peak = 0;
+        for bin in 0..BINS {
+            levels[bin] = level(fft::magnitude(re[bin], im[bin]));
+            // Ignore DC, which is whatever's left of the bias
+            if bin > 1 && levels[bin] > levels[peak] {
+                peak = bin;
+            }
+            let height = levels[bin];
+            let x = LEFT + bin;
+            fb.fill_rect(x, BARS_TOP, 1, BARS_HEIGHT - height, Colour::BLACK);
+            fb.fill_rect(x, BARS_TOP + BARS_HEIGHT - height, 1, height, Colour::WHITE);
+        }
+
+        frame += 1;
+        history.push(&levels, frame);
+        history.draw(fb);
+
+        let mut status = Buffer::new();
+        write!(
+            status,
+            "Peak {} Hz",
+            (peak as u32 * SAMPLE_RATE_HZ) / POINTS as u32
+        ).unwrap();
+        status_bar::draw(fb, "Spectrum", status.as_str());
+    }
+}
+
+interrupt!(ADC0SS3, adc_isr);
+
+fn adc_isr() {
+    let adc = unsafe { ADC.as_mut().unwrap() };
+    while let Some(sample) = adc.take() {
+        let n = CAPTURED.load(Ordering::Relaxed);
+        if n < POINTS {
+            unsafe { CAPTURE[n] = sample };
+            CAPTURED.store(n + 1, Ordering::Relaxed);
+        } else {
+            // Full - stop until the main loop has copied it out
+            let timer = unsafe { &*tm4c123x_hal::tm4c123x::TIMER1::ptr() };
+            timer.ctl.modify(|_, w| w.taen().clear_bit());
+        }
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
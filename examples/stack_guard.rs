@@ -0,0 +1,118 @@
+//! Catches a stack overflow with an MPU guard region.
+//!
+//! `demo::mpu` puts a no-access region at the bottom of the stack, and
+//! `demo::fault` reports the fault on UART0 (115200 bps) and flashes the
+//! red LED. Commands:
+//!
+//! * `stack` - show the most stack used so far
+//! * `recurse <n>` - call a function `n` levels deep, using 64 bytes or so
+//!   of stack each time
+//!
+//! `recurse 10` is fine. `recurse 1000` isn't.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+
+use core::fmt::Write;
+use core::ptr;
+use demo::console::Console;
+use demo::fault;
+use demo::hal;
+use demo::mpu;
+use demo::stack;
+use embedded_hal::prelude::*;
+use menu::*;
+
+const STACK_ITEM: Item = Item {
+    item_type: ItemType::Callback(stack_callback),
+    command: "stack",
+    help: Some("show the most stack used so far"),
+};
+
+const RECURSE_ITEM: Item = Item {
+    item_type: ItemType::Callback(recurse_callback),
+    command: "recurse",
+    help: Some("<n> - recurse n levels deep"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[&STACK_ITEM, &RECURSE_ITEM],
+    entry: None,
+    exit: None,
+};
+
+fn stack_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    writeln!(
+        Console,
+        "Stack: {} of {} bytes used at worst",
+        stack::used(),
+        stack::size()
+    ).unwrap();
+}
+
+fn recurse_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match input.split_whitespace().nth(1).map(|s| s.parse::<u32>()) {
+        Some(Ok(n)) => writeln!(Console, "Got {}", recurse(n)).unwrap(),
+        _ => writeln!(Console, "Usage: recurse <n>").unwrap(),
+    }
+}
+
+/// Use up some stack. The volatile writes stop the compiler turning this
+/// into a loop, or throwing the buffer away.
+fn recurse(n: u32) -> u32 {
+    let mut buffer = [0u32; 16];
+    for (i, word) in buffer.iter_mut().enumerate() {
+        unsafe { ptr::write_volatile(word, n + i as u32) };
+    }
+    if n == 0 {
+        0
+    } else {
+        unsafe { ptr::read_volatile(&buffer[0]) } + recurse(n - 1)
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let mut cp = hal::CorePeripherals::take().unwrap();
+
+    let floor = mpu::guard_stack(&mut cp.MPU);
+    stack::paint_above(floor);
+
+    let mut board = board!(p);
+
+    writeln!(
+        board.tx,
+        "Stack guard demo. {} bytes of stack, with a {} byte guard below.",
+        stack::size(),
+        mpu::GUARD_SIZE
+    ).unwrap();
+
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+
+    loop {
+        while let Ok(ch) = board.rx.read() {
+            r.input_byte(ch);
+        }
+    }
+}
+
+exception!(HardFault, fault::hard_fault);
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
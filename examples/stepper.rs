@@ -0,0 +1,278 @@
+//! Drives a 4-wire stepper motor with acceleration ramps.
+//!
+//! IN1..IN4 of a ULN2003 (or H-bridge) board go to PE0..PE3. Timer1A
+//! interrupts at 10 kHz and decides when to take each step. The console is
+//! on UART0 at 115200 bps.
+//!
+//! Commands:
+//!
+//! * `step <n>` - move `n` steps (negative for backwards)
+//! * `goto <pos>` - move to an absolute position
+//! * `pos` - show where we are
+//! * `speed <steps/s>` - set the top speed
+//! * `accel <steps/s/s>` - set the acceleration
+//! * `log [<level>]` - show or set the log level (`off`, `error` .. `trace`)
+//!
+//! Moves are logged with `demo::logger`, at `info`, and the end of each
+//! move at `debug`. With `--features defmt` they're logged with `defmt`
+//! over RTT instead, so they don't get mixed up with the console (see
+//! `demo::defmt_log`). Then the level is set when you build, with
+//! `DEFMT_LOG`, so `log` only says so.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[cfg(feature = "defmt")]
+extern crate defmt;
+#[macro_use(board, gpio_port)]
+extern crate demo;
+extern crate embedded_hal;
+#[cfg(not(feature = "defmt"))]
+#[macro_use]
+extern crate log;
+extern crate menu;
+extern crate panic_halt;
+#[cfg(feature = "tm4c123")]
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+#[cfg(feature = "tm4c129")]
+#[macro_use(interrupt)]
+extern crate tm4c129x;
+
+use core::fmt::Write;
+use cortex_m::interrupt;
+#[cfg(feature = "defmt")]
+use defmt::{debug, info};
+use demo::board::SYSCLK_HZ;
+use demo::config;
+use demo::console::Console;
+use demo::hal;
+#[cfg(not(feature = "defmt"))]
+use demo::logger;
+use demo::pac;
+use demo::stepper::Stepper;
+use embedded_hal::prelude::*;
+use hal::gpio::GpioExt;
+use hal::sysctl;
+use menu::*;
+use rt::ExceptionFrame;
+
+/// How often Timer1A ticks the stepper.
+const TICK_HZ: u32 = 10_000;
+
+/// Mask for PE0..PE3.
+const COIL_MASK: u32 = 0x0F;
+
+static mut STEPPER: Option<Stepper> = None;
+
+const STEP_ITEM: Item = Item {
+    item_type: ItemType::Callback(step_callback),
+    command: "step",
+    help: Some("<n> - move n steps"),
+};
+
+const GOTO_ITEM: Item = Item {
+    item_type: ItemType::Callback(goto_callback),
+    command: "goto",
+    help: Some("<pos> - move to an absolute position"),
+};
+
+const POS_ITEM: Item = Item {
+    item_type: ItemType::Callback(pos_callback),
+    command: "pos",
+    help: Some("show the current position"),
+};
+
+const SPEED_ITEM: Item = Item {
+    item_type: ItemType::Callback(speed_callback),
+    command: "speed",
+    help: Some("<steps/s> - set the top speed"),
+};
+
+const ACCEL_ITEM: Item = Item {
+    item_type: ItemType::Callback(accel_callback),
+    command: "accel",
+    help: Some("<steps/s/s> - set the acceleration"),
+};
+
+const LOG_ITEM: Item = Item {
+    item_type: ItemType::Callback(log_callback),
+    command: "log",
+    help: Some("[<level>] - show or set the log level"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[
+        &STEP_ITEM,
+        &GOTO_ITEM,
+        &POS_ITEM,
+        &SPEED_ITEM,
+        &ACCEL_ITEM,
+        &LOG_ITEM,
+    ],
+    entry: None,
+    exit: None,
+};
+
+/// Parse the first argument after the command.
+fn argument<T>(input: &str) -> Option<T>
+where
+    T: core::str::FromStr,
+{
+    input.split_whitespace().nth(1).and_then(|s| s.parse::<T>().ok())
+}
+
+/// Run a closure against the stepper with the timer interrupt masked.
+fn with_stepper<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut Stepper) -> R,
+{
+    interrupt::free(|_| f(unsafe { STEPPER.as_mut().unwrap() }))
+}
+
+fn step_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match argument::<i32>(input) {
+        Some(n) => {
+            info!("Moving {} steps", n);
+            with_stepper(|s| s.move_by(n))
+        }
+        None => writeln!(Console, "Usage: step <n>").unwrap(),
+    }
+}
+
+fn goto_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match argument::<i32>(input) {
+        Some(pos) => {
+            info!("Moving to {}", pos);
+            with_stepper(|s| s.move_to(pos))
+        }
+        None => writeln!(Console, "Usage: goto <pos>").unwrap(),
+    }
+}
+
+fn pos_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    let (pos, target, speed) = with_stepper(|s| (s.position(), s.target(), s.speed()));
+    writeln!(
+        Console,
+        "Position {}, target {}, speed {} steps/s",
+        pos, target, speed
+    ).unwrap();
+}
+
+fn speed_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match argument::<u32>(input) {
+        Some(sps) => {
+            info!("Top speed {} steps/s", sps);
+            with_stepper(|s| s.set_max_speed(sps))
+        }
+        None => writeln!(Console, "Usage: speed <steps/s>").unwrap(),
+    }
+}
+
+fn accel_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match argument::<u32>(input) {
+        Some(a) => {
+            info!("Acceleration {} steps/s/s", a);
+            with_stepper(|s| s.set_accel(a))
+        }
+        None => writeln!(Console, "Usage: accel <steps/s/s>").unwrap(),
+    }
+}
+
+#[cfg(not(feature = "defmt"))]
+fn log_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match argument::<log::LevelFilter>(input) {
+        Some(level) => logger::set_level(level),
+        None => writeln!(Console, "Log level is {}", logger::level()).unwrap(),
+    }
+}
+
+#[cfg(feature = "defmt")]
+fn log_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    writeln!(Console, "Logging with defmt - set DEFMT_LOG when you build").unwrap();
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Timer1);
+
+    let porte = gpio_port!(p, E).split(&board.power_control);
+
+    #[cfg(not(feature = "defmt"))]
+    logger::init(logger::Output::Uart, log::LevelFilter::Info);
+
+    // The ISR drives these through the data register directly
+    let _in1 = porte.pe0.into_push_pull_output();
+    let _in2 = porte.pe1.into_push_pull_output();
+    let _in3 = porte.pe2.into_push_pull_output();
+    let _in4 = porte.pe3.into_push_pull_output();
+
+    unsafe {
+        STEPPER = Some(Stepper::new(TICK_HZ, 400, 800));
+    }
+
+    // Timer1A, 32-bit periodic, interrupting on timeout
+    let timer = p.TIMER1;
+    timer.ctl.modify(|_, w| w.taen().clear_bit());
+    timer.cfg.write(|w| unsafe { w.bits(0) });
+    timer.tamr.modify(|_, w| w.tamr().period());
+    timer
+        .tailr
+        .write(|w| unsafe { w.bits(SYSCLK_HZ / TICK_HZ - 1) });
+    timer.imr.modify(|_, w| w.tatoim().set_bit());
+    timer.icr.write(|w| w.tatocint().set_bit());
+    timer.ctl.modify(|_, w| w.taen().set_bit());
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(hal::Interrupt::TIMER1A);
+
+    writeln!(board.tx, "Stepper demo").unwrap();
+
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+
+    loop {
+        if let Ok(ch) = board.rx.read() {
+            r.input_byte(ch);
+        }
+    }
+}
+
+interrupt!(TIMER1A, timer1a_isr);
+
+fn timer1a_isr() {
+    let timer = unsafe { &*pac::TIMER1::ptr() };
+    timer.icr.write(|w| w.tatocint().set_bit());
+    if let Some(stepper) = unsafe { STEPPER.as_mut() } {
+        if stepper.tick().is_some() {
+            if stepper.position() == stepper.target() {
+                debug!("Arrived at {}", stepper.position());
+            }
+            let gpio = config::Port::E.registers();
+            let coils = u32::from(stepper.coils());
+            gpio.data
+                .modify(|r, w| unsafe { w.bits((r.bits() & !COIL_MASK) | coils) });
+        }
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,69 @@
+//! VGA on an STM32F4 Discovery, with `demo::vga_stm32f4`.
+//!
+//! HSYNC is PB4, VSYNC is PB5 and green is PB15. Wire them up as for
+//! `hello_vga` - the green needs dropping to 0.7V, ideally with a 75R
+//! source impedance.
+//!
+//! ```text
+//! $ cargo build --no-default-features --features stm32f407 --example stm32f4_vga
+//! ```
+//!
+//! OpenOCD wants `-f board/stm32f4discovery.cfg` rather than the LaunchPad
+//! one.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate stm32f4;
+
+use cortex_m::asm;
+use demo::graphics::{Canvas, Colour, VGA_HEIGHT, VGA_WIDTH};
+use demo::pac;
+use demo::status_bar;
+use demo::vga_stm32f4;
+use rt::ExceptionFrame;
+
+entry!(main);
+
+fn main() -> ! {
+    let p = pac::Peripherals::take().unwrap();
+    let cp = cortex_m::Peripherals::take().unwrap();
+
+    vga_stm32f4::set_up_clock(&p.RCC, &p.FLASH);
+    vga_stm32f4::init(&p.RCC, p.GPIOB, p.TIM3, p.SPI2, p.DMA1);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(pac::Interrupt::TIM3);
+
+    let fb = vga_stm32f4::framebuffer();
+    fb.clear(Colour::BLACK);
+    status_bar::draw(fb, "STM32F4 Discovery", "160 MHz");
+    fb.draw_rect(20, 40, VGA_WIDTH - 40, VGA_HEIGHT - 60, Colour::WHITE);
+    fb.draw_str_scaled(68, 110, "Hello, VGA!", 3, Colour::WHITE, Colour::BLACK);
+    fb.draw_str(128, 190, "TIM3 + SPI2 + DMA1", Colour::WHITE, Colour::BLACK);
+
+    // Everything else happens in the interrupt
+    loop {
+        asm::wfi();
+    }
+}
+
+interrupt!(TIM3, vga_stm32f4::tim3_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,238 @@
+//! Play with `demo::synth` from the console, with the VGA screen running.
+//!
+//! The output is PB4 - see `demo::synth` for the filter. The VGA output is
+//! the same as `hello_vga` (HSYNC on PB6, VSYNC on PC4 and green on PB7),
+//! and shows what each channel is doing, to prove the two get along. The
+//! console is on UART0 at 115200 bps. Channels are numbered 0 to 2 and
+//! notes are MIDI numbers (60 is middle C). Commands:
+//!
+//! * `note <ch> <note>` - start a note
+//! * `off <ch>` - release it
+//! * `wave <ch> <sine|square|triangle|saw|noise>` - set the waveform
+//! * `vol <ch> <0..255>` - set the volume
+//! * `env <ch> <attack> <decay> <sustain> <release>` - set the envelope
+//!   (times in ms, sustain 0..255)
+//! * `chord` - play a C major chord on all three
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use demo::board::Board;
+use demo::console::Console;
+use demo::graphics::Colour;
+use demo::midi;
+use demo::status_bar;
+use demo::synth::{self, Envelope};
+use demo::vga;
+use demo::wavetable::Waveform;
+use embedded_hal::prelude::*;
+use menu::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::sysctl;
+
+const NOTE_ITEM: Item = Item {
+    item_type: ItemType::Callback(note_callback),
+    command: "note",
+    help: Some("<ch> <note> - start a note"),
+};
+
+const OFF_ITEM: Item = Item {
+    item_type: ItemType::Callback(off_callback),
+    command: "off",
+    help: Some("<ch> - release a note"),
+};
+
+const WAVE_ITEM: Item = Item {
+    item_type: ItemType::Callback(wave_callback),
+    command: "wave",
+    help: Some("<ch> <sine|square|triangle|saw|noise> - set the waveform"),
+};
+
+const VOL_ITEM: Item = Item {
+    item_type: ItemType::Callback(vol_callback),
+    command: "vol",
+    help: Some("<ch> <0..255> - set the volume"),
+};
+
+const ENV_ITEM: Item = Item {
+    item_type: ItemType::Callback(env_callback),
+    command: "env",
+    help: Some("<ch> <a> <d> <s> <r> - set the envelope"),
+};
+
+const CHORD_ITEM: Item = Item {
+    item_type: ItemType::Callback(chord_callback),
+    command: "chord",
+    help: Some("play C major"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[
+        &NOTE_ITEM,
+        &OFF_ITEM,
+        &WAVE_ITEM,
+        &VOL_ITEM,
+        &ENV_ITEM,
+        &CHORD_ITEM,
+    ],
+    entry: None,
+    exit: None,
+};
+
+/// Argument `n` after the command.
+fn argument<T>(input: &str, n: usize) -> Option<T>
+where
+    T: core::str::FromStr,
+{
+    input.split_whitespace().nth(n).and_then(|s| s.parse::<T>().ok())
+}
+
+/// The channel number, which is always the first argument.
+fn channel(input: &str) -> Option<usize> {
+    argument::<usize>(input, 1).filter(|&ch| ch < synth::CHANNELS)
+}
+
+fn note_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match (channel(input), argument::<u8>(input, 2)) {
+        (Some(ch), Some(note)) if note < 128 => {
+            synth::with(|s| s.note_on(ch, synth::note_millihertz(note)))
+        }
+        _ => writeln!(Console, "Usage: note <0..2> <0..127>").unwrap(),
+    }
+}
+
+fn off_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match channel(input) {
+        Some(ch) => synth::with(|s| s.note_off(ch)),
+        None => writeln!(Console, "Usage: off <0..2>").unwrap(),
+    }
+}
+
+fn wave_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let waveform = match input.split_whitespace().nth(2) {
+        Some("sine") => Some(Waveform::Sine),
+        Some("square") => Some(Waveform::Square),
+        Some("triangle") => Some(Waveform::Triangle),
+        Some("saw") => Some(Waveform::Sawtooth),
+        Some("noise") => Some(Waveform::Noise),
+        _ => None,
+    };
+    match (channel(input), waveform) {
+        (Some(ch), Some(waveform)) => synth::with(|s| s.set_waveform(ch, waveform)),
+        _ => writeln!(Console, "Usage: wave <0..2> <sine|square|triangle|saw|noise>").unwrap(),
+    }
+}
+
+fn vol_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match (channel(input), argument::<u8>(input, 2)) {
+        (Some(ch), Some(volume)) => synth::with(|s| s.set_volume(ch, volume)),
+        _ => writeln!(Console, "Usage: vol <0..2> <0..255>").unwrap(),
+    }
+}
+
+fn env_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let envelope = match (
+        argument::<u32>(input, 2),
+        argument::<u32>(input, 3),
+        argument::<u8>(input, 4),
+        argument::<u32>(input, 5),
+    ) {
+        (Some(attack_ms), Some(decay_ms), Some(sustain), Some(release_ms)) => Some(Envelope {
+            attack_ms,
+            decay_ms,
+            sustain,
+            release_ms,
+        }),
+        _ => None,
+    };
+    match (channel(input), envelope) {
+        (Some(ch), Some(envelope)) => synth::with(|s| s.set_envelope(ch, envelope)),
+        _ => writeln!(Console, "Usage: env <0..2> <a ms> <d ms> <0..255> <r ms>").unwrap(),
+    }
+}
+
+fn chord_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    synth::with(|s| {
+        for (ch, offset) in [0, 4, 7].iter().enumerate() {
+            s.note_on(ch, synth::note_millihertz(midi::MIDDLE_C + offset));
+        }
+    });
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::Timer1);
+    board.enable(sysctl::Domain::Timer2);
+
+    vga::init(p.TIMER0, p.SSI2);
+    synth::start(p.TIMER1, p.TIMER2, &board.clocks);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+    // Below the VGA timers, so the picture stays put
+    unsafe { nvic.set_priority(tm4c123x_hal::Interrupt::TIMER2A, 0x40) };
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER2A);
+
+    vga::framebuffer().clear(Colour::BLACK);
+
+    writeln!(board.tx, "Synth on PB4. Try 'chord', then 'off 0'.").unwrap();
+
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+
+    let mut shown = None;
+    loop {
+        while let Ok(ch) = board.rx.read() {
+            r.input_byte(ch);
+        }
+        let playing = synth::with(|s| [s.is_playing(0), s.is_playing(1), s.is_playing(2)]);
+        if shown != Some(playing) {
+            let mut channels = *b"0:- 1:- 2:-";
+            for (i, &on) in playing.iter().enumerate() {
+                if on {
+                    channels[(i * 4) + 2] = b'#';
+                }
+            }
+            let channels = core::str::from_utf8(&channels).unwrap();
+            status_bar::draw(vga::framebuffer(), "Synth", channels);
+            shown = Some(playing);
+        }
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+interrupt!(TIMER2A, synth::timer2a_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
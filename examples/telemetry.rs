@@ -0,0 +1,171 @@
+//! Streams two analog inputs, the chip temperature and how long each
+//! reading takes down UART0 (115200 bps), for a serial plotter on the host.
+//!
+//! The inputs are AIN0 (PE3) and AIN1 (PE2), 0 to 3.3V, sent in millivolts.
+//! The temperature is in tenths of a degree, and `busy_us` is how many
+//! microseconds the last lot took to read and send - watch it go up as the
+//! rate does.
+//!
+//! It starts sending CSV ten times a second, which the Arduino IDE's serial
+//! plotter draws as it is. Type (without pressing Enter):
+//!
+//! * `c` or `b` - CSV, or binary frames for SerialPlot (see
+//!   `demo::telemetry` for how to set it up)
+//! * `+` or `-` - faster or slower, from 100 times a second down to once
+//! * `1` to `4` - turn a channel on or off
+//! * space - stop (and say what the settings are) or start again
+//!
+//! ---
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use]
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+
+use core::fmt::Write;
+use demo::adc::{self, Adc};
+use demo::board::Board;
+use demo::fault;
+use demo::hal;
+use demo::pac;
+use demo::profile;
+use demo::scheduler::{self, Task};
+use demo::telemetry::{Format, Telemetry};
+use embedded_hal::prelude::*;
+use hal::sysctl;
+use pac::ADC0;
+
+/// What each value is called, in the order they're sent.
+const NAMES: [&str; 4] = ["ain0_mV", "ain1_mV", "temp_dC", "busy_us"];
+
+/// The rates `+` and `-` step through, fastest first.
+const PERIODS_MS: [u32; 7] = [10, 20, 50, 100, 200, 500, 1000];
+
+/// Where we start: ten times a second.
+const START_PERIOD: usize = 3;
+
+struct Context {
+    board: Board,
+    adc: Adc<ADC0>,
+    telemetry: Telemetry,
+    /// Which of `PERIODS_MS` we're on
+    period: usize,
+    paused: bool,
+    /// How long the last lot took, in cycles
+    busy: u32,
+}
+
+static TASKS: [Task<Context>; 2] = [
+    Task {
+        name: "keys",
+        period_ms: 0,
+        run: keys,
+    },
+    Task {
+        name: "stream",
+        period_ms: 1,
+        run: stream,
+    },
+];
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let mut cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Adc0);
+    demo::config::Port::E.enable();
+    adc::configure_pin(0);
+    adc::configure_pin(1);
+    let adc = Adc::adc0(p.ADC0);
+
+    profile::init(&mut cp.DCB, &mut cp.DWT);
+
+    let clocks = board.clocks;
+    let mut context = Context {
+        board,
+        adc,
+        telemetry: Telemetry::new(Format::Csv, PERIODS_MS[START_PERIOD]),
+        period: START_PERIOD,
+        paused: false,
+        busy: 0,
+    };
+    scheduler::start(cp.SYST, &clocks, &TASKS).run(&mut context);
+}
+
+/// Take a reading of everything, and send it if it's time.
+fn stream(c: &mut Context) {
+    if c.paused || !c.telemetry.due(scheduler::now()) {
+        return;
+    }
+    let clocks = c.board.clocks;
+    let busy_us = profile::micros(c.busy, &clocks) as i32;
+    let (_, cycles) = profile!{
+        let values = [
+            adc::millivolts(c.adc.read(0)) as i32,
+            adc::millivolts(c.adc.read(1)) as i32,
+            adc::temperature(c.adc.read(adc::TEMPERATURE)),
+            busy_us,
+        ];
+        c.telemetry.send(&mut c.board.tx, &NAMES, &values).unwrap();
+    };
+    c.busy = cycles;
+}
+
+/// Act on anything typed.
+fn keys(c: &mut Context) {
+    while let Ok(byte) = c.board.rx.read() {
+        match byte {
+            b'c' => c.telemetry.set_format(Format::Csv),
+            b'b' => c.telemetry.set_format(Format::Binary),
+            b'+' if c.period > 0 => c.period -= 1,
+            b'-' if c.period < PERIODS_MS.len() - 1 => c.period += 1,
+            b'1'...b'4' => {
+                let channel = (byte - b'1') as usize;
+                let on = c.telemetry.is_selected(channel);
+                c.telemetry.select(channel, !on);
+            }
+            b' ' => {
+                c.paused = !c.paused;
+                // The names again, so a plotter that's just been
+                // started knows what's what
+                c.telemetry.restart();
+            }
+            _ => continue,
+        }
+        c.telemetry.set_period_ms(PERIODS_MS[c.period]);
+        if c.paused {
+            show_settings(c);
+        }
+    }
+}
+
+fn show_settings(c: &mut Context) {
+    let tx = &mut c.board.tx;
+    let format = c.telemetry.format().name();
+    write!(tx, "\nStopped: {}, every {} ms,", format, PERIODS_MS[c.period]).unwrap();
+    for (i, name) in NAMES.iter().enumerate() {
+        let mark = if c.telemetry.is_selected(i) { '+' } else { '-' };
+        write!(tx, " {}{}:{}", i + 1, mark, name).unwrap();
+    }
+    writeln!(tx, "\nc/b format, +/- rate, 1-4 channels, space to start").unwrap();
+}
+
+exception!(SysTick, scheduler::tick);
+
+// Print what went wrong on UART0 and flash the red LED
+exception!(HardFault, fault::hard_fault);
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
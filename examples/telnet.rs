@@ -0,0 +1,292 @@
+//! A command console on UART0 and over Telnet at the same time.
+//!
+//! The Ethernet side is an ENC28J60, wired as for the `ethernet` example,
+//! with a fixed IP address (change `IP_ADDRESS` to suit your network). Try
+//! `telnet 192.168.1.50` and you get the same menu as on the serial port,
+//! with its own line buffer, so both can be used at once. One network
+//! session at a time, mind.
+//!
+//! Commands:
+//!
+//! * `led <red|green|blue|off>` - set the LED
+//! * `uptime` - how long we've been running
+//! * `temp` - the chip temperature
+//! * `bye` - hang up the network session
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+extern crate smoltcp;
+
+use core::fmt::Write;
+use demo::adc::{self, Adc};
+use demo::config;
+use demo::console::{self, Console};
+use demo::enc28j60::Enc28j60;
+use demo::hal;
+use demo::pac;
+use demo::spi::Spi;
+use demo::telnet;
+use embedded_hal::prelude::*;
+use embedded_hal::spi::MODE_0;
+use hal::delay::Delay;
+use hal::sysctl;
+use hal::time::U32Ext;
+use menu::*;
+use pac::ADC0;
+use rt::ExceptionFrame;
+use smoltcp::iface::{EthernetInterfaceBuilder, NeighborCache};
+use smoltcp::socket::{SocketSet, TcpSocket, TcpSocketBuffer};
+use smoltcp::time::Instant;
+use smoltcp::wire::{EthernetAddress, IpAddress, IpCidr};
+
+/// A locally administered address, so it can't clash with a real card.
+const MAC_ADDRESS: [u8; 6] = [0x02, 0x00, 0x00, 0x12, 0x34, 0x56];
+
+/// Who we are.
+const IP_ADDRESS: [u8; 4] = [192, 168, 1, 50];
+const PREFIX_LEN: u8 = 24;
+
+/// How long we sleep each time round the main loop.
+const POLL_MS: u32 = 1;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Led {
+    Off,
+    Red,
+    Green,
+    Blue,
+}
+
+static mut LED: Led = Led::Off;
+
+static mut UPTIME_MS: u32 = 0;
+
+static mut ADC: Option<Adc<ADC0>> = None;
+
+/// Set by `bye`, and acted on by the main loop.
+static mut HANG_UP: bool = false;
+
+const LED_ITEM: Item = Item {
+    item_type: ItemType::Callback(led_callback),
+    command: "led",
+    help: Some("<red|green|blue|off> - set the LED"),
+};
+
+const UPTIME_ITEM: Item = Item {
+    item_type: ItemType::Callback(uptime_callback),
+    command: "uptime",
+    help: Some("show how long we've been running"),
+};
+
+const TEMP_ITEM: Item = Item {
+    item_type: ItemType::Callback(temp_callback),
+    command: "temp",
+    help: Some("show the chip temperature"),
+};
+
+const BYE_ITEM: Item = Item {
+    item_type: ItemType::Callback(bye_callback),
+    command: "bye",
+    help: Some("hang up the network session"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[&LED_ITEM, &UPTIME_ITEM, &TEMP_ITEM, &BYE_ITEM],
+    entry: None,
+    exit: None,
+};
+
+fn led_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let led = match input.split_whitespace().nth(1) {
+        Some("red") => Led::Red,
+        Some("green") => Led::Green,
+        Some("blue") => Led::Blue,
+        Some("off") => Led::Off,
+        _ => {
+            writeln!(Console, "Usage: led <red|green|blue|off>").unwrap();
+            return;
+        }
+    };
+    unsafe { LED = led };
+}
+
+fn uptime_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    let seconds = unsafe { UPTIME_MS } / 1000;
+    writeln!(
+        Console,
+        "Up {}:{:02}:{:02}",
+        seconds / 3600,
+        (seconds / 60) % 60,
+        seconds % 60
+    ).unwrap();
+}
+
+fn temp_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    let adc = unsafe { ADC.as_mut().unwrap() };
+    let temperature = adc::temperature(adc.read(adc::TEMPERATURE));
+    let sign = if temperature < 0 { "-" } else { "" };
+    writeln!(
+        Console,
+        "{}{}.{} C",
+        sign,
+        temperature.abs() / 10,
+        temperature.abs() % 10
+    ).unwrap();
+}
+
+fn bye_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    writeln!(Console, "Bye!").unwrap();
+    unsafe { HANG_UP = true };
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Ssi0);
+    board.enable(sysctl::Domain::Adc0);
+
+    // SSI0Clk, SSI0Rx and SSI0Tx
+    config::SSI0.connect();
+    let porta = board.porta;
+    // The errata say older chips want at least 8 MHz
+    let spi = Spi::ssi0(p.SSI0, MODE_0, 10_000_000_u32.hz(), &board.clocks);
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+
+    unsafe {
+        ADC = Some(Adc::adc0(p.ADC0));
+    }
+
+    writeln!(board.tx, "Telnet console demo").unwrap();
+
+    let eth = Enc28j60::new(spi, porta.pa3.into_push_pull_output(), &mut d, MAC_ADDRESS).unwrap();
+
+    let mut neighbor_storage = [None; 8];
+    let mut ip_addrs = [IpCidr::new(
+        IpAddress::v4(IP_ADDRESS[0], IP_ADDRESS[1], IP_ADDRESS[2], IP_ADDRESS[3]),
+        PREFIX_LEN,
+    )];
+    let mut iface = EthernetInterfaceBuilder::new(eth)
+        .ethernet_addr(EthernetAddress(MAC_ADDRESS))
+        .neighbor_cache(NeighborCache::new(&mut neighbor_storage[..]))
+        .ip_addrs(&mut ip_addrs[..])
+        .finalize();
+
+    let mut rx_storage = [0u8; 256];
+    let mut tx_storage = [0u8; 1024];
+    let telnet_socket = TcpSocket::new(
+        TcpSocketBuffer::new(&mut rx_storage[..]),
+        TcpSocketBuffer::new(&mut tx_storage[..]),
+    );
+    let mut socket_storage = [None];
+    let mut sockets = SocketSet::new(&mut socket_storage[..]);
+    let handle = sockets.add(telnet_socket);
+
+    writeln!(
+        board.tx,
+        "Listening on {}:{}",
+        iface.ip_addrs()[0].address(),
+        telnet::PORT
+    ).unwrap();
+
+    // One runner for the serial port...
+    let mut serial_buffer = [0u8; 64];
+    let mut serial = Runner::new(&ROOT_MENU, &mut serial_buffer, &mut board.tx);
+
+    // ...and one for the network
+    let mut network_buffer = [0u8; 64];
+    let mut network_output = telnet::Output::new();
+    let mut network = Runner::new(&ROOT_MENU, &mut network_buffer, &mut network_output);
+    let mut decoder = telnet::Decoder::new();
+    let mut connected = false;
+
+    loop {
+        let uptime_ms = unsafe { UPTIME_MS };
+        match iface.poll(&mut sockets, Instant::from_millis(i64::from(uptime_ms))) {
+            Ok(_) | Err(smoltcp::Error::Unrecognized) => {}
+            Err(e) => writeln!(Console, "Poll error: {}", e).unwrap(),
+        }
+
+        while let Ok(ch) = board.rx.read() {
+            serial.input_byte(ch);
+        }
+
+        {
+            let mut socket = sockets.get::<TcpSocket>(handle);
+            if !socket.is_open() {
+                socket.listen(telnet::PORT).unwrap();
+            }
+
+            if socket.is_active() && !connected {
+                connected = true;
+                writeln!(Console, "Telnet from {}", socket.remote_endpoint()).unwrap();
+                decoder = telnet::Decoder::new();
+                network.output.clear();
+                network.output.write_bytes(&telnet::NEGOTIATION);
+                network.prompt();
+            } else if !socket.is_active() && connected {
+                connected = false;
+                writeln!(Console, "Telnet session closed").unwrap();
+            }
+
+            let mut chunk = [0u8; 64];
+            let len = socket.recv_slice(&mut chunk).unwrap_or(0);
+            for &byte in &chunk[0..len] {
+                if let Some(ch) = decoder.input(byte) {
+                    // Callbacks talk to the Console, so catch what they say
+                    // and send it to the right place
+                    let said = console::capture(|| network.input_byte(ch));
+                    network.output.write_bytes(said.as_bytes());
+                }
+            }
+
+            if socket.can_send() && !network.output.pending().is_empty() {
+                let sent = socket.send_slice(network.output.pending()).unwrap_or(0);
+                network.output.consume(sent);
+            }
+
+            // Closing waits for everything we've sent to go first
+            let hang_up = unsafe { HANG_UP };
+            if hang_up || (connected && !socket.may_recv()) {
+                socket.close();
+            }
+            unsafe { HANG_UP = false };
+        }
+
+        let led = unsafe { LED };
+        board.led_red.set(led == Led::Red);
+        board.led_green.set(led == Led::Green);
+        board.led_blue.set(led == Led::Blue);
+
+        d.delay_ms(POLL_MS);
+        unsafe {
+            UPTIME_MS = UPTIME_MS.wrapping_add(POLL_MS);
+        }
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
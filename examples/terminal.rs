@@ -0,0 +1,115 @@
+//! A dumb serial terminal, on the VGA screen.
+//!
+//! Connect the machine you want to talk to to UART1 (PB1 is TX, PB0 is RX,
+//! at 3.3V) at 9600 bps. Whatever it sends goes through `demo::vt100` onto
+//! the screen, and whatever you type on UART0 (115200 bps) is sent to it -
+//! so the PC on the end of the debug USB port is just the keyboard.
+//!
+//! The VGA output is the same as `hello_vga` (HSYNC on PB6, VSYNC on PC4
+//! and green on PB7). There's no local echo, as most hosts echo for you.
+//! A piezo sounder on PB4 beeps when the host rings the bell.
+//!
+//! To try it out without another machine, connect PB1 to PB0: everything
+//! you type comes straight back and gets drawn, escape sequences and all.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+#[macro_use]
+extern crate nb;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use demo::audio;
+use demo::board::Board;
+use demo::vga;
+use demo::vt100::Terminal;
+use embedded_hal::prelude::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::gpio::GpioExt;
+use tm4c123x_hal::serial::{NewlineMode, Serial};
+use tm4c123x_hal::sysctl;
+use tm4c123x_hal::time::U32Ext;
+
+/// The host's baud rate. Scrolling the whole screen takes a while, so go
+/// faster than this and you'll want flow control.
+const HOST_BAUD: u32 = 9600;
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::Pwm0);
+    board.enable(sysctl::Domain::Timer5);
+
+    let mut portb = p.GPIO_PORTB.split(&board.power_control);
+    let uart1 = Serial::uart1(
+        p.UART1,
+        portb.pb1.into_af1(&mut portb.control),
+        portb.pb0.into_af1(&mut portb.control),
+        (),
+        (),
+        HOST_BAUD.bps(),
+        NewlineMode::Binary,
+        &board.clocks,
+        &board.power_control,
+    );
+    let (mut host_tx, mut host_rx) = uart1.split();
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+
+    audio::init(p.PWM0, p.TIMER5, &board.clocks);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER5A);
+
+    let mut terminal = Terminal::new(vga::framebuffer());
+    let (cols, rows) = terminal.size();
+    writeln!(
+        board.tx,
+        "Terminal: {}x{} at {} bps on UART1. Type away.",
+        cols,
+        rows,
+        HOST_BAUD
+    ).unwrap();
+
+    loop {
+        while let Ok(byte) = host_rx.read() {
+            terminal.input(vga::framebuffer(), byte);
+        }
+        while let Ok(byte) = board.rx.read() {
+            block!(host_tx.write(byte)).unwrap();
+        }
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+interrupt!(TIMER5A, audio::timer5a_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,125 @@
+//! A splash screen, with `demo::tracker::DEMO` playing behind it.
+//!
+//! The music comes out of PB4, as in the `synth` example - see
+//! `demo::synth` for the filter. The VGA output is the same as `hello_vga`
+//! (HSYNC on PB6, VSYNC on PC4 and green on PB7). Press any key on the
+//! console to start the song again.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use demo::board::Board;
+use demo::font;
+use demo::graphics::{Canvas, Colour, VGA_HEIGHT, VGA_WIDTH};
+use demo::status_bar;
+use demo::synth;
+use demo::text::Buffer;
+use demo::tracker;
+use demo::vga;
+use embedded_hal::prelude::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::sysctl;
+
+/// The Rust logo, a bit per pixel, most significant bit on the left.
+static LOGO: [[u16; VGA_WIDTH / 16]; VGA_HEIGHT] = include!("rust_logo.inc");
+
+/// Draw the logo and a caption.
+fn draw_splash<C>(canvas: &mut C)
+where
+    C: Canvas,
+{
+    for (y, row) in LOGO.iter().enumerate() {
+        for (i, &word) in row.iter().enumerate() {
+            for bit in 0..16 {
+                if word & (0x8000 >> bit) != 0 {
+                    canvas.draw_point((i * 16) + bit, y, Colour::WHITE);
+                }
+            }
+        }
+    }
+    let caption = "Rust on the TM4C123";
+    let x = (VGA_WIDTH - (caption.len() * font::WIDTH)) / 2;
+    let y = VGA_HEIGHT - font::HEIGHT - 4;
+    canvas.draw_str(x, y, caption, Colour::WHITE, Colour::BLACK);
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::Timer1);
+    board.enable(sysctl::Domain::Timer2);
+    board.enable(sysctl::Domain::Timer4);
+
+    vga::init(p.TIMER0, p.SSI2);
+    synth::start(p.TIMER1, p.TIMER2, &board.clocks);
+    tracker::start(p.TIMER4, &board.clocks);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+    // Below the VGA timers, so the picture stays put
+    unsafe {
+        nvic.set_priority(tm4c123x_hal::Interrupt::TIMER2A, 0x40);
+        nvic.set_priority(tm4c123x_hal::Interrupt::TIMER4A, 0x40);
+    }
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER2A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER4A);
+
+    let fb = vga::framebuffer();
+    fb.clear(Colour::BLACK);
+    draw_splash(fb);
+
+    writeln!(board.tx, "Playing '{}'. Press a key to restart.", tracker::DEMO.name).unwrap();
+    tracker::play(&tracker::DEMO);
+
+    let mut shown = None;
+    loop {
+        if board.rx.read().is_ok() {
+            tracker::play(&tracker::DEMO);
+        }
+        let position = tracker::position();
+        if position != shown {
+            let mut text = Buffer::new();
+            match position {
+                Some((order, row)) => write!(text, "{:02}:{:02}", order, row).unwrap(),
+                None => write!(text, "Stopped").unwrap(),
+            }
+            status_bar::draw(vga::framebuffer(), tracker::DEMO.name, text.as_str());
+            shown = position;
+        }
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+interrupt!(TIMER2A, synth::timer2a_isr);
+interrupt!(TIMER4A, tracker::timer4a_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,266 @@
+//! A settings screen, built from `demo::tui` widgets.
+//!
+//! The VGA output is the same as `hello_vga` (HSYNC on PB6, VSYNC on PC4
+//! and green on PB7). Drive it from a PS/2 keyboard (clock on PD2, data on
+//! PD3) or a terminal on the console (UART0 at 115200 bps): Tab, Up and
+//! Down move between the widgets, Left and Right move the slider, and
+//! Enter or Space pick things. A piezo on PB4 (as in `ui_sounds`) makes the
+//! interface noises, if they're turned on.
+//!
+//! Only the keyboard layout is saved, in the same EEPROM words as the
+//! `keyboard` example uses.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use demo::audio::{self, UiSound};
+use demo::board::Board;
+use demo::eeprom::Eeprom;
+use demo::graphics::{Canvas, Colour};
+use demo::input::{self, InputEvent};
+use demo::keyboard::{self, Layout, Settings};
+use demo::status_bar;
+use demo::tui::{Button, CheckBox, Dialog, Focus, Label, ListBox, ProgressBar, Rect};
+use demo::tui::{Response, Widget};
+use demo::vga;
+use embedded_hal::prelude::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::sysctl;
+
+const LAYOUTS: [&str; 3] = ["US", "UK", "German"];
+const SAVE_BUTTONS: [&str; 2] = ["Save", "Cancel"];
+const OK_BUTTON: [&str; 1] = ["OK"];
+
+// Which widget is which, in `widgets!`
+const LAYOUT: usize = 0;
+const SOUNDS: usize = 1;
+const VOLUME: usize = 2;
+const SAVE: usize = 3;
+const DEFAULTS: usize = 4;
+
+/// What's on top of the screen.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Showing {
+    Screen,
+    /// Asking whether to save
+    Confirm,
+    /// Saying how that went
+    Result,
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::Pwm0);
+    board.enable(sysctl::Domain::Timer5);
+    board.enable(sysctl::Domain::Eeprom);
+
+    vga::init(p.TIMER0, p.SSI2);
+    audio::init(p.PWM0, p.TIMER5, &board.clocks);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+    // Below the VGA timers, so the picture stays put
+    unsafe {
+        nvic.set_priority(tm4c123x_hal::Interrupt::GPIOD, 0x40);
+        nvic.set_priority(tm4c123x_hal::Interrupt::TIMER5A, 0x40);
+    }
+    nvic.enable(tm4c123x_hal::Interrupt::GPIOD);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER5A);
+
+    let mut eeprom = Eeprom::new(p.EEPROM).ok();
+    let settings = eeprom
+        .as_mut()
+        .map(|e| Settings::load(e))
+        .unwrap_or(Settings::DEFAULT);
+    let mut keyboard = keyboard::init(&board.clocks, settings);
+
+    let mut layout = ListBox::new(
+        Rect {
+            col: 2,
+            row: 2,
+            width: 20,
+            height: 5,
+        },
+        Some("Keyboard layout"),
+        &LAYOUTS,
+    );
+    layout.select(Layout::ALL.iter().position(|&l| l == settings.layout).unwrap_or(0));
+    let mut sounds = CheckBox {
+        col: 2,
+        row: 8,
+        label: "Interface sounds",
+        checked: audio::ui_sounds(),
+    };
+    let mut volume = ProgressBar {
+        col: 3,
+        row: 11,
+        width: 20,
+        value: u32::from(audio::volume()),
+        max: u32::from(audio::MAX_VOLUME),
+        step: 10,
+    };
+    let mut save = Button {
+        col: 2,
+        row: 14,
+        label: "Save",
+    };
+    let mut defaults = Button {
+        col: 10,
+        row: 14,
+        label: "Defaults",
+    };
+    let mut volume_label = Label {
+        col: 2,
+        row: 10,
+        width: 20,
+        text: "Volume",
+    };
+    let mut help = Label {
+        col: 2,
+        row: 16,
+        width: 46,
+        text: "Tab/Up/Down to move, Enter or Space to pick",
+    };
+
+    // Borrow all the widgets, in the order of the constants above
+    macro_rules! widgets {
+        () => {
+            [
+                &mut layout as &mut Widget,
+                &mut sounds,
+                &mut volume,
+                &mut save,
+                &mut defaults,
+                &mut volume_label,
+                &mut help,
+            ]
+        };
+    }
+
+    let fb = vga::framebuffer();
+    let mut focus = Focus::new();
+    let mut showing = Showing::Screen;
+    let mut dialog = Dialog::new("", "", &OK_BUTTON);
+    let mut redraw = true;
+    let mut keys = input::Decoder::new();
+
+    writeln!(board.tx, "Settings. Tab to move, Enter to pick.").unwrap();
+
+    loop {
+        if redraw {
+            redraw = false;
+            fb.clear(Colour::BLACK);
+            status_bar::draw(fb, "Settings", "");
+            focus.draw(fb, &mut widgets!());
+            if showing != Showing::Screen {
+                dialog.draw(fb, true);
+            }
+        }
+
+        if let Ok(byte) = board.rx.read() {
+            keys.input(byte);
+        }
+        input::keyboard(&mut keyboard);
+        let key = match input::next() {
+            Some(InputEvent::KeyDown(event)) => event.key,
+            _ => continue,
+        };
+        audio::ui(UiSound::Click);
+
+        if showing != Showing::Screen {
+            match dialog.key(key) {
+                Response::Activated if showing == Showing::Confirm && dialog.selected() == 0 => {
+                    let mut settings = keyboard.settings();
+                    settings.layout = Layout::ALL[layout.selected()];
+                    let result = match eeprom.as_mut().map(|e| settings.save(e)) {
+                        Some(Ok(())) => "Saved.",
+                        Some(Err(_)) => "The EEPROM didn't take it.",
+                        None => "There's no EEPROM.",
+                    };
+                    showing = Showing::Result;
+                    dialog = Dialog::new("Save", result, &OK_BUTTON);
+                    redraw = true;
+                }
+                Response::Activated | Response::Cancelled => {
+                    // Put back what was under it
+                    showing = Showing::Screen;
+                    redraw = true;
+                }
+                _ => dialog.draw(fb, true),
+            }
+            continue;
+        }
+
+        let (index, response) = {
+            let mut widgets = widgets!();
+            let result = focus.key(&mut widgets, key);
+            focus.draw(fb, &mut widgets);
+            result
+        };
+        match (index, response) {
+            (LAYOUT, Response::Changed) => {
+                let mut settings = keyboard.settings();
+                settings.layout = Layout::ALL[layout.selected()];
+                keyboard.set_settings(settings);
+            }
+            (SOUNDS, Response::Changed) => audio::set_ui_sounds(sounds.checked),
+            (VOLUME, Response::Changed) => audio::set_volume(volume.value as u8),
+            (SAVE, Response::Activated) => {
+                showing = Showing::Confirm;
+                dialog = Dialog::new(
+                    "Save",
+                    "Keep this keyboard layout\nfor next time?",
+                    &SAVE_BUTTONS,
+                );
+                dialog.draw(fb, true);
+            }
+            (DEFAULTS, Response::Activated) => {
+                layout.select(0);
+                sounds.checked = false;
+                volume.value = u32::from(audio::MAX_VOLUME);
+                audio::set_ui_sounds(false);
+                audio::set_volume(audio::MAX_VOLUME);
+                keyboard.set_settings(Settings::DEFAULT);
+                redraw = true;
+            }
+            (_, Response::Ignored) => audio::ui(UiSound::Error),
+            _ => {}
+        }
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+interrupt!(GPIOD, keyboard::gpiod_isr);
+interrupt!(TIMER5A, audio::timer5a_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,188 @@
+//! Key clicks, menu blips and error buzzes from `demo::audio`.
+//!
+//! A piezo sounder (or a small speaker and a transistor) goes on PB4
+//! (M0PWM2), as in the `basic` example. The console is on UART0 at 115200
+//! bps, and every key clicks. The VGA output is the same as `hello_vga`
+//! (HSYNC on PB6, VSYNC on PC4 and green on PB7), and shows the settings.
+//! Commands:
+//!
+//! * `sounds <on|off>` - turn the interface noises on or off
+//! * `volume <0..100>` - set the volume of everything
+//! * `beep <hz> <ms>` - play a tone
+//! * `settings` - a sub-menu (with `sounds` and `volume` again), to hear
+//!   the blip when you go into it
+//!
+//! The menu `Runner` prints its own message for a command it doesn't know,
+//! without telling us, so that doesn't buzz - but bad arguments do.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use demo::audio::{self, UiSound};
+use demo::board::Board;
+use demo::console::Console;
+use demo::graphics::Colour;
+use demo::status_bar;
+use demo::text::Buffer;
+use demo::vga;
+use embedded_hal::prelude::*;
+use menu::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::sysctl;
+
+const SOUNDS_ITEM: Item = Item {
+    item_type: ItemType::Callback(sounds_callback),
+    command: "sounds",
+    help: Some("<on|off> - turn the interface noises on or off"),
+};
+
+const VOLUME_ITEM: Item = Item {
+    item_type: ItemType::Callback(volume_callback),
+    command: "volume",
+    help: Some("<0..100> - set the volume"),
+};
+
+const BEEP_ITEM: Item = Item {
+    item_type: ItemType::Callback(beep_callback),
+    command: "beep",
+    help: Some("<hz> <ms> - play a tone"),
+};
+
+const SETTINGS_ITEM: Item = Item {
+    item_type: ItemType::Menu(&SETTINGS_MENU),
+    command: "settings",
+    help: Some("enter the settings menu"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[&SOUNDS_ITEM, &VOLUME_ITEM, &BEEP_ITEM, &SETTINGS_ITEM],
+    entry: None,
+    exit: None,
+};
+
+const SETTINGS_MENU: Menu = Menu {
+    label: "settings",
+    items: &[&SOUNDS_ITEM, &VOLUME_ITEM],
+    entry: Some(menu_blip),
+    exit: Some(menu_blip),
+};
+
+/// Argument `n` after the command.
+fn argument<T>(input: &str, n: usize) -> Option<T>
+where
+    T: core::str::FromStr,
+{
+    input.split_whitespace().nth(n).and_then(|s| s.parse::<T>().ok())
+}
+
+/// Show the settings on the status bar.
+fn show() {
+    let mut text = Buffer::new();
+    let sounds = if audio::ui_sounds() { "on" } else { "off" };
+    write!(text, "Sounds {}, volume {}", sounds, audio::volume()).unwrap();
+    status_bar::draw(vga::framebuffer(), "UI sounds", text.as_str());
+}
+
+/// In or out of a sub-menu.
+fn menu_blip(_menu: &Menu) {
+    audio::ui(UiSound::Enter);
+}
+
+fn sounds_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match input.split_whitespace().nth(1) {
+        Some("on") => audio::set_ui_sounds(true),
+        Some("off") => audio::set_ui_sounds(false),
+        _ => {
+            audio::ui(UiSound::Error);
+            writeln!(Console, "Usage: sounds <on|off>").unwrap();
+        }
+    }
+    show();
+}
+
+fn volume_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match argument::<u8>(input, 1) {
+        Some(volume) if volume <= audio::MAX_VOLUME => audio::set_volume(volume),
+        _ => {
+            audio::ui(UiSound::Error);
+            writeln!(Console, "Usage: volume <0..{}>", audio::MAX_VOLUME).unwrap();
+        }
+    }
+    show();
+}
+
+fn beep_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match (argument::<u32>(input, 1), argument::<u32>(input, 2)) {
+        (Some(hz), Some(ms)) => audio::beep(hz, ms),
+        _ => {
+            audio::ui(UiSound::Error);
+            writeln!(Console, "Usage: beep <hz> <ms>").unwrap();
+        }
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::Pwm0);
+    board.enable(sysctl::Domain::Timer5);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+
+    audio::init(p.PWM0, p.TIMER5, &board.clocks);
+    audio::set_ui_sounds(true);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER5A);
+
+    vga::framebuffer().clear(Colour::BLACK);
+    show();
+
+    writeln!(board.tx, "Type away. 'help' for help.").unwrap();
+
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+    loop {
+        if let Ok(ch) = board.rx.read() {
+            audio::key(ch);
+            r.input_byte(ch);
+        }
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+interrupt!(TIMER5A, audio::timer5a_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,167 @@
+//! The VGA console, with its tasks watched by the watchdog.
+//!
+//! The VGA output is the same as `hello_vga` (HSYNC on PB6, VSYNC on PC4
+//! and green on PB7) and the console is on UART0 at 115200 bps. The main
+//! loop runs two tasks, each with a `demo::heartbeat`: `console` handles
+//! the UART, and `blink` flashes the green LED.
+//!
+//! Commands:
+//!
+//! * `wedge console` - go into an infinite loop in the command handler
+//! * `wedge blink` - stop the blink task, but leave the console going
+//!
+//! Either way, the watchdog interrupt notices within a couple of seconds,
+//! says so in the status bar and names the wedged task on the screen. The
+//! screen keeps going - the VGA interrupts outrank the watchdog - until the
+//! watchdog resets the chip a second later. After the reset the screen says
+//! it was the watchdog.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+extern crate vga_framebuffer as fb;
+
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
+use demo::board::Board;
+use demo::console::Console;
+use demo::heartbeat;
+use demo::reset;
+use demo::status_bar;
+use demo::vga;
+use embedded_hal::prelude::*;
+use menu::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::delay::Delay;
+use tm4c123x_hal::sysctl;
+
+/// The watchdog period.
+const WATCHDOG_MS: u32 = 1000;
+
+/// How often the green LED changes.
+const BLINK_MS: u32 = 250;
+
+/// Set by `wedge blink`.
+static BLINK_WEDGED: AtomicBool = AtomicBool::new(false);
+
+const WEDGE_ITEM: Item = Item {
+    item_type: ItemType::Callback(wedge_callback),
+    command: "wedge",
+    help: Some("<console|blink> - stop a task checking in"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[&WEDGE_ITEM],
+    entry: None,
+    exit: None,
+};
+
+fn wedge_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match input.split_whitespace().nth(1) {
+        Some("console") => {
+            writeln!(Console, "Wedging the console...").unwrap();
+            loop {}
+        }
+        Some("blink") => {
+            writeln!(Console, "Wedging the blinker...").unwrap();
+            BLINK_WEDGED.store(true, Ordering::Relaxed);
+        }
+        _ => writeln!(Console, "Usage: wedge <console|blink>").unwrap(),
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let cause = reset::cause();
+
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::Watchdog0);
+
+    vga::init(p.TIMER0, p.SSI2);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+
+    // Leave the top row for the status bar
+    let mut c = fb::TextFrameBuffer::new(vga::framebuffer());
+    c.clear();
+    writeln!(c, "\nWatchdog demo").unwrap();
+    writeln!(c, "Reset by: {}", cause).unwrap();
+    status_bar::draw(vga::framebuffer(), "Monotron", "All tasks OK");
+
+    let console = heartbeat::register("console");
+    let blink = heartbeat::register("blink");
+    heartbeat::start(p.WATCHDOG0, &board.clocks, WATCHDOG_MS);
+    // Below the VGA timers, so the screen keeps going
+    unsafe { nvic.set_priority(tm4c123x_hal::Interrupt::WATCHDOG0, 0xE0) };
+    nvic.enable(tm4c123x_hal::Interrupt::WATCHDOG0);
+
+    writeln!(board.tx, "Watchdog demo. Try 'wedge blink'.").unwrap();
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+
+    let mut blink_ms = 0;
+    loop {
+        while let Ok(ch) = board.rx.read() {
+            r.input_byte(ch);
+        }
+        console.beat();
+
+        if !BLINK_WEDGED.load(Ordering::Relaxed) {
+            blink_ms += 1;
+            if blink_ms == BLINK_MS {
+                board.led_green.toggle();
+                blink_ms = 0;
+            }
+            blink.beat();
+        }
+
+        d.delay_ms(1u32);
+    }
+}
+
+interrupt!(WATCHDOG0, watchdog_isr);
+
+fn watchdog_isr() {
+    if let Err(missed) = heartbeat::check() {
+        writeln!(Console, "Wedged: {} - resetting", missed).unwrap();
+        status_bar::draw(vga::framebuffer(), "Wedged - resetting", "");
+        let mut c = fb::TextFrameBuffer::new(vga::framebuffer());
+        writeln!(c, "\nWedged: {}", missed).unwrap();
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,409 @@
+//! Plays 8-bit mono WAV files through the R-2R ladder, with the VGA screen
+//! running.
+//!
+//! Type `play <file.wav>` on UART0 (115200 bps) to play a file from the SD
+//! card. The card is on SSI0 - SCK is PA2 (SSI0Clk), MISO is PA4 (SSI0Rx),
+//! MOSI is PA5 (SSI0Tx) and chip select is PA3 - as in `sd_files`, and
+//! paths start from the root directory. `mount` starts the card again
+//! after you've swapped it.
+//!
+//! Or type just `play` and then send the file raw - with picocom, that's
+//! Ctrl-A Ctrl-S and `ascii-xfr -sn`, or just `cat x.wav > /dev/ttyACM0`
+//! from another terminal. The console only manages about 11,000 bytes a
+//! second, so stick to 11,025 Hz or less (`sox in.wav -r 8000 -c 1 -b 8
+//! out.wav` will make one). Anything faster plays, but with gaps. The card
+//! keeps up with anything.
+//!
+//! See `demo::dac` for the wiring. The VGA output is the same as
+//! `hello_vga` (HSYNC on PB6, VSYNC on PC4 and green on PB7), and shows
+//! how the transfer is going.
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+#[macro_use(interrupt)]
+extern crate tm4c123x;
+extern crate tm4c123x_hal;
+
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
+use demo::board::Board;
+use demo::config;
+use demo::dac;
+use demo::fat::{self, Dir, Volume};
+use demo::graphics::Colour;
+use demo::sdcard::SdCard;
+use demo::spi::Spi;
+use demo::status_bar;
+use demo::text::Buffer;
+use demo::udma;
+use demo::vga;
+use demo::wav;
+use embedded_hal::prelude::*;
+use embedded_hal::spi::MODE_0;
+use menu::*;
+use rt::ExceptionFrame;
+use tm4c123x_hal::delay::Delay;
+use tm4c123x_hal::gpio::gpioa::PA3;
+use tm4c123x_hal::gpio::{Output, PushPull};
+use tm4c123x_hal::sysctl::{self, Clocks};
+use tm4c123x_hal::time::U32Ext;
+
+/// What the DAC runs at between files.
+const IDLE_RATE_HZ: u32 = 8000;
+
+/// The fastest the console can keep up with.
+const MAX_RATE_HZ: u32 = 11_025;
+
+/// How much of the file we'll look through for the samples.
+const HEADER_LEN: usize = 512;
+
+/// Give up when the sender has gone quiet for this many underruns.
+const QUIET_UNDERRUNS: usize = 16;
+
+/// Cards start at 400 kHz or less.
+const INIT_HZ: u32 = 400_000;
+
+/// And then most can go this fast.
+const FAST_HZ: u32 = 20_000_000;
+
+/// How often we update the screen while playing from the card.
+const SHOW_BLOCKS: u32 = 8;
+
+type Card = SdCard<Spi<tm4c123x::SSI0>, PA3<Output<PushPull>>>;
+
+const PLAY_ITEM: Item = Item {
+    item_type: ItemType::Callback(play_callback),
+    command: "play",
+    help: Some("[file.wav] - play a WAV file from the card, or the console"),
+};
+
+const MOUNT_ITEM: Item = Item {
+    item_type: ItemType::Callback(mount_callback),
+    command: "mount",
+    help: Some("start the card again"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[&PLAY_ITEM, &MOUNT_ITEM],
+    entry: None,
+    exit: None,
+};
+
+/// Set by `play` and `mount`, for the main loop to pick up.
+static PLAY: AtomicBool = AtomicBool::new(false);
+static MOUNT: AtomicBool = AtomicBool::new(false);
+
+/// The file `play` asked for, if it gave one.
+static mut FILE_NAME: Option<Buffer<[u8; 64]>> = None;
+
+fn play_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let name = input.split_whitespace().nth(1).and_then(|name| {
+        let mut buffer = Buffer::from_storage([0u8; 64]);
+        write!(buffer, "{}", name).ok().map(|_| buffer)
+    });
+    unsafe { FILE_NAME = name };
+    PLAY.store(true, Ordering::Relaxed);
+}
+
+fn mount_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    MOUNT.store(true, Ordering::Relaxed);
+}
+
+/// Show how the transfer is going.
+fn show(state: &str, received: u32, total: u32) {
+    let mut text = Buffer::new();
+    write!(
+        text,
+        "{}/{} underruns {}",
+        received,
+        total,
+        wav::underruns()
+    ).unwrap();
+    status_bar::draw(vga::framebuffer(), state, text.as_str());
+}
+
+/// Say what we found, and get the DAC going at its rate.
+fn start<T>(tx: &mut T, found: &wav::Header, clocks: &Clocks)
+where
+    T: Write,
+{
+    writeln!(tx, "{} Hz, {} bytes", found.sample_rate, found.data_len).unwrap();
+    wav::reset();
+    dac::set_sample_rate(found.sample_rate.max(1), clocks);
+}
+
+/// Let the file finish, then say how it went.
+fn finish<T>(tx: &mut T, played: u32, total: u32, clocks: &Clocks)
+where
+    T: Write,
+{
+    // Count the underruns before the idle ones start
+    while wav::queued() > 0 {}
+    let state = if played < total { "Gave up" } else { "Done" };
+    show(state, played, total);
+    writeln!(
+        tx,
+        "{}: {} of {} bytes, {} underruns",
+        state,
+        played,
+        total,
+        wav::underruns()
+    ).unwrap();
+    dac::set_sample_rate(IDLE_RATE_HZ, clocks);
+}
+
+/// Play `name` from the card.
+fn play_file<T>(volume: &mut Volume<Card>, name: &str, tx: &mut T, clocks: &Clocks)
+where
+    T: Write,
+{
+    let mut file = match volume.open(Dir::root(), name) {
+        Ok(file) => file,
+        Err(e) => {
+            writeln!(tx, "{}: {}", name, e).unwrap();
+            return;
+        }
+    };
+
+    // Find the samples
+    let mut header = [0u8; HEADER_LEN];
+    let found = match volume.read(&mut file, &mut header) {
+        Ok(len) => match wav::parse_header(&header[0..len]) {
+            Err(wav::Error::Incomplete) => Err(wav::Error::NoFormat),
+            result => result,
+        },
+        Err(e) => {
+            writeln!(tx, "{}: {}", name, e).unwrap();
+            return;
+        }
+    };
+    let found = match found {
+        Ok(found) => found,
+        Err(e) => {
+            writeln!(tx, "Can't play that: {:?}", e).unwrap();
+            show("Error", 0, 0);
+            return;
+        }
+    };
+    start(tx, &found, clocks);
+
+    // The file can stop short of what the header says
+    let total = found
+        .data_len
+        .min(file.size().saturating_sub(found.data_start as u32));
+    file.seek(found.data_start as u32);
+    let mut played = 0;
+    let mut blocks = 0;
+    let mut block = [0u8; fat::BLOCK_LEN];
+    while played < total {
+        let want = ((total - played) as usize).min(fat::BLOCK_LEN);
+        let count = match volume.read(&mut file, &mut block[0..want]) {
+            Ok(0) => break,
+            Ok(count) => count,
+            Err(e) => {
+                writeln!(tx, "{}", e).unwrap();
+                break;
+            }
+        };
+        // Wait for room, rather than lose it
+        let mut pushed = 0;
+        while pushed < count {
+            pushed += wav::push(&block[pushed..count]);
+        }
+        played += count as u32;
+        blocks += 1;
+        if blocks % SHOW_BLOCKS == 0 {
+            show("Playing", played, total);
+        }
+    }
+    finish(tx, played, total, clocks);
+}
+
+/// Receive a file on `rx` and play it as it arrives.
+fn play_console<R, T>(rx: &mut R, tx: &mut T, clocks: &Clocks)
+where
+    R: embedded_hal::serial::Read<u8>,
+    T: Write,
+{
+    writeln!(tx, "Send the file now").unwrap();
+    show("Waiting", 0, 0);
+
+    // Find the samples
+    let mut header = [0u8; HEADER_LEN];
+    let mut len = 0;
+    let found = loop {
+        if let Ok(byte) = rx.read() {
+            header[len] = byte;
+            len += 1;
+            match wav::parse_header(&header[0..len]) {
+                Err(wav::Error::Incomplete) if len < HEADER_LEN => {}
+                Err(wav::Error::Incomplete) => break Err(wav::Error::NoFormat),
+                result => break result,
+            }
+        }
+    };
+    let found = match found {
+        Ok(found) => found,
+        Err(e) => {
+            writeln!(tx, "Can't play that: {:?}", e).unwrap();
+            show("Error", 0, 0);
+            return;
+        }
+    };
+    if found.sample_rate > MAX_RATE_HZ {
+        writeln!(tx, "That's faster than the console - expect gaps").unwrap();
+    }
+    start(tx, &found, clocks);
+
+    // The header might have the first few samples in it
+    let mut received = (len - found.data_start).min(found.data_len as usize) as u32;
+    wav::push(&header[found.data_start..found.data_start + received as usize]);
+
+    let mut quiet_since = wav::underruns();
+    while received < found.data_len {
+        match rx.read() {
+            Ok(byte) => {
+                // Wait for room, rather than lose it
+                while wav::push(&[byte]) == 0 {}
+                received += 1;
+                quiet_since = wav::underruns();
+                if received % 1024 == 0 {
+                    show("Playing", received, found.data_len);
+                }
+            }
+            Err(_) => {
+                if wav::underruns() - quiet_since > QUIET_UNDERRUNS {
+                    break;
+                }
+            }
+        }
+    }
+    finish(tx, received, found.data_len, clocks);
+}
+
+/// Wake the card up and find its filesystem. If there isn't one, you get
+/// the card back to try again later.
+fn mount<T>(
+    mut card: Card,
+    delay: &mut Delay,
+    tx: &mut T,
+    clocks: &Clocks,
+) -> Result<Volume<Card>, Card>
+where
+    T: Write,
+{
+    card.spi().set_frequency(INIT_HZ.hz(), clocks);
+    if let Err(e) = card.init(delay) {
+        writeln!(tx, "No card ({:?})", e).unwrap();
+        return Err(card);
+    }
+    card.spi().set_frequency(FAST_HZ.hz(), clocks);
+    match Volume::mount(card) {
+        Ok(volume) => {
+            writeln!(tx, "{} KiB of {}", volume.size_kib(), volume.fat_name()).unwrap();
+            Ok(volume)
+        }
+        Err((card, e)) => {
+            writeln!(tx, "{}", e).unwrap();
+            Err(card)
+        }
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = tm4c123x_hal::Peripherals::take().unwrap();
+    let cp = tm4c123x_hal::CorePeripherals::take().unwrap();
+
+    let mut board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+    board.enable(sysctl::Domain::Timer0);
+    board.enable(sysctl::Domain::Ssi2);
+    board.enable(sysctl::Domain::Timer3);
+    board.enable(sysctl::Domain::MicroDma);
+    board.enable(sysctl::Domain::Ssi0);
+
+    vga::init(p.TIMER0, p.SSI2);
+    udma::init();
+    dac::start(p.TIMER3, IDLE_RATE_HZ, &board.clocks, wav::fill);
+
+    let mut nvic = cp.NVIC;
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0A);
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER0B);
+    // Below the VGA timers, so the picture stays put
+    unsafe { nvic.set_priority(tm4c123x_hal::Interrupt::TIMER3A, 0x40) };
+    nvic.enable(tm4c123x_hal::Interrupt::TIMER3A);
+
+    vga::framebuffer().clear(Colour::BLACK);
+    status_bar::draw(vga::framebuffer(), "WAV player", "Type 'play'");
+
+    let clocks = board.clocks;
+    let mut rx = board.rx;
+    let mut tx = board.tx;
+
+    // SSI0Clk, SSI0Rx and SSI0Tx
+    config::SSI0.connect();
+    let spi = Spi::ssi0(p.SSI0, MODE_0, INIT_HZ.hz(), &clocks);
+    let card = SdCard::new(spi, board.porta.pa3.into_push_pull_output());
+    let mut delay = Delay::new(cp.SYST, &clocks);
+    let mut storage = mount(card, &mut delay, &mut tx, &clocks);
+
+    let mut buffer = [0u8; 64];
+    loop {
+        {
+            let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut tx);
+            while !PLAY.load(Ordering::Relaxed) && !MOUNT.load(Ordering::Relaxed) {
+                if let Ok(ch) = rx.read() {
+                    r.input_byte(ch);
+                }
+            }
+        }
+        if MOUNT.swap(false, Ordering::Relaxed) {
+            let card = match storage {
+                Ok(volume) => volume.free(),
+                Err(card) => card,
+            };
+            storage = mount(card, &mut delay, &mut tx, &clocks);
+        }
+        if PLAY.swap(false, Ordering::Relaxed) {
+            match unsafe { FILE_NAME.take() } {
+                Some(name) => {
+                    // Have another go, in case it's gone in since
+                    storage = match storage {
+                        Ok(volume) => Ok(volume),
+                        Err(card) => mount(card, &mut delay, &mut tx, &clocks),
+                    };
+                    if let Ok(ref mut volume) = storage {
+                        play_file(volume, name.as_str(), &mut tx, &clocks);
+                    }
+                }
+                None => play_console(&mut rx, &mut tx, &clocks),
+            }
+        }
+    }
+}
+
+interrupt!(TIMER0A, vga::timer0a_isr);
+interrupt!(TIMER0B, vga::timer0b_isr);
+interrupt!(TIMER3A, dac::timer3a_isr);
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
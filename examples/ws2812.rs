@@ -0,0 +1,203 @@
+//! Drives a string of WS2812 "NeoPixel" LEDs.
+//!
+//! LED data is PA5 (SSI0Tx). The console is on UART0 at 115200 bps.
+//!
+//! Commands:
+//!
+//! * `rainbow` - a rotating colour wheel across the string
+//! * `chase` - a single red LED running along the string
+//! * `led <n> <r> <g> <b>` - set one LED (and stop any animation)
+//! * `bright <0..255>` - set the overall brightness
+//! * `off` - turn everything off
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+
+use core::fmt::Write;
+use demo::config;
+use demo::console::Console;
+use demo::hal;
+use demo::udma;
+use demo::ws2812::{Rgb, Ws2812, WORDS_PER_LED};
+use embedded_hal::prelude::*;
+use hal::delay::Delay;
+use hal::sysctl;
+use menu::*;
+use rt::ExceptionFrame;
+
+const NUM_LEDS: usize = 30;
+
+/// How often we step the animation.
+const FRAME_MS: u32 = 20;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Rainbow,
+    Chase,
+    Manual,
+}
+
+static mut LED_BUFFER: [u16; NUM_LEDS * WORDS_PER_LED] = [0; NUM_LEDS * WORDS_PER_LED];
+
+static mut MODE: Mode = Mode::Rainbow;
+
+static mut BRIGHTNESS: u8 = 64;
+
+static mut MANUAL: [Rgb; NUM_LEDS] = [Rgb {
+    red: 0,
+    green: 0,
+    blue: 0,
+}; NUM_LEDS];
+
+const RAINBOW_ITEM: Item = Item {
+    item_type: ItemType::Callback(rainbow_callback),
+    command: "rainbow",
+    help: Some("rotating colour wheel"),
+};
+
+const CHASE_ITEM: Item = Item {
+    item_type: ItemType::Callback(chase_callback),
+    command: "chase",
+    help: Some("one LED chasing along the string"),
+};
+
+const LED_ITEM: Item = Item {
+    item_type: ItemType::Callback(led_callback),
+    command: "led",
+    help: Some("<n> <r> <g> <b> - set one LED"),
+};
+
+const BRIGHT_ITEM: Item = Item {
+    item_type: ItemType::Callback(bright_callback),
+    command: "bright",
+    help: Some("<0..255> - set brightness"),
+};
+
+const OFF_ITEM: Item = Item {
+    item_type: ItemType::Callback(off_callback),
+    command: "off",
+    help: Some("turn all the LEDs off"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[&RAINBOW_ITEM, &CHASE_ITEM, &LED_ITEM, &BRIGHT_ITEM, &OFF_ITEM],
+    entry: None,
+    exit: None,
+};
+
+fn rainbow_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    unsafe { MODE = Mode::Rainbow };
+}
+
+fn chase_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    unsafe { MODE = Mode::Chase };
+}
+
+fn led_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let mut args = input.split_whitespace().skip(1);
+    let index = args.next().and_then(|s| s.parse::<usize>().ok());
+    let red = args.next().and_then(|s| s.parse::<u8>().ok());
+    let green = args.next().and_then(|s| s.parse::<u8>().ok());
+    let blue = args.next().and_then(|s| s.parse::<u8>().ok());
+    match (index, red, green, blue) {
+        (Some(index), Some(red), Some(green), Some(blue)) if index < NUM_LEDS => unsafe {
+            if MODE != Mode::Manual {
+                MANUAL = [Rgb::default(); NUM_LEDS];
+                MODE = Mode::Manual;
+            }
+            MANUAL[index] = Rgb::new(red, green, blue);
+        },
+        _ => {
+            writeln!(Console, "Usage: led <0..{}> <r> <g> <b>", NUM_LEDS - 1).unwrap();
+        }
+    }
+}
+
+fn bright_callback(_menu: &Menu, _item: &Item, input: &str) {
+    match input.split_whitespace().nth(1).and_then(|s| s.parse::<u8>().ok()) {
+        Some(level) => unsafe { BRIGHTNESS = level },
+        None => writeln!(Console, "Usage: bright <0..255>").unwrap(),
+    }
+}
+
+fn off_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    unsafe {
+        MANUAL = [Rgb::default(); NUM_LEDS];
+        MODE = Mode::Manual;
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Ssi0);
+    board.enable(sysctl::Domain::MicroDma);
+
+    // SSI0Tx
+    config::SSI0.tx.into_af(config::SSI0.function);
+
+    udma::init();
+    let mut leds = Ws2812::new(p.SSI0, unsafe { &mut LED_BUFFER });
+    leds.show();
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+
+    writeln!(board.tx, "WS2812 demo - {} LEDs", NUM_LEDS).unwrap();
+
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+
+    let mut frame: u32 = 0;
+    loop {
+        while let Ok(ch) = board.rx.read() {
+            r.input_byte(ch);
+        }
+
+        d.delay_ms(FRAME_MS);
+        frame = frame.wrapping_add(1);
+
+        let (mode, brightness) = unsafe { (MODE, BRIGHTNESS) };
+        for i in 0..NUM_LEDS {
+            let colour = match mode {
+                Mode::Rainbow => {
+                    let pos = (i * 256 / NUM_LEDS) as u32 + frame;
+                    Rgb::wheel(pos as u8)
+                }
+                Mode::Chase => if i == (frame as usize / 2) % NUM_LEDS {
+                    Rgb::new(255, 0, 0)
+                } else {
+                    Rgb::default()
+                },
+                Mode::Manual => unsafe { MANUAL[i] },
+            };
+            leds.set(i, colour.scale(brightness));
+        }
+        leds.show();
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
@@ -0,0 +1,567 @@
+//! Transfers files over the console with XMODEM and YMODEM.
+//!
+//! Type `rx <filename>` on UART0 (115200 bps) and then start an XMODEM (or
+//! XMODEM-CRC, or XMODEM-1K) upload in your terminal emulator - with
+//! minicom it's Ctrl-A S, and with picocom, `sx` does the job. Or type `rb`
+//! and send as many files as you like with YMODEM (`sb`), names and all.
+//! `sb` sends everything back again.
+//!
+//! With an SD card on SSI0 - SCK is PA2 (SSI0Clk), MISO is PA4 (SSI0Rx),
+//! MOSI is PA5 (SSI0Tx) and chip select is PA3, as in `sd_files` - `rx`
+//! saves the file on the card, with its path from the root directory and
+//! the padding off the end. A file that doesn't all arrive is deleted.
+//! Without a card, and for `rb`, files go into a RAM staging buffer, one
+//! after the other, where something else could pick them up.
+//!
+//! Commands:
+//!
+//! * `rx <filename>` - receive a file with XMODEM
+//! * `mount` - start the card again, after swapping it
+//! * `rb` - receive a batch of files with YMODEM
+//! * `sb` - send all the staged files with YMODEM
+//! * `ls` - list the staged files
+//! * `dump [offset]` - hex dump 128 bytes of the staging buffer
+//! * `clear` - forget all the staged files
+
+#![no_std]
+#![no_main]
+
+extern crate cortex_m;
+#[macro_use(entry, exception)]
+extern crate cortex_m_rt as rt;
+#[macro_use(board)]
+extern crate demo;
+extern crate embedded_hal;
+extern crate menu;
+extern crate panic_halt;
+
+use core::fmt::Write;
+use demo::config;
+use demo::console::Console;
+use demo::fat::{Dir, File, Volume};
+use demo::hal;
+use demo::pac;
+use demo::sdcard::SdCard;
+use demo::spi::Spi;
+use demo::xmodem::{self, Sink, Xmodem};
+use embedded_hal::prelude::*;
+use embedded_hal::spi::MODE_0;
+use hal::delay::Delay;
+use hal::gpio::gpioa::PA3;
+use hal::gpio::{Output, PushPull};
+use hal::sysctl::{self, Clocks};
+use hal::time::U32Ext;
+use menu::*;
+use rt::ExceptionFrame;
+
+/// Half our RAM.
+const STAGING_LEN: usize = 16 * 1024;
+
+const MAX_FILES: usize = 16;
+
+const MAX_NAME: usize = 32;
+
+/// How much `dump` shows.
+const DUMP_LEN: usize = 128;
+
+/// Cards start at 400 kHz or less.
+const INIT_HZ: u32 = 400_000;
+
+/// And then most can go this fast.
+const FAST_HZ: u32 = 20_000_000;
+
+type Card = SdCard<Spi<pac::SSI0>, PA3<Output<PushPull>>>;
+
+/// A file in the staging buffer.
+#[derive(Clone, Copy)]
+struct Entry {
+    name: [u8; MAX_NAME],
+    name_len: usize,
+    start: usize,
+    len: usize,
+}
+
+const EMPTY_ENTRY: Entry = Entry {
+    name: [0; MAX_NAME],
+    name_len: 0,
+    start: 0,
+    len: 0,
+};
+
+impl Entry {
+    fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[0..self.name_len]).unwrap_or("?")
+    }
+}
+
+/// Files, packed one after the other. The one being received is
+/// `entries[count]`.
+struct Staging {
+    data: [u8; STAGING_LEN],
+    used: usize,
+    entries: [Entry; MAX_FILES],
+    count: usize,
+    receiving: bool,
+}
+
+impl Staging {
+    /// Forget a file that didn't finish arriving.
+    fn abandon(&mut self) {
+        if self.receiving {
+            self.used = self.entries[self.count].start;
+            self.receiving = false;
+        }
+    }
+
+    /// Remove `SUB`s from the end of the file being received.
+    fn trim_padding(&mut self) {
+        let entry = &mut self.entries[self.count];
+        while entry.len > 0 && self.data[entry.start + entry.len - 1] == xmodem::SUB {
+            entry.len -= 1;
+        }
+        self.used = entry.start + entry.len;
+    }
+}
+
+impl xmodem::Sink for Staging {
+    fn open(&mut self, name: &str, size: Option<usize>) -> bool {
+        let space = STAGING_LEN - self.used;
+        if self.count == MAX_FILES || size.map_or(false, |size| size > space) {
+            return false;
+        }
+        // Long names get cut short
+        let name_len = name.len().min(MAX_NAME);
+        let entry = &mut self.entries[self.count];
+        entry.name[0..name_len].copy_from_slice(&name.as_bytes()[0..name_len]);
+        entry.name_len = name_len;
+        entry.start = self.used;
+        entry.len = 0;
+        self.receiving = true;
+        true
+    }
+
+    fn write(&mut self, data: &[u8]) -> bool {
+        let end = self.used + data.len();
+        if end > STAGING_LEN {
+            return false;
+        }
+        self.data[self.used..end].copy_from_slice(data);
+        self.used = end;
+        self.entries[self.count].len += data.len();
+        true
+    }
+
+    fn close(&mut self) {
+        self.count += 1;
+        self.receiving = false;
+    }
+}
+
+/// A file on the card that `rx` is writing.
+struct CardFile<'a> {
+    volume: &'a mut Volume<Card>,
+    file: File,
+    /// The latest block, held back until we know if it's the last one (and
+    /// so padded)
+    last: [u8; xmodem::LONG_BLOCK_LEN],
+    last_len: usize,
+    /// What went wrong, to say once the transfer's over
+    error: Option<&'static str>,
+}
+
+impl<'a> CardFile<'a> {
+    fn new(volume: &'a mut Volume<Card>, file: File) -> CardFile<'a> {
+        CardFile {
+            volume,
+            file,
+            last: [0; xmodem::LONG_BLOCK_LEN],
+            last_len: 0,
+            error: None,
+        }
+    }
+
+    /// Write out the block before this one, and keep this one.
+    fn write(&mut self, block: &[u8]) -> bool {
+        if let Err(e) = self.volume.write(&mut self.file, &self.last[0..self.last_len]) {
+            self.error = Some(e.message());
+            return false;
+        }
+        self.last[0..block.len()].copy_from_slice(block);
+        self.last_len = block.len();
+        true
+    }
+
+    /// Write the last block without its `SUB`s, if everything arrived, or
+    /// delete `name` if it didn't.
+    fn finish(mut self, name: &str, received: bool) -> Result<(), &'static str> {
+        if received && self.error.is_none() {
+            let mut len = self.last_len;
+            while len > 0 && self.last[len - 1] == xmodem::SUB {
+                len -= 1;
+            }
+            let result = match self.volume.write(&mut self.file, &self.last[0..len]) {
+                Ok(()) => self.volume.sync(&mut self.file),
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(()) => return Ok(()),
+                Err(e) => self.error = Some(e.message()),
+            }
+        }
+        // Best effort - the card might be why we're here
+        let _ = self.volume.delete(Dir::root(), name);
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+static mut STAGING: Staging = Staging {
+    data: [0; STAGING_LEN],
+    used: 0,
+    entries: [EMPTY_ENTRY; MAX_FILES],
+    count: 0,
+    receiving: false,
+};
+
+/// A transfer for the main loop to do, as it has the UART.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Transfer {
+    Xmodem,
+    YmodemReceive,
+    YmodemSend,
+}
+
+static mut TRANSFER: Option<Transfer> = None;
+
+/// The card, while it hasn't got a filesystem we can use.
+static mut CARD: Option<Card> = None;
+
+/// The card, once it's mounted.
+static mut VOLUME: Option<Volume<Card>> = None;
+
+/// `mount` asks the main loop, which has the delay and the clocks.
+static mut MOUNT_NOW: bool = false;
+
+/// The name `rx` was given.
+static mut NAME: [u8; MAX_NAME] = [0; MAX_NAME];
+static mut NAME_LEN: usize = 0;
+
+const RX_ITEM: Item = Item {
+    item_type: ItemType::Callback(rx_callback),
+    command: "rx",
+    help: Some("<filename> - receive a file with XMODEM"),
+};
+
+const RB_ITEM: Item = Item {
+    item_type: ItemType::Callback(rb_callback),
+    command: "rb",
+    help: Some("receive files with YMODEM"),
+};
+
+const SB_ITEM: Item = Item {
+    item_type: ItemType::Callback(sb_callback),
+    command: "sb",
+    help: Some("send the staged files with YMODEM"),
+};
+
+const LS_ITEM: Item = Item {
+    item_type: ItemType::Callback(ls_callback),
+    command: "ls",
+    help: Some("list the staged files"),
+};
+
+const DUMP_ITEM: Item = Item {
+    item_type: ItemType::Callback(dump_callback),
+    command: "dump",
+    help: Some("[offset] - hex dump the staging buffer"),
+};
+
+const CLEAR_ITEM: Item = Item {
+    item_type: ItemType::Callback(clear_callback),
+    command: "clear",
+    help: Some("forget the staged files"),
+};
+
+const MOUNT_ITEM: Item = Item {
+    item_type: ItemType::Callback(mount_callback),
+    command: "mount",
+    help: Some("start the card again"),
+};
+
+const ROOT_MENU: Menu = Menu {
+    label: "root",
+    items: &[
+        &RX_ITEM,
+        &RB_ITEM,
+        &SB_ITEM,
+        &LS_ITEM,
+        &DUMP_ITEM,
+        &CLEAR_ITEM,
+        &MOUNT_ITEM,
+    ],
+    entry: None,
+    exit: None,
+};
+
+fn staging() -> &'static mut Staging {
+    unsafe { &mut STAGING }
+}
+
+fn rx_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let filename = match input.split_whitespace().nth(1) {
+        Some(filename) if filename.len() <= MAX_NAME => filename,
+        _ => {
+            writeln!(Console, "Usage: rx <filename> (up to {} chars)", MAX_NAME).unwrap();
+            return;
+        }
+    };
+    unsafe {
+        NAME[0..filename.len()].copy_from_slice(filename.as_bytes());
+        NAME_LEN = filename.len();
+        TRANSFER = Some(Transfer::Xmodem);
+    }
+    writeln!(
+        Console,
+        "Start the XMODEM upload now (Ctrl-X twice to give up)"
+    ).unwrap();
+}
+
+fn rb_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    unsafe { TRANSFER = Some(Transfer::YmodemReceive) };
+    writeln!(
+        Console,
+        "Start the YMODEM upload now (Ctrl-X twice to give up)"
+    ).unwrap();
+}
+
+fn sb_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    if staging().count == 0 {
+        writeln!(Console, "Nothing to send").unwrap();
+        return;
+    }
+    unsafe { TRANSFER = Some(Transfer::YmodemSend) };
+    writeln!(Console, "Start the YMODEM download now").unwrap();
+}
+
+fn ls_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    let staging = staging();
+    for entry in &staging.entries[0..staging.count] {
+        writeln!(
+            Console,
+            "{:<32} {:>6} bytes at {:06x}",
+            entry.name(),
+            entry.len,
+            entry.start
+        ).unwrap();
+    }
+    writeln!(
+        Console,
+        "{} files, {} bytes free",
+        staging.count,
+        STAGING_LEN - staging.used
+    ).unwrap();
+}
+
+fn dump_callback(_menu: &Menu, _item: &Item, input: &str) {
+    let offset = match input.split_whitespace().nth(1) {
+        Some(arg) => match arg.parse::<usize>() {
+            Ok(offset) => offset,
+            Err(_) => {
+                writeln!(Console, "Usage: dump [offset]").unwrap();
+                return;
+            }
+        },
+        None => 0,
+    };
+    let staging = staging();
+    let data = &staging.data[0..staging.used];
+    if offset >= data.len() {
+        writeln!(Console, "That's past the end ({} bytes)", data.len()).unwrap();
+        return;
+    }
+    let end = (offset + DUMP_LEN).min(data.len());
+    for (row, chunk) in data[offset..end].chunks(16).enumerate() {
+        write!(Console, "{:06x}:", offset + (row * 16)).unwrap();
+        for byte in chunk {
+            write!(Console, " {:02x}", byte).unwrap();
+        }
+        for _ in chunk.len()..16 {
+            write!(Console, "   ").unwrap();
+        }
+        write!(Console, "  ").unwrap();
+        for &byte in chunk {
+            let ch = if byte >= 0x20 && byte < 0x7F {
+                byte as char
+            } else {
+                '.'
+            };
+            write!(Console, "{}", ch).unwrap();
+        }
+        writeln!(Console).unwrap();
+    }
+}
+
+fn clear_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    let staging = staging();
+    staging.used = 0;
+    staging.count = 0;
+}
+
+fn mount_callback(_menu: &Menu, _item: &Item, _input: &str) {
+    unsafe { MOUNT_NOW = true };
+}
+
+/// Wake the card up and find its filesystem. Either way, it ends up in
+/// `VOLUME` or `CARD`.
+fn mount(mut card: Card, delay: &mut Delay, clocks: &Clocks) {
+    card.spi().set_frequency(INIT_HZ.hz(), clocks);
+    if let Err(e) = card.init(delay) {
+        writeln!(Console, "No card ({:?}) - files stay in RAM", e).unwrap();
+        unsafe { CARD = Some(card) };
+        return;
+    }
+    card.spi().set_frequency(FAST_HZ.hz(), clocks);
+    match Volume::mount(card) {
+        Ok(volume) => {
+            writeln!(
+                Console,
+                "`rx` saves to the card - {} KiB of {}",
+                volume.size_kib(),
+                volume.fat_name()
+            ).unwrap();
+            unsafe { VOLUME = Some(volume) };
+        }
+        Err((card, e)) => {
+            writeln!(Console, "{} - files stay in RAM", e).unwrap();
+            unsafe { CARD = Some(card) };
+        }
+    }
+}
+
+entry!(main);
+
+fn main() -> ! {
+    let p = hal::Peripherals::take().unwrap();
+    let cp = hal::CorePeripherals::take().unwrap();
+
+    let mut board = board!(p);
+    board.enable(sysctl::Domain::Ssi0);
+
+    let mut d = Delay::new(cp.SYST, &board.clocks);
+
+    // SSI0Clk, SSI0Rx and SSI0Tx
+    config::SSI0.connect();
+    let porta = board.porta;
+    let spi = Spi::ssi0(p.SSI0, MODE_0, INIT_HZ.hz(), &board.clocks);
+    let card = SdCard::new(spi, porta.pa3.into_push_pull_output());
+
+    writeln!(board.tx, "XMODEM/YMODEM demo - {} byte staging buffer", STAGING_LEN).unwrap();
+    mount(card, &mut d, &board.clocks);
+
+    let mut buffer = [0u8; 64];
+    let mut r = Runner::new(&ROOT_MENU, &mut buffer, &mut board.tx);
+
+    loop {
+        while let Ok(ch) = board.rx.read() {
+            r.input_byte(ch);
+        }
+
+        if unsafe { core::mem::replace(&mut MOUNT_NOW, false) } {
+            let card = unsafe { VOLUME.take().map(Volume::free).or_else(|| CARD.take()) };
+            if let Some(card) = card {
+                mount(card, &mut d, &board.clocks);
+            }
+        }
+
+        let transfer = match unsafe { TRANSFER.take() } {
+            Some(transfer) => transfer,
+            None => continue,
+        };
+        let staging = staging();
+        let mut card_error = None;
+        let result = {
+            let mut modem = Xmodem::new(r.output, &mut board.rx, &mut d);
+            match transfer {
+                Transfer::Xmodem => {
+                    let name = unsafe { core::str::from_utf8(&NAME[0..NAME_LEN]).unwrap() };
+                    if let Some(volume) = unsafe { VOLUME.as_mut() } {
+                        match volume.create(Dir::root(), name) {
+                            Ok(file) => {
+                                let mut file = CardFile::new(volume, file);
+                                let result = modem.receive(|block| file.write(block));
+                                match file.finish(name, result.is_ok()) {
+                                    Ok(()) => result.map(|_| 1),
+                                    Err(e) => {
+                                        card_error = Some(e);
+                                        Err(xmodem::Error::Refused)
+                                    }
+                                }
+                            }
+                            Err(e) => {
+                                card_error = Some(e.message());
+                                Err(xmodem::Error::Refused)
+                            }
+                        }
+                    } else if staging.open(name, None) {
+                        let result = modem.receive(|block| staging.write(block));
+                        if result.is_ok() {
+                            staging.trim_padding();
+                            staging.close();
+                        }
+                        result.map(|_| 1)
+                    } else {
+                        Err(xmodem::Error::Refused)
+                    }
+                }
+                Transfer::YmodemReceive => modem.receive_batch(staging),
+                Transfer::YmodemSend => {
+                    let mut result = Ok(());
+                    for entry in &staging.entries[0..staging.count] {
+                        let data = &staging.data[entry.start..entry.start + entry.len];
+                        result = modem.send_file(entry.name(), data);
+                        if result.is_err() {
+                            break;
+                        }
+                    }
+                    result
+                        .and_then(|_| modem.end_batch())
+                        .map(|_| staging.count)
+                }
+            }
+        };
+        // Give the terminal emulator a moment to tidy up
+        d.delay_ms(500u32);
+        match result {
+            Ok(count) if transfer == Transfer::YmodemSend => {
+                writeln!(Console, "\nSent {} files", count).unwrap()
+            }
+            Ok(count) => writeln!(Console, "\nGot {} files", count).unwrap(),
+            Err(xmodem::Error::Refused) => {
+                staging.abandon();
+                match card_error {
+                    Some(e) => writeln!(Console, "\nCard: {}", e).unwrap(),
+                    None => writeln!(Console, "\nNo room for that").unwrap(),
+                }
+            }
+            Err(e) => {
+                staging.abandon();
+                writeln!(Console, "\nTransfer failed: {:?}", e).unwrap();
+            }
+        }
+        r.prompt();
+    }
+}
+
+exception!(HardFault, hard_fault);
+
+fn hard_fault(ef: &ExceptionFrame) -> ! {
+    panic!("HardFault at {:#?}", ef);
+}
+
+exception!(*, default_handler);
+
+fn default_handler(irqn: i16) {
+    panic!("Unhandled exception (IRQn = {})", irqn);
+}
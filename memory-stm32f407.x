@@ -0,0 +1,10 @@
+MEMORY
+{
+  /* NOTE K = KiBi = 1024 bytes */
+  /* The STM32F407VGT6 on the STM32F4 Discovery. The 64K of CCM RAM at
+     0x10000000 isn't used. */
+  FLASH : ORIGIN = 0x08000000, LENGTH = 1024K
+  RAM : ORIGIN = 0x20000000, LENGTH = 128K
+}
+
+/* See `memory.x` for the other settings. */
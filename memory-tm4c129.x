@@ -0,0 +1,10 @@
+MEMORY
+{
+  /* NOTE K = KiBi = 1024 bytes */
+  /* The TM4C1294NCPDT on the EK-TM4C1294XL Connected LaunchPad */
+  FLASH : ORIGIN = 0x00000000, LENGTH = 1024K
+  RAM : ORIGIN = 0x20000000, LENGTH = 256K
+}
+
+/* See `memory.x` for the other settings. The `apps` example (and so the
+   APP region) is only there on the TM4C123. */
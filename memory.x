@@ -3,7 +3,9 @@ MEMORY
   /* NOTE K = KiBi = 1024 bytes */
   /* TODO Adjust these memory regions to match your device memory layout */
   FLASH : ORIGIN = 0x00000000, LENGTH = 256K
-  RAM : ORIGIN = 0x20000000, LENGTH = 32K
+  RAM : ORIGIN = 0x20000000, LENGTH = 28K
+  /* The top 4K is kept for loadable applications - see `src/app.rs` */
+  APP : ORIGIN = 0x20007000, LENGTH = 4K
 }
 
 /* This is where the call stack will be allocated. */
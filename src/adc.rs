@@ -0,0 +1,215 @@
+//! One-off readings from the 12-bit ADCs.
+//!
+//! We use sample sequencer 3, which takes a single sample when software
+//! asks for it, and wait for the result. That's plenty for reading pots
+//! and sensors a few times a second. The caller must power up the ADC
+//! (`sysctl::Domain::Adc0`) and the GPIO port of any pin it uses, then call
+//! `configure_pin` on it.
+//!
+//! For regular samples, `start_triggered` has a timer trigger the sequencer
+//! instead. Set the timer's `TnOTE` bit, enable the sequencer 3 interrupt
+//! (`ADC0SS3` or `ADC1SS3`) and call `take` from it until it returns
+//! `None`. `read` doesn't work again until `stop_triggered`.
+//!
+//! The TM4C123 has twelve analog inputs and the TM4C1294 has twenty. The
+//! first twelve are on the same pins on both, apart from AIN4 to AIN7.
+
+use config::{Pin, Port};
+use pac::{ADC0, ADC1};
+
+/// The on-chip temperature sensor, which isn't a real channel number.
+pub const TEMPERATURE: u8 = 0xFF;
+
+/// Full scale reading.
+pub const MAX: u16 = 4095;
+
+/// The reference (and so full scale) voltage on the LaunchPad.
+pub const VREF_MV: u32 = 3300;
+
+/// The sequencer we use.
+const SS3: u32 = 1 << 3;
+
+// ADCSSCTL3
+const CTL_END0: u32 = 1 << 1;
+const CTL_IE0: u32 = 1 << 2;
+const CTL_TS0: u32 = 1 << 3;
+
+/// ADCEMUX: sequencer 3 triggered by a timer
+const EMUX_TIMER_SS3: u32 = 0x5 << 12;
+/// ADCEMUX: sequencer 3's trigger bits
+const EMUX_MASK_SS3: u32 = 0xF << 12;
+
+/// ADCSSFSTAT3: the FIFO is empty
+const FSTAT_EMPTY: u32 = 1 << 8;
+
+/// ADCCC: clock the ADC from the 16 MHz PIOSC. Otherwise it would get the
+/// 480 MHz PLL output, which is far too fast.
+#[cfg(feature = "tm4c129")]
+const CC_PIOSC: u32 = 0x1;
+
+pub struct Adc<ADC> {
+    adc: ADC,
+}
+
+/// Put the pin for an analog input into analog mode. The caller must
+/// power up the port.
+pub fn configure_pin(channel: u8) {
+    let pin = match analog_pin(channel) {
+        Some(pin) => pin,
+        None => return,
+    };
+    let port = pin.port.registers();
+    let mask = 1 << pin.bit;
+    port.afsel.modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+    port.den.modify(|r, w| unsafe { w.bits(r.bits() & !mask) });
+    port.amsel.modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+}
+
+/// Where AIN0..AIN11 are.
+#[cfg(feature = "tm4c123")]
+fn analog_pin(channel: u8) -> Option<Pin> {
+    let (port, bit) = match channel {
+        0 => (Port::E, 3),
+        1 => (Port::E, 2),
+        2 => (Port::E, 1),
+        3 => (Port::E, 0),
+        4 => (Port::D, 3),
+        5 => (Port::D, 2),
+        6 => (Port::D, 1),
+        7 => (Port::D, 0),
+        8 => (Port::E, 5),
+        9 => (Port::E, 4),
+        10 => (Port::B, 4),
+        11 => (Port::B, 5),
+        _ => return None,
+    };
+    Some(Pin { port, bit })
+}
+
+/// Where AIN0..AIN19 are.
+#[cfg(feature = "tm4c129")]
+fn analog_pin(channel: u8) -> Option<Pin> {
+    let (port, bit) = match channel {
+        0 => (Port::E, 3),
+        1 => (Port::E, 2),
+        2 => (Port::E, 1),
+        3 => (Port::E, 0),
+        4 => (Port::D, 7),
+        5 => (Port::D, 6),
+        6 => (Port::D, 5),
+        7 => (Port::D, 4),
+        8 => (Port::E, 5),
+        9 => (Port::E, 4),
+        10 => (Port::B, 4),
+        11 => (Port::B, 5),
+        12 => (Port::D, 3),
+        13 => (Port::D, 2),
+        14 => (Port::D, 1),
+        15 => (Port::D, 0),
+        16 => (Port::K, 0),
+        17 => (Port::K, 1),
+        18 => (Port::K, 2),
+        19 => (Port::K, 3),
+        _ => return None,
+    };
+    Some(Pin { port, bit })
+}
+
+/// Convert a reading to millivolts.
+pub fn millivolts(sample: u16) -> u32 {
+    (u32::from(sample) * VREF_MV) / u32::from(MAX)
+}
+
+/// Convert a reading of the `TEMPERATURE` channel to tenths of a degree
+/// Celsius. The sensor is only good to a few degrees.
+pub fn temperature(sample: u16) -> i32 {
+    // TEMP = 147.5 - ((75 * VREF * sample) / 4096), from the datasheet,
+    // with VREF = 3.3V
+    1475 - ((2475 * i32::from(sample)) / 4096)
+}
+
+macro_rules! hal {
+    ($($ADC:ident: $adcX:ident,)+) => {
+        $(
+            impl Adc<$ADC> {
+                /// Set up sequencer 3 for software triggered single samples.
+                pub fn $adcX(adc: $ADC) -> Self {
+                    adc.actss.modify(|r, w| unsafe { w.bits(r.bits() & !SS3) });
+                    #[cfg(feature = "tm4c129")]
+                    adc.cc.write(|w| unsafe { w.bits(CC_PIOSC) });
+                    // Triggered by software
+                    adc.emux.modify(|r, w| unsafe { w.bits(r.bits() & !EMUX_MASK_SS3) });
+                    adc.actss.modify(|r, w| unsafe { w.bits(r.bits() | SS3) });
+                    Adc { adc }
+                }
+
+                /// Give the peripheral back.
+                pub fn free(self) -> $ADC {
+                    self.adc
+                }
+
+                /// Take one sample of an analog input, or the
+                /// `TEMPERATURE` sensor.
+                pub fn read(&mut self, channel: u8) -> u16 {
+                    let adc = &self.adc;
+                    adc.actss.modify(|r, w| unsafe { w.bits(r.bits() & !SS3) });
+                    if channel == TEMPERATURE {
+                        adc.ssctl3
+                            .write(|w| unsafe { w.bits(CTL_TS0 | CTL_IE0 | CTL_END0) });
+                    } else {
+                        adc.ssmux3.write(|w| unsafe { w.bits(u32::from(channel)) });
+                        adc.ssctl3.write(|w| unsafe { w.bits(CTL_IE0 | CTL_END0) });
+                    }
+                    adc.actss.modify(|r, w| unsafe { w.bits(r.bits() | SS3) });
+                    adc.pssi.write(|w| unsafe { w.bits(SS3) });
+                    while adc.ris.read().bits() & SS3 == 0 {}
+                    let sample = adc.ssfifo3.read().bits() as u16 & MAX;
+                    adc.isc.write(|w| unsafe { w.bits(SS3) });
+                    sample
+                }
+
+                /// Sample an analog input every time a timer with
+                /// its ADC trigger enabled times out, interrupting after each
+                /// one.
+                pub fn start_triggered(&mut self, channel: u8) {
+                    let adc = &self.adc;
+                    adc.actss.modify(|r, w| unsafe { w.bits(r.bits() & !SS3) });
+                    adc.emux.modify(|r, w| unsafe {
+                        w.bits((r.bits() & !EMUX_MASK_SS3) | EMUX_TIMER_SS3)
+                    });
+                    adc.ssmux3.write(|w| unsafe { w.bits(u32::from(channel)) });
+                    adc.ssctl3.write(|w| unsafe { w.bits(CTL_IE0 | CTL_END0) });
+                    adc.isc.write(|w| unsafe { w.bits(SS3) });
+                    adc.im.modify(|r, w| unsafe { w.bits(r.bits() | SS3) });
+                    adc.actss.modify(|r, w| unsafe { w.bits(r.bits() | SS3) });
+                }
+
+                /// Go back to taking samples when `read` asks.
+                pub fn stop_triggered(&mut self) {
+                    let adc = &self.adc;
+                    adc.actss.modify(|r, w| unsafe { w.bits(r.bits() & !SS3) });
+                    adc.im.modify(|r, w| unsafe { w.bits(r.bits() & !SS3) });
+                    adc.emux.modify(|r, w| unsafe { w.bits(r.bits() & !EMUX_MASK_SS3) });
+                    adc.actss.modify(|r, w| unsafe { w.bits(r.bits() | SS3) });
+                }
+
+                /// The next sample from a triggered run, if there is one.
+                /// Clears the interrupt.
+                pub fn take(&mut self) -> Option<u16> {
+                    let adc = &self.adc;
+                    adc.isc.write(|w| unsafe { w.bits(SS3) });
+                    if adc.ssfstat3.read().bits() & FSTAT_EMPTY != 0 {
+                        None
+                    } else {
+                        Some(adc.ssfifo3.read().bits() as u16 & MAX)
+                    }
+                }
+            }
+        )+
+    }
+}
+
+hal! {
+    ADC0: adc0,
+    ADC1: adc1,
+}
@@ -0,0 +1,160 @@
+//! Drives an Analog Devices ADXL345 accelerometer over I2C, with its tap
+//! and free-fall detection.
+//!
+//! The ADXL345 can spot taps, double taps and free-fall on its own and
+//! raise an interrupt pin, so the microcontroller doesn't need to watch
+//! the data stream. Reading `interrupt_source()` clears the events and
+//! lets the pin go.
+
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+
+/// The address with ALT ADDRESS tied low. Tie it high for 0x1D.
+pub const DEFAULT_ADDRESS: u8 = 0x53;
+
+/// Interrupt bits, for `enable_interrupts()` and `interrupt_source()`.
+pub const INT_DATA_READY: u8 = 0x80;
+pub const INT_SINGLE_TAP: u8 = 0x40;
+pub const INT_DOUBLE_TAP: u8 = 0x20;
+pub const INT_ACTIVITY: u8 = 0x10;
+pub const INT_INACTIVITY: u8 = 0x08;
+pub const INT_FREE_FALL: u8 = 0x04;
+
+/// Milli-g per count, in full resolution mode.
+pub const MG_PER_LSB: i32 = 4;
+
+const REG_DEVID: u8 = 0x00;
+const REG_THRESH_TAP: u8 = 0x1D;
+const REG_DUR: u8 = 0x21;
+const REG_LATENT: u8 = 0x22;
+const REG_WINDOW: u8 = 0x23;
+const REG_THRESH_FF: u8 = 0x28;
+const REG_TIME_FF: u8 = 0x29;
+const REG_TAP_AXES: u8 = 0x2A;
+const REG_BW_RATE: u8 = 0x2C;
+const REG_POWER_CTL: u8 = 0x2D;
+const REG_INT_ENABLE: u8 = 0x2E;
+const REG_INT_MAP: u8 = 0x2F;
+const REG_INT_SOURCE: u8 = 0x30;
+const REG_DATA_FORMAT: u8 = 0x31;
+const REG_DATAX0: u8 = 0x32;
+
+const DEVID: u8 = 0xE5;
+
+/// Full resolution, +/-16g, interrupts active high.
+const DATA_FORMAT_FULL_RES_16G: u8 = 0x0B;
+
+/// 100 Hz output rate.
+const BW_RATE_100HZ: u8 = 0x0A;
+
+const POWER_CTL_MEASURE: u8 = 0x08;
+
+/// Tap detection on all three axes.
+const TAP_AXES_XYZ: u8 = 0x07;
+
+/// Something went wrong talking to the ADXL345.
+#[derive(Debug)]
+pub enum Error<E> {
+    /// The I2C bus reported an error
+    I2c(E),
+    /// DEVID didn't read back as 0xE5
+    WrongChip(u8),
+}
+
+pub struct Adxl345<I2C> {
+    i2c: I2C,
+    address: u8,
+}
+
+impl<I2C, E> Adxl345<I2C>
+where
+    I2C: Write<Error = E> + WriteRead<Error = E>,
+{
+    /// Check it's there and start it measuring at 100 Hz. All the
+    /// interrupts start off disabled, and all go to INT1.
+    pub fn new(i2c: I2C, address: u8) -> Result<Self, Error<E>> {
+        let mut accel = Adxl345 { i2c, address };
+        let id = accel.read_register(REG_DEVID)?;
+        if id != DEVID {
+            return Err(Error::WrongChip(id));
+        }
+        accel.write_register(REG_POWER_CTL, 0)?;
+        accel.write_register(REG_INT_ENABLE, 0)?;
+        accel.write_register(REG_INT_MAP, 0)?;
+        accel.write_register(REG_DATA_FORMAT, DATA_FORMAT_FULL_RES_16G)?;
+        accel.write_register(REG_BW_RATE, BW_RATE_100HZ)?;
+        accel.write_register(REG_POWER_CTL, POWER_CTL_MEASURE)?;
+        Ok(accel)
+    }
+
+    /// Give the bus back.
+    pub fn free(self) -> I2C {
+        self.i2c
+    }
+
+    /// Set up tap detection. A tap is a spike above `threshold_mg` that is
+    /// over within `duration_us`. A double tap is a second one starting
+    /// more than `latency_ms` but less than `window_ms` after the first.
+    pub fn configure_tap(
+        &mut self,
+        threshold_mg: u32,
+        duration_us: u32,
+        latency_ms: u32,
+        window_ms: u32,
+    ) -> Result<(), Error<E>> {
+        // 62.5 mg, 625 us, 1.25 ms and 1.25 ms per count respectively
+        self.write_register(REG_THRESH_TAP, clamp((threshold_mg * 2) / 125))?;
+        self.write_register(REG_DUR, clamp(duration_us / 625))?;
+        self.write_register(REG_LATENT, clamp((latency_ms * 4) / 5))?;
+        self.write_register(REG_WINDOW, clamp((window_ms * 4) / 5))?;
+        self.write_register(REG_TAP_AXES, TAP_AXES_XYZ)
+    }
+
+    /// Set up free-fall detection: all axes below `threshold_mg` for at
+    /// least `time_ms`. The datasheet suggests 300-600 mg and 100-350 ms.
+    pub fn configure_free_fall(&mut self, threshold_mg: u32, time_ms: u32) -> Result<(), Error<E>> {
+        // 62.5 mg and 5 ms per count
+        self.write_register(REG_THRESH_FF, clamp((threshold_mg * 2) / 125))?;
+        self.write_register(REG_TIME_FF, clamp(time_ms / 5))
+    }
+
+    /// Choose which events drive the INT1 pin (a combination of the `INT_`
+    /// bits).
+    pub fn enable_interrupts(&mut self, mask: u8) -> Result<(), Error<E>> {
+        self.write_register(REG_INT_ENABLE, mask)
+    }
+
+    /// Which events have happened since last time we asked. Reading this
+    /// clears them (except DATA_READY, which clears when the data is read).
+    pub fn interrupt_source(&mut self) -> Result<u8, Error<E>> {
+        self.read_register(REG_INT_SOURCE)
+    }
+
+    /// Read the X, Y and Z acceleration. Multiply by `MG_PER_LSB` to get
+    /// milli-g.
+    pub fn read(&mut self) -> Result<[i16; 3], Error<E>> {
+        let mut d = [0u8; 6];
+        self.i2c
+            .write_read(self.address, &[REG_DATAX0], &mut d)
+            .map_err(Error::I2c)?;
+        let le = |i: usize| (u16::from(d[i]) | (u16::from(d[i + 1]) << 8)) as i16;
+        Ok([le(0), le(2), le(4)])
+    }
+
+    fn read_register(&mut self, reg: u8) -> Result<u8, Error<E>> {
+        let mut value = [0u8];
+        self.i2c
+            .write_read(self.address, &[reg], &mut value)
+            .map_err(Error::I2c)?;
+        Ok(value[0])
+    }
+
+    fn write_register(&mut self, reg: u8, value: u8) -> Result<(), Error<E>> {
+        self.i2c
+            .write(self.address, &[reg, value])
+            .map_err(Error::I2c)
+    }
+}
+
+fn clamp(value: u32) -> u8 {
+    value.min(255) as u8
+}
@@ -0,0 +1,256 @@
+//! A two axis analog thumbstick, like the ones on game controllers and in
+//! Arduino kits, read with ADC0.
+//!
+//! Each axis is a pot between 3.3V and ground, with the wiper on an analog
+//! input: X on PD0 (AIN7) and Y on PD1 (AIN6). Pressing the stick in is a
+//! switch to ground on PD6, with the internal pull-up. Those are also the
+//! `dac`'s pins. `position` says where the stick is, from -100 to +100 on
+//! each axis with right and down positive - if yours comes out the wrong
+//! way round, swap the ends of that pot.
+//!
+//! No two sticks read the same, and few rest exactly in the middle, so each
+//! gets a `Calibration`: what the ADC reads at rest and at each end. Let go
+//! of the stick and call `start_calibration`, then go round the edges a few
+//! times while `sample` is running, then call `finish_calibration`. Keep
+//! the result in the EEPROM with `Calibration::save`.
+//!
+//! `sample` also turns the stick into the digital joystick's directions -
+//! pushed over half way is pressed, and back under a third is released
+//! again - and the stick's button into fire. They go to the `joystick`
+//! module's events, so games don't need to know which sort is plugged in.
+//! Don't have both at once, though, as they'll argue. Call `sample` every
+//! millisecond or so, like `joystick::sample`.
+
+use adc::{self, Adc};
+use config::{Pin, Port};
+use eeprom::{self, Eeprom};
+use joystick::{self, Button, Buttons};
+use pac::ADC0;
+
+/// Where the `Calibration` goes in the EEPROM. Four words.
+pub const EEPROM_ADDRESS: usize = eeprom::WORDS - 40;
+
+const X_CHANNEL: u8 = 7;
+const Y_CHANNEL: u8 = 6;
+const BUTTON: Pin = Pin {
+    port: Port::D,
+    bit: 6,
+};
+
+const MAGIC: u32 = 0x4A4F_5953;
+
+/// How far (out of 100) the stick must go to press a direction.
+const PRESS: i16 = 50;
+/// How far back it must come to release it.
+const RELEASE: i16 = 33;
+
+/// Readings averaged to find the centre.
+const CENTRE_SAMPLES: u32 = 64;
+
+/// What the ADC reads at rest and at each end, for X and Y.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Calibration {
+    pub min: [u16; 2],
+    pub centre: [u16; 2],
+    pub max: [u16; 2],
+}
+
+impl Calibration {
+    /// A perfect stick.
+    pub const DEFAULT: Calibration = Calibration {
+        min: [0, 0],
+        centre: [adc::MAX / 2, adc::MAX / 2],
+        max: [adc::MAX, adc::MAX],
+    };
+
+    /// Read the calibration from the EEPROM, or give the default if there
+    /// isn't one.
+    pub fn load(eeprom: &mut Eeprom) -> Calibration {
+        let mut words = [0u32; 4];
+        if eeprom.read(EEPROM_ADDRESS, &mut words).is_err() || words[0] != MAGIC {
+            return Calibration::DEFAULT;
+        }
+        let pair = |word: u32| [word as u16, (word >> 16) as u16];
+        Calibration {
+            min: pair(words[1]),
+            centre: pair(words[2]),
+            max: pair(words[3]),
+        }
+    }
+
+    /// Write the calibration to the EEPROM.
+    pub fn save(&self, eeprom: &mut Eeprom) -> Result<(), eeprom::Error> {
+        let word = |pair: [u16; 2]| u32::from(pair[0]) | (u32::from(pair[1]) << 16);
+        eeprom.write(
+            EEPROM_ADDRESS,
+            &[MAGIC, word(self.min), word(self.centre), word(self.max)],
+        )
+    }
+
+    /// A reading on `axis`, from -100 to +100.
+    fn scale(&self, axis: usize, raw: u16) -> i16 {
+        let (min, centre, max) = (
+            i32::from(self.min[axis]),
+            i32::from(self.centre[axis]),
+            i32::from(self.max[axis]),
+        );
+        let raw = i32::from(raw);
+        let scaled = if raw >= centre {
+            if max > centre {
+                ((raw - centre) * 100) / (max - centre)
+            } else {
+                0
+            }
+        } else if centre > min {
+            ((raw - centre) * 100) / (centre - min)
+        } else {
+            0
+        };
+        scaled.max(-100).min(100) as i16
+    }
+}
+
+pub struct AnalogJoystick {
+    adc: Adc<ADC0>,
+    calibration: Calibration,
+    /// While calibrating, the range so far
+    calibrating: Option<Calibration>,
+    /// The last reading, as -100..100
+    position: (i16, i16),
+    /// What we've told `joystick` is down
+    buttons: Buttons,
+    /// What the stick's button said last time, and for how many samples
+    last_fire: bool,
+    same_for: u8,
+}
+
+impl AnalogJoystick {
+    /// Set up the pins. The caller must power up ADC0.
+    pub fn new(adc: Adc<ADC0>, calibration: Calibration) -> AnalogJoystick {
+        // This powers up port D for the analog pins too
+        BUTTON.into_input();
+        let port = BUTTON.port.registers();
+        port.pur
+            .modify(|r, w| unsafe { w.bits(r.bits() | (1 << BUTTON.bit)) });
+        adc::configure_pin(X_CHANNEL);
+        adc::configure_pin(Y_CHANNEL);
+        AnalogJoystick {
+            adc,
+            calibration,
+            calibrating: None,
+            position: (0, 0),
+            buttons: 0,
+            last_fire: false,
+            same_for: 0,
+        }
+    }
+
+    pub fn calibration(&self) -> Calibration {
+        self.calibration
+    }
+
+    /// What the ADC reads, for X and Y.
+    pub fn raw(&mut self) -> [u16; 2] {
+        [self.adc.read(X_CHANNEL), self.adc.read(Y_CHANNEL)]
+    }
+
+    /// Where the stick was at the last `sample`, from -100 to +100 on each
+    /// axis. Right and down are positive.
+    pub fn position(&self) -> (i16, i16) {
+        self.position
+    }
+
+    /// Take the centre from where the stick is now, and start finding the
+    /// ends. Let go of the stick first.
+    pub fn start_calibration(&mut self) {
+        let mut total = [0u32; 2];
+        for _ in 0..CENTRE_SAMPLES {
+            let raw = self.raw();
+            total[0] += u32::from(raw[0]);
+            total[1] += u32::from(raw[1]);
+        }
+        let centre = [
+            (total[0] / CENTRE_SAMPLES) as u16,
+            (total[1] / CENTRE_SAMPLES) as u16,
+        ];
+        self.calibrating = Some(Calibration {
+            min: centre,
+            centre,
+            max: centre,
+        });
+        joystick::set(0);
+        self.buttons = 0;
+    }
+
+    pub fn is_calibrating(&self) -> bool {
+        self.calibrating.is_some()
+    }
+
+    /// Use the ends found since `start_calibration`. If the stick didn't
+    /// get at least a quarter of the way each way on each axis, that
+    /// doesn't look right, so we keep the old calibration and give `None`.
+    pub fn finish_calibration(&mut self) -> Option<Calibration> {
+        let found = self.calibrating.take()?;
+        let quarter = adc::MAX / 4;
+        for axis in 0..2 {
+            if found.centre[axis] - found.min[axis] < quarter
+                || found.max[axis] - found.centre[axis] < quarter
+            {
+                return None;
+            }
+        }
+        self.calibration = found;
+        Some(found)
+    }
+
+    /// Read the stick, and update the `joystick` module's buttons.
+    pub fn sample(&mut self) {
+        let raw = self.raw();
+        if let Some(ref mut found) = self.calibrating {
+            for axis in 0..2 {
+                found.min[axis] = found.min[axis].min(raw[axis]);
+                found.max[axis] = found.max[axis].max(raw[axis]);
+            }
+            return;
+        }
+        let x = self.calibration.scale(0, raw[0]);
+        let y = self.calibration.scale(1, raw[1]);
+        self.position = (x, y);
+
+        let mut buttons = self.buttons;
+        for &(button, value) in [
+            (Button::Left, -x),
+            (Button::Right, x),
+            (Button::Up, -y),
+            (Button::Down, y),
+        ].iter()
+        {
+            if value >= PRESS {
+                buttons |= button.mask();
+            } else if value < RELEASE {
+                buttons &= !button.mask();
+            }
+        }
+
+        // Active low, and bouncy
+        let fire = !BUTTON.is_high();
+        if fire == self.last_fire {
+            self.same_for = self.same_for.saturating_add(1);
+        } else {
+            self.last_fire = fire;
+            self.same_for = 1;
+        }
+        if self.same_for >= joystick::DEBOUNCE_SAMPLES {
+            if fire {
+                buttons |= Button::Fire.mask();
+            } else {
+                buttons &= !Button::Fire.mask();
+            }
+        }
+
+        if buttons != self.buttons {
+            self.buttons = buttons;
+            joystick::set(buttons);
+        }
+    }
+}
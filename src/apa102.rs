@@ -0,0 +1,80 @@
+//! Drives a string of APA102 ("DotStar") LEDs from SSI0.
+//!
+//! Unlike the WS2812, the APA102 has a separate clock line so timing doesn't
+//! matter and we can just poll the SSI FIFO. Each LED also has a 5-bit
+//! global brightness which is applied with a separate, slow PWM - handy for
+//! dimming without losing colour resolution.
+//!
+//! SSI0Clk is PA2 and SSI0Tx is PA5 on the TM4C123 (PA4 on the TM4C1294) -
+//! `config::SSI0.connect()` sets them up.
+
+use pac;
+pub use ws2812::Rgb;
+
+/// SSIClk = SysClk / (CPSDVSR * (1 + SCR)): 4 MHz = 80 MHz / (2 * (1 + 9))
+#[cfg(feature = "tm4c123")]
+const SCR: u8 = 9;
+/// SSIClk = SysClk / (CPSDVSR * (1 + SCR)): 4 MHz = 120 MHz / (2 * (1 + 14))
+#[cfg(feature = "tm4c129")]
+const SCR: u8 = 14;
+
+/// The largest value for the global brightness.
+pub const MAX_BRIGHTNESS: u8 = 31;
+
+/// Owns SSI0.
+pub struct Apa102 {
+    ssi: pac::SSI0,
+}
+
+impl Apa102 {
+    /// Set up SSI0 at 4 MHz. The caller must have powered up SSI0 and
+    /// called `config::SSI0.connect()`. The system clock must be what
+    /// `Board` sets (80 MHz, or 120 MHz on the TM4C1294).
+    pub fn new(ssi: pac::SSI0) -> Apa102 {
+        ssi.cr1.modify(|_, w| w.sse().clear_bit());
+        ssi.cpsr.write(|w| unsafe { w.cpsdvsr().bits(2) });
+        // Send 8 bits at a time in Freescale format, SPI mode 0
+        ssi.cr0.write(|w| {
+            w.dss()._8();
+            w.frf().moto();
+            w.spo().clear_bit();
+            w.sph().clear_bit();
+            unsafe { w.scr().bits(SCR) };
+            w
+        });
+        ssi.cc.modify(|_, w| w.cs().syspll());
+        ssi.cr1.modify(|_, w| w.sse().set_bit());
+        Apa102 { ssi }
+    }
+
+    /// Send a full set of colours to the string, with the given global
+    /// brightness (0..=31).
+    pub fn write(&mut self, colours: &[Rgb], brightness: u8) {
+        let brightness = if brightness > MAX_BRIGHTNESS {
+            MAX_BRIGHTNESS
+        } else {
+            brightness
+        };
+        // Start frame
+        for _ in 0..4 {
+            self.send(0x00);
+        }
+        for colour in colours {
+            self.send(0xE0 | brightness);
+            self.send(colour.blue);
+            self.send(colour.green);
+            self.send(colour.red);
+        }
+        // The data is delayed half a clock per LED, so we need at least
+        // n/2 more clocks to push it all out.
+        for _ in 0..(colours.len() / 16) + 1 {
+            self.send(0xFF);
+        }
+        while self.ssi.sr.read().bsy().bit_is_set() {}
+    }
+
+    fn send(&mut self, byte: u8) {
+        while self.ssi.sr.read().tnf().bit_is_clear() {}
+        self.ssi.dr.write(|w| unsafe { w.data().bits(u16::from(byte)) });
+    }
+}
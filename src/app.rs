@@ -0,0 +1,94 @@
+//! The interface between the firmware and applications loaded into RAM.
+//!
+//! An application is built on its own (see `apps/hello`), linked to run
+//! from `APP_START`, and turned into a flat binary with `objcopy -O binary`.
+//! The firmware loads it into the top `APP_LEN` bytes of RAM, which
+//! `memory.x` keeps for the purpose, and calls it.
+//!
+//! The image starts with a `Header`, which says where to start. The entry
+//! point gets an `Api` - a table of functions for talking to the hardware -
+//! so the application needs no drivers of its own and doesn't care where
+//! anything is in the firmware. Anything past the end of the image (like
+//! `.bss`) starts out as zeroes.
+//!
+//! Both structures only ever grow at the end: new calls go after the old
+//! ones and bump `VERSION`, so an application built against an older table
+//! still works.
+//!
+//! This file is built into the applications too, so it must not use
+//! anything but `core`.
+
+/// Where applications live.
+pub const APP_START: usize = 0x2000_7000;
+
+/// How much room they have.
+pub const APP_LEN: usize = 4 * 1024;
+
+/// The first word of every application: "APP!".
+pub const MAGIC: u32 = 0x2150_5041;
+
+/// Which version of the `Api` this is.
+pub const VERSION: u32 = 1;
+
+/// The start of an application image.
+#[repr(C)]
+pub struct Header {
+    /// Always `MAGIC`
+    pub magic: u32,
+    /// The oldest `Api` version the application can live with
+    pub version: u32,
+    /// Where to start. What it returns is up to the application; we print it.
+    pub entry: extern "C" fn(api: &'static Api) -> i32,
+}
+
+/// What the firmware gives an application.
+#[repr(C)]
+pub struct Api {
+    /// The firmware's `VERSION`
+    pub version: u32,
+    /// Print a character on the console. `\n` becomes `\r\n`.
+    pub print_char: extern "C" fn(ch: u8),
+    /// Get a key from the console, or -1 if there isn't one.
+    pub read_key: extern "C" fn() -> i32,
+    /// Set or clear a pixel on the 400 x 300 VGA screen.
+    pub plot: extern "C" fn(x: u32, y: u32, on: bool),
+    /// Play a note for a while (0 Hz is a rest), returning when it's done.
+    pub play_note: extern "C" fn(hz: u32, ms: u32),
+    /// Milliseconds since start up.
+    pub get_time: extern "C" fn() -> u32,
+}
+
+/// What's wrong with an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// It doesn't start with `MAGIC`
+    NotAnApp,
+    /// It wants a newer `Api` than we have
+    TooNew(u32),
+}
+
+/// The application area, as bytes. The caller must make sure nothing is
+/// running from it.
+pub unsafe fn area() -> &'static mut [u8] {
+    ::core::slice::from_raw_parts_mut(APP_START as *mut u8, APP_LEN)
+}
+
+/// Check the header of whatever is in the application area.
+pub fn header() -> Result<&'static Header, Error> {
+    // Look at the numbers before trusting the function pointer
+    let words = APP_START as *const u32;
+    let (magic, version) = unsafe { (*words, *words.offset(1)) };
+    if magic != MAGIC {
+        Err(Error::NotAnApp)
+    } else if version > VERSION {
+        Err(Error::TooNew(version))
+    } else {
+        Ok(unsafe { &*(APP_START as *const Header) })
+    }
+}
+
+/// Call the application, if there's a good one loaded.
+pub fn run(api: &'static Api) -> Result<i32, Error> {
+    let header = header()?;
+    Ok((header.entry)(api))
+}
@@ -0,0 +1,255 @@
+//! Square wave tones on a piezo sounder, without waiting for them to end.
+//!
+//! The output is M0PWM2 on PB4 (PF2 on the TM4C1294), as in the `basic` and
+//! `apps` examples - a piezo sounder, or a small speaker and a transistor.
+//! `beep` starts a tone and Timer5A stops it again later, so the caller can get
+//! on with something else. The caller must power up PWM0 and Timer5, call
+//! `init`, enable the TIMER5A interrupt and hook up the handler:
+//!
+//! ``` ignore
+//! interrupt!(TIMER5A, audio::timer5a_isr);
+//! ```
+//!
+//! `bell` and `error` are the two noises everything else uses. They do
+//! nothing if `init` hasn't been called, so `Console` can ring the bell on
+//! a `0x07` whether or not there's a sounder fitted.
+//!
+//! There are quieter noises for user interfaces too - see `UiSound`. They
+//! are off until `set_ui_sounds` turns them on, as not everyone wants a
+//! click on every key. `set_volume` turns everything down, by shortening
+//! the high part of the square wave.
+//!
+//! We slow the PWM clock down by 64 (with RCC.USEPWMDIV, or PWMCC on the
+//! TM4C1294), so `clkout::ClockOut` and this can't be used together.
+
+use config::{Pin, Port};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use hal::sysctl::Clocks;
+use pac::{self, PWM0, TIMER5};
+
+/// The lowest tone. The 16-bit PWM counter can't go any lower.
+pub const MIN_HZ: u32 = 20;
+/// The highest tone.
+pub const MAX_HZ: u32 = 20_000;
+
+/// What `bell` plays.
+pub const BELL_HZ: u32 = 880;
+pub const BELL_MS: u32 = 100;
+
+/// What `error` plays.
+pub const ERROR_HZ: u32 = 220;
+pub const ERROR_MS: u32 = 250;
+
+/// The loudest `set_volume` goes - a square wave, high half the time.
+pub const MAX_VOLUME: u8 = 100;
+
+/// Where the sound comes out.
+#[cfg(feature = "tm4c123")]
+const PIN: Pin = Pin {
+    port: Port::B,
+    bit: 4,
+};
+/// Where the sound comes out. M0PWM2 isn't on PB4 on the TM4C1294.
+#[cfg(feature = "tm4c129")]
+const PIN: Pin = Pin {
+    port: Port::F,
+    bit: 2,
+};
+/// M0PWM2's alternate function.
+#[cfg(feature = "tm4c123")]
+const PIN_FUNCTION: u32 = 4;
+#[cfg(feature = "tm4c129")]
+const PIN_FUNCTION: u32 = 6;
+/// M0PWM2 in PWMENABLE.
+const OUTPUT: u32 = 1 << 2;
+/// RCC.USEPWMDIV, and RCC.PWMDIV set to /64.
+#[cfg(feature = "tm4c123")]
+const RCC_PWMDIV_64: u32 = (1 << 20) | (0x7 << 17);
+/// PWMCC.USEPWM, and PWMCC.PWMDIV set to /64. The TM4C1294 keeps the
+/// divider in the PWM block rather than in RCC.
+#[cfg(feature = "tm4c129")]
+const PWMCC_PWMDIV_64: u32 = (1 << 8) | 0x5;
+const PWM_DIVIDER: u32 = 64;
+
+// GPTMCFG, GPTMTAMR
+const CFG_32_BIT: u32 = 0x0;
+const TAMR_ONE_SHOT: u32 = 0x1;
+
+static READY: AtomicBool = AtomicBool::new(false);
+static SYSCLK_HZ: AtomicUsize = AtomicUsize::new(0);
+static UI_SOUNDS: AtomicBool = AtomicBool::new(false);
+static VOLUME: AtomicUsize = AtomicUsize::new(MAX_VOLUME as usize);
+
+/// A noise for a user interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UiSound {
+    /// A key was pressed
+    Click,
+    /// Into a menu, or a command was entered
+    Enter,
+    /// That didn't work
+    Error,
+}
+
+impl UiSound {
+    /// The frequency and length of the noise.
+    fn tone(self) -> (u32, u32) {
+        match self {
+            UiSound::Click => (4000, 2),
+            UiSound::Enter => (1760, 30),
+            UiSound::Error => (110, 150),
+        }
+    }
+}
+
+/// Set up the pin, PWM0 generator 1 and Timer5A. Nothing plays yet.
+pub fn init(pwm: PWM0, timer: TIMER5, clocks: &Clocks) {
+    SYSCLK_HZ.store(clocks.sysclk.0 as usize, Ordering::Relaxed);
+
+    PIN.into_af(PIN_FUNCTION);
+    set_pwm_divider(&pwm);
+    pwm._1_ctl.write(|w| unsafe { w.bits(0) });
+    // ACTCMPAD = drive high, ACTLOAD = drive low
+    pwm._1_gena.write(|w| unsafe { w.bits((0x3 << 6) | (0x2 << 2)) });
+    pwm._1_ctl.write(|w| unsafe { w.bits(1) });
+
+    // Timer5A, 32-bit one-shot, interrupting when the tone should stop
+    timer.ctl.modify(|_, w| w.taen().clear_bit());
+    timer.cfg.write(|w| unsafe { w.bits(CFG_32_BIT) });
+    timer.tamr.write(|w| unsafe { w.bits(TAMR_ONE_SHOT) });
+    timer.icr.write(|w| w.tatocint().set_bit());
+    timer.imr.modify(|_, w| w.tatoim().set_bit());
+
+    READY.store(true, Ordering::Relaxed);
+}
+
+/// Run the PWM clock at sysclk / `PWM_DIVIDER`.
+#[cfg(feature = "tm4c123")]
+fn set_pwm_divider(_pwm: &PWM0) {
+    let sysctl = unsafe { &*pac::SYSCTL::ptr() };
+    sysctl
+        .rcc
+        .modify(|r, w| unsafe { w.bits(r.bits() | RCC_PWMDIV_64) });
+}
+
+/// Run the PWM clock at sysclk / `PWM_DIVIDER`.
+#[cfg(feature = "tm4c129")]
+fn set_pwm_divider(pwm: &PWM0) {
+    pwm.cc.write(|w| unsafe { w.bits(PWMCC_PWMDIV_64) });
+}
+
+/// Start a tone, which plays until `stop`. Frequencies are clamped to
+/// `MIN_HZ..MAX_HZ`.
+pub fn tone(hz: u32) {
+    if !READY.load(Ordering::Relaxed) {
+        return;
+    }
+    let pwm = unsafe { &*pac::PWM0::ptr() };
+    let pwm_clock = SYSCLK_HZ.load(Ordering::Relaxed) as u32 / PWM_DIVIDER;
+    let period = pwm_clock / hz.max(MIN_HZ).min(MAX_HZ);
+    let volume = VOLUME.load(Ordering::Relaxed) as u32;
+    if volume == 0 {
+        pwm.enable
+            .modify(|r, w| unsafe { w.bits(r.bits() & !OUTPUT) });
+        return;
+    }
+    // The output is high from the compare value down to zero
+    let high = ((period * volume) / (2 * u32::from(MAX_VOLUME))).max(1);
+    pwm._1_load.write(|w| unsafe { w.bits(period - 1) });
+    pwm._1_cmpa.write(|w| unsafe { w.bits(high - 1) });
+    pwm.enable
+        .modify(|r, w| unsafe { w.bits(r.bits() | OUTPUT) });
+}
+
+/// Play a tone for `ms` milliseconds, and return straight away. Anything
+/// already playing is cut off.
+pub fn beep(hz: u32, ms: u32) {
+    if !READY.load(Ordering::Relaxed) {
+        return;
+    }
+    let timer = unsafe { &*pac::TIMER5::ptr() };
+    let clocks_per_ms = SYSCLK_HZ.load(Ordering::Relaxed) as u32 / 1000;
+    timer.ctl.modify(|_, w| w.taen().clear_bit());
+    tone(hz);
+    timer
+        .tailr
+        .write(|w| unsafe { w.bits(clocks_per_ms.saturating_mul(ms.max(1))) });
+    timer.ctl.modify(|_, w| w.taen().set_bit());
+}
+
+/// Stop whatever's playing.
+pub fn stop() {
+    if !READY.load(Ordering::Relaxed) {
+        return;
+    }
+    let timer = unsafe { &*pac::TIMER5::ptr() };
+    timer.ctl.modify(|_, w| w.taen().clear_bit());
+    let pwm = unsafe { &*pac::PWM0::ptr() };
+    pwm.enable
+        .modify(|r, w| unsafe { w.bits(r.bits() & !OUTPUT) });
+}
+
+/// Is a tone playing?
+pub fn is_playing() -> bool {
+    if !READY.load(Ordering::Relaxed) {
+        return false;
+    }
+    let pwm = unsafe { &*pac::PWM0::ptr() };
+    pwm.enable.read().bits() & OUTPUT != 0
+}
+
+/// The terminal bell.
+pub fn bell() {
+    beep(BELL_HZ, BELL_MS);
+}
+
+/// Something went wrong.
+pub fn error() {
+    beep(ERROR_HZ, ERROR_MS);
+}
+
+/// Turn the `UiSound`s on or off.
+pub fn set_ui_sounds(enabled: bool) {
+    UI_SOUNDS.store(enabled, Ordering::Relaxed);
+}
+
+/// Are the `UiSound`s on?
+pub fn ui_sounds() -> bool {
+    UI_SOUNDS.load(Ordering::Relaxed)
+}
+
+/// Set the volume of everything, from 0 (silent) to `MAX_VOLUME`. Takes
+/// effect from the next tone.
+pub fn set_volume(volume: u8) {
+    VOLUME.store(volume.min(MAX_VOLUME) as usize, Ordering::Relaxed);
+}
+
+/// The current volume.
+pub fn volume() -> u8 {
+    VOLUME.load(Ordering::Relaxed) as u8
+}
+
+/// Make a user interface noise, if they're turned on.
+pub fn ui(sound: UiSound) {
+    if ui_sounds() {
+        let (hz, ms) = sound.tone();
+        beep(hz, ms);
+    }
+}
+
+/// The noise for a key being typed. Call this with every byte from the
+/// keyboard (or the console) before handing it on to the menu `Runner`.
+pub fn key(byte: u8) {
+    match byte {
+        b'\r' | b'\n' => ui(UiSound::Enter),
+        0x08 | 0x20...0x7F => ui(UiSound::Click),
+        _ => {}
+    }
+}
+
+/// The end of a `beep`. The TIMER5A handler.
+pub fn timer5a_isr() {
+    let timer = unsafe { &*pac::TIMER5::ptr() };
+    timer.icr.write(|w| w.tatocint().set_bit());
+    stop();
+}
@@ -0,0 +1,878 @@
+//! A Tiny BASIC interpreter, for turning the board into a home computer.
+//!
+//! Type a line with a number on the front and it goes into the program;
+//! type one without and it runs straight away. Keywords are turned into
+//! single-byte tokens as lines come in, so the program takes less RAM and
+//! runs faster, and `LIST` turns them back into words.
+//!
+//! It's integers only - 26 variables, `A` to `Z`, each 32 bits - and
+//! strings only appear in `PRINT`. The statements are:
+//!
+//! * `PRINT`, `LET`, `INPUT`, `IF ... THEN`, `GOTO`, `GOSUB`, `RETURN`,
+//!   `FOR ... TO ... STEP`, `NEXT`, `END` and `REM`
+//! * `POKE addr, byte`, with `PEEK(addr)` to read memory back
+//! * `CLS`, `PLOT x, y`, `UNPLOT x, y` and `LINE x1, y1, x2, y2`
+//! * `SOUND hz, ms` and `PAUSE ms`
+//! * `LIST [from [, to]]`, `RUN`, `NEW`, `SAVE` and `LOAD`
+//!
+//! Expressions have `+ - * / MOD`, the comparisons, `AND`, `OR`, `NOT`
+//! and the functions `PEEK`, `RND` and `ABS`. Ctrl-C stops a program.
+//!
+//! Everything to do with the outside world - the screen, the keyboard,
+//! sound and somewhere to save programs - goes through the `Host` trait.
+
+use core::fmt::Write;
+use random;
+
+/// How much program we can hold.
+pub const PROGRAM_LEN: usize = 4096;
+
+/// The longest line, once tokenised.
+pub const LINE_LEN: usize = 128;
+
+/// Each stored line starts with its number (little-endian) and length.
+const HEADER_LEN: usize = 3;
+
+const MAX_GOSUB: usize = 16;
+const MAX_FOR: usize = 8;
+
+const CTRL_C: u8 = 0x03;
+
+/// Tokens are 0x80 plus the keyword's place in here. No keyword may start
+/// with another one, or the tokeniser will find the short one first.
+const KEYWORDS: [&str; 33] = [
+    "PRINT", "LET", "IF", "THEN", "GOTO", "GOSUB", "RETURN", "FOR", "TO", "STEP", "NEXT", "END",
+    "REM", "INPUT", "POKE", "CLS", "PLOT", "UNPLOT", "LINE", "SOUND", "PAUSE", "LIST", "RUN",
+    "NEW", "SAVE", "LOAD", "PEEK", "RND", "ABS", "MOD", "AND", "OR", "NOT",
+];
+
+const PRINT: u8 = 0x80;
+const LET: u8 = 0x81;
+const IF: u8 = 0x82;
+const THEN: u8 = 0x83;
+const GOTO: u8 = 0x84;
+const GOSUB: u8 = 0x85;
+const RETURN: u8 = 0x86;
+const FOR: u8 = 0x87;
+const TO: u8 = 0x88;
+const STEP: u8 = 0x89;
+const NEXT: u8 = 0x8A;
+const END: u8 = 0x8B;
+const REM: u8 = 0x8C;
+const INPUT: u8 = 0x8D;
+const POKE: u8 = 0x8E;
+const CLS: u8 = 0x8F;
+const PLOT: u8 = 0x90;
+const UNPLOT: u8 = 0x91;
+const LINE: u8 = 0x92;
+const SOUND: u8 = 0x93;
+const PAUSE: u8 = 0x94;
+const LIST: u8 = 0x95;
+const RUN: u8 = 0x96;
+const NEW: u8 = 0x97;
+const SAVE: u8 = 0x98;
+const LOAD: u8 = 0x99;
+const PEEK: u8 = 0x9A;
+const RND: u8 = 0x9B;
+const ABS: u8 = 0x9C;
+const MOD: u8 = 0x9D;
+const AND: u8 = 0x9E;
+const OR: u8 = 0x9F;
+const NOT: u8 = 0xA0;
+
+/// What the interpreter needs from the machine it's running on.
+pub trait Host: Write {
+    /// A key, if one has been pressed. Only used to look for Ctrl-C.
+    fn read_byte(&mut self) -> Option<u8>;
+
+    /// Read a line for `INPUT`, echoing it. `None` if the user gave up.
+    fn read_line(&mut self, buffer: &mut [u8]) -> Option<usize>;
+
+    /// Clear the screen.
+    fn cls(&mut self);
+
+    /// Light (or clear) a pixel.
+    fn plot(&mut self, x: i32, y: i32, on: bool);
+
+    /// Draw (or clear) a line.
+    fn line(&mut self, start: (i32, i32), end: (i32, i32), on: bool);
+
+    /// Play a tone, returning when it's finished.
+    fn sound(&mut self, hz: u32, ms: u32);
+
+    /// Let the user know something went wrong, beyond the message. Does
+    /// nothing, unless there's a sounder to beep.
+    fn alert(&mut self) {}
+
+    /// Do nothing for a while.
+    fn pause(&mut self, ms: u32);
+
+    /// Keep the program somewhere safe. False if it doesn't fit.
+    fn save(&mut self, program: &[u8]) -> bool;
+
+    /// Get the program back. Gives its length, if there was one.
+    fn load(&mut self, program: &mut [u8]) -> Option<usize>;
+
+    fn peek(&mut self, address: u32) -> u8 {
+        unsafe { ::core::ptr::read_volatile(address as *const u8) }
+    }
+
+    fn poke(&mut self, address: u32, value: u8) {
+        unsafe { ::core::ptr::write_volatile(address as *mut u8, value) }
+    }
+}
+
+/// What went wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The line doesn't make sense
+    Syntax,
+    /// A `GOTO` or `GOSUB` to a line that isn't there
+    NoSuchLine,
+    /// `RETURN` with no `GOSUB`
+    ReturnWithoutGosub,
+    /// `NEXT` with no `FOR`
+    NextWithoutFor,
+    /// Too many `GOSUB`s or `FOR`s inside each other
+    TooDeep,
+    /// The program is full
+    OutOfMemory,
+    /// Dividing by zero
+    DivideByZero,
+    /// The line is too long
+    LineTooLong,
+    /// Someone pressed Ctrl-C
+    Break,
+    /// The host couldn't save the program
+    CantSave,
+    /// There was nothing (sensible) to load
+    CantLoad,
+}
+
+/// Where we are in the program, or in the line just typed.
+#[derive(Clone, Copy)]
+struct Position {
+    /// Offset of the current line in the program, or `None` for the
+    /// typed-in line
+    line: Option<usize>,
+    pos: usize,
+    end: usize,
+}
+
+#[derive(Clone, Copy)]
+struct Loop {
+    var: usize,
+    limit: i32,
+    step: i32,
+    /// Just after the `FOR`
+    body: Position,
+}
+
+/// What the run loop does after a statement.
+enum Flow {
+    /// Look for a `:` or the end of the line
+    Next,
+    /// We've moved somewhere else - just carry on from there
+    Jumped,
+    /// Stop running
+    End,
+}
+
+pub struct Basic {
+    program: [u8; PROGRAM_LEN],
+    len: usize,
+    /// The tokenised form of the line just typed
+    direct: [u8; LINE_LEN],
+    vars: [i32; 26],
+    at: Position,
+    gosubs: [Position; MAX_GOSUB],
+    gosub_depth: usize,
+    fors: [Loop; MAX_FOR],
+    for_depth: usize,
+}
+
+impl Basic {
+    pub fn new() -> Basic {
+        let start = Position {
+            line: None,
+            pos: 0,
+            end: 0,
+        };
+        Basic {
+            program: [0u8; PROGRAM_LEN],
+            len: 0,
+            direct: [0u8; LINE_LEN],
+            vars: [0; 26],
+            at: start,
+            gosubs: [start; MAX_GOSUB],
+            gosub_depth: 0,
+            fors: [Loop {
+                var: 0,
+                limit: 0,
+                step: 0,
+                body: start,
+            }; MAX_FOR],
+            for_depth: 0,
+        }
+    }
+
+    /// How much room is left for the program.
+    pub fn free(&self) -> usize {
+        PROGRAM_LEN - self.len
+    }
+
+    /// Deal with a line the user typed, reporting any error to the host.
+    pub fn enter<H: Host>(&mut self, host: &mut H, text: &str) {
+        self.at = Position {
+            line: None,
+            pos: 0,
+            end: 0,
+        };
+        self.gosub_depth = 0;
+        self.for_depth = 0;
+        if let Err(e) = self.execute(host, text.trim()) {
+            host.alert();
+            match self.at.line {
+                Some(line) => writeln!(host, "? {:?} in {}", e, self.line_number(line)).unwrap(),
+                None => writeln!(host, "? {:?}", e).unwrap(),
+            }
+        }
+    }
+
+    fn execute<H: Host>(&mut self, host: &mut H, text: &str) -> Result<(), Error> {
+        let digits = text.bytes().take_while(|b| b.is_ascii_digit()).count();
+        if digits > 0 {
+            let number = text[0..digits].parse::<u16>().map_err(|_| Error::Syntax)?;
+            let len = tokenise(text[digits..].trim_left().as_bytes(), &mut self.direct)?;
+            self.store(number, len)
+        } else {
+            let len = tokenise(text.as_bytes(), &mut self.direct)?;
+            self.at.end = len;
+            self.run(host)
+        }
+    }
+
+    /// Put a line in the program, replacing any with the same number. An
+    /// empty line just removes it.
+    fn store(&mut self, number: u16, len: usize) -> Result<(), Error> {
+        let mut offset = 0;
+        while offset < self.len && self.line_number(offset) < number {
+            offset += self.line_len(offset);
+        }
+        if offset < self.len && self.line_number(offset) == number {
+            let old_len = self.line_len(offset);
+            for i in offset..self.len - old_len {
+                self.program[i] = self.program[i + old_len];
+            }
+            self.len -= old_len;
+        }
+        if len == 0 {
+            return Ok(());
+        }
+        let needed = len + HEADER_LEN;
+        if self.len + needed > PROGRAM_LEN {
+            return Err(Error::OutOfMemory);
+        }
+        for i in (offset..self.len).rev() {
+            self.program[i + needed] = self.program[i];
+        }
+        self.program[offset] = number as u8;
+        self.program[offset + 1] = (number >> 8) as u8;
+        self.program[offset + 2] = needed as u8;
+        self.program[offset + HEADER_LEN..offset + needed].copy_from_slice(&self.direct[0..len]);
+        self.len += needed;
+        Ok(())
+    }
+
+    fn line_number(&self, offset: usize) -> u16 {
+        u16::from(self.program[offset]) | (u16::from(self.program[offset + 1]) << 8)
+    }
+
+    fn line_len(&self, offset: usize) -> usize {
+        usize::from(self.program[offset + 2])
+    }
+
+    /// Run statements until we fall off the end, or hit `END`.
+    fn run<H: Host>(&mut self, host: &mut H) -> Result<(), Error> {
+        loop {
+            if host.read_byte() == Some(CTRL_C) {
+                return Err(Error::Break);
+            }
+            while self.peek() == b' ' || self.peek() == b':' {
+                self.at.pos += 1;
+            }
+            if self.at.pos >= self.at.end {
+                match self.at.line {
+                    Some(line) if line + self.line_len(line) < self.len => {
+                        let next = line + self.line_len(line);
+                        self.goto_offset(next);
+                    }
+                    _ => return Ok(()),
+                }
+                continue;
+            }
+            match self.statement(host)? {
+                Flow::Next => {
+                    self.skip_spaces();
+                    match self.peek() {
+                        0 | b':' => {}
+                        _ => return Err(Error::Syntax),
+                    }
+                }
+                Flow::Jumped => {}
+                Flow::End => return Ok(()),
+            }
+        }
+    }
+
+    fn statement<H: Host>(&mut self, host: &mut H) -> Result<Flow, Error> {
+        match self.next_byte() {
+            PRINT => self.print(host),
+            LET => self.assign(host),
+            b'A'...b'Z' => {
+                self.at.pos -= 1;
+                self.assign(host)
+            }
+            IF => {
+                let condition = self.expr(host)?;
+                self.expect(THEN)?;
+                if condition == 0 {
+                    self.at.pos = self.at.end;
+                } else {
+                    self.skip_spaces();
+                    if self.peek().is_ascii_digit() {
+                        let number = self.expr(host)?;
+                        self.goto_line(number)?;
+                    }
+                }
+                // Either way, the run loop carries on from here
+                Ok(Flow::Jumped)
+            }
+            GOTO => {
+                let number = self.expr(host)?;
+                self.goto_line(number)?;
+                Ok(Flow::Jumped)
+            }
+            GOSUB => {
+                let number = self.expr(host)?;
+                if self.gosub_depth == MAX_GOSUB {
+                    return Err(Error::TooDeep);
+                }
+                self.gosubs[self.gosub_depth] = self.at;
+                self.gosub_depth += 1;
+                self.goto_line(number)?;
+                Ok(Flow::Jumped)
+            }
+            RETURN => {
+                if self.gosub_depth == 0 {
+                    return Err(Error::ReturnWithoutGosub);
+                }
+                self.gosub_depth -= 1;
+                self.at = self.gosubs[self.gosub_depth];
+                Ok(Flow::Next)
+            }
+            FOR => self.for_loop(host),
+            NEXT => self.next(),
+            END => Ok(Flow::End),
+            REM => {
+                self.at.pos = self.at.end;
+                Ok(Flow::Jumped)
+            }
+            INPUT => self.input(host),
+            POKE => {
+                let address = self.expr(host)?;
+                self.expect(b',')?;
+                let value = self.expr(host)?;
+                host.poke(address as u32, value as u8);
+                Ok(Flow::Next)
+            }
+            CLS => {
+                host.cls();
+                Ok(Flow::Next)
+            }
+            token @ PLOT | token @ UNPLOT => {
+                let x = self.expr(host)?;
+                self.expect(b',')?;
+                let y = self.expr(host)?;
+                host.plot(x, y, token == PLOT);
+                Ok(Flow::Next)
+            }
+            LINE => {
+                let mut args = [0i32; 4];
+                for (i, arg) in args.iter_mut().enumerate() {
+                    if i != 0 {
+                        self.expect(b',')?;
+                    }
+                    *arg = self.expr(host)?;
+                }
+                host.line((args[0], args[1]), (args[2], args[3]), true);
+                Ok(Flow::Next)
+            }
+            SOUND => {
+                let hz = self.expr(host)?;
+                self.expect(b',')?;
+                let ms = self.expr(host)?;
+                host.sound(hz.max(0) as u32, ms.max(0) as u32);
+                Ok(Flow::Next)
+            }
+            PAUSE => {
+                let ms = self.expr(host)?;
+                host.pause(ms.max(0) as u32);
+                Ok(Flow::Next)
+            }
+            LIST => self.list(host),
+            RUN => {
+                self.vars = [0; 26];
+                self.gosub_depth = 0;
+                self.for_depth = 0;
+                if self.len == 0 {
+                    return Ok(Flow::End);
+                }
+                self.goto_offset(0);
+                Ok(Flow::Jumped)
+            }
+            NEW => {
+                self.len = 0;
+                self.vars = [0; 26];
+                Ok(Flow::End)
+            }
+            SAVE => {
+                if host.save(&self.program[0..self.len]) {
+                    Ok(Flow::Next)
+                } else {
+                    Err(Error::CantSave)
+                }
+            }
+            LOAD => {
+                self.len = match host.load(&mut self.program) {
+                    Some(len) if self.check(len) => len,
+                    _ => 0,
+                };
+                if self.len == 0 {
+                    Err(Error::CantLoad)
+                } else {
+                    Ok(Flow::End)
+                }
+            }
+            _ => Err(Error::Syntax),
+        }
+    }
+
+    /// Do the lines' lengths add up? We don't want to run off the end
+    /// because of a bad `LOAD`.
+    fn check(&self, len: usize) -> bool {
+        if len > PROGRAM_LEN {
+            return false;
+        }
+        let mut offset = 0;
+        while offset < len {
+            if offset + HEADER_LEN > len || self.line_len(offset) <= HEADER_LEN {
+                return false;
+            }
+            offset += self.line_len(offset);
+        }
+        offset == len
+    }
+
+    fn print<H: Host>(&mut self, host: &mut H) -> Result<Flow, Error> {
+        let mut newline = true;
+        loop {
+            self.skip_spaces();
+            match self.peek() {
+                0 | b':' => break,
+                b'"' => {
+                    self.at.pos += 1;
+                    loop {
+                        match self.next_byte() {
+                            0 => return Err(Error::Syntax),
+                            b'"' => break,
+                            ch => host.write_char(ch as char).unwrap(),
+                        }
+                    }
+                }
+                _ => {
+                    let value = self.expr(host)?;
+                    write!(host, "{}", value).unwrap();
+                }
+            }
+            newline = true;
+            self.skip_spaces();
+            match self.peek() {
+                b';' => newline = false,
+                b',' => {
+                    host.write_char(' ').unwrap();
+                    newline = false;
+                }
+                _ => break,
+            }
+            self.at.pos += 1;
+        }
+        if newline {
+            writeln!(host).unwrap();
+        }
+        Ok(Flow::Next)
+    }
+
+    fn assign<H: Host>(&mut self, host: &mut H) -> Result<Flow, Error> {
+        let var = self.variable()?;
+        self.expect(b'=')?;
+        self.vars[var] = self.expr(host)?;
+        Ok(Flow::Next)
+    }
+
+    fn input<H: Host>(&mut self, host: &mut H) -> Result<Flow, Error> {
+        let var = self.variable()?;
+        loop {
+            host.write_str("? ").unwrap();
+            let mut buffer = [0u8; 16];
+            let len = host.read_line(&mut buffer).ok_or(Error::Break)?;
+            let text = ::core::str::from_utf8(&buffer[0..len]).unwrap_or("");
+            match text.trim().parse::<i32>() {
+                Ok(value) => {
+                    self.vars[var] = value;
+                    return Ok(Flow::Next);
+                }
+                Err(_) => writeln!(host, "Numbers only").unwrap(),
+            }
+        }
+    }
+
+    fn for_loop<H: Host>(&mut self, host: &mut H) -> Result<Flow, Error> {
+        let var = self.variable()?;
+        self.expect(b'=')?;
+        let start = self.expr(host)?;
+        self.expect(TO)?;
+        let limit = self.expr(host)?;
+        self.skip_spaces();
+        let step = if self.peek() == STEP {
+            self.at.pos += 1;
+            self.expr(host)?
+        } else {
+            1
+        };
+        self.vars[var] = start;
+        // Starting a loop again throws away it (and anything inside it)
+        if let Some(index) = self.fors[0..self.for_depth]
+            .iter()
+            .position(|l| l.var == var)
+        {
+            self.for_depth = index;
+        }
+        if self.for_depth == MAX_FOR {
+            return Err(Error::TooDeep);
+        }
+        self.fors[self.for_depth] = Loop {
+            var,
+            limit,
+            step,
+            body: self.at,
+        };
+        self.for_depth += 1;
+        Ok(Flow::Next)
+    }
+
+    fn next(&mut self) -> Result<Flow, Error> {
+        self.skip_spaces();
+        let index = match self.peek() {
+            b'A'...b'Z' => {
+                let var = self.variable()?;
+                self.fors[0..self.for_depth]
+                    .iter()
+                    .position(|l| l.var == var)
+            }
+            _ => self.for_depth.checked_sub(1),
+        };
+        let index = index.ok_or(Error::NextWithoutFor)?;
+        let l = self.fors[index];
+        let value = self.vars[l.var].wrapping_add(l.step);
+        self.vars[l.var] = value;
+        let again = if l.step >= 0 {
+            value <= l.limit
+        } else {
+            value >= l.limit
+        };
+        if again {
+            self.for_depth = index + 1;
+            self.at = l.body;
+        } else {
+            self.for_depth = index;
+        }
+        Ok(Flow::Next)
+    }
+
+    fn list<H: Host>(&mut self, host: &mut H) -> Result<Flow, Error> {
+        self.skip_spaces();
+        let mut from = 0;
+        let mut to = i32::from(u16::max_value());
+        if self.peek().is_ascii_digit() {
+            from = self.expr(host)?;
+            to = from;
+            self.skip_spaces();
+            if self.peek() == b',' {
+                self.at.pos += 1;
+                to = self.expr(host)?;
+            }
+        }
+        let mut offset = 0;
+        while offset < self.len {
+            let number = i32::from(self.line_number(offset));
+            let len = self.line_len(offset);
+            if number >= from && number <= to {
+                write!(host, "{} ", number).unwrap();
+                for &byte in &self.program[offset + HEADER_LEN..offset + len] {
+                    if byte >= PRINT {
+                        host.write_str(KEYWORDS[usize::from(byte - PRINT)]).unwrap();
+                    } else {
+                        host.write_char(byte as char).unwrap();
+                    }
+                }
+                writeln!(host).unwrap();
+            }
+            offset += len;
+        }
+        Ok(Flow::Next)
+    }
+
+    /// The lowest precedence: `OR`.
+    fn expr<H: Host>(&mut self, host: &mut H) -> Result<i32, Error> {
+        let mut value = self.and_expr(host)?;
+        loop {
+            self.skip_spaces();
+            if self.peek() != OR {
+                return Ok(value);
+            }
+            self.at.pos += 1;
+            let rhs = self.and_expr(host)?;
+            value = (value != 0 || rhs != 0) as i32;
+        }
+    }
+
+    fn and_expr<H: Host>(&mut self, host: &mut H) -> Result<i32, Error> {
+        let mut value = self.comparison(host)?;
+        loop {
+            self.skip_spaces();
+            if self.peek() != AND {
+                return Ok(value);
+            }
+            self.at.pos += 1;
+            let rhs = self.comparison(host)?;
+            value = (value != 0 && rhs != 0) as i32;
+        }
+    }
+
+    fn comparison<H: Host>(&mut self, host: &mut H) -> Result<i32, Error> {
+        let lhs = self.additive(host)?;
+        self.skip_spaces();
+        let first = self.peek();
+        if first != b'=' && first != b'<' && first != b'>' {
+            return Ok(lhs);
+        }
+        self.at.pos += 1;
+        let second = self.peek();
+        let op = match (first, second) {
+            (b'<', b'>') | (b'<', b'=') | (b'>', b'=') => {
+                self.at.pos += 1;
+                (first, second)
+            }
+            _ => (first, 0),
+        };
+        let rhs = self.additive(host)?;
+        let result = match op {
+            (b'=', _) => lhs == rhs,
+            (b'<', b'>') => lhs != rhs,
+            (b'<', b'=') => lhs <= rhs,
+            (b'>', b'=') => lhs >= rhs,
+            (b'<', _) => lhs < rhs,
+            _ => lhs > rhs,
+        };
+        Ok(result as i32)
+    }
+
+    fn additive<H: Host>(&mut self, host: &mut H) -> Result<i32, Error> {
+        let mut value = self.term(host)?;
+        loop {
+            self.skip_spaces();
+            match self.peek() {
+                b'+' => {
+                    self.at.pos += 1;
+                    value = value.wrapping_add(self.term(host)?);
+                }
+                b'-' => {
+                    self.at.pos += 1;
+                    value = value.wrapping_sub(self.term(host)?);
+                }
+                _ => return Ok(value),
+            }
+        }
+    }
+
+    fn term<H: Host>(&mut self, host: &mut H) -> Result<i32, Error> {
+        let mut value = self.unary(host)?;
+        loop {
+            self.skip_spaces();
+            let op = self.peek();
+            if op != b'*' && op != b'/' && op != MOD {
+                return Ok(value);
+            }
+            self.at.pos += 1;
+            let rhs = self.unary(host)?;
+            value = match op {
+                b'*' => value.wrapping_mul(rhs),
+                _ if rhs == 0 => return Err(Error::DivideByZero),
+                b'/' => value.wrapping_div(rhs),
+                _ => value.wrapping_rem(rhs),
+            };
+        }
+    }
+
+    fn unary<H: Host>(&mut self, host: &mut H) -> Result<i32, Error> {
+        self.skip_spaces();
+        match self.peek() {
+            b'-' => {
+                self.at.pos += 1;
+                Ok(self.unary(host)?.wrapping_neg())
+            }
+            NOT => {
+                self.at.pos += 1;
+                Ok((self.unary(host)? == 0) as i32)
+            }
+            _ => self.primary(host),
+        }
+    }
+
+    fn primary<H: Host>(&mut self, host: &mut H) -> Result<i32, Error> {
+        self.skip_spaces();
+        match self.next_byte() {
+            digit @ b'0'...b'9' => {
+                let mut value = i32::from(digit - b'0');
+                while self.peek().is_ascii_digit() {
+                    let digit = i32::from(self.next_byte() - b'0');
+                    value = value.wrapping_mul(10).wrapping_add(digit);
+                }
+                Ok(value)
+            }
+            var @ b'A'...b'Z' => Ok(self.vars[usize::from(var - b'A')]),
+            b'(' => {
+                let value = self.expr(host)?;
+                self.expect(b')')?;
+                Ok(value)
+            }
+            function @ PEEK | function @ RND | function @ ABS => {
+                self.expect(b'(')?;
+                let arg = self.expr(host)?;
+                self.expect(b')')?;
+                Ok(match function {
+                    PEEK => i32::from(host.peek(arg as u32)),
+                    RND => random::below(arg.max(0) as u32) as i32,
+                    _ => arg.wrapping_abs(),
+                })
+            }
+            _ => Err(Error::Syntax),
+        }
+    }
+
+    fn variable(&mut self) -> Result<usize, Error> {
+        self.skip_spaces();
+        match self.peek() {
+            var @ b'A'...b'Z' => {
+                self.at.pos += 1;
+                Ok(usize::from(var - b'A'))
+            }
+            _ => Err(Error::Syntax),
+        }
+    }
+
+    fn goto_line(&mut self, number: i32) -> Result<(), Error> {
+        let mut offset = 0;
+        while offset < self.len {
+            if i32::from(self.line_number(offset)) == number {
+                self.goto_offset(offset);
+                return Ok(());
+            }
+            offset += self.line_len(offset);
+        }
+        Err(Error::NoSuchLine)
+    }
+
+    fn goto_offset(&mut self, offset: usize) {
+        self.at = Position {
+            line: Some(offset),
+            pos: offset + HEADER_LEN,
+            end: offset + self.line_len(offset),
+        };
+    }
+
+    /// The byte at the current position - 0 at the end of the line.
+    fn peek(&self) -> u8 {
+        if self.at.pos >= self.at.end {
+            return 0;
+        }
+        match self.at.line {
+            Some(_) => self.program[self.at.pos],
+            None => self.direct[self.at.pos],
+        }
+    }
+
+    fn next_byte(&mut self) -> u8 {
+        let byte = self.peek();
+        if byte != 0 {
+            self.at.pos += 1;
+        }
+        byte
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek() == b' ' {
+            self.at.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), Error> {
+        self.skip_spaces();
+        if self.next_byte() == byte {
+            Ok(())
+        } else {
+            Err(Error::Syntax)
+        }
+    }
+}
+
+/// Turn keywords into tokens, and everything outside quotes into capitals.
+/// Gives the tokenised length.
+fn tokenise(text: &[u8], out: &mut [u8]) -> Result<usize, Error> {
+    let mut len = 0;
+    let mut i = 0;
+    let mut quoted = false;
+    // After a REM we leave the comment exactly as typed
+    let mut comment = false;
+    while i < text.len() {
+        if len == out.len() {
+            return Err(Error::LineTooLong);
+        }
+        let mut byte = text[i];
+        // Anything from 0x80 up would look like a token
+        if byte >= 0x80 || byte < 0x20 {
+            return Err(Error::Syntax);
+        }
+        i += 1;
+        if byte == b'"' && !comment {
+            quoted = !quoted;
+        } else if !quoted && !comment {
+            match KEYWORDS.iter().position(|k| starts_with(&text[i - 1..], k)) {
+                Some(index) => {
+                    byte = PRINT + index as u8;
+                    i += KEYWORDS[index].len() - 1;
+                    comment = byte == REM;
+                }
+                None => byte = byte.to_ascii_uppercase(),
+            }
+        }
+        out[len] = byte;
+        len += 1;
+    }
+    Ok(len)
+}
+
+fn starts_with(text: &[u8], keyword: &str) -> bool {
+    text.len() >= keyword.len() && text[0..keyword.len()].eq_ignore_ascii_case(keyword.as_bytes())
+}
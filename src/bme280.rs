@@ -0,0 +1,210 @@
+//! Drives a Bosch BME280 temperature, pressure and humidity sensor over I2C.
+//!
+//! The raw readings are meaningless without the per-chip calibration data,
+//! and the compensation formulas are fairly hairy. We use the integer
+//! versions from the datasheet (section 4.2.3, and 8.2 for the 64-bit
+//! pressure one), so there's no floating point.
+//!
+//! We run the sensor in normal mode, taking a reading every second with 1x
+//! oversampling, which is what Bosch recommend for weather monitoring.
+
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+
+/// The address with SDO tied low. Tie it high for 0x77.
+pub const DEFAULT_ADDRESS: u8 = 0x76;
+
+const REG_CALIB00: u8 = 0x88;
+const REG_ID: u8 = 0xD0;
+const REG_CALIB26: u8 = 0xE1;
+const REG_CTRL_HUM: u8 = 0xF2;
+const REG_CTRL_MEAS: u8 = 0xF4;
+const REG_CONFIG: u8 = 0xF5;
+const REG_PRESS_MSB: u8 = 0xF7;
+
+const CHIP_ID: u8 = 0x60;
+
+/// Humidity oversampling x1.
+const CTRL_HUM_X1: u8 = 0x01;
+
+/// Temperature and pressure oversampling x1, normal mode.
+const CTRL_MEAS_X1_NORMAL: u8 = 0x27;
+
+/// 1000ms standby, filter off.
+const CONFIG_1S: u8 = 0xA0;
+
+/// Something went wrong talking to the BME280.
+#[derive(Debug)]
+pub enum Error<E> {
+    /// The I2C bus reported an error
+    I2c(E),
+    /// The ID register wasn't 0x60 (a BMP280 gives 0x58)
+    WrongChip(u8),
+}
+
+/// One set of compensated readings.
+#[derive(Clone, Copy, Debug)]
+pub struct Measurement {
+    /// Hundredths of a degree C
+    pub temperature: i32,
+    /// Pascals
+    pub pressure: u32,
+    /// Thousandths of a percent relative humidity
+    pub humidity: u32,
+}
+
+/// The factory calibration, read once at start-up.
+struct Calibration {
+    t1: u16,
+    t2: i16,
+    t3: i16,
+    p1: u16,
+    p2: i16,
+    p3: i16,
+    p4: i16,
+    p5: i16,
+    p6: i16,
+    p7: i16,
+    p8: i16,
+    p9: i16,
+    h1: u8,
+    h2: i16,
+    h3: u8,
+    h4: i16,
+    h5: i16,
+    h6: i8,
+}
+
+pub struct Bme280<I2C> {
+    i2c: I2C,
+    address: u8,
+    calib: Calibration,
+}
+
+impl<I2C, E> Bme280<I2C>
+where
+    I2C: Write<Error = E> + WriteRead<Error = E>,
+{
+    /// Check the chip ID, read the calibration and start measuring.
+    pub fn new(mut i2c: I2C, address: u8) -> Result<Self, Error<E>> {
+        let mut id = [0u8];
+        i2c.write_read(address, &[REG_ID], &mut id)
+            .map_err(Error::I2c)?;
+        if id[0] != CHIP_ID {
+            return Err(Error::WrongChip(id[0]));
+        }
+
+        let mut c = [0u8; 26];
+        i2c.write_read(address, &[REG_CALIB00], &mut c)
+            .map_err(Error::I2c)?;
+        let mut h = [0u8; 7];
+        i2c.write_read(address, &[REG_CALIB26], &mut h)
+            .map_err(Error::I2c)?;
+        let le_u16 = |lo: u8, hi: u8| u16::from(lo) | (u16::from(hi) << 8);
+        let le_i16 = |lo: u8, hi: u8| le_u16(lo, hi) as i16;
+        let calib = Calibration {
+            t1: le_u16(c[0], c[1]),
+            t2: le_i16(c[2], c[3]),
+            t3: le_i16(c[4], c[5]),
+            p1: le_u16(c[6], c[7]),
+            p2: le_i16(c[8], c[9]),
+            p3: le_i16(c[10], c[11]),
+            p4: le_i16(c[12], c[13]),
+            p5: le_i16(c[14], c[15]),
+            p6: le_i16(c[16], c[17]),
+            p7: le_i16(c[18], c[19]),
+            p8: le_i16(c[20], c[21]),
+            p9: le_i16(c[22], c[23]),
+            h1: c[25],
+            h2: le_i16(h[0], h[1]),
+            h3: h[2],
+            // These two are 12-bit values sharing a nibble
+            h4: (i16::from(h[3] as i8) << 4) | i16::from(h[4] & 0x0F),
+            h5: (i16::from(h[5] as i8) << 4) | i16::from(h[4] >> 4),
+            h6: h[6] as i8,
+        };
+
+        let mut sensor = Bme280 {
+            i2c,
+            address,
+            calib,
+        };
+        // ctrl_hum only takes effect after a write to ctrl_meas
+        sensor.write_register(REG_CTRL_HUM, CTRL_HUM_X1)?;
+        sensor.write_register(REG_CONFIG, CONFIG_1S)?;
+        sensor.write_register(REG_CTRL_MEAS, CTRL_MEAS_X1_NORMAL)?;
+        Ok(sensor)
+    }
+
+    /// Give the bus back.
+    pub fn free(self) -> I2C {
+        self.i2c
+    }
+
+    /// Read and compensate the latest measurement.
+    pub fn read(&mut self) -> Result<Measurement, Error<E>> {
+        let mut d = [0u8; 8];
+        self.i2c
+            .write_read(self.address, &[REG_PRESS_MSB], &mut d)
+            .map_err(Error::I2c)?;
+        let adc_p = (i32::from(d[0]) << 12) | (i32::from(d[1]) << 4) | (i32::from(d[2]) >> 4);
+        let adc_t = (i32::from(d[3]) << 12) | (i32::from(d[4]) << 4) | (i32::from(d[5]) >> 4);
+        let adc_h = (i32::from(d[6]) << 8) | i32::from(d[7]);
+
+        let t_fine = self.t_fine(adc_t);
+        Ok(Measurement {
+            temperature: (t_fine * 5 + 128) >> 8,
+            pressure: self.compensate_pressure(adc_p, t_fine),
+            humidity: (self.compensate_humidity(adc_h, t_fine) * 1000) >> 10,
+        })
+    }
+
+    /// The fine resolution temperature, which the other two formulas need.
+    fn t_fine(&self, adc_t: i32) -> i32 {
+        let c = &self.calib;
+        let t1 = i32::from(c.t1);
+        let var1 = (((adc_t >> 3) - (t1 << 1)) * i32::from(c.t2)) >> 11;
+        let var2 = (((((adc_t >> 4) - t1) * ((adc_t >> 4) - t1)) >> 12) * i32::from(c.t3)) >> 14;
+        var1 + var2
+    }
+
+    /// Pressure in Pa.
+    fn compensate_pressure(&self, adc_p: i32, t_fine: i32) -> u32 {
+        let c = &self.calib;
+        let mut var1 = i64::from(t_fine) - 128_000;
+        let mut var2 = var1 * var1 * i64::from(c.p6);
+        var2 += (var1 * i64::from(c.p5)) << 17;
+        var2 += i64::from(c.p4) << 35;
+        var1 = ((var1 * var1 * i64::from(c.p3)) >> 8) + ((var1 * i64::from(c.p2)) << 12);
+        var1 = (((1i64 << 47) + var1) * i64::from(c.p1)) >> 33;
+        if var1 == 0 {
+            // Avoid dividing by zero
+            return 0;
+        }
+        let mut p = 1_048_576 - i64::from(adc_p);
+        p = (((p << 31) - var2) * 3125) / var1;
+        var1 = (i64::from(c.p9) * (p >> 13) * (p >> 13)) >> 25;
+        var2 = (i64::from(c.p8) * p) >> 19;
+        p = ((p + var1 + var2) >> 8) + (i64::from(c.p7) << 4);
+        // p is now in Q24.8 Pa
+        (p >> 8) as u32
+    }
+
+    /// Humidity in Q22.10 %RH.
+    fn compensate_humidity(&self, adc_h: i32, t_fine: i32) -> u32 {
+        let c = &self.calib;
+        let x = t_fine - 76_800;
+        let mut v = (((adc_h << 14) - (i32::from(c.h4) << 20) - (i32::from(c.h5) * x)) + 16_384)
+            >> 15;
+        v *= ((((((x * i32::from(c.h6)) >> 10) * (((x * i32::from(c.h3)) >> 11) + 32_768))
+            >> 10) + 2_097_152) * i32::from(c.h2) + 8192) >> 14;
+        v -= ((((v >> 15) * (v >> 15)) >> 7) * i32::from(c.h1)) >> 4;
+        v = v.max(0).min(419_430_400);
+        (v >> 12) as u32
+    }
+
+    fn write_register(&mut self, reg: u8, value: u8) -> Result<(), Error<E>> {
+        self.i2c
+            .write(self.address, &[reg, value])
+            .map_err(Error::I2c)
+    }
+}
@@ -0,0 +1,538 @@
+//! The LaunchPad itself.
+//!
+//! Nearly every example starts the same way: the PLL, UART0 at 115200 bps
+//! (or whatever `demo::config` says) on the debug USB port, and the LEDs and
+//! the two switches. `board!` does all of that:
+//!
+//! ``` ignore
+//! let p = hal::Peripherals::take().unwrap();
+//! let mut board = board!(p);
+//! board.led_red.on();
+//! if board.sw1.is_pressed() {
+//!     writeln!(board.tx, "SW1 is down").unwrap();
+//! }
+//! ```
+//!
+//! Everything else in `p` is still yours. Use `board.power_control` and
+//! `board.clocks` to set it up, and `board.enable` to power it up. The rest
+//! of Port A (everything but the UART pins) is in `board.porta`.
+//!
+//! There are two boards, picked with a Cargo feature:
+//!
+//! * `tm4c123` (the default) - the EK-TM4C123GXL LaunchPad. The system
+//!   clock is 80 MHz from the 16 MHz crystal, the RGB LED is on PF1 to PF3
+//!   and the switches are PF4 (SW1) and PF0 (SW2).
+//! * `tm4c129` - the EK-TM4C1294XL Connected LaunchPad. Build with
+//!   `--no-default-features --features tm4c129`. Its PLL runs at 480 MHz
+//!   from the 25 MHz crystal, and is divided down to a 120 MHz system
+//!   clock, which needs five flash wait states (see `set_up_clock`). It
+//!   has four LEDs of one colour rather than an RGB LED, so `led_red`,
+//!   `led_green` and `led_blue` are D1 (PN1), D2 (PN0) and D3 (PF4). The
+//!   switches are USR_SW1 (PJ0) and USR_SW2 (PJ1).
+//!
+//! The VGA driver is wired to the TM4C123's pins and timers (SSI2 on PB7,
+//! HSYNC on PB6 and VSYNC on PC4), so the VGA examples only build for the
+//! TM4C123, and so do the few that use peripherals the TM4C1294 doesn't
+//! have (wide timers, PWM1 and QEI1). The others
+//! build for both - they pick their pins from `demo::config`, which knows
+//! about both chips.
+
+use config;
+use embedded_hal::digital::{InputPin, OutputPin};
+use hal::gpio::gpioa::{GpioControl, PA2, PA3, PA4, PA5, PA6, PA7};
+#[cfg(feature = "tm4c123")]
+use hal::gpio::gpiof::{PF0, PF1, PF2, PF3, PF4};
+#[cfg(feature = "tm4c129")]
+use hal::gpio::gpiof::PF4;
+#[cfg(feature = "tm4c129")]
+use hal::gpio::gpioj::{PJ0, PJ1};
+#[cfg(feature = "tm4c129")]
+use hal::gpio::gpion::{PN0, PN1};
+use hal::gpio::{GpioExt, Input, Output, PullUp, PushPull, Tristate};
+use hal::serial::{NewlineMode, Rx, Serial, Tx};
+use hal::sysctl::{self, Clocks, PowerControl, SysctlExt};
+#[cfg(feature = "tm4c129")]
+use hal::time::Hertz;
+use hal::time::U32Ext;
+#[cfg(feature = "tm4c123")]
+use pac::{GPIO_PORTA, GPIO_PORTF, SYSCTL, UART0};
+#[cfg(feature = "tm4c129")]
+use pac::{GPIO_PORTA_AHB, GPIO_PORTF_AHB, GPIO_PORTJ_AHB, GPIO_PORTN, SYSCTL, UART0};
+
+/// What `Board::new` sets the system clock to, for anything that needs
+/// to know before it has `board.clocks`.
+#[cfg(feature = "tm4c123")]
+pub const SYSCLK_HZ: u32 = 80_000_000;
+/// What `Board::new` sets the system clock to, for anything that needs
+/// to know before it has `board.clocks`.
+#[cfg(feature = "tm4c129")]
+pub const SYSCLK_HZ: u32 = 120_000_000;
+
+/// The Connected LaunchPad's crystal.
+#[cfg(feature = "tm4c129")]
+const CRYSTAL_HZ: u32 = 25_000_000;
+/// What the PLL runs at.
+#[cfg(feature = "tm4c129")]
+const VCO_HZ: u32 = 480_000_000;
+
+// MOSCCTL: the crystal is above 10 MHz, and everything else is off
+#[cfg(feature = "tm4c129")]
+const MOSCCTL_OSCRNG: u32 = 1 << 4;
+// RIS: the main oscillator has powered up
+#[cfg(feature = "tm4c129")]
+const RIS_MOSCPUPRIS: u32 = 1 << 8;
+// RSCLKCFG
+#[cfg(feature = "tm4c129")]
+const RSCLKCFG_MEMTIMU: u32 = 1 << 31;
+#[cfg(feature = "tm4c129")]
+const RSCLKCFG_NEWFREQ: u32 = 1 << 30;
+#[cfg(feature = "tm4c129")]
+const RSCLKCFG_USEPLL: u32 = 1 << 28;
+#[cfg(feature = "tm4c129")]
+const RSCLKCFG_PLLSRC_MOSC: u32 = 0x3 << 24;
+#[cfg(feature = "tm4c129")]
+const RSCLKCFG_OSCSRC_MOSC: u32 = 0x3 << 20;
+// PLLFREQ0 and PLLFREQ1: VCO = 25 MHz / (N + 1) * MINT = 5 MHz * 96
+#[cfg(feature = "tm4c129")]
+const PLLFREQ0_PLLPWR: u32 = 1 << 23;
+#[cfg(feature = "tm4c129")]
+const PLLFREQ0_MINT: u32 = 96;
+#[cfg(feature = "tm4c129")]
+const PLLFREQ1_N: u32 = 4;
+// PLLSTAT
+#[cfg(feature = "tm4c129")]
+const PLLSTAT_LOCK: u32 = 1 << 0;
+/// MEMTIM0 for 100 to 120 MHz, from the data sheet: five wait states and
+/// a 3.5 clock high time for both flash (FWS, FBCHT) and EEPROM (EWS,
+/// EBCHT). Bits 4 and 20 must be written as one.
+#[cfg(feature = "tm4c129")]
+const MEMTIM0_120MHZ: u32 = (6 << 22) | (1 << 20) | (5 << 16) | (6 << 6) | (1 << 4) | 5;
+
+/// One colour of the RGB LED. It's lit when the pin is high.
+pub struct Led<P> {
+    pin: P,
+}
+
+/// SW1 or SW2. They pull the pin low when pressed.
+pub struct Switch<P> {
+    pin: P,
+}
+
+/// The Port A pins the UART doesn't use.
+pub struct PortA {
+    pub pa2: PA2<Tristate>,
+    pub pa3: PA3<Tristate>,
+    pub pa4: PA4<Tristate>,
+    pub pa5: PA5<Tristate>,
+    pub pa6: PA6<Tristate>,
+    pub pa7: PA7<Tristate>,
+    pub control: GpioControl,
+}
+
+/// Everything on the LaunchPad, ready to use.
+#[cfg(feature = "tm4c123")]
+pub struct Board {
+    /// The system clock, at 80 MHz
+    pub clocks: Clocks,
+    /// For setting up other peripherals
+    pub power_control: PowerControl,
+    /// UART0, through the debug USB port. `write!` sends `\n` as `\r\n`,
+    /// but bytes sent with `write` go as they are.
+    pub tx: Tx<UART0>,
+    pub rx: Rx<UART0>,
+    /// What's left of Port A
+    pub porta: PortA,
+    /// PF1
+    pub led_red: Led<PF1<Output<PushPull>>>,
+    /// PF2
+    pub led_blue: Led<PF2<Output<PushPull>>>,
+    /// PF3
+    pub led_green: Led<PF3<Output<PushPull>>>,
+    /// PF4
+    pub sw1: Switch<PF4<Input<PullUp>>>,
+    /// PF0
+    pub sw2: Switch<PF0<Input<PullUp>>>,
+}
+
+/// Everything on the Connected LaunchPad, ready to use.
+#[cfg(feature = "tm4c129")]
+pub struct Board {
+    /// The system clock, at 120 MHz
+    pub clocks: Clocks,
+    /// For setting up other peripherals
+    pub power_control: PowerControl,
+    /// UART0, through the debug USB port. `write!` sends `\n` as `\r\n`,
+    /// but bytes sent with `write` go as they are.
+    pub tx: Tx<UART0>,
+    pub rx: Rx<UART0>,
+    /// What's left of Port A
+    pub porta: PortA,
+    /// D1, on PN1
+    pub led_red: Led<PN1<Output<PushPull>>>,
+    /// D3, on PF4
+    pub led_blue: Led<PF4<Output<PushPull>>>,
+    /// D2, on PN0
+    pub led_green: Led<PN0<Output<PushPull>>>,
+    /// USR_SW1, on PJ0
+    pub sw1: Switch<PJ0<Input<PullUp>>>,
+    /// USR_SW2, on PJ1
+    pub sw2: Switch<PJ1<Input<PullUp>>>,
+}
+
+/// `Board::new`, with the right peripherals for the chip. `p` is the
+/// `hal::Peripherals`, which must still have them all.
+#[cfg(feature = "tm4c123")]
+#[macro_export]
+macro_rules! board {
+    ($p:expr) => {
+        $crate::board::Board::new($p.SYSCTL, $p.GPIO_PORTA, $p.GPIO_PORTF, $p.UART0)
+    };
+}
+
+/// `Board::new`, with the right peripherals for the chip. `p` is the
+/// `hal::Peripherals`, which must still have them all.
+#[cfg(feature = "tm4c129")]
+#[macro_export]
+macro_rules! board {
+    ($p:expr) => {
+        $crate::board::Board::new(
+            $p.SYSCTL,
+            $p.GPIO_PORTA_AHB,
+            $p.GPIO_PORTF_AHB,
+            $p.GPIO_PORTJ_AHB,
+            $p.GPIO_PORTN,
+            $p.UART0,
+        )
+    };
+}
+
+/// One of the GPIO ports in `p`, ready to `split`:
+///
+/// ``` ignore
+/// let mut portb = gpio_port!(p, B).split(&board.power_control);
+/// ```
+///
+/// It's `p.GPIO_PORTB` on the TM4C123, and `p.GPIO_PORTB_AHB` on the
+/// TM4C1294, where ports A to J are only on the AHB.
+#[cfg(feature = "tm4c123")]
+#[macro_export]
+macro_rules! gpio_port {
+    ($p:expr, A) => {
+        $p.GPIO_PORTA
+    };
+    ($p:expr, B) => {
+        $p.GPIO_PORTB
+    };
+    ($p:expr, C) => {
+        $p.GPIO_PORTC
+    };
+    ($p:expr, D) => {
+        $p.GPIO_PORTD
+    };
+    ($p:expr, E) => {
+        $p.GPIO_PORTE
+    };
+    ($p:expr, F) => {
+        $p.GPIO_PORTF
+    };
+}
+
+/// One of the GPIO ports in `p`, ready to `split`. Ports A to J are the
+/// `_AHB` ones, and K to Q only come one way.
+#[cfg(feature = "tm4c129")]
+#[macro_export]
+macro_rules! gpio_port {
+    ($p:expr, A) => {
+        $p.GPIO_PORTA_AHB
+    };
+    ($p:expr, B) => {
+        $p.GPIO_PORTB_AHB
+    };
+    ($p:expr, C) => {
+        $p.GPIO_PORTC_AHB
+    };
+    ($p:expr, D) => {
+        $p.GPIO_PORTD_AHB
+    };
+    ($p:expr, E) => {
+        $p.GPIO_PORTE_AHB
+    };
+    ($p:expr, F) => {
+        $p.GPIO_PORTF_AHB
+    };
+    ($p:expr, G) => {
+        $p.GPIO_PORTG_AHB
+    };
+    ($p:expr, H) => {
+        $p.GPIO_PORTH_AHB
+    };
+    ($p:expr, J) => {
+        $p.GPIO_PORTJ_AHB
+    };
+    ($p:expr, K) => {
+        $p.GPIO_PORTK
+    };
+    ($p:expr, L) => {
+        $p.GPIO_PORTL
+    };
+    ($p:expr, M) => {
+        $p.GPIO_PORTM
+    };
+    ($p:expr, N) => {
+        $p.GPIO_PORTN
+    };
+    ($p:expr, P) => {
+        $p.GPIO_PORTP
+    };
+    ($p:expr, Q) => {
+        $p.GPIO_PORTQ
+    };
+}
+
+#[cfg(feature = "tm4c123")]
+impl Board {
+    /// Set the clock up and take over UART0 and the LED and switches. The
+    /// LED starts off.
+    pub fn new(
+        sysctl: SYSCTL,
+        gpio_porta: GPIO_PORTA,
+        gpio_portf: GPIO_PORTF,
+        uart0: UART0,
+    ) -> Board {
+        let mut sc = sysctl.constrain();
+        sc.clock_setup.oscillator = sysctl::Oscillator::Main(
+            sysctl::CrystalFrequency::_16mhz,
+            sysctl::SystemClock::UsePll(sysctl::PllOutputFrequency::_80_00mhz),
+        );
+        let clocks = sc.clock_setup.freeze();
+
+        let mut porta = gpio_porta.split(&sc.power_control);
+        let mut portf = gpio_portf.split(&sc.power_control);
+
+        let uart = Serial::uart0(
+            uart0,
+            porta.pa1.into_af1(&mut porta.control),
+            porta.pa0.into_af1(&mut porta.control),
+            (),
+            (),
+            config::CONSOLE_BAUD.bps(),
+            NewlineMode::SwapLFtoCRLF,
+            &clocks,
+            &sc.power_control,
+        );
+        let (tx, rx) = uart.split();
+
+        let mut board = Board {
+            clocks,
+            power_control: sc.power_control,
+            tx,
+            rx,
+            porta: PortA {
+                pa2: porta.pa2,
+                pa3: porta.pa3,
+                pa4: porta.pa4,
+                pa5: porta.pa5,
+                pa6: porta.pa6,
+                pa7: porta.pa7,
+                control: porta.control,
+            },
+            led_red: Led::new(portf.pf1.into_push_pull_output()),
+            led_blue: Led::new(portf.pf2.into_push_pull_output()),
+            led_green: Led::new(portf.pf3.into_push_pull_output()),
+            sw1: Switch::new(portf.pf4.into_pull_up_input()),
+            // PF0 is an NMI pin, so it's locked until we ask
+            sw2: Switch::new(portf.pf0.unlock(&mut portf.control).into_pull_up_input()),
+        };
+        board.led_red.off();
+        board.led_blue.off();
+        board.led_green.off();
+        board
+    }
+}
+
+#[cfg(feature = "tm4c129")]
+impl Board {
+    /// Set the clock up and take over UART0 and the LEDs and switches. The
+    /// LEDs start off.
+    pub fn new(
+        sysctl: SYSCTL,
+        gpio_porta: GPIO_PORTA_AHB,
+        gpio_portf: GPIO_PORTF_AHB,
+        gpio_portj: GPIO_PORTJ_AHB,
+        gpio_portn: GPIO_PORTN,
+        uart0: UART0,
+    ) -> Board {
+        set_up_clock(&sysctl);
+        let sc = sysctl.constrain();
+        let clocks = Clocks {
+            osc: Hertz(CRYSTAL_HZ),
+            sysclk: Hertz(SYSCLK_HZ),
+        };
+
+        let mut porta = gpio_porta.split(&sc.power_control);
+        let portf = gpio_portf.split(&sc.power_control);
+        let portj = gpio_portj.split(&sc.power_control);
+        let portn = gpio_portn.split(&sc.power_control);
+
+        let uart = Serial::uart0(
+            uart0,
+            porta.pa1.into_af1(&mut porta.control),
+            porta.pa0.into_af1(&mut porta.control),
+            (),
+            (),
+            config::CONSOLE_BAUD.bps(),
+            NewlineMode::SwapLFtoCRLF,
+            &clocks,
+            &sc.power_control,
+        );
+        let (tx, rx) = uart.split();
+
+        let mut board = Board {
+            clocks,
+            power_control: sc.power_control,
+            tx,
+            rx,
+            porta: PortA {
+                pa2: porta.pa2,
+                pa3: porta.pa3,
+                pa4: porta.pa4,
+                pa5: porta.pa5,
+                pa6: porta.pa6,
+                pa7: porta.pa7,
+                control: porta.control,
+            },
+            led_red: Led::new(portn.pn1.into_push_pull_output()),
+            led_blue: Led::new(portf.pf4.into_push_pull_output()),
+            led_green: Led::new(portn.pn0.into_push_pull_output()),
+            sw1: Switch::new(portj.pj0.into_pull_up_input()),
+            sw2: Switch::new(portj.pj1.into_pull_up_input()),
+        };
+        board.led_red.off();
+        board.led_blue.off();
+        board.led_green.off();
+        board
+    }
+}
+
+impl Board {
+    /// Power up (and reset) a peripheral.
+    pub fn enable(&mut self, domain: sysctl::Domain) {
+        let pc = &mut self.power_control;
+        sysctl::control_power(pc, domain, sysctl::RunMode::Run, sysctl::PowerState::On);
+        sysctl::control_power(pc, domain, sysctl::RunMode::Sleep, sysctl::PowerState::On);
+        sysctl::reset(pc, domain);
+    }
+
+    /// Change UART0's baud rate (say, to what `settings` asks for). Waits
+    /// for anything being sent to go first.
+    pub fn set_baud(&mut self, baud: u32) {
+        let uart = unsafe { &*UART0::ptr() };
+        while uart.fr.read().busy().bit_is_set() {}
+        // The divisor is sysclk / (16 * baud), with six fractional bits
+        let divisor = ((self.clocks.sysclk.0 * 4) + (baud / 2)) / baud;
+        uart.ctl.modify(|_, w| w.uarten().clear_bit());
+        uart.ibrd.write(|w| unsafe { w.divint().bits((divisor >> 6) as u16) });
+        uart.fbrd.write(|w| unsafe { w.divfrac().bits((divisor & 0x3F) as u8) });
+        // The new divisor only takes with a write to LCRH
+        uart.lcrh.modify(|r, w| unsafe { w.bits(r.bits()) });
+        uart.ctl.modify(|_, w| w.uarten().set_bit());
+    }
+}
+
+/// Run the TM4C1294 at 120 MHz from the PLL. The flash can't keep up at
+/// that speed, so the new wait states go in MEMTIM0 before the switch
+/// (MEMTIMU applies them as the clock changes).
+#[cfg(feature = "tm4c129")]
+fn set_up_clock(sysctl: &SYSCTL) {
+    // Start the crystal, and wait for it
+    sysctl.moscctl.write(|w| unsafe { w.bits(MOSCCTL_OSCRNG) });
+    while sysctl.ris.read().bits() & RIS_MOSCPUPRIS == 0 {}
+
+    // It feeds the PLL, and the system clock until we switch
+    sysctl
+        .rsclkcfg
+        .write(|w| unsafe { w.bits(RSCLKCFG_PLLSRC_MOSC | RSCLKCFG_OSCSRC_MOSC) });
+    sysctl.pllfreq1.write(|w| unsafe { w.bits(PLLFREQ1_N) });
+    sysctl
+        .pllfreq0
+        .write(|w| unsafe { w.bits(PLLFREQ0_PLLPWR | PLLFREQ0_MINT) });
+    sysctl.memtim0.write(|w| unsafe { w.bits(MEMTIM0_120MHZ) });
+    sysctl
+        .rsclkcfg
+        .modify(|r, w| unsafe { w.bits(r.bits() | RSCLKCFG_NEWFREQ) });
+    while sysctl.pllstat.read().bits() & PLLSTAT_LOCK == 0 {}
+
+    let psysdiv = (VCO_HZ / SYSCLK_HZ) - 1;
+    sysctl.rsclkcfg.modify(|r, w| unsafe {
+        w.bits(r.bits() | RSCLKCFG_MEMTIMU | RSCLKCFG_USEPLL | psysdiv)
+    });
+}
+
+impl<P> Led<P>
+where
+    P: OutputPin,
+{
+    pub fn new(pin: P) -> Led<P> {
+        Led { pin }
+    }
+
+    pub fn on(&mut self) {
+        self.pin.set_high();
+    }
+
+    pub fn off(&mut self) {
+        self.pin.set_low();
+    }
+
+    pub fn set(&mut self, on: bool) {
+        if on {
+            self.on();
+        } else {
+            self.off();
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        let on = self.is_on();
+        self.set(!on);
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.pin.is_high()
+    }
+}
+
+/// So an `Led` can go anywhere a pin can.
+impl<P> OutputPin for Led<P>
+where
+    P: OutputPin,
+{
+    fn is_high(&self) -> bool {
+        self.pin.is_high()
+    }
+
+    fn is_low(&self) -> bool {
+        self.pin.is_low()
+    }
+
+    fn set_high(&mut self) {
+        self.pin.set_high()
+    }
+
+    fn set_low(&mut self) {
+        self.pin.set_low()
+    }
+}
+
+impl<P> Switch<P>
+where
+    P: InputPin,
+{
+    pub fn new(pin: P) -> Switch<P> {
+        Switch { pin }
+    }
+
+    pub fn is_pressed(&self) -> bool {
+        self.pin.is_low()
+    }
+}
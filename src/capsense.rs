@@ -0,0 +1,234 @@
+//! Touch buttons made from nothing but a bit of copper and a resistor.
+//!
+//! Every pad is wired to one common 'send' pin through a large resistor
+//! (1M to 10M - bigger is more sensitive, but slower) and directly to its
+//! own 'receive' pin. When the send pin goes high, each receive pin follows
+//! it after a delay set by the resistor and the pad's capacitance. A finger
+//! on (or near) the pad adds capacitance, so the delay gets longer.
+//!
+//! All the pins must be on the same GPIO port, which the caller must have
+//! powered up. We time the charge with a 16/32-bit timer, which the caller
+//! must also power up and then leave alone.
+//!
+//! Interrupts can only make a charge look slower than it really was, so we
+//! take the fastest of several charges rather than turning them off.
+
+use config::GpioRegisters;
+use pac::timer0;
+
+/// The most pads we can look after.
+pub const MAX_PADS: usize = 8;
+
+/// How many charges we time for each measurement.
+const CHARGES: usize = 8;
+
+/// Give up on a pad after this many clocks (1ms at 80 MHz) - it's probably
+/// not connected.
+const TIMEOUT: u32 = 80_000;
+
+/// How many measurements `calibrate` averages.
+const CALIBRATE_SAMPLES: u32 = 16;
+
+/// Without being told otherwise, a pad is touched when it takes this much
+/// longer than usual to charge, as a fraction (1/n) of its baseline.
+const DEFAULT_THRESHOLD_DIVISOR: u32 = 4;
+
+/// When a pad isn't touched, its baseline moves 1/n of the way towards
+/// each new reading, to follow slow changes in temperature and humidity.
+const DRIFT_DIVISOR: u32 = 64;
+
+// GPTMCFG, GPTMTAMR, GPTMCTL
+const CFG_32_BIT: u32 = 0x0;
+const TAMR_PERIODIC: u32 = 0x2;
+const CTL_TAEN: u32 = 1 << 0;
+
+#[derive(Clone, Copy)]
+struct Pad {
+    mask: u32,
+    baseline: u32,
+    threshold: u32,
+    reading: u32,
+    touched: bool,
+}
+
+/// A set of touch pads sharing a send pin.
+pub struct CapSense {
+    port: &'static GpioRegisters,
+    timer: &'static timer0::RegisterBlock,
+    send: u32,
+    pads: [Pad; MAX_PADS],
+    count: usize,
+}
+
+impl CapSense {
+    /// Set up the pins and start the timer running. `send` and `pads` are
+    /// pin numbers (0..7) on `port`. Pads past `MAX_PADS` are ignored.
+    ///
+    /// Call `calibrate` (with nobody touching anything) before use.
+    pub fn new(
+        port: &'static GpioRegisters,
+        send: u8,
+        pads: &[u8],
+        timer: &'static timer0::RegisterBlock,
+    ) -> CapSense {
+        let mut c = CapSense {
+            port,
+            timer,
+            send: 1 << send,
+            pads: [Pad {
+                mask: 0,
+                baseline: 0,
+                threshold: 0,
+                reading: 0,
+                touched: false,
+            }; MAX_PADS],
+            count: pads.len().min(MAX_PADS),
+        };
+        let mut all = c.send;
+        for (pad, pin) in c.pads.iter_mut().zip(pads.iter()) {
+            pad.mask = 1 << pin;
+            all |= pad.mask;
+        }
+        port.afsel.modify(|r, w| unsafe { w.bits(r.bits() & !all) });
+        port.den.modify(|r, w| unsafe { w.bits(r.bits() | all) });
+        port.dir.modify(|r, w| unsafe { w.bits(r.bits() | c.send) });
+        c.discharge();
+
+        // Free running, counting down from the top
+        timer.ctl.write(|w| unsafe { w.bits(0) });
+        timer.cfg.write(|w| unsafe { w.bits(CFG_32_BIT) });
+        timer.tamr.write(|w| unsafe { w.bits(TAMR_PERIODIC) });
+        timer.tailr.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+        timer.ctl.write(|w| unsafe { w.bits(CTL_TAEN) });
+        c
+    }
+
+    /// How many pads we have.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Work out what each pad reads when nobody is touching it, and set
+    /// the default thresholds from that.
+    pub fn calibrate(&mut self) {
+        let mut totals = [0u32; MAX_PADS];
+        for _ in 0..CALIBRATE_SAMPLES {
+            let readings = self.charge_times();
+            for (total, reading) in totals.iter_mut().zip(readings.iter()) {
+                *total += *reading;
+            }
+        }
+        for (pad, total) in self.pads[0..self.count].iter_mut().zip(totals.iter()) {
+            pad.baseline = total / CALIBRATE_SAMPLES;
+            pad.reading = pad.baseline;
+            pad.threshold = (pad.baseline / DEFAULT_THRESHOLD_DIVISOR).max(1);
+            pad.touched = false;
+        }
+    }
+
+    /// Change how many clocks over its baseline a pad has to go to count
+    /// as touched.
+    pub fn set_threshold(&mut self, pad: usize, clocks: u32) {
+        if pad < self.count {
+            self.pads[pad].threshold = clocks.max(1);
+        }
+    }
+
+    /// Measure every pad and update which ones are touched. Returns a bit
+    /// mask of the touched pads (bit 0 is the first pad).
+    pub fn update(&mut self) -> u32 {
+        let readings = self.charge_times();
+        let mut touched = 0;
+        for (i, pad) in self.pads[0..self.count].iter_mut().enumerate() {
+            pad.reading = readings[i];
+            let over = pad.reading.saturating_sub(pad.baseline);
+            // Half the threshold to let go, so a finger on the edge
+            // doesn't flicker
+            pad.touched = if pad.touched {
+                over > pad.threshold / 2
+            } else {
+                over > pad.threshold
+            };
+            if pad.touched {
+                touched |= 1 << i;
+            } else if pad.reading > pad.baseline {
+                pad.baseline += (pad.reading - pad.baseline) / DRIFT_DIVISOR;
+            } else {
+                pad.baseline -= (pad.baseline - pad.reading) / DRIFT_DIVISOR;
+            }
+        }
+        touched
+    }
+
+    /// Was the pad touched at the last `update`?
+    pub fn is_touched(&self, pad: usize) -> bool {
+        pad < self.count && self.pads[pad].touched
+    }
+
+    /// The pad's last reading, in timer clocks.
+    pub fn reading(&self, pad: usize) -> u32 {
+        self.pads[pad].reading
+    }
+
+    /// What the pad reads when it isn't touched, in timer clocks.
+    pub fn baseline(&self, pad: usize) -> u32 {
+        self.pads[pad].baseline
+    }
+
+    /// Time several charges of every pad at once, keeping the fastest for
+    /// each.
+    fn charge_times(&mut self) -> [u32; MAX_PADS] {
+        let mut best = [TIMEOUT; MAX_PADS];
+        for _ in 0..CHARGES {
+            let mut waiting = 0;
+            for pad in &self.pads[0..self.count] {
+                waiting |= pad.mask;
+            }
+            let start = self.timer.tar.read().bits();
+            self.set_send(true);
+            loop {
+                let elapsed = start.wrapping_sub(self.timer.tar.read().bits());
+                let high = self.port.data.read().bits() & waiting;
+                for (i, pad) in self.pads[0..self.count].iter().enumerate() {
+                    if high & pad.mask != 0 {
+                        best[i] = best[i].min(elapsed);
+                    }
+                }
+                waiting &= !high;
+                if waiting == 0 || elapsed >= TIMEOUT {
+                    break;
+                }
+            }
+            self.discharge();
+        }
+        best
+    }
+
+    /// Pull everything low, and leave the pads as inputs ready for the
+    /// next charge.
+    fn discharge(&mut self) {
+        self.set_send(false);
+        let mut pads = 0;
+        for pad in &self.pads[0..self.count] {
+            pads |= pad.mask;
+        }
+        self.port
+            .data
+            .modify(|r, w| unsafe { w.bits(r.bits() & !pads) });
+        self.port.dir.modify(|r, w| unsafe { w.bits(r.bits() | pads) });
+        // Give the pads a moment to empty
+        for _ in 0..100 {
+            self.port.data.read();
+        }
+        self.port
+            .dir
+            .modify(|r, w| unsafe { w.bits(r.bits() & !pads) });
+    }
+
+    fn set_send(&mut self, high: bool) {
+        let send = self.send;
+        self.port.data.modify(|r, w| unsafe {
+            w.bits(if high { r.bits() | send } else { r.bits() & !send })
+        });
+    }
+}
@@ -0,0 +1,346 @@
+//! A CHIP-8 interpreter.
+//!
+//! CHIP-8 is the little virtual machine from the COSMAC VIP days: 4 KiB of
+//! memory, sixteen 8-bit registers (`V0` to `VF`), a 12-bit index register,
+//! a 64 x 32 monochrome display drawn with XORed sprites, two 60 Hz count
+//! down timers and a sixteen key hex keypad.
+//!
+//! We don't know about screens or keyboards or time. Call `step` to run an
+//! instruction (a few hundred a second is about right) and `tick` sixty
+//! times a second for the timers. Tell us about keys with `set_key` and read
+//! the display back with `pixel`. Where the various interpreters disagree,
+//! we do what most modern games expect: the shifts work on `Vx` alone and
+//! `Fx55`/`Fx65` leave `I` alone. `Cxkk` takes its random numbers from
+//! `random`.
+
+use random;
+
+/// The display is this many pixels across...
+pub const WIDTH: usize = 64;
+
+/// ...and this many down.
+pub const HEIGHT: usize = 32;
+
+/// How much memory the machine has.
+pub const MEMORY_LEN: usize = 4096;
+
+/// Programs are loaded here. Below it sit the hex digit sprites.
+pub const PROGRAM_START: usize = 0x200;
+
+/// How deep subroutine calls can go.
+const STACK_LEN: usize = 16;
+
+/// The hex digit sprites are five bytes each.
+const DIGIT_LEN: u16 = 5;
+
+/// The sprites for 0 to F, which `Fx29` points at.
+const DIGITS: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+/// Something went wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The program doesn't fit in memory
+    RomTooBig,
+    /// We don't know this instruction (which includes machine code calls)
+    BadInstruction(u16),
+    /// Too many nested calls
+    StackOverflow,
+    /// A return with no call
+    StackUnderflow,
+}
+
+/// The machine itself.
+pub struct Chip8 {
+    memory: [u8; MEMORY_LEN],
+    v: [u8; 16],
+    i: u16,
+    pc: u16,
+    stack: [u16; STACK_LEN],
+    sp: usize,
+    delay: u8,
+    sound: u8,
+    /// One word per row; bit 63 is the left hand column.
+    display: [u64; HEIGHT],
+    /// One bit per key.
+    keys: u16,
+    /// Set while `Fx0A` waits: the register, and the key once it's down.
+    waiting: Option<(usize, Option<u8>)>,
+    dirty: bool,
+}
+
+impl Chip8 {
+    /// A machine with nothing loaded. Load something before you `step`.
+    pub fn new() -> Chip8 {
+        let mut chip8 = Chip8 {
+            memory: [0; MEMORY_LEN],
+            v: [0; 16],
+            i: 0,
+            pc: PROGRAM_START as u16,
+            stack: [0; STACK_LEN],
+            sp: 0,
+            delay: 0,
+            sound: 0,
+            display: [0; HEIGHT],
+            keys: 0,
+            waiting: None,
+            dirty: true,
+        };
+        chip8.memory[0..DIGITS.len()].copy_from_slice(&DIGITS);
+        chip8
+    }
+
+    /// Reset the machine and load a program.
+    pub fn load(&mut self, rom: &[u8]) -> Result<(), Error> {
+        if rom.len() > MEMORY_LEN - PROGRAM_START {
+            return Err(Error::RomTooBig);
+        }
+        *self = Chip8::new();
+        self.memory[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(rom);
+        Ok(())
+    }
+
+    /// Stir some entropy (like a timer value) into the random numbers.
+    pub fn seed(&mut self, entropy: u32) {
+        random::stir(entropy);
+    }
+
+    /// Press (or release) one of the keys, 0x0 to 0xF.
+    pub fn set_key(&mut self, key: u8, down: bool) {
+        let bit = 1 << (key & 0x0F);
+        if down {
+            self.keys |= bit;
+        } else {
+            self.keys &= !bit;
+        }
+    }
+
+    /// Is the pixel at (`x`, `y`) lit?
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        (self.display[y] >> (WIDTH - 1 - x)) & 1 != 0
+    }
+
+    /// Has the display changed since we last asked?
+    pub fn take_dirty(&mut self) -> bool {
+        let dirty = self.dirty;
+        self.dirty = false;
+        dirty
+    }
+
+    /// Should the buzzer be sounding?
+    pub fn is_beeping(&self) -> bool {
+        self.sound > 0
+    }
+
+    /// Count the timers down. Call this at 60 Hz.
+    pub fn tick(&mut self) {
+        self.delay = self.delay.saturating_sub(1);
+        self.sound = self.sound.saturating_sub(1);
+    }
+
+    /// Run one instruction. If the program is waiting for a key, this does
+    /// nothing until one has been pressed and released.
+    pub fn step(&mut self) -> Result<(), Error> {
+        if let Some((x, key)) = self.waiting {
+            match key {
+                None => {
+                    if self.keys != 0 {
+                        let key = self.keys.trailing_zeros() as u8;
+                        self.waiting = Some((x, Some(key)));
+                    }
+                }
+                Some(key) => {
+                    if self.keys & (1 << key) == 0 {
+                        self.v[x] = key;
+                        self.waiting = None;
+                    }
+                }
+            }
+            return Ok(());
+        }
+
+        let pc = self.pc as usize & (MEMORY_LEN - 1);
+        let op = (self.memory[pc] as u16) << 8 | self.memory[(pc + 1) & (MEMORY_LEN - 1)] as u16;
+        self.pc = (self.pc + 2) & 0x0FFF;
+
+        let x = ((op >> 8) & 0x0F) as usize;
+        let y = ((op >> 4) & 0x0F) as usize;
+        let n = (op & 0x0F) as u8;
+        let kk = (op & 0xFF) as u8;
+        let nnn = op & 0x0FFF;
+
+        match op >> 12 {
+            0x0 => match op {
+                0x00E0 => {
+                    self.display = [0; HEIGHT];
+                    self.dirty = true;
+                }
+                0x00EE => {
+                    if self.sp == 0 {
+                        return Err(Error::StackUnderflow);
+                    }
+                    self.sp -= 1;
+                    self.pc = self.stack[self.sp];
+                }
+                _ => return Err(Error::BadInstruction(op)),
+            },
+            0x1 => self.pc = nnn,
+            0x2 => {
+                if self.sp == STACK_LEN {
+                    return Err(Error::StackOverflow);
+                }
+                self.stack[self.sp] = self.pc;
+                self.sp += 1;
+                self.pc = nnn;
+            }
+            0x3 => {
+                if self.v[x] == kk {
+                    self.skip();
+                }
+            }
+            0x4 => {
+                if self.v[x] != kk {
+                    self.skip();
+                }
+            }
+            0x5 if n == 0 => {
+                if self.v[x] == self.v[y] {
+                    self.skip();
+                }
+            }
+            0x6 => self.v[x] = kk,
+            0x7 => self.v[x] = self.v[x].wrapping_add(kk),
+            0x8 => self.arithmetic(op, x, y)?,
+            0x9 if n == 0 => {
+                if self.v[x] != self.v[y] {
+                    self.skip();
+                }
+            }
+            0xA => self.i = nnn,
+            0xB => self.pc = (nnn + self.v[0] as u16) & 0x0FFF,
+            0xC => self.v[x] = (random::rand_u32() >> 24) as u8 & kk,
+            0xD => self.draw(x, y, n),
+            0xE => {
+                let down = self.keys & (1 << (self.v[x] & 0x0F)) != 0;
+                match kk {
+                    0x9E if down => self.skip(),
+                    0xA1 if !down => self.skip(),
+                    0x9E | 0xA1 => {}
+                    _ => return Err(Error::BadInstruction(op)),
+                }
+            }
+            0xF => self.misc(op, x)?,
+            _ => return Err(Error::BadInstruction(op)),
+        }
+        Ok(())
+    }
+
+    /// The `8xyn` register to register instructions.
+    fn arithmetic(&mut self, op: u16, x: usize, y: usize) -> Result<(), Error> {
+        let (vx, vy) = (self.v[x], self.v[y]);
+        // VF gets the flag after the result, in case x is F
+        let (result, flag) = match op & 0x0F {
+            0x0 => (vy, None),
+            0x1 => (vx | vy, None),
+            0x2 => (vx & vy, None),
+            0x3 => (vx ^ vy, None),
+            0x4 => {
+                let (sum, carry) = vx.overflowing_add(vy);
+                (sum, Some(carry as u8))
+            }
+            0x5 => (vx.wrapping_sub(vy), Some((vx >= vy) as u8)),
+            0x6 => (vx >> 1, Some(vx & 1)),
+            0x7 => (vy.wrapping_sub(vx), Some((vy >= vx) as u8)),
+            0xE => (vx << 1, Some(vx >> 7)),
+            _ => return Err(Error::BadInstruction(op)),
+        };
+        self.v[x] = result;
+        if let Some(flag) = flag {
+            self.v[0xF] = flag;
+        }
+        Ok(())
+    }
+
+    /// The `Fxkk` instructions.
+    fn misc(&mut self, op: u16, x: usize) -> Result<(), Error> {
+        match op & 0xFF {
+            0x07 => self.v[x] = self.delay,
+            0x0A => self.waiting = Some((x, None)),
+            0x15 => self.delay = self.v[x],
+            0x18 => self.sound = self.v[x],
+            0x1E => self.i = (self.i + self.v[x] as u16) & 0x0FFF,
+            0x29 => self.i = (self.v[x] & 0x0F) as u16 * DIGIT_LEN,
+            0x33 => {
+                let value = self.v[x];
+                self.store(0, value / 100);
+                self.store(1, (value / 10) % 10);
+                self.store(2, value % 10);
+            }
+            0x55 => {
+                for r in 0..x + 1 {
+                    let value = self.v[r];
+                    self.store(r, value);
+                }
+            }
+            0x65 => {
+                for r in 0..x + 1 {
+                    self.v[r] = self.load_byte(r);
+                }
+            }
+            _ => return Err(Error::BadInstruction(op)),
+        }
+        Ok(())
+    }
+
+    /// XOR an `n` row sprite from `I` onto the screen at (`Vx`, `Vy`). The
+    /// starting point wraps round; the rest of the sprite is clipped. VF
+    /// says whether we rubbed anything out.
+    fn draw(&mut self, x: usize, y: usize, n: u8) {
+        let left = self.v[x] as usize % WIDTH;
+        let top = self.v[y] as usize % HEIGHT;
+        let mut collision = false;
+        for row in 0..n as usize {
+            if top + row >= HEIGHT {
+                break;
+            }
+            let sprite = self.load_byte(row) as u64;
+            // Line the sprite's left hand bit up with bit 63 - left
+            let bits = (sprite << 56) >> left;
+            let line = &mut self.display[top + row];
+            collision |= *line & bits != 0;
+            *line ^= bits;
+        }
+        self.v[0xF] = collision as u8;
+        self.dirty = true;
+    }
+
+    fn skip(&mut self) {
+        self.pc = (self.pc + 2) & 0x0FFF;
+    }
+
+    /// Read the byte `offset` past `I`.
+    fn load_byte(&self, offset: usize) -> u8 {
+        self.memory[(self.i as usize + offset) & (MEMORY_LEN - 1)]
+    }
+
+    /// Write the byte `offset` past `I`.
+    fn store(&mut self, offset: usize, value: u8) {
+        self.memory[(self.i as usize + offset) & (MEMORY_LEN - 1)] = value;
+    }
+}
@@ -0,0 +1,97 @@
+//! A square wave from the system clock, for checking it with a scope or a
+//! frequency counter.
+//!
+//! The TM4C123 can't put its system clock straight out on a pin, but PWM0
+//! runs from it, so a square wave from PWM0 is just as accurate. Ask for
+//! 1 MHz, and if your counter says 1.000 MHz then the PLL really is at 80
+//! MHz - and if the monitor still won't sync, it's not the clock.
+//!
+//! The output is M0PWM4, on PE4. Only exact divisions of the system clock
+//! (by 2 to 65536) are possible, so `set` says what you actually got.
+//!
+//! The caller must power up PWM0. `ClockOut::new` turns off the PWM clock
+//! divider (RCC.USEPWMDIV), which slows down nothing else in these demos
+//! but would speed up `audio` and the beeper in `apps`.
+
+use config::{Pin, Port};
+use hal::sysctl::Clocks;
+use pac::{self, PWM0};
+
+/// The smallest division of the system clock.
+pub const MIN_DIVIDER: u32 = 2;
+/// The largest division - the counter is 16 bits.
+pub const MAX_DIVIDER: u32 = 65536;
+
+/// Where the clock comes out.
+const PIN: Pin = Pin {
+    port: Port::E,
+    bit: 4,
+};
+/// M0PWM4's alternate function.
+const PIN_FUNCTION: u32 = 4;
+/// M0PWM4 in PWMENABLE.
+const OUTPUT: u32 = 1 << 4;
+/// RCC.USEPWMDIV
+const RCC_USEPWMDIV: u32 = 1 << 20;
+
+/// Can't get that frequency from this system clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfRange {
+    pub min_hz: u32,
+    pub max_hz: u32,
+}
+
+/// PWM0 generator 2, as a clock output.
+pub struct ClockOut {
+    pwm: PWM0,
+    sysclk: u32,
+}
+
+impl ClockOut {
+    /// Set up the generator and the pin. The output starts off.
+    pub fn new(pwm: PWM0, clocks: &Clocks) -> ClockOut {
+        let sysctl = unsafe { &*pac::SYSCTL::ptr() };
+        sysctl
+            .rcc
+            .modify(|r, w| unsafe { w.bits(r.bits() & !RCC_USEPWMDIV) });
+        PIN.into_af(PIN_FUNCTION);
+        pwm._2_ctl.write(|w| unsafe { w.bits(0) });
+        // Low on load, high on compare A counting down
+        pwm._2_gena
+            .write(|w| unsafe { w.bits((0x3 << 6) | (0x2 << 2)) });
+        pwm._2_ctl.write(|w| unsafe { w.bits(1) });
+        ClockOut {
+            pwm,
+            sysclk: clocks.sysclk.0,
+        }
+    }
+
+    /// Put out the nearest frequency to `hz` we can, and say what it is.
+    pub fn set(&mut self, hz: u32) -> Result<u32, OutOfRange> {
+        // Round up, so the divider can't come out too big
+        let min_hz = (self.sysclk + MAX_DIVIDER - 1) / MAX_DIVIDER;
+        let max_hz = self.sysclk / MIN_DIVIDER;
+        if hz < min_hz || hz > max_hz {
+            return Err(OutOfRange { min_hz, max_hz });
+        }
+        let divider = (self.sysclk + (hz / 2)) / hz;
+        self.pwm._2_load.write(|w| unsafe { w.bits(divider - 1) });
+        self.pwm._2_cmpa.write(|w| unsafe { w.bits((divider / 2) - 1) });
+        self.pwm
+            .enable
+            .modify(|r, w| unsafe { w.bits(r.bits() | OUTPUT) });
+        Ok(self.sysclk / divider)
+    }
+
+    /// Turn the output off. It goes low.
+    pub fn stop(&mut self) {
+        self.pwm
+            .enable
+            .modify(|r, w| unsafe { w.bits(r.bits() & !OUTPUT) });
+    }
+
+    /// Give the PWM back.
+    pub fn free(self) -> PWM0 {
+        self.pwm
+    }
+}
@@ -0,0 +1,165 @@
+//! Consistent Overhead Byte Stuffing, for sending packets down a serial
+//! line.
+//!
+//! COBS turns a packet into one with no zero bytes in it, for an overhead
+//! of one byte in 254, so a zero can mark where each packet ends. Each run
+//! of non-zero bytes goes out after a byte saying how long it is (plus
+//! one), and the zero that followed the run is left out. A length of 255
+//! means 254 bytes with no zero after them.
+//!
+//! Unlike SLIP, COBS never makes a packet much longer, whatever's in it,
+//! so the buffers can be sized exactly.
+//!
+//! `Decoder` will share a line with someone typing at a `menu`: a zero
+//! starts a packet, and the next zero ends it, and everything outside a
+//! packet is handed straight back. So a host should send a zero before
+//! each packet as well as after it.
+
+use embedded_hal::serial;
+
+/// The largest packet `Decoder` takes, before encoding.
+pub const MAX_PACKET: usize = 256;
+
+/// The longest run between length bytes.
+const MAX_RUN: usize = 254;
+
+/// How long `len` bytes could be once encoded (not counting the zero).
+pub fn max_encoded_len(len: usize) -> usize {
+    len + (len / MAX_RUN) + 1
+}
+
+/// Encode `data` into `out`, which must be at least `max_encoded_len`
+/// long, and say how much of it was used. No zero goes on the end.
+pub fn encode(data: &[u8], out: &mut [u8]) -> usize {
+    let mut rest = data;
+    let mut len = 0;
+    loop {
+        let limit = rest.len().min(MAX_RUN);
+        let run = rest[0..limit].iter().position(|&b| b == 0).unwrap_or(limit);
+        out[len] = run as u8 + 1;
+        out[len + 1..len + 1 + run].copy_from_slice(&rest[0..run]);
+        len += run + 1;
+        if run == MAX_RUN {
+            rest = &rest[run..];
+        } else if run < rest.len() {
+            // Skip the zero
+            rest = &rest[run + 1..];
+        } else {
+            return len;
+        }
+    }
+}
+
+/// Decode a packet (without its zero), in place. Gives the decoded
+/// length, or `None` if it isn't valid COBS.
+pub fn decode(data: &mut [u8]) -> Option<usize> {
+    let mut read = 0;
+    let mut written = 0;
+    while read < data.len() {
+        let code = data[read] as usize;
+        if code == 0 || read + code > data.len() {
+            return None;
+        }
+        read += 1;
+        // We're always behind where we're reading, so this is safe
+        for _ in 1..code {
+            data[written] = data[read];
+            written += 1;
+            read += 1;
+        }
+        if code != MAX_RUN + 1 && read < data.len() {
+            data[written] = 0;
+            written += 1;
+        }
+    }
+    Some(written)
+}
+
+/// Send one packet, with a zero either side.
+pub fn send<TX>(tx: &mut TX, packet: &[u8]) -> Result<(), TX::Error>
+where
+    TX: serial::Write<u8>,
+{
+    block!(tx.write(0))?;
+    let mut rest = packet;
+    loop {
+        let limit = rest.len().min(MAX_RUN);
+        let run = rest[0..limit].iter().position(|&b| b == 0).unwrap_or(limit);
+        block!(tx.write(run as u8 + 1))?;
+        for &byte in &rest[0..run] {
+            block!(tx.write(byte))?;
+        }
+        if run == MAX_RUN {
+            rest = &rest[run..];
+        } else if run < rest.len() {
+            rest = &rest[run + 1..];
+        } else {
+            break;
+        }
+    }
+    block!(tx.write(0))
+}
+
+/// What a byte fed into a `Decoder` turned out to be.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Input<'a> {
+    /// Part of a packet, or a zero
+    Nothing,
+    /// Not in a packet - pass it on
+    Text(u8),
+    /// The end of a packet, decoded
+    Packet(&'a [u8]),
+}
+
+/// Picks packets out of a serial line.
+pub struct Decoder {
+    buffer: [u8; MAX_PACKET + (MAX_PACKET / MAX_RUN) + 1],
+    len: usize,
+    /// Set between a packet's two zeroes
+    in_packet: bool,
+}
+
+impl Decoder {
+    pub fn new() -> Decoder {
+        Decoder {
+            buffer: [0u8; MAX_PACKET + (MAX_PACKET / MAX_RUN) + 1],
+            len: 0,
+            in_packet: false,
+        }
+    }
+
+    /// Feed in the next byte. Packets that are too long, or not valid COBS,
+    /// are thrown away.
+    pub fn input(&mut self, byte: u8) -> Input {
+        if !self.in_packet {
+            if byte == 0 {
+                self.in_packet = true;
+                return Input::Nothing;
+            }
+            return Input::Text(byte);
+        }
+        if byte != 0 {
+            if self.len == self.buffer.len() {
+                // Too long. Give up on it, and go back to text.
+                self.len = 0;
+                self.in_packet = false;
+            } else {
+                self.buffer[self.len] = byte;
+                self.len += 1;
+            }
+            return Input::Nothing;
+        }
+        // Two zeroes in a row (the end of one packet and the start of the
+        // next, perhaps, if we missed a byte) keep us in a packet
+        if self.len == 0 {
+            return Input::Nothing;
+        }
+        let len = self.len;
+        self.len = 0;
+        self.in_packet = false;
+        match decode(&mut self.buffer[0..len]) {
+            Some(decoded) => Input::Packet(&self.buffer[0..decoded]),
+            None => Input::Nothing,
+        }
+    }
+}
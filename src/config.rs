@@ -0,0 +1,308 @@
+//! Which pins and peripherals the demos use.
+//!
+//! The defaults match the wiring in `hello_vga`. If your board is wired
+//! differently, pick another arrangement with Cargo features rather than
+//! editing the drivers:
+//!
+//! * `vga-hsync-pf0` - HSYNC on PF0 rather than PB6. Both are T0CCP0, so
+//!   nothing else changes, but PF0 is SW2 on the Launchpad.
+//! * `vga-vsync-pb5` or `vga-vsync-pe4` - VSYNC on PB5 or PE4 rather than
+//!   PC4. VSYNC is driven by software, so any free GPIO will do; add
+//!   another feature here if you need a different one.
+//! * `console-921600` - run the console at 921600 bps rather than 115200
+//!   bps.
+//!
+//! ```text
+//! $ cargo build --example basic --features vga-hsync-pf0,vga-vsync-pb5
+//! ```
+//!
+//! Some things can't move. The pixel data has to come out of SSI2Tx, which
+//! is only on PB7, and the console is UART0 (PA0 and PA1) because that's
+//! the one wired to the debug USB port.
+//!
+//! The chip is a feature too. The default, `tm4c123`, is the TM4C123GH6PM on
+//! the LaunchPad. `tm4c129` is the TM4C1294NCPDT on the Connected LaunchPad,
+//! which has more ports (up to `Port::Q`) but no VGA output - see `board`.
+
+use cortex_m::asm;
+use hal::bb;
+use pac;
+
+/// A GPIO port's registers. They're all the same shape.
+#[cfg(feature = "tm4c123")]
+pub type GpioRegisters = pac::gpio_porta::RegisterBlock;
+/// A GPIO port's registers. They're all the same shape.
+#[cfg(feature = "tm4c129")]
+pub type GpioRegisters = pac::gpio_porta_ahb::RegisterBlock;
+
+/// GPIOLOCK: the magic unlock value
+const UNLOCK: u32 = 0x4C4F_434B;
+
+/// HSYNC, which must be a T0CCP0 pin.
+#[cfg(feature = "vga-hsync-pf0")]
+pub const VGA_HSYNC: Pin = Pin { port: Port::F, bit: 0 };
+/// HSYNC, which must be a T0CCP0 pin.
+#[cfg(not(feature = "vga-hsync-pf0"))]
+pub const VGA_HSYNC: Pin = Pin { port: Port::B, bit: 6 };
+
+/// VSYNC, which can be any GPIO.
+#[cfg(feature = "vga-vsync-pb5")]
+pub const VGA_VSYNC: Pin = Pin { port: Port::B, bit: 5 };
+/// VSYNC, which can be any GPIO.
+#[cfg(all(feature = "vga-vsync-pe4", not(feature = "vga-vsync-pb5")))]
+pub const VGA_VSYNC: Pin = Pin { port: Port::E, bit: 4 };
+/// VSYNC, which can be any GPIO.
+#[cfg(not(any(feature = "vga-vsync-pb5", feature = "vga-vsync-pe4")))]
+pub const VGA_VSYNC: Pin = Pin { port: Port::C, bit: 4 };
+#[cfg(all(feature = "vga-vsync-pb5", feature = "vga-vsync-pe4"))]
+compile_error!("Pick one VSYNC pin - `vga-vsync-pb5` or `vga-vsync-pe4`, not both");
+
+/// The green pixel data. SSI2Tx is only on PB7.
+pub const VGA_PIXELS: Pin = Pin { port: Port::B, bit: 7 };
+
+/// The console's baud rate.
+#[cfg(feature = "console-921600")]
+pub const CONSOLE_BAUD: u32 = 921_600;
+/// The console's baud rate.
+#[cfg(not(feature = "console-921600"))]
+pub const CONSOLE_BAUD: u32 = 115_200;
+
+/// SSI0, which the SPI examples use: SCK on PA2, MOSI on PA5 and MISO on
+/// PA4. Chip select is up to the example.
+#[cfg(feature = "tm4c123")]
+pub const SSI0: SsiPins = SsiPins {
+    clk: Pin { port: Port::A, bit: 2 },
+    tx: Pin { port: Port::A, bit: 5 },
+    rx: Pin { port: Port::A, bit: 4 },
+    function: 2,
+};
+/// SSI0, which the SPI examples use. The TM4C1294 has TX (XDAT0) and RX
+/// (XDAT1) the other way round to the TM4C123, so MOSI is on PA4 and MISO
+/// is on PA5. SCK is still PA2.
+#[cfg(feature = "tm4c129")]
+pub const SSI0: SsiPins = SsiPins {
+    clk: Pin { port: Port::A, bit: 2 },
+    tx: Pin { port: Port::A, bit: 4 },
+    rx: Pin { port: Port::A, bit: 5 },
+    function: 15,
+};
+
+/// I2C0, which the I2C examples use: SCL on PB2 and SDA on PB3.
+#[cfg(feature = "tm4c123")]
+pub const I2C0: I2cPins = I2cPins {
+    scl: Pin { port: Port::B, bit: 2 },
+    sda: Pin { port: Port::B, bit: 3 },
+    function: 3,
+};
+/// I2C0, which the I2C examples use: SCL on PB2 and SDA on PB3.
+#[cfg(feature = "tm4c129")]
+pub const I2C0: I2cPins = I2cPins {
+    scl: Pin { port: Port::B, bit: 2 },
+    sda: Pin { port: Port::B, bit: 3 },
+    function: 2,
+};
+
+/// A GPIO port. They're in the same order as their bits in RCGCGPIO.
+#[cfg(feature = "tm4c123")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Port {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+}
+
+/// A GPIO port. They're in the same order as their bits in RCGCGPIO.
+#[cfg(feature = "tm4c129")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Port {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    J,
+    K,
+    L,
+    M,
+    N,
+    P,
+    Q,
+}
+
+/// A GPIO pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pin {
+    pub port: Port,
+    pub bit: u8,
+}
+
+/// The pins an SSI uses in SPI mode, and their alternate function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SsiPins {
+    pub clk: Pin,
+    pub tx: Pin,
+    pub rx: Pin,
+    pub function: u32,
+}
+
+/// The pins an I2C peripheral uses, and their alternate function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct I2cPins {
+    pub scl: Pin,
+    pub sda: Pin,
+    pub function: u32,
+}
+
+impl Port {
+    /// The port's registers.
+    #[cfg(feature = "tm4c123")]
+    pub fn registers(self) -> &'static GpioRegisters {
+        unsafe {
+            match self {
+                Port::A => &*pac::GPIO_PORTA::ptr(),
+                Port::B => &*pac::GPIO_PORTB::ptr(),
+                Port::C => &*pac::GPIO_PORTC::ptr(),
+                Port::D => &*pac::GPIO_PORTD::ptr(),
+                Port::E => &*pac::GPIO_PORTE::ptr(),
+                Port::F => &*pac::GPIO_PORTF::ptr(),
+            }
+        }
+    }
+
+    /// The port's registers. Ports A to J are only on the AHB.
+    #[cfg(feature = "tm4c129")]
+    pub fn registers(self) -> &'static GpioRegisters {
+        unsafe {
+            match self {
+                Port::A => &*pac::GPIO_PORTA_AHB::ptr(),
+                Port::B => &*pac::GPIO_PORTB_AHB::ptr(),
+                Port::C => &*pac::GPIO_PORTC_AHB::ptr(),
+                Port::D => &*pac::GPIO_PORTD_AHB::ptr(),
+                Port::E => &*pac::GPIO_PORTE_AHB::ptr(),
+                Port::F => &*pac::GPIO_PORTF_AHB::ptr(),
+                Port::G => &*pac::GPIO_PORTG_AHB::ptr(),
+                Port::H => &*pac::GPIO_PORTH_AHB::ptr(),
+                Port::J => &*pac::GPIO_PORTJ_AHB::ptr(),
+                Port::K => &*pac::GPIO_PORTK::ptr(),
+                Port::L => &*pac::GPIO_PORTL::ptr(),
+                Port::M => &*pac::GPIO_PORTM::ptr(),
+                Port::N => &*pac::GPIO_PORTN::ptr(),
+                Port::P => &*pac::GPIO_PORTP::ptr(),
+                Port::Q => &*pac::GPIO_PORTQ::ptr(),
+            }
+        }
+    }
+
+    /// Power the port up, if it isn't already, and wait for it to be ready.
+    pub fn enable(self) {
+        let sysctl = unsafe { &*pac::SYSCTL::ptr() };
+        let mask = 1 << (self as u32);
+        sysctl
+            .rcgcgpio
+            .modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+        while sysctl.prgpio.read().bits() & mask == 0 {
+            asm::nop();
+        }
+    }
+}
+
+impl Pin {
+    /// Make the pin a push-pull output.
+    pub fn into_output(self) {
+        let port = self.prepare();
+        let mask = 1 << self.bit;
+        port.afsel.modify(|r, w| unsafe { w.bits(r.bits() & !mask) });
+        port.dir.modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+        port.den.modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+    }
+
+    /// Make the pin a floating input.
+    pub fn into_input(self) {
+        let port = self.prepare();
+        let mask = 1 << self.bit;
+        port.afsel.modify(|r, w| unsafe { w.bits(r.bits() & !mask) });
+        port.dir.modify(|r, w| unsafe { w.bits(r.bits() & !mask) });
+        port.den.modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+    }
+
+    /// Hand the pin over to a peripheral. `function` is the PCTL value
+    /// from the datasheet's pin mux table.
+    pub fn into_af(self, function: u32) {
+        let port = self.prepare();
+        let mask = 1 << self.bit;
+        let shift = u32::from(self.bit) * 4;
+        port.pctl.modify(|r, w| unsafe {
+            w.bits((r.bits() & !(0xF << shift)) | (function << shift))
+        });
+        port.afsel.modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+        port.den.modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+    }
+
+    /// Drive an output high or low. Safe to call from an interrupt, as it
+    /// only touches this pin.
+    pub fn set(self, high: bool) {
+        unsafe { bb::change_bit(&self.port.registers().data, self.bit, high) };
+    }
+
+    /// Read the pin's level.
+    pub fn is_high(self) -> bool {
+        self.port.registers().data.read().bits() & (1 << self.bit) != 0
+    }
+
+    /// Power the port up, and unlock the pin if it's one of the two that
+    /// are locked at reset.
+    fn prepare(self) -> &'static GpioRegisters {
+        self.port.enable();
+        let port = self.port.registers();
+        if self.is_locked() {
+            port.lock.write(|w| unsafe { w.bits(UNLOCK) });
+            port.cr.modify(|r, w| unsafe { w.bits(r.bits() | (1 << self.bit)) });
+            port.lock.write(|w| unsafe { w.bits(0) });
+        }
+        port
+    }
+
+    /// PD7 and PF0, which can be NMI.
+    #[cfg(feature = "tm4c123")]
+    fn is_locked(self) -> bool {
+        match (self.port, self.bit) {
+            (Port::D, 7) | (Port::F, 0) => true,
+            _ => false,
+        }
+    }
+
+    /// PD7 and PE7, which can be NMI.
+    #[cfg(feature = "tm4c129")]
+    fn is_locked(self) -> bool {
+        match (self.port, self.bit) {
+            (Port::D, 7) | (Port::E, 7) => true,
+            _ => false,
+        }
+    }
+}
+
+impl SsiPins {
+    /// Hand all three pins over to the SSI.
+    pub fn connect(self) {
+        self.clk.into_af(self.function);
+        self.tx.into_af(self.function);
+        self.rx.into_af(self.function);
+    }
+}
+
+impl I2cPins {
+    /// Hand both pins over to the I2C peripheral, with SDA open drain.
+    pub fn connect(self) {
+        self.scl.into_af(self.function);
+        self.sda.into_af(self.function);
+        let port = self.sda.port.registers();
+        port.odr
+            .modify(|r, w| unsafe { w.bits(r.bits() | (1 << self.sda.bit)) });
+    }
+}
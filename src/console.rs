@@ -0,0 +1,96 @@
+//! A polled writer for UART0.
+//!
+//! Menu callbacks don't get a handle to the `Runner`'s output, so they use
+//! this to talk to the user instead. It pokes the UART0 registers directly,
+//! so UART0 must already have been set up (e.g. with `Serial::uart0`).
+//!
+//! If the `Runner` isn't on UART0 (see `telnet`), wrap its `input_byte` in
+//! `capture` and pass on whatever the callbacks said.
+//!
+//! Writing a BEL (`0x07`) also rings `audio::bell`, if there's a sounder.
+
+use audio;
+use core::fmt;
+use pac;
+
+/// How much `capture` can hold. Anything more is lost.
+pub const CAPTURE_LEN: usize = 1024;
+
+/// The bell character.
+const BEL: u8 = 0x07;
+
+/// While a `capture` is running, output goes here instead of UART0.
+static mut CAPTURE: Option<Captured> = None;
+
+/// Writes to UART0, converting `\n` to `\r\n` as it goes.
+pub struct Console;
+
+/// What was written to the `Console` during a `capture`.
+pub struct Captured {
+    data: [u8; CAPTURE_LEN],
+    len: usize,
+}
+
+impl Console {
+    /// Send a single byte, waiting for room in the TX FIFO.
+    pub fn write_byte(&mut self, byte: u8) {
+        if byte == BEL {
+            audio::bell();
+        }
+        if let Some(captured) = unsafe { CAPTURE.as_mut() } {
+            if captured.len < CAPTURE_LEN {
+                captured.data[captured.len] = byte;
+                captured.len += 1;
+            }
+            return;
+        }
+        let uart = unsafe { &*pac::UART0::ptr() };
+        while uart.fr.read().txff().bit_is_set() {}
+        uart.dr.write(|w| unsafe { w.data().bits(byte) });
+    }
+
+    /// Take a byte from the RX FIFO, if there is one. For when you need to
+    /// read somewhere the `Serial` can't go, like an exception handler.
+    pub fn read_byte(&mut self) -> Option<u8> {
+        let uart = unsafe { &*pac::UART0::ptr() };
+        if uart.fr.read().rxfe().bit_is_set() {
+            None
+        } else {
+            Some(uart.dr.read().data().bits())
+        }
+    }
+}
+
+impl fmt::Write for Console {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            if byte == b'\n' {
+                self.write_byte(b'\r');
+            }
+            self.write_byte(byte);
+        }
+        Ok(())
+    }
+}
+
+impl Captured {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data[0..self.len]
+    }
+}
+
+/// Run `f` with `Console` output going into a buffer instead of UART0,
+/// and hand back what it wrote. Captures don't nest.
+pub fn capture<F>(f: F) -> Captured
+where
+    F: FnOnce(),
+{
+    unsafe {
+        CAPTURE = Some(Captured {
+            data: [0u8; CAPTURE_LEN],
+            len: 0,
+        });
+    }
+    f();
+    unsafe { CAPTURE.take().unwrap() }
+}
@@ -0,0 +1,138 @@
+//! 8-bit audio out of an R-2R ladder on Port D, fed by uDMA.
+//!
+//! Put a resistor ladder (say 10k and 20k) on PD0..PD7, with PD7 as the
+//! most significant bit, and filter and buffer the output before it goes
+//! anywhere near a speaker. On the LaunchPad, PD0 and PD1 are joined to PB6
+//! and PB7 by R9 and R10 - take those off first, or the VGA output will
+//! fight the ladder.
+//!
+//! Timer3A ticks once a sample, and each tick asks uDMA channel 2 to copy
+//! one byte from a buffer to the port. There are two buffers, used in
+//! ping-pong mode: while the hardware plays one, the TIMER3A interrupt
+//! (which only fires when a buffer runs out) calls the `Source` to fill the
+//! other. The CPU only does any work once every `BUFFER_LEN` samples.
+//!
+//! The caller must power up Timer3 and the uDMA controller, call
+//! `udma::init`, then `start`, and enable the TIMER3A interrupt (at a lower
+//! priority than the VGA timers, as filling a buffer takes a while) and hook
+//! up the handler:
+//!
+//! ``` ignore
+//! interrupt!(TIMER3A, dac::timer3a_isr);
+//! ```
+
+use config::{Pin, Port};
+use cortex_m::interrupt;
+use hal::sysctl::Clocks;
+use pac::{self, TIMER3};
+use udma;
+
+/// Samples in each of the two buffers.
+pub const BUFFER_LEN: usize = 256;
+
+/// Fills a buffer with the next unsigned samples (128 is silence).
+pub type Source = fn(&mut [u8]);
+
+/// Where the ladder is.
+const PORT: Port = Port::D;
+
+/// uDMA channel 2, encoding 1 is Timer3A.
+const DMA_CHANNEL: u8 = 2;
+const DMA_ENCODING: u8 = 1;
+
+// GPTMCFG
+const CFG_32_BIT: u32 = 0x0;
+
+const TRANSFER: udma::Transfer = udma::Transfer {
+    size: udma::Size::Byte,
+    src_inc: udma::Increment::Byte,
+    dst_inc: udma::Increment::None,
+    arbitrate: udma::Arbitrate::_1,
+    mode: udma::Mode::PingPong,
+};
+
+static mut BUFFERS: [[u8; BUFFER_LEN]; 2] = [[128; BUFFER_LEN]; 2];
+static mut SOURCE: Option<Source> = None;
+
+/// Point one of the two control structures at its buffer.
+fn queue(select: udma::Select) {
+    let half = match select {
+        udma::Select::Primary => 0,
+        udma::Select::Alternate => 1,
+    };
+    let port = PORT.registers();
+    unsafe {
+        if let Some(source) = SOURCE {
+            source(&mut BUFFERS[half]);
+        }
+        udma::configure(
+            DMA_CHANNEL,
+            select,
+            &TRANSFER,
+            BUFFERS[half].as_ptr(),
+            &port.data as *const _ as *mut u8,
+            BUFFER_LEN,
+        );
+    }
+}
+
+/// Set up the port, the timer and the uDMA channel, and start playing
+/// whatever `source` makes at `sample_rate_hz`.
+pub fn start(timer: TIMER3, sample_rate_hz: u32, clocks: &Clocks, source: Source) {
+    for bit in 0..8 {
+        Pin { port: PORT, bit }.into_output();
+    }
+    PORT.registers().data.write(|w| unsafe { w.bits(128) });
+
+    interrupt::free(|_| unsafe { SOURCE = Some(source) });
+
+    // Timer3A, 32-bit periodic. The timeout is what asks for a byte; while
+    // the channel is enabled it doesn't reach the NVIC.
+    timer.ctl.modify(|_, w| w.taen().clear_bit());
+    timer.cfg.write(|w| unsafe { w.bits(CFG_32_BIT) });
+    timer.tamr.modify(|_, w| w.tamr().period());
+    timer.tailr.write(|w| unsafe { w.bits((clocks.sysclk.0 / sample_rate_hz) - 1) });
+    timer.imr.modify(|_, w| w.tatoim().set_bit());
+    timer.icr.write(|w| w.tatocint().set_bit());
+
+    udma::assign(DMA_CHANNEL, DMA_ENCODING);
+    queue(udma::Select::Primary);
+    queue(udma::Select::Alternate);
+    udma::enable(DMA_CHANNEL);
+
+    timer.ctl.modify(|_, w| w.taen().set_bit());
+}
+
+/// Change the sample rate without stopping.
+pub fn set_sample_rate(sample_rate_hz: u32, clocks: &Clocks) {
+    let timer = unsafe { &*pac::TIMER3::ptr() };
+    timer
+        .tailr
+        .write(|w| unsafe { w.bits((clocks.sysclk.0 / sample_rate_hz) - 1) });
+}
+
+/// Stop playing, and leave the output at the midpoint.
+pub fn stop() {
+    let timer = unsafe { &*pac::TIMER3::ptr() };
+    timer.ctl.modify(|_, w| w.taen().clear_bit());
+    udma::disable(DMA_CHANNEL);
+    PORT.registers().data.write(|w| unsafe { w.bits(128) });
+}
+
+/// Refill whichever buffer just finished. The TIMER3A handler.
+pub fn timer3a_isr() {
+    let timer = unsafe { &*pac::TIMER3::ptr() };
+    timer.icr.write(|w| w.tatocint().set_bit());
+    if !udma::take_interrupt(DMA_CHANNEL) {
+        return;
+    }
+    for &select in [udma::Select::Primary, udma::Select::Alternate].iter() {
+        if udma::mode(DMA_CHANNEL, select) == udma::Mode::Stop {
+            queue(select);
+        }
+    }
+    // If we were so late that both ran out, the channel will have stopped
+    if !udma::is_enabled(DMA_CHANNEL) {
+        udma::enable(DMA_CHANNEL);
+    }
+}
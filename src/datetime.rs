@@ -0,0 +1,175 @@
+//! A calendar date and time, for the clocks and loggers to share.
+//!
+//! We only deal with 2000-01-01 to 2099-12-31, which keeps the leap year
+//! rule simple and matches what the RTC chips can count to. There are no
+//! time zones - everything is UTC, or whatever you set the clock to.
+
+use core::fmt;
+
+/// Seconds from the Unix epoch (1970-01-01) to 2000-01-01.
+const EPOCH_2000: u32 = 946_684_800;
+
+const SECONDS_PER_DAY: u32 = 86_400;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DateTime {
+    /// 2000..2099
+    pub year: u16,
+    /// 1..12
+    pub month: u8,
+    /// 1..31
+    pub day: u8,
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+}
+
+impl DateTime {
+    /// Midnight at the start of 2000.
+    pub fn zero() -> DateTime {
+        DateTime {
+            year: 2000,
+            month: 1,
+            day: 1,
+            hours: 0,
+            minutes: 0,
+            seconds: 0,
+        }
+    }
+
+    /// Is every field in range?
+    pub fn is_valid(&self) -> bool {
+        self.year >= 2000 && self.year <= 2099 && self.month >= 1 && self.month <= 12
+            && self.day >= 1 && self.day <= days_in_month(self.year, self.month)
+            && self.hours < 24 && self.minutes < 60 && self.seconds < 60
+    }
+
+    /// Day of the week, where 0 is Monday.
+    pub fn weekday(&self) -> u8 {
+        // 2000-01-01 was a Saturday
+        ((self.days_since_2000() + 5) % 7) as u8
+    }
+
+    /// Convert from seconds since 1970-01-01. Times before 2000 come out as
+    /// 2000-01-01.
+    pub fn from_unix(unix: u32) -> DateTime {
+        let secs = unix.saturating_sub(EPOCH_2000);
+        let mut days = secs / SECONDS_PER_DAY;
+        let time = secs % SECONDS_PER_DAY;
+        let mut year = 2000;
+        while days >= days_in_year(year) {
+            days -= days_in_year(year);
+            year += 1;
+        }
+        let mut month = 1;
+        while days >= u32::from(days_in_month(year, month)) {
+            days -= u32::from(days_in_month(year, month));
+            month += 1;
+        }
+        DateTime {
+            year,
+            month,
+            day: days as u8 + 1,
+            hours: (time / 3600) as u8,
+            minutes: ((time / 60) % 60) as u8,
+            seconds: (time % 60) as u8,
+        }
+    }
+
+    /// Convert to seconds since 1970-01-01.
+    pub fn to_unix(&self) -> u32 {
+        EPOCH_2000 + (self.days_since_2000() * SECONDS_PER_DAY)
+            + (u32::from(self.hours) * 3600) + (u32::from(self.minutes) * 60)
+            + u32::from(self.seconds)
+    }
+
+    /// Set the date from a `YYYY-MM-DD` string, leaving the time alone.
+    pub fn set_date(&mut self, s: &str) -> Result<(), ()> {
+        let mut parts = s.split('-').map(|p| p.parse::<u16>());
+        let mut new = *self;
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            // Range checked before they're cut down to a `u8`, so 257
+            // can't become 1
+            (Some(Ok(y)), Some(Ok(m)), Some(Ok(d)), None) if m <= 12 && d <= 31 => {
+                new.year = y;
+                new.month = m as u8;
+                new.day = d as u8;
+            }
+            _ => return Err(()),
+        }
+        if !new.is_valid() {
+            return Err(());
+        }
+        *self = new;
+        Ok(())
+    }
+
+    /// Set the time from an `HH:MM:SS` (or `HH:MM`) string, leaving the
+    /// date alone.
+    pub fn set_time(&mut self, s: &str) -> Result<(), ()> {
+        let mut parts = s.split(':').map(|p| p.parse::<u8>());
+        let mut new = *self;
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(Ok(h)), Some(Ok(m)), sec, None) => {
+                new.hours = h;
+                new.minutes = m;
+                new.seconds = match sec {
+                    Some(Ok(s)) => s,
+                    None => 0,
+                    Some(Err(_)) => return Err(()),
+                };
+            }
+            _ => return Err(()),
+        }
+        if !new.is_valid() {
+            return Err(());
+        }
+        *self = new;
+        Ok(())
+    }
+
+    fn days_since_2000(&self) -> u32 {
+        let mut days = 0;
+        for year in 2000..self.year {
+            days += days_in_year(year);
+        }
+        for month in 1..self.month {
+            days += u32::from(days_in_month(self.year, month));
+        }
+        days + u32::from(self.day) - 1
+    }
+}
+
+impl fmt::Display for DateTime {
+    /// ISO 8601 style: `2018-03-01 12:34:56`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hours, self.minutes, self.seconds
+        )
+    }
+}
+
+fn is_leap_year(year: u16) -> bool {
+    // Good until 2100
+    year % 4 == 0
+}
+
+fn days_in_year(year: u16) -> u32 {
+    if is_leap_year(year) {
+        366
+    } else {
+        365
+    }
+}
+
+/// Days in the given month (1..12).
+pub fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
@@ -0,0 +1,199 @@
+//! A `defmt` global logger, over RTT or UART0.
+//!
+//! With `--features defmt`, the examples that log use `defmt::info!` and
+//! friends instead of the `log` crate. The format strings stay on the host -
+//! the board only sends an index and the raw arguments - so logging is
+//! quicker, and the binary loses most of its `core::fmt`. Each record is
+//! stamped with `scheduler::now()`, in milliseconds (which stays at zero if
+//! the scheduler isn't running).
+//!
+//! By default the records go out over RTT, which `probe-run` reads through
+//! the debug probe while it runs the program:
+//!
+//! ```text
+//! $ cargo run --features defmt --example hello
+//! ```
+//!
+//! (with the `probe-run` runner in `.cargo/config` uncommented).
+//! Without a probe, call `init(Output::Uart)` once UART0 is set up and feed
+//! the port to `defmt-print`:
+//!
+//! ```text
+//! $ stty -F /dev/ttyACM0 115200 raw
+//! $ defmt-print -e target/thumbv7em-none-eabihf/debug/examples/hello < /dev/ttyACM0
+//! ```
+//!
+//! Which levels are compiled in is set by the `DEFMT_LOG` environment
+//! variable when you build (e.g. `DEFMT_LOG=debug`); there's no run-time
+//! level. `build.rs` adds the `defmt.x` linker script it needs.
+//!
+//! `defmt` needs stable Rust 1.56 or newer, and the `nightly` examples only
+//! build on the 2018 nightlies, so `--features defmt` can't be used with
+//! `nightly` (or `heap` or `rtfm`, which turn it on).
+
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use cortex_m::interrupt;
+use cortex_m::register::primask;
+use defmt;
+use pac;
+use scheduler;
+
+/// How much RTT can hold before the probe reads it.
+const BUFFER_LEN: usize = 1024;
+
+/// RTT channel flags: drop what doesn't fit, or wait for the probe.
+/// `probe-run` switches to waiting once it's attached.
+const MODE_MASK: usize = 0b11;
+const MODE_BLOCK_IF_FULL: usize = 2;
+
+/// Where the records go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    /// RTT up channel 0, for `probe-run`
+    Rtt,
+    /// UART0, for `defmt-print`
+    Uart,
+}
+
+/// The RTT control block, which the probe finds by its `_SEGGER_RTT`
+/// symbol. We only have the one up channel.
+#[repr(C)]
+struct Header {
+    id: [u8; 16],
+    max_up_channels: usize,
+    max_down_channels: usize,
+    up: Channel,
+}
+
+#[repr(C)]
+struct Channel {
+    name: *const u8,
+    buffer: *mut u8,
+    size: usize,
+    /// Where we write next - only we move it
+    write: AtomicUsize,
+    /// Where the probe reads next - only it moves it
+    read: AtomicUsize,
+    flags: AtomicUsize,
+}
+
+static NAME: [u8; 6] = *b"defmt\0";
+
+static mut BUFFER: [u8; BUFFER_LEN] = [0; BUFFER_LEN];
+
+#[no_mangle]
+static mut _SEGGER_RTT: Header = Header {
+    id: *b"SEGGER RTT\0\0\0\0\0\0",
+    max_up_channels: 1,
+    max_down_channels: 0,
+    up: Channel {
+        name: &NAME as *const [u8; 6] as *const u8,
+        buffer: unsafe { ptr::addr_of_mut!(BUFFER) as *mut u8 },
+        size: BUFFER_LEN,
+        write: AtomicUsize::new(0),
+        read: AtomicUsize::new(0),
+        flags: AtomicUsize::new(0),
+    },
+};
+
+static TO_UART: AtomicBool = AtomicBool::new(false);
+
+/// Is a record being written? Only ever looked at with interrupts masked.
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Were interrupts on before `acquire` masked them?
+static mut RESTORE: bool = false;
+
+/// Turns records into frames `defmt-print` can pick out of the stream.
+static mut ENCODER: defmt::Encoder = defmt::Encoder::new();
+
+defmt::timestamp!("{=u32:ms}", scheduler::now());
+
+/// Choose where records go. Records sent before this go to RTT.
+pub fn init(output: Output) {
+    TO_UART.store(output == Output::Uart, Ordering::Relaxed);
+}
+
+#[defmt::global_logger]
+struct Logger;
+
+unsafe impl defmt::Logger for Logger {
+    fn acquire() {
+        let active = primask::read().is_active();
+        interrupt::disable();
+        if TAKEN.load(Ordering::Relaxed) {
+            // A record from inside `write` - a `Format` impl that logs
+            panic!("defmt logger taken reentrantly");
+        }
+        TAKEN.store(true, Ordering::Relaxed);
+        unsafe {
+            RESTORE = active;
+            ENCODER.start_frame(write_bytes);
+        }
+    }
+
+    unsafe fn flush() {
+        if TO_UART.load(Ordering::Relaxed) {
+            let uart = &*pac::UART0::ptr();
+            while uart.fr.read().busy().bit_is_set() {}
+        }
+    }
+
+    unsafe fn release() {
+        ENCODER.end_frame(write_bytes);
+        TAKEN.store(false, Ordering::Relaxed);
+        if RESTORE {
+            interrupt::enable();
+        }
+    }
+
+    unsafe fn write(bytes: &[u8]) {
+        ENCODER.write(bytes, write_bytes);
+    }
+}
+
+fn write_bytes(bytes: &[u8]) {
+    if TO_UART.load(Ordering::Relaxed) {
+        // No `\n` conversion here - it's a binary stream
+        let uart = unsafe { &*pac::UART0::ptr() };
+        for &byte in bytes {
+            while uart.fr.read().txff().bit_is_set() {}
+            uart.dr.write(|w| unsafe { w.data().bits(byte) });
+        }
+    } else {
+        rtt_write(bytes);
+    }
+}
+
+fn rtt_write(mut bytes: &[u8]) {
+    let channel = unsafe { &*ptr::addr_of!(_SEGGER_RTT.up) };
+    let blocking = channel.flags.load(Ordering::Relaxed) & MODE_MASK == MODE_BLOCK_IF_FULL;
+    while !bytes.is_empty() {
+        let read = channel.read.load(Ordering::Relaxed);
+        let write = channel.write.load(Ordering::Acquire);
+        // One byte always stays empty, so full and empty look different
+        let room = if read > write {
+            read - write - 1
+        } else if read == 0 {
+            BUFFER_LEN - write - 1
+        } else {
+            BUFFER_LEN - write
+        };
+        if room == 0 {
+            if blocking {
+                continue;
+            }
+            // Nobody's reading - drop the rest rather than hang
+            return;
+        }
+        let count = room.min(bytes.len());
+        unsafe {
+            ptr::copy_nonoverlapping(bytes.as_ptr(), channel.buffer.add(write), count);
+        }
+        channel
+            .write
+            .store((write + count) % BUFFER_LEN, Ordering::Release);
+        bytes = &bytes[count..];
+    }
+}
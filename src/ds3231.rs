@@ -0,0 +1,110 @@
+//! Drives a Maxim DS3231 temperature compensated real time clock over I2C.
+//!
+//! The DS3231 keeps time on its own coin cell, to within a couple of
+//! minutes a year, so it's a good alternative to fitting the 32.768 kHz
+//! crystal for the TM4C123's hibernation module. We always run it in 24
+//! hour mode.
+
+use datetime::DateTime;
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+
+/// The DS3231's fixed I2C address.
+pub const ADDRESS: u8 = 0x68;
+
+const REG_SECONDS: u8 = 0x00;
+const REG_CONTROL: u8 = 0x0E;
+const REG_STATUS: u8 = 0x0F;
+const REG_TEMP_MSB: u8 = 0x11;
+
+/// STATUS.OSF - the oscillator stopped at some point, so the time is junk.
+const STATUS_OSF: u8 = 0x80;
+
+/// Oscillator on, square wave and alarms off.
+const CONTROL_DEFAULT: u8 = 0x1C;
+
+pub struct Ds3231<I2C> {
+    i2c: I2C,
+}
+
+impl<I2C, E> Ds3231<I2C>
+where
+    I2C: Write<Error = E> + WriteRead<Error = E>,
+{
+    pub fn new(i2c: I2C) -> Result<Self, E> {
+        let mut rtc = Ds3231 { i2c };
+        rtc.i2c.write(ADDRESS, &[REG_CONTROL, CONTROL_DEFAULT])?;
+        Ok(rtc)
+    }
+
+    /// Give the bus back.
+    pub fn free(self) -> I2C {
+        self.i2c
+    }
+
+    /// Has the clock lost power (or never been set)? Setting the time
+    /// clears this.
+    pub fn lost_power(&mut self) -> Result<bool, E> {
+        let mut status = [0u8];
+        self.i2c.write_read(ADDRESS, &[REG_STATUS], &mut status)?;
+        Ok(status[0] & STATUS_OSF != 0)
+    }
+
+    /// Read the current date and time.
+    pub fn get(&mut self) -> Result<DateTime, E> {
+        let mut regs = [0u8; 7];
+        self.i2c.write_read(ADDRESS, &[REG_SECONDS], &mut regs)?;
+        let hours = if regs[2] & 0x40 != 0 {
+            // Someone else put it in 12 hour mode
+            let hour = bcd_to_bin(regs[2] & 0x1F) % 12;
+            if regs[2] & 0x20 != 0 {
+                hour + 12
+            } else {
+                hour
+            }
+        } else {
+            bcd_to_bin(regs[2] & 0x3F)
+        };
+        Ok(DateTime {
+            year: 2000 + u16::from(bcd_to_bin(regs[6])),
+            month: bcd_to_bin(regs[5] & 0x1F),
+            day: bcd_to_bin(regs[4] & 0x3F),
+            hours,
+            minutes: bcd_to_bin(regs[1] & 0x7F),
+            seconds: bcd_to_bin(regs[0] & 0x7F),
+        })
+    }
+
+    /// Set the date and time, and clear the lost power flag.
+    pub fn set(&mut self, dt: &DateTime) -> Result<(), E> {
+        self.i2c.write(
+            ADDRESS,
+            &[
+                REG_SECONDS,
+                bin_to_bcd(dt.seconds),
+                bin_to_bcd(dt.minutes),
+                bin_to_bcd(dt.hours),
+                dt.weekday() + 1,
+                bin_to_bcd(dt.day),
+                bin_to_bcd(dt.month),
+                bin_to_bcd((dt.year % 100) as u8),
+            ],
+        )?;
+        self.i2c.write(ADDRESS, &[REG_STATUS, 0x00])
+    }
+
+    /// The die temperature, in quarters of a degree C. It's updated every
+    /// 64 seconds.
+    pub fn temperature(&mut self) -> Result<i16, E> {
+        let mut regs = [0u8; 2];
+        self.i2c.write_read(ADDRESS, &[REG_TEMP_MSB], &mut regs)?;
+        Ok(i16::from(regs[0] as i8) * 4 + i16::from(regs[1] >> 6))
+    }
+}
+
+fn bcd_to_bin(bcd: u8) -> u8 {
+    ((bcd >> 4) * 10) + (bcd & 0x0F)
+}
+
+fn bin_to_bcd(bin: u8) -> u8 {
+    ((bin / 10) << 4) | (bin % 10)
+}
@@ -0,0 +1,131 @@
+//! Reads and writes the on-chip EEPROM.
+//!
+//! The TM4C123 has 2 KiB and the TM4C1294 has 6 KiB. We only use the first
+//! 2 KiB on either chip, so every address below means the same thing on
+//! both. The EEPROM is addressed in 32-bit words, in blocks of 16 words. We
+//! hide the blocks and just take a word address (0..512). Writes take a few
+//! hundred microseconds per word and we wait for each one.
+//!
+//! The caller must power up the EEPROM (`sysctl::Domain::Eeprom`) first.
+//!
+//! Examples are welcome to the bottom of it, and `records` keeps things
+//! there safely. Drivers that keep settings here go near the top, with a
+//! `pub const EEPROM_ADDRESS`: `keyboard` uses two words at `WORDS - 32`,
+//! `analog_joystick` four at `WORDS - 40` and `settings` eight at
+//! `WORDS - 48`. `selftest` borrows the last 16 words, and puts them back.
+
+use cortex_m::asm;
+use pac::{self, EEPROM};
+
+/// Size of the EEPROM in words.
+pub const WORDS: usize = 512;
+
+/// Words per block.
+const BLOCK_WORDS: usize = 16;
+
+/// EEDONE.WORKING
+const DONE_WORKING: u32 = 1 << 0;
+
+/// EESUPP.ERETRY and EESUPP.PRETRY
+const SUPP_RETRY: u32 = (1 << 2) | (1 << 3);
+
+/// Something went wrong with the EEPROM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The EEPROM didn't come up cleanly, even after a reset. The
+    /// datasheet says to give up and not touch it if this happens.
+    InitFailed,
+    /// The address (plus length) runs off the end
+    OutOfRange,
+    /// A write failed, with the given EEDONE flags
+    WriteFailed(u32),
+}
+
+pub struct Eeprom {
+    eeprom: EEPROM,
+}
+
+impl Eeprom {
+    /// Run the start-up checks from the datasheet. If the EEPROM was
+    /// part-way through a write or erase when the power went, it says so
+    /// in EESUPP, and resetting the EEPROM lets it finish the job.
+    pub fn new(eeprom: EEPROM) -> Result<Eeprom, Error> {
+        let mut e = Eeprom { eeprom };
+        e.wait_done();
+        if e.eeprom.eesupp.read().bits() & SUPP_RETRY == 0 {
+            return Ok(e);
+        }
+        reset();
+        e.wait_done();
+        if e.eeprom.eesupp.read().bits() & SUPP_RETRY != 0 {
+            return Err(Error::InitFailed);
+        }
+        Ok(e)
+    }
+
+    /// Read `data.len()` words, starting at word `address`.
+    pub fn read(&mut self, address: usize, data: &mut [u32]) -> Result<(), Error> {
+        if address + data.len() > WORDS {
+            return Err(Error::OutOfRange);
+        }
+        for (i, word) in data.iter_mut().enumerate() {
+            self.select(address + i);
+            *word = self.eeprom.eerdwr.read().bits();
+        }
+        Ok(())
+    }
+
+    /// Write `data.len()` words, starting at word `address`.
+    pub fn write(&mut self, address: usize, data: &[u32]) -> Result<(), Error> {
+        if address + data.len() > WORDS {
+            return Err(Error::OutOfRange);
+        }
+        for (i, word) in data.iter().enumerate() {
+            self.select(address + i);
+            self.eeprom.eerdwr.write(|w| unsafe { w.bits(*word) });
+            let done = self.wait_done();
+            if done != 0 {
+                return Err(Error::WriteFailed(done));
+            }
+        }
+        Ok(())
+    }
+
+    /// Give the EEPROM back.
+    pub fn free(self) -> EEPROM {
+        self.eeprom
+    }
+
+    fn select(&mut self, address: usize) {
+        let block = (address / BLOCK_WORDS) as u32;
+        let offset = (address % BLOCK_WORDS) as u32;
+        self.eeprom.eeblock.write(|w| unsafe { w.bits(block) });
+        self.eeprom.eeoffset.write(|w| unsafe { w.bits(offset) });
+    }
+
+    /// Wait for the current operation to finish, returning any error flags
+    /// in EEDONE.
+    fn wait_done(&mut self) -> u32 {
+        loop {
+            let done = self.eeprom.eedone.read().bits();
+            if done & DONE_WORKING == 0 {
+                return done;
+            }
+        }
+    }
+}
+
+/// Put the EEPROM through a reset with SREEPROM, and wait for it to be
+/// ready again.
+fn reset() {
+    let sysctl = unsafe { &*pac::SYSCTL::ptr() };
+    sysctl.sreeprom.write(|w| unsafe { w.bits(1) });
+    // The datasheet wants a few clocks with the reset held
+    for _ in 0..16 {
+        asm::nop();
+    }
+    sysctl.sreeprom.write(|w| unsafe { w.bits(0) });
+    while sysctl.preeprom.read().bits() & 1 == 0 {
+        asm::nop();
+    }
+}
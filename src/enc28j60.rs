@@ -0,0 +1,420 @@
+//! Drives a Microchip ENC28J60 10BASE-T Ethernet controller over SPI, and
+//! plugs it into smoltcp as a `Device`.
+//!
+//! The chip has 8 KiB of packet buffer. We give 6.5 KiB to received frames
+//! (which the chip stores as a ring) and the rest to the one frame we're
+//! sending. There are a few errata to step around, noted as we go - see
+//! Microchip's DS80349 for the details.
+//!
+//! The common modules are half-duplex only as far as the link partner is
+//! concerned (there's no autonegotiation), so that's how we set it up.
+//! We poll rather than use the INT pin.
+
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::blocking::spi;
+use embedded_hal::digital::OutputPin;
+use smoltcp;
+use smoltcp::phy::{self, DeviceCapabilities};
+use smoltcp::time::Instant;
+
+/// The largest frame we'll send or receive, including the CRC.
+pub const MAX_FRAME: usize = 1518;
+
+/// The ring of received frames.
+const RX_START: u16 = 0x0000;
+const RX_END: u16 = 0x19FF;
+
+/// The frame we're sending, with its control byte.
+const TX_START: u16 = 0x1A00;
+
+// SPI instructions
+const RCR: u8 = 0x00;
+const RBM: u8 = 0x3A;
+const WCR: u8 = 0x40;
+const WBM: u8 = 0x7A;
+const BFS: u8 = 0x80;
+const BFC: u8 = 0xA0;
+const SRC: u8 = 0xFF;
+
+// Registers are the address in the bottom five bits, the bank in the next
+// two, and the top bit set for MAC and MII registers, which send a dummy
+// byte before the data when you read them. 0x1B and up are in every bank.
+const ERDPTL: u8 = 0x00;
+const EWRPTL: u8 = 0x02;
+const ETXSTL: u8 = 0x04;
+const ETXNDL: u8 = 0x06;
+const ERXSTL: u8 = 0x08;
+const ERXNDL: u8 = 0x0A;
+const ERXRDPTL: u8 = 0x0C;
+const EIE: u8 = 0x1B;
+const EIR: u8 = 0x1C;
+const ESTAT: u8 = 0x1D;
+const ECON2: u8 = 0x1E;
+const ECON1: u8 = 0x1F;
+const ERXFCON: u8 = (1 << 5) | 0x18;
+const EPKTCNT: u8 = (1 << 5) | 0x19;
+const MACON1: u8 = 0x80 | (2 << 5);
+const MACON3: u8 = 0x80 | (2 << 5) | 0x02;
+const MACON4: u8 = 0x80 | (2 << 5) | 0x03;
+const MABBIPG: u8 = 0x80 | (2 << 5) | 0x04;
+const MAIPGL: u8 = 0x80 | (2 << 5) | 0x06;
+const MAIPGH: u8 = 0x80 | (2 << 5) | 0x07;
+const MAMXFLL: u8 = 0x80 | (2 << 5) | 0x0A;
+const MICMD: u8 = 0x80 | (2 << 5) | 0x12;
+const MIREGADR: u8 = 0x80 | (2 << 5) | 0x14;
+const MIWRL: u8 = 0x80 | (2 << 5) | 0x16;
+const MIWRH: u8 = 0x80 | (2 << 5) | 0x17;
+const MIRDL: u8 = 0x80 | (2 << 5) | 0x18;
+const MIRDH: u8 = 0x80 | (2 << 5) | 0x19;
+const MAADR5: u8 = 0x80 | (3 << 5);
+const MAADR6: u8 = 0x80 | (3 << 5) | 0x01;
+const MAADR3: u8 = 0x80 | (3 << 5) | 0x02;
+const MAADR4: u8 = 0x80 | (3 << 5) | 0x03;
+const MAADR1: u8 = 0x80 | (3 << 5) | 0x04;
+const MAADR2: u8 = 0x80 | (3 << 5) | 0x05;
+const MISTAT: u8 = 0x80 | (3 << 5) | 0x0A;
+const EREVID: u8 = (3 << 5) | 0x12;
+
+// PHY registers
+const PHCON2: u8 = 0x10;
+const PHSTAT2: u8 = 0x11;
+const PHLCON: u8 = 0x14;
+
+const ECON1_TXRST: u8 = 1 << 7;
+const ECON1_TXRTS: u8 = 1 << 3;
+const ECON1_RXEN: u8 = 1 << 2;
+const ECON1_BSEL: u8 = 0x03;
+const ECON2_AUTOINC: u8 = 1 << 7;
+const ECON2_PKTDEC: u8 = 1 << 6;
+const ESTAT_CLKRDY: u8 = 1 << 0;
+const EIR_TXIF: u8 = 1 << 3;
+const EIR_TXERIF: u8 = 1 << 1;
+/// Unicast to us, broadcast, and only with a good CRC
+const ERXFCON_UCEN_CRCEN_BCEN: u8 = 0xA1;
+const MACON1_TXPAUS_RXPAUS_MARXEN: u8 = 0x0D;
+/// Pad short frames, add the CRC, check the length field
+const MACON3_PADCFG0_TXCRCEN_FRMLNEN: u8 = 0x32;
+const MACON4_DEFER: u8 = 1 << 6;
+const MICMD_MIIRD: u8 = 1 << 0;
+const MISTAT_BUSY: u8 = 1 << 0;
+const PHCON2_HDLDIS: u16 = 1 << 8;
+const PHSTAT2_LSTAT: u16 = 1 << 10;
+/// LEDA shows link, LEDB shows activity
+const PHLCON_LINK_ACTIVITY: u16 = 0x0476;
+
+/// Bit 7 of the third status byte says the frame was good.
+const RSV_RECEIVED_OK: u8 = 1 << 7;
+
+/// Something went wrong talking to the ENC28J60.
+#[derive(Debug)]
+pub enum Error<E> {
+    /// The SPI bus reported an error
+    Spi(E),
+    /// The revision register read back as all zeros or all ones, so
+    /// there's probably no chip there
+    NotFound(u8),
+    /// The frame was too long
+    TooLong,
+}
+
+pub struct Enc28j60<SPI, CS> {
+    spi: SPI,
+    cs: CS,
+    bank: u8,
+    next_packet: u16,
+}
+
+impl<SPI, CS, E> Enc28j60<SPI, CS>
+where
+    SPI: spi::Transfer<u8, Error = E> + spi::Write<u8, Error = E>,
+    CS: OutputPin,
+{
+    /// Reset the chip and set it up with the given MAC address. The bus
+    /// should be SPI mode 0 at up to 20 MHz (but note the errata about
+    /// the slower revisions needing at least 8 MHz).
+    pub fn new<D>(spi: SPI, cs: CS, delay: &mut D, mac: [u8; 6]) -> Result<Self, Error<E>>
+    where
+        D: DelayMs<u32>,
+    {
+        let mut eth = Enc28j60 {
+            spi,
+            cs,
+            bank: 0,
+            next_packet: RX_START,
+        };
+        eth.cs.set_high();
+        eth.command(&mut [SRC])?;
+        // CLKRDY can't be trusted straight after a reset, so wait first
+        delay.delay_ms(1);
+        while eth.read(ESTAT)? & ESTAT_CLKRDY == 0 {}
+
+        let revision = eth.read(EREVID)?;
+        if revision == 0x00 || revision == 0xFF {
+            return Err(Error::NotFound(revision));
+        }
+
+        eth.write_u16(ERXSTL, RX_START)?;
+        eth.write_u16(ERXNDL, RX_END)?;
+        // The read pointer must always be odd
+        eth.write_u16(ERXRDPTL, RX_END)?;
+        eth.write_u16(ETXSTL, TX_START)?;
+        eth.set_bits(ECON2, ECON2_AUTOINC)?;
+        eth.write(ERXFCON, ERXFCON_UCEN_CRCEN_BCEN)?;
+
+        eth.write(MACON1, MACON1_TXPAUS_RXPAUS_MARXEN)?;
+        eth.write(MACON3, MACON3_PADCFG0_TXCRCEN_FRMLNEN)?;
+        eth.write(MACON4, MACON4_DEFER)?;
+        eth.write_u16(MAMXFLL, MAX_FRAME as u16)?;
+        // Inter-packet gaps for half-duplex
+        eth.write(MABBIPG, 0x12)?;
+        eth.write(MAIPGL, 0x12)?;
+        eth.write(MAIPGH, 0x0C)?;
+        eth.write(MAADR1, mac[0])?;
+        eth.write(MAADR2, mac[1])?;
+        eth.write(MAADR3, mac[2])?;
+        eth.write(MAADR4, mac[3])?;
+        eth.write(MAADR5, mac[4])?;
+        eth.write(MAADR6, mac[5])?;
+
+        // Don't loop back our own transmissions
+        eth.write_phy(PHCON2, PHCON2_HDLDIS)?;
+        eth.write_phy(PHLCON, PHLCON_LINK_ACTIVITY)?;
+
+        eth.write(EIE, 0)?;
+        eth.set_bits(ECON1, ECON1_RXEN)?;
+        Ok(eth)
+    }
+
+    /// Give back the bus and the chip select pin.
+    pub fn free(self) -> (SPI, CS) {
+        (self.spi, self.cs)
+    }
+
+    /// Is the cable plugged in at both ends?
+    pub fn is_link_up(&mut self) -> Result<bool, Error<E>> {
+        Ok(self.read_phy(PHSTAT2)? & PHSTAT2_LSTAT != 0)
+    }
+
+    /// Copy the next received frame (without its CRC) into `buffer` and
+    /// return its length. Bad frames and ones that don't fit are dropped,
+    /// and returned as `Some(0)`.
+    pub fn receive(&mut self, buffer: &mut [u8]) -> Result<Option<usize>, Error<E>> {
+        if self.read(EPKTCNT)? == 0 {
+            return Ok(None);
+        }
+        let start = self.next_packet;
+        self.write_u16(ERDPTL, start)?;
+        // Next packet pointer, then the receive status vector
+        let mut header = [0u8; 6];
+        self.read_buffer(&mut header)?;
+        let next = u16::from(header[0]) | (u16::from(header[1]) << 8);
+        let len = (usize::from(header[2]) | (usize::from(header[3]) << 8)).saturating_sub(4);
+        let ok = header[4] & RSV_RECEIVED_OK != 0;
+
+        let result = if ok && len <= buffer.len() {
+            self.read_buffer(&mut buffer[0..len])?;
+            len
+        } else {
+            0
+        };
+
+        // Free up the space. The read pointer must be odd, so it goes one
+        // behind the next packet (which is always even).
+        self.next_packet = next;
+        let read_pointer = if next == RX_START { RX_END } else { next - 1 };
+        self.write_u16(ERXRDPTL, read_pointer)?;
+        self.set_bits(ECON2, ECON2_PKTDEC)?;
+        Ok(Some(result))
+    }
+
+    /// Send a frame. The chip adds any padding and the CRC. We wait for the
+    /// last frame to go first, but not for this one.
+    pub fn transmit(&mut self, frame: &[u8]) -> Result<(), Error<E>> {
+        if frame.len() > MAX_FRAME - 4 {
+            return Err(Error::TooLong);
+        }
+        while self.read(ECON1)? & ECON1_TXRTS != 0 {}
+        // The transmit logic can get stuck after an error, so reset it
+        // every time
+        self.set_bits(ECON1, ECON1_TXRST)?;
+        self.clear_bits(ECON1, ECON1_TXRST)?;
+        self.clear_bits(EIR, EIR_TXIF | EIR_TXERIF)?;
+
+        self.write_u16(EWRPTL, TX_START)?;
+        // A control byte of zero means 'use the MACON3 settings'
+        self.write_buffer(&[0x00])?;
+        self.write_buffer(frame)?;
+        self.write_u16(ETXSTL, TX_START)?;
+        self.write_u16(ETXNDL, TX_START + frame.len() as u16)?;
+        self.set_bits(ECON1, ECON1_TXRTS)
+    }
+
+    fn select_bank(&mut self, register: u8) -> Result<(), Error<E>> {
+        let address = register & 0x1F;
+        let bank = (register >> 5) & 0x03;
+        if address >= 0x1B || bank == self.bank {
+            return Ok(());
+        }
+        self.command(&mut [BFC | ECON1, ECON1_BSEL])?;
+        self.command(&mut [BFS | ECON1, bank])?;
+        self.bank = bank;
+        Ok(())
+    }
+
+    fn read(&mut self, register: u8) -> Result<u8, Error<E>> {
+        self.select_bank(register)?;
+        let mut buffer = [RCR | (register & 0x1F), 0, 0];
+        if register & 0x80 != 0 {
+            self.command(&mut buffer)?;
+            Ok(buffer[2])
+        } else {
+            self.command(&mut buffer[0..2])?;
+            Ok(buffer[1])
+        }
+    }
+
+    fn write(&mut self, register: u8, value: u8) -> Result<(), Error<E>> {
+        self.select_bank(register)?;
+        self.command(&mut [WCR | (register & 0x1F), value])
+    }
+
+    /// Write the low byte to `register` and the high byte to the one after.
+    fn write_u16(&mut self, register: u8, value: u16) -> Result<(), Error<E>> {
+        self.write(register, value as u8)?;
+        self.write(register + 1, (value >> 8) as u8)
+    }
+
+    /// Only works on the ETH registers, not the MAC and MII ones.
+    fn set_bits(&mut self, register: u8, bits: u8) -> Result<(), Error<E>> {
+        self.select_bank(register)?;
+        self.command(&mut [BFS | (register & 0x1F), bits])
+    }
+
+    fn clear_bits(&mut self, register: u8, bits: u8) -> Result<(), Error<E>> {
+        self.select_bank(register)?;
+        self.command(&mut [BFC | (register & 0x1F), bits])
+    }
+
+    fn read_phy(&mut self, register: u8) -> Result<u16, Error<E>> {
+        self.write(MIREGADR, register)?;
+        self.write(MICMD, MICMD_MIIRD)?;
+        while self.read(MISTAT)? & MISTAT_BUSY != 0 {}
+        self.write(MICMD, 0)?;
+        let low = self.read(MIRDL)?;
+        let high = self.read(MIRDH)?;
+        Ok(u16::from(low) | (u16::from(high) << 8))
+    }
+
+    fn write_phy(&mut self, register: u8, value: u16) -> Result<(), Error<E>> {
+        self.write(MIREGADR, register)?;
+        self.write(MIWRL, value as u8)?;
+        // Writing the high byte starts the transaction
+        self.write(MIWRH, (value >> 8) as u8)?;
+        while self.read(MISTAT)? & MISTAT_BUSY != 0 {}
+        Ok(())
+    }
+
+    fn read_buffer(&mut self, buffer: &mut [u8]) -> Result<(), Error<E>> {
+        self.cs.set_low();
+        let result = self
+            .spi
+            .write(&[RBM])
+            .and_then(|_| self.spi.transfer(buffer).map(|_| ()));
+        self.cs.set_high();
+        result.map_err(Error::Spi)
+    }
+
+    fn write_buffer(&mut self, data: &[u8]) -> Result<(), Error<E>> {
+        self.cs.set_low();
+        let result = self.spi.write(&[WBM]).and_then(|_| self.spi.write(data));
+        self.cs.set_high();
+        result.map_err(Error::Spi)
+    }
+
+    fn command(&mut self, buffer: &mut [u8]) -> Result<(), Error<E>> {
+        self.cs.set_low();
+        let result = self.spi.transfer(buffer).map(|_| ());
+        self.cs.set_high();
+        result.map_err(Error::Spi)
+    }
+}
+
+/// A frame we've already read out of the chip.
+pub struct RxToken {
+    buffer: [u8; MAX_FRAME],
+    len: usize,
+}
+
+/// Permission to send one frame.
+pub struct TxToken<'a, SPI: 'a, CS: 'a> {
+    eth: &'a mut Enc28j60<SPI, CS>,
+}
+
+impl<'a, SPI, CS, E> phy::Device<'a> for Enc28j60<SPI, CS>
+where
+    SPI: spi::Transfer<u8, Error = E> + spi::Write<u8, Error = E> + 'a,
+    CS: OutputPin + 'a,
+{
+    type RxToken = RxToken;
+    type TxToken = TxToken<'a, SPI, CS>;
+
+    fn receive(&'a mut self) -> Option<(RxToken, TxToken<'a, SPI, CS>)> {
+        let mut rx = RxToken {
+            buffer: [0u8; MAX_FRAME],
+            len: 0,
+        };
+        // Skip over any bad frames
+        loop {
+            let result = Enc28j60::receive(self, &mut rx.buffer);
+            match result {
+                Ok(Some(0)) => {}
+                Ok(Some(len)) => {
+                    rx.len = len;
+                    return Some((rx, TxToken { eth: self }));
+                }
+                Ok(None) | Err(_) => return None,
+            }
+        }
+    }
+
+    fn transmit(&'a mut self) -> Option<TxToken<'a, SPI, CS>> {
+        Some(TxToken { eth: self })
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.max_transmission_unit = MAX_FRAME - 4;
+        caps.max_burst_size = Some(1);
+        caps
+    }
+}
+
+impl phy::RxToken for RxToken {
+    fn consume<R, F>(self, _timestamp: Instant, f: F) -> smoltcp::Result<R>
+    where
+        F: FnOnce(&[u8]) -> smoltcp::Result<R>,
+    {
+        f(&self.buffer[0..self.len])
+    }
+}
+
+impl<'a, SPI, CS, E> phy::TxToken for TxToken<'a, SPI, CS>
+where
+    SPI: spi::Transfer<u8, Error = E> + spi::Write<u8, Error = E>,
+    CS: OutputPin,
+{
+    fn consume<R, F>(self, _timestamp: Instant, len: usize, f: F) -> smoltcp::Result<R>
+    where
+        F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
+    {
+        if len > MAX_FRAME - 4 {
+            return Err(smoltcp::Error::Truncated);
+        }
+        let mut buffer = [0u8; MAX_FRAME];
+        let result = f(&mut buffer[0..len])?;
+        self.eth
+            .transmit(&buffer[0..len])
+            .map_err(|_| smoltcp::Error::Exhausted)?;
+        Ok(result)
+    }
+}
@@ -0,0 +1,369 @@
+//! Drives an ESP8266 WiFi module running Espressif's AT command firmware.
+//!
+//! We send a command and then read lines until the module says `OK`, or
+//! `ERROR`, or we run out of patience. In between, the module can send
+//! 'unsolicited result codes' at any time - `WIFI DISCONNECT`, `CLOSED`
+//! and, most importantly, `+IPD,<len>:<data>`, which is how data from the
+//! network turns up. We deal with those whenever we're reading, stashing
+//! any data until `receive` is called.
+//!
+//! We use a single connection at a time (`AT+CIPMUX=0`), which is all the
+//! examples need. The module defaults to 115200 bps. Without hardware flow
+//! control it will happily overrun us, so the data buffer is generous and
+//! callers should read it often.
+
+use core::fmt;
+use embedded_hal::blocking::delay::DelayUs;
+use embedded_hal::serial;
+
+/// The longest response line we keep. Longer ones are cut short.
+const MAX_LINE: usize = 128;
+
+/// How much received network data we can hold.
+pub const RX_BUFFER: usize = 1024;
+
+/// The most we can send in one `AT+CIPSEND`.
+const MAX_SEND: usize = 2048;
+
+/// Ordinary commands should be done in this long.
+const COMMAND_TIMEOUT_MS: u32 = 2_000;
+
+/// Joining a network takes a while.
+const JOIN_TIMEOUT_MS: u32 = 20_000;
+
+/// As does opening a connection, with a DNS lookup.
+const CONNECT_TIMEOUT_MS: u32 = 10_000;
+
+/// Something went wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The UART reported an error
+    Serial,
+    /// The module didn't answer in time
+    Timeout,
+    /// The module said `ERROR` or `FAIL`
+    Failed,
+    /// The connection has closed
+    Closed,
+}
+
+/// What kind of connection to open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+/// Something we read.
+enum Event {
+    /// A complete line, of this length
+    Line(usize),
+    /// The `>` that means 'send your data now'
+    Prompt,
+}
+
+pub struct Esp8266<TX, RX, D> {
+    tx: TX,
+    rx: RX,
+    delay: D,
+    line: [u8; MAX_LINE],
+    line_len: usize,
+    /// Bytes of `+IPD` data still to come
+    ipd_remaining: usize,
+    data: [u8; RX_BUFFER],
+    data_len: usize,
+    /// The last `+` reply to a command, like `+CIFSR:STAIP,"10.0.0.2"`
+    reply: [u8; MAX_LINE],
+    reply_len: usize,
+    connected: bool,
+}
+
+/// Lets us `write!` commands straight out of the UART.
+struct Sender<'a, TX: 'a>(&'a mut TX);
+
+impl<'a, TX> fmt::Write for Sender<'a, TX>
+where
+    TX: serial::Write<u8>,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            block!(self.0.write(byte)).map_err(|_| fmt::Error)?;
+        }
+        Ok(())
+    }
+}
+
+impl<TX, RX, D> Esp8266<TX, RX, D>
+where
+    TX: serial::Write<u8>,
+    RX: serial::Read<u8>,
+    D: DelayUs<u32>,
+{
+    /// Reset the module and set it up as a WiFi client with one connection
+    /// at a time.
+    pub fn new(tx: TX, rx: RX, delay: D) -> Result<Self, Error> {
+        let mut esp = Esp8266 {
+            tx,
+            rx,
+            delay,
+            line: [0u8; MAX_LINE],
+            line_len: 0,
+            ipd_remaining: 0,
+            data: [0u8; RX_BUFFER],
+            data_len: 0,
+            reply: [0u8; MAX_LINE],
+            reply_len: 0,
+            connected: false,
+        };
+        esp.command(format_args!("AT+RST"), COMMAND_TIMEOUT_MS)?;
+        esp.wait_for("ready", COMMAND_TIMEOUT_MS)?;
+        // No echo
+        esp.command(format_args!("ATE0"), COMMAND_TIMEOUT_MS)?;
+        // Station mode
+        esp.command(format_args!("AT+CWMODE=1"), COMMAND_TIMEOUT_MS)?;
+        esp.command(format_args!("AT+CIPMUX=0"), COMMAND_TIMEOUT_MS)?;
+        Ok(esp)
+    }
+
+    /// Give back the UART and the delay.
+    pub fn free(self) -> (TX, RX, D) {
+        (self.tx, self.rx, self.delay)
+    }
+
+    /// Join a WiFi network.
+    pub fn join(&mut self, ssid: &str, password: &str) -> Result<(), Error> {
+        self.command(
+            format_args!("AT+CWJAP=\"{}\",\"{}\"", ssid, password),
+            JOIN_TIMEOUT_MS,
+        )
+    }
+
+    /// Get our IP address, once we've joined a network. It's written into
+    /// `buffer`, and we return the part that was used.
+    pub fn local_ip<'b>(&mut self, buffer: &'b mut [u8]) -> Result<&'b str, Error> {
+        self.command(format_args!("AT+CIFSR"), COMMAND_TIMEOUT_MS)?;
+        // The reply we kept is the last one, which may be the MAC address,
+        // so look for the quoted part of `+CIFSR:STAIP,"a.b.c.d"` instead
+        let reply = self.reply();
+        let ip = reply.split('"').nth(1).unwrap_or("");
+        let len = ip.len().min(buffer.len());
+        buffer[0..len].copy_from_slice(&ip.as_bytes()[0..len]);
+        ::core::str::from_utf8(&buffer[0..len]).map_err(|_| Error::Failed)
+    }
+
+    /// Open a connection. `host` can be a name or an IP address.
+    pub fn connect(&mut self, protocol: Protocol, host: &str, port: u16) -> Result<(), Error> {
+        let kind = match protocol {
+            Protocol::Tcp => "TCP",
+            Protocol::Udp => "UDP",
+        };
+        self.data_len = 0;
+        self.command(
+            format_args!("AT+CIPSTART=\"{}\",\"{}\",{}", kind, host, port),
+            CONNECT_TIMEOUT_MS,
+        )?;
+        self.connected = true;
+        Ok(())
+    }
+
+    /// Is the connection still open, as far as we know?
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// Send data down the connection.
+    pub fn send(&mut self, data: &[u8]) -> Result<(), Error> {
+        for chunk in data.chunks(MAX_SEND) {
+            if !self.connected {
+                return Err(Error::Closed);
+            }
+            self.start_command(format_args!("AT+CIPSEND={}", chunk.len()))?;
+            self.wait_for_prompt(COMMAND_TIMEOUT_MS)?;
+            for byte in chunk {
+                block!(self.tx.write(*byte)).map_err(|_| Error::Serial)?;
+            }
+            self.wait_for("SEND OK", COMMAND_TIMEOUT_MS)?;
+        }
+        Ok(())
+    }
+
+    /// Copy out any data that has arrived, waiting up to `timeout_ms` for
+    /// some if there isn't any yet. Returns how many bytes we copied, which
+    /// is zero if nothing came. Once the connection has closed and the
+    /// data has all been read, we return `Error::Closed`.
+    pub fn receive(&mut self, buffer: &mut [u8], timeout_ms: u32) -> Result<usize, Error> {
+        let mut ticks = timeout_ms * 10;
+        while self.data_len == 0 && self.connected && ticks > 0 {
+            // There shouldn't be any lines now, other than URCs, which
+            // `poll` has dealt with
+            let _ = self.poll()?;
+            self.delay.delay_us(100);
+            ticks -= 1;
+        }
+        if self.data_len == 0 && !self.connected {
+            return Err(Error::Closed);
+        }
+        let len = self.data_len.min(buffer.len());
+        buffer[0..len].copy_from_slice(&self.data[0..len]);
+        // Shuffle down what's left
+        for i in len..self.data_len {
+            self.data[i - len] = self.data[i];
+        }
+        self.data_len -= len;
+        Ok(len)
+    }
+
+    /// Close the connection.
+    pub fn close(&mut self) -> Result<(), Error> {
+        if !self.connected {
+            return Ok(());
+        }
+        let result = self.command(format_args!("AT+CIPCLOSE"), COMMAND_TIMEOUT_MS);
+        self.connected = false;
+        result
+    }
+
+    /// Do nothing for a while, but keep an eye on what the module says.
+    pub fn wait(&mut self, ms: u32) -> Result<(), Error> {
+        for _ in 0..ms * 10 {
+            let _ = self.poll()?;
+            self.delay.delay_us(100);
+        }
+        Ok(())
+    }
+
+    /// The last `+` reply we saw, without the `+`.
+    pub fn reply(&self) -> &str {
+        ::core::str::from_utf8(&self.reply[0..self.reply_len]).unwrap_or("")
+    }
+
+    /// Send a command and wait for `OK`.
+    pub fn command(&mut self, command: fmt::Arguments, timeout_ms: u32) -> Result<(), Error> {
+        self.start_command(command)?;
+        self.wait_for("OK", timeout_ms)
+    }
+
+    fn start_command(&mut self, command: fmt::Arguments) -> Result<(), Error> {
+        self.reply_len = 0;
+        let mut sender = Sender(&mut self.tx);
+        fmt::Write::write_fmt(&mut sender, command).map_err(|_| Error::Serial)?;
+        fmt::Write::write_str(&mut sender, "\r\n").map_err(|_| Error::Serial)
+    }
+
+    /// Read lines until we get the one we want, or an error.
+    fn wait_for(&mut self, wanted: &str, timeout_ms: u32) -> Result<(), Error> {
+        let mut ticks = timeout_ms * 10;
+        while ticks > 0 {
+            match self.poll()? {
+                Some(Event::Line(len)) => {
+                    let line = &self.line[0..len];
+                    if line == wanted.as_bytes() {
+                        return Ok(());
+                    }
+                    if line == b"ERROR" || line == b"FAIL" || line == b"SEND FAIL" {
+                        return Err(Error::Failed);
+                    }
+                    if line.starts_with(b"+") {
+                        self.reply[0..len - 1].copy_from_slice(&line[1..]);
+                        self.reply_len = len - 1;
+                    }
+                }
+                Some(Event::Prompt) => {}
+                None => {
+                    self.delay.delay_us(100);
+                    ticks -= 1;
+                }
+            }
+        }
+        Err(Error::Timeout)
+    }
+
+    fn wait_for_prompt(&mut self, timeout_ms: u32) -> Result<(), Error> {
+        let mut ticks = timeout_ms * 10;
+        while ticks > 0 {
+            match self.poll()? {
+                Some(Event::Prompt) => return Ok(()),
+                Some(Event::Line(len)) => {
+                    let line = &self.line[0..len];
+                    if line == b"ERROR" || line == b"link is not valid" {
+                        return Err(Error::Failed);
+                    }
+                }
+                None => {
+                    self.delay.delay_us(100);
+                    ticks -= 1;
+                }
+            }
+        }
+        Err(Error::Timeout)
+    }
+
+    /// Deal with whatever the UART has for us, until we have a complete
+    /// line (or prompt) or run out of bytes. URCs are handled here and
+    /// not returned.
+    fn poll(&mut self) -> Result<Option<Event>, Error> {
+        loop {
+            let byte = match self.rx.read() {
+                Ok(byte) => byte,
+                Err(::nb::Error::WouldBlock) => return Ok(None),
+                // Usually an overrun. Drop whatever we were working on.
+                Err(::nb::Error::Other(_)) => {
+                    self.line_len = 0;
+                    continue;
+                }
+            };
+
+            if self.ipd_remaining > 0 {
+                if self.data_len < RX_BUFFER {
+                    self.data[self.data_len] = byte;
+                    self.data_len += 1;
+                }
+                self.ipd_remaining -= 1;
+                continue;
+            }
+
+            match byte {
+                b'\r' => {}
+                b'\n' => {
+                    let len = self.line_len;
+                    self.line_len = 0;
+                    if len == 0 {
+                        continue;
+                    }
+                    if self.unsolicited(len) {
+                        continue;
+                    }
+                    return Ok(Some(Event::Line(len)));
+                }
+                b'>' if self.line_len == 0 => return Ok(Some(Event::Prompt)),
+                b':' if self.line.starts_with(b"+IPD,") && self.line_len >= 5 => {
+                    // The data follows straight on - no newline
+                    self.ipd_remaining = ::core::str::from_utf8(&self.line[5..self.line_len])
+                        .ok()
+                        .and_then(|s| s.parse().ok())
+                        .unwrap_or(0);
+                    self.line_len = 0;
+                }
+                _ => {
+                    if self.line_len < MAX_LINE {
+                        self.line[self.line_len] = byte;
+                        self.line_len += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    /// If this line is an unsolicited result code, deal with it and return
+    /// true.
+    fn unsolicited(&mut self, len: usize) -> bool {
+        match &self.line[0..len] {
+            b"CLOSED" | b"WIFI DISCONNECT" => {
+                self.connected = false;
+                true
+            }
+            b"WIFI CONNECTED" | b"WIFI GOT IP" | b"CONNECT" => true,
+            _ => false,
+        }
+    }
+}
@@ -1,33 +1,50 @@
 //! Prints "Hello, world!" on the OpenOCD console using semihosting
 //!
+//! Semihosting needs a debugger attached, so release builds print on UART0
+//! (115200 bps) instead, and run on a bare LaunchPad.
+//!
 //! ---
 //!
 //! ```
 //!
-//! #![feature(used)]
+//! #![no_main]
 //! #![no_std]
 //!
-//! extern crate cortex_m;
-//! extern crate cortex_m_rt;
-//! extern crate cortex_m_semihosting;
+//! #[macro_use(entry, exception)]
+//! extern crate cortex_m_rt as rt;
+//! extern crate demo;
+//! #[macro_use]
+//! extern crate log;
+//! extern crate panic_halt;
+//! extern crate tm4c123x_hal;
+//!
+//! use demo::board::Board;
+//! use demo::logger;
+//! use rt::ExceptionFrame;
+//!
+//! entry!(main);
 //!
-//! use core::fmt::Write;
+//! fn main() -> ! {
+//!     let p = tm4c123x_hal::Peripherals::take().unwrap();
+//!     // Release builds need UART0
+//!     let _board = Board::new(p.SYSCTL, p.GPIO_PORTA, p.GPIO_PORTF, p.UART0);
+//!
+//!     logger::init(logger::Output::Semihosting, log::LevelFilter::Info);
+//!     info!("Hello, world!");
+//!
+//!     loop {}
+//! }
 //!
-//! use cortex_m::asm;
-//! use cortex_m_semihosting::hio;
+//! exception!(HardFault, hard_fault);
 //!
-//! fn main() {
-//!     let mut stdout = hio::hstdout().unwrap();
-//!     writeln!(stdout, "Hello, world!").unwrap();
+//! fn hard_fault(ef: &ExceptionFrame) -> ! {
+//!     panic!("HardFault at {:#?}", ef);
 //! }
 //!
-//! // As we are not using interrupts, we just register a dummy catch all handler
-//! #[link_section = ".vector_table.interrupts"]
-//! #[used]
-//! static INTERRUPTS: [extern "C" fn(); 240] = [default_handler; 240];
+//! exception!(*, default_handler);
 //!
-//! extern "C" fn default_handler() {
-//!     asm::bkpt();
+//! fn default_handler(irqn: i16) {
+//!     panic!("Unhandled exception (IRQn = {})", irqn);
 //! }
 //! ```
 // Auto-generated. Do not modify.
@@ -9,36 +9,45 @@
 //! You'll need [`itmdump`] to receive the message on the host plus you'll need
 //! to uncomment the `monitor` commands in the `.gdbinit` file.
 //!
-//! [`itmdump`]: https://docs.rs/itm/0.1.1/itm/
+//! [`itmdump`]: https://docs.rs/itm/0.2.1/itm/
 //!
 //! ---
 //!
 //! ```
 //!
-//! #![feature(used)]
+//! #![no_main]
 //! #![no_std]
 //!
 //! #[macro_use]
 //! extern crate cortex_m;
-//! extern crate cortex_m_rt;
+//! #[macro_use(entry, exception)]
+//! extern crate cortex_m_rt as rt;
+//! extern crate panic_halt;
 //!
-//! use cortex_m::{asm, interrupt, peripheral};
+//! use cortex_m::Peripherals;
+//! use rt::ExceptionFrame;
 //!
-//! fn main() {
-//!     interrupt::free(|cs| {
-//!         let itm = peripheral::ITM.borrow(&cs);
+//! entry!(main);
 //!
-//!         iprintln!(&itm.stim[0], "Hello, world!");
-//!     });
+//! fn main() -> ! {
+//!     let mut p = Peripherals::take().unwrap();
+//!     let stim = &mut p.ITM.stim[0];
+//!
+//!     iprintln!(stim, "Hello, world!");
+//!
+//!     loop {}
+//! }
+//!
+//! exception!(HardFault, hard_fault);
+//!
+//! fn hard_fault(ef: &ExceptionFrame) -> ! {
+//!     panic!("HardFault at {:#?}", ef);
 //! }
 //!
-//! // As we are not using interrupts, we just register a dummy catch all handler
-//! #[link_section = ".vector_table.interrupts"]
-//! #[used]
-//! static INTERRUPTS: [extern "C" fn(); 240] = [default_handler; 240];
+//! exception!(*, default_handler);
 //!
-//! extern "C" fn default_handler() {
-//!     asm::bkpt();
+//! fn default_handler(irqn: i16) {
+//!     panic!("Unhandled exception (IRQn = {})", irqn);
 //! }
 //! ```
 // Auto-generated. Do not modify.
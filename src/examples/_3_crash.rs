@@ -1,9 +1,8 @@
 //! Debugging a crash (exception)
 //!
-//! The `cortex-m-rt` crate provides functionality for this through a default
-//! exception handler. When an exception is hit, the default handler will
-//! trigger a breakpoint and in this debugging context the stacked registers
-//! are accessible.
+//! The `cortex-m-rt` crate hands the HardFault handler the registers that
+//! were stacked when the exception was hit. Put a breakpoint on the handler
+//! and, in this debugging context, the stacked registers are accessible.
 //!
 //! In you run the example below, you'll be able to inspect the state of your
 //! program under the debugger using these commands:
@@ -11,7 +10,7 @@
 //! ``` text
 //! (gdb) # Exception frame = program state during the crash
 //! (gdb) print/x *ef
-//! $1 = cortex_m::exception::ExceptionFrame {
+//! $1 = cortex_m_rt::ExceptionFrame {
 //!   r0 = 0x2fffffff,
 //!   r1 = 0x2fffffff,
 //!   r2 = 0x0,
@@ -24,10 +23,10 @@
 //!
 //! (gdb) # Where did we come from?
 //! (gdb) backtrace
-//! #0  cortex_m_rt::default_handler (ef=0x20004f54) at (..)
+//! #0  crash::hard_fault (ef=0x20004f54) at (..)
 //! #1  <signal handler called>
 //! #2  0x08000460 in core::ptr::read_volatile<u32> (src=0x2fffffff) at (..)
-//! #3  0x08000480 in crash::main () at examples/crash.rs:68
+//! #3  0x08000480 in crash::main () at examples/crash.rs:76
 //!
 //! (gdb) # Nail down the location of the crash
 //! (gdb) disassemble/m ef.pc
@@ -60,30 +59,39 @@
 //!
 //! ```
 //!
-//! #![feature(used)]
+//! #![no_main]
 //! #![no_std]
 //!
-//! extern crate cortex_m;
-//! extern crate cortex_m_rt;
+//! #[macro_use(entry, exception)]
+//! extern crate cortex_m_rt as rt;
+//! extern crate panic_halt;
 //!
 //! use core::ptr;
 //!
-//! use cortex_m::asm;
+//! use rt::ExceptionFrame;
 //!
-//! fn main() {
+//! entry!(main);
+//!
+//! fn main() -> ! {
 //!     // Read an invalid memory address
 //!     unsafe {
 //!         ptr::read_volatile(0x2FFF_FFFF as *const u32);
 //!     }
+//!
+//!     loop {}
+//! }
+//!
+//! exception!(HardFault, hard_fault);
+//!
+//! fn hard_fault(ef: &ExceptionFrame) -> ! {
+//!     // Inspect `ef` here
+//!     panic!("HardFault at {:#?}", ef);
 //! }
 //!
-//! // As we are not using interrupts, we just register a dummy catch all handler
-//! #[link_section = ".vector_table.interrupts"]
-//! #[used]
-//! static INTERRUPTS: [extern "C" fn(); 240] = [default_handler; 240];
+//! exception!(*, default_handler);
 //!
-//! extern "C" fn default_handler() {
-//!     asm::bkpt();
+//! fn default_handler(irqn: i16) {
+//!     panic!("Unhandled exception (IRQn = {})", irqn);
 //! }
 //! ```
 // Auto-generated. Do not modify.
@@ -2,96 +2,96 @@
 //!
 //! Crates generated using [`svd2rust`] are referred to as device crates. These
 //! crates provides an API to access the peripherals of a device. When you
-//! depend on one of these crates and the "rt" feature is enabled you don't need
-//! link to the cortex-m-rt crate.
+//! depend on one of these crates with the "rt" feature enabled, it provides
+//! the device's interrupt vectors for cortex-m-rt.
 //!
 //! [`svd2rust`]: https://crates.io/crates/svd2rust
 //!
 //! Device crates also provide an `interrupt!` macro to register interrupt
 //! handlers.
 //!
-//! This example depends on the [`stm32f103xx`] crate so you'll have to add it
-//! to your Cargo.toml.
+//! This example uses the [`tm4c123x`] crate, which this crate already
+//! depends on.
 //!
-//! [`stm32f103xx`]: https://crates.io/crates/stm32f103xx
-//!
-//! ```
-//! $ edit Cargo.toml && cat $_
-//! [dependencies.stm32f103xx]
-//! features = ["rt"]
-//! version = "0.7.0"
-//! ```
+//! [`tm4c123x`]: https://crates.io/crates/tm4c123x
 //!
 //! ---
 //!
 //! ```
 //!
-//! #![deny(warnings)]
-//! #![feature(const_fn)]
+//! #![no_main]
 //! #![no_std]
 //!
 //! extern crate cortex_m;
-//! extern crate cortex_m_semihosting;
-//! #[macro_use(exception, interrupt)]
-//! extern crate stm32f103xx;
+//! #[macro_use(entry, exception)]
+//! extern crate cortex_m_rt as rt;
+//! extern crate cortex_m_semihosting as sh;
+//! extern crate panic_halt;
+//! #[macro_use(interrupt)]
+//! extern crate tm4c123x;
 //!
-//! use core::cell::RefCell;
 //! use core::fmt::Write;
 //!
-//! use cortex_m::interrupt::{self, Mutex};
-//! use cortex_m::peripheral::SystClkSource;
-//! use cortex_m_semihosting::hio::{self, HStdout};
-//! use stm32f103xx::Interrupt;
-//!
-//! static HSTDOUT: Mutex<RefCell<Option<HStdout>>> =
-//!     Mutex::new(RefCell::new(None));
-//!
-//! fn main() {
-//!     interrupt::free(|cs| {
-//!         let hstdout = HSTDOUT.borrow(cs);
-//!         if let Ok(fd) = hio::hstdout() {
-//!             *hstdout.borrow_mut() = Some(fd);
-//!         }
-//!
-//!         let nvic = stm32f103xx::NVIC.borrow(cs);
-//!         nvic.enable(Interrupt::TIM2);
-//!
-//!         let syst = stm32f103xx::SYST.borrow(cs);
-//!         syst.set_clock_source(SystClkSource::Core);
-//!         syst.set_reload(8_000_000); // 1s
-//!         syst.enable_counter();
-//!         syst.enable_interrupt();
-//!     });
+//! use cortex_m::peripheral::syst::SystClkSource;
+//! use cortex_m::peripheral::NVIC;
+//! use rt::ExceptionFrame;
+//! use sh::hio;
+//! use tm4c123x::Interrupt;
+//!
+//! /// GPIO Port A is interrupt 0, and nothing else is using it.
+//! const TOCK_IRQ: u32 = 0;
+//!
+//! entry!(main);
+//!
+//! fn main() -> ! {
+//!     let p = cortex_m::Peripherals::take().unwrap();
+//!     let mut syst = p.SYST;
+//!     let mut nvic = p.NVIC;
+//!
+//!     nvic.enable(Interrupt::GPIOA);
+//!
+//!     // Configure the system timer to trigger a SysTick exception every second
+//!     syst.set_clock_source(SystClkSource::Core);
+//!     syst.set_reload(16_000_000); // 1s, on the 16 MHz internal oscillator
+//!     syst.enable_counter();
+//!     syst.enable_interrupt();
+//!
+//!     loop {}
 //! }
 //!
-//! exception!(SYS_TICK, tick);
+//! exception!(SysTick, tick, state: u32 = 0);
 //!
-//! fn tick() {
-//!     interrupt::free(|cs| {
-//!         let hstdout = HSTDOUT.borrow(cs);
-//!         if let Some(hstdout) = hstdout.borrow_mut().as_mut() {
-//!             writeln!(*hstdout, "Tick").ok();
-//!         }
+//! fn tick(ticks: &mut u32) {
+//!     *ticks += 1;
 //!
-//!         let nvic = stm32f103xx::NVIC.borrow(cs);
+//!     if let Ok(mut hstdout) = hio::hstdout() {
+//!         writeln!(hstdout, "Tick ({})", ticks).ok();
+//!     }
 //!
-//!         nvic.set_pending(Interrupt::TIM2);
-//!     });
+//!     // Pend GPIO Port A, which runs `tock` once we return
+//!     unsafe { (*NVIC::ptr()).ispr[0].write(1 << TOCK_IRQ) };
 //! }
 //!
-//! interrupt!(TIM2, tock, locals: {
-//!     tocks: u32 = 0;
-//! });
+//! interrupt!(GPIOA, tock, state: u32 = 0);
+//!
+//! fn tock(tocks: &mut u32) {
+//!     *tocks += 1;
+//!
+//!     if let Ok(mut hstdout) = hio::hstdout() {
+//!         writeln!(hstdout, "Tock ({})", tocks).ok();
+//!     }
+//! }
+//!
+//! exception!(HardFault, hard_fault);
+//!
+//! fn hard_fault(ef: &ExceptionFrame) -> ! {
+//!     panic!("HardFault at {:#?}", ef);
+//! }
 //!
-//! fn tock(l: &mut TIM2::Locals) {
-//!     l.tocks += 1;
+//! exception!(*, default_handler);
 //!
-//!     interrupt::free(|cs| {
-//!         let hstdout = HSTDOUT.borrow(cs);
-//!         if let Some(hstdout) = hstdout.borrow_mut().as_mut() {
-//!             writeln!(*hstdout, "Tock ({})", l.tocks).ok();
-//!         }
-//!     });
+//! fn default_handler(irqn: i16) {
+//!     panic!("Unhandled exception (IRQn = {})", irqn);
 //! }
 //! ```
 // Auto-generated. Do not modify.
@@ -0,0 +1,184 @@
+//! A tiny executor for tasks written as generators.
+//!
+//! Each task is a generator that `yield`s whenever it's waiting for
+//! something. The `await!` macro does that for you: it polls a `Future`
+//! and yields until the future is ready, so a task reads like blocking
+//! code:
+//!
+//! ``` ignore
+//! let mut echo = move || loop {
+//!     let byte = await!(executor::uart0_read());
+//!     await!(executor::delay_ms(100));
+//!     tx.write(byte).unwrap();
+//! };
+//! executor::run(&mut [&mut echo]);
+//! ```
+//!
+//! There are no wakers. A future that isn't ready makes sure some interrupt
+//! will fire when it might be, and `run` sleeps with `wfi` after each pass
+//! over the tasks, so any interrupt sends it round again. That costs a poll
+//! of every task per interrupt, which is nothing next to the sleeping. An
+//! interrupt that slips in between a poll and the `wfi` is caught by the
+//! next SysTick, a millisecond later at most.
+//!
+//! The millisecond clock for `delay_ms` is SysTick, which is ours after
+//! `start`. The example must send the SysTick exception here:
+//!
+//! ``` ignore
+//! exception!(SysTick, demo::executor::tick);
+//! ```
+//!
+//! Tasks can't hold a borrow across a `yield`, so futures own everything
+//! they need - the UART ones go straight to the registers.
+
+use core::ops::{Generator, GeneratorState};
+use cortex_m::asm;
+use cortex_m::peripheral::{SystClkSource, SYST};
+use hal::sysctl::Clocks;
+use pac;
+
+/// How many tasks `run` can handle.
+pub const MAX_TASKS: usize = 8;
+
+/// Milliseconds since `start`, counted by `tick`.
+static mut TICKS: u32 = 0;
+
+/// Whether a `Future` has finished.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Poll<T> {
+    /// Here's the answer
+    Ready(T),
+    /// Not yet - try again after the next interrupt
+    Pending,
+}
+
+/// Something that will finish later.
+pub trait Future {
+    /// What it produces
+    type Output;
+
+    /// Finish if possible. If not, make sure an interrupt will fire when
+    /// it's worth asking again.
+    fn poll(&mut self) -> Poll<Self::Output>;
+}
+
+/// Wait for a `Future` inside a task, yielding until it's ready.
+#[macro_export]
+macro_rules! await {
+    ($e:expr) => {{
+        let mut future = $e;
+        loop {
+            let poll = $crate::executor::Future::poll(&mut future);
+            match poll {
+                $crate::executor::Poll::Ready(result) => break result,
+                $crate::executor::Poll::Pending => yield,
+            }
+        }
+    }};
+}
+
+/// Ready once `now()` reaches a deadline.
+pub struct Delay {
+    until: u32,
+}
+
+/// Ready when a byte arrives on UART0.
+pub struct Uart0Read;
+
+/// Start SysTick interrupting every millisecond.
+pub fn start(mut syst: SYST, clocks: &Clocks) {
+    syst.set_clock_source(SystClkSource::Core);
+    syst.set_reload((clocks.sysclk.0 / 1000) - 1);
+    syst.clear_current();
+    unsafe { TICKS = 0 };
+    syst.enable_counter();
+    syst.enable_interrupt();
+}
+
+/// The SysTick handler.
+pub fn tick() {
+    unsafe { TICKS = TICKS.wrapping_add(1) };
+}
+
+/// Milliseconds since `start`. Wraps after about 49 days.
+pub fn now() -> u32 {
+    unsafe { ::core::ptr::read_volatile(&TICKS) }
+}
+
+/// Finishes after the given number of milliseconds. SysTick wakes us.
+pub fn delay_ms(ms: u32) -> Delay {
+    Delay {
+        until: now().wrapping_add(ms),
+    }
+}
+
+/// Finishes with the next byte from UART0. The UART must already be set up
+/// (e.g. with `Serial::uart0`), the UART0 interrupt enabled in the NVIC and
+/// `uart0_isr` hooked up with `interrupt!(UART0, executor::uart0_isr)`.
+pub fn uart0_read() -> Uart0Read {
+    Uart0Read
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(&mut self) -> Poll<()> {
+        // Wrapping compare, so this survives the counter rolling over
+        if (now().wrapping_sub(self.until) as i32) >= 0 {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Future for Uart0Read {
+    type Output = u8;
+
+    fn poll(&mut self) -> Poll<u8> {
+        let uart = unsafe { &*pac::UART0::ptr() };
+        if uart.fr.read().rxfe().bit_is_clear() {
+            return Poll::Ready(uart.dr.read().data().bits());
+        }
+        // Ask for an interrupt on the next byte (or the next few, if they
+        // arrive together - that's the receive timeout).
+        uart.im.modify(|_, w| {
+            w.rxim().set_bit();
+            w.rtim().set_bit();
+            w
+        });
+        Poll::Pending
+    }
+}
+
+/// The UART0 interrupt handler. It only wakes `run` - the byte stays in the
+/// FIFO for `Uart0Read` - so it masks the interrupt to stop it firing again
+/// straight away.
+pub fn uart0_isr() {
+    let uart = unsafe { &*pac::UART0::ptr() };
+    uart.im.modify(|_, w| {
+        w.rxim().clear_bit();
+        w.rtim().clear_bit();
+        w
+    });
+}
+
+/// Run the tasks, sleeping whenever they're all waiting. If they all
+/// finish, just sleep.
+pub fn run(tasks: &mut [&mut Generator<Yield = (), Return = ()>]) -> ! {
+    assert!(tasks.len() <= MAX_TASKS);
+    let mut finished = [false; MAX_TASKS];
+    loop {
+        for (task, done) in tasks.iter_mut().zip(finished.iter_mut()) {
+            if *done {
+                continue;
+            }
+            // The tasks live in our caller's stack frame and don't move
+            // while we're running them.
+            if let GeneratorState::Complete(()) = unsafe { task.resume() } {
+                *done = true;
+            }
+        }
+        asm::wfi();
+    }
+}
@@ -0,0 +1,1189 @@
+//! FAT16 and FAT32 filesystems, on anything that reads and writes blocks.
+//!
+//! That's an SD card (`demo::sdcard`), as a PC formatted it: either a
+//! partition table with one FAT partition on it, or a 'superfloppy' with no
+//! table at all. Cards over 32 GB come as exFAT, which we can't read -
+//! format them as FAT32. FAT12 (floppies, and cards of a few MB) isn't
+//! handled either.
+//!
+//! Names are 8.3 only. Long names are skipped over when listing, so a file
+//! called `Saved Game.dat` shows up as its short name (`SAVEDG~1.DAT`), and
+//! anything we create only gets a short name. Paths use `/`, with `.` and
+//! `..` as usual, and are relative to a `Dir` unless they start with `/`.
+//!
+//! We keep one block in RAM, so everything goes through it and only what
+//! changed gets written back - to every copy of the FAT, if it's a FAT
+//! block. Call `flush` (or `sync`, for a file you've written to) before the
+//! card might come out; until then, the card can be missing the last few
+//! hundred bytes and the new size of the file.
+//!
+//! ``` ignore
+//! let mut volume = fat::Volume::mount(card).map_err(|(_card, e)| e)?;
+//! let mut file = volume.append(fat::Dir::root(), "/LOG.CSV")?;
+//! volume.write(&mut file, b"1,2,3\n")?;
+//! volume.sync(&mut file)?;
+//! ```
+
+use core::fmt;
+use datetime::DateTime;
+
+/// Bytes in a block. We don't do any other size.
+pub const BLOCK_LEN: usize = 512;
+
+/// Bytes in a directory entry.
+const ENTRY_LEN: usize = 32;
+
+const ENTRIES_PER_BLOCK: usize = BLOCK_LEN / ENTRY_LEN;
+
+/// The partition types we look for in the partition table.
+const PARTITION_TYPES: [u8; 5] = [0x04, 0x06, 0x0E, 0x0B, 0x0C];
+
+/// FAT entries, once FAT16's have been made to look like FAT32's.
+const FREE: u32 = 0;
+const BAD: u32 = 0x0FFF_FFF7;
+const END: u32 = 0x0FFF_FFFF;
+
+/// The first byte of a deleted directory entry.
+const DELETED: u8 = 0xE5;
+
+/// Where an entry whose name starts with 0xE5 keeps it.
+const E5_ESCAPE: u8 = 0x05;
+
+/// FAT32's FSInfo block: where the free cluster count is, and what it
+/// looks like when nobody knows.
+const FSINFO_FREE_COUNT: usize = 488;
+const FSINFO_UNKNOWN: u32 = 0xFFFF_FFFF;
+
+pub const ATTR_READ_ONLY: u8 = 0x01;
+pub const ATTR_HIDDEN: u8 = 0x02;
+pub const ATTR_SYSTEM: u8 = 0x04;
+pub const ATTR_VOLUME_ID: u8 = 0x08;
+pub const ATTR_DIRECTORY: u8 = 0x10;
+pub const ATTR_ARCHIVE: u8 = 0x20;
+
+/// All four low bits set means it's a piece of a long name.
+const ATTR_LONG_NAME: u8 = 0x0F;
+
+/// Something that stores `BLOCK_LEN` byte blocks.
+pub trait BlockDevice {
+    type Error;
+
+    fn read_block(&mut self, block: u32, data: &mut [u8; BLOCK_LEN]) -> Result<(), Self::Error>;
+
+    fn write_block(&mut self, block: u32, data: &[u8; BLOCK_LEN]) -> Result<(), Self::Error>;
+}
+
+#[derive(Debug)]
+pub enum Error<E> {
+    /// The block device failed - the card's probably gone
+    Device(E),
+    /// No FAT16 or FAT32 filesystem on there
+    NoFilesystem,
+    NotFound,
+    NotADirectory,
+    IsADirectory,
+    AlreadyExists,
+    DirectoryNotEmpty,
+    /// FAT16's root directory has a fixed size, and it's full
+    DirectoryFull,
+    /// Not something that fits in 8.3
+    BadName,
+    DiskFull,
+    /// A cluster chain ran somewhere it shouldn't
+    Corrupt,
+}
+
+impl<E> Error<E> {
+    /// What went wrong, for a person.
+    pub fn message(&self) -> &'static str {
+        match *self {
+            Error::Device(_) => "Card error",
+            Error::NoFilesystem => "No FAT16 or FAT32 filesystem",
+            Error::NotFound => "Not found",
+            Error::NotADirectory => "Not a directory",
+            Error::IsADirectory => "Is a directory",
+            Error::AlreadyExists => "Already exists",
+            Error::DirectoryNotEmpty => "Directory not empty",
+            Error::DirectoryFull => "Root directory full",
+            Error::BadName => "Not an 8.3 name",
+            Error::DiskFull => "Card full",
+            Error::Corrupt => "Filesystem corrupt",
+        }
+    }
+}
+
+impl<E> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FatType {
+    Fat16,
+    Fat32,
+}
+
+/// A directory, by its first cluster.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Dir {
+    /// 0 for the root, on FAT16 and FAT32 both (as in a `..` entry)
+    cluster: u32,
+}
+
+impl Dir {
+    pub fn root() -> Dir {
+        Dir { cluster: 0 }
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.cluster == 0
+    }
+}
+
+/// An 8.3 name, as it is on the card: upper case, and padded with spaces.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ShortName([u8; 11]);
+
+impl ShortName {
+    /// Turn `name.ext` into a `ShortName`, if it fits. Lower case is
+    /// allowed, and made upper case.
+    pub fn parse(name: &str) -> Option<ShortName> {
+        let mut bytes = [b' '; 11];
+        let (base, ext) = match name.rfind('.') {
+            Some(dot) => (&name[..dot], &name[dot + 1..]),
+            None => (name, ""),
+        };
+        if base.is_empty() || base.len() > 8 || ext.len() > 3 {
+            return None;
+        }
+        for (i, b) in base.bytes().enumerate() {
+            bytes[i] = short_name_byte(b)?;
+        }
+        for (i, b) in ext.bytes().enumerate() {
+            bytes[8 + i] = short_name_byte(b)?;
+        }
+        if bytes[0] == DELETED {
+            bytes[0] = E5_ESCAPE;
+        }
+        Some(ShortName(bytes))
+    }
+}
+
+impl fmt::Display for ShortName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, &b) in self.0[..8].iter().enumerate() {
+            let b = if i == 0 && b == E5_ESCAPE { DELETED } else { b };
+            if b != b' ' {
+                write!(f, "{}", b as char)?;
+            }
+        }
+        if self.0[8] != b' ' {
+            f.write_str(".")?;
+            for &b in self.0[8..].iter().filter(|&&b| b != b' ') {
+                write!(f, "{}", b as char)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for ShortName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\"{}\"", self)
+    }
+}
+
+/// Where a directory entry lives.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Slot {
+    block: u32,
+    index: usize,
+}
+
+/// A file or directory, as `entries` and `lookup` find it.
+#[derive(Clone, Copy, Debug)]
+pub struct DirEntry {
+    pub name: ShortName,
+    /// The `ATTR_` bits
+    pub attributes: u8,
+    /// The first cluster (0 for an empty file)
+    pub cluster: u32,
+    /// In bytes (0 for a directory)
+    pub size: u32,
+    pub modified: DateTime,
+    slot: Slot,
+}
+
+impl DirEntry {
+    pub fn is_dir(&self) -> bool {
+        self.attributes & ATTR_DIRECTORY != 0
+    }
+
+    /// The directory, if it is one.
+    pub fn dir(&self) -> Option<Dir> {
+        if self.is_dir() {
+            Some(Dir {
+                cluster: self.cluster,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Where `next_entry` is up to in a directory.
+pub struct Entries {
+    /// 0 for FAT16's fixed root directory
+    cluster: u32,
+    /// The block within the cluster (or the fixed root directory)
+    block: u32,
+    index: usize,
+    done: bool,
+}
+
+/// An open file. It's only a position - the `Volume` does the work.
+#[derive(Debug)]
+pub struct File {
+    slot: Slot,
+    /// The first cluster, or 0 if nothing's been written yet
+    start: u32,
+    size: u32,
+    position: u32,
+    /// The cluster `position` is in, or 0 if there isn't one yet. At the
+    /// end of a cluster this is left on that cluster, rather than moving on
+    /// to one that might not exist.
+    cluster: u32,
+    /// How far into the file `cluster` starts
+    cluster_offset: u32,
+    /// The directory entry needs the new size (or start)
+    dirty: bool,
+}
+
+impl File {
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn position(&self) -> u32 {
+        self.position
+    }
+
+    pub fn is_eof(&self) -> bool {
+        self.position >= self.size
+    }
+
+    /// Move to `position` (or the end, if that's past it) for the next
+    /// `read` or `write`, which finds the cluster it's in.
+    pub fn seek(&mut self, position: u32) {
+        let position = position.min(self.size);
+        if position < self.cluster_offset {
+            self.cluster = self.start;
+            self.cluster_offset = 0;
+        }
+        self.position = position;
+    }
+}
+
+/// The longest path `Path` keeps the text of.
+const PATH_LEN: usize = 64;
+
+/// The text of a directory's path, to show alongside its `Dir`. Paths too
+/// long to keep end in `/...`.
+#[derive(Clone)]
+pub struct Path {
+    bytes: [u8; PATH_LEN],
+    len: usize,
+    /// Names that didn't fit
+    lost: usize,
+}
+
+impl Path {
+    pub fn root() -> Path {
+        Path {
+            bytes: [0u8; PATH_LEN],
+            len: 0,
+            lost: 0,
+        }
+    }
+
+    /// Follow `path` the way `Volume::open_dir` does.
+    pub fn change(&mut self, path: &str) {
+        if path.starts_with('/') {
+            *self = Path::root();
+        }
+        for part in path.split('/').filter(|p| !p.is_empty()) {
+            match part {
+                "." => {}
+                ".." => self.pop(),
+                _ => self.push(part),
+            }
+        }
+    }
+
+    fn push(&mut self, name: &str) {
+        if self.lost > 0 || self.len + 1 + name.len() > PATH_LEN {
+            self.lost += 1;
+            return;
+        }
+        self.bytes[self.len] = b'/';
+        for (i, b) in name.bytes().enumerate() {
+            self.bytes[self.len + 1 + i] = b.to_ascii_uppercase();
+        }
+        self.len += 1 + name.len();
+    }
+
+    fn pop(&mut self) {
+        if self.lost > 0 {
+            self.lost -= 1;
+        } else {
+            while self.len > 0 {
+                self.len -= 1;
+                if self.bytes[self.len] == b'/' {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.len == 0 && self.lost == 0 {
+            return f.write_str("/");
+        }
+        // Only ever ASCII
+        for &b in self.bytes[..self.len].iter() {
+            write!(f, "{}", b as char)?;
+        }
+        if self.lost > 0 {
+            f.write_str("/...")?;
+        }
+        Ok(())
+    }
+}
+
+/// A mounted FAT filesystem, and the device it's on.
+pub struct Volume<D> {
+    device: D,
+    fat_type: FatType,
+    /// The first block of the first FAT
+    fat_start: u32,
+    /// Blocks in each FAT
+    fat_blocks: u32,
+    fats: u32,
+    /// FAT16's fixed root directory
+    root_start: u32,
+    root_blocks: u32,
+    /// FAT32's root directory
+    root_cluster: u32,
+    /// The block cluster 2 starts at
+    data_start: u32,
+    blocks_per_cluster: u32,
+    /// Data clusters, which are numbered from 2
+    clusters: u32,
+    /// FAT32's FSInfo block, until we've marked its free count unknown
+    fsinfo: Option<u32>,
+    /// Where to start looking for a free cluster
+    next_free: u32,
+    cache: [u8; BLOCK_LEN],
+    cached: Option<u32>,
+    dirty: bool,
+    /// What to stamp new and changed entries with
+    now: DateTime,
+}
+
+impl<D> Volume<D>
+where
+    D: BlockDevice,
+{
+    /// Find the filesystem on `device`. If there isn't one, you get the
+    /// device back with the error.
+    pub fn mount(device: D) -> Result<Volume<D>, (D, Error<D::Error>)> {
+        let mut volume = Volume {
+            device,
+            fat_type: FatType::Fat16,
+            fat_start: 0,
+            fat_blocks: 0,
+            fats: 0,
+            root_start: 0,
+            root_blocks: 0,
+            root_cluster: 0,
+            data_start: 0,
+            blocks_per_cluster: 0,
+            clusters: 0,
+            fsinfo: None,
+            next_free: 2,
+            cache: [0u8; BLOCK_LEN],
+            cached: None,
+            dirty: false,
+            now: DateTime::zero(),
+        };
+        match volume.read_layout() {
+            Ok(()) => Ok(volume),
+            Err(e) => Err((volume.device, e)),
+        }
+    }
+
+    /// Give the device back. Anything not flushed is lost.
+    pub fn free(self) -> D {
+        self.device
+    }
+
+    /// Set the time that new and changed files are stamped with.
+    pub fn set_time(&mut self, now: DateTime) {
+        self.now = now;
+    }
+
+    /// "FAT16" or "FAT32".
+    pub fn fat_name(&self) -> &'static str {
+        match self.fat_type {
+            FatType::Fat16 => "FAT16",
+            FatType::Fat32 => "FAT32",
+        }
+    }
+
+    /// The size of the volume, in KiB.
+    pub fn size_kib(&self) -> u32 {
+        self.clusters * self.blocks_per_cluster / 2
+    }
+
+    /// Write the block we're holding, if it's changed.
+    pub fn flush(&mut self) -> Result<(), Error<D::Error>> {
+        if let Some(block) = self.cached {
+            if self.dirty {
+                self.device
+                    .write_block(block, &self.cache)
+                    .map_err(Error::Device)?;
+                // Keep the other FATs the same as the first
+                if block >= self.fat_start && block < self.fat_start + self.fat_blocks {
+                    for copy in 1..self.fats {
+                        self.device
+                            .write_block(block + (copy * self.fat_blocks), &self.cache)
+                            .map_err(Error::Device)?;
+                    }
+                }
+                self.dirty = false;
+            }
+        }
+        Ok(())
+    }
+
+    /// Start listing a directory.
+    pub fn entries(&self, dir: Dir) -> Entries {
+        Entries {
+            cluster: if dir.cluster == 0 {
+                self.root_cluster
+            } else {
+                dir.cluster
+            },
+            block: 0,
+            index: 0,
+            done: false,
+        }
+    }
+
+    /// The next file or directory in a listing, leaving out `.`, `..`, the
+    /// volume label, deleted files and long names.
+    pub fn next_entry(
+        &mut self,
+        entries: &mut Entries,
+    ) -> Result<Option<DirEntry>, Error<D::Error>> {
+        while let Some((slot, raw)) = self.next_slot(entries)? {
+            if raw[0] == 0 {
+                // Nothing after this is in use
+                entries.done = true;
+                break;
+            }
+            let attributes = raw[11];
+            if raw[0] == DELETED || raw[0] == b'.' || attributes & ATTR_VOLUME_ID != 0
+                || attributes & ATTR_LONG_NAME == ATTR_LONG_NAME
+            {
+                continue;
+            }
+            return Ok(Some(entry_from_raw(&raw, slot)));
+        }
+        Ok(None)
+    }
+
+    /// Follow `path` from `cwd` to a directory.
+    pub fn open_dir(&mut self, cwd: Dir, path: &str) -> Result<Dir, Error<D::Error>> {
+        let mut dir = if path.starts_with('/') {
+            Dir::root()
+        } else {
+            cwd
+        };
+        for part in path.split('/').filter(|p| !p.is_empty()) {
+            dir = match part {
+                "." => dir,
+                ".." => self.parent(dir)?,
+                _ => {
+                    let name = ShortName::parse(part).ok_or(Error::BadName)?;
+                    match self.find(dir, &name)? {
+                        Some(entry) => entry.dir().ok_or(Error::NotADirectory)?,
+                        None => return Err(Error::NotFound),
+                    }
+                }
+            };
+        }
+        Ok(dir)
+    }
+
+    /// The directory above `dir` (the root is its own parent).
+    pub fn parent(&mut self, dir: Dir) -> Result<Dir, Error<D::Error>> {
+        if dir.is_root() {
+            return Ok(dir);
+        }
+        let first = self.cluster_block(dir.cluster);
+        let raw = self.read_slot(Slot {
+            block: first,
+            index: 1,
+        })?;
+        if &raw[..2] != b".." {
+            return Err(Error::Corrupt);
+        }
+        // Some formatters point FAT32's `..` at the root's real cluster
+        let cluster = entry_cluster(&raw);
+        Ok(Dir {
+            cluster: if cluster == self.root_cluster { 0 } else { cluster },
+        })
+    }
+
+    /// Find the file or directory at `path`.
+    pub fn lookup(&mut self, cwd: Dir, path: &str) -> Result<DirEntry, Error<D::Error>> {
+        let (dir_path, name) = split_path(path);
+        let dir = self.open_dir(cwd, dir_path)?;
+        let name = ShortName::parse(name).ok_or(Error::BadName)?;
+        self.find(dir, &name)?.ok_or(Error::NotFound)
+    }
+
+    /// Open a file to read it.
+    pub fn open(&mut self, cwd: Dir, path: &str) -> Result<File, Error<D::Error>> {
+        let entry = self.lookup(cwd, path)?;
+        if entry.is_dir() {
+            return Err(Error::IsADirectory);
+        }
+        Ok(File {
+            slot: entry.slot,
+            start: entry.cluster,
+            size: entry.size,
+            position: 0,
+            cluster: entry.cluster,
+            cluster_offset: 0,
+            dirty: false,
+        })
+    }
+
+    /// Make an empty file to write, throwing away what's there if it
+    /// already exists.
+    pub fn create(&mut self, cwd: Dir, path: &str) -> Result<File, Error<D::Error>> {
+        let entry = match self.lookup(cwd, path) {
+            Ok(entry) => {
+                if entry.is_dir() {
+                    return Err(Error::IsADirectory);
+                }
+                self.free_chain(entry.cluster)?;
+                entry
+            }
+            Err(Error::NotFound) => {
+                let (dir_path, name) = split_path(path);
+                let dir = self.open_dir(cwd, dir_path)?;
+                let name = ShortName::parse(name).ok_or(Error::BadName)?;
+                self.new_entry(dir, name, ATTR_ARCHIVE, 0)?
+            }
+            Err(e) => return Err(e),
+        };
+        let mut file = File {
+            slot: entry.slot,
+            start: 0,
+            size: 0,
+            position: 0,
+            cluster: 0,
+            cluster_offset: 0,
+            dirty: true,
+        };
+        self.sync(&mut file)?;
+        Ok(file)
+    }
+
+    /// Open a file to add to the end of it, making it if it isn't there.
+    pub fn append(&mut self, cwd: Dir, path: &str) -> Result<File, Error<D::Error>> {
+        let mut file = match self.open(cwd, path) {
+            Err(Error::NotFound) => return self.create(cwd, path),
+            result => result?,
+        };
+        file.position = file.size;
+        // Walk to the last cluster, but not past it
+        let cluster_len = self.cluster_len();
+        while file.position - file.cluster_offset > cluster_len {
+            file.cluster = self.next_cluster(file.cluster)?.ok_or(Error::Corrupt)?;
+            file.cluster_offset += cluster_len;
+        }
+        Ok(file)
+    }
+
+    /// Read from where the file's up to, returning how many bytes we got.
+    /// That's 0 at the end of the file.
+    pub fn read(&mut self, file: &mut File, buffer: &mut [u8]) -> Result<usize, Error<D::Error>> {
+        let mut done = 0;
+        while done < buffer.len() && file.position < file.size {
+            self.find_position(file, false)?;
+            let (block, offset) = self.file_block(file);
+            let count = (BLOCK_LEN - offset)
+                .min(buffer.len() - done)
+                .min((file.size - file.position) as usize);
+            self.load(block)?;
+            buffer[done..done + count].copy_from_slice(&self.cache[offset..offset + count]);
+            done += count;
+            file.position += count as u32;
+        }
+        Ok(done)
+    }
+
+    /// Write at where the file's up to, adding clusters as we go.
+    pub fn write(&mut self, file: &mut File, data: &[u8]) -> Result<(), Error<D::Error>> {
+        let mut done = 0;
+        while done < data.len() {
+            self.find_position(file, true)?;
+            let (block, offset) = self.file_block(file);
+            let count = (BLOCK_LEN - offset).min(data.len() - done);
+            self.load(block)?;
+            self.cache[offset..offset + count].copy_from_slice(&data[done..done + count]);
+            self.dirty = true;
+            done += count;
+            file.position += count as u32;
+            if file.position > file.size {
+                file.size = file.position;
+            }
+            file.dirty = true;
+        }
+        Ok(())
+    }
+
+    /// Put the file's size and time in its directory entry, and flush.
+    pub fn sync(&mut self, file: &mut File) -> Result<(), Error<D::Error>> {
+        if file.dirty {
+            let mut raw = self.read_slot(file.slot)?;
+            set_entry_cluster(&mut raw, file.start);
+            put_u32(&mut raw, 28, file.size);
+            stamp(&mut raw, &self.now, false);
+            self.write_slot(file.slot, &raw)?;
+            file.dirty = false;
+        }
+        self.flush()
+    }
+
+    /// Delete a file, or an empty directory.
+    pub fn delete(&mut self, cwd: Dir, path: &str) -> Result<(), Error<D::Error>> {
+        let entry = self.lookup(cwd, path)?;
+        if let Some(dir) = entry.dir() {
+            let mut entries = self.entries(dir);
+            if self.next_entry(&mut entries)?.is_some() {
+                return Err(Error::DirectoryNotEmpty);
+            }
+        }
+        let mut raw = self.read_slot(entry.slot)?;
+        raw[0] = DELETED;
+        self.write_slot(entry.slot, &raw)?;
+        self.free_chain(entry.cluster)?;
+        self.flush()
+    }
+
+    /// Copy the file at `from` (from `from_dir`) to `to` (from `to_dir`),
+    /// returning how many bytes it was.
+    pub fn copy(
+        &mut self,
+        from_dir: Dir,
+        from: &str,
+        to_dir: Dir,
+        to: &str,
+    ) -> Result<u32, Error<D::Error>> {
+        let mut source = self.open(from_dir, from)?;
+        // Making the copy would empty the original first
+        if let Ok(existing) = self.lookup(to_dir, to) {
+            if existing.slot == source.slot {
+                return Err(Error::AlreadyExists);
+            }
+        }
+        let mut dest = self.create(to_dir, to)?;
+        let mut buffer = [0u8; BLOCK_LEN];
+        loop {
+            let count = self.read(&mut source, &mut buffer)?;
+            if count == 0 {
+                break;
+            }
+            self.write(&mut dest, &buffer[..count])?;
+        }
+        self.sync(&mut dest)?;
+        Ok(dest.size)
+    }
+
+    /// Make a directory.
+    pub fn make_dir(&mut self, cwd: Dir, path: &str) -> Result<Dir, Error<D::Error>> {
+        let (dir_path, name) = split_path(path);
+        let parent = self.open_dir(cwd, dir_path)?;
+        let name = ShortName::parse(name).ok_or(Error::BadName)?;
+        if self.find(parent, &name)?.is_some() {
+            return Err(Error::AlreadyExists);
+        }
+        let cluster = self.alloc_cluster(None, true)?;
+        let mut dot = [0u8; ENTRY_LEN];
+        dot[..11].copy_from_slice(b".          ");
+        dot[11] = ATTR_DIRECTORY;
+        set_entry_cluster(&mut dot, cluster);
+        stamp(&mut dot, &self.now, true);
+        let first = self.cluster_block(cluster);
+        self.write_slot(
+            Slot {
+                block: first,
+                index: 0,
+            },
+            &dot,
+        )?;
+        dot[1] = b'.';
+        set_entry_cluster(&mut dot, parent.cluster);
+        self.write_slot(
+            Slot {
+                block: first,
+                index: 1,
+            },
+            &dot,
+        )?;
+        self.new_entry(parent, name, ATTR_DIRECTORY, cluster)?;
+        self.flush()?;
+        Ok(Dir { cluster })
+    }
+
+    /// Read the boot sector (and the partition table, if there is one).
+    fn read_layout(&mut self) -> Result<(), Error<D::Error>> {
+        self.load(0)?;
+        if self.cache[510] != 0x55 || self.cache[511] != 0xAA {
+            return Err(Error::NoFilesystem);
+        }
+        // A boot sector starts with a jump; a partition table doesn't
+        let start = if (self.cache[0] == 0xEB || self.cache[0] == 0xE9)
+            && get_u16(&self.cache, 11) == BLOCK_LEN as u16
+        {
+            0
+        } else {
+            let mut start = None;
+            for i in 0..4 {
+                let entry = 446 + (i * 16);
+                if PARTITION_TYPES.contains(&self.cache[entry + 4]) {
+                    start = Some(get_u32(&self.cache, entry + 8));
+                    break;
+                }
+            }
+            let start = start.ok_or(Error::NoFilesystem)?;
+            self.load(start)?;
+            start
+        };
+
+        let bpb = &self.cache;
+        let blocks_per_cluster = u32::from(bpb[13]);
+        let reserved = u32::from(get_u16(bpb, 14));
+        let fats = u32::from(bpb[16]);
+        let root_entries = u32::from(get_u16(bpb, 17));
+        let total = match get_u16(bpb, 19) {
+            0 => get_u32(bpb, 32),
+            total => u32::from(total),
+        };
+        let fat_blocks = match get_u16(bpb, 22) {
+            0 => get_u32(bpb, 36),
+            blocks => u32::from(blocks),
+        };
+        if bpb[510] != 0x55 || bpb[511] != 0xAA || get_u16(bpb, 11) != BLOCK_LEN as u16
+            || blocks_per_cluster == 0 || fats == 0 || fat_blocks == 0
+        {
+            return Err(Error::NoFilesystem);
+        }
+        let root_blocks = (root_entries * ENTRY_LEN as u32 + BLOCK_LEN as u32 - 1)
+            / BLOCK_LEN as u32;
+        let overhead = reserved + (fats * fat_blocks) + root_blocks;
+        if total <= overhead {
+            return Err(Error::NoFilesystem);
+        }
+        let clusters = (total - overhead) / blocks_per_cluster;
+        // The cluster count is what decides, not what the label says
+        let fat_type = if clusters < 4085 {
+            return Err(Error::NoFilesystem);
+        } else if clusters < 65525 {
+            FatType::Fat16
+        } else {
+            FatType::Fat32
+        };
+
+        self.fat_type = fat_type;
+        self.fat_start = start + reserved;
+        self.fat_blocks = fat_blocks;
+        self.fats = fats;
+        self.root_start = self.fat_start + (fats * fat_blocks);
+        self.root_blocks = root_blocks;
+        self.data_start = self.root_start + root_blocks;
+        self.blocks_per_cluster = blocks_per_cluster;
+        self.clusters = clusters;
+        if fat_type == FatType::Fat32 {
+            self.root_cluster = get_u32(bpb, 44);
+            self.fsinfo = match get_u16(bpb, 48) {
+                0 | 0xFFFF => None,
+                block => Some(start + u32::from(block)),
+            };
+        }
+        Ok(())
+    }
+
+    /// Get `block` into the cache, writing back what was there.
+    fn load(&mut self, block: u32) -> Result<(), Error<D::Error>> {
+        if self.cached != Some(block) {
+            self.flush()?;
+            self.cached = None;
+            self.device
+                .read_block(block, &mut self.cache)
+                .map_err(Error::Device)?;
+            self.cached = Some(block);
+        }
+        Ok(())
+    }
+
+    fn read_slot(&mut self, slot: Slot) -> Result<[u8; ENTRY_LEN], Error<D::Error>> {
+        self.load(slot.block)?;
+        let mut raw = [0u8; ENTRY_LEN];
+        let offset = slot.index * ENTRY_LEN;
+        raw.copy_from_slice(&self.cache[offset..offset + ENTRY_LEN]);
+        Ok(raw)
+    }
+
+    fn write_slot(&mut self, slot: Slot, raw: &[u8; ENTRY_LEN]) -> Result<(), Error<D::Error>> {
+        self.load(slot.block)?;
+        let offset = slot.index * ENTRY_LEN;
+        self.cache[offset..offset + ENTRY_LEN].copy_from_slice(raw);
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// The next slot of a directory, used or not, and move on.
+    fn next_slot(
+        &mut self,
+        entries: &mut Entries,
+    ) -> Result<Option<(Slot, [u8; ENTRY_LEN])>, Error<D::Error>> {
+        if entries.done {
+            return Ok(None);
+        }
+        let block = if entries.cluster == 0 {
+            self.root_start + entries.block
+        } else {
+            self.cluster_block(entries.cluster) + entries.block
+        };
+        let slot = Slot {
+            block,
+            index: entries.index,
+        };
+        let raw = self.read_slot(slot)?;
+        entries.index += 1;
+        if entries.index == ENTRIES_PER_BLOCK {
+            entries.index = 0;
+            entries.block += 1;
+            if entries.cluster == 0 {
+                entries.done = entries.block == self.root_blocks;
+            } else if entries.block == self.blocks_per_cluster {
+                // At the end, `cluster` stays on the last one so a new one
+                // can be added after it
+                match self.next_cluster(entries.cluster)? {
+                    Some(next) => {
+                        entries.cluster = next;
+                        entries.block = 0;
+                    }
+                    None => entries.done = true,
+                }
+            }
+        }
+        Ok(Some((slot, raw)))
+    }
+
+    fn find(&mut self, dir: Dir, name: &ShortName) -> Result<Option<DirEntry>, Error<D::Error>> {
+        let mut entries = self.entries(dir);
+        while let Some(entry) = self.next_entry(&mut entries)? {
+            if entry.name == *name {
+                return Ok(Some(entry));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Add an entry to a directory, growing it if need be.
+    fn new_entry(
+        &mut self,
+        dir: Dir,
+        name: ShortName,
+        attributes: u8,
+        cluster: u32,
+    ) -> Result<DirEntry, Error<D::Error>> {
+        let slot = self.free_slot(dir)?;
+        let mut raw = [0u8; ENTRY_LEN];
+        raw[..11].copy_from_slice(&name.0);
+        raw[11] = attributes;
+        set_entry_cluster(&mut raw, cluster);
+        stamp(&mut raw, &self.now, true);
+        self.write_slot(slot, &raw)?;
+        Ok(entry_from_raw(&raw, slot))
+    }
+
+    fn free_slot(&mut self, dir: Dir) -> Result<Slot, Error<D::Error>> {
+        let mut entries = self.entries(dir);
+        while let Some((slot, raw)) = self.next_slot(&mut entries)? {
+            if raw[0] == 0 || raw[0] == DELETED {
+                return Ok(slot);
+            }
+        }
+        if entries.cluster == 0 {
+            return Err(Error::DirectoryFull);
+        }
+        let last = entries.cluster;
+        let cluster = self.alloc_cluster(Some(last), true)?;
+        Ok(Slot {
+            block: self.cluster_block(cluster),
+            index: 0,
+        })
+    }
+
+    /// Make sure `file.cluster` has `file.position` in it, moving along
+    /// the chain (and adding to it, if `grow`).
+    fn find_position(&mut self, file: &mut File, grow: bool) -> Result<(), Error<D::Error>> {
+        if file.cluster == 0 {
+            if !grow {
+                return Err(Error::Corrupt);
+            }
+            let cluster = self.alloc_cluster(None, false)?;
+            file.start = cluster;
+            file.cluster = cluster;
+            file.cluster_offset = 0;
+            file.dirty = true;
+        }
+        let cluster_len = self.cluster_len();
+        while file.position - file.cluster_offset >= cluster_len {
+            file.cluster = match self.next_cluster(file.cluster)? {
+                Some(next) => next,
+                None if grow => self.alloc_cluster(Some(file.cluster), false)?,
+                None => return Err(Error::Corrupt),
+            };
+            file.cluster_offset += cluster_len;
+        }
+        Ok(())
+    }
+
+    /// The block `file.position` is in, and how far into it.
+    fn file_block(&self, file: &File) -> (u32, usize) {
+        let offset = file.position - file.cluster_offset;
+        (
+            self.cluster_block(file.cluster) + (offset / BLOCK_LEN as u32),
+            (offset % BLOCK_LEN as u32) as usize,
+        )
+    }
+
+    fn cluster_len(&self) -> u32 {
+        self.blocks_per_cluster * BLOCK_LEN as u32
+    }
+
+    fn cluster_block(&self, cluster: u32) -> u32 {
+        self.data_start + ((cluster - 2) * self.blocks_per_cluster)
+    }
+
+    fn fat_position(&self, cluster: u32) -> (u32, usize) {
+        let offset = match self.fat_type {
+            FatType::Fat16 => cluster * 2,
+            FatType::Fat32 => cluster * 4,
+        };
+        (
+            self.fat_start + (offset / BLOCK_LEN as u32),
+            (offset % BLOCK_LEN as u32) as usize,
+        )
+    }
+
+    /// Read a FAT entry, with FAT16's special values made into FAT32's.
+    fn fat_entry(&mut self, cluster: u32) -> Result<u32, Error<D::Error>> {
+        let (block, offset) = self.fat_position(cluster);
+        self.load(block)?;
+        Ok(match self.fat_type {
+            FatType::Fat16 => match get_u16(&self.cache, offset) {
+                0xFFF8...0xFFFF => END,
+                0xFFF7 => BAD,
+                entry => u32::from(entry),
+            },
+            FatType::Fat32 => get_u32(&self.cache, offset) & 0x0FFF_FFFF,
+        })
+    }
+
+    fn set_fat_entry(&mut self, cluster: u32, value: u32) -> Result<(), Error<D::Error>> {
+        let (block, offset) = self.fat_position(cluster);
+        self.load(block)?;
+        match self.fat_type {
+            FatType::Fat16 => put_u16(&mut self.cache, offset, value as u16),
+            FatType::Fat32 => {
+                // The top four bits are reserved, and left alone
+                let old = get_u32(&self.cache, offset);
+                put_u32(&mut self.cache, offset, (old & 0xF000_0000) | value);
+            }
+        }
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// The cluster after this one, if there is one.
+    fn next_cluster(&mut self, cluster: u32) -> Result<Option<u32>, Error<D::Error>> {
+        match self.fat_entry(cluster)? {
+            next if next >= 2 && next < self.clusters + 2 => Ok(Some(next)),
+            next if next >= 0x0FFF_FFF8 => Ok(None),
+            _ => Err(Error::Corrupt),
+        }
+    }
+
+    /// Find a free cluster, mark it as the end of a chain, and put it on
+    /// the end of `previous`. Directories want theirs zeroed.
+    fn alloc_cluster(&mut self, previous: Option<u32>, zero: bool) -> Result<u32, Error<D::Error>> {
+        let mut cluster = self.next_free;
+        let mut found = None;
+        for _ in 0..self.clusters {
+            if cluster >= self.clusters + 2 {
+                cluster = 2;
+            }
+            if self.fat_entry(cluster)? == FREE {
+                found = Some(cluster);
+                break;
+            }
+            cluster += 1;
+        }
+        let cluster = found.ok_or(Error::DiskFull)?;
+        self.forget_free_count()?;
+        self.set_fat_entry(cluster, END)?;
+        if let Some(previous) = previous {
+            self.set_fat_entry(previous, cluster)?;
+        }
+        self.next_free = cluster + 1;
+        if zero {
+            let first = self.cluster_block(cluster);
+            for block in first..first + self.blocks_per_cluster {
+                self.flush()?;
+                for byte in self.cache.iter_mut() {
+                    *byte = 0;
+                }
+                self.cached = Some(block);
+                self.dirty = true;
+            }
+        }
+        Ok(cluster)
+    }
+
+    /// Free every cluster in a chain (which can be 0, for no chain).
+    fn free_chain(&mut self, start: u32) -> Result<(), Error<D::Error>> {
+        if start == 0 {
+            return Ok(());
+        }
+        self.forget_free_count()?;
+        let mut cluster = Some(start);
+        while let Some(this) = cluster {
+            cluster = self.next_cluster(this)?;
+            self.set_fat_entry(this, FREE)?;
+        }
+        if start < self.next_free {
+            self.next_free = start;
+        }
+        Ok(())
+    }
+
+    /// We don't keep FAT32's free cluster count up to date, so say so
+    /// before we change anything. The PC counts them again.
+    fn forget_free_count(&mut self) -> Result<(), Error<D::Error>> {
+        if let Some(block) = self.fsinfo.take() {
+            self.load(block)?;
+            put_u32(&mut self.cache, FSINFO_FREE_COUNT, FSINFO_UNKNOWN);
+            self.dirty = true;
+        }
+        Ok(())
+    }
+}
+
+/// Split `a/b/c` into `a/b` and `c`.
+fn split_path(path: &str) -> (&str, &str) {
+    match path.rfind('/') {
+        Some(0) => ("/", &path[1..]),
+        Some(slash) => (&path[..slash], &path[slash + 1..]),
+        None => ("", path),
+    }
+}
+
+/// What can go in a short name, upper cased.
+fn short_name_byte(b: u8) -> Option<u8> {
+    match b {
+        b'a'...b'z' => Some(b - b'a' + b'A'),
+        b'A'...b'Z' | b'0'...b'9' => Some(b),
+        b'!' | b'#' | b'$' | b'%' | b'&' | b'\'' | b'(' | b')' | b'-' | b'@' | b'^' | b'_'
+        | b'`' | b'{' | b'}' | b'~' => Some(b),
+        _ => None,
+    }
+}
+
+fn entry_from_raw(raw: &[u8; ENTRY_LEN], slot: Slot) -> DirEntry {
+    let mut name = [0u8; 11];
+    name.copy_from_slice(&raw[..11]);
+    let time = get_u16(raw, 22);
+    let date = get_u16(raw, 24);
+    DirEntry {
+        name: ShortName(name),
+        attributes: raw[11],
+        cluster: entry_cluster(raw),
+        size: get_u32(raw, 28),
+        modified: DateTime {
+            year: 1980 + (date >> 9),
+            month: ((date >> 5) & 0x0F) as u8,
+            day: (date & 0x1F) as u8,
+            hours: (time >> 11) as u8,
+            minutes: ((time >> 5) & 0x3F) as u8,
+            seconds: ((time & 0x1F) * 2) as u8,
+        },
+        slot,
+    }
+}
+
+/// The first cluster, which FAT32 splits in two.
+fn entry_cluster(raw: &[u8]) -> u32 {
+    (u32::from(get_u16(raw, 20)) << 16) | u32::from(get_u16(raw, 26))
+}
+
+fn set_entry_cluster(raw: &mut [u8], cluster: u32) {
+    put_u16(raw, 20, (cluster >> 16) as u16);
+    put_u16(raw, 26, cluster as u16);
+}
+
+/// Set the modified time (and the created time, if `created`).
+fn stamp(raw: &mut [u8], now: &DateTime, created: bool) {
+    let date = ((now.year.saturating_sub(1980)) << 9) | (u16::from(now.month) << 5)
+        | u16::from(now.day);
+    let time = (u16::from(now.hours) << 11) | (u16::from(now.minutes) << 5)
+        | u16::from(now.seconds / 2);
+    if created {
+        put_u16(raw, 14, time);
+        put_u16(raw, 16, date);
+    }
+    // Last accessed is a date only
+    put_u16(raw, 18, date);
+    put_u16(raw, 22, time);
+    put_u16(raw, 24, date);
+}
+
+fn get_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from(bytes[offset]) | (u16::from(bytes[offset + 1]) << 8)
+}
+
+fn get_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from(get_u16(bytes, offset)) | (u32::from(get_u16(bytes, offset + 2)) << 16)
+}
+
+fn put_u16(bytes: &mut [u8], offset: usize, value: u16) {
+    bytes[offset] = value as u8;
+    bytes[offset + 1] = (value >> 8) as u8;
+}
+
+fn put_u32(bytes: &mut [u8], offset: usize, value: u32) {
+    put_u16(bytes, offset, value as u16);
+    put_u16(bytes, offset + 2, (value >> 16) as u16);
+}
@@ -0,0 +1,159 @@
+//! Fault handlers that say what went wrong.
+//!
+//! The usual `hard_fault` just panics, which (with `panic-halt`) gives you a
+//! board that does nothing until you attach a debugger. These print the
+//! stacked registers and the fault status registers on UART0, then flash
+//! the red LED forever so you can see something's up:
+//!
+//! ``` ignore
+//! exception!(HardFault, demo::fault::hard_fault);
+//! exception!(BusFault, demo::fault::bus_fault);
+//! ```
+//!
+//! Bus faults escalate to HardFault (and are reported there) unless you
+//! call `enable_bus_fault` - do that if you want to know about them even
+//! with interrupts masked, or want `BFAR` to be checked before anything
+//! else can fault.
+//!
+//! Everything here pokes the registers directly and polls, so it works no
+//! matter what state the program was in. UART0 must already have been set
+//! up (e.g. with `Serial::uart0`) or you'll only get the LED.
+
+use config::{Pin, Port};
+use core::fmt::{self, Write};
+use cortex_m::asm;
+use cortex_m::peripheral::SCB;
+use cortex_m_rt::ExceptionFrame;
+use pac;
+
+/// CFSR bits, from the TM4C123GH6PM data sheet (section 3.6.2.4)
+const CFSR_FLAGS: [(u32, &str); 14] = [
+    (1 << 0, "IERR (instruction access violation)"),
+    (1 << 1, "DERR (data access violation)"),
+    (1 << 3, "MUSTKE (unstacking memory fault)"),
+    (1 << 4, "MSTKE (stacking memory fault)"),
+    (1 << 8, "IBUS (instruction bus error)"),
+    (1 << 9, "PRECISE (precise data bus error)"),
+    (1 << 10, "IMPRE (imprecise data bus error)"),
+    (1 << 11, "BUSTKE (unstacking bus fault)"),
+    (1 << 12, "BSTKE (stacking bus fault)"),
+    (1 << 16, "UNDEF (undefined instruction)"),
+    (1 << 17, "INVSTAT (invalid state - Thumb bit clear?)"),
+    (1 << 18, "INVPC (invalid PC load)"),
+    (1 << 19, "NOCP (no coprocessor - FPU off?)"),
+    (1 << 25, "DIV0 (divide by zero)"),
+];
+
+/// CFSR: MMFAR holds the faulting address
+const CFSR_MMARV: u32 = 1 << 7;
+/// CFSR: BFAR holds the faulting address
+const CFSR_BFARV: u32 = 1 << 15;
+/// CFSR: unaligned access (only if UNALIGN_TRP is set)
+const CFSR_UNALIGN: u32 = 1 << 24;
+
+/// HFSR: a vector table read failed
+const HFSR_VECT: u32 = 1 << 1;
+/// HFSR: a configurable fault escalated
+const HFSR_FORCED: u32 = 1 << 30;
+
+/// SHCSR: BusFault enable
+const SHCSR_BUSFAULTENA: u32 = 1 << 17;
+
+/// The red LED, PF1
+#[cfg(feature = "tm4c123")]
+const RED_LED: Pin = Pin { port: Port::F, bit: 1 };
+/// D1 on the Connected LaunchPad, PN1
+#[cfg(feature = "tm4c129")]
+const RED_LED: Pin = Pin { port: Port::N, bit: 1 };
+
+/// Writes straight to UART0, converting `\n` to `\r\n`. Unlike `Console`,
+/// nothing can capture this.
+struct FaultWriter;
+
+impl fmt::Write for FaultWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let uart = unsafe { &*pac::UART0::ptr() };
+        for byte in s.bytes() {
+            if byte == b'\n' {
+                while uart.fr.read().txff().bit_is_set() {}
+                uart.dr.write(|w| unsafe { w.data().bits(b'\r') });
+            }
+            while uart.fr.read().txff().bit_is_set() {}
+            uart.dr.write(|w| unsafe { w.data().bits(byte) });
+        }
+        Ok(())
+    }
+}
+
+/// Send bus faults to `bus_fault` instead of escalating them to HardFault.
+pub fn enable_bus_fault(scb: &mut SCB) {
+    unsafe { scb.shcsr.modify(|r| r | SHCSR_BUSFAULTENA) };
+}
+
+/// A HardFault handler. Prints `ef` and the fault status, then flashes the
+/// red LED.
+pub fn hard_fault(ef: &ExceptionFrame) -> ! {
+    let mut w = FaultWriter;
+    let _ = writeln!(w, "\n*** HardFault ***");
+    let _ = dump_frame(&mut w, ef);
+    let _ = dump_status(&mut w);
+    spin()
+}
+
+/// A BusFault handler, for use with `enable_bus_fault`. `cortex-m-rt` doesn't
+/// give us the stacked registers here, so it's just the fault status.
+pub fn bus_fault() {
+    let mut w = FaultWriter;
+    let _ = writeln!(w, "\n*** BusFault ***");
+    let _ = dump_status(&mut w);
+    spin()
+}
+
+fn dump_frame<W: Write>(w: &mut W, ef: &ExceptionFrame) -> fmt::Result {
+    writeln!(w, "r0   = 0x{:08x}  r1 = 0x{:08x}", ef.r0, ef.r1)?;
+    writeln!(w, "r2   = 0x{:08x}  r3 = 0x{:08x}", ef.r2, ef.r3)?;
+    writeln!(w, "r12  = 0x{:08x}  lr = 0x{:08x}", ef.r12, ef.lr)?;
+    writeln!(w, "pc   = 0x{:08x}", ef.pc)?;
+    writeln!(w, "xpsr = 0x{:08x}", ef.xpsr)
+}
+
+fn dump_status<W: Write>(w: &mut W) -> fmt::Result {
+    let scb = unsafe { &*SCB::ptr() };
+    let cfsr = scb.cfsr.read();
+    let hfsr = scb.hfsr.read();
+    writeln!(w, "CFSR = 0x{:08x}", cfsr)?;
+    for &(bit, name) in CFSR_FLAGS.iter() {
+        if cfsr & bit != 0 {
+            writeln!(w, "  {}", name)?;
+        }
+    }
+    if cfsr & CFSR_UNALIGN != 0 {
+        writeln!(w, "  UNALIGN (unaligned access)")?;
+    }
+    // The address registers are only worth reading if they're marked valid
+    if cfsr & CFSR_MMARV != 0 {
+        writeln!(w, "MMFAR = 0x{:08x}", scb.mmfar.read())?;
+    }
+    if cfsr & CFSR_BFARV != 0 {
+        writeln!(w, "BFAR = 0x{:08x}", scb.bfar.read())?;
+    }
+    writeln!(w, "HFSR = 0x{:08x}", hfsr)?;
+    if hfsr & HFSR_VECT != 0 {
+        writeln!(w, "  VECTTBL (vector table read failed)")?;
+    }
+    if hfsr & HFSR_FORCED != 0 {
+        writeln!(w, "  FORCED (escalated from the CFSR fault)")?;
+    }
+    Ok(())
+}
+
+/// Flash the red LED, forever. Sets the pin up from scratch, as we don't
+/// know what the program did with it.
+fn spin() -> ! {
+    RED_LED.into_output();
+    loop {
+        RED_LED.set(!RED_LED.is_high());
+        // About a quarter of a second at 80 MHz, longer if slower
+        asm::delay(20_000_000);
+    }
+}
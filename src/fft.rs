@@ -0,0 +1,90 @@
+//! A fixed-point, radix-2 Fast Fourier Transform.
+//!
+//! Samples are Q15, in separate real and imaginary slices, and are
+//! transformed in place. Each of the log2(N) stages halves the values, so
+//! nothing can overflow and the output is the DFT divided by N - a full
+//! scale sine wave comes out as two bins of about 16384.
+//!
+//! The twiddle factors come from `trig`, whose table has 256 entries, so up
+//! to 256 points are exact and 512 or 1024 points use its interpolation.
+
+use trig;
+
+/// The most points `transform` will take.
+pub const MAX_POINTS: usize = 1024;
+
+/// Apply a Hann window to `samples`, to cut down the leakage from signals
+/// which aren't a whole number of cycles long.
+pub fn hann(samples: &mut [i16]) {
+    let n = samples.len() as u32;
+    for (i, sample) in samples.iter_mut().enumerate() {
+        let angle = ((i as u32 * 65536) / n) as u16;
+        // 0.5 - 0.5cos(x), in Q15
+        let weight = (32767 - i32::from(trig::cos(angle))) / 2;
+        *sample = ((i32::from(*sample) * weight) >> 15) as i16;
+    }
+}
+
+/// Transform `re` and `im` in place. They must be the same length, which
+/// must be a power of two no bigger than `MAX_POINTS`.
+pub fn transform(re: &mut [i16], im: &mut [i16]) {
+    let n = re.len();
+    assert!(n == im.len() && n.is_power_of_two() && n <= MAX_POINTS);
+    if n < 2 {
+        return;
+    }
+
+    // Decimation in time wants the input in bit-reversed order
+    let bits = n.trailing_zeros();
+    for i in 0..n {
+        let j = reverse(i, bits);
+        if j > i {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut size = 2;
+    while size <= n {
+        let half = size / 2;
+        for k in 0..half {
+            // e^(-2 pi i k / size)
+            let angle = ((k * 65536) / size) as u16;
+            let wr = i32::from(trig::cos(angle));
+            let wi = -i32::from(trig::sin(angle));
+            let mut start = 0;
+            while start < n {
+                let (a, b) = (start + k, start + k + half);
+                let br = i32::from(re[b]);
+                let bi = i32::from(im[b]);
+                let tr = ((br * wr) - (bi * wi)) >> 15;
+                let ti = ((br * wi) + (bi * wr)) >> 15;
+                let ar = i32::from(re[a]);
+                let ai = i32::from(im[a]);
+                re[a] = ((ar + tr) >> 1) as i16;
+                im[a] = ((ai + ti) >> 1) as i16;
+                re[b] = ((ar - tr) >> 1) as i16;
+                im[b] = ((ai - ti) >> 1) as i16;
+                start += size;
+            }
+        }
+        size *= 2;
+    }
+}
+
+/// The bottom `bits` bits of `i`, backwards.
+fn reverse(i: usize, bits: u32) -> usize {
+    let mut i = i;
+    let mut result = 0;
+    for _ in 0..bits {
+        result = (result << 1) | (i & 1);
+        i >>= 1;
+    }
+    result
+}
+
+/// The magnitude of one bin.
+pub fn magnitude(re: i16, im: i16) -> u16 {
+    let (re, im) = (i32::from(re), i32::from(im));
+    trig::isqrt(((re * re) + (im * im)) as u32) as u16
+}
@@ -0,0 +1,431 @@
+//! A two-pane file manager for an SD card, drawn with `tui`.
+//!
+//! Each pane lists a directory of a `fat::Volume`, directories first. Tab
+//! swaps panes, the arrow keys (and Page Up, Page Down, Home and End) move
+//! up and down, Enter goes into a directory and Backspace comes back out.
+//! C (or F5) copies the file under the cursor to the other pane's
+//! directory, M (or F7) makes a directory and D (or F8, or Delete) deletes
+//! a file or an empty directory, once you've said yes. Escape leaves.
+//!
+//! Only the first `MAX_ENTRIES` names in a directory are shown. The panes
+//! are read again after anything that changes the card, so they can both
+//! show the same directory.
+
+use core::fmt::Write;
+use fat::{BlockDevice, Dir, Path, ShortName, Volume};
+use graphics::Canvas;
+use keyboard::Key;
+use text::Buffer;
+use tui::{self, Attr, Lines, Rect, Response};
+
+/// Names kept for each pane.
+pub const MAX_ENTRIES: usize = 40;
+
+/// Names on the screen in each pane.
+const ROWS: usize = 14;
+
+/// Half the 50 column VGA screen, under the status bar.
+const LEFT: Rect = Rect {
+    col: 0,
+    row: 1,
+    width: 25,
+    height: ROWS + 2,
+};
+
+const RIGHT: Rect = Rect {
+    col: 25,
+    row: 1,
+    width: 25,
+    height: ROWS + 2,
+};
+
+/// The row under the panes, for help, questions and complaints.
+const HELP_ROW: usize = LEFT.row + LEFT.height;
+const HELP_WIDTH: usize = LEFT.width + RIGHT.width;
+
+const HELP: &str = "Tab Enter Bksp C)opy M)kdir D)elete Esc";
+
+/// The longest name we can type, `NAME.EXT`.
+const NAME_LEN: usize = 12;
+
+#[derive(Clone, Copy)]
+struct Item {
+    name: ShortName,
+    /// Set for a directory
+    dir: Option<Dir>,
+    size: u32,
+}
+
+/// One directory listing.
+struct Pane {
+    dir: Dir,
+    path: Path,
+    items: [Option<Item>; MAX_ENTRIES],
+    count: usize,
+    /// There were more than `MAX_ENTRIES`
+    more: bool,
+    selected: usize,
+    /// The first item on the screen
+    top: usize,
+}
+
+impl Pane {
+    fn new(dir: Dir, path: Path) -> Pane {
+        Pane {
+            dir,
+            path,
+            items: [None; MAX_ENTRIES],
+            count: 0,
+            more: false,
+            selected: 0,
+            top: 0,
+        }
+    }
+
+    /// Read the directory again, keeping the cursor where it was if we can.
+    fn load<D>(&mut self, volume: &mut Volume<D>) -> Result<(), &'static str>
+    where
+        D: BlockDevice,
+    {
+        self.count = 0;
+        self.more = false;
+        let mut entries = volume.entries(self.dir);
+        let result = loop {
+            match volume.next_entry(&mut entries) {
+                Ok(Some(entry)) if self.count < MAX_ENTRIES => {
+                    self.items[self.count] = Some(Item {
+                        name: entry.name,
+                        dir: entry.dir(),
+                        size: entry.size,
+                    });
+                    self.count += 1;
+                }
+                Ok(Some(_)) => {
+                    self.more = true;
+                    break Ok(());
+                }
+                Ok(None) => break Ok(()),
+                Err(e) => break Err(e.message()),
+            }
+        };
+        self.items[..self.count].sort_unstable_by_key(|item| {
+            item.map(|item| (item.dir.is_none(), item.name))
+        });
+        let selected = self.selected;
+        self.select(selected);
+        result
+    }
+
+    /// Move the cursor, scrolling to keep it on the screen.
+    fn select(&mut self, index: usize) {
+        self.selected = index.min(self.count.saturating_sub(1));
+        if self.selected < self.top {
+            self.top = self.selected;
+        } else if self.selected >= self.top + ROWS {
+            self.top = self.selected + 1 - ROWS;
+        }
+    }
+
+    fn item(&self) -> Option<Item> {
+        if self.selected < self.count {
+            self.items[self.selected]
+        } else {
+            None
+        }
+    }
+
+    fn draw(&self, canvas: &mut Canvas, rect: Rect, active: bool) {
+        let lines = if active { &Lines::DOUBLE } else { &Lines::SINGLE };
+        let mut title = Buffer::new();
+        let _ = write!(title, "{}", self.path);
+        if self.more {
+            let _ = write!(title, " (first {})", MAX_ENTRIES);
+        }
+        let inside = rect.inside();
+        tui::clear(canvas, inside);
+        tui::frame(canvas, rect, lines, Some(title.as_str()));
+        for row in 0..ROWS {
+            let index = self.top + row;
+            let item = match self.items.get(index) {
+                Some(&Some(item)) if index < self.count => item,
+                _ => break,
+            };
+            let mut name = Buffer::new();
+            let _ = write!(name, "{}", item.name);
+            let mut line = Buffer::new();
+            let _ = match item.dir {
+                Some(_) => write!(line, " {:<12} {:>8}", name.as_str(), "<DIR>"),
+                None => write!(line, " {:<12} {:>8}", name.as_str(), item.size),
+            };
+            let attr = if active && index == self.selected {
+                Attr::Highlight
+            } else {
+                Attr::Normal
+            };
+            tui::text(canvas, inside.col, inside.row + row, inside.width, line.as_str(), attr);
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Browse,
+    /// Asking whether to delete what's under the cursor
+    Delete,
+    /// Typing the name of a new directory
+    MakeDir,
+}
+
+/// Both panes, and what we're doing.
+pub struct FileManager {
+    panes: [Pane; 2],
+    active: usize,
+    mode: Mode,
+    name: [u8; NAME_LEN],
+    name_len: usize,
+    /// What went wrong with the last key, in place of the help
+    message: Option<&'static str>,
+}
+
+impl FileManager {
+    /// Start with both panes on `cwd`, whose path is `path`.
+    pub fn new<D>(volume: &mut Volume<D>, cwd: Dir, path: &Path) -> FileManager
+    where
+        D: BlockDevice,
+    {
+        let mut manager = FileManager {
+            panes: [Pane::new(cwd, path.clone()), Pane::new(cwd, path.clone())],
+            active: 0,
+            mode: Mode::Browse,
+            name: [0u8; NAME_LEN],
+            name_len: 0,
+            message: None,
+        };
+        manager.reload(volume);
+        manager
+    }
+
+    /// Handle a key. Escape gives `Response::Cancelled`, which means we're
+    /// done; redraw after anything else. `Response::Changed` means the card
+    /// was written to.
+    pub fn key<D>(&mut self, volume: &mut Volume<D>, key: Key) -> Response
+    where
+        D: BlockDevice,
+    {
+        self.message = None;
+        match self.mode {
+            Mode::Delete => return self.delete_key(volume, key),
+            Mode::MakeDir => return self.make_dir_key(volume, key),
+            Mode::Browse => {}
+        }
+        let selected = self.panes[self.active].selected;
+        match key {
+            Key::Escape => Response::Cancelled,
+            Key::Tab => {
+                self.active ^= 1;
+                Response::Handled
+            }
+            Key::Up => self.select(selected.saturating_sub(1)),
+            Key::Down => self.select(selected + 1),
+            Key::PageUp => self.select(selected.saturating_sub(ROWS)),
+            Key::PageDown => self.select(selected + ROWS),
+            Key::Home => self.select(0),
+            Key::End => self.select(MAX_ENTRIES),
+            Key::Enter => self.enter(volume),
+            Key::Backspace => self.leave(volume),
+            Key::Function(5) | Key::Char('c') | Key::Char('C') => self.copy(volume),
+            Key::Function(7) | Key::Char('m') | Key::Char('M') => {
+                self.mode = Mode::MakeDir;
+                self.name_len = 0;
+                Response::Handled
+            }
+            Key::Function(8) | Key::Delete | Key::Char('d') | Key::Char('D') => {
+                if self.panes[self.active].item().is_some() {
+                    self.mode = Mode::Delete;
+                }
+                Response::Handled
+            }
+            _ => Response::Ignored,
+        }
+    }
+
+    /// Draw the lot.
+    pub fn draw(&self, canvas: &mut Canvas) {
+        self.panes[0].draw(canvas, LEFT, self.active == 0);
+        self.panes[1].draw(canvas, RIGHT, self.active == 1);
+        let mut help = Buffer::new();
+        match self.mode {
+            Mode::Browse => {
+                let _ = write!(help, "{}", self.message.unwrap_or(HELP));
+            }
+            Mode::Delete => {
+                if let Some(item) = self.panes[self.active].item() {
+                    let _ = write!(help, "Delete {}? (y/n)", item.name);
+                }
+            }
+            Mode::MakeDir => {
+                let _ = write!(help, "New directory: {}_", self.typed());
+            }
+        }
+        tui::text(canvas, 0, HELP_ROW, HELP_WIDTH, help.as_str(), Attr::Normal);
+    }
+
+    fn select(&mut self, index: usize) -> Response {
+        self.panes[self.active].select(index);
+        Response::Handled
+    }
+
+    fn enter<D>(&mut self, volume: &mut Volume<D>) -> Response
+    where
+        D: BlockDevice,
+    {
+        let item = match self.panes[self.active].item() {
+            Some(item) => item,
+            None => return Response::Ignored,
+        };
+        let dir = match item.dir {
+            Some(dir) => dir,
+            None => return Response::Ignored,
+        };
+        let mut name = Buffer::new();
+        let _ = write!(name, "{}", item.name);
+        self.change_dir(volume, dir, name.as_str())
+    }
+
+    fn leave<D>(&mut self, volume: &mut Volume<D>) -> Response
+    where
+        D: BlockDevice,
+    {
+        let dir = self.panes[self.active].dir;
+        if dir.is_root() {
+            return Response::Ignored;
+        }
+        match volume.parent(dir) {
+            Ok(parent) => self.change_dir(volume, parent, ".."),
+            Err(e) => {
+                self.message = Some(e.message());
+                Response::Ignored
+            }
+        }
+    }
+
+    fn change_dir<D>(&mut self, volume: &mut Volume<D>, dir: Dir, name: &str) -> Response
+    where
+        D: BlockDevice,
+    {
+        {
+            let pane = &mut self.panes[self.active];
+            pane.dir = dir;
+            pane.path.change(name);
+            pane.selected = 0;
+            pane.top = 0;
+        }
+        self.reload(volume);
+        Response::Handled
+    }
+
+    fn copy<D>(&mut self, volume: &mut Volume<D>) -> Response
+    where
+        D: BlockDevice,
+    {
+        let item = match self.panes[self.active].item() {
+            Some(item) => item,
+            None => return Response::Ignored,
+        };
+        if item.dir.is_some() {
+            self.message = Some("Can't copy a directory");
+            return Response::Ignored;
+        }
+        let mut name = Buffer::new();
+        let _ = write!(name, "{}", item.name);
+        let from = self.panes[self.active].dir;
+        let to = self.panes[self.active ^ 1].dir;
+        let response = match volume.copy(from, name.as_str(), to, name.as_str()) {
+            Ok(_) => Response::Changed,
+            Err(e) => {
+                self.message = Some(e.message());
+                Response::Ignored
+            }
+        };
+        self.reload(volume);
+        response
+    }
+
+    fn delete_key<D>(&mut self, volume: &mut Volume<D>, key: Key) -> Response
+    where
+        D: BlockDevice,
+    {
+        self.mode = Mode::Browse;
+        let item = match (key, self.panes[self.active].item()) {
+            (Key::Char('y'), Some(item)) | (Key::Char('Y'), Some(item)) => item,
+            _ => return Response::Handled,
+        };
+        let mut name = Buffer::new();
+        let _ = write!(name, "{}", item.name);
+        let dir = self.panes[self.active].dir;
+        let response = match volume.delete(dir, name.as_str()) {
+            Ok(()) => Response::Changed,
+            Err(e) => {
+                self.message = Some(e.message());
+                Response::Ignored
+            }
+        };
+        self.reload(volume);
+        response
+    }
+
+    fn make_dir_key<D>(&mut self, volume: &mut Volume<D>, key: Key) -> Response
+    where
+        D: BlockDevice,
+    {
+        match key {
+            Key::Escape => {
+                self.mode = Mode::Browse;
+                Response::Handled
+            }
+            Key::Backspace => {
+                self.name_len = self.name_len.saturating_sub(1);
+                Response::Handled
+            }
+            Key::Char(c) if c > ' ' && c <= '~' && self.name_len < NAME_LEN => {
+                self.name[self.name_len] = c as u8;
+                self.name_len += 1;
+                Response::Handled
+            }
+            Key::Enter => {
+                self.mode = Mode::Browse;
+                let mut name = [0u8; NAME_LEN];
+                name.copy_from_slice(&self.name);
+                let name = ::core::str::from_utf8(&name[..self.name_len]).unwrap_or("");
+                let dir = self.panes[self.active].dir;
+                let response = match volume.make_dir(dir, name) {
+                    Ok(_) => Response::Changed,
+                    Err(e) => {
+                        self.message = Some(e.message());
+                        Response::Ignored
+                    }
+                };
+                self.reload(volume);
+                response
+            }
+            _ => Response::Ignored,
+        }
+    }
+
+    /// What's been typed of a new name.
+    fn typed(&self) -> &str {
+        ::core::str::from_utf8(&self.name[..self.name_len]).unwrap_or("")
+    }
+
+    /// Read both panes again. Only the first complaint is kept.
+    fn reload<D>(&mut self, volume: &mut Volume<D>)
+    where
+        D: BlockDevice,
+    {
+        for pane in self.panes.iter_mut() {
+            if let Err(message) = pane.load(volume) {
+                self.message = self.message.or(Some(message));
+            }
+        }
+    }
+}
@@ -0,0 +1,228 @@
+//! Firmata, the protocol that lets a PC use a microcontroller board as a
+//! bag of pins.
+//!
+//! It's built on MIDI messages (see `midi`): a command byte with the top
+//! bit set, then 7-bit data bytes, so anything bigger is sent seven bits
+//! at a time, least significant first. Longer messages go between SysEx
+//! start and end bytes. Pins are numbered from 0 by the board, in groups
+//! of eight called ports, and the analog inputs also have their own
+//! numbers (A0, A1...).
+//!
+//! `Parser` picks out the messages a host sends. The `send_` functions are
+//! for the replies. Which pins there are, and what they can do, is up to
+//! the example - this is the wire format only.
+
+use embedded_hal::serial;
+
+/// What StandardFirmata runs at, so what host libraries expect.
+pub const BAUD_RATE: u32 = 57_600;
+
+/// The protocol version we speak.
+pub const VERSION: (u8, u8) = (2, 5);
+
+// Pin modes
+pub const INPUT: u8 = 0x00;
+pub const OUTPUT: u8 = 0x01;
+pub const ANALOG: u8 = 0x02;
+pub const PWM: u8 = 0x03;
+pub const PULLUP: u8 = 0x0B;
+
+// SysEx commands
+pub const ANALOG_MAPPING_QUERY: u8 = 0x69;
+pub const ANALOG_MAPPING_RESPONSE: u8 = 0x6A;
+pub const CAPABILITY_QUERY: u8 = 0x6B;
+pub const CAPABILITY_RESPONSE: u8 = 0x6C;
+pub const PIN_STATE_QUERY: u8 = 0x6D;
+pub const PIN_STATE_RESPONSE: u8 = 0x6E;
+pub const REPORT_FIRMWARE: u8 = 0x79;
+pub const SAMPLING_INTERVAL: u8 = 0x7A;
+
+/// Goes after each pin in a capability response, and in the analog
+/// mapping for pins that aren't analog inputs.
+pub const NONE: u8 = 0x7F;
+
+/// The longest SysEx message `Parser` keeps. Anything longer is cut off.
+pub const MAX_SYSEX: usize = 64;
+
+const DIGITAL_MESSAGE: u8 = 0x90;
+const ANALOG_MESSAGE: u8 = 0xE0;
+const REPORT_ANALOG: u8 = 0xC0;
+const REPORT_DIGITAL: u8 = 0xD0;
+const SYSEX_START: u8 = 0xF0;
+const SET_PIN_MODE: u8 = 0xF4;
+const SET_DIGITAL_PIN: u8 = 0xF5;
+const SYSEX_END: u8 = 0xF7;
+const REPORT_VERSION: u8 = 0xF9;
+const SYSTEM_RESET: u8 = 0xFF;
+
+/// Something the host asked for.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Message<'a> {
+    /// Set the output pins in a port: bit 0 is the first pin
+    DigitalPort { port: u8, value: u8 },
+    /// Set a PWM pin's duty cycle, 0 to 255
+    AnalogWrite { pin: u8, value: u16 },
+    /// Start or stop sending an analog input's readings
+    ReportAnalog { channel: u8, on: bool },
+    /// Start or stop sending a port's inputs when they change
+    ReportDigital { port: u8, on: bool },
+    SetPinMode { pin: u8, mode: u8 },
+    SetPin { pin: u8, high: bool },
+    ReportVersion,
+    SystemReset,
+    /// A SysEx command, and its data (still in 7-bit bytes)
+    Sysex { command: u8, data: &'a [u8] },
+}
+
+/// Turns a stream of bytes from the host into messages.
+pub struct Parser {
+    command: u8,
+    data: [u8; MAX_SYSEX],
+    count: usize,
+}
+
+impl Parser {
+    pub fn new() -> Parser {
+        Parser {
+            command: 0,
+            data: [0; MAX_SYSEX],
+            count: 0,
+        }
+    }
+
+    /// Feed in the next byte. Returns a message when one is complete.
+    pub fn input(&mut self, byte: u8) -> Option<Message> {
+        if byte & 0x80 != 0 {
+            if byte == SYSEX_END && self.command == SYSEX_START && self.count > 0 {
+                self.command = 0;
+                return Some(Message::Sysex {
+                    command: self.data[0],
+                    data: &self.data[1..self.count],
+                });
+            }
+            self.command = byte;
+            self.count = 0;
+            return match byte {
+                REPORT_VERSION => Some(Message::ReportVersion),
+                SYSTEM_RESET => Some(Message::SystemReset),
+                _ => None,
+            };
+        }
+        if self.command == SYSEX_START {
+            if self.count < MAX_SYSEX {
+                self.data[self.count] = byte;
+                self.count += 1;
+            }
+            return None;
+        }
+        if self.command == 0 || self.count == 2 {
+            return None;
+        }
+        self.data[self.count] = byte;
+        self.count += 1;
+        let wanted = match self.command & 0xF0 {
+            REPORT_ANALOG | REPORT_DIGITAL => 1,
+            _ => 2,
+        };
+        if self.count < wanted {
+            return None;
+        }
+        let (first, second) = (self.data[0], self.data[1]);
+        let channel = self.command & 0x0F;
+        let message = match (self.command, self.command & 0xF0) {
+            (SET_PIN_MODE, _) => Message::SetPinMode {
+                pin: first,
+                mode: second,
+            },
+            (SET_DIGITAL_PIN, _) => Message::SetPin {
+                pin: first,
+                high: second != 0,
+            },
+            (_, DIGITAL_MESSAGE) => Message::DigitalPort {
+                port: channel,
+                value: first | (second << 7),
+            },
+            (_, ANALOG_MESSAGE) => Message::AnalogWrite {
+                pin: channel,
+                value: u16::from(first) | (u16::from(second) << 7),
+            },
+            (_, REPORT_ANALOG) => Message::ReportAnalog {
+                channel,
+                on: first != 0,
+            },
+            (_, REPORT_DIGITAL) => Message::ReportDigital {
+                port: channel,
+                on: first != 0,
+            },
+            _ => return None,
+        };
+        // Ready for the next message with the same command
+        if self.command < SYSEX_START {
+            self.count = 0;
+        }
+        Some(message)
+    }
+}
+
+/// Say which version of the protocol we speak.
+pub fn send_version<TX>(tx: &mut TX) -> Result<(), TX::Error>
+where
+    TX: serial::Write<u8>,
+{
+    write_all(tx, &[REPORT_VERSION, VERSION.0, VERSION.1])
+}
+
+/// Say what we are. Host libraries ask for this when they connect.
+pub fn send_firmware<TX>(tx: &mut TX, name: &str) -> Result<(), TX::Error>
+where
+    TX: serial::Write<u8>,
+{
+    write_all(tx, &[SYSEX_START, REPORT_FIRMWARE, VERSION.0, VERSION.1])?;
+    for byte in name.bytes() {
+        write_all(tx, &[byte & 0x7F, byte >> 7])?;
+    }
+    block!(tx.write(SYSEX_END))
+}
+
+/// Send an analog input's reading (up to 14 bits).
+pub fn send_analog<TX>(tx: &mut TX, channel: u8, value: u16) -> Result<(), TX::Error>
+where
+    TX: serial::Write<u8>,
+{
+    write_all(
+        tx,
+        &[
+            ANALOG_MESSAGE | (channel & 0x0F),
+            (value & 0x7F) as u8,
+            ((value >> 7) & 0x7F) as u8,
+        ],
+    )
+}
+
+/// Send the state of a port's pins.
+pub fn send_digital_port<TX>(tx: &mut TX, port: u8, value: u8) -> Result<(), TX::Error>
+where
+    TX: serial::Write<u8>,
+{
+    write_all(tx, &[DIGITAL_MESSAGE | (port & 0x0F), value & 0x7F, value >> 7])
+}
+
+/// Send a SysEx message. `data` must already be in 7-bit bytes.
+pub fn send_sysex<TX>(tx: &mut TX, command: u8, data: &[u8]) -> Result<(), TX::Error>
+where
+    TX: serial::Write<u8>,
+{
+    write_all(tx, &[SYSEX_START, command])?;
+    write_all(tx, data)?;
+    block!(tx.write(SYSEX_END))
+}
+
+fn write_all<TX>(tx: &mut TX, bytes: &[u8]) -> Result<(), TX::Error>
+where
+    TX: serial::Write<u8>,
+{
+    for &byte in bytes {
+        block!(tx.write(byte))?;
+    }
+    Ok(())
+}
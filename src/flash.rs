@@ -0,0 +1,139 @@
+//! Erases and programs the on-chip flash.
+//!
+//! Flash is erased in pages (to all ones) - 1 KiB on the TM4C123, 16 KiB on the
+//! TM4C1294 - and programmed a 32-bit word at a time. Each operation is started
+//! by writing to FMC with a key (which key depends on the KEY bit in BOOTCFG)
+//! and we wait for it to finish. Code keeps running from flash while we do
+//! this; the CPU just stalls if it fetches from flash mid-operation.
+//!
+//! Anything write-protected in FMPPEn gives an access error rather than
+//! being changed.
+
+use pac::FLASH_CTRL;
+
+/// The erase unit.
+#[cfg(feature = "tm4c123")]
+pub const PAGE_SIZE: usize = 1024;
+/// The erase unit.
+#[cfg(feature = "tm4c129")]
+pub const PAGE_SIZE: usize = 16 * 1024;
+
+/// The whole flash.
+#[cfg(feature = "tm4c123")]
+pub const FLASH_SIZE: usize = 256 * 1024;
+/// The whole flash.
+#[cfg(feature = "tm4c129")]
+pub const FLASH_SIZE: usize = 1024 * 1024;
+
+// FMC
+const FMC_WRITE: u32 = 1 << 0;
+const FMC_ERASE: u32 = 1 << 1;
+const FMC_KEY_SHIFT: u32 = 16;
+
+/// BOOTCFG.KEY says which key to use.
+const BOOTCFG_KEY: u32 = 1 << 4;
+const KEY_A442: u32 = 0xA442;
+const KEY_71D5: u32 = 0x71D5;
+
+/// FCRIS.ARIS, the access (protection) error
+const FCRIS_ACCESS: u32 = 1 << 0;
+
+/// Something went wrong with the flash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The address isn't aligned (to a word, or a page for erases), or
+    /// runs off the end
+    BadAddress,
+    /// The page is write-protected
+    Protected,
+    /// What we read back isn't what we wrote
+    Verify,
+}
+
+pub struct Flash {
+    flash: FLASH_CTRL,
+    key: u32,
+}
+
+impl Flash {
+    pub fn new(flash: FLASH_CTRL) -> Flash {
+        let key = if flash.bootcfg.read().bits() & BOOTCFG_KEY != 0 {
+            KEY_A442
+        } else {
+            KEY_71D5
+        };
+        Flash { flash, key }
+    }
+
+    /// Give the peripheral back.
+    pub fn free(self) -> FLASH_CTRL {
+        self.flash
+    }
+
+    /// Erase the page starting at `address`.
+    pub fn erase(&mut self, address: usize) -> Result<(), Error> {
+        if address % PAGE_SIZE != 0 || address >= FLASH_SIZE {
+            return Err(Error::BadAddress);
+        }
+        self.run(address, FMC_ERASE)?;
+        for offset in 0..PAGE_SIZE / 4 {
+            if read_word(address + (offset * 4)) != 0xFFFF_FFFF {
+                return Err(Error::Verify);
+            }
+        }
+        Ok(())
+    }
+
+    /// Program words starting at `address`, which must have been erased.
+    pub fn write(&mut self, address: usize, data: &[u32]) -> Result<(), Error> {
+        if address % 4 != 0 || address + (data.len() * 4) > FLASH_SIZE {
+            return Err(Error::BadAddress);
+        }
+        for (i, word) in data.iter().enumerate() {
+            let word_address = address + (i * 4);
+            self.flash.fmd.write(|w| unsafe { w.bits(*word) });
+            self.run(word_address, FMC_WRITE)?;
+            if read_word(word_address) != *word {
+                return Err(Error::Verify);
+            }
+        }
+        Ok(())
+    }
+
+    /// Start an operation and wait for it to finish.
+    fn run(&mut self, address: usize, command: u32) -> Result<(), Error> {
+        self.flash.fcmisc.write(|w| unsafe { w.bits(FCRIS_ACCESS) });
+        self.flash.fma.write(|w| unsafe { w.bits(address as u32) });
+        self.flash
+            .fmc
+            .write(|w| unsafe { w.bits((self.key << FMC_KEY_SHIFT) | command) });
+        while self.flash.fmc.read().bits() & command != 0 {}
+        if self.flash.fcris.read().bits() & FCRIS_ACCESS != 0 {
+            self.flash.fcmisc.write(|w| unsafe { w.bits(FCRIS_ACCESS) });
+            return Err(Error::Protected);
+        }
+        Ok(())
+    }
+}
+
+/// Read a word from flash (or anywhere else, really).
+pub fn read_word(address: usize) -> u32 {
+    unsafe { ::core::ptr::read_volatile(address as *const u32) }
+}
+
+/// The CRC-32 (as in zip and Ethernet) of some memory, for checking images.
+pub fn crc32(addresses: ::core::ops::Range<usize>) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for address in addresses {
+        let byte = unsafe { ::core::ptr::read_volatile(address as *const u8) };
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
@@ -0,0 +1,23 @@
+//! The 8x16 font used by the VGA text mode, for use on other displays.
+//!
+//! Each glyph is sixteen bytes, one per row, with the leftmost pixel in the
+//! most significant bit. The character set is Code Page 850.
+
+use fb::freebsd_cp850::FONT_DATA;
+
+/// Width of a glyph in pixels.
+pub const WIDTH: usize = 8;
+
+/// Height of a glyph in pixels.
+pub const HEIGHT: usize = 16;
+
+/// Get the sixteen rows of pixels for a character.
+pub fn glyph(ch: u8) -> &'static [u8] {
+    let start = ch as usize * HEIGHT;
+    &FONT_DATA[start..start + HEIGHT]
+}
+
+/// Is the pixel at (`x`, `y`) within the glyph for `ch` set?
+pub fn pixel(ch: u8, x: usize, y: usize) -> bool {
+    (glyph(ch)[y] & (0x80 >> x)) != 0
+}
@@ -0,0 +1,975 @@
+//! A small indirect-threaded Forth.
+//!
+//! The dictionary is a block of RAM the caller hands us. Each word has a
+//! header - a link to the previous word, the name's length and the name
+//! itself - then a code field saying what kind of word it is, then its
+//! body. A colon definition's body is a list of code field addresses
+//! ('execution tokens'), which the inner interpreter follows one at a
+//! time, looking up what to do in each code field.
+//!
+//! Built in are the usual stack, arithmetic, logic, memory and output words,
+//! `: ;`, `CONSTANT`, `VARIABLE`, `'`, `EXECUTE`, comments, `."` and the
+//! control structures (`IF ELSE THEN`, `BEGIN UNTIL`, `BEGIN AGAIN`,
+//! `BEGIN WHILE REPEAT`, `DO LOOP`, `DO +LOOP`). `@`, `!` and friends work
+//! on real addresses, so you can poke the hardware registers directly;
+//! `VARIABLE` and `HERE` give real addresses too. Numbers are 32 bits.
+//!
+//! Anything else - talking to particular hardware - is a native word,
+//! added with `define`, which gets the data stack to work on.
+
+use core::fmt::Write;
+
+/// How deep the data stack goes.
+pub const STACK_LEN: usize = 64;
+
+/// How deep the return stack goes.
+const RSTACK_LEN: usize = 64;
+
+/// How many native words we can have.
+pub const MAX_NATIVES: usize = 32;
+
+/// The longest name a word can have.
+pub const MAX_NAME: usize = 31;
+
+/// A native word - it works on the data stack.
+pub type Native = fn(&mut Stack) -> Result<(), Error>;
+
+// Code field values. Natives come after these.
+const DOCOL: u32 = 0;
+const DOCON: u32 = 1;
+const DOVAR: u32 = 2;
+const EXIT: u32 = 3;
+const LIT: u32 = 4;
+const BRANCH: u32 = 5;
+const ZBRANCH: u32 = 6;
+const DO_RT: u32 = 7;
+const LOOP_RT: u32 = 8;
+const PLUS_LOOP_RT: u32 = 9;
+const DOT_QUOTE_RT: u32 = 10;
+const DUP: u32 = 11;
+const DROP: u32 = 12;
+const SWAP: u32 = 13;
+const OVER: u32 = 14;
+const ROT: u32 = 15;
+const NIP: u32 = 16;
+const TUCK: u32 = 17;
+const QDUP: u32 = 18;
+const DEPTH: u32 = 19;
+const ADD: u32 = 20;
+const SUB: u32 = 21;
+const MUL: u32 = 22;
+const DIV: u32 = 23;
+const MOD: u32 = 24;
+const NEGATE: u32 = 25;
+const ABS: u32 = 26;
+const MIN: u32 = 27;
+const MAX: u32 = 28;
+const INC: u32 = 29;
+const DEC: u32 = 30;
+const AND: u32 = 31;
+const OR: u32 = 32;
+const XOR: u32 = 33;
+const INVERT: u32 = 34;
+const LSHIFT: u32 = 35;
+const RSHIFT: u32 = 36;
+const EQ: u32 = 37;
+const NE: u32 = 38;
+const LT: u32 = 39;
+const GT: u32 = 40;
+const ZERO_EQ: u32 = 41;
+const ZERO_LT: u32 = 42;
+const TO_R: u32 = 43;
+const R_FROM: u32 = 44;
+const R_FETCH: u32 = 45;
+const I: u32 = 46;
+const J: u32 = 47;
+const FETCH: u32 = 48;
+const STORE: u32 = 49;
+const C_FETCH: u32 = 50;
+const C_STORE: u32 = 51;
+const PLUS_STORE: u32 = 52;
+const HERE: u32 = 53;
+const COMMA: u32 = 54;
+const ALLOT: u32 = 55;
+const CELLS: u32 = 56;
+const DOT: u32 = 57;
+const U_DOT: u32 = 58;
+const DOT_S: u32 = 59;
+const EMIT: u32 = 60;
+const CR: u32 = 61;
+const SPACE: u32 = 62;
+const HEX: u32 = 63;
+const DECIMAL: u32 = 64;
+const WORDS: u32 = 65;
+const EXECUTE: u32 = 66;
+// Everything from here to NATIVE is handled by the outer interpreter, as
+// they read ahead in the input or compile something
+const COLON: u32 = 67;
+const SEMICOLON: u32 = 68;
+const CONSTANT: u32 = 69;
+const VARIABLE: u32 = 70;
+const TICK: u32 = 71;
+const PAREN: u32 = 72;
+const BACKSLASH: u32 = 73;
+const DOT_QUOTE: u32 = 74;
+const IF: u32 = 75;
+const ELSE: u32 = 76;
+const THEN: u32 = 77;
+const BEGIN: u32 = 78;
+const UNTIL: u32 = 79;
+const AGAIN: u32 = 80;
+const WHILE: u32 = 81;
+const REPEAT: u32 = 82;
+const DO: u32 = 83;
+const LOOP: u32 = 84;
+const PLUS_LOOP: u32 = 85;
+const NATIVE: u32 = 86;
+
+/// The execution tokens of the words that only appear inside definitions.
+/// They have no headers, so nobody can find them.
+const XT_EXIT: usize = 1;
+const XT_LIT: usize = 2;
+const XT_BRANCH: usize = 3;
+const XT_ZBRANCH: usize = 4;
+const XT_DO: usize = 5;
+const XT_LOOP: usize = 6;
+const XT_PLUS_LOOP: usize = 7;
+const XT_DOT_QUOTE: usize = 8;
+const FIRST_HEADER: usize = 9;
+
+const BUILTINS: [(&str, u32); 76] = [
+    ("EXIT", EXIT),
+    ("DUP", DUP),
+    ("DROP", DROP),
+    ("SWAP", SWAP),
+    ("OVER", OVER),
+    ("ROT", ROT),
+    ("NIP", NIP),
+    ("TUCK", TUCK),
+    ("?DUP", QDUP),
+    ("DEPTH", DEPTH),
+    ("+", ADD),
+    ("-", SUB),
+    ("*", MUL),
+    ("/", DIV),
+    ("MOD", MOD),
+    ("NEGATE", NEGATE),
+    ("ABS", ABS),
+    ("MIN", MIN),
+    ("MAX", MAX),
+    ("1+", INC),
+    ("1-", DEC),
+    ("AND", AND),
+    ("OR", OR),
+    ("XOR", XOR),
+    ("INVERT", INVERT),
+    ("LSHIFT", LSHIFT),
+    ("RSHIFT", RSHIFT),
+    ("=", EQ),
+    ("<>", NE),
+    ("<", LT),
+    (">", GT),
+    ("0=", ZERO_EQ),
+    ("0<", ZERO_LT),
+    (">R", TO_R),
+    ("R>", R_FROM),
+    ("R@", R_FETCH),
+    ("I", I),
+    ("J", J),
+    ("@", FETCH),
+    ("!", STORE),
+    ("C@", C_FETCH),
+    ("C!", C_STORE),
+    ("+!", PLUS_STORE),
+    ("HERE", HERE),
+    (",", COMMA),
+    ("ALLOT", ALLOT),
+    ("CELLS", CELLS),
+    (".", DOT),
+    ("U.", U_DOT),
+    (".S", DOT_S),
+    ("EMIT", EMIT),
+    ("CR", CR),
+    ("SPACE", SPACE),
+    ("HEX", HEX),
+    ("DECIMAL", DECIMAL),
+    ("WORDS", WORDS),
+    ("EXECUTE", EXECUTE),
+    (":", COLON),
+    (";", SEMICOLON),
+    ("CONSTANT", CONSTANT),
+    ("VARIABLE", VARIABLE),
+    ("'", TICK),
+    ("(", PAREN),
+    ("\\", BACKSLASH),
+    (".\"", DOT_QUOTE),
+    ("IF", IF),
+    ("ELSE", ELSE),
+    ("THEN", THEN),
+    ("BEGIN", BEGIN),
+    ("UNTIL", UNTIL),
+    ("AGAIN", AGAIN),
+    ("WHILE", WHILE),
+    ("REPEAT", REPEAT),
+    ("DO", DO),
+    ("LOOP", LOOP),
+    ("+LOOP", PLUS_LOOP),
+];
+
+/// What went wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// A word we don't know, which isn't a number either
+    Unknown,
+    /// Not enough on the stack
+    StackUnderflow,
+    /// Too much on the stack
+    StackOverflow,
+    /// Return stack trouble - nested too deep, or unbalanced `>R`/`R>`
+    ReturnStack,
+    /// Dividing by zero
+    DivideByZero,
+    /// No room for another word
+    DictionaryFull,
+    /// Too many natives
+    TooManyNatives,
+    /// A defining word with no name after it, or a name that's too long
+    BadName,
+    /// A word that only makes sense inside a definition, used outside one
+    /// (or the other way round)
+    CompileOnly,
+    /// Mismatched control structures, or a corrupted definition
+    BadStructure,
+    /// Stopped by the `stop` callback
+    Interrupted,
+}
+
+/// The data stack. Natives get this.
+pub struct Stack {
+    cells: [i32; STACK_LEN],
+    depth: usize,
+}
+
+impl Stack {
+    pub fn push(&mut self, value: i32) -> Result<(), Error> {
+        if self.depth == STACK_LEN {
+            return Err(Error::StackOverflow);
+        }
+        self.cells[self.depth] = value;
+        self.depth += 1;
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Result<i32, Error> {
+        if self.depth == 0 {
+            return Err(Error::StackUnderflow);
+        }
+        self.depth -= 1;
+        Ok(self.cells[self.depth])
+    }
+
+    /// The item `n` down from the top, without taking it off.
+    pub fn pick(&self, n: usize) -> Result<i32, Error> {
+        if n >= self.depth {
+            return Err(Error::StackUnderflow);
+        }
+        Ok(self.cells[self.depth - 1 - n])
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn as_slice(&self) -> &[i32] {
+        &self.cells[0..self.depth]
+    }
+}
+
+pub struct Forth<'a> {
+    /// The dictionary, in cells
+    mem: &'a mut [u32],
+    here: usize,
+    /// The most recent header, or zero
+    latest: usize,
+    pub stack: Stack,
+    rstack: [u32; RSTACK_LEN],
+    rdepth: usize,
+    natives: [Option<Native>; MAX_NATIVES],
+    compiling: bool,
+    /// Where to go back to if a definition fails half way
+    rollback: (usize, usize),
+    base: u32,
+    /// Checked every so often while running - return true to stop
+    pub stop: Option<fn() -> bool>,
+}
+
+impl<'a> Forth<'a> {
+    /// Set up the built-in words in `mem`, which needs to be a few
+    /// hundred cells at least.
+    pub fn new(mem: &'a mut [u32]) -> Result<Forth<'a>, Error> {
+        let mut forth = Forth {
+            mem,
+            here: FIRST_HEADER,
+            latest: 0,
+            stack: Stack {
+                cells: [0; STACK_LEN],
+                depth: 0,
+            },
+            rstack: [0; RSTACK_LEN],
+            rdepth: 0,
+            natives: [None; MAX_NATIVES],
+            compiling: false,
+            rollback: (0, 0),
+            base: 10,
+            stop: None,
+        };
+        if forth.mem.len() < FIRST_HEADER {
+            return Err(Error::DictionaryFull);
+        }
+        let runtime = [
+            EXIT,
+            LIT,
+            BRANCH,
+            ZBRANCH,
+            DO_RT,
+            LOOP_RT,
+            PLUS_LOOP_RT,
+            DOT_QUOTE_RT,
+        ];
+        forth.mem[XT_EXIT..FIRST_HEADER].copy_from_slice(&runtime);
+        for &(name, code) in BUILTINS.iter() {
+            forth.create(name, code)?;
+        }
+        Ok(forth)
+    }
+
+    /// Add a word that runs some Rust.
+    pub fn define(&mut self, name: &str, native: Native) -> Result<(), Error> {
+        let index = self.natives
+            .iter()
+            .position(|n| n.is_none())
+            .ok_or(Error::TooManyNatives)?;
+        self.create(name, NATIVE + index as u32)?;
+        self.natives[index] = Some(native);
+        Ok(())
+    }
+
+    /// How many cells are left in the dictionary.
+    pub fn free(&self) -> usize {
+        self.mem.len() - self.here
+    }
+
+    /// True in the middle of a colon definition (so the prompt can say so).
+    pub fn is_compiling(&self) -> bool {
+        self.compiling
+    }
+
+    /// Interpret (or compile) a line. If anything goes wrong, the stacks
+    /// are emptied and any half-made definition thrown away.
+    pub fn interpret<W: Write>(&mut self, line: &str, out: &mut W) -> Result<(), Error> {
+        let result = self.interpret_line(line, out);
+        if result.is_err() {
+            self.stack.depth = 0;
+            self.rdepth = 0;
+            if self.compiling {
+                self.here = self.rollback.0;
+                self.latest = self.rollback.1;
+                self.compiling = false;
+            }
+        }
+        result
+    }
+
+    fn interpret_line<W: Write>(&mut self, line: &str, out: &mut W) -> Result<(), Error> {
+        let mut rest = line;
+        loop {
+            let (word, after) = next_word(rest);
+            rest = after;
+            if word.is_empty() {
+                return Ok(());
+            }
+            let xt = match self.find(word) {
+                Some(xt) => xt,
+                None => {
+                    let value = parse_number(word, self.base).ok_or(Error::Unknown)?;
+                    if self.compiling {
+                        self.comma(XT_LIT as u32)?;
+                        self.comma(value as u32)?;
+                    } else {
+                        self.stack.push(value)?;
+                    }
+                    continue;
+                }
+            };
+            let code = self.mem[xt];
+            if code < COLON || code >= NATIVE {
+                if self.compiling {
+                    self.comma(xt as u32)?;
+                } else {
+                    self.execute(xt, out)?;
+                }
+                continue;
+            }
+            match code {
+                COLON => {
+                    if self.compiling {
+                        return Err(Error::CompileOnly);
+                    }
+                    let (name, after) = next_word(rest);
+                    rest = after;
+                    self.rollback = (self.here, self.latest);
+                    self.compiling = true;
+                    self.create(name, DOCOL)?;
+                }
+                CONSTANT | VARIABLE => {
+                    let (name, after) = next_word(rest);
+                    rest = after;
+                    if code == CONSTANT {
+                        let value = self.stack.pop()?;
+                        self.create(name, DOCON)?;
+                        self.comma(value as u32)?;
+                    } else {
+                        self.create(name, DOVAR)?;
+                        self.comma(0)?;
+                    }
+                }
+                TICK => {
+                    let (name, after) = next_word(rest);
+                    rest = after;
+                    let xt = self.find(name).ok_or(Error::Unknown)?;
+                    if self.compiling {
+                        self.comma(XT_LIT as u32)?;
+                        self.comma(xt as u32)?;
+                    } else {
+                        self.stack.push(xt as i32)?;
+                    }
+                }
+                PAREN => {
+                    rest = match rest.find(')') {
+                        Some(end) => &rest[end + 1..],
+                        None => "",
+                    };
+                }
+                BACKSLASH => rest = "",
+                DOT_QUOTE => {
+                    // The text starts after the one space that ends `."`
+                    let text = if rest.starts_with(' ') {
+                        &rest[1..]
+                    } else {
+                        rest
+                    };
+                    let end = text.find('"').unwrap_or(text.len());
+                    rest = if end < text.len() { &text[end + 1..] } else { "" };
+                    let text = &text[0..end];
+                    if self.compiling {
+                        self.comma(XT_DOT_QUOTE as u32)?;
+                        self.comma(text.len() as u32)?;
+                        for chunk in text.as_bytes().chunks(4) {
+                            self.comma(pack(chunk))?;
+                        }
+                    } else {
+                        out.write_str(text).unwrap();
+                    }
+                }
+                _ => self.compile_control(code)?,
+            }
+        }
+    }
+
+    /// `;` and the control structures. At compile time, the data stack
+    /// holds the places that need patching.
+    fn compile_control(&mut self, code: u32) -> Result<(), Error> {
+        if !self.compiling {
+            return Err(Error::CompileOnly);
+        }
+        match code {
+            SEMICOLON => {
+                self.comma(XT_EXIT as u32)?;
+                self.compiling = false;
+            }
+            IF => {
+                self.comma(XT_ZBRANCH as u32)?;
+                self.stack.push(self.here as i32)?;
+                self.comma(0)?;
+            }
+            ELSE => {
+                let orig = self.stack.pop()?;
+                self.comma(XT_BRANCH as u32)?;
+                self.stack.push(self.here as i32)?;
+                self.comma(0)?;
+                self.resolve(orig)?;
+            }
+            THEN => {
+                let orig = self.stack.pop()?;
+                self.resolve(orig)?;
+            }
+            BEGIN => self.stack.push(self.here as i32)?,
+            UNTIL | AGAIN => {
+                let dest = self.stack.pop()?;
+                let branch = if code == UNTIL { XT_ZBRANCH } else { XT_BRANCH };
+                self.comma(branch as u32)?;
+                self.comma(dest as u32)?;
+            }
+            WHILE => {
+                let dest = self.stack.pop()?;
+                self.comma(XT_ZBRANCH as u32)?;
+                self.stack.push(self.here as i32)?;
+                self.stack.push(dest)?;
+                self.comma(0)?;
+            }
+            REPEAT => {
+                let dest = self.stack.pop()?;
+                let orig = self.stack.pop()?;
+                self.comma(XT_BRANCH as u32)?;
+                self.comma(dest as u32)?;
+                self.resolve(orig)?;
+            }
+            DO => {
+                self.comma(XT_DO as u32)?;
+                self.stack.push(self.here as i32)?;
+            }
+            _ => {
+                // LOOP and +LOOP
+                let dest = self.stack.pop()?;
+                let xt = if code == LOOP { XT_LOOP } else { XT_PLUS_LOOP };
+                self.comma(xt as u32)?;
+                self.comma(dest as u32)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Point a forward branch at `here`.
+    fn resolve(&mut self, orig: i32) -> Result<(), Error> {
+        let orig = orig as usize;
+        if orig < FIRST_HEADER || orig >= self.here {
+            return Err(Error::BadStructure);
+        }
+        self.mem[orig] = self.here as u32;
+        Ok(())
+    }
+
+    /// Run a word, and (for a colon definition) everything it calls.
+    fn execute<W: Write>(&mut self, xt: usize, out: &mut W) -> Result<(), Error> {
+        let mut w = xt;
+        // Zero means 'back to the outer interpreter'
+        let mut ip = 0;
+        let mut count = 0u32;
+        loop {
+            let code = self.fetch(w)?;
+            match code {
+                DOCOL => {
+                    self.rpush(ip as u32)?;
+                    ip = w + 1;
+                }
+                EXIT => ip = self.rpop()? as usize,
+                DOCON => {
+                    let value = self.fetch(w + 1)?;
+                    self.stack.push(value as i32)?;
+                }
+                DOVAR => {
+                    let address = self.address(w + 1);
+                    self.stack.push(address as i32)?;
+                }
+                LIT => {
+                    let value = self.fetch(ip)?;
+                    self.stack.push(value as i32)?;
+                    ip += 1;
+                }
+                BRANCH => ip = self.fetch(ip)? as usize,
+                ZBRANCH => {
+                    if self.stack.pop()? == 0 {
+                        ip = self.fetch(ip)? as usize;
+                    } else {
+                        ip += 1;
+                    }
+                }
+                DO_RT => {
+                    let index = self.stack.pop()?;
+                    let limit = self.stack.pop()?;
+                    self.rpush(limit as u32)?;
+                    self.rpush(index as u32)?;
+                }
+                LOOP_RT | PLUS_LOOP_RT => {
+                    let step = if code == LOOP_RT {
+                        1
+                    } else {
+                        self.stack.pop()?
+                    };
+                    let index = self.rpop()? as i32;
+                    let limit = self.rpop()? as i32;
+                    // We stop when the index crosses from limit - 1 to limit
+                    // (either way), i.e. when index - limit changes sign
+                    let before = index.wrapping_sub(limit);
+                    let after = before.wrapping_add(step);
+                    if (before ^ after) < 0 {
+                        ip += 1;
+                    } else {
+                        self.rpush(limit as u32)?;
+                        self.rpush(index.wrapping_add(step) as u32)?;
+                        ip = self.fetch(ip)? as usize;
+                    }
+                }
+                DOT_QUOTE_RT => {
+                    let len = self.fetch(ip)? as usize;
+                    let cells = (len + 3) / 4;
+                    for i in 0..len {
+                        let word = self.fetch(ip + 1 + (i / 4))?;
+                        out.write_char((word >> ((i % 4) * 8)) as u8 as char)
+                            .unwrap();
+                    }
+                    ip += 1 + cells;
+                }
+                EXECUTE => w = self.stack.pop()? as usize,
+                _ => self.primitive(code, out)?,
+            }
+            // EXECUTE has given us a new word to run straight away
+            if code == EXECUTE {
+                continue;
+            }
+            if ip == 0 {
+                return Ok(());
+            }
+            w = self.fetch(ip)? as usize;
+            ip += 1;
+            count = count.wrapping_add(1);
+            if count % 1024 == 0 {
+                if let Some(stop) = self.stop {
+                    if stop() {
+                        return Err(Error::Interrupted);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The words that just do something to the stacks, memory or output.
+    fn primitive<W: Write>(&mut self, code: u32, out: &mut W) -> Result<(), Error> {
+        let s = &mut self.stack;
+        match code {
+            DUP => {
+                let a = s.pick(0)?;
+                s.push(a)?;
+            }
+            DROP => {
+                s.pop()?;
+            }
+            SWAP => {
+                let b = s.pop()?;
+                let a = s.pop()?;
+                s.push(b)?;
+                s.push(a)?;
+            }
+            OVER => {
+                let a = s.pick(1)?;
+                s.push(a)?;
+            }
+            ROT => {
+                let c = s.pop()?;
+                let b = s.pop()?;
+                let a = s.pop()?;
+                s.push(b)?;
+                s.push(c)?;
+                s.push(a)?;
+            }
+            NIP => {
+                let b = s.pop()?;
+                s.pop()?;
+                s.push(b)?;
+            }
+            TUCK => {
+                let b = s.pop()?;
+                let a = s.pop()?;
+                s.push(b)?;
+                s.push(a)?;
+                s.push(b)?;
+            }
+            QDUP => {
+                let a = s.pick(0)?;
+                if a != 0 {
+                    s.push(a)?;
+                }
+            }
+            DEPTH => {
+                let depth = s.depth() as i32;
+                s.push(depth)?;
+            }
+            ADD...MOD | MIN | MAX | AND...RSHIFT | EQ...GT => {
+                let b = s.pop()?;
+                let a = s.pop()?;
+                let result = match code {
+                    ADD => a.wrapping_add(b),
+                    SUB => a.wrapping_sub(b),
+                    MUL => a.wrapping_mul(b),
+                    DIV | MOD if b == 0 => return Err(Error::DivideByZero),
+                    DIV => a.wrapping_div(b),
+                    MOD => a.wrapping_rem(b),
+                    MIN => a.min(b),
+                    MAX => a.max(b),
+                    AND => a & b,
+                    OR => a | b,
+                    XOR => a ^ b,
+                    LSHIFT => ((a as u32).wrapping_shl(b as u32)) as i32,
+                    RSHIFT => ((a as u32).wrapping_shr(b as u32)) as i32,
+                    EQ => flag(a == b),
+                    NE => flag(a != b),
+                    LT => flag(a < b),
+                    _ => flag(a > b),
+                };
+                s.push(result)?;
+            }
+            NEGATE | ABS | INC | DEC | INVERT | ZERO_EQ | ZERO_LT | CELLS => {
+                let a = s.pop()?;
+                let result = match code {
+                    NEGATE => a.wrapping_neg(),
+                    ABS => a.wrapping_abs(),
+                    INC => a.wrapping_add(1),
+                    DEC => a.wrapping_sub(1),
+                    INVERT => !a,
+                    ZERO_EQ => flag(a == 0),
+                    ZERO_LT => flag(a < 0),
+                    _ => a.wrapping_mul(4),
+                };
+                s.push(result)?;
+            }
+            TO_R => {
+                let a = s.pop()?;
+                if self.rdepth == RSTACK_LEN {
+                    return Err(Error::ReturnStack);
+                }
+                self.rstack[self.rdepth] = a as u32;
+                self.rdepth += 1;
+            }
+            R_FROM => {
+                if self.rdepth == 0 {
+                    return Err(Error::ReturnStack);
+                }
+                self.rdepth -= 1;
+                s.push(self.rstack[self.rdepth] as i32)?;
+            }
+            R_FETCH | I | J => {
+                // R@ and I are the same thing; J is the outer loop's
+                // index, under the inner loop's index and limit
+                let n = if code == J { 3 } else { 1 };
+                if self.rdepth < n {
+                    return Err(Error::ReturnStack);
+                }
+                s.push(self.rstack[self.rdepth - n] as i32)?;
+            }
+            FETCH => {
+                let address = s.pop()? as usize;
+                s.push(unsafe { ::core::ptr::read_volatile(address as *const i32) })?;
+            }
+            STORE => {
+                let address = s.pop()? as usize;
+                let value = s.pop()?;
+                unsafe { ::core::ptr::write_volatile(address as *mut i32, value) };
+            }
+            C_FETCH => {
+                let address = s.pop()? as usize;
+                let value = unsafe { ::core::ptr::read_volatile(address as *const u8) };
+                s.push(i32::from(value))?;
+            }
+            C_STORE => {
+                let address = s.pop()? as usize;
+                let value = s.pop()?;
+                unsafe { ::core::ptr::write_volatile(address as *mut u8, value as u8) };
+            }
+            PLUS_STORE => {
+                let address = s.pop()? as usize;
+                let value = s.pop()?;
+                unsafe {
+                    let old = ::core::ptr::read_volatile(address as *const i32);
+                    ::core::ptr::write_volatile(address as *mut i32, old.wrapping_add(value));
+                }
+            }
+            HERE => {
+                let address = self.mem.as_ptr() as usize + (self.here * 4);
+                s.push(address as i32)?;
+            }
+            COMMA => {
+                let value = s.pop()?;
+                if self.here == self.mem.len() {
+                    return Err(Error::DictionaryFull);
+                }
+                self.mem[self.here] = value as u32;
+                self.here += 1;
+            }
+            ALLOT => {
+                let cells = (s.pop()?.max(0) as usize + 3) / 4;
+                if self.here + cells > self.mem.len() {
+                    return Err(Error::DictionaryFull);
+                }
+                self.here += cells;
+            }
+            DOT => {
+                let a = s.pop()?;
+                if self.base == 16 {
+                    write!(out, "{:x} ", a).unwrap();
+                } else {
+                    write!(out, "{} ", a).unwrap();
+                }
+            }
+            U_DOT => {
+                let a = s.pop()? as u32;
+                if self.base == 16 {
+                    write!(out, "{:x} ", a).unwrap();
+                } else {
+                    write!(out, "{} ", a).unwrap();
+                }
+            }
+            DOT_S => {
+                write!(out, "<{}> ", s.depth()).unwrap();
+                for value in s.as_slice() {
+                    if self.base == 16 {
+                        write!(out, "{:x} ", value).unwrap();
+                    } else {
+                        write!(out, "{} ", value).unwrap();
+                    }
+                }
+            }
+            EMIT => {
+                let ch = s.pop()? as u8;
+                out.write_char(ch as char).unwrap();
+            }
+            CR => writeln!(out).unwrap(),
+            SPACE => out.write_char(' ').unwrap(),
+            HEX => self.base = 16,
+            DECIMAL => self.base = 10,
+            WORDS => {
+                let mut header = self.latest;
+                while header != 0 {
+                    let len = self.mem[header + 1] as usize;
+                    for i in 0..len {
+                        let word = self.mem[header + 2 + (i / 4)];
+                        out.write_char((word >> ((i % 4) * 8)) as u8 as char)
+                            .unwrap();
+                    }
+                    out.write_char(' ').unwrap();
+                    header = self.mem[header] as usize;
+                }
+                writeln!(out).unwrap();
+            }
+            n if n >= NATIVE => match self.natives.get((n - NATIVE) as usize) {
+                Some(&Some(native)) => native(s)?,
+                _ => return Err(Error::BadStructure),
+            },
+            _ => return Err(Error::CompileOnly),
+        }
+        Ok(())
+    }
+
+    /// Make a header, and a code field, for a new word.
+    fn create(&mut self, name: &str, code: u32) -> Result<(), Error> {
+        if name.is_empty() || name.len() > MAX_NAME {
+            return Err(Error::BadName);
+        }
+        let name_cells = (name.len() + 3) / 4;
+        if self.here + 3 + name_cells > self.mem.len() {
+            return Err(Error::DictionaryFull);
+        }
+        let header = self.here;
+        self.mem[header] = self.latest as u32;
+        self.mem[header + 1] = name.len() as u32;
+        for (i, chunk) in name.as_bytes().chunks(4).enumerate() {
+            let mut upper = [0u8; 4];
+            for (u, c) in upper.iter_mut().zip(chunk) {
+                *u = c.to_ascii_uppercase();
+            }
+            self.mem[header + 2 + i] = pack(&upper[0..chunk.len()]);
+        }
+        self.here = header + 2 + name_cells;
+        self.latest = header;
+        self.comma(code)
+    }
+
+    /// Look a word up, newest first. Gives its execution token.
+    fn find(&self, name: &str) -> Option<usize> {
+        let mut header = self.latest;
+        while header != 0 {
+            let len = self.mem[header + 1] as usize;
+            if len == name.len() {
+                let matches = name.bytes().enumerate().all(|(i, c)| {
+                    let stored = (self.mem[header + 2 + (i / 4)] >> ((i % 4) * 8)) as u8;
+                    stored == c.to_ascii_uppercase()
+                });
+                if matches {
+                    return Some(header + 2 + ((len + 3) / 4));
+                }
+            }
+            header = self.mem[header] as usize;
+        }
+        None
+    }
+
+    fn comma(&mut self, value: u32) -> Result<(), Error> {
+        if self.here == self.mem.len() {
+            return Err(Error::DictionaryFull);
+        }
+        self.mem[self.here] = value;
+        self.here += 1;
+        Ok(())
+    }
+
+    /// A dictionary cell, checking it's there - a bad `EXECUTE` or a
+    /// mangled definition could send us anywhere.
+    fn fetch(&self, cell: usize) -> Result<u32, Error> {
+        self.mem.get(cell).cloned().ok_or(Error::BadStructure)
+    }
+
+    /// The real address of a dictionary cell.
+    fn address(&self, cell: usize) -> usize {
+        self.mem.as_ptr() as usize + (cell * 4)
+    }
+
+    fn rpush(&mut self, value: u32) -> Result<(), Error> {
+        if self.rdepth == RSTACK_LEN {
+            return Err(Error::ReturnStack);
+        }
+        self.rstack[self.rdepth] = value;
+        self.rdepth += 1;
+        Ok(())
+    }
+
+    fn rpop(&mut self) -> Result<u32, Error> {
+        if self.rdepth == 0 {
+            return Err(Error::ReturnStack);
+        }
+        self.rdepth -= 1;
+        Ok(self.rstack[self.rdepth])
+    }
+}
+
+/// Forth's true is all ones.
+fn flag(value: bool) -> i32 {
+    if value {
+        -1
+    } else {
+        0
+    }
+}
+
+/// Up to four bytes into a cell, little-endian.
+fn pack(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .enumerate()
+        .fold(0, |word, (i, &b)| word | (u32::from(b) << (i * 8)))
+}
+
+/// Split off the next space-separated word. Gives an empty word at the end.
+fn next_word(text: &str) -> (&str, &str) {
+    let text = text.trim_left();
+    let end = text.find(char::is_whitespace).unwrap_or(text.len());
+    (&text[0..end], &text[end..])
+}
+
+fn parse_number(word: &str, base: u32) -> Option<i32> {
+    // Allow an explicit hex prefix, whatever the base
+    if word.starts_with('$') {
+        return u32::from_str_radix(&word[1..], 16).ok().map(|n| n as i32);
+    }
+    i32::from_str_radix(word, base)
+        .ok()
+        .or_else(|| u32::from_str_radix(word, base).ok().map(|n| n as i32))
+}
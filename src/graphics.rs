@@ -0,0 +1,191 @@
+//! A common set of drawing primitives, so demos can draw on whichever
+//! display is fitted.
+//!
+//! A display only has to provide `draw_point` - everything else has a
+//! default implementation built on it, which displays can override if they
+//! have a faster way of doing it (e.g. a DMA fill).
+
+use bresenham::Bresenham;
+use embedded_hal::blocking::spi;
+use embedded_hal::digital::OutputPin;
+use fb;
+use font;
+use pcd8544::{self, Pcd8544};
+
+/// A colour in RGB565 format. Monochrome displays treat anything other
+/// than black as 'on'.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Colour(pub u16);
+
+impl Colour {
+    pub const BLACK: Colour = Colour(0x0000);
+    pub const WHITE: Colour = Colour(0xFFFF);
+    pub const RED: Colour = Colour(0xF800);
+    pub const GREEN: Colour = Colour(0x07E0);
+    pub const BLUE: Colour = Colour(0x001F);
+    pub const YELLOW: Colour = Colour(0xFFE0);
+    pub const CYAN: Colour = Colour(0x07FF);
+    pub const MAGENTA: Colour = Colour(0xF81F);
+
+    /// Build a colour from 8-bit components.
+    pub fn from_rgb(red: u8, green: u8, blue: u8) -> Colour {
+        Colour(
+            ((u16::from(red) & 0xF8) << 8) | ((u16::from(green) & 0xFC) << 3)
+                | (u16::from(blue) >> 3),
+        )
+    }
+
+    /// Should a monochrome display light this pixel?
+    pub fn is_lit(&self) -> bool {
+        self.0 != 0
+    }
+}
+
+pub trait Canvas {
+    /// Width in pixels.
+    fn width(&self) -> usize;
+
+    /// Height in pixels.
+    fn height(&self) -> usize;
+
+    /// Set one pixel. Off-screen pixels must be ignored.
+    fn draw_point(&mut self, x: usize, y: usize, colour: Colour);
+
+    /// Fill a rectangle, clipped to the screen.
+    fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, colour: Colour) {
+        let x_end = (x + width).min(self.width());
+        let y_end = (y + height).min(self.height());
+        for py in y..y_end {
+            for px in x..x_end {
+                self.draw_point(px, py, colour);
+            }
+        }
+    }
+
+    /// Fill the whole screen.
+    fn clear(&mut self, colour: Colour) {
+        let (width, height) = (self.width(), self.height());
+        self.fill_rect(0, 0, width, height, colour);
+    }
+
+    /// Draw a line between two points. The ends may be off-screen.
+    fn draw_line(&mut self, start: (isize, isize), end: (isize, isize), colour: Colour) {
+        for (x, y) in Bresenham::new(start, end) {
+            if x >= 0 && y >= 0 {
+                self.draw_point(x as usize, y as usize, colour);
+            }
+        }
+        // Bresenham doesn't yield the final point
+        if end.0 >= 0 && end.1 >= 0 {
+            self.draw_point(end.0 as usize, end.1 as usize, colour);
+        }
+    }
+
+    /// Draw the outline of a rectangle.
+    fn draw_rect(&mut self, x: usize, y: usize, width: usize, height: usize, colour: Colour) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        self.fill_rect(x, y, width, 1, colour);
+        self.fill_rect(x, y + height - 1, width, 1, colour);
+        self.fill_rect(x, y, 1, height, colour);
+        self.fill_rect(x + width - 1, y, 1, height, colour);
+    }
+
+    /// Draw a character from the VGA font with its top-left corner at
+    /// (`x`, `y`).
+    fn draw_char(&mut self, x: usize, y: usize, ch: u8, fg: Colour, bg: Colour) {
+        for row in 0..font::HEIGHT {
+            for col in 0..font::WIDTH {
+                let colour = if font::pixel(ch, col, row) { fg } else { bg };
+                self.draw_point(x + col, y + row, colour);
+            }
+        }
+    }
+
+    /// Draw a string on one line, with no wrapping.
+    fn draw_str(&mut self, x: usize, y: usize, s: &str, fg: Colour, bg: Colour) {
+        for (i, ch) in s.bytes().enumerate() {
+            self.draw_char(x + (i * font::WIDTH), y, ch, fg, bg);
+        }
+    }
+
+    /// Draw a character with each font pixel as a `scale` x `scale` block,
+    /// for readouts you can see from across the room.
+    fn draw_char_scaled(
+        &mut self,
+        x: usize,
+        y: usize,
+        ch: u8,
+        scale: usize,
+        fg: Colour,
+        bg: Colour,
+    ) {
+        for row in 0..font::HEIGHT {
+            for col in 0..font::WIDTH {
+                let colour = if font::pixel(ch, col, row) { fg } else { bg };
+                self.fill_rect(x + (col * scale), y + (row * scale), scale, scale, colour);
+            }
+        }
+    }
+
+    /// Draw a string of scaled characters on one line, with no wrapping.
+    fn draw_str_scaled(
+        &mut self,
+        x: usize,
+        y: usize,
+        s: &str,
+        scale: usize,
+        fg: Colour,
+        bg: Colour,
+    ) {
+        for (i, ch) in s.bytes().enumerate() {
+            self.draw_char_scaled(x + (i * font::WIDTH * scale), y, ch, scale, fg, bg);
+        }
+    }
+}
+
+/// The VGA framebuffer is 400 x 300, as we double up the 800 x 600 mode's
+/// pixels in both directions.
+pub const VGA_WIDTH: usize = 400;
+
+/// See `VGA_WIDTH`.
+pub const VGA_HEIGHT: usize = 300;
+
+impl<T> Canvas for fb::FrameBuffer<T>
+where
+    T: fb::Hardware,
+{
+    fn width(&self) -> usize {
+        VGA_WIDTH
+    }
+
+    fn height(&self) -> usize {
+        VGA_HEIGHT
+    }
+
+    fn draw_point(&mut self, x: usize, y: usize, colour: Colour) {
+        if x < VGA_WIDTH && y < VGA_HEIGHT {
+            fb::FrameBuffer::draw_point(self, x, y, colour.is_lit());
+        }
+    }
+}
+
+impl<SPI, DC, CE, E> Canvas for Pcd8544<SPI, DC, CE>
+where
+    SPI: spi::Write<u8, Error = E>,
+    DC: OutputPin,
+    CE: OutputPin,
+{
+    fn width(&self) -> usize {
+        pcd8544::WIDTH
+    }
+
+    fn height(&self) -> usize {
+        pcd8544::HEIGHT
+    }
+
+    fn draw_point(&mut self, x: usize, y: usize, colour: Colour) {
+        self.set_pixel(x, y, colour.is_lit());
+    }
+}
@@ -0,0 +1,127 @@
+//! Extra outputs from a chain of 74HC595 shift registers on an SPI bus.
+//!
+//! SER goes to MOSI, SRCLK to SCK and RCLK to any GPIO (the 'latch'). Tie
+//! /OE low and /SRCLR high. Up to four chips (32 outputs) are supported.
+//!
+//! Each output can be borrowed as a `Pin`, which implements `OutputPin` so
+//! it can be handed to any driver that wants one. Every change is shifted
+//! out to the whole chain immediately.
+
+use core::cell::RefCell;
+use embedded_hal::blocking::spi;
+use embedded_hal::digital::OutputPin;
+
+/// The most chips we support in one chain.
+pub const MAX_CHIPS: usize = 4;
+
+struct Inner<SPI, LATCH> {
+    spi: SPI,
+    latch: LATCH,
+    chips: usize,
+    state: u32,
+}
+
+impl<SPI, LATCH, E> Inner<SPI, LATCH>
+where
+    SPI: spi::Write<u8, Error = E>,
+    LATCH: OutputPin,
+{
+    fn update(&mut self) -> Result<(), E> {
+        let mut bytes = [0u8; MAX_CHIPS];
+        // The last chip in the chain needs its byte clocked out first
+        for (i, byte) in bytes[0..self.chips].iter_mut().enumerate() {
+            let chip = self.chips - 1 - i;
+            *byte = (self.state >> (chip * 8)) as u8;
+        }
+        let result = self.spi.write(&bytes[0..self.chips]);
+        self.latch.set_high();
+        self.latch.set_low();
+        result
+    }
+}
+
+pub struct ShiftRegister<SPI, LATCH> {
+    inner: RefCell<Inner<SPI, LATCH>>,
+}
+
+impl<SPI, LATCH, E> ShiftRegister<SPI, LATCH>
+where
+    SPI: spi::Write<u8, Error = E>,
+    LATCH: OutputPin,
+{
+    /// Set up a chain of `chips` shift registers with every output low. The
+    /// bus should be SPI mode 0; the '595 is good for well over 10 MHz.
+    pub fn new(spi: SPI, mut latch: LATCH, chips: usize) -> Result<Self, E> {
+        assert!(chips > 0 && chips <= MAX_CHIPS);
+        latch.set_low();
+        let mut inner = Inner {
+            spi,
+            latch,
+            chips,
+            state: 0,
+        };
+        inner.update()?;
+        Ok(ShiftRegister {
+            inner: RefCell::new(inner),
+        })
+    }
+
+    /// How many outputs there are.
+    pub fn len(&self) -> usize {
+        self.inner.borrow().chips * 8
+    }
+
+    /// Set all the outputs at once. Bit 0 is QA on the first chip.
+    pub fn set_all(&self, state: u32) -> Result<(), E> {
+        let mut inner = self.inner.borrow_mut();
+        inner.state = state;
+        inner.update()
+    }
+
+    /// Read back what we last set the outputs to.
+    pub fn get_all(&self) -> u32 {
+        self.inner.borrow().state
+    }
+
+    /// Borrow one output as an `OutputPin`.
+    pub fn pin(&self, index: usize) -> Pin<SPI, LATCH> {
+        assert!(index < self.len());
+        Pin {
+            parent: &self.inner,
+            mask: 1 << index,
+        }
+    }
+}
+
+/// One output on the chain.
+pub struct Pin<'a, SPI: 'a, LATCH: 'a> {
+    parent: &'a RefCell<Inner<SPI, LATCH>>,
+    mask: u32,
+}
+
+impl<'a, SPI, LATCH, E> OutputPin for Pin<'a, SPI, LATCH>
+where
+    SPI: spi::Write<u8, Error = E>,
+    LATCH: OutputPin,
+{
+    fn is_high(&self) -> bool {
+        (self.parent.borrow().state & self.mask) != 0
+    }
+
+    fn is_low(&self) -> bool {
+        !self.is_high()
+    }
+
+    fn set_high(&mut self) {
+        let mut inner = self.parent.borrow_mut();
+        inner.state |= self.mask;
+        // OutputPin can't report errors
+        let _ = inner.update();
+    }
+
+    fn set_low(&mut self) {
+        let mut inner = self.parent.borrow_mut();
+        inner.state &= !self.mask;
+        let _ = inner.update();
+    }
+}
@@ -0,0 +1,309 @@
+//! Drives an HD44780 compatible character LCD in 4-bit mode.
+//!
+//! The four data lines (D4..D7) must be on PE0..PE3, because we need to flip
+//! them between input and output to read the busy flag and the HAL's pin
+//! types won't let us do that. RS, RW and E can be any output pins.
+//!
+//! The TM4C123's GPIOs (except PB0, PB1, PD4 and PD5) are 5V tolerant, so
+//! you can run the LCD at 5V and still read the busy flag.
+//!
+//! If the busy flag never clears - there's no LCD, or RW is tied to ground
+//! rather than wired to us - we give up on it after about 10ms and wait a
+//! fixed time before each write from then on, which is slower but never
+//! hangs.
+
+use config;
+use core::fmt;
+use cortex_m::asm;
+use embedded_hal::blocking::delay::DelayUs;
+use embedded_hal::digital::OutputPin;
+use hal::gpio::gpioe::{PE0, PE1, PE2, PE3};
+use hal::gpio::{Output, PushPull};
+
+/// The data pins, D4 through D7.
+pub type DataPins = (
+    PE0<Output<PushPull>>,
+    PE1<Output<PushPull>>,
+    PE2<Output<PushPull>>,
+    PE3<Output<PushPull>>,
+);
+
+/// The size of the display.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Geometry {
+    /// 16 columns, 2 rows
+    _16x2,
+    /// 20 columns, 4 rows
+    _20x4,
+}
+
+impl Geometry {
+    pub fn cols(&self) -> u8 {
+        match *self {
+            Geometry::_16x2 => 16,
+            Geometry::_20x4 => 20,
+        }
+    }
+
+    pub fn rows(&self) -> u8 {
+        match *self {
+            Geometry::_16x2 => 2,
+            Geometry::_20x4 => 4,
+        }
+    }
+}
+
+/// Where each row starts in display RAM.
+const ROW_OFFSETS: [u8; 4] = [0x00, 0x40, 0x14, 0x54];
+
+const CMD_CLEAR: u8 = 0x01;
+const CMD_ENTRY_MODE_INC: u8 = 0x06;
+const CMD_DISPLAY_ON: u8 = 0x0C;
+const CMD_FUNCTION_4BIT_2LINE: u8 = 0x28;
+const CMD_SET_CGRAM: u8 = 0x40;
+const CMD_SET_DDRAM: u8 = 0x80;
+
+/// Mask for PE0..PE3.
+const DATA_MASK: u32 = 0x0F;
+
+/// How many times to read the busy flag before giving up on it. Each read
+/// takes a couple of microseconds, so this is about 10ms.
+const BUSY_POLLS: u32 = 4000;
+
+/// How long to wait instead of polling, in microseconds. Clearing the
+/// display takes 1.52ms, and everything else 37us.
+const SLOW_COMMAND_US: u32 = 2000;
+const FAST_COMMAND_US: u32 = 50;
+
+pub struct Hd44780<RS, RW, E> {
+    _data: DataPins,
+    rs: RS,
+    rw: RW,
+    e: E,
+    geometry: Geometry,
+    col: u8,
+    row: u8,
+    /// The busy flag has worked so far
+    busy_flag: bool,
+    /// The last command was a slow one
+    slow: bool,
+}
+
+impl<RS, RW, E> Hd44780<RS, RW, E>
+where
+    RS: OutputPin,
+    RW: OutputPin,
+    E: OutputPin,
+{
+    /// Run the 4-bit initialisation sequence, then clear the display. After
+    /// this we poll the busy flag, so the delay is only needed here.
+    pub fn new<D>(
+        data: DataPins,
+        rs: RS,
+        rw: RW,
+        e: E,
+        delay: &mut D,
+        geometry: Geometry,
+    ) -> Self
+    where
+        D: DelayUs<u32>,
+    {
+        let mut lcd = Hd44780 {
+            _data: data,
+            rs,
+            rw,
+            e,
+            geometry,
+            col: 0,
+            row: 0,
+            busy_flag: true,
+            slow: false,
+        };
+        lcd.rs.set_low();
+        lcd.rw.set_low();
+        lcd.e.set_low();
+        // We can't poll the busy flag until the interface is in 4-bit mode,
+        // so this bit uses the delays from the datasheet.
+        delay.delay_us(40_000);
+        lcd.write_nibble(0x3);
+        delay.delay_us(4_100);
+        lcd.write_nibble(0x3);
+        delay.delay_us(100);
+        lcd.write_nibble(0x3);
+        delay.delay_us(100);
+        lcd.write_nibble(0x2);
+        delay.delay_us(100);
+        lcd.command(CMD_FUNCTION_4BIT_2LINE);
+        lcd.command(CMD_DISPLAY_ON);
+        lcd.command(CMD_ENTRY_MODE_INC);
+        lcd.clear();
+        lcd
+    }
+
+    pub fn geometry(&self) -> Geometry {
+        self.geometry
+    }
+
+    /// Blank the display and home the cursor.
+    pub fn clear(&mut self) {
+        self.command(CMD_CLEAR);
+        self.col = 0;
+        self.row = 0;
+    }
+
+    /// Move the cursor. Out of range positions are clamped.
+    pub fn set_position(&mut self, col: u8, row: u8) {
+        let col = col.min(self.geometry.cols() - 1);
+        let row = row.min(self.geometry.rows() - 1);
+        self.command(CMD_SET_DDRAM | (ROW_OFFSETS[row as usize] + col));
+        self.col = col;
+        self.row = row;
+    }
+
+    /// Upload one of the eight custom characters (codes 0..7). Each byte is
+    /// one row of the 5x8 glyph, with the pixels in the bottom five bits.
+    pub fn define_char(&mut self, code: u8, glyph: &[u8; 8]) {
+        self.command(CMD_SET_CGRAM | ((code & 0x07) << 3));
+        for row in glyph.iter() {
+            self.data(*row & 0x1F);
+        }
+        // Go back to display RAM where we were
+        let (col, row) = (self.col, self.row);
+        self.set_position(col, row);
+    }
+
+    /// Write a raw character code at the cursor, wrapping at the end of the
+    /// line.
+    pub fn write_byte(&mut self, byte: u8) {
+        if self.col >= self.geometry.cols() {
+            let row = (self.row + 1) % self.geometry.rows();
+            self.set_position(0, row);
+        }
+        self.data(byte);
+        self.col += 1;
+    }
+
+    /// Write a whole row, padding with spaces so it overwrites whatever was
+    /// there before.
+    pub fn write_row(&mut self, row: u8, text: &str) {
+        self.set_position(0, row);
+        let cols = self.geometry.cols() as usize;
+        for byte in text.bytes().chain(core::iter::repeat(b' ')).take(cols) {
+            self.data(byte);
+        }
+        self.col = self.geometry.cols();
+    }
+
+    fn command(&mut self, cmd: u8) {
+        self.wait_busy();
+        self.rs.set_low();
+        self.write_nibble(cmd >> 4);
+        self.write_nibble(cmd & 0x0F);
+        self.slow = cmd == CMD_CLEAR;
+    }
+
+    fn data(&mut self, byte: u8) {
+        self.wait_busy();
+        self.rs.set_high();
+        self.write_nibble(byte >> 4);
+        self.write_nibble(byte & 0x0F);
+        self.slow = false;
+    }
+
+    fn write_nibble(&mut self, nibble: u8) {
+        let gpio = config::Port::E.registers();
+        self.rw.set_low();
+        gpio.dir.modify(|r, w| unsafe { w.bits(r.bits() | DATA_MASK) });
+        gpio.data
+            .modify(|r, w| unsafe { w.bits((r.bits() & !DATA_MASK) | u32::from(nibble)) });
+        self.pulse_enable();
+    }
+
+    fn read_nibble(&mut self) -> u8 {
+        let gpio = config::Port::E.registers();
+        self.e.set_high();
+        short_delay();
+        let nibble = (gpio.data.read().bits() & DATA_MASK) as u8;
+        self.e.set_low();
+        short_delay();
+        nibble
+    }
+
+    /// Read the busy flag (D7 of the status byte) until it clears. If it
+    /// doesn't clear in time, stop trusting it and wait instead.
+    fn wait_busy(&mut self) {
+        if !self.busy_flag {
+            let us = if self.slow {
+                SLOW_COMMAND_US
+            } else {
+                FAST_COMMAND_US
+            };
+            delay_us(us);
+            return;
+        }
+        let gpio = config::Port::E.registers();
+        gpio.dir.modify(|r, w| unsafe { w.bits(r.bits() & !DATA_MASK) });
+        self.rs.set_low();
+        self.rw.set_high();
+        let mut polls = 0;
+        loop {
+            let high = self.read_nibble();
+            let _low = self.read_nibble();
+            if high & 0x08 == 0 {
+                break;
+            }
+            polls += 1;
+            if polls == BUSY_POLLS {
+                self.busy_flag = false;
+                break;
+            }
+        }
+        self.rw.set_low();
+    }
+
+    fn pulse_enable(&mut self) {
+        self.e.set_high();
+        short_delay();
+        self.e.set_low();
+        short_delay();
+    }
+}
+
+/// Comfortably more than the 450ns the HD44780 wants for an E pulse, at
+/// 80 MHz or 120 MHz.
+fn short_delay() {
+    for _ in 0..40 {
+        asm::nop();
+    }
+}
+
+/// At least `us` microseconds, give or take.
+fn delay_us(us: u32) {
+    for _ in 0..(us * 2) {
+        short_delay();
+    }
+}
+
+impl<RS, RW, E> fmt::Write for Hd44780<RS, RW, E>
+where
+    RS: OutputPin,
+    RW: OutputPin,
+    E: OutputPin,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            match byte {
+                b'\r' => {
+                    let row = self.row;
+                    self.set_position(0, row);
+                }
+                b'\n' => {
+                    let row = (self.row + 1) % self.geometry.rows();
+                    self.set_position(0, row);
+                }
+                _ => self.write_byte(byte),
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+//! Task heartbeats, supervised by the watchdog.
+//!
+//! Feeding the watchdog from the main loop only proves the main loop is
+//! going round. Here, each logical task gets a `Heartbeat` and must `beat`
+//! at least once per watchdog period. The watchdog interrupt checks that
+//! they all did, and only then feeds the watchdog. If one didn't, it's
+//! wedged: `check` says which, and stops feeding, so the watchdog resets
+//! the chip at the end of the next period.
+//!
+//! ``` ignore
+//! let console = heartbeat::register("console");
+//! heartbeat::start(p.WATCHDOG0, &clocks, 1000);
+//! loop {
+//!     console.beat();
+//!     ...
+//! }
+//!
+//! interrupt!(WATCHDOG0, watchdog_isr);
+//!
+//! fn watchdog_isr() {
+//!     if let Err(missed) = heartbeat::check() {
+//!         writeln!(Console, "Wedged: {}", missed).unwrap();
+//!     }
+//! }
+//! ```
+//!
+//! Anything at a higher interrupt priority than the watchdog (like the VGA
+//! timers) keeps running the whole time, so you get to say what went wrong
+//! on the screen before the reset.
+
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use cortex_m::interrupt::{self, Nr};
+use cortex_m::peripheral::NVIC;
+use hal::sysctl::Clocks;
+use pac::{Interrupt, WATCHDOG0};
+
+/// How many tasks we can watch.
+pub const MAX_TASKS: usize = 8;
+
+/// WDTCTL: interrupt at the first time-out
+const CTL_INTEN: u32 = 1 << 0;
+/// WDTCTL: reset at the second time-out
+const CTL_RESEN: u32 = 1 << 1;
+/// WDTLOCK: the magic unlock value
+const UNLOCK: u32 = 0x1ACC_E551;
+
+/// The names of the registered tasks.
+static mut NAMES: [&str; MAX_TASKS] = [""; MAX_TASKS];
+/// How many tasks there are.
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+/// A bit for each task that's checked in this period.
+static SEEN: AtomicUsize = AtomicUsize::new(0);
+
+/// Held by a task, to check in with.
+pub struct Heartbeat {
+    mask: usize,
+}
+
+/// The tasks that didn't check in. Displays as a list of their names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Missed(pub usize);
+
+/// Add a task. Do this before `start`, or it'll be counted as wedged
+/// before it's had a chance.
+pub fn register(name: &'static str) -> Heartbeat {
+    interrupt::free(|_| {
+        let id = COUNT.load(Ordering::Relaxed);
+        assert!(id < MAX_TASKS);
+        unsafe { NAMES[id] = name };
+        COUNT.store(id + 1, Ordering::Relaxed);
+        Heartbeat { mask: 1 << id }
+    })
+}
+
+/// Start the watchdog, with a period of `period_ms`. A wedged task is
+/// spotted within two periods, and the reset comes one period after that.
+///
+/// Enable `Interrupt::WATCHDOG0` in the NVIC after this. Once started, the
+/// watchdog can't be stopped.
+pub fn start(wdt: WATCHDOG0, clocks: &Clocks, period_ms: u32) {
+    let load = (clocks.sysclk.0 / 1000) * period_ms;
+    wdt.lock.write(|w| unsafe { w.bits(UNLOCK) });
+    wdt.load.write(|w| unsafe { w.bits(load) });
+    wdt.ctl.write(|w| unsafe { w.bits(CTL_INTEN | CTL_RESEN) });
+    // Writing anything else locks it again
+    wdt.lock.write(|w| unsafe { w.bits(0) });
+}
+
+/// Check every task beat this period, and if so feed the watchdog. Call
+/// this from the WATCHDOG0 interrupt handler. If a task didn't beat, this
+/// turns the interrupt off and leaves the watchdog to reset us.
+pub fn check() -> Result<(), Missed> {
+    let wdt = unsafe { &*WATCHDOG0::ptr() };
+    let all = (1 << COUNT.load(Ordering::Relaxed)) - 1;
+    let seen = SEEN.swap(0, Ordering::Relaxed);
+    if seen & all == all {
+        // Any write to ICR clears the interrupt and reloads the counter
+        wdt.icr.write(|w| unsafe { w.bits(0) });
+        Ok(())
+    } else {
+        // The interrupt stays pending until the reset, so mask it
+        let nr = Interrupt::WATCHDOG0.nr();
+        let nvic = unsafe { &*NVIC::ptr() };
+        unsafe { nvic.icer[usize::from(nr / 32)].write(1 << (nr % 32)) };
+        Err(Missed(all & !seen))
+    }
+}
+
+impl Heartbeat {
+    /// Check in. Cheap enough to call every time round a loop.
+    pub fn beat(&self) {
+        SEEN.fetch_or(self.mask, Ordering::Relaxed);
+    }
+}
+
+impl fmt::Display for Missed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let count = COUNT.load(Ordering::Relaxed);
+        let mut first = true;
+        for id in 0..count {
+            if self.0 & (1 << id) != 0 {
+                if !first {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", unsafe { NAMES[id] })?;
+                first = false;
+            }
+        }
+        Ok(())
+    }
+}
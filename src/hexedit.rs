@@ -0,0 +1,407 @@
+//! A full-screen hex editor, drawn with `tui`.
+//!
+//! It shows a page of `LINES` rows of eight bytes - the address, the bytes
+//! in hex and then as text - and moves a cursor around them with the arrow
+//! keys, Page Up, Page Down, Home and End. Tab swaps between the hex and
+//! text columns. Typing hex digits (or, over on the text side, any
+//! printable character) changes the byte under the cursor, straight away,
+//! if the memory lets us. Escape leaves.
+//!
+//! What gets edited is anything that's a `Memory`: the SRAM, the flash
+//! (which is read-only here - use `flash` to change it), the EEPROM and
+//! files on a FAT volume are provided. The editor only remembers the page
+//! on the screen, which it reads again after every key.
+
+use eeprom::{self, Eeprom};
+use fat::{BlockDevice, File, Volume};
+use flash;
+use graphics::Canvas;
+use keyboard::Key;
+use tui::{self, Attr, Lines, Rect, Response};
+
+/// Bytes on each line.
+const BYTES_PER_LINE: usize = 8;
+
+/// Lines on a page.
+pub const LINES: usize = 14;
+
+/// Bytes on a page.
+const PAGE: usize = BYTES_PER_LINE * LINES;
+
+/// Where the frame goes. Inside it there's a space, the address, two
+/// spaces, the hex, a space and the text.
+const FRAME: Rect = Rect {
+    col: 2,
+    row: 1,
+    width: 46,
+    height: LINES + 2,
+};
+
+/// The column of the first hex digit, and of the first character of text.
+const HEX_COL: usize = FRAME.col + 1 + 1 + 8 + 2;
+const TEXT_COL: usize = HEX_COL + (BYTES_PER_LINE * 3);
+
+/// The row under the frame, for help and complaints.
+const HELP_ROW: usize = FRAME.row + FRAME.height;
+
+const HELP: &str = "Arrows/PgUp/PgDn move, Tab swaps, Esc leaves";
+
+/// Something with bytes in it.
+pub trait Memory {
+    /// What to call it, in the frame.
+    fn name(&self) -> &str;
+
+    /// The address shown for offset 0.
+    fn start(&self) -> usize;
+
+    /// How many bytes there are.
+    fn size(&self) -> usize;
+
+    fn read(&mut self, offset: usize) -> u8;
+
+    /// Change a byte. Returns false if we can't.
+    fn write(&mut self, offset: usize, byte: u8) -> bool;
+}
+
+/// The 32 KiB of SRAM. Be careful what you change.
+pub struct Ram;
+
+impl Memory for Ram {
+    fn name(&self) -> &str {
+        "RAM"
+    }
+
+    fn start(&self) -> usize {
+        0x2000_0000
+    }
+
+    fn size(&self) -> usize {
+        32 * 1024
+    }
+
+    fn read(&mut self, offset: usize) -> u8 {
+        unsafe { ::core::ptr::read_volatile((self.start() + offset) as *const u8) }
+    }
+
+    fn write(&mut self, offset: usize, byte: u8) -> bool {
+        unsafe { ::core::ptr::write_volatile((self.start() + offset) as *mut u8, byte) };
+        true
+    }
+}
+
+/// The 256 KiB of flash, to look at.
+pub struct Rom;
+
+impl Memory for Rom {
+    fn name(&self) -> &str {
+        "Flash (read only)"
+    }
+
+    fn start(&self) -> usize {
+        0
+    }
+
+    fn size(&self) -> usize {
+        flash::FLASH_SIZE
+    }
+
+    fn read(&mut self, offset: usize) -> u8 {
+        let word = flash::read_word(offset & !3);
+        (word >> ((offset & 3) * 8)) as u8
+    }
+
+    fn write(&mut self, _offset: usize, _byte: u8) -> bool {
+        false
+    }
+}
+
+/// The 2 KiB of EEPROM, byte by byte. Writing a byte rewrites its word.
+pub struct EepromBytes<'a> {
+    eeprom: &'a mut Eeprom,
+}
+
+impl<'a> EepromBytes<'a> {
+    pub fn new(eeprom: &'a mut Eeprom) -> EepromBytes<'a> {
+        EepromBytes { eeprom }
+    }
+
+    fn read_word(&mut self, offset: usize) -> Option<u32> {
+        let mut word = [0u32];
+        self.eeprom.read(offset / 4, &mut word).ok()?;
+        Some(word[0])
+    }
+}
+
+impl<'a> Memory for EepromBytes<'a> {
+    fn name(&self) -> &str {
+        "EEPROM"
+    }
+
+    fn start(&self) -> usize {
+        0
+    }
+
+    fn size(&self) -> usize {
+        eeprom::WORDS * 4
+    }
+
+    fn read(&mut self, offset: usize) -> u8 {
+        let word = self.read_word(offset).unwrap_or(0);
+        (word >> ((offset & 3) * 8)) as u8
+    }
+
+    fn write(&mut self, offset: usize, byte: u8) -> bool {
+        let shift = (offset & 3) * 8;
+        match self.read_word(offset) {
+            Some(word) => {
+                let word = (word & !(0xFF << shift)) | (u32::from(byte) << shift);
+                self.eeprom.write(offset / 4, &[word]).is_ok()
+            }
+            None => false,
+        }
+    }
+}
+
+/// A file on a FAT volume. Bytes can be changed, but the file can't get
+/// any bigger. Each change goes straight onto the card.
+pub struct FileBytes<'a, D>
+where
+    D: BlockDevice + 'a,
+{
+    volume: &'a mut Volume<D>,
+    file: File,
+    name: &'a str,
+}
+
+impl<'a, D> FileBytes<'a, D>
+where
+    D: BlockDevice,
+{
+    /// `file` came from `volume`, and `name` is what to call it.
+    pub fn new(volume: &'a mut Volume<D>, file: File, name: &'a str) -> FileBytes<'a, D> {
+        FileBytes { volume, file, name }
+    }
+}
+
+impl<'a, D> Memory for FileBytes<'a, D>
+where
+    D: BlockDevice,
+{
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn start(&self) -> usize {
+        0
+    }
+
+    fn size(&self) -> usize {
+        self.file.size() as usize
+    }
+
+    fn read(&mut self, offset: usize) -> u8 {
+        let mut byte = [0u8];
+        self.file.seek(offset as u32);
+        match self.volume.read(&mut self.file, &mut byte) {
+            Ok(1) => byte[0],
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, offset: usize, byte: u8) -> bool {
+        if offset >= self.size() {
+            return false;
+        }
+        self.file.seek(offset as u32);
+        match self.volume.write(&mut self.file, &[byte]) {
+            Ok(()) => self.volume.sync(&mut self.file).is_ok(),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Where we are, and what's on the screen.
+pub struct HexEditor {
+    /// The offset of the first byte on the screen
+    top: usize,
+    /// The offset of the byte under the cursor
+    cursor: usize,
+    /// In the text column, rather than the hex
+    text: bool,
+    /// Half way through typing a byte in hex
+    low_nibble: bool,
+    /// The bytes on the screen
+    page: [u8; PAGE],
+    /// What went wrong with the last key, in place of the help
+    message: Option<&'static str>,
+}
+
+impl HexEditor {
+    /// Start with the cursor on `offset` (which `Memory::start` is added
+    /// to, for the address).
+    pub fn new(memory: &mut Memory, offset: usize) -> HexEditor {
+        let cursor = offset.min(memory.size().saturating_sub(1));
+        let mut editor = HexEditor {
+            top: cursor - (cursor % BYTES_PER_LINE),
+            cursor,
+            text: false,
+            low_nibble: false,
+            page: [0u8; PAGE],
+            message: None,
+        };
+        editor.load(memory);
+        editor
+    }
+
+    /// The offset of the byte under the cursor.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Handle a key. Escape gives `Response::Cancelled`, which means we're
+    /// done; redraw after anything else.
+    pub fn key(&mut self, memory: &mut Memory, key: Key) -> Response {
+        self.message = None;
+        let size = memory.size();
+        let last = size.saturating_sub(1);
+        let cursor = self.cursor;
+        let response = match key {
+            Key::Escape => return Response::Cancelled,
+            Key::Tab => {
+                self.text = !self.text;
+                self.low_nibble = false;
+                Response::Handled
+            }
+            Key::Left if cursor > 0 => self.move_to(cursor - 1),
+            Key::Right if cursor < last => self.move_to(cursor + 1),
+            Key::Up if cursor >= BYTES_PER_LINE => self.move_to(cursor - BYTES_PER_LINE),
+            Key::Down if cursor + BYTES_PER_LINE < size => {
+                self.move_to(cursor + BYTES_PER_LINE)
+            }
+            Key::PageUp => self.move_to(cursor.saturating_sub(PAGE)),
+            Key::PageDown => self.move_to((cursor + PAGE).min(last)),
+            Key::Home => self.move_to(0),
+            Key::End => self.move_to(last),
+            Key::Char(c) if self.text && c >= ' ' && c <= '~' => {
+                self.change(memory, c as u8, true)
+            }
+            Key::Char(c) if !self.text && c.is_digit(16) => {
+                let digit = c.to_digit(16).unwrap_or(0) as u8;
+                let old = memory.read(cursor);
+                if self.low_nibble {
+                    self.change(memory, (old & 0xF0) | digit, true)
+                } else {
+                    self.change(memory, (old & 0x0F) | (digit << 4), false)
+                }
+            }
+            _ => Response::Ignored,
+        };
+        self.load(memory);
+        response
+    }
+
+    /// Draw the lot.
+    pub fn draw(&self, canvas: &mut Canvas, memory: &Memory) {
+        tui::clear(canvas, FRAME.inside());
+        tui::frame(canvas, FRAME, &Lines::DOUBLE, Some(memory.name()));
+        for line in 0..LINES {
+            let row = FRAME.row + 1 + line;
+            let offset = self.top + (line * BYTES_PER_LINE);
+            if offset >= memory.size() {
+                break;
+            }
+            let address = memory.start() + offset;
+            for i in 0..8 {
+                let nibble = (address >> (28 - (i * 4))) as u8;
+                tui::put(canvas, FRAME.col + 2 + i, row, hex_digit(nibble), Attr::Normal);
+            }
+            for i in 0..BYTES_PER_LINE {
+                let index = offset - self.top + i;
+                if offset + i >= memory.size() {
+                    break;
+                }
+                let byte = self.page[index];
+                let under = offset + i == self.cursor;
+                let hex_attr = if under && !self.text {
+                    Attr::Highlight
+                } else {
+                    Attr::Normal
+                };
+                let text_attr = if under && self.text {
+                    Attr::Highlight
+                } else {
+                    Attr::Normal
+                };
+                let col = HEX_COL + (i * 3);
+                tui::put(canvas, col, row, hex_digit(byte >> 4), hex_attr);
+                // Show that half a byte's been typed by lighting just the
+                // half still to come
+                let low_attr = if under && self.low_nibble {
+                    Attr::Normal
+                } else {
+                    hex_attr
+                };
+                tui::put(canvas, col + 1, row, hex_digit(byte), low_attr);
+                let ch = match byte {
+                    0x20...0x7E => byte,
+                    _ => b'.',
+                };
+                tui::put(canvas, TEXT_COL + i, row, ch, text_attr);
+            }
+        }
+        tui::text(
+            canvas,
+            FRAME.col,
+            HELP_ROW,
+            FRAME.width,
+            self.message.unwrap_or(HELP),
+            Attr::Normal,
+        );
+    }
+
+    /// Put the cursor somewhere, scrolling to keep it on the screen.
+    fn move_to(&mut self, cursor: usize) -> Response {
+        self.cursor = cursor;
+        self.low_nibble = false;
+        if cursor < self.top {
+            self.top = cursor - (cursor % BYTES_PER_LINE);
+        } else if cursor >= self.top + PAGE {
+            let line = cursor - (cursor % BYTES_PER_LINE);
+            self.top = line + BYTES_PER_LINE - PAGE;
+        }
+        Response::Handled
+    }
+
+    /// Write the byte under the cursor, and move on if `done`.
+    fn change(&mut self, memory: &mut Memory, byte: u8, done: bool) -> Response {
+        if !memory.write(self.cursor, byte) {
+            self.low_nibble = false;
+            self.message = Some("Can't change that");
+            return Response::Ignored;
+        }
+        if done {
+            let next = (self.cursor + 1).min(memory.size().saturating_sub(1));
+            self.move_to(next);
+        } else {
+            self.low_nibble = true;
+        }
+        Response::Changed
+    }
+
+    /// Read the page on the screen.
+    fn load(&mut self, memory: &mut Memory) {
+        let size = memory.size();
+        for (i, byte) in self.page.iter_mut().enumerate() {
+            let offset = self.top + i;
+            *byte = if offset < size { memory.read(offset) } else { 0 };
+        }
+    }
+}
+
+/// The hex digit for the bottom four bits of `n`.
+fn hex_digit(n: u8) -> u8 {
+    match n & 0x0F {
+        n @ 0...9 => b'0' + n,
+        n => b'A' + n - 10,
+    }
+}
@@ -0,0 +1,99 @@
+//! The real-time clock in the hibernation module.
+//!
+//! The hibernation module runs from its own 32.768 kHz crystal, and from
+//! VBAT if there's a battery (on the LaunchPad VBAT is just tied to 3.3V),
+//! so the clock keeps going through a reset. It counts whole seconds in a
+//! 32-bit register and has a 15-bit sub-seconds counter underneath.
+//!
+//! A cheap watch crystal is only good to about 20 ppm, or nearly a minute a
+//! month. The trim register fixes that: once every 64 seconds the
+//! sub-seconds counter counts to the trim value instead of 0x7FFF, so that
+//! one second is stretched (trim above 0x7FFF, for a fast crystal) or
+//! squashed by a few ticks.
+//!
+//! Writes to the module's registers take a few 32 kHz clocks to go
+//! through, so each one waits for the previous one to finish.
+
+use pac::HIB;
+
+/// The rate the sub-seconds counter runs at.
+pub const TICKS_PER_SECOND: u32 = 32_768;
+
+/// The trim value that leaves the clock alone.
+pub const NOMINAL_TRIM: u16 = 0x7FFF;
+
+// HIBCTL
+const CTL_RTCEN: u32 = 1 << 0;
+const CTL_CLK32EN: u32 = 1 << 6;
+const CTL_WRC: u32 = 1 << 31;
+
+/// The bottom 15 bits of HIBRTCSS count up through each second.
+const RTCSS_MASK: u32 = 0x7FFF;
+
+pub struct Rtc {
+    hib: HIB,
+}
+
+impl Rtc {
+    /// Start the clock, unless it's running already (in which case we
+    /// leave the time and trim alone). The caller must power up the
+    /// module first (`sysctl::Domain::Hibernation`).
+    pub fn new(hib: HIB) -> Rtc {
+        let rtc = Rtc { hib };
+        if rtc.hib.ctl.read().bits() & CTL_RTCEN == 0 {
+            rtc.wait_for_write();
+            rtc.hib.ctl.write(|w| unsafe { w.bits(CTL_CLK32EN) });
+            rtc.wait_for_write();
+            rtc.hib
+                .ctl
+                .write(|w| unsafe { w.bits(CTL_CLK32EN | CTL_RTCEN) });
+            rtc.wait_for_write();
+        }
+        rtc
+    }
+
+    /// Give the peripheral back. The clock keeps running.
+    pub fn free(self) -> HIB {
+        self.hib
+    }
+
+    /// Whole seconds, since whenever you said it was zero.
+    pub fn seconds(&self) -> u32 {
+        self.hib.rtcc.read().bits()
+    }
+
+    /// Whole seconds and ticks (1/32768ths) into the current second.
+    pub fn now(&self) -> (u32, u16) {
+        // Read the seconds either side, in case the second rolls over in
+        // between
+        loop {
+            let seconds = self.hib.rtcc.read().bits();
+            let ticks = self.hib.rtcss.read().bits() & RTCSS_MASK;
+            if self.hib.rtcc.read().bits() == seconds {
+                return (seconds, ticks as u16);
+            }
+        }
+    }
+
+    /// Set the time. The sub-seconds counter starts again from zero.
+    pub fn set_seconds(&mut self, seconds: u32) {
+        self.wait_for_write();
+        self.hib.rtcld.write(|w| unsafe { w.bits(seconds) });
+        self.wait_for_write();
+    }
+
+    pub fn trim(&self) -> u16 {
+        self.hib.rtct.read().bits() as u16
+    }
+
+    /// Set the trim. Each step away from `NOMINAL_TRIM` is about 0.48 ppm.
+    pub fn set_trim(&mut self, trim: u16) {
+        self.wait_for_write();
+        self.hib.rtct.write(|w| unsafe { w.bits(u32::from(trim)) });
+        self.wait_for_write();
+    }
+
+    fn wait_for_write(&self) {
+        while self.hib.ctl.read().bits() & CTL_WRC == 0 {}
+    }
+}
@@ -0,0 +1,189 @@
+//! Blocking I2C master using the I2C peripherals.
+//!
+//! The caller is responsible for powering up the I2C peripheral and putting
+//! the pins into the right alternate function (AF3 on the TM4C123, AF2 on
+//! the TM4C1294). SDA must also be set to open drain, which the HAL's GPIO
+//! types can't do yet, so use `open_drain()` on it. SCL is driven by the
+//! peripheral and doesn't need it. `config::I2C0` does all that for I2C0.
+//! You will need pull-ups on both lines; most breakout boards have them.
+
+use cortex_m::asm;
+use embedded_hal::blocking::i2c::{Read, Write, WriteRead};
+use hal::sysctl::Clocks;
+use hal::time::Hertz;
+use config::GpioRegisters;
+use pac::{I2C0, I2C1, I2C2, I2C3};
+
+/// Something went wrong on the I2C bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// Nobody acknowledged the address
+    AddressNack,
+    /// The device stopped acknowledging data
+    DataNack,
+    /// Another master won the bus
+    ArbitrationLost,
+}
+
+/// MCR.MFE
+const MCR_MASTER: u32 = 1 << 4;
+
+// MCS bits, when written
+const MCS_RUN: u32 = 1 << 0;
+const MCS_START: u32 = 1 << 1;
+const MCS_STOP: u32 = 1 << 2;
+const MCS_ACK: u32 = 1 << 3;
+
+// MCS bits, when read
+const MCS_BUSY: u32 = 1 << 0;
+const MCS_ERROR: u32 = 1 << 1;
+const MCS_ADRACK: u32 = 1 << 2;
+const MCS_ARBLST: u32 = 1 << 4;
+
+/// An I2C peripheral in master mode.
+pub struct I2c<I2C> {
+    i2c: I2C,
+}
+
+/// Set the given pin on a GPIO port to open drain, for I2C SDA.
+pub fn open_drain(port: &GpioRegisters, pin: u8) {
+    port.odr
+        .modify(|r, w| unsafe { w.bits(r.bits() | (1 << pin)) });
+}
+
+macro_rules! hal {
+    ($($I2C:ident: $i2cX:ident,)+) => {
+        $(
+            impl I2c<$I2C> {
+                /// Configure the peripheral as a master at (approximately)
+                /// the given frequency - usually 100 kHz or 400 kHz.
+                pub fn $i2cX(i2c: $I2C, freq: Hertz, clocks: &Clocks) -> Self {
+                    i2c.mcr.write(|w| unsafe { w.bits(MCR_MASTER) });
+                    // SCL period = 2 * (1 + TPR) * 10 clocks
+                    let tpr = (clocks.sysclk.0 / (20 * freq.0)).max(1) - 1;
+                    i2c.mtpr.write(|w| unsafe { w.bits(tpr) });
+                    I2c { i2c }
+                }
+
+                /// Give the peripheral back.
+                pub fn free(self) -> $I2C {
+                    self.i2c
+                }
+
+                /// Kick off one byte's worth of bus activity and wait for it
+                /// to finish.
+                fn run(&mut self, command: u32) -> Result<(), Error> {
+                    self.i2c.mcs.write(|w| unsafe { w.bits(command) });
+                    // The busy bit takes a few cycles to go high
+                    asm::nop();
+                    asm::nop();
+                    asm::nop();
+                    asm::nop();
+                    let status = loop {
+                        let status = self.i2c.mcs.read().bits();
+                        if status & MCS_BUSY == 0 {
+                            break status;
+                        }
+                    };
+                    if status & MCS_ERROR == 0 {
+                        return Ok(());
+                    }
+                    if status & MCS_ARBLST != 0 {
+                        return Err(Error::ArbitrationLost);
+                    }
+                    // We still own the bus, so let go of it
+                    if command & MCS_STOP == 0 {
+                        self.i2c.mcs.write(|w| unsafe { w.bits(MCS_STOP) });
+                    }
+                    if status & MCS_ADRACK != 0 {
+                        Err(Error::AddressNack)
+                    } else {
+                        Err(Error::DataNack)
+                    }
+                }
+
+                fn write_bytes(&mut self, addr: u8, bytes: &[u8], stop: bool) -> Result<(), Error> {
+                    self.i2c.msa.write(|w| unsafe { w.bits(u32::from(addr) << 1) });
+                    if bytes.is_empty() {
+                        // Just probe the address
+                        return self.run(MCS_START | MCS_RUN | MCS_STOP);
+                    }
+                    let last = bytes.len() - 1;
+                    for (i, byte) in bytes.iter().enumerate() {
+                        self.i2c.mdr.write(|w| unsafe { w.bits(u32::from(*byte)) });
+                        let mut command = MCS_RUN;
+                        if i == 0 {
+                            command |= MCS_START;
+                        }
+                        if i == last && stop {
+                            command |= MCS_STOP;
+                        }
+                        self.run(command)?;
+                    }
+                    Ok(())
+                }
+
+                fn read_bytes(&mut self, addr: u8, buffer: &mut [u8]) -> Result<(), Error> {
+                    if buffer.is_empty() {
+                        return Ok(());
+                    }
+                    self.i2c.msa.write(|w| unsafe { w.bits((u32::from(addr) << 1) | 1) });
+                    let last = buffer.len() - 1;
+                    for (i, byte) in buffer.iter_mut().enumerate() {
+                        let mut command = MCS_RUN;
+                        if i == 0 {
+                            // This is a repeated start if we've just written
+                            command |= MCS_START;
+                        }
+                        if i == last {
+                            // NACK the last byte, then stop
+                            command |= MCS_STOP;
+                        } else {
+                            command |= MCS_ACK;
+                        }
+                        self.run(command)?;
+                        *byte = self.i2c.mdr.read().bits() as u8;
+                    }
+                    Ok(())
+                }
+            }
+
+            impl Write for I2c<$I2C> {
+                type Error = Error;
+
+                fn write(&mut self, addr: u8, bytes: &[u8]) -> Result<(), Error> {
+                    self.write_bytes(addr, bytes, true)
+                }
+            }
+
+            impl Read for I2c<$I2C> {
+                type Error = Error;
+
+                fn read(&mut self, addr: u8, buffer: &mut [u8]) -> Result<(), Error> {
+                    self.read_bytes(addr, buffer)
+                }
+            }
+
+            impl WriteRead for I2c<$I2C> {
+                type Error = Error;
+
+                fn write_read(
+                    &mut self,
+                    addr: u8,
+                    bytes: &[u8],
+                    buffer: &mut [u8],
+                ) -> Result<(), Error> {
+                    self.write_bytes(addr, bytes, false)?;
+                    self.read_bytes(addr, buffer)
+                }
+            }
+        )+
+    }
+}
+
+hal! {
+    I2C0: i2c0,
+    I2C1: i2c1,
+    I2C2: i2c2,
+    I2C3: i2c3,
+}
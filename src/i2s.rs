@@ -0,0 +1,201 @@
+//! 16-bit stereo audio to an I2S-style DAC, like the MAX98357, using SSI0
+//! and uDMA.
+//!
+//! The TM4C123 has no I2S peripheral, but an SSI in SPI mode gets most of
+//! the way there. SSI0 sends 16-bit words back to back - left, right, left,
+//! right - with its clock as the bit clock (BCLK): PA2 is BCLK and PA5 is
+//! the data (DIN). The SSI has no word select, so Timer3A makes one on PB2
+//! (LRCLK) as PWM, high for the left word and low for the right. Both run
+//! off the system clock with a fixed ratio between them and start in the
+//! same breath, so they stay in step. That's left-justified format, so
+//! wire the MAX98357B (or strap an A for left-justified).
+//!
+//! There are 32 bit clocks per sample, and the SSI divides the system clock
+//! by an even number, so not every sample rate is possible. At 80 MHz:
+//!
+//! | Asked for | Divider | Actual    | Error  |
+//! |-----------|---------|-----------|--------|
+//! | 8000 Hz   | 312     | 8013 Hz   | +0.16% |
+//! | 11025 Hz  | 226     | 11062 Hz  | +0.34% |
+//! | 16000 Hz  | 156     | 16026 Hz  | +0.16% |
+//! | 22050 Hz  | 114     | 21930 Hz  | -0.55% |
+//! | 32000 Hz  | 78      | 32051 Hz  | +0.16% |
+//! | 44100 Hz  | 56      | 44643 Hz  | +1.23% |
+//! | 48000 Hz  | 52      | 48077 Hz  | +0.16% |
+//!
+//! `start` picks the nearest and tells you what it got. uDMA channel 11
+//! feeds the SSI from two buffers in ping-pong mode, and the SSI0 interrupt
+//! (which only fires when a buffer runs out) calls the `Source` to refill
+//! one. The caller must power up SSI0, Timer3 and the uDMA controller, call
+//! `udma::init`, then `start`, and enable the SSI0 interrupt (below the VGA
+//! timers) and hook up the handler:
+//!
+//! ``` ignore
+//! interrupt!(SSI0, i2s::ssi0_isr);
+//! ```
+//!
+//! Timer3 is also what `dac` uses, so it's one or the other.
+
+use config::{Pin, Port};
+use cortex_m::interrupt;
+use hal::sysctl::Clocks;
+use pac::{self, SSI0, TIMER3};
+use udma;
+
+/// Stereo samples (a left and a right) in each of the two buffers.
+pub const BUFFER_FRAMES: usize = 256;
+
+/// Bit clocks per stereo sample.
+pub const BCLK_PER_SAMPLE: u32 = 32;
+
+/// Fills a buffer with the next samples, left then right.
+pub type Source = fn(&mut [i16]);
+
+const BCLK: Pin = Pin {
+    port: Port::A,
+    bit: 2,
+};
+const DIN: Pin = Pin {
+    port: Port::A,
+    bit: 5,
+};
+/// SSI0Clk and SSI0Tx's alternate function.
+const SSI_FUNCTION: u32 = 2;
+const LRCLK: Pin = Pin {
+    port: Port::B,
+    bit: 2,
+};
+/// T3CCP0's alternate function.
+const LRCLK_FUNCTION: u32 = 7;
+
+/// uDMA channel 11, encoding 0 is SSI0 TX.
+const DMA_CHANNEL: u8 = 11;
+
+/// The SSI's divider is CPSDVSR (which must be even) times (1 + SCR).
+const CPSDVSR: u32 = 2;
+const MAX_DIVIDER: u32 = CPSDVSR * 256;
+
+// GPTMCFG, GPTMTAMR
+const CFG_16_BIT: u32 = 0x4;
+/// Periodic and PWM
+const TAMR_PWM: u32 = 0x2 | (1 << 3);
+
+const TRANSFER: udma::Transfer = udma::Transfer {
+    size: udma::Size::HalfWord,
+    src_inc: udma::Increment::HalfWord,
+    dst_inc: udma::Increment::None,
+    arbitrate: udma::Arbitrate::_4,
+    mode: udma::Mode::PingPong,
+};
+
+static mut BUFFERS: [[i16; BUFFER_FRAMES * 2]; 2] = [[0; BUFFER_FRAMES * 2]; 2];
+static mut SOURCE: Option<Source> = None;
+
+/// The nearest divider we can do for `sample_rate_hz`.
+pub fn divider(sample_rate_hz: u32, clocks: &Clocks) -> u32 {
+    let per_step = BCLK_PER_SAMPLE * CPSDVSR * sample_rate_hz.max(1);
+    let steps = (clocks.sysclk.0 + (per_step / 2)) / per_step;
+    (steps * CPSDVSR).max(CPSDVSR).min(MAX_DIVIDER)
+}
+
+/// The sample rate a divider gives.
+pub fn actual_rate(divider: u32, clocks: &Clocks) -> u32 {
+    clocks.sysclk.0 / (BCLK_PER_SAMPLE * divider)
+}
+
+/// Point one of the two control structures at its buffer.
+fn queue(select: udma::Select) {
+    let half = match select {
+        udma::Select::Primary => 0,
+        udma::Select::Alternate => 1,
+    };
+    let ssi = unsafe { &*pac::SSI0::ptr() };
+    unsafe {
+        if let Some(source) = SOURCE {
+            source(&mut BUFFERS[half]);
+        }
+        udma::configure(
+            DMA_CHANNEL,
+            select,
+            &TRANSFER,
+            BUFFERS[half].as_ptr() as *const u8,
+            &ssi.dr as *const _ as *mut u8,
+            BUFFER_FRAMES * 2,
+        );
+    }
+}
+
+/// Set up the pins, SSI0, Timer3A and the uDMA channel, and start playing
+/// whatever `source` makes. Returns the sample rate we actually got.
+pub fn start(
+    ssi: SSI0,
+    timer: TIMER3,
+    sample_rate_hz: u32,
+    clocks: &Clocks,
+    source: Source,
+) -> u32 {
+    let divider = divider(sample_rate_hz, clocks);
+    BCLK.into_af(SSI_FUNCTION);
+    DIN.into_af(SSI_FUNCTION);
+    LRCLK.into_af(LRCLK_FUNCTION);
+
+    interrupt::free(|_| unsafe { SOURCE = Some(source) });
+
+    // 16-bit frames, back to back. With SPO and SPH set the data changes
+    // on the falling edge, for the DAC to read on the rising edge.
+    ssi.cr1.modify(|_, w| w.sse().clear_bit());
+    ssi.cpsr.write(|w| unsafe { w.cpsdvsr().bits(CPSDVSR as u8) });
+    ssi.cr0.write(|w| {
+        w.dss()._16();
+        w.frf().moto();
+        w.spo().set_bit();
+        w.sph().set_bit();
+        unsafe { w.scr().bits(((divider / CPSDVSR) - 1) as u8) };
+        w
+    });
+    ssi.cc.modify(|_, w| w.cs().syspll());
+    ssi.dmactl.write(|w| w.txdmae().set_bit());
+
+    // Timer3A as PWM, one cycle per stereo sample: high from the reload to
+    // half way, then low. Never more than 16 bits, so no prescaler.
+    let period = BCLK_PER_SAMPLE * divider;
+    timer.ctl.write(|w| unsafe { w.bits(0) });
+    timer.cfg.write(|w| unsafe { w.bits(CFG_16_BIT) });
+    timer.tamr.write(|w| unsafe { w.bits(TAMR_PWM) });
+    timer.tailr.write(|w| unsafe { w.bits(period - 1) });
+    timer.tapr.write(|w| unsafe { w.bits(0) });
+    timer.tamatchr.write(|w| unsafe { w.bits(period / 2) });
+    timer.tapmr.write(|w| unsafe { w.bits(0) });
+
+    // This fills the FIFO straight away, so the first word is ready to go
+    // the moment the SSI starts
+    udma::assign(DMA_CHANNEL, 0);
+    queue(udma::Select::Primary);
+    queue(udma::Select::Alternate);
+    udma::enable(DMA_CHANNEL);
+
+    interrupt::free(|_| {
+        timer.ctl.modify(|_, w| w.taen().set_bit());
+        ssi.cr1.modify(|_, w| w.sse().set_bit());
+    });
+
+    actual_rate(divider, clocks)
+}
+
+/// Refill whichever buffer just finished. The SSI0 handler.
+pub fn ssi0_isr() {
+    if !udma::take_interrupt(DMA_CHANNEL) {
+        return;
+    }
+    for &select in [udma::Select::Primary, udma::Select::Alternate].iter() {
+        if udma::mode(DMA_CHANNEL, select) == udma::Mode::Stop {
+            queue(select);
+        }
+    }
+    // If we were so late that both ran out, the channel will have stopped.
+    // So will the SSI, which means it's lost step with LRCLK and everything
+    // will sound awful. Bigger buffers, or a quicker `Source`, will help.
+    if !udma::is_enabled(DMA_CHANNEL) {
+        udma::enable(DMA_CHANNEL);
+    }
+}
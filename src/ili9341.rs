@@ -0,0 +1,214 @@
+//! Drives an ILI9341 320x240 colour TFT over SPI.
+//!
+//! Commands go out with ordinary blocking SPI writes, but pixel data is
+//! pushed with uDMA and 16-bit SSI frames so that large fills and blits
+//! don't need the CPU to feed the FIFO. We still wait for each transfer to
+//! finish, as the bus is shared with the command bytes.
+//!
+//! The caller must have called `udma::init()`.
+
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::blocking::spi;
+use embedded_hal::digital::OutputPin;
+use graphics::{Canvas, Colour};
+use spi::SpiDma;
+use udma;
+
+/// Width in landscape orientation.
+pub const WIDTH: usize = 320;
+
+/// Height in landscape orientation.
+pub const HEIGHT: usize = 240;
+
+const CMD_SWRESET: u8 = 0x01;
+const CMD_SLPOUT: u8 = 0x11;
+const CMD_DISPON: u8 = 0x29;
+const CMD_CASET: u8 = 0x2A;
+const CMD_PASET: u8 = 0x2B;
+const CMD_RAMWR: u8 = 0x2C;
+const CMD_MADCTL: u8 = 0x36;
+const CMD_PIXFMT: u8 = 0x3A;
+
+/// Row/column exchange plus BGR order, giving landscape mode.
+const MADCTL_LANDSCAPE: u8 = 0x28;
+
+/// 16 bits per pixel.
+const PIXFMT_16BIT: u8 = 0x55;
+
+pub struct Ili9341<SPI, DC, CS> {
+    spi: SPI,
+    dc: DC,
+    cs: CS,
+}
+
+impl<SPI, DC, CS, E> Ili9341<SPI, DC, CS>
+where
+    SPI: spi::Write<u8, Error = E> + SpiDma,
+    DC: OutputPin,
+    CS: OutputPin,
+{
+    /// Reset the panel and put it into 16-bit landscape mode. The bus should
+    /// be in SPI mode 0, at up to 20 MHz or so.
+    pub fn new<RST, D>(
+        spi: SPI,
+        dc: DC,
+        cs: CS,
+        rst: &mut RST,
+        delay: &mut D,
+    ) -> Result<Self, E>
+    where
+        RST: OutputPin,
+        D: DelayMs<u32>,
+    {
+        let mut tft = Ili9341 { spi, dc, cs };
+        let (channel, encoding) = tft.spi.tx_dma_channel();
+        udma::assign(channel, encoding);
+        tft.cs.set_high();
+        rst.set_low();
+        delay.delay_ms(10);
+        rst.set_high();
+        delay.delay_ms(120);
+        tft.command(CMD_SWRESET, &[])?;
+        delay.delay_ms(5);
+        tft.command(CMD_SLPOUT, &[])?;
+        delay.delay_ms(120);
+        tft.command(CMD_PIXFMT, &[PIXFMT_16BIT])?;
+        tft.command(CMD_MADCTL, &[MADCTL_LANDSCAPE])?;
+        tft.command(CMD_DISPON, &[])?;
+        Ok(tft)
+    }
+
+    /// Fill a rectangle with a single colour, using uDMA.
+    pub fn fill(
+        &mut self,
+        x: usize,
+        y: usize,
+        w: usize,
+        h: usize,
+        colour: Colour,
+    ) -> Result<(), E> {
+        if x >= WIDTH || y >= HEIGHT || w == 0 || h == 0 {
+            return Ok(());
+        }
+        let w = w.min(WIDTH - x);
+        let h = h.min(HEIGHT - y);
+        self.set_window(x, y, w, h)?;
+        let pixel = colour.0;
+        self.push_pixels(&pixel as *const u16, udma::Increment::None, w * h);
+        Ok(())
+    }
+
+    /// Copy a block of RGB565 pixels (row by row, `w` wide) to the screen,
+    /// using uDMA. The block must fit on the screen.
+    pub fn blit(&mut self, x: usize, y: usize, w: usize, pixels: &[u16]) -> Result<(), E> {
+        if w == 0 || pixels.is_empty() {
+            return Ok(());
+        }
+        let h = pixels.len() / w;
+        assert!(x + w <= WIDTH && y + h <= HEIGHT);
+        self.set_window(x, y, w, h)?;
+        self.push_pixels(pixels.as_ptr(), udma::Increment::HalfWord, w * h);
+        Ok(())
+    }
+
+    /// Set the region that subsequent pixel data fills.
+    fn set_window(&mut self, x: usize, y: usize, w: usize, h: usize) -> Result<(), E> {
+        let (x1, y1) = (x + w - 1, y + h - 1);
+        self.command(
+            CMD_CASET,
+            &[(x >> 8) as u8, x as u8, (x1 >> 8) as u8, x1 as u8],
+        )?;
+        self.command(
+            CMD_PASET,
+            &[(y >> 8) as u8, y as u8, (y1 >> 8) as u8, y1 as u8],
+        )?;
+        self.command(CMD_RAMWR, &[])
+    }
+
+    /// Send `count` 16-bit pixels. We must have just sent RAMWR.
+    fn push_pixels(&mut self, src: *const u16, inc: udma::Increment, count: usize) {
+        let (channel, _) = self.spi.tx_dma_channel();
+        let dst = self.spi.data_register();
+        let transfer = udma::Transfer {
+            size: udma::Size::HalfWord,
+            src_inc: inc,
+            dst_inc: udma::Increment::None,
+            arbitrate: udma::Arbitrate::_4,
+            mode: udma::Mode::Basic,
+        };
+        self.dc.set_high();
+        self.cs.set_low();
+        self.spi.set_16bit(true);
+        self.spi.set_tx_dma(true);
+        let mut done = 0;
+        while done < count {
+            let chunk = (count - done).min(udma::MAX_TRANSFER);
+            let chunk_src = match inc {
+                udma::Increment::None => src,
+                _ => unsafe { src.offset(done as isize) },
+            };
+            udma::configure(
+                channel,
+                udma::Select::Primary,
+                &transfer,
+                chunk_src as *const u8,
+                dst,
+                chunk,
+            );
+            udma::enable(channel);
+            while udma::is_enabled(channel) {}
+            done += chunk;
+        }
+        self.spi.wait_idle();
+        self.spi.set_tx_dma(false);
+        self.spi.set_16bit(false);
+        self.cs.set_high();
+    }
+
+    fn command(&mut self, cmd: u8, args: &[u8]) -> Result<(), E> {
+        self.cs.set_low();
+        self.dc.set_low();
+        let mut result = self.spi.write(&[cmd]);
+        if result.is_ok() && !args.is_empty() {
+            self.dc.set_high();
+            result = self.spi.write(args);
+        }
+        self.cs.set_high();
+        result
+    }
+
+    fn data(&mut self, bytes: &[u8]) -> Result<(), E> {
+        self.cs.set_low();
+        self.dc.set_high();
+        let result = self.spi.write(bytes);
+        self.cs.set_high();
+        result
+    }
+}
+
+impl<SPI, DC, CS, E> Canvas for Ili9341<SPI, DC, CS>
+where
+    SPI: spi::Write<u8, Error = E> + SpiDma,
+    DC: OutputPin,
+    CS: OutputPin,
+{
+    fn width(&self) -> usize {
+        WIDTH
+    }
+
+    fn height(&self) -> usize {
+        HEIGHT
+    }
+
+    fn draw_point(&mut self, x: usize, y: usize, colour: Colour) {
+        if x < WIDTH && y < HEIGHT {
+            if self.set_window(x, y, 1, 1).is_ok() {
+                let _ = self.data(&[(colour.0 >> 8) as u8, colour.0 as u8]);
+            }
+        }
+    }
+
+    fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, colour: Colour) {
+        let _ = self.fill(x, y, width, height, colour);
+    }
+}
@@ -0,0 +1,106 @@
+//! Where the bootloader keeps the application, and how it checks it.
+//!
+//! The flash is split up like this:
+//!
+//! * `0x00000` - the bootloader (up to 15 KiB)
+//! * `0x03C00` - the application's header (one page)
+//! * `0x04000` - the application (112 KiB)
+//! * `0x20000` - the staging area, where new images arrive (112 KiB)
+//!
+//! The TM4C1294 has 1 MiB of flash in 16 KiB pages, so there it's:
+//!
+//! * `0x00000` - the bootloader (up to 48 KiB)
+//! * `0x0C000` - the application's header (one page)
+//! * `0x10000` - the application (480 KiB)
+//! * `0x88000` - the staging area (480 KiB)
+//!
+//! A header is four little-endian words: `MAGIC`, the image's length, its
+//! CRC-32 and a zero. In the staging area the header is right in front of
+//! the image; for the application it has a page to itself, so the
+//! application can start on a nice round address.
+//!
+//! The TM4C123 has no CRC peripheral, so the check is done in software
+//! (`flash::crc32`), which takes around 50ms for a full slot at 80 MHz.
+//! Applications can check themselves at boot too, with
+//! `check_application` - if it fails, something has changed the flash
+//! since the bootloader last looked.
+
+use flash;
+
+/// Where the application's header lives.
+#[cfg(feature = "tm4c123")]
+pub const APP_HEADER: usize = 0x3C00;
+/// Where the application itself lives.
+#[cfg(feature = "tm4c123")]
+pub const APP_START: usize = 0x4000;
+/// Where new images (header and all) arrive.
+#[cfg(feature = "tm4c123")]
+pub const STAGING: usize = 0x2_0000;
+/// How big the application and staging slots are.
+#[cfg(feature = "tm4c123")]
+pub const SLOT_SIZE: usize = 0x1_C000;
+
+/// Where the application's header lives.
+#[cfg(feature = "tm4c129")]
+pub const APP_HEADER: usize = 0xC000;
+/// Where the application itself lives.
+#[cfg(feature = "tm4c129")]
+pub const APP_START: usize = 0x1_0000;
+/// Where new images (header and all) arrive.
+#[cfg(feature = "tm4c129")]
+pub const STAGING: usize = 0x8_8000;
+/// How big the application and staging slots are.
+#[cfg(feature = "tm4c129")]
+pub const SLOT_SIZE: usize = 0x7_8000;
+/// "BOOT"
+pub const MAGIC: u32 = 0x544F_4F42;
+/// How big a header is.
+pub const HEADER_LEN: usize = 16;
+
+/// What a header says.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub len: usize,
+    pub crc: u32,
+}
+
+/// Why an image isn't any good.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// There's no magic number, so no image
+    NoHeader,
+    /// The length doesn't fit in a slot
+    BadLength(usize),
+    /// The image isn't what the header says it should be
+    BadCrc { expected: u32, actual: u32 },
+}
+
+/// Check the header at `header` and the image at `start` it describes.
+pub fn check(header: usize, start: usize) -> Result<Header, Error> {
+    if flash::read_word(header) != MAGIC {
+        return Err(Error::NoHeader);
+    }
+    let len = flash::read_word(header + 4) as usize;
+    let crc = flash::read_word(header + 8);
+    if len == 0 || len > SLOT_SIZE - HEADER_LEN {
+        return Err(Error::BadLength(len));
+    }
+    let actual = flash::crc32(start..start + len);
+    if actual != crc {
+        return Err(Error::BadCrc {
+            expected: crc,
+            actual,
+        });
+    }
+    Ok(Header { len, crc })
+}
+
+/// Check the installed application.
+pub fn check_application() -> Result<Header, Error> {
+    check(APP_HEADER, APP_START)
+}
+
+/// Check the image in the staging area.
+pub fn check_staged() -> Result<Header, Error> {
+    check(STAGING, STAGING + HEADER_LEN)
+}
@@ -0,0 +1,308 @@
+//! Every sort of input, in one queue.
+//!
+//! A game shouldn't care whether it's played with a PS/2 keyboard, a
+//! terminal on the console, a joystick or a knob. Each driver has its own
+//! idea of an event, so this wraps them all up as an `InputEvent` and
+//! queues them in the order they happened. Feed the queue from whatever's
+//! attached, and take events out with `next`:
+//!
+//! ``` ignore
+//! let mut keys = input::Decoder::new();
+//! loop {
+//!     if let Ok(byte) = board.rx.read() {
+//!         keys.input(byte);
+//!     }
+//!     input::keyboard(&mut keyboard);
+//!     input::joystick();
+//!     input::mouse();
+//!     while let Some(event) = input::next() {
+//!         ...
+//!     }
+//! }
+//! ```
+//!
+//! The feeders are for the main loop, except `push`, which is safe from
+//! anywhere. `rotary` and `touch` sample the hardware, so call those every
+//! millisecond or so. A full queue drops new events.
+//!
+//! A terminal only sends characters, so `Decoder` turns its escape
+//! sequences (VT100 and xterm style) back into keys - but there's no
+//! telling when a key comes up, and Escape on its own is only known to be
+//! Escape when something else comes along, or `Decoder::timeout` is
+//! called.
+
+use capsense::CapSense;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use cortex_m::interrupt;
+use joystick;
+use keyboard::{Key, KeyEvent, Keyboard, Modifiers};
+use mouse;
+use rotary::{self, Rotary};
+
+/// Something happened, somewhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEvent {
+    /// A key went down, or is repeating
+    KeyDown(KeyEvent),
+    /// A key came up
+    KeyUp(KeyEvent),
+    Joystick(joystick::Event),
+    /// The mouse moved, or a button changed. Moves between calls to
+    /// `mouse` come out as one.
+    Mouse {
+        x: usize,
+        y: usize,
+        buttons: mouse::Buttons,
+    },
+    Rotary(rotary::Event),
+    /// A `capsense` pad was touched, or let go
+    Touch { pad: u8, touched: bool },
+}
+
+/// How many events we keep.
+const QUEUE_LEN: usize = 32;
+/// What's in it doesn't matter until `push` has written over it.
+const EMPTY: InputEvent = InputEvent::Rotary(rotary::Event::Press);
+static mut QUEUE: [InputEvent; QUEUE_LEN] = [EMPTY; QUEUE_LEN];
+/// Where `push` writes next. Only `push` moves it.
+static WRITE: AtomicUsize = AtomicUsize::new(0);
+/// Where `next` reads next. Only `next` moves it.
+static READ: AtomicUsize = AtomicUsize::new(0);
+
+/// What the mouse was doing at the last `mouse`.
+static mut MOUSE: Option<((usize, usize), mouse::Buttons)> = None;
+/// The pads touched at the last `touch`.
+static mut TOUCHED: u32 = 0;
+
+const ESC: u8 = 0x1B;
+const DEL: u8 = 0x7F;
+const BS: u8 = 0x08;
+
+/// Where we are in an escape sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    /// Just had an ESC
+    Escape,
+    /// In an `ESC [` sequence, with the number so far
+    Csi(u8),
+    /// Just had `ESC O`
+    Ss3,
+}
+
+/// Turns what a terminal sends into keys.
+pub struct Decoder {
+    state: State,
+    /// The last byte was a CR, so an LF after it isn't another Enter
+    after_cr: bool,
+}
+
+impl Decoder {
+    pub fn new() -> Decoder {
+        Decoder {
+            state: State::Ground,
+            after_cr: false,
+        }
+    }
+
+    /// Feed in a byte from the terminal, and queue up any key it finishes.
+    pub fn input(&mut self, byte: u8) {
+        let after_cr = self.after_cr;
+        self.after_cr = byte == b'\r';
+        let state = self.state;
+        match state {
+            State::Ground => self.ground(byte, after_cr),
+            State::Escape => match byte {
+                b'[' => self.state = State::Csi(0),
+                b'O' => self.state = State::Ss3,
+                _ => {
+                    // That was a real Escape
+                    self.state = State::Ground;
+                    push_key(Key::Escape, false);
+                    self.ground(byte, after_cr);
+                }
+            },
+            State::Csi(n) => match byte {
+                b'0'...b'9' => {
+                    let n = n.saturating_mul(10).saturating_add(byte - b'0');
+                    self.state = State::Csi(n);
+                }
+                // Modifiers (`1;5A`) we don't do, so start again
+                b';' => self.state = State::Csi(0),
+                b'~' => {
+                    self.state = State::Ground;
+                    if let Some(key) = tilde_key(n) {
+                        push_key(key, false);
+                    }
+                }
+                0x40...0x7E => {
+                    self.state = State::Ground;
+                    if let Some(key) = final_key(byte) {
+                        push_key(key, false);
+                    }
+                }
+                _ => {}
+            },
+            State::Ss3 => {
+                self.state = State::Ground;
+                let key = match byte {
+                    b'P'...b'S' => Some(Key::Function(byte - b'P' + 1)),
+                    _ => final_key(byte),
+                };
+                if let Some(key) = key {
+                    push_key(key, false);
+                }
+            }
+        }
+    }
+
+    /// Nothing's come in for a while (say 50ms), so an Escape on its own
+    /// really was the Escape key.
+    pub fn timeout(&mut self) {
+        if self.state == State::Escape {
+            self.state = State::Ground;
+            push_key(Key::Escape, false);
+        }
+    }
+
+    fn ground(&mut self, byte: u8, after_cr: bool) {
+        match byte {
+            ESC => self.state = State::Escape,
+            b'\r' => push_key(Key::Enter, false),
+            b'\n' if !after_cr => push_key(Key::Enter, false),
+            b'\n' => {}
+            BS | DEL => push_key(Key::Backspace, false),
+            b'\t' => push_key(Key::Tab, false),
+            0x01...0x1A => push_key(Key::Char(char::from(byte + b'a' - 1)), true),
+            0x20...0x7E => push_key(Key::Char(char::from(byte)), false),
+            _ => {}
+        }
+    }
+}
+
+/// The key for `ESC [ <n> ~`.
+fn tilde_key(n: u8) -> Option<Key> {
+    match n {
+        1 | 7 => Some(Key::Home),
+        2 => Some(Key::Insert),
+        3 => Some(Key::Delete),
+        4 | 8 => Some(Key::End),
+        5 => Some(Key::PageUp),
+        6 => Some(Key::PageDown),
+        11...15 => Some(Key::Function(n - 10)),
+        17...21 => Some(Key::Function(n - 11)),
+        23 | 24 => Some(Key::Function(n - 12)),
+        _ => None,
+    }
+}
+
+/// The key for `ESC [ <x>` or `ESC O <x>`.
+fn final_key(byte: u8) -> Option<Key> {
+    match byte {
+        b'A' => Some(Key::Up),
+        b'B' => Some(Key::Down),
+        b'C' => Some(Key::Right),
+        b'D' => Some(Key::Left),
+        b'H' => Some(Key::Home),
+        b'F' => Some(Key::End),
+        _ => None,
+    }
+}
+
+/// Queue a key from the terminal. It can't tell us much about modifiers.
+fn push_key(key: Key, ctrl: bool) {
+    let shift = match key {
+        Key::Char(c) => c.is_ascii_uppercase(),
+        _ => false,
+    };
+    push(InputEvent::KeyDown(KeyEvent {
+        key,
+        modifiers: Modifiers {
+            shift,
+            ctrl,
+            alt: false,
+            alt_gr: false,
+            caps_lock: false,
+            num_lock: false,
+        },
+        repeat: false,
+        released: false,
+    }));
+}
+
+/// Queue whatever the keyboard has for us, going down and coming up.
+pub fn keyboard(keyboard: &mut Keyboard) {
+    while let Some(event) = keyboard.poll_changes() {
+        push(if event.released {
+            InputEvent::KeyUp(event)
+        } else {
+            InputEvent::KeyDown(event)
+        });
+    }
+}
+
+/// Queue the `joystick` module's events, from either sort of joystick.
+pub fn joystick() {
+    while let Some(event) = joystick::next_event() {
+        push(InputEvent::Joystick(event));
+    }
+}
+
+/// Queue an event if the mouse has moved, or a button has changed, since
+/// last time.
+pub fn mouse() {
+    let now = (mouse::position(), mouse::buttons());
+    let was = unsafe { core::mem::replace(&mut MOUSE, Some(now)) };
+    if was != Some(now) && mouse::is_connected() {
+        let ((x, y), buttons) = now;
+        push(InputEvent::Mouse { x, y, buttons });
+    }
+}
+
+/// Poll the encoder, and queue what it says.
+pub fn rotary(rotary: &mut Rotary) {
+    if let Some(event) = rotary.poll() {
+        push(InputEvent::Rotary(event));
+    }
+}
+
+/// Measure the pads, and queue an event for each that's changed.
+pub fn touch(capsense: &mut CapSense) {
+    let now = capsense.update();
+    let was = unsafe { core::mem::replace(&mut TOUCHED, now) };
+    for pad in 0..capsense.count() {
+        let mask = 1 << pad;
+        if (was ^ now) & mask != 0 {
+            push(InputEvent::Touch {
+                pad: pad as u8,
+                touched: now & mask != 0,
+            });
+        }
+    }
+}
+
+/// Queue an event from anywhere else, or drop it if the queue's full.
+/// Safe to call from an interrupt.
+pub fn push(event: InputEvent) {
+    interrupt::free(|_| {
+        let read = READ.load(Ordering::Acquire);
+        let write = WRITE.load(Ordering::Relaxed);
+        let next = (write + 1) % QUEUE_LEN;
+        if next != read {
+            unsafe { QUEUE[write] = event };
+            WRITE.store(next, Ordering::Release);
+        }
+    });
+}
+
+/// The oldest event we haven't handed out.
+pub fn next() -> Option<InputEvent> {
+    let write = WRITE.load(Ordering::Acquire);
+    let read = READ.load(Ordering::Relaxed);
+    if read == write {
+        return None;
+    }
+    let event = unsafe { QUEUE[read] };
+    READ.store((read + 1) % QUEUE_LEN, Ordering::Release);
+    Some(event)
+}
@@ -0,0 +1,185 @@
+//! An Atari (or Sega Master System, or Commodore) DB9 digital joystick.
+//!
+//! Each direction and the fire button is a switch to ground, so wire them
+//! to five GPIOs and let the internal pull-ups do the rest:
+//!
+//! | DB9 pin | Switch | TM4C123 |
+//! |---------|--------|---------|
+//! | 1       | Up     | PE0     |
+//! | 2       | Down   | PE1     |
+//! | 3       | Left   | PE2     |
+//! | 4       | Right  | PE3     |
+//! | 6       | Fire   | PE4     |
+//! | 8       | Ground | GND     |
+//!
+//! Leave pin 7 (+5V, for Sega pads) alone. PE0..PE3 are also the `hd44780`'s
+//! data lines and `selftest`'s jumper.
+//!
+//! Switches bounce, so `sample` only believes a change once it has seen it
+//! `DEBOUNCE_SAMPLES` times running. Call it every millisecond or so, from
+//! a timer interrupt or a `scheduler` task. Each change goes in a queue for
+//! `next_event`, so a game that only looks once a frame still sees a quick
+//! tap of the fire button; `is_down` is there for things that want to know
+//! what's held right now. `analog_joystick` gives the same events from a
+//! thumbstick.
+
+use config::{Pin, Port};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// How many samples in a row a change must last.
+pub const DEBOUNCE_SAMPLES: u8 = 5;
+
+/// A direction, or the fire button.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    Up,
+    Down,
+    Left,
+    Right,
+    Fire,
+}
+
+impl Button {
+    pub const ALL: [Button; 5] = [
+        Button::Up,
+        Button::Down,
+        Button::Left,
+        Button::Right,
+        Button::Fire,
+    ];
+
+    /// This button's bit in `Buttons`.
+    pub fn mask(self) -> u8 {
+        1 << (self as u8)
+    }
+}
+
+/// Something changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Pressed(Button),
+    Released(Button),
+}
+
+/// Which buttons are down, one bit each (see `Button::mask`).
+pub type Buttons = u8;
+
+const PINS: [Pin; 5] = [
+    Pin {
+        port: Port::E,
+        bit: 0,
+    },
+    Pin {
+        port: Port::E,
+        bit: 1,
+    },
+    Pin {
+        port: Port::E,
+        bit: 2,
+    },
+    Pin {
+        port: Port::E,
+        bit: 3,
+    },
+    Pin {
+        port: Port::E,
+        bit: 4,
+    },
+];
+
+/// Events between `sample` and `next_event`.
+const QUEUE_LEN: usize = 16;
+static mut QUEUE: [Event; QUEUE_LEN] = [Event::Released(Button::Fire); QUEUE_LEN];
+/// Where `push` writes next. Only `push` moves it.
+static WRITE: AtomicUsize = AtomicUsize::new(0);
+/// Where `next_event` reads next. Only `next_event` moves it.
+static READ: AtomicUsize = AtomicUsize::new(0);
+
+/// What we've decided is down.
+static STABLE: AtomicUsize = AtomicUsize::new(0);
+/// What the pins said last time, and for how many samples.
+static mut LAST: Buttons = 0;
+static mut SAME_FOR: u8 = 0;
+
+/// Set up the pins.
+pub fn init() {
+    for pin in PINS.iter() {
+        pin.into_input();
+        let port = pin.port.registers();
+        port.pur
+            .modify(|r, w| unsafe { w.bits(r.bits() | (1 << pin.bit)) });
+    }
+}
+
+/// Read the pins, and queue an event for anything that's changed and
+/// stayed changed. Call this every millisecond or so.
+pub fn sample() {
+    let mut now = 0;
+    for (pin, button) in PINS.iter().zip(Button::ALL.iter()) {
+        // Active low
+        if !pin.is_high() {
+            now |= button.mask();
+        }
+    }
+    let settled = unsafe {
+        if now == LAST {
+            SAME_FOR = SAME_FOR.saturating_add(1);
+        } else {
+            LAST = now;
+            SAME_FOR = 1;
+        }
+        SAME_FOR == DEBOUNCE_SAMPLES
+    };
+    if settled {
+        set(now);
+    }
+}
+
+/// Change what's down to `now`, and queue an event for each difference.
+/// For drivers with their own idea of debouncing, like `analog_joystick`.
+pub fn set(now: Buttons) {
+    let was = STABLE.swap(usize::from(now), Ordering::Relaxed) as u8;
+    for &button in Button::ALL.iter() {
+        let mask = button.mask();
+        if (was ^ now) & mask != 0 {
+            push(if now & mask != 0 {
+                Event::Pressed(button)
+            } else {
+                Event::Released(button)
+            });
+        }
+    }
+}
+
+/// Is the button held down (after debouncing)?
+pub fn is_down(button: Button) -> bool {
+    buttons() & button.mask() != 0
+}
+
+/// Everything that's held down.
+pub fn buttons() -> Buttons {
+    STABLE.load(Ordering::Relaxed) as u8
+}
+
+/// The oldest change we haven't told anyone about.
+pub fn next_event() -> Option<Event> {
+    let write = WRITE.load(Ordering::Acquire);
+    let read = READ.load(Ordering::Relaxed);
+    if read == write {
+        return None;
+    }
+    let event = unsafe { QUEUE[read] };
+    READ.store((read + 1) % QUEUE_LEN, Ordering::Release);
+    Some(event)
+}
+
+/// Queue an event, or drop it if nobody's been listening.
+fn push(event: Event) {
+    let read = READ.load(Ordering::Acquire);
+    let write = WRITE.load(Ordering::Relaxed);
+    let next = (write + 1) % QUEUE_LEN;
+    if next != read {
+        unsafe { QUEUE[write] = event };
+        WRITE.store(next, Ordering::Release);
+    }
+}
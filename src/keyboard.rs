@@ -0,0 +1,653 @@
+//! A PS/2 keyboard, with US, UK and German layouts.
+//!
+//! The clock goes to PD2 and the data to PD3, with 4.7k pull-ups to the
+//! keyboard's 5V (see `ps2`). Those are two of the `dac`'s pins, so it's one
+//! or the other.
+//!
+//! Keyboards send 'scan code set 2': a byte when a key goes down, and 0xF0
+//! then the same byte when it comes up. Keys added since the PC/AT (the
+//! arrows, the right hand Ctrl and Alt, and so on) have an 0xE0 in front.
+//! A scan code says where the key is, not what's printed on it, so a
+//! `Layout` turns them into characters. We keep track of Shift, Ctrl, Alt
+//! and AltGr, and of Caps Lock and Num Lock, whose lights we look after.
+//! The German layout's dead keys (^, ´ and `) just type themselves.
+//!
+//! Hold a key down and the keyboard sends it over and over - typematic
+//! repeat. The `Settings` say how long it waits before it starts and how
+//! fast it goes, and live in the EEPROM. The keyboard can only wait 250,
+//! 500, 750 or 1000ms and do 2 to 30 characters a second, so you get the
+//! nearest it can do.
+//!
+//! The GPIOD interrupt queues up the bytes and `Keyboard::poll` makes sense
+//! of them. Call `poll` from the main loop, not an interrupt, as it
+//! sometimes has to talk to the keyboard. The caller must enable the GPIOD
+//! interrupt (below the VGA timers) before calling `init`, and hook up the
+//! handler:
+//!
+//! ``` ignore
+//! interrupt!(GPIOD, keyboard::gpiod_isr);
+//! ```
+
+use config::{Pin, Port};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use cortex_m::asm;
+use eeprom::{self, Eeprom};
+use hal::sysctl::Clocks;
+use ps2;
+
+/// Where the `Settings` go in the EEPROM. Two words.
+pub const EEPROM_ADDRESS: usize = eeprom::WORDS - 32;
+
+const CLOCK: Pin = Pin {
+    port: Port::D,
+    bit: 2,
+};
+const DATA: Pin = Pin {
+    port: Port::D,
+    bit: 3,
+};
+
+const MAGIC: u32 = 0x4B45_5942;
+
+// Commands, and what the keyboard says back
+const CMD_LEDS: u8 = 0xED;
+const CMD_TYPEMATIC: u8 = 0xF3;
+const ACK: u8 = 0xFA;
+
+// Scan code prefixes
+const EXTENDED: u8 = 0xE0;
+const RELEASE: u8 = 0xF0;
+/// Pause is E1 14 77 E1 F0 14 F0 77, and has no break code
+const PAUSE: u8 = 0xE1;
+const PAUSE_LEN: u8 = 8;
+
+// The lights, for `CMD_LEDS`
+const LED_NUM_LOCK: u8 = 1 << 1;
+const LED_CAPS_LOCK: u8 = 1 << 2;
+
+/// How long we wait for the keyboard to answer a command.
+const TIMEOUT_MS: u32 = 20;
+
+/// No character with AltGr.
+const NONE: char = '\0';
+
+/// Scan code, then the character on its own, with Shift and with AltGr.
+type Keys = [(u8, char, char, char)];
+
+const US: &Keys = &[
+    (0x0E, '`', '~', NONE),
+    (0x16, '1', '!', NONE),
+    (0x1E, '2', '@', NONE),
+    (0x26, '3', '#', NONE),
+    (0x25, '4', '$', NONE),
+    (0x2E, '5', '%', NONE),
+    (0x36, '6', '^', NONE),
+    (0x3D, '7', '&', NONE),
+    (0x3E, '8', '*', NONE),
+    (0x46, '9', '(', NONE),
+    (0x45, '0', ')', NONE),
+    (0x4E, '-', '_', NONE),
+    (0x55, '=', '+', NONE),
+    (0x15, 'q', 'Q', NONE),
+    (0x1D, 'w', 'W', NONE),
+    (0x24, 'e', 'E', NONE),
+    (0x2D, 'r', 'R', NONE),
+    (0x2C, 't', 'T', NONE),
+    (0x35, 'y', 'Y', NONE),
+    (0x3C, 'u', 'U', NONE),
+    (0x43, 'i', 'I', NONE),
+    (0x44, 'o', 'O', NONE),
+    (0x4D, 'p', 'P', NONE),
+    (0x54, '[', '{', NONE),
+    (0x5B, ']', '}', NONE),
+    (0x5D, '\\', '|', NONE),
+    (0x1C, 'a', 'A', NONE),
+    (0x1B, 's', 'S', NONE),
+    (0x23, 'd', 'D', NONE),
+    (0x2B, 'f', 'F', NONE),
+    (0x34, 'g', 'G', NONE),
+    (0x33, 'h', 'H', NONE),
+    (0x3B, 'j', 'J', NONE),
+    (0x42, 'k', 'K', NONE),
+    (0x4B, 'l', 'L', NONE),
+    (0x4C, ';', ':', NONE),
+    (0x52, '\'', '"', NONE),
+    (0x61, '\\', '|', NONE),
+    (0x1A, 'z', 'Z', NONE),
+    (0x22, 'x', 'X', NONE),
+    (0x21, 'c', 'C', NONE),
+    (0x2A, 'v', 'V', NONE),
+    (0x32, 'b', 'B', NONE),
+    (0x31, 'n', 'N', NONE),
+    (0x3A, 'm', 'M', NONE),
+    (0x41, ',', '<', NONE),
+    (0x49, '.', '>', NONE),
+    (0x4A, '/', '?', NONE),
+    (0x29, ' ', ' ', NONE),
+];
+
+/// Where the UK layout differs from the US one.
+const UK: &Keys = &[
+    (0x0E, '`', '¬', '¦'),
+    (0x1E, '2', '"', NONE),
+    (0x26, '3', '£', NONE),
+    (0x25, '4', '$', '€'),
+    (0x52, '\'', '@', NONE),
+    (0x5D, '#', '~', NONE),
+];
+
+/// Where the German layout differs from the US one.
+const DE: &Keys = &[
+    (0x0E, '^', '°', NONE),
+    (0x1E, '2', '"', '²'),
+    (0x26, '3', '§', '³'),
+    (0x36, '6', '&', NONE),
+    (0x3D, '7', '/', '{'),
+    (0x3E, '8', '(', '['),
+    (0x46, '9', ')', ']'),
+    (0x45, '0', '=', '}'),
+    (0x4E, 'ß', '?', '\\'),
+    (0x55, '´', '`', NONE),
+    (0x15, 'q', 'Q', '@'),
+    (0x24, 'e', 'E', '€'),
+    (0x35, 'z', 'Z', NONE),
+    (0x54, 'ü', 'Ü', NONE),
+    (0x5B, '+', '*', '~'),
+    (0x4C, 'ö', 'Ö', NONE),
+    (0x52, 'ä', 'Ä', NONE),
+    (0x5D, '#', '\'', NONE),
+    (0x61, '<', '>', '|'),
+    (0x1A, 'y', 'Y', NONE),
+    (0x3A, 'm', 'M', 'µ'),
+    (0x41, ',', ';', NONE),
+    (0x49, '.', ':', NONE),
+    (0x4A, '-', '_', NONE),
+];
+
+/// The keypad with Num Lock on. With it off, these are the same as the
+/// extended keys with the same codes, except 5 which does nothing.
+const KEYPAD: [(u8, char); 11] = [
+    (0x70, '0'),
+    (0x69, '1'),
+    (0x72, '2'),
+    (0x7A, '3'),
+    (0x6B, '4'),
+    (0x73, '5'),
+    (0x74, '6'),
+    (0x6C, '7'),
+    (0x75, '8'),
+    (0x7D, '9'),
+    (0x71, '.'),
+];
+
+/// F1 to F12.
+const FUNCTION_KEYS: [u8; 12] = [
+    0x05, 0x06, 0x04, 0x0C, 0x03, 0x0B, 0x83, 0x0A, 0x01, 0x09, 0x78, 0x07,
+];
+
+/// What's printed on the keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    Us,
+    Uk,
+    De,
+}
+
+impl Layout {
+    pub const ALL: [Layout; 3] = [Layout::Us, Layout::Uk, Layout::De];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Layout::Us => "us",
+            Layout::Uk => "uk",
+            Layout::De => "de",
+        }
+    }
+
+    /// The layout called `name` (as in `name()`).
+    pub fn from_name(name: &str) -> Option<Layout> {
+        Layout::ALL.iter().cloned().find(|l| l.name() == name)
+    }
+
+    /// The characters on a key.
+    fn keys(self, code: u8) -> Option<(char, char, char)> {
+        let differences: &Keys = match self {
+            Layout::Us => &[],
+            Layout::Uk => UK,
+            Layout::De => DE,
+        };
+        differences
+            .iter()
+            .chain(US.iter())
+            .find(|k| k.0 == code)
+            .map(|k| (k.1, k.2, k.3))
+    }
+}
+
+/// Is `c` a letter, for Caps Lock?
+fn is_letter(c: char) -> bool {
+    c.is_ascii_lowercase() || "äöü".contains(c)
+}
+
+/// How the keyboard repeats a held key, and what's printed on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+    pub layout: Layout,
+    /// How long a key has to be held before it repeats
+    pub delay_ms: u16,
+    /// Characters per second, once it does
+    pub rate_cps: u8,
+}
+
+impl Settings {
+    /// What a keyboard does when it's first switched on.
+    pub const DEFAULT: Settings = Settings {
+        layout: Layout::Us,
+        delay_ms: 500,
+        rate_cps: 11,
+    };
+
+    /// Read the settings from the EEPROM, or give the defaults if there
+    /// aren't any.
+    pub fn load(eeprom: &mut Eeprom) -> Settings {
+        let mut words = [0u32; 2];
+        if eeprom.read(EEPROM_ADDRESS, &mut words).is_err() || words[0] != MAGIC {
+            return Settings::DEFAULT;
+        }
+        let layout = match words[1] & 0xFF {
+            1 => Layout::Uk,
+            2 => Layout::De,
+            _ => Layout::Us,
+        };
+        Settings {
+            layout,
+            rate_cps: (words[1] >> 8) as u8,
+            delay_ms: (words[1] >> 16) as u16,
+        }
+    }
+
+    /// Write the settings to the EEPROM.
+    pub fn save(&self, eeprom: &mut Eeprom) -> Result<(), eeprom::Error> {
+        let packed = (self.layout as u32) | (u32::from(self.rate_cps) << 8)
+            | (u32::from(self.delay_ms) << 16);
+        eeprom.write(EEPROM_ADDRESS, &[MAGIC, packed])
+    }
+
+    /// `CMD_TYPEMATIC`'s argument: the delay in bits 6:5 and the rate in
+    /// bits 4:0.
+    fn typematic(&self) -> u8 {
+        let delay = ((u32::from(self.delay_ms) + 125) / 250).max(1).min(4) - 1;
+        let wanted = u32::from(self.rate_cps) * 10;
+        let rate = (0..32)
+            .min_by_key(|&r| {
+                // The period is (8 + bits 2:0) * 2^(bits 4:3) * 4.17ms
+                let period_us = (8 + (r & 7)) * (1 << (r >> 3)) * 4170;
+                let cps_x10 = 10_000_000 / period_us;
+                (cps_x10 as i32 - wanted as i32).abs()
+            })
+            .unwrap_or(0);
+        ((delay << 5) | rate) as u8
+    }
+}
+
+/// A key that isn't a modifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    /// Something that types, including the keypad with Num Lock on
+    Char(char),
+    Enter,
+    Backspace,
+    Tab,
+    Escape,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+    /// F1 is `Function(1)`
+    Function(u8),
+}
+
+/// Which modifiers were down, and which locks on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    /// Either Alt on a US keyboard, which doesn't have AltGr
+    pub alt: bool,
+    pub alt_gr: bool,
+    pub caps_lock: bool,
+    pub num_lock: bool,
+}
+
+/// A key going down, or repeating, or (from `poll_changes`) coming up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub key: Key,
+    pub modifiers: Modifiers,
+    /// Held down, rather than pressed
+    pub repeat: bool,
+    /// Let go of, rather than pressed
+    pub released: bool,
+}
+
+impl KeyEvent {
+    /// What a terminal would send, for the keys that have one. Ctrl and a
+    /// letter is a control code.
+    pub fn ascii(&self) -> Option<u8> {
+        match self.key {
+            Key::Char(c) if self.modifiers.ctrl && c.is_ascii_alphabetic() => {
+                Some((c as u8) & 0x1F)
+            }
+            Key::Char(c) if c.is_ascii() => Some(c as u8),
+            Key::Enter => Some(b'\r'),
+            Key::Backspace => Some(0x08),
+            Key::Tab => Some(b'\t'),
+            Key::Escape => Some(0x1B),
+            _ => None,
+        }
+    }
+}
+
+static mut HOST: Option<ps2::Host> = None;
+static ACKED: AtomicBool = AtomicBool::new(false);
+
+/// Bytes from the keyboard, between `gpiod_isr` and `poll`.
+const QUEUE_LEN: usize = 16;
+static mut QUEUE: [u8; QUEUE_LEN] = [0; QUEUE_LEN];
+/// Where the interrupt writes next. Only the interrupt moves it.
+static WRITE: AtomicUsize = AtomicUsize::new(0);
+/// Where `poll` reads next. Only `poll` moves it.
+static READ: AtomicUsize = AtomicUsize::new(0);
+
+/// Makes sense of what the keyboard sends.
+pub struct Keyboard {
+    settings: Settings,
+    cycles_per_ms: u32,
+    left_shift: bool,
+    right_shift: bool,
+    left_ctrl: bool,
+    right_ctrl: bool,
+    alt: bool,
+    alt_gr: bool,
+    caps_lock: bool,
+    num_lock: bool,
+    extended: bool,
+    release: bool,
+    /// Bytes of a Pause still to come
+    skip: u8,
+    /// The last key to go down, and whether it had `EXTENDED`
+    held: Option<(bool, u8)>,
+    connected: bool,
+}
+
+/// Set up the pins and send the keyboard its settings.
+pub fn init(clocks: &Clocks, settings: Settings) -> Keyboard {
+    unsafe { HOST = Some(ps2::Host::new(CLOCK, DATA, clocks)) };
+    let mut keyboard = Keyboard {
+        settings,
+        cycles_per_ms: clocks.sysclk.0 / 1000,
+        left_shift: false,
+        right_shift: false,
+        left_ctrl: false,
+        right_ctrl: false,
+        alt: false,
+        alt_gr: false,
+        caps_lock: false,
+        num_lock: false,
+        extended: false,
+        release: false,
+        skip: 0,
+        held: None,
+        connected: false,
+    };
+    keyboard.set_settings(settings);
+    keyboard.update_leds();
+    keyboard
+}
+
+impl Keyboard {
+    pub fn settings(&self) -> Settings {
+        self.settings
+    }
+
+    /// Change the layout and the repeat, and tell the keyboard. Doesn't
+    /// save them - see `Settings::save`.
+    pub fn set_settings(&mut self, settings: Settings) {
+        self.settings = settings;
+        let typematic = settings.typematic();
+        self.connected = self.command(&[CMD_TYPEMATIC, typematic]);
+    }
+
+    /// Did the keyboard answer the last thing we sent it?
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    pub fn modifiers(&self) -> Modifiers {
+        let us = self.settings.layout == Layout::Us;
+        Modifiers {
+            shift: self.left_shift || self.right_shift,
+            ctrl: self.left_ctrl || self.right_ctrl,
+            alt: self.alt || (us && self.alt_gr),
+            alt_gr: !us && self.alt_gr,
+            caps_lock: self.caps_lock,
+            num_lock: self.num_lock,
+        }
+    }
+
+    /// The next key pressed (or repeated), if there is one.
+    pub fn poll(&mut self) -> Option<KeyEvent> {
+        while let Some(event) = self.poll_changes() {
+            if !event.released {
+                return Some(event);
+            }
+        }
+        None
+    }
+
+    /// Like `poll`, but keys coming up too. A key coming up is named with
+    /// the modifiers as they are now, so shift then 'a' down, then shift up
+    /// and 'a' up, gives 'A' down and 'a' up.
+    pub fn poll_changes(&mut self) -> Option<KeyEvent> {
+        while let Some(byte) = pop() {
+            if let Some(event) = self.handle_byte(byte) {
+                return Some(event);
+            }
+        }
+        None
+    }
+
+    fn handle_byte(&mut self, byte: u8) -> Option<KeyEvent> {
+        if self.skip > 0 {
+            self.skip -= 1;
+            return None;
+        }
+        match byte {
+            EXTENDED => {
+                self.extended = true;
+                return None;
+            }
+            RELEASE => {
+                self.release = true;
+                return None;
+            }
+            PAUSE => {
+                self.skip = PAUSE_LEN - 1;
+                return None;
+            }
+            _ => {}
+        }
+        let extended = self.extended;
+        let release = self.release;
+        self.extended = false;
+        self.release = false;
+
+        let this = (extended, byte);
+        let repeat = self.held == Some(this);
+        if release {
+            if repeat {
+                self.held = None;
+            }
+        } else {
+            self.held = Some(this);
+        }
+
+        let down = !release;
+        match this {
+            // Print Screen and friends send a pretend Shift
+            (true, 0x12) | (true, 0x59) => {}
+            (false, 0x12) => self.left_shift = down,
+            (false, 0x59) => self.right_shift = down,
+            (false, 0x14) => self.left_ctrl = down,
+            (true, 0x14) => self.right_ctrl = down,
+            (false, 0x11) => self.alt = down,
+            (true, 0x11) => self.alt_gr = down,
+            (false, 0x58) if down && !repeat => {
+                self.caps_lock = !self.caps_lock;
+                self.update_leds();
+            }
+            (false, 0x77) if down && !repeat => {
+                self.num_lock = !self.num_lock;
+                self.update_leds();
+            }
+            _ => {
+                let key = self.key(extended, byte)?;
+                return Some(KeyEvent {
+                    key,
+                    modifiers: self.modifiers(),
+                    repeat: down && repeat,
+                    released: release,
+                });
+            }
+        }
+        None
+    }
+
+    /// What a scan code means, with the current modifiers.
+    fn key(&self, extended: bool, code: u8) -> Option<Key> {
+        if extended {
+            return match code {
+                0x75 => Some(Key::Up),
+                0x72 => Some(Key::Down),
+                0x6B => Some(Key::Left),
+                0x74 => Some(Key::Right),
+                0x6C => Some(Key::Home),
+                0x69 => Some(Key::End),
+                0x7D => Some(Key::PageUp),
+                0x7A => Some(Key::PageDown),
+                0x70 => Some(Key::Insert),
+                0x71 => Some(Key::Delete),
+                0x5A => Some(Key::Enter),
+                0x4A => Some(Key::Char('/')),
+                _ => None,
+            };
+        }
+        if let Some(&(_, c)) = KEYPAD.iter().find(|k| k.0 == code) {
+            return if self.num_lock {
+                Some(Key::Char(c))
+            } else {
+                self.key(true, code)
+            };
+        }
+        if let Some(n) = FUNCTION_KEYS.iter().position(|&f| f == code) {
+            return Some(Key::Function(n as u8 + 1));
+        }
+        match code {
+            0x5A => Some(Key::Enter),
+            0x66 => Some(Key::Backspace),
+            0x0D => Some(Key::Tab),
+            0x76 => Some(Key::Escape),
+            0x7C => Some(Key::Char('*')),
+            0x7B => Some(Key::Char('-')),
+            0x79 => Some(Key::Char('+')),
+            _ => {
+                let modifiers = self.modifiers();
+                let (normal, shifted, alt_gr) = self.settings.layout.keys(code)?;
+                if modifiers.alt_gr {
+                    return if alt_gr == NONE {
+                        None
+                    } else {
+                        Some(Key::Char(alt_gr))
+                    };
+                }
+                let shift = modifiers.shift != (self.caps_lock && is_letter(normal));
+                Some(Key::Char(if shift { shifted } else { normal }))
+            }
+        }
+    }
+
+    fn update_leds(&mut self) {
+        let mut leds = 0;
+        if self.num_lock {
+            leds |= LED_NUM_LOCK;
+        }
+        if self.caps_lock {
+            leds |= LED_CAPS_LOCK;
+        }
+        self.connected = self.command(&[CMD_LEDS, leds]);
+    }
+
+    /// Send a command, and its arguments, waiting for the keyboard to
+    /// acknowledge each byte. False if it doesn't.
+    fn command(&mut self, bytes: &[u8]) -> bool {
+        for &byte in bytes {
+            ACKED.store(false, Ordering::Relaxed);
+            match unsafe { HOST.as_mut() } {
+                Some(host) => host.send(byte),
+                None => return false,
+            }
+            let mut waited_ms = 0;
+            while !ACKED.load(Ordering::Relaxed) {
+                if waited_ms == TIMEOUT_MS {
+                    return false;
+                }
+                asm::delay(self.cycles_per_ms);
+                waited_ms += 1;
+            }
+        }
+        true
+    }
+}
+
+/// The next byte from the keyboard.
+fn pop() -> Option<u8> {
+    let write = WRITE.load(Ordering::Acquire);
+    let read = READ.load(Ordering::Relaxed);
+    if read == write {
+        return None;
+    }
+    let byte = unsafe { QUEUE[read] };
+    READ.store((read + 1) % QUEUE_LEN, Ordering::Release);
+    Some(byte)
+}
+
+/// Queue a byte for `poll`, or drop it if the queue's full.
+fn push(byte: u8) {
+    let read = READ.load(Ordering::Acquire);
+    let write = WRITE.load(Ordering::Relaxed);
+    let next = (write + 1) % QUEUE_LEN;
+    if next != read {
+        unsafe { QUEUE[write] = byte };
+        WRITE.store(next, Ordering::Release);
+    }
+}
+
+/// Clock in a bit, and queue it up if it finishes a byte. The GPIOD
+/// handler.
+pub fn gpiod_isr() {
+    let byte = match unsafe { HOST.as_mut() } {
+        Some(host) if host.take_interrupt() => host.clock_falling(),
+        _ => None,
+    };
+    match byte {
+        Some(ACK) => ACKED.store(true, Ordering::Relaxed),
+        Some(byte) => push(byte),
+        None => {}
+    }
+}
@@ -2,12 +2,11 @@
 //!
 //! # Dependencies
 //!
-//! - Nightly Rust toolchain: `rustup default nightly`
+//! - Rust 1.30 or newer, with the target: `rustup target add thumbv7em-none-eabihf`
 //! - ARM linker: `sudo apt-get install binutils-arm-none-eabi` (on Ubuntu)
 //! - Cargo `clone` subcommand: `cargo install cargo-clone`
 //! - GDB: `sudo apt-get install gdb-arm-none-eabi` (on Ubuntu)
 //! - OpenOCD: `sudo apt-get install OpenOCD` (on Ubuntu)
-//! - Xargo: `cargo install xargo`
 //! - [Optional] Cargo `add` subcommand: `cargo install cargo-edit`
 //!
 //! # Usage
@@ -75,8 +74,7 @@
 //! 7) Build the application
 //!
 //! ``` text
-//! $ # NOTE this command requires `arm-none-eabi-ld` to be in $PATH
-//! $ xargo build --release
+//! $ cargo build --release
 //!
 //! $ # sanity check
 //! $ arm-none-eabi-readelf -A target/thumbv7em-none-eabihf/release/demo
@@ -113,9 +111,8 @@
 //! $ arm-none-eabi-gdb target/thumbv7em-none-eabihf/release/demo
 //! ```
 //!
-//! **NOTE** As of nightly-2017-05-14 or so and cortex-m-quickstart v0.1.6 you can simply run `xargo
-//! run` or `xargo run --example $example` to build the program, *and* immediately start a debug
-//! session. IOW, it lets you omit the `arm-none-eabi-gdb` command.
+//! **NOTE** You can simply run `cargo run` or `cargo run --example $example` to build the program,
+//! *and* immediately start a debug session. IOW, it lets you omit the `arm-none-eabi-gdb` command.
 //!
 //! ``` text
 //! $ cargo run --example hello
@@ -151,7 +148,7 @@
 //! Error message:
 //!
 //! ``` text
-//! $ xargo build
+//! $ cargo build
 //! Compiling demo v0.1.0 (file:///home/japaric/tmp/demo)
 //! error: linking with `arm-none-eabi-ld` failed: exit code: 1
 //! |
@@ -172,7 +169,7 @@
 //! Error message:
 //!
 //! ``` text
-//! $ xargo build
+//! $ cargo build
 //! (..)
 //! Compiling cortex-m-semihosting v0.1.3
 //! error[E0463]: can't find crate for `std`
@@ -181,8 +178,8 @@
 //! ```
 //!
 //! Solution: Set a default build target in the `.cargo/config` file
-//! (see [Usage] section), or call Xargo with `--target` flag:
-//! `xargo build --target thumbv7em-none-eabi`.
+//! (see [Usage] section), or call Cargo with `--target` flag:
+//! `cargo build --target thumbv7em-none-eabi`.
 //!
 //! ## Overwrote the original `.cargo/config` file
 //!
@@ -229,13 +226,13 @@
 //! `/usr/share/openocd/scripts` directory (exact location varies per
 //! distribution / OS) for a list of scripts that can be used.
 //!
-//! ## Used Cargo instead of Xargo
+//! ## Didn't install the target
 //!
 //! Error message:
 //!
 //! ``` text
 //! $ cargo build
-//! Compiling cortex-m-rt v0.2.0
+//! Compiling cortex-m-rt v0.5.1
 //! error[E0463]: can't find crate for `core`
 //! |
 //! = note: the `thumbv7em-none-eabihf` target may not be installed
@@ -243,20 +240,58 @@
 //! error: aborting due to previous error
 //! ```
 //!
-//! Solution: Use `xargo build`.
+//! Solution: `rustup target add thumbv7em-none-eabihf`.
+//!
+//! ## Built a nightly-only example on stable
+//!
+//! Error message:
+//!
+//! ``` text
+//! $ cargo build --example monitor
+//! error: target `monitor` requires the features: `nightly`
+//! ```
+//!
+//! Solution: A few examples (and `demo::executor`) need unstable features. Build them with a
+//! nightly toolchain and `--features nightly`. The ones with a heap want `--features heap` and
+//! `rtfm_vga` wants `--features rtfm`, both of which turn on `nightly` too. The `messages` example
+//! needs serde and postcard, which come with `--features messages`.
+//!
+//! ## Built with both `nightly` and `defmt`
+//!
+//! Error message:
+//!
+//! ``` text
+//! error: `nightly` and `defmt` need different compilers - build with one or the other
+//! ```
+//!
+//! Solution: Pick one. `--features defmt` (see `demo::defmt_log`) needs stable Rust 1.56 or
+//! later, but the `nightly` examples use the generator and `asm!` syntax of the 2018 nightlies,
+//! which newer compilers reject. Build `defmt` with a current stable toolchain, and `nightly`
+//! (or `heap`, or `rtfm`) with the nightly the examples were written against.
 //!
-//! ## Used the stable toolchain
+//! ## Built a VGA example for the TM4C1294
 //!
 //! Error message:
 //!
 //! ``` text
-//! $ xargo build
-//! error: failed to run `rustc` to learn about target-specific information
+//! $ cargo build --no-default-features --features tm4c129 --example hello_vga
+//! error: target `hello_vga` requires the features: `tm4c123`
+//! ```
 //!
-//! To learn more, run the command again with --verbose.
+//! Solution: The VGA output (and a few other examples - see `demo::board`) needs pins that the
+//! TM4C1294 doesn't have, or has somewhere else. Those are TM4C123 LaunchPad only.
+//!
+//! ## Built a LaunchPad example for the STM32F4 Discovery
+//!
+//! Error message:
+//!
+//! ``` text
+//! $ cargo build --no-default-features --features stm32f407 --example hello
+//! error: target `hello` requires the features: `tm4c`
 //! ```
 //!
-//! Solution: Switch to the nightly toolchain with `rustup default nightly`.
+//! Solution: Only the VGA output (`demo::vga_stm32f4`) has been ported to the STM32F4, along with
+//! the modules that don't touch the hardware. Try `--example stm32f4_vga`.
 //!
 //! ## Used `gdb` instead of `arm-none-eabi-gdb`
 //!
@@ -288,7 +323,7 @@
 //! Error message:
 //!
 //! ``` text
-//! $ xargo run [--example ..]
+//! $ cargo run [--example ..]
 //!
 //! Reading symbols from target/thumbv7em-none-eabihf/debug/cortex-m-quickstart...done.
 //! cortex_m_rt::reset_handler ()
@@ -300,13 +335,13 @@
 //! ```
 //!
 //! Note that when you reach this point OpenOCD will become unresponsive and you'll have to kill it
-//! and start a new OpenOCD process before you can invoke `xargo run` / start GDB.
+//! and start a new OpenOCD process before you can invoke `cargo run` / start GDB.
 //!
 //! Cause: You uncommented the `monitor tpiu ..` line in `.gdbinit` and are using a named pipe to
 //! receive the ITM data (i.e. you ran `mkfifo itm.fifo`). This error occurs when `itmdump -f
 //! itm.fifo` (or equivalent, e.g. `cat itm.fifo`) is not running.
 //!
-//! Solution: Run `itmdump -f itm.fifo` (or equivalently `cat itm.fifo`) *before* invoking `xargo
+//! Solution: Run `itmdump -f itm.fifo` (or equivalently `cat itm.fifo`) *before* invoking `cargo
 //! run` / starting GDB. Note that sometimes `itmdump` will exit when the GDB session ends. In that
 //! case you'll have to run `itmdump` before you start the next GDB session.
 //!
@@ -314,6 +349,211 @@
 //! the `mkfifo itm.dump` command. You can use `itmdump`'s *follow* mode (-F) to get named pipe like
 //! output.
 
+#![cfg_attr(feature = "nightly", feature(generator_trait))]
 #![no_std]
 
+extern crate bresenham;
+extern crate cortex_m;
+extern crate cortex_m_rt;
+extern crate cortex_m_semihosting;
+#[cfg(feature = "defmt")]
+extern crate defmt;
+extern crate embedded_hal;
+extern crate log;
+extern crate menu;
+#[macro_use]
+extern crate nb;
+#[cfg(feature = "messages")]
+extern crate postcard;
+#[cfg(feature = "messages")]
+extern crate serde;
+extern crate smoltcp;
+#[cfg(feature = "tm4c123")]
+pub extern crate tm4c123x_hal as hal;
+#[cfg(feature = "tm4c129")]
+pub extern crate tm4c129x_hal as hal;
+#[cfg(feature = "stm32f407")]
+pub extern crate stm32f4xx_hal as hal;
+extern crate vga_framebuffer as fb;
+
+/// The registers of whichever chip we're building for.
+#[cfg(feature = "tm4c123")]
+pub use hal::tm4c123x as pac;
+/// The registers of whichever chip we're building for.
+#[cfg(feature = "tm4c129")]
+pub use hal::tm4c129x as pac;
+/// The registers of whichever chip we're building for.
+#[cfg(feature = "stm32f407")]
+pub use hal::stm32 as pac;
+
+#[cfg(any(
+    all(feature = "tm4c123", feature = "tm4c129"),
+    all(feature = "tm4c", feature = "stm32f407")
+))]
+compile_error!("Pick one chip - build with `--no-default-features` and the chip's feature");
+#[cfg(not(any(feature = "tm4c123", feature = "tm4c129", feature = "stm32f407")))]
+compile_error!("Pick a chip with `--features tm4c123`, `tm4c129` or `stm32f407`");
+#[cfg(all(feature = "nightly", feature = "defmt"))]
+compile_error!("`nightly` and `defmt` need different compilers - build with one or the other");
+
 pub mod examples;
+
+#[cfg(feature = "tm4c")]
+pub mod adc;
+pub mod adxl345;
+#[cfg(feature = "tm4c")]
+pub mod analog_joystick;
+#[cfg(feature = "tm4c")]
+pub mod apa102;
+pub mod app;
+#[cfg(feature = "tm4c")]
+pub mod audio;
+#[cfg(feature = "tm4c")]
+pub mod basic;
+#[cfg(feature = "tm4c")]
+pub mod board;
+pub mod bme280;
+#[cfg(feature = "tm4c")]
+pub mod capsense;
+#[cfg(feature = "tm4c")]
+pub mod chip8;
+#[cfg(feature = "tm4c123")]
+pub mod clkout;
+pub mod cobs;
+#[cfg(feature = "tm4c")]
+pub mod config;
+#[cfg(feature = "tm4c")]
+pub mod console;
+#[cfg(feature = "tm4c123")]
+pub mod dac;
+pub mod datetime;
+#[cfg(all(feature = "tm4c", feature = "defmt"))]
+pub mod defmt_log;
+pub mod ds3231;
+#[cfg(feature = "tm4c")]
+pub mod eeprom;
+pub mod enc28j60;
+pub mod esp8266;
+#[cfg(all(feature = "tm4c", feature = "nightly"))]
+pub mod executor;
+pub mod fat;
+#[cfg(feature = "tm4c")]
+pub mod fault;
+pub mod fft;
+#[cfg(feature = "tm4c")]
+pub mod file_manager;
+pub mod firmata;
+#[cfg(feature = "tm4c")]
+pub mod flash;
+pub mod font;
+pub mod forth;
+pub mod graphics;
+pub mod hc595;
+#[cfg(feature = "tm4c")]
+pub mod hd44780;
+#[cfg(feature = "tm4c")]
+pub mod heartbeat;
+#[cfg(feature = "tm4c")]
+pub mod hexedit;
+#[cfg(feature = "tm4c")]
+pub mod hib;
+#[cfg(feature = "tm4c")]
+pub mod i2c;
+#[cfg(feature = "tm4c123")]
+pub mod i2s;
+#[cfg(feature = "tm4c")]
+pub mod ili9341;
+#[cfg(feature = "tm4c")]
+pub mod image;
+#[cfg(feature = "tm4c")]
+pub mod input;
+#[cfg(feature = "tm4c")]
+pub mod joystick;
+#[cfg(feature = "tm4c")]
+pub mod keyboard;
+pub mod loader;
+#[cfg(feature = "tm4c")]
+pub mod logger;
+pub mod max7219;
+#[cfg(feature = "messages")]
+pub mod messages;
+pub mod mfrc522;
+pub mod midi;
+pub mod modbus;
+pub mod morse;
+pub mod mos6502;
+#[cfg(feature = "tm4c")]
+pub mod mouse;
+pub mod mpu;
+pub mod mpu6050;
+pub mod mqtt;
+pub mod nec;
+pub mod nmea;
+pub mod nrf24;
+pub mod pcd8544;
+pub mod pid;
+pub mod pointer;
+#[cfg(feature = "tm4c")]
+pub mod power;
+#[cfg(feature = "tm4c")]
+pub mod profile;
+#[cfg(feature = "tm4c")]
+pub mod ps2;
+#[cfg(feature = "tm4c")]
+pub mod random;
+pub mod rc;
+#[cfg(feature = "tm4c")]
+pub mod records;
+pub mod remote;
+#[cfg(feature = "tm4c")]
+pub mod reset;
+pub mod rfm69;
+#[cfg(feature = "tm4c")]
+pub mod rotary;
+#[cfg(feature = "tm4c")]
+pub mod rs485;
+pub mod slip;
+#[cfg(feature = "tm4c")]
+pub mod scheduler;
+#[cfg(feature = "tm4c")]
+pub mod scroll_menu;
+pub mod sdcard;
+#[cfg(feature = "tm4c123")]
+pub mod selftest;
+#[cfg(feature = "tm4c")]
+pub mod settings;
+pub mod sntp;
+#[cfg(feature = "tm4c")]
+pub mod spi;
+pub mod stack;
+pub mod status_bar;
+pub mod stepper;
+pub mod sump;
+pub mod sx127x;
+#[cfg(feature = "tm4c123")]
+pub mod synth;
+#[cfg(feature = "tm4c")]
+pub mod telemetry;
+pub mod telnet;
+pub mod text;
+pub mod thumb;
+#[cfg(feature = "tm4c123")]
+pub mod tracker;
+pub mod trig;
+#[cfg(feature = "tm4c")]
+pub mod tui;
+#[cfg(feature = "tm4c")]
+pub mod udma;
+#[cfg(feature = "tm4c123")]
+pub mod vga;
+#[cfg(feature = "stm32f407")]
+pub mod vga_stm32f4;
+#[cfg(feature = "tm4c")]
+pub mod vt100;
+#[cfg(feature = "tm4c")]
+pub mod wav;
+pub mod wavetable;
+#[cfg(feature = "tm4c")]
+pub mod ws2812;
+pub mod xmodem;
+pub mod z80;
@@ -0,0 +1,231 @@
+//! Loads Intel HEX and Motorola S-record files into memory.
+//!
+//! Each line of an Intel HEX file is a record - `:`, a length, a 16-bit
+//! address, a type, the data and a checksum, all in hex. The full 32-bit
+//! address comes from an 'extended linear address' record setting the top
+//! half (or, in the old 8086 style, an 'extended segment address' times
+//! sixteen).
+//!
+//! S-records (what `objcopy -O srec` and TI's tools make) are much the
+//! same, but start with `S` and a type digit, and the type says how long
+//! the address is - 16, 24 or 32 bits - so there's no need for extended
+//! address records.
+//!
+//! Parse each line with `Parser::parse`, which works out which kind it is,
+//! and hand the result to a `Region`, which checks the data falls inside it
+//! and copies it in.
+
+/// The most data a record can carry.
+pub const MAX_DATA: usize = 255;
+
+// Intel HEX record types
+const IHEX_DATA: u8 = 0x00;
+const IHEX_END: u8 = 0x01;
+const IHEX_SEGMENT: u8 = 0x02;
+const IHEX_START_SEGMENT: u8 = 0x03;
+const IHEX_LINEAR: u8 = 0x04;
+const IHEX_START_LINEAR: u8 = 0x05;
+
+// S-record types
+const SREC_HEADER: u8 = b'0';
+const SREC_DATA_16: u8 = b'1';
+const SREC_DATA_24: u8 = b'2';
+const SREC_DATA_32: u8 = b'3';
+const SREC_COUNT_16: u8 = b'5';
+const SREC_COUNT_24: u8 = b'6';
+const SREC_START_32: u8 = b'7';
+const SREC_START_24: u8 = b'8';
+const SREC_START_16: u8 = b'9';
+
+/// Something was wrong with a line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// Not a record, or a bad hex digit
+    Syntax,
+    /// The checksum didn't match
+    Checksum,
+    /// The data doesn't fit in the region
+    OutOfRange,
+    /// A record type we don't know
+    Unsupported,
+}
+
+/// A parsed line.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Record<'a> {
+    /// Some bytes to load at an address
+    Data { address: u32, data: &'a [u8] },
+    /// Where to start running the program
+    Start(u32),
+    /// The end of the file, maybe with the start address
+    End(Option<u32>),
+    /// Something that only matters to the parser (like an address change)
+    Other,
+}
+
+/// Parses the lines of an Intel HEX or S-record file.
+pub struct Parser {
+    /// Added to each Intel HEX data record's address
+    base: u32,
+    data: [u8; MAX_DATA + 5],
+}
+
+impl Parser {
+    pub fn new() -> Parser {
+        Parser {
+            base: 0,
+            data: [0u8; MAX_DATA + 5],
+        }
+    }
+
+    /// Parse one line, of either kind, without its line ending.
+    pub fn parse(&mut self, line: &str) -> Result<Record, Error> {
+        let line = line.trim().as_bytes();
+        match line.first() {
+            Some(&b':') => self.intel_hex(&line[1..]),
+            Some(&b'S') if line.len() >= 2 => self.srec(line[1], &line[2..]),
+            _ => Err(Error::Syntax),
+        }
+    }
+
+    fn intel_hex(&mut self, text: &[u8]) -> Result<Record, Error> {
+        // Length, address (2), type, data and checksum
+        let len = decode_hex(text, &mut self.data)?;
+        if len < 5 || len != usize::from(self.data[0]) + 5 {
+            return Err(Error::Syntax);
+        }
+        if sum(&self.data[0..len]) != 0 {
+            return Err(Error::Checksum);
+        }
+        let address = (u32::from(self.data[1]) << 8) | u32::from(self.data[2]);
+        let data = &self.data[4..len - 1];
+        match self.data[3] {
+            IHEX_DATA => Ok(Record::Data {
+                address: self.base.wrapping_add(address),
+                data,
+            }),
+            IHEX_END => Ok(Record::End(None)),
+            IHEX_SEGMENT if data.len() == 2 => {
+                self.base = be(data) << 4;
+                Ok(Record::Other)
+            }
+            IHEX_LINEAR if data.len() == 2 => {
+                self.base = be(data) << 16;
+                Ok(Record::Other)
+            }
+            IHEX_START_LINEAR if data.len() == 4 => Ok(Record::Start(be(data))),
+            // CS:IP means nothing to us
+            IHEX_START_SEGMENT => Ok(Record::Other),
+            _ => Err(Error::Unsupported),
+        }
+    }
+
+    fn srec(&mut self, kind: u8, text: &[u8]) -> Result<Record, Error> {
+        // Count, address, data and checksum
+        let len = decode_hex(text, &mut self.data)?;
+        if len < 2 || len != usize::from(self.data[0]) + 1 {
+            return Err(Error::Syntax);
+        }
+        // Here the checksum makes it all add up to 0xFF
+        if sum(&self.data[0..len]) != 0xFF {
+            return Err(Error::Checksum);
+        }
+        let address_len = match kind {
+            SREC_HEADER | SREC_DATA_16 | SREC_COUNT_16 | SREC_START_16 => 2,
+            SREC_DATA_24 | SREC_COUNT_24 | SREC_START_24 => 3,
+            SREC_DATA_32 | SREC_START_32 => 4,
+            _ => return Err(Error::Unsupported),
+        };
+        if len < address_len + 2 {
+            return Err(Error::Syntax);
+        }
+        let address = be(&self.data[1..1 + address_len]);
+        let data = &self.data[1 + address_len..len - 1];
+        match kind {
+            SREC_DATA_16 | SREC_DATA_24 | SREC_DATA_32 => Ok(Record::Data { address, data }),
+            SREC_START_16 | SREC_START_24 | SREC_START_32 => Ok(Record::End(Some(address))),
+            // The header's just a comment, and we don't check the counts
+            _ => Ok(Record::Other),
+        }
+    }
+}
+
+/// A piece of memory that records get loaded into.
+pub struct Region<'a> {
+    memory: &'a mut [u8],
+    /// Where `memory` starts, as far as the records are concerned
+    address: u32,
+    /// How many bytes we've loaded
+    pub loaded: usize,
+    /// The start address, if the file gave one
+    pub start: Option<u32>,
+}
+
+impl<'a> Region<'a> {
+    pub fn new(memory: &'a mut [u8], address: u32) -> Region<'a> {
+        Region {
+            memory,
+            address,
+            loaded: 0,
+            start: None,
+        }
+    }
+
+    /// Act on a record. Returns true at the end of the file.
+    pub fn apply(&mut self, record: Record) -> Result<bool, Error> {
+        match record {
+            Record::Data { address, data } => {
+                let offset = address.wrapping_sub(self.address) as usize;
+                if address < self.address || offset + data.len() > self.memory.len() {
+                    return Err(Error::OutOfRange);
+                }
+                self.memory[offset..offset + data.len()].copy_from_slice(data);
+                self.loaded += data.len();
+                Ok(false)
+            }
+            Record::Start(address) => {
+                self.start = Some(address);
+                Ok(false)
+            }
+            Record::End(start) => {
+                if start.is_some() {
+                    self.start = start;
+                }
+                Ok(true)
+            }
+            Record::Other => Ok(false),
+        }
+    }
+}
+
+/// Turn pairs of hex digits into bytes. Returns how many bytes.
+fn decode_hex(text: &[u8], buffer: &mut [u8]) -> Result<usize, Error> {
+    if text.len() % 2 != 0 || text.len() / 2 > buffer.len() {
+        return Err(Error::Syntax);
+    }
+    for (byte, pair) in buffer.iter_mut().zip(text.chunks(2)) {
+        *byte = (nibble(pair[0])? << 4) | nibble(pair[1])?;
+    }
+    Ok(text.len() / 2)
+}
+
+fn nibble(digit: u8) -> Result<u8, Error> {
+    match digit {
+        b'0'...b'9' => Ok(digit - b'0'),
+        b'a'...b'f' => Ok(digit - b'a' + 10),
+        b'A'...b'F' => Ok(digit - b'A' + 10),
+        _ => Err(Error::Syntax),
+    }
+}
+
+/// Big-endian, for the address fields.
+fn be(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .fold(0u32, |value, &byte| (value << 8) | u32::from(byte))
+}
+
+/// Checksums are the bottom byte of the sum of everything else.
+fn sum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+}
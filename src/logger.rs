@@ -0,0 +1,156 @@
+//! A `log` backend that writes to UART0, the ITM or the debugger's console.
+//!
+//! Call `init` once at start up, then use `error!`, `warn!`, `info!`,
+//! `debug!` and `trace!` from the `log` crate anywhere - including in
+//! interrupt handlers, as each record is written with interrupts masked so
+//! they can't get mixed up. Records look like:
+//!
+//! ```text
+//! [INFO  stepper] Moving 200 steps
+//! ```
+//!
+//! There are two limits on what gets printed. Records above the level this
+//! crate asks the `log` crate for in `Cargo.toml` (debug, or info in
+//! release builds) are compiled out completely, so they cost nothing.
+//! Records above the level given to `init` (or `set_level` later) are
+//! dropped at run time. `log::LevelFilter` parses from strings like `"info"`
+//! and `"off"`, which makes a menu command easy:
+//!
+//! ``` ignore
+//! match input.split_whitespace().nth(1).map(|s| s.parse()) {
+//!     Some(Ok(level)) => logger::set_level(level),
+//!     _ => writeln!(Console, "Level is {}", logger::level()).unwrap(),
+//! }
+//! ```
+//!
+//! UART0 output goes through `Console`, so UART0 must already be set up.
+//! ITM output goes to stimulus port 0, which your debugger must enable.
+//!
+//! Semihosting output appears on the OpenOCD console, but a semihosting
+//! call without a debugger attached is a HardFault - which is why `hello`
+//! used to hang on a bare LaunchPad. So `Output::Semihosting` only uses
+//! semihosting in debug builds; release builds get UART0 instead, and run
+//! standalone. Set UART0 up either way:
+//!
+//! ``` ignore
+//! let board = board!(p);
+//! logger::init(logger::Output::Semihosting, LevelFilter::Info);
+//! info!("Hello, world!");
+//! ```
+//!
+//! If you have a probe, ITM is the cheaper option: there's no baud rate to
+//! wait for. It's still `core::fmt` though, so it won't make your binary
+//! any smaller. `--features defmt` does, as the formatting is left to the
+//! host - see `defmt_log`.
+
+use console::Console;
+use core::fmt::Write;
+use cortex_m::interrupt;
+use cortex_m::itm;
+use cortex_m::peripheral::ITM;
+use cortex_m_semihosting::hio;
+use log::{self, LevelFilter, Log, Metadata, Record};
+
+/// Where the records go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    /// UART0, via `Console`
+    Uart,
+    /// ITM stimulus port 0
+    Itm,
+    /// The debugger's console in debug builds, and UART0 in release builds
+    Semihosting,
+}
+
+/// Implements `log::Log` for one `Output`.
+struct Logger {
+    output: Output,
+}
+
+static UART_LOGGER: Logger = Logger {
+    output: Output::Uart,
+};
+
+static ITM_LOGGER: Logger = Logger {
+    output: Output::Itm,
+};
+
+#[cfg(debug_assertions)]
+static SEMIHOSTING_LOGGER: Logger = Logger {
+    output: Output::Semihosting,
+};
+
+/// Install the logger. Only the first call does anything.
+pub fn init(output: Output, level: LevelFilter) {
+    let logger = match output {
+        Output::Uart => &UART_LOGGER,
+        Output::Itm => &ITM_LOGGER,
+        #[cfg(debug_assertions)]
+        Output::Semihosting => &SEMIHOSTING_LOGGER,
+        #[cfg(not(debug_assertions))]
+        Output::Semihosting => &UART_LOGGER,
+    };
+    if log::set_logger(logger).is_ok() {
+        set_level(level);
+    }
+}
+
+/// Change the run-time level. It can't go above what was compiled in.
+pub fn set_level(level: LevelFilter) {
+    log::set_max_level(level);
+}
+
+/// The current run-time level.
+pub fn level() -> LevelFilter {
+    log::max_level()
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        interrupt::free(|_| match self.output {
+            Output::Uart => {
+                let _ = writeln!(
+                    Console,
+                    "[{:<5} {}] {}",
+                    record.level(),
+                    record.target(),
+                    record.args()
+                );
+            }
+            Output::Itm => {
+                // Nobody else in this crate uses the ITM, and we're in a
+                // critical section
+                let stim = unsafe { &mut (*ITM::ptr()).stim[0] };
+                itm::write_fmt(
+                    stim,
+                    format_args!(
+                        "[{:<5} {}] {}\n",
+                        record.level(),
+                        record.target(),
+                        record.args()
+                    ),
+                );
+            }
+            Output::Semihosting => {
+                if let Ok(mut stdout) = hio::hstdout() {
+                    let _ = writeln!(
+                        stdout,
+                        "[{:<5} {}] {}",
+                        record.level(),
+                        record.target(),
+                        record.args()
+                    );
+                }
+            }
+        });
+    }
+
+    fn flush(&self) {}
+}
@@ -0,0 +1,109 @@
+//! Drives a chain of MAX7219 8x8 LED matrix modules over SPI.
+//!
+//! Each module is a 16-bit shift register, so to talk to module `n` we have
+//! to clock out a command for every module in the chain (no-ops for the
+//! ones we don't care about) before raising CS to latch them all.
+
+use embedded_hal::blocking::spi;
+use embedded_hal::digital::OutputPin;
+use font;
+
+/// The longest chain we support.
+pub const MAX_DEVICES: usize = 8;
+
+const REG_NOOP: u8 = 0x00;
+const REG_DIGIT0: u8 = 0x01;
+const REG_DECODE_MODE: u8 = 0x09;
+const REG_INTENSITY: u8 = 0x0A;
+const REG_SCAN_LIMIT: u8 = 0x0B;
+const REG_SHUTDOWN: u8 = 0x0C;
+const REG_DISPLAY_TEST: u8 = 0x0F;
+
+pub struct Max7219<SPI, CS> {
+    spi: SPI,
+    cs: CS,
+    devices: usize,
+}
+
+impl<SPI, CS, E> Max7219<SPI, CS>
+where
+    SPI: spi::Write<u8, Error = E>,
+    CS: OutputPin,
+{
+    /// Wake up a chain of `devices` modules, in raw (no BCD decode) mode
+    /// with all eight rows enabled. The bus should be SPI mode 0 at up to
+    /// 10 MHz.
+    pub fn new(spi: SPI, cs: CS, devices: usize) -> Result<Self, E> {
+        assert!(devices > 0 && devices <= MAX_DEVICES);
+        let mut max = Max7219 { spi, cs, devices };
+        max.cs.set_high();
+        max.write_all(REG_DISPLAY_TEST, 0)?;
+        max.write_all(REG_DECODE_MODE, 0)?;
+        max.write_all(REG_SCAN_LIMIT, 7)?;
+        max.write_all(REG_INTENSITY, 4)?;
+        max.write_all(REG_SHUTDOWN, 1)?;
+        max.clear()?;
+        Ok(max)
+    }
+
+    /// How many modules are in the chain.
+    pub fn devices(&self) -> usize {
+        self.devices
+    }
+
+    /// Set the brightness of every module (0..15).
+    pub fn set_intensity(&mut self, intensity: u8) -> Result<(), E> {
+        self.write_all(REG_INTENSITY, intensity & 0x0F)
+    }
+
+    /// Turn every LED off.
+    pub fn clear(&mut self) -> Result<(), E> {
+        for row in 0..8 {
+            self.write_all(REG_DIGIT0 + row, 0)?;
+        }
+        Ok(())
+    }
+
+    /// Set one row (0..7) on every module at once. `data[0]` goes to the
+    /// module nearest the microcontroller. The MSB is the leftmost LED.
+    pub fn write_row(&mut self, row: u8, data: &[u8]) -> Result<(), E> {
+        let mut buffer = [0u8; MAX_DEVICES * 2];
+        let len = self.devices * 2;
+        // The last module in the chain gets the first bytes clocked out
+        for (i, chunk) in buffer[0..len].chunks_mut(2).enumerate() {
+            let device = self.devices - 1 - i;
+            chunk[0] = REG_DIGIT0 + (row & 0x07);
+            chunk[1] = data.get(device).cloned().unwrap_or(0);
+        }
+        self.send(&buffer[0..len])
+    }
+
+    fn write_all(&mut self, reg: u8, value: u8) -> Result<(), E> {
+        let mut buffer = [REG_NOOP; MAX_DEVICES * 2];
+        let len = self.devices * 2;
+        for chunk in buffer[0..len].chunks_mut(2) {
+            chunk[0] = reg;
+            chunk[1] = value;
+        }
+        self.send(&buffer[0..len])
+    }
+
+    fn send(&mut self, bytes: &[u8]) -> Result<(), E> {
+        self.cs.set_low();
+        let result = self.spi.write(bytes);
+        self.cs.set_high();
+        result
+    }
+}
+
+/// Get one 8-pixel-high column of a character from the VGA font, using
+/// every other line of the 8x16 glyph. Bit 0 is the top row.
+pub fn font_column(ch: u8, col: usize) -> u8 {
+    let mut bits = 0;
+    for row in 0..8 {
+        if font::pixel(ch, col, row * 2) {
+            bits |= 1 << row;
+        }
+    }
+    bits
+}
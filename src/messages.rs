@@ -0,0 +1,96 @@
+//! The messages two boards send each other in the `messages` example,
+//! defined once here so both ends agree.
+//!
+//! Each message is a `Header` and then a `Body`, serialized with postcard
+//! (which is compact - no field names, no padding), and sent as a `cobs`
+//! frame.
+//!
+//! The `Header` must never change. If anything in `Body` (or the structs
+//! it uses) does, bump `VERSION`. Then a board running older firmware can
+//! still read the header of a newer board's message, see it's from a
+//! different version and leave the body alone, instead of reading it as
+//! something else. `decode` does that check.
+//!
+//! Needs `--features messages`, for serde and postcard.
+
+use postcard;
+use serde::{Deserialize, Serialize};
+
+/// The version of `Body` this firmware speaks.
+pub const VERSION: u16 = 1;
+
+/// Room for the biggest message, serialized.
+pub const MAX_MESSAGE: usize = 32;
+
+/// Something went wrong with a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// It didn't fit in the buffer
+    TooBig,
+    /// It wasn't a message we understand
+    Corrupt,
+    /// It came from a board speaking another version
+    Version(u16),
+}
+
+/// At the front of every message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Header {
+    pub version: u16,
+    /// Counts up by one with each message a board sends
+    pub sequence: u16,
+}
+
+/// What's in a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Body {
+    /// Sent at start-up, and to a board that's speaking another version so
+    /// it knows what we speak
+    Hello,
+    Sensors(Sensors),
+    Command(Command),
+    /// A command (by its sequence number) has been done
+    Ack(u16),
+}
+
+/// A reading of everything, sent every so often.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sensors {
+    pub uptime_ms: u32,
+    /// Tenths of a degree C
+    pub temperature: i16,
+    pub ain0_mv: u16,
+    pub sw1: bool,
+    pub sw2: bool,
+}
+
+/// Something for the other board to do. It sends an `Ack` when it has.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Command {
+    SetLed { red: bool, green: bool, blue: bool },
+    /// How often to send `Sensors`, in milliseconds
+    SetInterval(u32),
+}
+
+/// Serialize a message into `buffer`, and say how long it is.
+pub fn encode(header: &Header, body: &Body, buffer: &mut [u8]) -> Result<usize, Error> {
+    let len = postcard::to_slice(header, buffer)
+        .map_err(|_| Error::TooBig)?
+        .len();
+    let body_len = postcard::to_slice(body, &mut buffer[len..])
+        .map_err(|_| Error::TooBig)?
+        .len();
+    Ok(len + body_len)
+}
+
+/// Read a message. If it's from another version, that's
+/// `Error::Version`, and the header is all we look at.
+pub fn decode(bytes: &[u8]) -> Result<(Header, Body), Error> {
+    let (header, rest) =
+        postcard::take_from_bytes::<Header>(bytes).map_err(|_| Error::Corrupt)?;
+    if header.version != VERSION {
+        return Err(Error::Version(header.version));
+    }
+    let body = postcard::from_bytes::<Body>(rest).map_err(|_| Error::Corrupt)?;
+    Ok((header, body))
+}
@@ -0,0 +1,272 @@
+//! Drives an NXP MFRC522 13.56 MHz RFID reader (the common blue 'RC522'
+//! boards) over SPI.
+//!
+//! We only do enough ISO14443A to find a card and read its UID: REQA,
+//! anticollision and SELECT, for single (4 byte) and double (7 byte) size
+//! UIDs. Reading and writing the card's memory (MIFARE authentication and
+//! so on) is left as an exercise.
+
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::blocking::spi;
+use embedded_hal::digital::OutputPin;
+
+/// Longest UID we handle (double size).
+pub const MAX_UID: usize = 7;
+
+const REG_COMMAND: u8 = 0x01;
+const REG_COMIRQ: u8 = 0x04;
+const REG_DIVIRQ: u8 = 0x05;
+const REG_ERROR: u8 = 0x06;
+const REG_FIFODATA: u8 = 0x09;
+const REG_FIFOLEVEL: u8 = 0x0A;
+const REG_BITFRAMING: u8 = 0x0D;
+const REG_COLL: u8 = 0x0E;
+const REG_MODE: u8 = 0x11;
+const REG_TXCONTROL: u8 = 0x14;
+const REG_TXASK: u8 = 0x15;
+const REG_CRCRESULT_H: u8 = 0x21;
+const REG_CRCRESULT_L: u8 = 0x22;
+const REG_TMODE: u8 = 0x2A;
+const REG_TPRESCALER: u8 = 0x2B;
+const REG_TRELOAD_H: u8 = 0x2C;
+const REG_TRELOAD_L: u8 = 0x2D;
+const REG_VERSION: u8 = 0x37;
+
+const CMD_IDLE: u8 = 0x00;
+const CMD_CALC_CRC: u8 = 0x03;
+const CMD_TRANSCEIVE: u8 = 0x0C;
+const CMD_SOFT_RESET: u8 = 0x0F;
+
+const COMIRQ_RX: u8 = 0x20;
+const COMIRQ_IDLE: u8 = 0x10;
+const COMIRQ_ERR: u8 = 0x02;
+const COMIRQ_TIMER: u8 = 0x01;
+const COMIRQ_ALL: u8 = 0x7F;
+
+const DIVIRQ_CRC: u8 = 0x04;
+
+/// BufferOvfl, ParityErr and ProtocolErr.
+const ERROR_FATAL: u8 = 0x13;
+const ERROR_COLL: u8 = 0x08;
+
+const FIFOLEVEL_FLUSH: u8 = 0x80;
+const BITFRAMING_START_SEND: u8 = 0x80;
+
+const PICC_REQA: u8 = 0x26;
+const PICC_HLTA: u8 = 0x50;
+const PICC_SEL_CL1: u8 = 0x93;
+const PICC_SEL_CL2: u8 = 0x95;
+const PICC_CASCADE_TAG: u8 = 0x88;
+
+/// Something went wrong talking to the reader or the card.
+#[derive(Debug)]
+pub enum Error<E> {
+    /// The SPI bus reported an error
+    Spi(E),
+    /// No card answered in time
+    Timeout,
+    /// Two cards answered at once
+    Collision,
+    /// The card's reply was garbled
+    Protocol,
+    /// The UID check byte didn't match
+    BadBcc,
+}
+
+/// A card's unique ID.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Uid {
+    pub len: usize,
+    pub bytes: [u8; MAX_UID],
+}
+
+impl Uid {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[0..self.len]
+    }
+}
+
+pub struct Mfrc522<SPI, CS> {
+    spi: SPI,
+    cs: CS,
+}
+
+impl<SPI, CS, E> Mfrc522<SPI, CS>
+where
+    SPI: spi::Transfer<u8, Error = E>,
+    CS: OutputPin,
+{
+    /// Reset the reader and turn the antenna on. RST is active low. The bus
+    /// should be SPI mode 0 at up to 10 MHz.
+    pub fn new<RST, D>(spi: SPI, cs: CS, rst: &mut RST, delay: &mut D) -> Result<Self, Error<E>>
+    where
+        RST: OutputPin,
+        D: DelayMs<u32>,
+    {
+        let mut reader = Mfrc522 { spi, cs };
+        reader.cs.set_high();
+        rst.set_low();
+        delay.delay_ms(1);
+        rst.set_high();
+        delay.delay_ms(50);
+        reader.write_register(REG_COMMAND, CMD_SOFT_RESET)?;
+        delay.delay_ms(50);
+        // Timer at 40 kHz, 25ms timeout, started automatically at the end
+        // of each transmission
+        reader.write_register(REG_TMODE, 0x80)?;
+        reader.write_register(REG_TPRESCALER, 0xA9)?;
+        reader.write_register(REG_TRELOAD_H, 0x03)?;
+        reader.write_register(REG_TRELOAD_L, 0xE8)?;
+        // 100% ASK modulation
+        reader.write_register(REG_TXASK, 0x40)?;
+        // CRC preset 0x6363, as ISO14443A wants
+        reader.write_register(REG_MODE, 0x3D)?;
+        // Antenna on (TX1 and TX2)
+        let tx = reader.read_register(REG_TXCONTROL)?;
+        reader.write_register(REG_TXCONTROL, tx | 0x03)?;
+        Ok(reader)
+    }
+
+    /// The chip version (0x91 for v1.0, 0x92 for v2.0, clones vary).
+    pub fn version(&mut self) -> Result<u8, Error<E>> {
+        self.read_register(REG_VERSION)
+    }
+
+    /// Look for a card in the field, returning its UID if we find one. The
+    /// card is halted afterwards, so it won't answer again until it has
+    /// been taken out of the field and put back.
+    pub fn poll(&mut self) -> Result<Option<Uid>, Error<E>> {
+        // REQA is a short frame of seven bits
+        let mut atqa = [0u8; 2];
+        match self.transceive(&[PICC_REQA], 7, &mut atqa) {
+            Ok(_) => {}
+            Err(Error::Timeout) => return Ok(None),
+            Err(e) => return Err(e),
+        }
+
+        let mut uid = Uid {
+            len: 0,
+            bytes: [0u8; MAX_UID],
+        };
+        let part = self.select(PICC_SEL_CL1)?;
+        if part[0] == PICC_CASCADE_TAG {
+            // Double size UID - the first three bytes come from level 1
+            uid.bytes[0..3].copy_from_slice(&part[1..4]);
+            let part = self.select(PICC_SEL_CL2)?;
+            uid.bytes[3..7].copy_from_slice(&part[0..4]);
+            uid.len = 7;
+        } else {
+            uid.bytes[0..4].copy_from_slice(&part[0..4]);
+            uid.len = 4;
+        }
+        self.halt()?;
+        Ok(Some(uid))
+    }
+
+    /// Run anticollision and SELECT at one cascade level, returning the
+    /// four UID bytes from that level.
+    fn select(&mut self, level: u8) -> Result<[u8; 4], Error<E>> {
+        let mut reply = [0u8; 5];
+        self.write_register(REG_COLL, 0x80)?;
+        self.transceive(&[level, 0x20], 0, &mut reply)?;
+        if reply[0] ^ reply[1] ^ reply[2] ^ reply[3] != reply[4] {
+            return Err(Error::BadBcc);
+        }
+        let mut frame = [level, 0x70, reply[0], reply[1], reply[2], reply[3], reply[4], 0, 0];
+        let crc = self.crc(&frame[0..7])?;
+        frame[7] = crc[0];
+        frame[8] = crc[1];
+        let mut sak = [0u8; 3];
+        self.transceive(&frame, 0, &mut sak)?;
+        Ok([reply[0], reply[1], reply[2], reply[3]])
+    }
+
+    /// Tell the card to go to sleep. It doesn't answer, so the timeout is
+    /// the expected outcome.
+    fn halt(&mut self) -> Result<(), Error<E>> {
+        let mut frame = [PICC_HLTA, 0, 0, 0];
+        let crc = self.crc(&frame[0..2])?;
+        frame[2] = crc[0];
+        frame[3] = crc[1];
+        match self.transceive(&frame, 0, &mut []) {
+            Ok(_) | Err(Error::Timeout) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Get the reader to work out an ISO14443A CRC for us.
+    fn crc(&mut self, data: &[u8]) -> Result<[u8; 2], Error<E>> {
+        self.write_register(REG_COMMAND, CMD_IDLE)?;
+        self.write_register(REG_DIVIRQ, DIVIRQ_CRC)?;
+        self.write_register(REG_FIFOLEVEL, FIFOLEVEL_FLUSH)?;
+        for byte in data {
+            self.write_register(REG_FIFODATA, *byte)?;
+        }
+        self.write_register(REG_COMMAND, CMD_CALC_CRC)?;
+        while self.read_register(REG_DIVIRQ)? & DIVIRQ_CRC == 0 {}
+        self.write_register(REG_COMMAND, CMD_IDLE)?;
+        Ok([
+            self.read_register(REG_CRCRESULT_L)?,
+            self.read_register(REG_CRCRESULT_H)?,
+        ])
+    }
+
+    /// Send a frame (of which the last byte has `last_bits` bits, or 0 for
+    /// all eight) and collect the reply. Returns how many bytes came back.
+    fn transceive(
+        &mut self,
+        data: &[u8],
+        last_bits: u8,
+        reply: &mut [u8],
+    ) -> Result<usize, Error<E>> {
+        self.write_register(REG_COMMAND, CMD_IDLE)?;
+        self.write_register(REG_COMIRQ, COMIRQ_ALL)?;
+        self.write_register(REG_FIFOLEVEL, FIFOLEVEL_FLUSH)?;
+        for byte in data {
+            self.write_register(REG_FIFODATA, *byte)?;
+        }
+        self.write_register(REG_COMMAND, CMD_TRANSCEIVE)?;
+        self.write_register(REG_BITFRAMING, BITFRAMING_START_SEND | last_bits)?;
+
+        let irq = loop {
+            let irq = self.read_register(REG_COMIRQ)?;
+            if irq & (COMIRQ_RX | COMIRQ_IDLE | COMIRQ_ERR | COMIRQ_TIMER) != 0 {
+                break irq;
+            }
+        };
+        self.write_register(REG_BITFRAMING, 0)?;
+
+        if irq & COMIRQ_TIMER != 0 && irq & COMIRQ_RX == 0 {
+            return Err(Error::Timeout);
+        }
+        let error = self.read_register(REG_ERROR)?;
+        if error & ERROR_COLL != 0 {
+            return Err(Error::Collision);
+        }
+        if error & ERROR_FATAL != 0 {
+            return Err(Error::Protocol);
+        }
+
+        let len = (self.read_register(REG_FIFOLEVEL)? as usize).min(reply.len());
+        for byte in reply[0..len].iter_mut() {
+            *byte = self.read_register(REG_FIFODATA)?;
+        }
+        Ok(len)
+    }
+
+    fn read_register(&mut self, reg: u8) -> Result<u8, Error<E>> {
+        let mut buffer = [0x80 | (reg << 1), 0];
+        self.cs.set_low();
+        let result = self.spi.transfer(&mut buffer).map(|b| b[1]);
+        self.cs.set_high();
+        result.map_err(Error::Spi)
+    }
+
+    fn write_register(&mut self, reg: u8, value: u8) -> Result<(), Error<E>> {
+        let mut buffer = [reg << 1, value];
+        self.cs.set_low();
+        let result = self.spi.transfer(&mut buffer).map(|_| ());
+        self.cs.set_high();
+        result.map_err(Error::Spi)
+    }
+}
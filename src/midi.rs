@@ -0,0 +1,115 @@
+//! Just enough MIDI to play notes.
+//!
+//! MIDI is a 31250 bps serial stream of messages: a status byte (top bit
+//! set) followed by one or two data bytes. A sender may leave out the
+//! status byte if it's the same as last time ('running status'), and
+//! one-byte real-time messages (clock, start, stop...) can turn up anywhere,
+//! even in the middle of another message. We pick out Note On and Note
+//! Off and skip over everything else.
+
+/// The MIDI baud rate.
+pub const BAUD_RATE: u32 = 31_250;
+
+/// Middle C.
+pub const MIDDLE_C: u8 = 60;
+
+const NOTE_OFF: u8 = 0x80;
+const NOTE_ON: u8 = 0x90;
+const SYSEX_START: u8 = 0xF0;
+const REAL_TIME: u8 = 0xF8;
+
+/// Something we understood. Channels are 0..15, although musicians count
+/// them from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    NoteOn { channel: u8, note: u8, velocity: u8 },
+    NoteOff { channel: u8, note: u8 },
+}
+
+/// Turns a stream of bytes into messages.
+pub struct Parser {
+    status: u8,
+    data: [u8; 2],
+    count: usize,
+}
+
+impl Parser {
+    pub fn new() -> Parser {
+        Parser {
+            status: 0,
+            data: [0; 2],
+            count: 0,
+        }
+    }
+
+    /// Feed in the next byte off the wire. Returns a message when one is
+    /// complete.
+    pub fn input(&mut self, byte: u8) -> Option<Message> {
+        if byte >= REAL_TIME {
+            // Doesn't affect whatever we were in the middle of
+            return None;
+        }
+        if byte & 0x80 != 0 {
+            // New status. System messages (including SysEx) cancel running
+            // status, and we ignore their data.
+            self.status = if byte >= SYSEX_START { 0 } else { byte };
+            self.count = 0;
+            return None;
+        }
+        if self.status == 0 {
+            return None;
+        }
+        self.data[self.count] = byte;
+        self.count += 1;
+        if self.count < data_bytes(self.status) {
+            return None;
+        }
+        // Ready for the next message with the same status
+        self.count = 0;
+        let channel = self.status & 0x0F;
+        match self.status & 0xF0 {
+            NOTE_ON if self.data[1] != 0 => Some(Message::NoteOn {
+                channel,
+                note: self.data[0],
+                velocity: self.data[1],
+            }),
+            // Note On with zero velocity is how most keyboards say Note Off
+            NOTE_ON | NOTE_OFF => Some(Message::NoteOff {
+                channel,
+                note: self.data[0],
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// How many data bytes go with a channel message.
+fn data_bytes(status: u8) -> usize {
+    match status & 0xF0 {
+        // Program Change and Channel Pressure
+        0xC0 | 0xD0 => 1,
+        _ => 2,
+    }
+}
+
+impl Message {
+    /// Encode the message, ready to send.
+    pub fn to_bytes(&self) -> [u8; 3] {
+        match *self {
+            Message::NoteOn {
+                channel,
+                note,
+                velocity,
+            } => [NOTE_ON | (channel & 0x0F), note & 0x7F, velocity & 0x7F],
+            Message::NoteOff { channel, note } => [NOTE_OFF | (channel & 0x0F), note & 0x7F, 0],
+        }
+    }
+}
+
+/// The name of a note, like `C#`, and its octave (middle C is C4).
+pub fn note_name(note: u8) -> (&'static str, i8) {
+    const NAMES: [&str; 12] = [
+        "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+    ];
+    (NAMES[(note % 12) as usize], (note / 12) as i8 - 1)
+}
@@ -0,0 +1,205 @@
+//! Bits of Modbus RTU, the protocol most RS-485 sensors and meters speak.
+//!
+//! A frame is a slave address, a function code, some data and a CRC, sent
+//! as one burst. Frames are separated by at least 3.5 characters of silence
+//! - there is no start or end marker.
+//!
+//! We can be the master (`read_request`) or a slave (`serve`).
+
+/// Read Holding Registers
+pub const READ_HOLDING_REGISTERS: u8 = 0x03;
+/// Read Input Registers
+pub const READ_INPUT_REGISTERS: u8 = 0x04;
+/// Write Single Register
+pub const WRITE_SINGLE_REGISTER: u8 = 0x06;
+/// Write Multiple Registers
+pub const WRITE_MULTIPLE_REGISTERS: u8 = 0x10;
+
+/// Exception codes, sent after a function code with `EXCEPTION` set.
+pub const ILLEGAL_FUNCTION: u8 = 0x01;
+pub const ILLEGAL_DATA_ADDRESS: u8 = 0x02;
+pub const ILLEGAL_DATA_VALUE: u8 = 0x03;
+
+/// Requests to this address go to every slave, and nobody replies.
+pub const BROADCAST: u8 = 0;
+
+/// Set in the function code of a reply that reports an error.
+pub const EXCEPTION: u8 = 0x80;
+
+/// The longest frame the standard allows.
+pub const MAX_FRAME: usize = 256;
+
+/// The Modbus CRC-16 (polynomial 0xA001 reflected, starting at 0xFFFF).
+/// It goes on the end of the frame low byte first.
+pub fn crc(bytes: &[u8]) -> u16 {
+    let mut crc = 0xFFFFu16;
+    for byte in bytes {
+        crc ^= u16::from(*byte);
+        for _ in 0..8 {
+            if crc & 1 != 0 {
+                crc = (crc >> 1) ^ 0xA001;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+    crc
+}
+
+/// Does the frame (including its CRC) check out?
+pub fn crc_ok(frame: &[u8]) -> bool {
+    if frame.len() < 3 {
+        return false;
+    }
+    let (body, tail) = frame.split_at(frame.len() - 2);
+    crc(body) == u16::from(tail[0]) | (u16::from(tail[1]) << 8)
+}
+
+/// Build a request to read `count` registers starting at `start`, using
+/// one of the two read functions.
+pub fn read_request(slave: u8, function: u8, start: u16, count: u16) -> [u8; 8] {
+    let mut frame = [
+        slave,
+        function,
+        (start >> 8) as u8,
+        start as u8,
+        (count >> 8) as u8,
+        count as u8,
+        0,
+        0,
+    ];
+    let crc = crc(&frame[0..6]);
+    frame[6] = crc as u8;
+    frame[7] = (crc >> 8) as u8;
+    frame
+}
+
+/// How long the line must be quiet to end a frame: 3.5 characters of 11
+/// bits, or a fixed 1.75ms above 19200 bps as the standard says.
+pub fn silent_interval_us(baud: u32) -> u32 {
+    if baud > 19_200 {
+        1_750
+    } else {
+        38_500_000 / baud
+    }
+}
+
+/// The registers a slave has. We don't distinguish between holding and
+/// input registers - both read functions see the same map.
+pub trait Registers {
+    /// Read a register, or `None` if there isn't one at that address.
+    fn read(&mut self, address: u16) -> Option<u16>;
+    /// Write a register. Returns false if there isn't one at that address,
+    /// or it's read-only.
+    fn write(&mut self, address: u16, value: u16) -> bool;
+}
+
+/// Act on a request (including its CRC) as slave `slave`, and build the
+/// reply. Returns the length of the reply, which is zero if we shouldn't
+/// send one - because the request was corrupt, was for some other slave, or
+/// was a broadcast.
+pub fn serve<R>(slave: u8, request: &[u8], registers: &mut R, reply: &mut [u8]) -> usize
+where
+    R: Registers,
+{
+    if request.len() < 4 || !crc_ok(request) {
+        return 0;
+    }
+    let address = request[0];
+    if address != slave && address != BROADCAST {
+        return 0;
+    }
+    let function = request[1];
+    let data = &request[2..request.len() - 2];
+    reply[0] = slave;
+    reply[1] = function;
+    let result = match function {
+        READ_HOLDING_REGISTERS | READ_INPUT_REGISTERS => read_registers(data, registers, reply),
+        WRITE_SINGLE_REGISTER => write_single(data, registers, reply),
+        WRITE_MULTIPLE_REGISTERS => write_multiple(data, registers, reply),
+        _ => Err(ILLEGAL_FUNCTION),
+    };
+    let len = match result {
+        Ok(len) => len,
+        Err(code) => {
+            reply[1] |= EXCEPTION;
+            reply[2] = code;
+            3
+        }
+    };
+    if address == BROADCAST {
+        return 0;
+    }
+    let crc = crc(&reply[0..len]);
+    reply[len] = crc as u8;
+    reply[len + 1] = (crc >> 8) as u8;
+    len + 2
+}
+
+fn word(bytes: &[u8]) -> u16 {
+    (u16::from(bytes[0]) << 8) | u16::from(bytes[1])
+}
+
+fn read_registers<R>(data: &[u8], registers: &mut R, reply: &mut [u8]) -> Result<usize, u8>
+where
+    R: Registers,
+{
+    if data.len() != 4 {
+        return Err(ILLEGAL_DATA_VALUE);
+    }
+    let start = word(&data[0..2]);
+    let count = word(&data[2..4]);
+    // The most that fits in a frame
+    if count == 0 || count > 125 {
+        return Err(ILLEGAL_DATA_VALUE);
+    }
+    reply[2] = (count * 2) as u8;
+    for i in 0..count {
+        let value = registers
+            .read(start.wrapping_add(i))
+            .ok_or(ILLEGAL_DATA_ADDRESS)?;
+        let offset = 3 + (2 * i as usize);
+        reply[offset] = (value >> 8) as u8;
+        reply[offset + 1] = value as u8;
+    }
+    Ok(3 + (2 * count as usize))
+}
+
+fn write_single<R>(data: &[u8], registers: &mut R, reply: &mut [u8]) -> Result<usize, u8>
+where
+    R: Registers,
+{
+    if data.len() != 4 {
+        return Err(ILLEGAL_DATA_VALUE);
+    }
+    if !registers.write(word(&data[0..2]), word(&data[2..4])) {
+        return Err(ILLEGAL_DATA_ADDRESS);
+    }
+    // The reply is the request, echoed back
+    reply[2..6].copy_from_slice(data);
+    Ok(6)
+}
+
+fn write_multiple<R>(data: &[u8], registers: &mut R, reply: &mut [u8]) -> Result<usize, u8>
+where
+    R: Registers,
+{
+    if data.len() < 5 {
+        return Err(ILLEGAL_DATA_VALUE);
+    }
+    let start = word(&data[0..2]);
+    let count = word(&data[2..4]);
+    let bytes = data[4] as usize;
+    if count == 0 || count > 123 || bytes != 2 * count as usize || data.len() != 5 + bytes {
+        return Err(ILLEGAL_DATA_VALUE);
+    }
+    for i in 0..count {
+        let offset = 5 + (2 * i as usize);
+        if !registers.write(start.wrapping_add(i), word(&data[offset..offset + 2])) {
+            return Err(ILLEGAL_DATA_ADDRESS);
+        }
+    }
+    // The reply is the start and count
+    reply[2..6].copy_from_slice(&data[0..4]);
+    Ok(6)
+}
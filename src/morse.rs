@@ -0,0 +1,195 @@
+//! Sends text as Morse code.
+//!
+//! Everything is timed in 'units', the length of a dit. A dah is three
+//! units, the gap inside a letter is one, the gap between letters is three
+//! and the gap between words is seven. At `w` words per minute a unit
+//! lasts 1200/w milliseconds (from the standard word, "PARIS").
+//!
+//! `Elements` turns text into a list of on and off times, and `Keyer`
+//! works through them one unit at a time from a timer interrupt, turning
+//! whatever implements `Key` (an LED, a beeper, a transmitter...) on and
+//! off.
+
+use embedded_hal::digital::OutputPin;
+
+/// The longest message we'll hold. Anything longer is cut off.
+pub const MAX_TEXT: usize = 64;
+
+/// The length of a unit in milliseconds at the given speed.
+pub fn unit_ms(wpm: u32) -> u32 {
+    1200 / wpm.max(1)
+}
+
+/// The dits and dahs for a character, or `None` if there isn't one (like
+/// for a space).
+pub fn code(ch: u8) -> Option<&'static str> {
+    let code = match ch.to_ascii_uppercase() {
+        b'A' => ".-",
+        b'B' => "-...",
+        b'C' => "-.-.",
+        b'D' => "-..",
+        b'E' => ".",
+        b'F' => "..-.",
+        b'G' => "--.",
+        b'H' => "....",
+        b'I' => "..",
+        b'J' => ".---",
+        b'K' => "-.-",
+        b'L' => ".-..",
+        b'M' => "--",
+        b'N' => "-.",
+        b'O' => "---",
+        b'P' => ".--.",
+        b'Q' => "--.-",
+        b'R' => ".-.",
+        b'S' => "...",
+        b'T' => "-",
+        b'U' => "..-",
+        b'V' => "...-",
+        b'W' => ".--",
+        b'X' => "-..-",
+        b'Y' => "-.--",
+        b'Z' => "--..",
+        b'0' => "-----",
+        b'1' => ".----",
+        b'2' => "..---",
+        b'3' => "...--",
+        b'4' => "....-",
+        b'5' => ".....",
+        b'6' => "-....",
+        b'7' => "--...",
+        b'8' => "---..",
+        b'9' => "----.",
+        b'.' => ".-.-.-",
+        b',' => "--..--",
+        b'?' => "..--..",
+        b'/' => "-..-.",
+        b'=' => "-...-",
+        b'+' => ".-.-.",
+        b'-' => "-....-",
+        b'@' => ".--.-.",
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Something that can be keyed on and off.
+pub trait Key {
+    fn set(&mut self, down: bool);
+}
+
+impl<P> Key for P
+where
+    P: OutputPin,
+{
+    fn set(&mut self, down: bool) {
+        if down {
+            self.set_high();
+        } else {
+            self.set_low();
+        }
+    }
+}
+
+/// A message as a series of `(on, units)` pairs. Characters we don't have
+/// a code for are treated as spaces.
+pub struct Elements {
+    text: [u8; MAX_TEXT],
+    len: usize,
+    pos: usize,
+    symbol: usize,
+    gap_next: bool,
+}
+
+impl Elements {
+    pub fn new(text: &str) -> Elements {
+        let mut e = Elements {
+            text: [0u8; MAX_TEXT],
+            len: text.len().min(MAX_TEXT),
+            pos: 0,
+            symbol: 0,
+            gap_next: false,
+        };
+        e.text[0..e.len].copy_from_slice(&text.as_bytes()[0..e.len]);
+        e
+    }
+
+    /// How long a gap goes after the symbol we just sent.
+    fn gap(&self) -> u8 {
+        if self.symbol != 0 {
+            // Still in the middle of a letter
+            1
+        } else if self.pos < self.len && code(self.text[self.pos]).is_none() {
+            7
+        } else {
+            3
+        }
+    }
+}
+
+impl Iterator for Elements {
+    type Item = (bool, u8);
+
+    fn next(&mut self) -> Option<(bool, u8)> {
+        if self.gap_next {
+            self.gap_next = false;
+            return Some((false, self.gap()));
+        }
+        while self.pos < self.len {
+            let symbols = match code(self.text[self.pos]) {
+                Some(symbols) => symbols.as_bytes(),
+                None => {
+                    // The gap before it has already covered this
+                    self.pos += 1;
+                    continue;
+                }
+            };
+            let units = if symbols[self.symbol] == b'-' { 3 } else { 1 };
+            self.symbol += 1;
+            if self.symbol == symbols.len() {
+                self.symbol = 0;
+                self.pos += 1;
+            }
+            self.gap_next = true;
+            return Some((true, units));
+        }
+        None
+    }
+}
+
+/// Sends a message one unit at a time.
+pub struct Keyer {
+    elements: Elements,
+    remaining: u8,
+}
+
+impl Keyer {
+    pub fn new(text: &str) -> Keyer {
+        Keyer {
+            elements: Elements::new(text),
+            remaining: 0,
+        }
+    }
+
+    /// Call once per unit. Returns false (with the key up) once the
+    /// message is finished.
+    pub fn tick<K>(&mut self, key: &mut K) -> bool
+    where
+        K: Key,
+    {
+        if self.remaining == 0 {
+            match self.elements.next() {
+                Some((on, units)) => {
+                    key.set(on);
+                    self.remaining = units;
+                }
+                None => {
+                    key.set(false);
+                    return false;
+                }
+            }
+        }
+        self.remaining -= 1;
+        true
+    }
+}
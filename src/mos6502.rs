@@ -0,0 +1,778 @@
+//! A 6502 processor.
+//!
+//! This is the NMOS part, as in the Apple II, the BBC Micro and the
+//! Commodore 64, with decimal mode and the indirect `JMP` page wrapping bug
+//! but without the undocumented opcodes. Each instruction takes as many
+//! cycles as the data sheet says, including the extra one for crossing a
+//! page and for taken branches, but we do all the work in one go rather
+//! than cycle by cycle - which is close enough to keep time for anything
+//! that isn't counting cycles to race the video beam.
+//!
+//! The processor sees the rest of the machine through a `Bus`, which decides
+//! what is RAM, what is ROM and what is a peripheral.
+
+/// Everything outside the processor.
+pub trait Bus {
+    /// Read a byte. Peripherals may notice being read.
+    fn read(&mut self, address: u16) -> u8;
+
+    /// Write a byte. Writes to ROM should be ignored.
+    fn write(&mut self, address: u16, value: u8);
+}
+
+/// Carry
+pub const FLAG_C: u8 = 1 << 0;
+/// Zero
+pub const FLAG_Z: u8 = 1 << 1;
+/// Interrupts disabled
+pub const FLAG_I: u8 = 1 << 2;
+/// Decimal mode
+pub const FLAG_D: u8 = 1 << 3;
+/// Only seen on the stack, set by `BRK` and `PHP`
+pub const FLAG_B: u8 = 1 << 4;
+/// Always set
+pub const FLAG_U: u8 = 1 << 5;
+/// Overflow
+pub const FLAG_V: u8 = 1 << 6;
+/// Negative
+pub const FLAG_N: u8 = 1 << 7;
+
+const NMI_VECTOR: u16 = 0xFFFA;
+const RESET_VECTOR: u16 = 0xFFFC;
+const IRQ_VECTOR: u16 = 0xFFFE;
+
+/// The stack lives in page one.
+const STACK_PAGE: u16 = 0x0100;
+
+/// Something went wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// One of the undocumented opcodes, at the given address. Some of these
+    /// lock up a real 6502, so we stop rather than guess.
+    IllegalOpcode(u8, u16),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Adc,
+    And,
+    Asl,
+    Bcc,
+    Bcs,
+    Beq,
+    Bit,
+    Bmi,
+    Bne,
+    Bpl,
+    Brk,
+    Bvc,
+    Bvs,
+    Clc,
+    Cld,
+    Cli,
+    Clv,
+    Cmp,
+    Cpx,
+    Cpy,
+    Dec,
+    Dex,
+    Dey,
+    Eor,
+    Inc,
+    Inx,
+    Iny,
+    Jmp,
+    Jsr,
+    Lda,
+    Ldx,
+    Ldy,
+    Lsr,
+    Nop,
+    Ora,
+    Pha,
+    Php,
+    Pla,
+    Plp,
+    Rol,
+    Ror,
+    Rti,
+    Rts,
+    Sbc,
+    Sec,
+    Sed,
+    Sei,
+    Sta,
+    Stx,
+    Sty,
+    Tax,
+    Tay,
+    Tsx,
+    Txa,
+    Txs,
+    Tya,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Implied,
+    Accumulator,
+    Immediate,
+    ZeroPage,
+    ZeroPageX,
+    ZeroPageY,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    Indirect,
+    IndirectX,
+    IndirectY,
+    Relative,
+}
+
+/// The registers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cpu {
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    /// The stack pointer, an offset into page one
+    pub s: u8,
+    /// The status flags (`FLAG_x`)
+    pub p: u8,
+    pub pc: u16,
+    /// How many cycles we've run, which wraps round
+    pub cycles: u32,
+}
+
+impl Cpu {
+    pub fn new() -> Cpu {
+        Cpu {
+            a: 0,
+            x: 0,
+            y: 0,
+            s: 0xFD,
+            p: FLAG_U | FLAG_I,
+            pc: 0,
+            cycles: 0,
+        }
+    }
+
+    /// Start again from the reset vector.
+    pub fn reset<B: Bus>(&mut self, bus: &mut B) {
+        self.s = 0xFD;
+        self.p = FLAG_U | FLAG_I;
+        self.pc = read_word(bus, RESET_VECTOR);
+        self.cycles = self.cycles.wrapping_add(7);
+    }
+
+    /// Raise the interrupt request line, for one instruction's worth. It's
+    /// ignored if interrupts are disabled.
+    pub fn irq<B: Bus>(&mut self, bus: &mut B) {
+        if self.p & FLAG_I == 0 {
+            self.interrupt(bus, IRQ_VECTOR, false);
+            self.cycles = self.cycles.wrapping_add(7);
+        }
+    }
+
+    /// The non-maskable interrupt.
+    pub fn nmi<B: Bus>(&mut self, bus: &mut B) {
+        self.interrupt(bus, NMI_VECTOR, false);
+        self.cycles = self.cycles.wrapping_add(7);
+    }
+
+    /// Run one instruction. Returns how many cycles it took.
+    pub fn step<B: Bus>(&mut self, bus: &mut B) -> Result<u32, Error> {
+        use self::Op::*;
+        let start = self.pc;
+        let opcode = self.fetch(bus);
+        let (op, mode, mut cycles) = match decode(opcode) {
+            Some(decoded) => decoded,
+            None => {
+                self.pc = start;
+                return Err(Error::IllegalOpcode(opcode, start));
+            }
+        };
+        let (address, crossed) = self.address(bus, mode);
+        // Reads pay for a page crossing; stores and read-modify-writes
+        // always take the long way round and it's in their base count
+        let reads = match op {
+            Adc | And | Cmp | Eor | Lda | Ldx | Ldy | Ora | Sbc => true,
+            _ => false,
+        };
+        if crossed && reads {
+            cycles += 1;
+        }
+
+        match op {
+            Adc => {
+                let value = self.operand(bus, mode, address);
+                self.adc(value);
+            }
+            And => {
+                let value = self.operand(bus, mode, address);
+                self.a &= value;
+                let a = self.a;
+                self.set_nz(a);
+            }
+            Asl => self.modify(bus, mode, address, |cpu, value| {
+                cpu.set_flag(FLAG_C, value & 0x80 != 0);
+                value << 1
+            }),
+            Bcc => cycles += self.branch(address, FLAG_C, false),
+            Bcs => cycles += self.branch(address, FLAG_C, true),
+            Beq => cycles += self.branch(address, FLAG_Z, true),
+            Bmi => cycles += self.branch(address, FLAG_N, true),
+            Bne => cycles += self.branch(address, FLAG_Z, false),
+            Bpl => cycles += self.branch(address, FLAG_N, false),
+            Bvc => cycles += self.branch(address, FLAG_V, false),
+            Bvs => cycles += self.branch(address, FLAG_V, true),
+            Bit => {
+                let value = bus.read(address);
+                let zero = self.a & value == 0;
+                self.set_flag(FLAG_Z, zero);
+                self.p = (self.p & !(FLAG_N | FLAG_V)) | (value & (FLAG_N | FLAG_V));
+            }
+            Brk => {
+                // BRK has a padding byte after it
+                self.pc = self.pc.wrapping_add(1);
+                self.interrupt(bus, IRQ_VECTOR, true);
+            }
+            Clc => self.set_flag(FLAG_C, false),
+            Cld => self.set_flag(FLAG_D, false),
+            Cli => self.set_flag(FLAG_I, false),
+            Clv => self.set_flag(FLAG_V, false),
+            Cmp => {
+                let (a, value) = (self.a, self.operand(bus, mode, address));
+                self.compare(a, value);
+            }
+            Cpx => {
+                let (x, value) = (self.x, self.operand(bus, mode, address));
+                self.compare(x, value);
+            }
+            Cpy => {
+                let (y, value) = (self.y, self.operand(bus, mode, address));
+                self.compare(y, value);
+            }
+            Dec => self.modify(bus, mode, address, |_, value| value.wrapping_sub(1)),
+            Dex => {
+                let x = self.x.wrapping_sub(1);
+                self.x = x;
+                self.set_nz(x);
+            }
+            Dey => {
+                let y = self.y.wrapping_sub(1);
+                self.y = y;
+                self.set_nz(y);
+            }
+            Eor => {
+                let value = self.operand(bus, mode, address);
+                self.a ^= value;
+                let a = self.a;
+                self.set_nz(a);
+            }
+            Inc => self.modify(bus, mode, address, |_, value| value.wrapping_add(1)),
+            Inx => {
+                let x = self.x.wrapping_add(1);
+                self.x = x;
+                self.set_nz(x);
+            }
+            Iny => {
+                let y = self.y.wrapping_add(1);
+                self.y = y;
+                self.set_nz(y);
+            }
+            Jmp => self.pc = address,
+            Jsr => {
+                // The return address is the last byte of the JSR
+                let pc = self.pc.wrapping_sub(1);
+                self.push(bus, (pc >> 8) as u8);
+                self.push(bus, pc as u8);
+                self.pc = address;
+            }
+            Lda => {
+                let value = self.operand(bus, mode, address);
+                self.a = value;
+                self.set_nz(value);
+            }
+            Ldx => {
+                let value = self.operand(bus, mode, address);
+                self.x = value;
+                self.set_nz(value);
+            }
+            Ldy => {
+                let value = self.operand(bus, mode, address);
+                self.y = value;
+                self.set_nz(value);
+            }
+            Lsr => self.modify(bus, mode, address, |cpu, value| {
+                cpu.set_flag(FLAG_C, value & 0x01 != 0);
+                value >> 1
+            }),
+            Nop => {}
+            Ora => {
+                let value = self.operand(bus, mode, address);
+                self.a |= value;
+                let a = self.a;
+                self.set_nz(a);
+            }
+            Pha => {
+                let a = self.a;
+                self.push(bus, a);
+            }
+            Php => {
+                let p = self.p | FLAG_B | FLAG_U;
+                self.push(bus, p);
+            }
+            Pla => {
+                let value = self.pull(bus);
+                self.a = value;
+                self.set_nz(value);
+            }
+            Plp => {
+                let value = self.pull(bus);
+                self.p = (value & !FLAG_B) | FLAG_U;
+            }
+            Rol => self.modify(bus, mode, address, |cpu, value| {
+                let carry = cpu.p & FLAG_C;
+                cpu.set_flag(FLAG_C, value & 0x80 != 0);
+                (value << 1) | carry
+            }),
+            Ror => self.modify(bus, mode, address, |cpu, value| {
+                let carry = (cpu.p & FLAG_C) << 7;
+                cpu.set_flag(FLAG_C, value & 0x01 != 0);
+                (value >> 1) | carry
+            }),
+            Rti => {
+                let value = self.pull(bus);
+                self.p = (value & !FLAG_B) | FLAG_U;
+                let low = self.pull(bus) as u16;
+                let high = self.pull(bus) as u16;
+                self.pc = (high << 8) | low;
+            }
+            Rts => {
+                let low = self.pull(bus) as u16;
+                let high = self.pull(bus) as u16;
+                self.pc = ((high << 8) | low).wrapping_add(1);
+            }
+            Sbc => {
+                let value = self.operand(bus, mode, address);
+                self.sbc(value);
+            }
+            Sec => self.set_flag(FLAG_C, true),
+            Sed => self.set_flag(FLAG_D, true),
+            Sei => self.set_flag(FLAG_I, true),
+            Sta => bus.write(address, self.a),
+            Stx => bus.write(address, self.x),
+            Sty => bus.write(address, self.y),
+            Tax => {
+                let a = self.a;
+                self.x = a;
+                self.set_nz(a);
+            }
+            Tay => {
+                let a = self.a;
+                self.y = a;
+                self.set_nz(a);
+            }
+            Tsx => {
+                let s = self.s;
+                self.x = s;
+                self.set_nz(s);
+            }
+            Txa => {
+                let x = self.x;
+                self.a = x;
+                self.set_nz(x);
+            }
+            // The only transfer that leaves the flags alone
+            Txs => self.s = self.x,
+            Tya => {
+                let y = self.y;
+                self.a = y;
+                self.set_nz(y);
+            }
+        }
+
+        self.cycles = self.cycles.wrapping_add(cycles);
+        Ok(cycles)
+    }
+
+    /// Work out where the operand is, and whether indexing crossed a page.
+    /// For immediate mode, that's where the byte after the opcode is; for
+    /// branches, it's the destination.
+    fn address<B: Bus>(&mut self, bus: &mut B, mode: Mode) -> (u16, bool) {
+        match mode {
+            Mode::Implied | Mode::Accumulator => (0, false),
+            Mode::Immediate => {
+                let address = self.pc;
+                self.pc = self.pc.wrapping_add(1);
+                (address, false)
+            }
+            Mode::ZeroPage => (self.fetch(bus) as u16, false),
+            Mode::ZeroPageX => (self.fetch(bus).wrapping_add(self.x) as u16, false),
+            Mode::ZeroPageY => (self.fetch(bus).wrapping_add(self.y) as u16, false),
+            Mode::Absolute => (self.fetch_word(bus), false),
+            Mode::AbsoluteX => {
+                let base = self.fetch_word(bus);
+                indexed(base, self.x)
+            }
+            Mode::AbsoluteY => {
+                let base = self.fetch_word(bus);
+                indexed(base, self.y)
+            }
+            Mode::Indirect => {
+                // The high byte doesn't carry into the next page
+                let pointer = self.fetch_word(bus);
+                let low = bus.read(pointer) as u16;
+                let high = bus.read((pointer & 0xFF00) | (pointer.wrapping_add(1) & 0x00FF));
+                ((high as u16) << 8 | low, false)
+            }
+            Mode::IndirectX => {
+                let pointer = self.fetch(bus).wrapping_add(self.x);
+                (read_zero_page_word(bus, pointer), false)
+            }
+            Mode::IndirectY => {
+                let pointer = self.fetch(bus);
+                let base = read_zero_page_word(bus, pointer);
+                indexed(base, self.y)
+            }
+            Mode::Relative => {
+                let offset = self.fetch(bus) as i8;
+                let target = self.pc.wrapping_add(offset as u16);
+                (target, target & 0xFF00 != self.pc & 0xFF00)
+            }
+        }
+    }
+
+    /// Get the value an instruction works on.
+    fn operand<B: Bus>(&mut self, bus: &mut B, mode: Mode, address: u16) -> u8 {
+        match mode {
+            Mode::Accumulator => self.a,
+            _ => bus.read(address),
+        }
+    }
+
+    /// The shifts, rotates, increments and decrements, on memory or the
+    /// accumulator.
+    fn modify<B, F>(&mut self, bus: &mut B, mode: Mode, address: u16, f: F)
+    where
+        B: Bus,
+        F: FnOnce(&mut Cpu, u8) -> u8,
+    {
+        let value = self.operand(bus, mode, address);
+        let result = f(self, value);
+        self.set_nz(result);
+        match mode {
+            Mode::Accumulator => self.a = result,
+            _ => bus.write(address, result),
+        }
+    }
+
+    /// Take the branch if the flag is as given. Returns the extra cycles.
+    fn branch(&mut self, target: u16, flag: u8, set: bool) -> u32 {
+        if (self.p & flag != 0) != set {
+            return 0;
+        }
+        let crossed = target & 0xFF00 != self.pc & 0xFF00;
+        self.pc = target;
+        if crossed {
+            2
+        } else {
+            1
+        }
+    }
+
+    fn adc(&mut self, value: u8) {
+        let a = self.a;
+        let carry = (self.p & FLAG_C) as u16;
+        let sum = a as u16 + value as u16 + carry;
+        if self.p & FLAG_D == 0 {
+            let result = sum as u8;
+            self.set_flag(FLAG_C, sum > 0xFF);
+            self.set_flag(FLAG_V, !(a ^ value) & (a ^ result) & 0x80 != 0);
+            self.a = result;
+            self.set_nz(result);
+        } else {
+            // The NMOS part sets Z from the binary sum, and N and V part
+            // way through the decimal adjustment
+            let mut low = (a & 0x0F) as u16 + (value & 0x0F) as u16 + carry;
+            let mut high = (a >> 4) as u16 + (value >> 4) as u16;
+            if low > 9 {
+                low += 6;
+            }
+            if low > 0x0F {
+                high += 1;
+            }
+            let partial = ((high << 4) | (low & 0x0F)) as u8;
+            self.set_flag(FLAG_Z, sum as u8 == 0);
+            self.set_flag(FLAG_N, partial & 0x80 != 0);
+            self.set_flag(FLAG_V, !(a ^ value) & (a ^ partial) & 0x80 != 0);
+            if high > 9 {
+                high += 6;
+            }
+            self.set_flag(FLAG_C, high > 0x0F);
+            self.a = ((high << 4) | (low & 0x0F)) as u8;
+        }
+    }
+
+    fn sbc(&mut self, value: u8) {
+        let a = self.a;
+        let borrow = 1 - (self.p & FLAG_C) as i16;
+        let difference = a as i16 - value as i16 - borrow;
+        let result = difference as u8;
+        // The NMOS part sets all the flags from the binary result
+        self.set_flag(FLAG_C, difference >= 0);
+        self.set_flag(FLAG_V, (a ^ value) & (a ^ result) & 0x80 != 0);
+        self.set_nz(result);
+        if self.p & FLAG_D == 0 {
+            self.a = result;
+        } else {
+            let mut low = (a & 0x0F) as i16 - (value & 0x0F) as i16 - borrow;
+            let mut high = (a >> 4) as i16 - (value >> 4) as i16;
+            if low < 0 {
+                low -= 6;
+                high -= 1;
+            }
+            if high < 0 {
+                high -= 6;
+            }
+            self.a = ((high << 4) | (low & 0x0F)) as u8;
+        }
+    }
+
+    fn compare(&mut self, register: u8, value: u8) {
+        self.set_flag(FLAG_C, register >= value);
+        self.set_nz(register.wrapping_sub(value));
+    }
+
+    /// Push the program counter and flags and jump through a vector.
+    fn interrupt<B: Bus>(&mut self, bus: &mut B, vector: u16, brk: bool) {
+        let pc = self.pc;
+        self.push(bus, (pc >> 8) as u8);
+        self.push(bus, pc as u8);
+        let p = if brk {
+            self.p | FLAG_B | FLAG_U
+        } else {
+            (self.p & !FLAG_B) | FLAG_U
+        };
+        self.push(bus, p);
+        self.p |= FLAG_I;
+        self.pc = read_word(bus, vector);
+    }
+
+    fn fetch<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        let value = bus.read(self.pc);
+        self.pc = self.pc.wrapping_add(1);
+        value
+    }
+
+    fn fetch_word<B: Bus>(&mut self, bus: &mut B) -> u16 {
+        let low = self.fetch(bus) as u16;
+        let high = self.fetch(bus) as u16;
+        (high << 8) | low
+    }
+
+    fn push<B: Bus>(&mut self, bus: &mut B, value: u8) {
+        bus.write(STACK_PAGE | self.s as u16, value);
+        self.s = self.s.wrapping_sub(1);
+    }
+
+    fn pull<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        self.s = self.s.wrapping_add(1);
+        bus.read(STACK_PAGE | self.s as u16)
+    }
+
+    fn set_flag(&mut self, flag: u8, set: bool) {
+        if set {
+            self.p |= flag;
+        } else {
+            self.p &= !flag;
+        }
+    }
+
+    fn set_nz(&mut self, value: u8) {
+        self.set_flag(FLAG_Z, value == 0);
+        self.set_flag(FLAG_N, value & 0x80 != 0);
+    }
+}
+
+/// Add an index to a base address, noting if we went into the next page.
+fn indexed(base: u16, index: u8) -> (u16, bool) {
+    let address = base.wrapping_add(index as u16);
+    (address, address & 0xFF00 != base & 0xFF00)
+}
+
+fn read_word<B: Bus>(bus: &mut B, address: u16) -> u16 {
+    let low = bus.read(address) as u16;
+    let high = bus.read(address.wrapping_add(1)) as u16;
+    (high << 8) | low
+}
+
+/// Pointers in the zero page wrap round within it.
+fn read_zero_page_word<B: Bus>(bus: &mut B, pointer: u8) -> u16 {
+    let low = bus.read(pointer as u16) as u16;
+    let high = bus.read(pointer.wrapping_add(1) as u16) as u16;
+    (high << 8) | low
+}
+
+/// What an opcode does, how it finds its operand and how many cycles it
+/// takes before any page crossing or branch penalties.
+fn decode(opcode: u8) -> Option<(Op, Mode, u32)> {
+    use self::Mode::*;
+    use self::Op::*;
+    let decoded = match opcode {
+        0x00 => (Brk, Implied, 7),
+        0x01 => (Ora, IndirectX, 6),
+        0x05 => (Ora, ZeroPage, 3),
+        0x06 => (Asl, ZeroPage, 5),
+        0x08 => (Php, Implied, 3),
+        0x09 => (Ora, Immediate, 2),
+        0x0A => (Asl, Accumulator, 2),
+        0x0D => (Ora, Absolute, 4),
+        0x0E => (Asl, Absolute, 6),
+        0x10 => (Bpl, Relative, 2),
+        0x11 => (Ora, IndirectY, 5),
+        0x15 => (Ora, ZeroPageX, 4),
+        0x16 => (Asl, ZeroPageX, 6),
+        0x18 => (Clc, Implied, 2),
+        0x19 => (Ora, AbsoluteY, 4),
+        0x1D => (Ora, AbsoluteX, 4),
+        0x1E => (Asl, AbsoluteX, 7),
+        0x20 => (Jsr, Absolute, 6),
+        0x21 => (And, IndirectX, 6),
+        0x24 => (Bit, ZeroPage, 3),
+        0x25 => (And, ZeroPage, 3),
+        0x26 => (Rol, ZeroPage, 5),
+        0x28 => (Plp, Implied, 4),
+        0x29 => (And, Immediate, 2),
+        0x2A => (Rol, Accumulator, 2),
+        0x2C => (Bit, Absolute, 4),
+        0x2D => (And, Absolute, 4),
+        0x2E => (Rol, Absolute, 6),
+        0x30 => (Bmi, Relative, 2),
+        0x31 => (And, IndirectY, 5),
+        0x35 => (And, ZeroPageX, 4),
+        0x36 => (Rol, ZeroPageX, 6),
+        0x38 => (Sec, Implied, 2),
+        0x39 => (And, AbsoluteY, 4),
+        0x3D => (And, AbsoluteX, 4),
+        0x3E => (Rol, AbsoluteX, 7),
+        0x40 => (Rti, Implied, 6),
+        0x41 => (Eor, IndirectX, 6),
+        0x45 => (Eor, ZeroPage, 3),
+        0x46 => (Lsr, ZeroPage, 5),
+        0x48 => (Pha, Implied, 3),
+        0x49 => (Eor, Immediate, 2),
+        0x4A => (Lsr, Accumulator, 2),
+        0x4C => (Jmp, Absolute, 3),
+        0x4D => (Eor, Absolute, 4),
+        0x4E => (Lsr, Absolute, 6),
+        0x50 => (Bvc, Relative, 2),
+        0x51 => (Eor, IndirectY, 5),
+        0x55 => (Eor, ZeroPageX, 4),
+        0x56 => (Lsr, ZeroPageX, 6),
+        0x58 => (Cli, Implied, 2),
+        0x59 => (Eor, AbsoluteY, 4),
+        0x5D => (Eor, AbsoluteX, 4),
+        0x5E => (Lsr, AbsoluteX, 7),
+        0x60 => (Rts, Implied, 6),
+        0x61 => (Adc, IndirectX, 6),
+        0x65 => (Adc, ZeroPage, 3),
+        0x66 => (Ror, ZeroPage, 5),
+        0x68 => (Pla, Implied, 4),
+        0x69 => (Adc, Immediate, 2),
+        0x6A => (Ror, Accumulator, 2),
+        0x6C => (Jmp, Indirect, 5),
+        0x6D => (Adc, Absolute, 4),
+        0x6E => (Ror, Absolute, 6),
+        0x70 => (Bvs, Relative, 2),
+        0x71 => (Adc, IndirectY, 5),
+        0x75 => (Adc, ZeroPageX, 4),
+        0x76 => (Ror, ZeroPageX, 6),
+        0x78 => (Sei, Implied, 2),
+        0x79 => (Adc, AbsoluteY, 4),
+        0x7D => (Adc, AbsoluteX, 4),
+        0x7E => (Ror, AbsoluteX, 7),
+        0x81 => (Sta, IndirectX, 6),
+        0x84 => (Sty, ZeroPage, 3),
+        0x85 => (Sta, ZeroPage, 3),
+        0x86 => (Stx, ZeroPage, 3),
+        0x88 => (Dey, Implied, 2),
+        0x8A => (Txa, Implied, 2),
+        0x8C => (Sty, Absolute, 4),
+        0x8D => (Sta, Absolute, 4),
+        0x8E => (Stx, Absolute, 4),
+        0x90 => (Bcc, Relative, 2),
+        0x91 => (Sta, IndirectY, 6),
+        0x94 => (Sty, ZeroPageX, 4),
+        0x95 => (Sta, ZeroPageX, 4),
+        0x96 => (Stx, ZeroPageY, 4),
+        0x98 => (Tya, Implied, 2),
+        0x99 => (Sta, AbsoluteY, 5),
+        0x9A => (Txs, Implied, 2),
+        0x9D => (Sta, AbsoluteX, 5),
+        0xA0 => (Ldy, Immediate, 2),
+        0xA1 => (Lda, IndirectX, 6),
+        0xA2 => (Ldx, Immediate, 2),
+        0xA4 => (Ldy, ZeroPage, 3),
+        0xA5 => (Lda, ZeroPage, 3),
+        0xA6 => (Ldx, ZeroPage, 3),
+        0xA8 => (Tay, Implied, 2),
+        0xA9 => (Lda, Immediate, 2),
+        0xAA => (Tax, Implied, 2),
+        0xAC => (Ldy, Absolute, 4),
+        0xAD => (Lda, Absolute, 4),
+        0xAE => (Ldx, Absolute, 4),
+        0xB0 => (Bcs, Relative, 2),
+        0xB1 => (Lda, IndirectY, 5),
+        0xB4 => (Ldy, ZeroPageX, 4),
+        0xB5 => (Lda, ZeroPageX, 4),
+        0xB6 => (Ldx, ZeroPageY, 4),
+        0xB8 => (Clv, Implied, 2),
+        0xB9 => (Lda, AbsoluteY, 4),
+        0xBA => (Tsx, Implied, 2),
+        0xBC => (Ldy, AbsoluteX, 4),
+        0xBD => (Lda, AbsoluteX, 4),
+        0xBE => (Ldx, AbsoluteY, 4),
+        0xC0 => (Cpy, Immediate, 2),
+        0xC1 => (Cmp, IndirectX, 6),
+        0xC4 => (Cpy, ZeroPage, 3),
+        0xC5 => (Cmp, ZeroPage, 3),
+        0xC6 => (Dec, ZeroPage, 5),
+        0xC8 => (Iny, Implied, 2),
+        0xC9 => (Cmp, Immediate, 2),
+        0xCA => (Dex, Implied, 2),
+        0xCC => (Cpy, Absolute, 4),
+        0xCD => (Cmp, Absolute, 4),
+        0xCE => (Dec, Absolute, 6),
+        0xD0 => (Bne, Relative, 2),
+        0xD1 => (Cmp, IndirectY, 5),
+        0xD5 => (Cmp, ZeroPageX, 4),
+        0xD6 => (Dec, ZeroPageX, 6),
+        0xD8 => (Cld, Implied, 2),
+        0xD9 => (Cmp, AbsoluteY, 4),
+        0xDD => (Cmp, AbsoluteX, 4),
+        0xDE => (Dec, AbsoluteX, 7),
+        0xE0 => (Cpx, Immediate, 2),
+        0xE1 => (Sbc, IndirectX, 6),
+        0xE4 => (Cpx, ZeroPage, 3),
+        0xE5 => (Sbc, ZeroPage, 3),
+        0xE6 => (Inc, ZeroPage, 5),
+        0xE8 => (Inx, Implied, 2),
+        0xE9 => (Sbc, Immediate, 2),
+        0xEA => (Nop, Implied, 2),
+        0xEC => (Cpx, Absolute, 4),
+        0xED => (Sbc, Absolute, 4),
+        0xEE => (Inc, Absolute, 6),
+        0xF0 => (Beq, Relative, 2),
+        0xF1 => (Sbc, IndirectY, 5),
+        0xF5 => (Sbc, ZeroPageX, 4),
+        0xF6 => (Inc, ZeroPageX, 6),
+        0xF8 => (Sed, Implied, 2),
+        0xF9 => (Sbc, AbsoluteY, 4),
+        0xFD => (Sbc, AbsoluteX, 4),
+        0xFE => (Inc, AbsoluteX, 7),
+        _ => return None,
+    };
+    Some(decoded)
+}
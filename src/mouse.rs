@@ -0,0 +1,174 @@
+//! A PS/2 mouse, with its position kept in VGA screen coordinates.
+//!
+//! The clock goes to PC6 and the data to PC7, with 4.7k pull-ups to the
+//! mouse's 5V (see `ps2`). `init` turns on data reporting, after which the
+//! mouse sends a three byte packet whenever it moves or a button changes:
+//!
+//! | Byte | Bits                                                    |
+//! |------|---------------------------------------------------------|
+//! | 0    | Y overflow, X overflow, Y sign, X sign, 1, M, R, L      |
+//! | 1    | X movement (the low 8 bits of 9)                        |
+//! | 2    | Y movement, up is positive                              |
+//!
+//! Bit 3 of the first byte is always set, which is how we find our place
+//! again after a lost byte. Packets that overflowed are thrown away, as
+//! the movement in them is nonsense. The position is clamped to the
+//! screen, and `pointer` will draw it.
+//!
+//! The caller must enable the GPIOC interrupt (below the VGA timers), then
+//! call `init` - the mouse answers straight away, and we mustn't miss it -
+//! and hook up the handler:
+//!
+//! ``` ignore
+//! interrupt!(GPIOC, mouse::gpioc_isr);
+//! ```
+//!
+//! VSYNC is on PC4 by default, but it doesn't use the port's interrupt, so
+//! they get along.
+
+use config::{Pin, Port};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use graphics::{VGA_HEIGHT, VGA_WIDTH};
+use hal::sysctl::Clocks;
+use ps2;
+
+const CLOCK: Pin = Pin {
+    port: Port::C,
+    bit: 6,
+};
+const DATA: Pin = Pin {
+    port: Port::C,
+    bit: 7,
+};
+
+/// Enable Data Reporting
+const CMD_ENABLE: u8 = 0xF4;
+/// What the mouse says to a command
+const ACK: u8 = 0xFA;
+
+// The first byte of a packet
+const LEFT: u8 = 1 << 0;
+const RIGHT: u8 = 1 << 1;
+const MIDDLE: u8 = 1 << 2;
+const ALWAYS_ONE: u8 = 1 << 3;
+const X_SIGN: u8 = 1 << 4;
+const Y_SIGN: u8 = 1 << 5;
+const OVERFLOW: u8 = (1 << 6) | (1 << 7);
+
+/// Which buttons are down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Buttons {
+    pub left: bool,
+    pub right: bool,
+    pub middle: bool,
+}
+
+static mut HOST: Option<ps2::Host> = None;
+static mut PACKET: [u8; 3] = [0; 3];
+static mut PACKET_LEN: usize = 0;
+/// Set once the mouse has acknowledged `CMD_ENABLE`
+static CONNECTED: AtomicBool = AtomicBool::new(false);
+static X: AtomicUsize = AtomicUsize::new(VGA_WIDTH / 2);
+static Y: AtomicUsize = AtomicUsize::new(VGA_HEIGHT / 2);
+static BUTTONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Set up the pins and ask the mouse to start sending packets. Give it
+/// half a second after power up first, as it's busy testing itself.
+pub fn init(clocks: &Clocks) {
+    unsafe {
+        HOST = Some(ps2::Host::new(CLOCK, DATA, clocks));
+        if let Some(host) = HOST.as_mut() {
+            host.send(CMD_ENABLE);
+        }
+    }
+}
+
+/// Has the mouse answered `init`?
+pub fn is_connected() -> bool {
+    CONNECTED.load(Ordering::Relaxed)
+}
+
+/// Where the mouse is, from (0, 0) at the top left to (`VGA_WIDTH` - 1,
+/// `VGA_HEIGHT` - 1). It starts in the middle.
+pub fn position() -> (usize, usize) {
+    (X.load(Ordering::Relaxed), Y.load(Ordering::Relaxed))
+}
+
+/// Which buttons are down.
+pub fn buttons() -> Buttons {
+    let bits = BUTTONS.load(Ordering::Relaxed) as u8;
+    Buttons {
+        left: bits & LEFT != 0,
+        right: bits & RIGHT != 0,
+        middle: bits & MIDDLE != 0,
+    }
+}
+
+/// Frames that didn't arrive in one piece, from `ps2::Host::errors`.
+pub fn errors() -> u32 {
+    unsafe { HOST.as_ref().map(|h| h.errors()).unwrap_or(0) }
+}
+
+/// A 9-bit two's complement movement.
+fn movement(low: u8, negative: bool) -> isize {
+    if negative {
+        low as isize - 256
+    } else {
+        low as isize
+    }
+}
+
+/// Keep a coordinate inside `0..limit`.
+fn clamp(position: isize, limit: usize) -> usize {
+    position.max(0).min(limit as isize - 1) as usize
+}
+
+/// Make sense of a whole packet.
+fn handle_packet(packet: &[u8; 3]) {
+    BUTTONS.store(usize::from(packet[0] & (LEFT | RIGHT | MIDDLE)), Ordering::Relaxed);
+    if packet[0] & OVERFLOW != 0 {
+        return;
+    }
+    let dx = movement(packet[1], packet[0] & X_SIGN != 0);
+    let dy = movement(packet[2], packet[0] & Y_SIGN != 0);
+    // The mouse counts up the way, and the screen counts down
+    let x = clamp(X.load(Ordering::Relaxed) as isize + dx, VGA_WIDTH);
+    let y = clamp(Y.load(Ordering::Relaxed) as isize - dy, VGA_HEIGHT);
+    X.store(x, Ordering::Relaxed);
+    Y.store(y, Ordering::Relaxed);
+}
+
+/// Make sense of a byte from the mouse.
+fn handle_byte(byte: u8) {
+    if !CONNECTED.load(Ordering::Relaxed) {
+        // Anything before the answer to `init` is left over from the
+        // self-test
+        if byte == ACK {
+            CONNECTED.store(true, Ordering::Relaxed);
+        }
+        return;
+    }
+    unsafe {
+        if PACKET_LEN == 0 && byte & ALWAYS_ONE == 0 {
+            return;
+        }
+        PACKET[PACKET_LEN] = byte;
+        PACKET_LEN += 1;
+        if PACKET_LEN == PACKET.len() {
+            PACKET_LEN = 0;
+            handle_packet(&PACKET);
+        }
+    }
+}
+
+/// Clock in a bit, and deal with it if it finishes a byte. The GPIOC
+/// handler.
+pub fn gpioc_isr() {
+    let byte = match unsafe { HOST.as_mut() } {
+        Some(host) if host.take_interrupt() => host.clock_falling(),
+        _ => None,
+    };
+    if let Some(byte) = byte {
+        handle_byte(byte);
+    }
+}
@@ -0,0 +1,75 @@
+//! A guard at the bottom of the stack, using the MPU.
+//!
+//! The stack grows down towards the statics, and nothing stops it going
+//! past them - on the VGA demos, the first thing it usually hits is the
+//! frame buffer, and you get garbage on the screen and a crash some time
+//! later. `guard_stack` makes the bottom `GUARD_SIZE` bytes of the stack
+//! no-access instead, so the first push into them faults straight away.
+//!
+//! MemManage faults are left disabled, so the fault escalates to HardFault.
+//! The MPU is off while the HardFault handler runs, so the handler has room
+//! to run in the guard itself (which is why the guard's bigger than it
+//! strictly needs to be). Use `demo::fault::hard_fault` and you'll see:
+//!
+//! ```text
+//! *** HardFault ***
+//! ...
+//!   DERR (data access violation)
+//! MMFAR = 0x200016fc
+//! HFSR = 0x40000000
+//!   FORCED (escalated from the CFSR fault)
+//! ```
+//!
+//! (or `MSTKE` if it was an interrupt that pushed us over) with `MMFAR` in
+//! the guard. If you're painting the stack with `demo::stack`, paint above
+//! the guard, or measuring it will fault:
+//!
+//! ``` ignore
+//! let floor = mpu::guard_stack(&mut cp.MPU);
+//! stack::paint_above(floor);
+//! ```
+
+use cortex_m::asm;
+use cortex_m::peripheral::MPU;
+use cortex_m_rt;
+
+/// How much of the stack to guard. A power of two, at least 32.
+pub const GUARD_SIZE: usize = 512;
+
+/// The MPU region we use. Higher numbered regions win where they overlap,
+/// so the last one means nothing else can open the guard up again.
+const REGION: u32 = 7;
+
+/// MPU_CTRL: enable
+const CTRL_ENABLE: u32 = 1 << 0;
+/// MPU_CTRL: use the default memory map where there's no region
+const CTRL_PRIVDEFENA: u32 = 1 << 2;
+
+/// MPU_RASR: region enable
+const RASR_ENABLE: u32 = 1 << 0;
+/// MPU_RASR: SRAM attributes - shareable and cacheable (TEX=0, S=1, C=1)
+const RASR_SRAM: u32 = (1 << 18) | (1 << 17);
+/// MPU_RASR: execute never
+const RASR_XN: u32 = 1 << 28;
+// AP (bits 24..26) of zero means no access, privileged or not
+
+/// Guard the bottom of the stack, and turn the MPU on. Returns the lowest
+/// address the stack can now use.
+pub fn guard_stack(mpu: &mut MPU) -> usize {
+    // Regions have to be aligned to their size
+    let start = cortex_m_rt::heap_start() as usize;
+    let base = (start + GUARD_SIZE - 1) & !(GUARD_SIZE - 1);
+    // SIZE is log2(bytes) - 1
+    let size = GUARD_SIZE.trailing_zeros() - 1;
+    unsafe {
+        mpu.ctrl.write(0);
+        mpu.rnr.write(REGION);
+        mpu.rbar.write(base as u32);
+        mpu.rasr.write(RASR_XN | RASR_SRAM | (size << 1) | RASR_ENABLE);
+        mpu.ctrl.write(CTRL_PRIVDEFENA | CTRL_ENABLE);
+    }
+    // Make sure nothing runs before the MPU has taken notice
+    asm::dsb();
+    asm::isb();
+    base + GUARD_SIZE
+}
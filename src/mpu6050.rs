@@ -0,0 +1,105 @@
+//! Drives an InvenSense MPU-6050 accelerometer and gyroscope over I2C.
+//!
+//! We set it up for +/-2g and +/-250 degrees/s full scale, with the 44 Hz
+//! digital low pass filter, which suits something being waved around by
+//! hand. Readings are returned raw; divide by `ACCEL_LSB_PER_G` and
+//! `GYRO_LSB_PER_DPS` to get real units.
+
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+
+/// The address with AD0 tied low. Tie it high for 0x69.
+pub const DEFAULT_ADDRESS: u8 = 0x68;
+
+/// Accelerometer counts per g at +/-2g full scale.
+pub const ACCEL_LSB_PER_G: i32 = 16_384;
+
+/// Gyro counts per degree per second at +/-250 degrees/s full scale.
+pub const GYRO_LSB_PER_DPS: i32 = 131;
+
+const REG_SMPLRT_DIV: u8 = 0x19;
+const REG_CONFIG: u8 = 0x1A;
+const REG_GYRO_CONFIG: u8 = 0x1B;
+const REG_ACCEL_CONFIG: u8 = 0x1C;
+const REG_ACCEL_XOUT_H: u8 = 0x3B;
+const REG_PWR_MGMT_1: u8 = 0x6B;
+const REG_WHO_AM_I: u8 = 0x75;
+
+/// Wake up, clocked from the X gyro's PLL.
+const PWR_MGMT_1_PLL_X: u8 = 0x01;
+
+/// 44 Hz low pass filter, 1 kHz internal sample rate.
+const CONFIG_DLPF_44HZ: u8 = 0x03;
+
+/// Something went wrong talking to the MPU-6050.
+#[derive(Debug)]
+pub enum Error<E> {
+    /// The I2C bus reported an error
+    I2c(E),
+    /// WHO_AM_I didn't read back as expected
+    WrongChip(u8),
+}
+
+/// One raw reading of all six axes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Reading {
+    pub accel: [i16; 3],
+    pub gyro: [i16; 3],
+    /// Die temperature, raw. Degrees C = (temp / 340) + 36.53
+    pub temp: i16,
+}
+
+pub struct Mpu6050<I2C> {
+    i2c: I2C,
+    address: u8,
+}
+
+impl<I2C, E> Mpu6050<I2C>
+where
+    I2C: Write<Error = E> + WriteRead<Error = E>,
+{
+    /// Check it's there, wake it up and set the ranges, sampling at
+    /// 1 kHz / (1 + `sample_divider`).
+    pub fn new(i2c: I2C, address: u8, sample_divider: u8) -> Result<Self, Error<E>> {
+        let mut imu = Mpu6050 { i2c, address };
+        let mut id = [0u8];
+        imu.i2c
+            .write_read(address, &[REG_WHO_AM_I], &mut id)
+            .map_err(Error::I2c)?;
+        // WHO_AM_I doesn't include AD0, so it's always 0x68
+        if id[0] != DEFAULT_ADDRESS {
+            return Err(Error::WrongChip(id[0]));
+        }
+        imu.write_register(REG_PWR_MGMT_1, PWR_MGMT_1_PLL_X)?;
+        imu.write_register(REG_CONFIG, CONFIG_DLPF_44HZ)?;
+        imu.write_register(REG_SMPLRT_DIV, sample_divider)?;
+        imu.write_register(REG_GYRO_CONFIG, 0x00)?;
+        imu.write_register(REG_ACCEL_CONFIG, 0x00)?;
+        Ok(imu)
+    }
+
+    /// Give the bus back.
+    pub fn free(self) -> I2C {
+        self.i2c
+    }
+
+    /// Read all the sensors in one go, so the values are from the same
+    /// sample.
+    pub fn read(&mut self) -> Result<Reading, Error<E>> {
+        let mut d = [0u8; 14];
+        self.i2c
+            .write_read(self.address, &[REG_ACCEL_XOUT_H], &mut d)
+            .map_err(Error::I2c)?;
+        let be = |i: usize| ((u16::from(d[i]) << 8) | u16::from(d[i + 1])) as i16;
+        Ok(Reading {
+            accel: [be(0), be(2), be(4)],
+            temp: be(6),
+            gyro: [be(8), be(10), be(12)],
+        })
+    }
+
+    fn write_register(&mut self, reg: u8, value: u8) -> Result<(), Error<E>> {
+        self.i2c
+            .write(self.address, &[reg, value])
+            .map_err(Error::I2c)
+    }
+}
@@ -0,0 +1,280 @@
+//! A minimal MQTT 3.1.1 client.
+//!
+//! This only builds and parses packets - it doesn't care how they get to
+//! the broker, so it works over the ESP8266, the ENC28J60 or anything else
+//! with a TCP connection. Open the connection, send `connect`, wait for a
+//! `ConnAck`, and then `publish` and `subscribe` as you like. Everything is
+//! QoS 0 (fire and forget), which keeps us from having to store packets for
+//! resending. If you don't send anything for the keep-alive time, send a
+//! `ping_request` or the broker will hang up.
+
+/// The largest packet we'll build or accept.
+pub const MAX_PACKET: usize = 256;
+
+// Packet types, in the top four bits of the first byte
+const CONNECT: u8 = 1;
+const CONNACK: u8 = 2;
+const PUBLISH: u8 = 3;
+const SUBSCRIBE: u8 = 8;
+const SUBACK: u8 = 9;
+const PINGREQ: u8 = 12;
+const PINGRESP: u8 = 13;
+const DISCONNECT: u8 = 14;
+
+const PROTOCOL_LEVEL_3_1_1: u8 = 4;
+
+const CONNECT_CLEAN_SESSION: u8 = 1 << 1;
+const CONNECT_PASSWORD: u8 = 1 << 6;
+const CONNECT_USERNAME: u8 = 1 << 7;
+
+const PUBLISH_RETAIN: u8 = 1 << 0;
+const PUBLISH_QOS_MASK: u8 = 0x3 << 1;
+
+/// SUBSCRIBE has to have these flag bits set.
+const SUBSCRIBE_FLAGS: u8 = 0x2;
+
+/// Something went wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The packet won't fit in the buffer
+    TooLong,
+}
+
+/// A packet from the broker.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Packet<'a> {
+    /// The answer to our `connect`. A code of zero means we're in.
+    ConnAck { session_present: bool, code: u8 },
+    /// A message on a topic we subscribed to.
+    Publish { topic: &'a str, payload: &'a [u8] },
+    /// The answer to a `subscribe`. `granted` is the QoS we got, or 0x80 if
+    /// the broker said no.
+    SubAck { packet_id: u16, granted: u8 },
+    /// The answer to a `ping_request`.
+    PingResp,
+    /// Anything else, by packet type.
+    Other(u8),
+}
+
+/// Builds up a packet in a buffer.
+struct Writer<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Writer<'a> {
+    /// Start a packet. We leave room for the biggest length field we
+    /// support (two bytes) and shuffle down later if it's shorter.
+    fn new(buffer: &'a mut [u8], first_byte: u8) -> Result<Writer<'a>, Error> {
+        if buffer.len() < 3 {
+            return Err(Error::TooLong);
+        }
+        buffer[0] = first_byte;
+        Ok(Writer { buffer, len: 3 })
+    }
+
+    fn byte(&mut self, byte: u8) -> Result<(), Error> {
+        self.bytes(&[byte])
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let end = self.len + bytes.len();
+        if end > self.buffer.len() {
+            return Err(Error::TooLong);
+        }
+        self.buffer[self.len..end].copy_from_slice(bytes);
+        self.len = end;
+        Ok(())
+    }
+
+    fn word(&mut self, word: u16) -> Result<(), Error> {
+        self.bytes(&[(word >> 8) as u8, word as u8])
+    }
+
+    /// Strings have a two byte length on the front.
+    fn string(&mut self, s: &str) -> Result<(), Error> {
+        self.word(s.len() as u16)?;
+        self.bytes(s.as_bytes())
+    }
+
+    /// Fill in the remaining length and return the packet's length.
+    fn finish(self) -> Result<usize, Error> {
+        let remaining = self.len - 3;
+        if remaining < 128 {
+            self.buffer[1] = remaining as u8;
+            for i in 3..self.len {
+                self.buffer[i - 1] = self.buffer[i];
+            }
+            Ok(self.len - 1)
+        } else if remaining < 16384 {
+            self.buffer[1] = (remaining as u8 & 0x7F) | 0x80;
+            self.buffer[2] = (remaining >> 7) as u8;
+            Ok(self.len)
+        } else {
+            Err(Error::TooLong)
+        }
+    }
+}
+
+/// Build a CONNECT packet, asking for a clean session. Returns how much of
+/// `buffer` was used.
+pub fn connect(
+    buffer: &mut [u8],
+    client_id: &str,
+    keep_alive_secs: u16,
+    login: Option<(&str, &str)>,
+) -> Result<usize, Error> {
+    let mut w = Writer::new(buffer, CONNECT << 4)?;
+    w.string("MQTT")?;
+    w.byte(PROTOCOL_LEVEL_3_1_1)?;
+    let mut flags = CONNECT_CLEAN_SESSION;
+    if login.is_some() {
+        flags |= CONNECT_USERNAME | CONNECT_PASSWORD;
+    }
+    w.byte(flags)?;
+    w.word(keep_alive_secs)?;
+    w.string(client_id)?;
+    if let Some((username, password)) = login {
+        w.string(username)?;
+        w.string(password)?;
+    }
+    w.finish()
+}
+
+/// Build a PUBLISH packet, at QoS 0.
+pub fn publish(
+    buffer: &mut [u8],
+    topic: &str,
+    payload: &[u8],
+    retain: bool,
+) -> Result<usize, Error> {
+    let flags = if retain { PUBLISH_RETAIN } else { 0 };
+    let mut w = Writer::new(buffer, (PUBLISH << 4) | flags)?;
+    w.string(topic)?;
+    w.bytes(payload)?;
+    w.finish()
+}
+
+/// Build a SUBSCRIBE packet for one topic (which can have wildcards), at
+/// QoS 0. The `packet_id` comes back in the `SubAck`, and mustn't be zero.
+pub fn subscribe(buffer: &mut [u8], packet_id: u16, topic: &str) -> Result<usize, Error> {
+    let mut w = Writer::new(buffer, (SUBSCRIBE << 4) | SUBSCRIBE_FLAGS)?;
+    w.word(packet_id)?;
+    w.string(topic)?;
+    w.byte(0)?;
+    w.finish()
+}
+
+/// A PINGREQ packet.
+pub fn ping_request() -> [u8; 2] {
+    [PINGREQ << 4, 0]
+}
+
+/// A DISCONNECT packet.
+pub fn disconnect() -> [u8; 2] {
+    [DISCONNECT << 4, 0]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Waiting for the first byte
+    Type,
+    /// Reading the remaining length, with this much read so far
+    Length(u8),
+    /// Reading the rest of the packet
+    Body,
+}
+
+/// Splits the bytes from the broker up into packets.
+pub struct Parser {
+    first_byte: u8,
+    state: State,
+    remaining: usize,
+    buffer: [u8; MAX_PACKET],
+    len: usize,
+}
+
+impl Parser {
+    pub fn new() -> Parser {
+        Parser {
+            first_byte: 0,
+            state: State::Type,
+            remaining: 0,
+            buffer: [0u8; MAX_PACKET],
+            len: 0,
+        }
+    }
+
+    /// Feed in the next byte from the broker. Returns a packet once one is
+    /// complete. Packets too big for us are skipped.
+    pub fn input(&mut self, byte: u8) -> Option<Packet> {
+        match self.state {
+            State::Type => {
+                self.first_byte = byte;
+                self.remaining = 0;
+                self.len = 0;
+                self.state = State::Length(0);
+                None
+            }
+            State::Length(n) => {
+                self.remaining |= usize::from(byte & 0x7F) << (7 * n);
+                if byte & 0x80 != 0 && n < 3 {
+                    self.state = State::Length(n + 1);
+                    None
+                } else if self.remaining == 0 {
+                    self.state = State::Type;
+                    self.decode()
+                } else {
+                    self.state = State::Body;
+                    None
+                }
+            }
+            State::Body => {
+                if self.len < MAX_PACKET {
+                    self.buffer[self.len] = byte;
+                }
+                self.len += 1;
+                if self.len < self.remaining {
+                    return None;
+                }
+                self.state = State::Type;
+                if self.len > MAX_PACKET {
+                    return None;
+                }
+                self.decode()
+            }
+        }
+    }
+
+    fn decode(&self) -> Option<Packet> {
+        let body = &self.buffer[0..self.len];
+        let packet = match self.first_byte >> 4 {
+            CONNACK if body.len() >= 2 => Packet::ConnAck {
+                session_present: body[0] & 1 != 0,
+                code: body[1],
+            },
+            PUBLISH if body.len() >= 2 => {
+                let topic_len = (usize::from(body[0]) << 8) | usize::from(body[1]);
+                let mut payload_start = 2 + topic_len;
+                // Anything above QoS 0 has a packet ID too
+                if self.first_byte & PUBLISH_QOS_MASK != 0 {
+                    payload_start += 2;
+                }
+                if payload_start > body.len() {
+                    return None;
+                }
+                Packet::Publish {
+                    topic: ::core::str::from_utf8(&body[2..2 + topic_len]).ok()?,
+                    payload: &body[payload_start..],
+                }
+            }
+            SUBACK if body.len() >= 3 => Packet::SubAck {
+                packet_id: (u16::from(body[0]) << 8) | u16::from(body[1]),
+                granted: body[2],
+            },
+            PINGRESP => Packet::PingResp,
+            other => Packet::Other(other),
+        };
+        Some(packet)
+    }
+}
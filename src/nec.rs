@@ -0,0 +1,164 @@
+//! The NEC infrared remote control protocol, used by a great many cheap
+//! remotes.
+//!
+//! Everything is made of 'marks' (bursts of 38 kHz carrier, which an
+//! IR receiver module turns into a low output) and 'spaces' (nothing). A
+//! frame is a 9ms mark and a 4.5ms space, then 32 bits LSB first, then a
+//! final 562us mark. Each bit is a 562us mark followed by a 562us space for
+//! a 0 or a 1687us space for a 1. The bits are the address, the inverted
+//! address, the command and the inverted command. 'Extended' NEC uses the
+//! inverted address byte as the top half of a 16-bit address instead. While
+//! a button is held, the remote sends a repeat code every 108ms: a 9ms mark,
+//! a 2.25ms space and a 562us mark.
+
+/// The length of each part, in microseconds.
+pub const LEADER_MARK_US: u32 = 9_000;
+pub const LEADER_SPACE_US: u32 = 4_500;
+pub const REPEAT_SPACE_US: u32 = 2_250;
+pub const BIT_MARK_US: u32 = 562;
+pub const ZERO_SPACE_US: u32 = 562;
+pub const ONE_SPACE_US: u32 = 1_687;
+
+/// A frame, including the gap after it, takes this long.
+pub const FRAME_US: u32 = 108_000;
+
+/// What we heard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// A button press. The address is only 8 bits (with the top byte zero)
+    /// unless the remote uses extended NEC.
+    Command { address: u16, command: u8 },
+    /// The last button is still held down.
+    Repeat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Idle,
+    LeaderSpace,
+    RepeatMark,
+    BitMark,
+    BitSpace,
+}
+
+/// Turns mark and space lengths into events.
+pub struct Decoder {
+    state: State,
+    bits: u32,
+    count: u8,
+}
+
+impl Decoder {
+    pub fn new() -> Decoder {
+        Decoder {
+            state: State::Idle,
+            bits: 0,
+            count: 0,
+        }
+    }
+
+    /// Feed in the length of the mark or space that just finished. Returns
+    /// an event once a frame is complete. Anything that doesn't fit the
+    /// protocol puts us back to waiting for a leader.
+    pub fn pulse(&mut self, mark: bool, us: u32) -> Option<Event> {
+        let (next, event) = match (self.state, mark) {
+            (State::Idle, true) if near(us, LEADER_MARK_US) => (State::LeaderSpace, None),
+            (State::LeaderSpace, false) if near(us, LEADER_SPACE_US) => {
+                self.bits = 0;
+                self.count = 0;
+                (State::BitMark, None)
+            }
+            (State::LeaderSpace, false) if near(us, REPEAT_SPACE_US) => (State::RepeatMark, None),
+            (State::RepeatMark, true) if near(us, BIT_MARK_US) => {
+                (State::Idle, Some(Event::Repeat))
+            }
+            (State::BitMark, true) if near(us, BIT_MARK_US) => (State::BitSpace, None),
+            (State::BitSpace, false) if near(us, ZERO_SPACE_US) || near(us, ONE_SPACE_US) => {
+                if near(us, ONE_SPACE_US) {
+                    self.bits |= 1 << self.count;
+                }
+                self.count += 1;
+                if self.count == 32 {
+                    (State::Idle, decode(self.bits))
+                } else {
+                    (State::BitMark, None)
+                }
+            }
+            // A leader might also be the start of a new frame
+            (_, true) if near(us, LEADER_MARK_US) => (State::LeaderSpace, None),
+            _ => (State::Idle, None),
+        };
+        self.state = next;
+        event
+    }
+}
+
+/// Within 25%?
+fn near(us: u32, expected: u32) -> bool {
+    us > expected - (expected / 4) && us < expected + (expected / 4)
+}
+
+/// Check the 32 received bits and pull out the address and command.
+fn decode(bits: u32) -> Option<Event> {
+    let address_lo = bits as u8;
+    let address_hi = (bits >> 8) as u8;
+    let command = (bits >> 16) as u8;
+    if (bits >> 24) as u8 != !command {
+        return None;
+    }
+    let address = if address_hi == !address_lo {
+        u16::from(address_lo)
+    } else {
+        u16::from(address_lo) | (u16::from(address_hi) << 8)
+    };
+    Some(Event::Command { address, command })
+}
+
+/// Build the 32 bits to send (LSB first) for an address and command.
+/// Addresses over 255 are sent as extended NEC.
+pub fn encode(address: u16, command: u8) -> u32 {
+    let address = if address > 0xFF {
+        u32::from(address)
+    } else {
+        u32::from(address) | (u32::from(!(address as u8)) << 8)
+    };
+    address | (u32::from(command) << 16) | (u32::from(!command) << 24)
+}
+
+/// The marks and spaces that make up one frame, for a transmitter to work
+/// through.
+pub struct Pulses {
+    bits: u32,
+    index: u8,
+}
+
+/// Get the marks and spaces for a frame of 32 bits (from `encode`).
+pub fn pulses(bits: u32) -> Pulses {
+    Pulses { bits, index: 0 }
+}
+
+impl Iterator for Pulses {
+    /// Whether it's a mark, and how long it lasts in microseconds.
+    type Item = (bool, u32);
+
+    fn next(&mut self) -> Option<(bool, u32)> {
+        let pulse = match self.index {
+            0 => (true, LEADER_MARK_US),
+            1 => (false, LEADER_SPACE_US),
+            n @ 2...65 => {
+                let bit = (n - 2) / 2;
+                if n % 2 == 0 {
+                    (true, BIT_MARK_US)
+                } else if self.bits & (1 << bit) != 0 {
+                    (false, ONE_SPACE_US)
+                } else {
+                    (false, ZERO_SPACE_US)
+                }
+            }
+            66 => (true, BIT_MARK_US),
+            _ => return None,
+        };
+        self.index += 1;
+        Some(pulse)
+    }
+}
@@ -0,0 +1,301 @@
+//! Parses the NMEA 0183 sentences that GPS modules send.
+//!
+//! Each sentence is a line like
+//! `$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47`: a
+//! talker (`GP` for GPS, `GN` for multi-constellation, and so on), a type,
+//! comma separated fields and an XOR checksum. We only look at GGA (fix
+//! data) and RMC (the recommended minimum, which has the date) and ignore
+//! the rest. There's no floating point: positions are in millionths of a
+//! degree (north and east positive), which is about 10cm.
+
+use core::fmt;
+use datetime::DateTime;
+
+/// The longest sentence the standard allows, without the CR LF.
+pub const MAX_SENTENCE: usize = 82;
+
+/// A point on the Earth, in millionths of a degree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub latitude: i32,
+    pub longitude: i32,
+}
+
+/// Time of day in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Time {
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+}
+
+/// Global Positioning System Fix Data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gga {
+    pub time: Option<Time>,
+    pub position: Option<Position>,
+    /// 0 = no fix, 1 = GPS, 2 = differential GPS, and some rarer ones
+    pub quality: u8,
+    /// Satellites used in the fix
+    pub satellites: u8,
+    /// Horizontal dilution of precision, in hundredths
+    pub hdop: Option<i32>,
+    /// Height above mean sea level, in decimetres
+    pub altitude: Option<i32>,
+}
+
+/// Recommended Minimum Specific GNSS Data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rmc {
+    /// Has the receiver got a fix?
+    pub valid: bool,
+    pub datetime: Option<DateTime>,
+    pub position: Option<Position>,
+    /// Speed over the ground, in thousandths of a knot
+    pub speed: Option<i32>,
+    /// Course over the ground, in hundredths of a degree from true north
+    pub course: Option<i32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sentence {
+    Gga(Gga),
+    Rmc(Rmc),
+}
+
+/// Collects bytes into sentences.
+pub struct Parser {
+    buffer: [u8; MAX_SENTENCE],
+    len: usize,
+    /// Set when the sentence was too long, so we skip the rest of it.
+    overflow: bool,
+}
+
+impl Parser {
+    pub fn new() -> Parser {
+        Parser {
+            buffer: [0u8; MAX_SENTENCE],
+            len: 0,
+            overflow: false,
+        }
+    }
+
+    /// Feed in the next byte from the GPS. Returns a sentence when a line
+    /// we understand (with a good checksum) is complete.
+    pub fn input(&mut self, byte: u8) -> Option<Sentence> {
+        match byte {
+            b'$' => {
+                self.buffer[0] = byte;
+                self.len = 1;
+                self.overflow = false;
+                None
+            }
+            b'\r' | b'\n' => {
+                let result = if self.len > 0 && !self.overflow {
+                    ::core::str::from_utf8(&self.buffer[0..self.len])
+                        .ok()
+                        .and_then(parse)
+                } else {
+                    None
+                };
+                self.len = 0;
+                result
+            }
+            _ if self.len == 0 => None,
+            _ if self.len == MAX_SENTENCE => {
+                self.overflow = true;
+                None
+            }
+            _ => {
+                self.buffer[self.len] = byte;
+                self.len += 1;
+                None
+            }
+        }
+    }
+}
+
+/// Parse one complete sentence, starting with the `$`. Anything that
+/// isn't a sentence we understand is `None` - this is fed straight from
+/// the serial port, so line noise mustn't make it panic.
+pub fn parse(line: &str) -> Option<Sentence> {
+    // NMEA is all ASCII. Checking that up front means the fields below can
+    // be sliced by byte without landing inside a UTF-8 character.
+    if !line.starts_with('$') || !line.is_ascii() {
+        return None;
+    }
+    let star = line.rfind('*')?;
+    let body = &line[1..star];
+    let checksum = u8::from_str_radix(&line[star + 1..], 16).ok()?;
+    if body.bytes().fold(0, |acc, b| acc ^ b) != checksum {
+        return None;
+    }
+    let mut fields = body.split(',');
+    let kind = fields.next()?;
+    if kind.len() != 5 {
+        return None;
+    }
+    match &kind[2..] {
+        "GGA" => parse_gga(&mut fields).map(Sentence::Gga),
+        "RMC" => parse_rmc(&mut fields).map(Sentence::Rmc),
+        _ => None,
+    }
+}
+
+fn parse_gga<'a, I>(fields: &mut I) -> Option<Gga>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let time = parse_time(fields.next()?);
+    let position = parse_position(fields.next()?, fields.next()?, fields.next()?, fields.next()?);
+    let quality = fields.next()?.parse::<u8>().unwrap_or(0);
+    let satellites = fields.next()?.parse::<u8>().unwrap_or(0);
+    let hdop = fixed(fields.next()?, 2);
+    let altitude = fixed(fields.next()?, 1);
+    Some(Gga {
+        time,
+        position,
+        quality,
+        satellites,
+        hdop,
+        altitude,
+    })
+}
+
+fn parse_rmc<'a, I>(fields: &mut I) -> Option<Rmc>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let time = parse_time(fields.next()?);
+    let valid = fields.next()? == "A";
+    let position = parse_position(fields.next()?, fields.next()?, fields.next()?, fields.next()?);
+    let speed = fixed(fields.next()?, 3);
+    let course = fixed(fields.next()?, 2);
+    let date = fields.next()?;
+    let datetime = match (time, date.len()) {
+        (Some(time), 6) => {
+            let dt = DateTime {
+                day: date[0..2].parse().ok()?,
+                month: date[2..4].parse().ok()?,
+                year: 2000 + date[4..6].parse::<u16>().ok()?,
+                hours: time.hours,
+                minutes: time.minutes,
+                seconds: time.seconds,
+            };
+            if dt.is_valid() {
+                Some(dt)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    };
+    Some(Rmc {
+        valid,
+        datetime,
+        position,
+        speed,
+        course,
+    })
+}
+
+/// `hhmmss` or `hhmmss.ss`
+fn parse_time(field: &str) -> Option<Time> {
+    if field.len() < 6 {
+        return None;
+    }
+    Some(Time {
+        hours: field[0..2].parse().ok()?,
+        minutes: field[2..4].parse().ok()?,
+        seconds: field[4..6].parse().ok()?,
+    })
+}
+
+/// `ddmm.mmmm,N,dddmm.mmmm,E`
+fn parse_position(lat: &str, ns: &str, lon: &str, ew: &str) -> Option<Position> {
+    let mut latitude = parse_angle(lat, 2)?;
+    let mut longitude = parse_angle(lon, 3)?;
+    if ns == "S" {
+        latitude = -latitude;
+    }
+    if ew == "W" {
+        longitude = -longitude;
+    }
+    Some(Position {
+        latitude,
+        longitude,
+    })
+}
+
+/// Degrees and decimal minutes, with `digits` digits of degrees, to
+/// millionths of a degree.
+fn parse_angle(field: &str, digits: usize) -> Option<i32> {
+    if field.len() <= digits {
+        return None;
+    }
+    let degrees = field[0..digits].parse::<i32>().ok()?;
+    // Minutes to 1/100000, and 1/100000 minute is 1/6 of a millionth of a
+    // degree
+    let minutes = fixed(&field[digits..], 5)?;
+    degrees
+        .checked_mul(1_000_000)?
+        .checked_add(minutes / 6)
+}
+
+/// Parse a decimal number into an integer with `places` decimal places,
+/// so `fixed("12.3", 2)` is 1230. Extra digits are dropped. Too big for
+/// an `i32` is `None`.
+fn fixed(field: &str, places: u32) -> Option<i32> {
+    if field.is_empty() {
+        return None;
+    }
+    let (negative, field) = if field.starts_with('-') {
+        (true, &field[1..])
+    } else {
+        (false, field)
+    };
+    let mut parts = field.splitn(2, '.');
+    let whole = parts.next()?;
+    let mut value = if whole.is_empty() {
+        0
+    } else {
+        whole.parse::<i32>().ok()?
+    };
+    let mut frac = parts.next().unwrap_or("").bytes();
+    for _ in 0..places {
+        let digit = match frac.next() {
+            Some(b) if b >= b'0' && b <= b'9' => i32::from(b - b'0'),
+            Some(_) => return None,
+            None => 0,
+        };
+        value = value.checked_mul(10)?.checked_add(digit)?;
+    }
+    Some(if negative { -value } else { value })
+}
+
+impl fmt::Display for Position {
+    /// Like `51.501234N 0.141234W`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_angle(f, self.latitude, 'N', 'S')?;
+        write!(f, " ")?;
+        write_angle(f, self.longitude, 'E', 'W')
+    }
+}
+
+fn write_angle(f: &mut fmt::Formatter, angle: i32, pos: char, neg: char) -> fmt::Result {
+    let hemisphere = if angle < 0 { neg } else { pos };
+    let angle = angle.abs();
+    write!(
+        f,
+        "{}.{:06}{}",
+        angle / 1_000_000,
+        angle % 1_000_000,
+        hemisphere
+    )
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02}:{:02}:{:02}", self.hours, self.minutes, self.seconds)
+    }
+}
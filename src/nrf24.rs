@@ -0,0 +1,248 @@
+//! Drives a Nordic nRF24L01+ 2.4 GHz transceiver over SPI.
+//!
+//! We use Enhanced ShockBurst with auto-acknowledge and fixed 32 byte
+//! payloads, talking on pipe 0 only. That's enough for two boards to swap
+//! packets; the chip can do a lot more (six pipes, dynamic payloads, ACK
+//! payloads) if you need it.
+//!
+//! The chip has no RSSI, so the only link quality figures we get are the
+//! retransmit and lost packet counters in OBSERVE_TX, plus the 'received
+//! power above -64 dBm' bit in RPD.
+
+use cortex_m::asm;
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::blocking::spi;
+use embedded_hal::digital::OutputPin;
+
+/// Every packet is this long.
+pub const PAYLOAD_SIZE: usize = 32;
+
+/// Addresses are five bytes.
+pub const ADDRESS_SIZE: usize = 5;
+
+const CMD_R_REGISTER: u8 = 0x00;
+const CMD_W_REGISTER: u8 = 0x20;
+const CMD_R_RX_PAYLOAD: u8 = 0x61;
+const CMD_W_TX_PAYLOAD: u8 = 0xA0;
+const CMD_FLUSH_TX: u8 = 0xE1;
+const CMD_FLUSH_RX: u8 = 0xE2;
+const CMD_NOP: u8 = 0xFF;
+
+const REG_CONFIG: u8 = 0x00;
+const REG_EN_AA: u8 = 0x01;
+const REG_EN_RXADDR: u8 = 0x02;
+const REG_SETUP_AW: u8 = 0x03;
+const REG_SETUP_RETR: u8 = 0x04;
+const REG_RF_CH: u8 = 0x05;
+const REG_RF_SETUP: u8 = 0x06;
+const REG_STATUS: u8 = 0x07;
+const REG_OBSERVE_TX: u8 = 0x08;
+const REG_RPD: u8 = 0x09;
+const REG_RX_ADDR_P0: u8 = 0x0A;
+const REG_TX_ADDR: u8 = 0x10;
+const REG_RX_PW_P0: u8 = 0x11;
+const REG_FIFO_STATUS: u8 = 0x17;
+
+/// EN_CRC, CRCO (two byte CRC) and PWR_UP.
+const CONFIG_BASE: u8 = 0x0E;
+const CONFIG_PRIM_RX: u8 = 0x01;
+
+const STATUS_RX_DR: u8 = 0x40;
+const STATUS_TX_DS: u8 = 0x20;
+const STATUS_MAX_RT: u8 = 0x10;
+
+const FIFO_RX_EMPTY: u8 = 0x01;
+
+/// 750us between retries, up to 15 retries.
+const SETUP_RETR_DEFAULT: u8 = 0x2F;
+
+/// 1 Mbps, 0 dBm.
+const RF_SETUP_1MBPS_0DBM: u8 = 0x06;
+
+/// The channel we start on (2476 MHz), above most Wi-Fi.
+pub const DEFAULT_CHANNEL: u8 = 76;
+
+/// What happened to a packet we sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TxResult {
+    /// The other end acknowledged it, after this many retransmits
+    Acked(u8),
+    /// We gave up waiting for an acknowledgement
+    Lost,
+}
+
+pub struct Nrf24<SPI, CSN, CE> {
+    spi: SPI,
+    csn: CSN,
+    ce: CE,
+}
+
+impl<SPI, CSN, CE, E> Nrf24<SPI, CSN, CE>
+where
+    SPI: spi::Transfer<u8, Error = E>,
+    CSN: OutputPin,
+    CE: OutputPin,
+{
+    /// Power up the radio in standby, on the default channel with
+    /// auto-acknowledge on pipe 0. The bus should be SPI mode 0 at up to
+    /// 8 MHz.
+    pub fn new<D>(spi: SPI, csn: CSN, ce: CE, delay: &mut D) -> Result<Self, E>
+    where
+        D: DelayMs<u32>,
+    {
+        let mut radio = Nrf24 { spi, csn, ce };
+        radio.csn.set_high();
+        radio.ce.set_low();
+        // Power on reset takes up to 100ms
+        delay.delay_ms(100);
+        radio.write_register(REG_SETUP_AW, 0x03)?;
+        radio.write_register(REG_EN_AA, 0x01)?;
+        radio.write_register(REG_EN_RXADDR, 0x01)?;
+        radio.write_register(REG_SETUP_RETR, SETUP_RETR_DEFAULT)?;
+        radio.write_register(REG_RF_CH, DEFAULT_CHANNEL)?;
+        radio.write_register(REG_RF_SETUP, RF_SETUP_1MBPS_0DBM)?;
+        radio.write_register(REG_RX_PW_P0, PAYLOAD_SIZE as u8)?;
+        radio.command(CMD_FLUSH_TX)?;
+        radio.command(CMD_FLUSH_RX)?;
+        radio.clear_status()?;
+        radio.write_register(REG_CONFIG, CONFIG_BASE)?;
+        // Start-up from power down takes 1.5ms
+        delay.delay_ms(2);
+        Ok(radio)
+    }
+
+    /// Change the RF channel (0..125). The frequency is 2400 MHz plus the
+    /// channel number.
+    pub fn set_channel(&mut self, channel: u8) -> Result<(), E> {
+        self.write_register(REG_RF_CH, channel.min(125))
+    }
+
+    /// Set the address we send to and listen on. Both ends use the same
+    /// address, as the auto-acknowledge comes back on the TX address.
+    pub fn set_address(&mut self, address: &[u8; ADDRESS_SIZE]) -> Result<(), E> {
+        self.write_registers(REG_TX_ADDR, address)?;
+        self.write_registers(REG_RX_ADDR_P0, address)
+    }
+
+    /// Go into receive mode and stay there.
+    pub fn listen(&mut self) -> Result<(), E> {
+        self.ce.set_low();
+        self.write_register(REG_CONFIG, CONFIG_BASE | CONFIG_PRIM_RX)?;
+        self.clear_status()?;
+        self.ce.set_high();
+        Ok(())
+    }
+
+    /// Stop receiving and drop back to standby.
+    pub fn standby(&mut self) -> Result<(), E> {
+        self.ce.set_low();
+        self.write_register(REG_CONFIG, CONFIG_BASE)
+    }
+
+    /// Send a packet and wait for it to be acknowledged or given up on.
+    /// Short packets are padded with zeroes. Leaves the radio in standby.
+    pub fn send(&mut self, data: &[u8]) -> Result<TxResult, E> {
+        self.standby()?;
+        self.command(CMD_FLUSH_TX)?;
+        let mut buffer = [0u8; PAYLOAD_SIZE + 1];
+        buffer[0] = CMD_W_TX_PAYLOAD;
+        let len = data.len().min(PAYLOAD_SIZE);
+        buffer[1..len + 1].copy_from_slice(&data[0..len]);
+        self.csn.set_low();
+        let result = self.spi.transfer(&mut buffer).map(|_| ());
+        self.csn.set_high();
+        result?;
+
+        // A CE pulse of at least 10us starts the transmission
+        self.ce.set_high();
+        ce_pulse_delay();
+        self.ce.set_low();
+
+        let status = loop {
+            let status = self.status()?;
+            if status & (STATUS_TX_DS | STATUS_MAX_RT) != 0 {
+                break status;
+            }
+        };
+        self.clear_status()?;
+        if status & STATUS_TX_DS != 0 {
+            let observe = self.read_register(REG_OBSERVE_TX)?;
+            Ok(TxResult::Acked(observe & 0x0F))
+        } else {
+            // The packet is still in the TX FIFO, so throw it away
+            self.command(CMD_FLUSH_TX)?;
+            Ok(TxResult::Lost)
+        }
+    }
+
+    /// Fetch a packet, if one has arrived. Call `listen()` first.
+    pub fn receive(&mut self, data: &mut [u8; PAYLOAD_SIZE]) -> Result<bool, E> {
+        if self.read_register(REG_FIFO_STATUS)? & FIFO_RX_EMPTY != 0 {
+            return Ok(false);
+        }
+        let mut buffer = [0u8; PAYLOAD_SIZE + 1];
+        buffer[0] = CMD_R_RX_PAYLOAD;
+        self.csn.set_low();
+        let result = self.spi.transfer(&mut buffer).map(|_| ());
+        self.csn.set_high();
+        result?;
+        data.copy_from_slice(&buffer[1..]);
+        self.write_register(REG_STATUS, STATUS_RX_DR)?;
+        Ok(true)
+    }
+
+    /// Was the last packet received above -64 dBm? This is as close as the
+    /// nRF24L01+ gets to an RSSI reading.
+    pub fn strong_signal(&mut self) -> Result<bool, E> {
+        Ok(self.read_register(REG_RPD)? & 0x01 != 0)
+    }
+
+    fn status(&mut self) -> Result<u8, E> {
+        self.command(CMD_NOP)
+    }
+
+    fn clear_status(&mut self) -> Result<(), E> {
+        self.write_register(REG_STATUS, STATUS_RX_DR | STATUS_TX_DS | STATUS_MAX_RT)
+    }
+
+    /// Send a single byte command, returning the STATUS register that
+    /// comes back.
+    fn command(&mut self, cmd: u8) -> Result<u8, E> {
+        let mut buffer = [cmd];
+        self.csn.set_low();
+        let result = self.spi.transfer(&mut buffer).map(|b| b[0]);
+        self.csn.set_high();
+        result
+    }
+
+    fn read_register(&mut self, reg: u8) -> Result<u8, E> {
+        let mut buffer = [CMD_R_REGISTER | reg, 0];
+        self.csn.set_low();
+        let result = self.spi.transfer(&mut buffer).map(|b| b[1]);
+        self.csn.set_high();
+        result
+    }
+
+    fn write_register(&mut self, reg: u8, value: u8) -> Result<(), E> {
+        self.write_registers(reg, &[value])
+    }
+
+    fn write_registers(&mut self, reg: u8, values: &[u8]) -> Result<(), E> {
+        let mut buffer = [0u8; ADDRESS_SIZE + 1];
+        buffer[0] = CMD_W_REGISTER | reg;
+        buffer[1..values.len() + 1].copy_from_slice(values);
+        self.csn.set_low();
+        let result = self.spi
+            .transfer(&mut buffer[0..values.len() + 1])
+            .map(|_| ());
+        self.csn.set_high();
+        result
+    }
+}
+
+/// Comfortably more than 10us, at 80 MHz or 120 MHz.
+fn ce_pulse_delay() {
+    for _ in 0..1_000 {
+        asm::nop();
+    }
+}
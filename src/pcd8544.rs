@@ -0,0 +1,205 @@
+//! Drives the PCD8544 84x48 monochrome LCD, as used in the Nokia 5110.
+//!
+//! We keep a local copy of the display in RAM (504 bytes) and push the whole
+//! thing out with `flush()`. Text uses the same font as the VGA text mode,
+//! either at full 8x16 size (10 columns, 3 rows) or squashed to 8x8 by
+//! dropping every other line (10 columns, 6 rows).
+
+use core::fmt;
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::blocking::spi;
+use embedded_hal::digital::OutputPin;
+use font;
+
+/// Display width in pixels.
+pub const WIDTH: usize = 84;
+
+/// Display height in pixels.
+pub const HEIGHT: usize = 48;
+
+/// Each byte holds a vertical strip of eight pixels, so the display is six
+/// 'banks' high.
+const BANKS: usize = HEIGHT / 8;
+
+const CMD_FUNCTION_BASIC: u8 = 0x20;
+const CMD_FUNCTION_EXTENDED: u8 = 0x21;
+const CMD_DISPLAY_NORMAL: u8 = 0x0C;
+const CMD_SET_X: u8 = 0x80;
+const CMD_SET_Y: u8 = 0x40;
+const CMD_EXT_TEMP_COEFF: u8 = 0x04;
+const CMD_EXT_BIAS: u8 = 0x14;
+const CMD_EXT_VOP: u8 = 0x80;
+
+/// The default contrast. Most modules want something between 0x30 and 0x50.
+pub const DEFAULT_CONTRAST: u8 = 0x3F;
+
+/// How big to draw text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextSize {
+    /// The full 8x16 glyphs
+    Tall,
+    /// Every other line of the 8x16 glyphs
+    Short,
+}
+
+impl TextSize {
+    fn height(&self) -> usize {
+        match *self {
+            TextSize::Tall => font::HEIGHT,
+            TextSize::Short => font::HEIGHT / 2,
+        }
+    }
+}
+
+pub struct Pcd8544<SPI, DC, CE> {
+    spi: SPI,
+    dc: DC,
+    ce: CE,
+    buffer: [u8; WIDTH * BANKS],
+    text_size: TextSize,
+    col: usize,
+    row: usize,
+}
+
+impl<SPI, DC, CE, E> Pcd8544<SPI, DC, CE>
+where
+    SPI: spi::Write<u8, Error = E>,
+    DC: OutputPin,
+    CE: OutputPin,
+{
+    /// Reset the display and set it up with the default contrast. The SPI
+    /// bus should be in mode 0 at no more than 4 MHz.
+    pub fn new<RST, D>(
+        spi: SPI,
+        dc: DC,
+        ce: CE,
+        rst: &mut RST,
+        delay: &mut D,
+    ) -> Result<Self, E>
+    where
+        RST: OutputPin,
+        D: DelayMs<u32>,
+    {
+        let mut lcd = Pcd8544 {
+            spi,
+            dc,
+            ce,
+            buffer: [0u8; WIDTH * BANKS],
+            text_size: TextSize::Short,
+            col: 0,
+            row: 0,
+        };
+        lcd.ce.set_high();
+        rst.set_low();
+        delay.delay_ms(10);
+        rst.set_high();
+        lcd.set_contrast(DEFAULT_CONTRAST)?;
+        lcd.command(&[CMD_FUNCTION_BASIC, CMD_DISPLAY_NORMAL])?;
+        lcd.flush()?;
+        Ok(lcd)
+    }
+
+    /// Set the LCD operating voltage (0..127), which acts as contrast.
+    pub fn set_contrast(&mut self, contrast: u8) -> Result<(), E> {
+        self.command(&[
+            CMD_FUNCTION_EXTENDED,
+            CMD_EXT_VOP | (contrast & 0x7F),
+            CMD_EXT_TEMP_COEFF,
+            CMD_EXT_BIAS,
+            CMD_FUNCTION_BASIC,
+        ])
+    }
+
+    /// Set the size used for text written with `write!`.
+    pub fn set_text_size(&mut self, size: TextSize) {
+        self.text_size = size;
+    }
+
+    /// Clear the local buffer and home the text cursor. Call `flush()` to
+    /// see the result.
+    pub fn clear(&mut self) {
+        for b in self.buffer.iter_mut() {
+            *b = 0;
+        }
+        self.col = 0;
+        self.row = 0;
+    }
+
+    /// Set or clear a pixel in the local buffer. Off-screen pixels are
+    /// ignored.
+    pub fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
+        if x >= WIDTH || y >= HEIGHT {
+            return;
+        }
+        let idx = (y / 8) * WIDTH + x;
+        if on {
+            self.buffer[idx] |= 1 << (y % 8);
+        } else {
+            self.buffer[idx] &= !(1 << (y % 8));
+        }
+    }
+
+    /// Draw a character with its top-left corner at (`x`, `y`).
+    pub fn draw_char(&mut self, x: usize, y: usize, ch: u8, size: TextSize) {
+        let step = font::HEIGHT / size.height();
+        for row in 0..size.height() {
+            for col in 0..font::WIDTH {
+                let on = font::pixel(ch, col, row * step);
+                self.set_pixel(x + col, y + row, on);
+            }
+        }
+    }
+
+    /// Send the local buffer to the display.
+    pub fn flush(&mut self) -> Result<(), E> {
+        self.command(&[CMD_SET_X, CMD_SET_Y])?;
+        self.dc.set_high();
+        self.ce.set_low();
+        let result = self.spi.write(&self.buffer);
+        self.ce.set_high();
+        result
+    }
+
+    fn command(&mut self, cmds: &[u8]) -> Result<(), E> {
+        self.dc.set_low();
+        self.ce.set_low();
+        let result = self.spi.write(cmds);
+        self.ce.set_high();
+        result
+    }
+
+    fn newline(&mut self) {
+        self.col = 0;
+        self.row += 1;
+        if (self.row + 1) * self.text_size.height() > HEIGHT {
+            self.row = 0;
+        }
+    }
+}
+
+impl<SPI, DC, CE, E> fmt::Write for Pcd8544<SPI, DC, CE>
+where
+    SPI: spi::Write<u8, Error = E>,
+    DC: OutputPin,
+    CE: OutputPin,
+{
+    /// Render text into the local buffer. Call `flush()` to see it.
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            match byte {
+                b'\r' => self.col = 0,
+                b'\n' => self.newline(),
+                _ => {
+                    if (self.col + 1) * font::WIDTH > WIDTH {
+                        self.newline();
+                    }
+                    let size = self.text_size;
+                    let (x, y) = (self.col * font::WIDTH, self.row * size.height());
+                    self.draw_char(x, y, byte, size);
+                    self.col += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+}
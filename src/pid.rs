@@ -0,0 +1,63 @@
+//! A fixed-point PID controller.
+//!
+//! Gains are given in thousandths, so `kp = 1500` means a proportional gain
+//! of 1.5. The integral is clamped so the I term alone can never exceed the
+//! output limit, which stops it winding up while the output is saturated.
+
+/// Gains are scaled by this much.
+pub const SCALE: i32 = 1000;
+
+pub struct Pid {
+    /// Proportional gain, in thousandths
+    pub kp: i32,
+    /// Integral gain, in thousandths (per update)
+    pub ki: i32,
+    /// Derivative gain, in thousandths (per update)
+    pub kd: i32,
+    limit: i32,
+    integral: i32,
+    last_error: i32,
+}
+
+impl Pid {
+    /// Create a controller whose output is clamped to +/- `limit`.
+    pub fn new(kp: i32, ki: i32, kd: i32, limit: i32) -> Pid {
+        Pid {
+            kp,
+            ki,
+            kd,
+            limit,
+            integral: 0,
+            last_error: 0,
+        }
+    }
+
+    /// Forget the history, e.g. after the setpoint jumps a long way.
+    pub fn reset(&mut self) {
+        self.integral = 0;
+        self.last_error = 0;
+    }
+
+    /// Run one iteration of the loop. This must be called at a fixed rate.
+    pub fn update(&mut self, setpoint: i32, measured: i32) -> i32 {
+        let error = setpoint - measured;
+        let derivative = error - self.last_error;
+        self.last_error = error;
+
+        self.integral = self.integral.saturating_add(error);
+        if self.ki != 0 {
+            let max_integral = ((i64::from(self.limit) * i64::from(SCALE))
+                / i64::from(self.ki))
+                .abs()
+                .min(i64::from(i32::max_value())) as i32;
+            self.integral = self.integral.max(-max_integral).min(max_integral);
+        }
+
+        let output = (i64::from(self.kp) * i64::from(error)
+            + i64::from(self.ki) * i64::from(self.integral)
+            + i64::from(self.kd) * i64::from(derivative)) / i64::from(SCALE);
+        output
+            .max(-i64::from(self.limit))
+            .min(i64::from(self.limit)) as i32
+    }
+}
@@ -0,0 +1,166 @@
+//! A mouse pointer, XORed onto a `Canvas`.
+//!
+//! Where the pointer goes, each of its pixels is the opposite of what's
+//! underneath, so it shows up on black and on white. None of our displays
+//! can be read back, though, so the caller has to say what's underneath:
+//! that's the `Background`. A game knows what its playfield looks like, and
+//! anything that keeps a copy of its screen (a text console, say) can look
+//! it up.
+//!
+//! Draw through an `Over`, or `hide` the pointer while you draw under it
+//! and `show` it again after. Otherwise the next move will 'restore' what
+//! used to be there.
+
+use graphics::{Canvas, Colour};
+
+/// Width of the pointer sprite.
+pub const WIDTH: usize = 8;
+
+/// Height of the pointer sprite.
+pub const HEIGHT: usize = 12;
+
+/// An arrow, with the hot spot at the top left. MSB is on the left.
+const SPRITE: [u8; HEIGHT] = [
+    0b1000_0000,
+    0b1100_0000,
+    0b1110_0000,
+    0b1111_0000,
+    0b1111_1000,
+    0b1111_1100,
+    0b1111_1110,
+    0b1111_1000,
+    0b1101_1000,
+    0b1000_1100,
+    0b0000_1100,
+    0b0000_0110,
+];
+
+/// Is the pixel at (x, y) lit, without the pointer?
+pub type Background = fn(usize, usize) -> bool;
+
+pub struct Pointer {
+    x: usize,
+    y: usize,
+    visible: bool,
+    background: Background,
+}
+
+impl Pointer {
+    /// A pointer at (x, y). It isn't drawn until you call `show`.
+    pub fn new(x: usize, y: usize, background: Background) -> Pointer {
+        Pointer {
+            x,
+            y,
+            visible: false,
+            background,
+        }
+    }
+
+    /// Where the hot spot is.
+    pub fn position(&self) -> (usize, usize) {
+        (self.x, self.y)
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Draw the pointer.
+    pub fn show<C>(&mut self, canvas: &mut C)
+    where
+        C: Canvas,
+    {
+        if !self.visible {
+            self.paint(canvas, true);
+            self.visible = true;
+        }
+    }
+
+    /// Put back what was under the pointer.
+    pub fn hide<C>(&mut self, canvas: &mut C)
+    where
+        C: Canvas,
+    {
+        if self.visible {
+            self.paint(canvas, false);
+            self.visible = false;
+        }
+    }
+
+    /// Move the hot spot to (x, y). Does nothing if it's already there.
+    pub fn move_to<C>(&mut self, canvas: &mut C, x: usize, y: usize)
+    where
+        C: Canvas,
+    {
+        if (x, y) == (self.x, self.y) {
+            return;
+        }
+        let visible = self.visible;
+        self.hide(canvas);
+        self.x = x;
+        self.y = y;
+        if visible {
+            self.show(canvas);
+        }
+    }
+
+    /// Draw the sprite XORed with the background, or just the background.
+    fn paint<C>(&self, canvas: &mut C, with_sprite: bool)
+    where
+        C: Canvas,
+    {
+        for (row, bits) in SPRITE.iter().enumerate() {
+            for col in 0..WIDTH {
+                if bits & (0x80 >> col) == 0 {
+                    continue;
+                }
+                let (x, y) = (self.x + col, self.y + row);
+                let lit = (self.background)(x, y) != with_sprite;
+                let colour = if lit { Colour::WHITE } else { Colour::BLACK };
+                canvas.draw_point(x, y, colour);
+            }
+        }
+    }
+
+    /// Does the sprite cover (x, y)?
+    fn covers(&self, x: usize, y: usize) -> bool {
+        if x < self.x || y < self.y || x >= self.x + WIDTH || y >= self.y + HEIGHT {
+            return false;
+        }
+        SPRITE[y - self.y] & (0x80 >> (x - self.x)) != 0
+    }
+}
+
+/// A canvas with the pointer on top, which stays on top of whatever you
+/// draw. The `Background` must already agree with what you draw, as
+/// that's what comes back when the pointer moves away.
+pub struct Over<'a, C: 'a> {
+    pub pointer: &'a Pointer,
+    pub canvas: &'a mut C,
+}
+
+impl<'a, C> Canvas for Over<'a, C>
+where
+    C: Canvas,
+{
+    fn width(&self) -> usize {
+        self.canvas.width()
+    }
+
+    fn height(&self) -> usize {
+        self.canvas.height()
+    }
+
+    fn draw_point(&mut self, x: usize, y: usize, colour: Colour) {
+        let colour = if self.pointer.visible && self.pointer.covers(x, y) {
+            if colour.is_lit() {
+                Colour::BLACK
+            } else {
+                Colour::WHITE
+            }
+        } else {
+            colour
+        };
+        self.canvas.draw_point(x, y, colour);
+    }
+}
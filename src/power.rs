@@ -0,0 +1,135 @@
+//! Keeping an eye on the supply, for boards running from a battery.
+//!
+//! The ADC's reference is the 3.3V rail, so it can't measure that rail -
+//! VDD would always read full scale, however flat the battery. Instead the
+//! battery (or whatever feeds the regulator) goes to an analog input
+//! through a divider. 10k over 10k, as in the `dashboard` example, lets it
+//! go up to 6.6V. Set the pin up with `adc::configure_pin` as usual.
+//!
+//! `Monitor` takes the raw ADC samples and keeps a rolling average of the
+//! last `AVERAGE_LEN`, so one noisy sample (or the dip when a motor starts)
+//! doesn't set off the alarm. The alarm goes on when the average drops
+//! below the threshold, and only goes off again once it's
+//! `HYSTERESIS_MV` above it, so a battery sitting right on the line
+//! doesn't turn it on and off every sample. What the alarm does is up to
+//! the caller - the `power_monitor` example uses the status bar, the red
+//! LED and `audio::beep`.
+
+use adc;
+
+/// How many samples the average is over.
+pub const AVERAGE_LEN: usize = 16;
+
+/// How far back above the threshold the average must get to turn the
+/// alarm off.
+pub const HYSTERESIS_MV: u32 = 100;
+
+/// What the supply goes through before it gets to the pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Divider {
+    /// From the supply to the pin, in ohms
+    pub top: u32,
+    /// From the pin to ground, in ohms
+    pub bottom: u32,
+}
+
+impl Divider {
+    /// No divider - the supply goes straight to the pin, so it must be
+    /// under 3.3V.
+    pub const NONE: Divider = Divider { top: 0, bottom: 1 };
+
+    /// Turn the voltage at the pin back into the supply voltage.
+    pub fn supply_mv(self, pin_mv: u32) -> u32 {
+        (pin_mv * (self.top + self.bottom)) / self.bottom
+    }
+}
+
+/// The alarm went on or off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// The average is below the threshold
+    Low(u32),
+    /// It's come back up again
+    Recovered(u32),
+}
+
+/// Watches one analog input.
+pub struct Monitor {
+    channel: u8,
+    divider: Divider,
+    threshold_mv: u32,
+    samples: [u16; AVERAGE_LEN],
+    next: usize,
+    count: usize,
+    low: bool,
+}
+
+impl Monitor {
+    /// Watch `channel`, raising the alarm below `threshold_mv` (at the
+    /// supply, not the pin).
+    pub fn new(channel: u8, divider: Divider, threshold_mv: u32) -> Monitor {
+        Monitor {
+            channel,
+            divider,
+            threshold_mv,
+            samples: [0; AVERAGE_LEN],
+            next: 0,
+            count: 0,
+            low: false,
+        }
+    }
+
+    /// The analog input to read for `sample`.
+    pub fn channel(&self) -> u8 {
+        self.channel
+    }
+
+    pub fn threshold_mv(&self) -> u32 {
+        self.threshold_mv
+    }
+
+    /// Move the threshold. The alarm is checked again at the next sample.
+    pub fn set_threshold_mv(&mut self, mv: u32) {
+        self.threshold_mv = mv;
+    }
+
+    /// Add a raw ADC reading. Returns an event if that turns the alarm on
+    /// or off. Nothing is decided until there are `AVERAGE_LEN` samples to
+    /// average, so the alarm can't go off at start-up on one bad reading.
+    pub fn sample(&mut self, sample: u16) -> Option<Event> {
+        self.samples[self.next] = sample;
+        self.next = (self.next + 1) % AVERAGE_LEN;
+        self.count = (self.count + 1).min(AVERAGE_LEN);
+        let mv = match self.average_mv() {
+            Some(mv) if self.count == AVERAGE_LEN => mv,
+            _ => return None,
+        };
+        if !self.low && mv < self.threshold_mv {
+            self.low = true;
+            Some(Event::Low(mv))
+        } else if self.low && mv >= self.threshold_mv + HYSTERESIS_MV {
+            self.low = false;
+            Some(Event::Recovered(mv))
+        } else {
+            None
+        }
+    }
+
+    /// The supply voltage, averaged over the samples so far.
+    pub fn average_mv(&self) -> Option<u32> {
+        if self.count == 0 {
+            return None;
+        }
+        let total: u32 = self.samples[0..self.count]
+            .iter()
+            .map(|&s| u32::from(s))
+            .sum();
+        let sample = (total / self.count as u32) as u16;
+        Some(self.divider.supply_mv(adc::millivolts(sample)))
+    }
+
+    /// Is the alarm on?
+    pub fn is_low(&self) -> bool {
+        self.low
+    }
+}
@@ -0,0 +1,67 @@
+//! Timing code with the DWT cycle counter.
+//!
+//! The Cortex-M4's DWT unit has a 32-bit counter that goes up by one every
+//! CPU clock. It's off at reset - turn it on with `init`, then wrap
+//! anything in `profile!` to find out how long it took:
+//!
+//! ``` ignore
+//! profile::init(&mut cp.DCB, &mut cp.DWT);
+//! let (_, cycles) = profile!{ c.clear() };
+//! writeln!(tx, "{}", profile::Cycles(cycles, &clocks)).unwrap();
+//! ```
+//!
+//! At 80 MHz the counter wraps every 53 seconds, so don't time anything
+//! longer than that. Interrupts still run while you're timing, and their
+//! cycles count too - with VGA running, that's most of them.
+
+use core::fmt;
+use cortex_m::peripheral::{DCB, DWT};
+use hal::sysctl::Clocks;
+
+/// DEMCR: enable the DWT and ITM
+const DEMCR_TRCENA: u32 = 1 << 24;
+
+/// Time an expression (or a block's worth of statements). Gives back its
+/// value and how many cycles it took.
+#[macro_export]
+macro_rules! profile {
+    ($($body:tt)*) => {{
+        let start = $crate::profile::now();
+        let result = { $($body)* };
+        (result, $crate::profile::now().wrapping_sub(start))
+    }};
+}
+
+/// Displays a cycle count, and what that is in microseconds.
+pub struct Cycles<'a>(pub u32, pub &'a Clocks);
+
+/// Turn the cycle counter on.
+pub fn init(dcb: &mut DCB, dwt: &mut DWT) {
+    unsafe { dcb.demcr.modify(|r| r | DEMCR_TRCENA) };
+    dwt.enable_cycle_counter();
+}
+
+/// The cycle counter.
+pub fn now() -> u32 {
+    unsafe { (*DWT::ptr()).cyccnt.read() }
+}
+
+/// Run `f` and return how many cycles it took, as well as what it returned.
+pub fn measure<F, R>(f: F) -> (R, u32)
+where
+    F: FnOnce() -> R,
+{
+    profile!{ f() }
+}
+
+/// Convert cycles to microseconds at the given clock speed.
+pub fn micros(cycles: u32, clocks: &Clocks) -> u32 {
+    let mhz = clocks.sysclk.0 / 1_000_000;
+    cycles / mhz
+}
+
+impl<'a> fmt::Display for Cycles<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} cycles ({} us)", self.0, micros(self.0, self.1))
+    }
+}
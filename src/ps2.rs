@@ -0,0 +1,215 @@
+//! The host end of a PS/2 link, as used by mice and keyboards.
+//!
+//! PS/2 is two open-collector lines, clock and data, each pulled up. The
+//! device drives the clock at 10-16.7 kHz whichever way the data is going.
+//! A frame is a start bit (0), eight data bits LSB first, an odd parity bit
+//! and a stop bit (1). From the device, each bit is valid while the clock
+//! is low, so we read them on the falling edge. To send, the host holds
+//! the clock low for at least 100us, pulls data low (the start bit) and
+//! lets the clock go. The device then clocks the rest out of us - we
+//! change the data on each falling edge - and pulls data low on an
+//! eleventh clock to say it got it.
+//!
+//! `Host` does the bits. The driver for whatever's plugged in (`mouse` or
+//! `keyboard`) sets up the pins, calls `take_interrupt` and `clock_falling`
+//! from the GPIO interrupt, and works out what the bytes mean. The pins are
+//! 5V tolerant, so the pull-ups can go to the device's 5V supply. Don't use
+//! PB0, PB1, PD4 or PD5, which aren't.
+
+use config::Pin;
+use cortex_m::asm;
+use hal::sysctl::Clocks;
+
+/// How long the host holds the clock low to ask to send.
+const REQUEST_US: u32 = 120;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Receiving,
+    Sending,
+}
+
+/// One PS/2 port.
+pub struct Host {
+    clock: Pin,
+    data: Pin,
+    state: State,
+    /// The frame so far, or the byte being sent
+    shift: u16,
+    /// Clock edges so far this frame
+    count: u8,
+    errors: u32,
+    /// `REQUEST_US` in clock cycles
+    request_cycles: u32,
+}
+
+/// Let a line float high, for the device (or the pull-up) to drive.
+fn release(pin: Pin) {
+    let port = pin.port.registers();
+    port.dir
+        .modify(|r, w| unsafe { w.bits(r.bits() & !(1 << pin.bit)) });
+}
+
+/// Pull a line low.
+fn drive_low(pin: Pin) {
+    let port = pin.port.registers();
+    pin.set(false);
+    port.dir
+        .modify(|r, w| unsafe { w.bits(r.bits() | (1 << pin.bit)) });
+}
+
+/// Is there an even number of ones? Then the parity bit is a 1.
+fn parity(byte: u8) -> bool {
+    byte.count_ones() % 2 == 0
+}
+
+impl Host {
+    /// Set up the pins and interrupt on the clock's falling edges. The
+    /// caller still has to enable the port's interrupt in the NVIC.
+    pub fn new(clock: Pin, data: Pin, clocks: &Clocks) -> Host {
+        for &pin in [clock, data].iter() {
+            pin.into_input();
+            let port = pin.port.registers();
+            let mask = 1 << pin.bit;
+            port.odr.modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+            port.pur.modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+        }
+        let port = clock.port.registers();
+        let mask = 1 << clock.bit;
+        port.im.modify(|r, w| unsafe { w.bits(r.bits() & !mask) });
+        port.is.modify(|r, w| unsafe { w.bits(r.bits() & !mask) });
+        port.ibe.modify(|r, w| unsafe { w.bits(r.bits() & !mask) });
+        port.iev.modify(|r, w| unsafe { w.bits(r.bits() & !mask) });
+        port.icr.write(|w| unsafe { w.bits(mask) });
+        port.im.modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+        Host {
+            clock,
+            data,
+            state: State::Receiving,
+            shift: 0,
+            count: 0,
+            errors: 0,
+            request_cycles: (clocks.sysclk.0 / 1_000_000) * REQUEST_US,
+        }
+    }
+
+    /// Start sending a byte to the device. Anything half received is
+    /// lost. The device's answer (usually 0xFA) arrives through
+    /// `clock_falling` like anything else.
+    ///
+    /// This waits for about 120us, with only the clock's interrupt masked.
+    pub fn send(&mut self, byte: u8) {
+        let port = self.clock.port.registers();
+        let mask = 1 << self.clock.bit;
+        port.im.modify(|r, w| unsafe { w.bits(r.bits() & !mask) });
+        drive_low(self.clock);
+        asm::delay(self.request_cycles);
+        drive_low(self.data);
+        self.state = State::Sending;
+        self.shift = u16::from(byte);
+        self.count = 0;
+        port.icr.write(|w| unsafe { w.bits(mask) });
+        port.im.modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+        release(self.clock);
+    }
+
+    /// Is a `send` still going?
+    pub fn is_sending(&self) -> bool {
+        self.state == State::Sending
+    }
+
+    /// Frames thrown away for bad start, parity or stop bits, and bytes the
+    /// device didn't acknowledge.
+    pub fn errors(&self) -> u32 {
+        self.errors
+    }
+
+    /// Was it the clock that interrupted? Clears the interrupt if so.
+    pub fn take_interrupt(&self) -> bool {
+        let port = self.clock.port.registers();
+        let mask = 1 << self.clock.bit;
+        if port.mis.read().bits() & mask == 0 {
+            return false;
+        }
+        port.icr.write(|w| unsafe { w.bits(mask) });
+        true
+    }
+
+    /// Call on every falling edge of the clock. Gives back a byte when one
+    /// has arrived.
+    pub fn clock_falling(&mut self) -> Option<u8> {
+        match self.state {
+            State::Receiving => self.receive_bit(),
+            State::Sending => {
+                self.send_bit();
+                None
+            }
+        }
+    }
+
+    fn receive_bit(&mut self) -> Option<u8> {
+        let bit = self.data.is_high();
+        let count = self.count;
+        self.count += 1;
+        match count {
+            // Wait for a start bit
+            0 if bit => {
+                self.count = 0;
+                None
+            }
+            0 => {
+                self.shift = 0;
+                None
+            }
+            1...8 => {
+                if bit {
+                    self.shift |= 1 << (count - 1);
+                }
+                None
+            }
+            9 => {
+                if bit {
+                    self.shift |= 1 << 8;
+                }
+                None
+            }
+            _ => {
+                self.count = 0;
+                let byte = self.shift as u8;
+                let parity_ok = (self.shift & (1 << 8) != 0) == parity(byte);
+                if bit && parity_ok {
+                    Some(byte)
+                } else {
+                    self.errors += 1;
+                    None
+                }
+            }
+        }
+    }
+
+    fn send_bit(&mut self) {
+        let count = self.count;
+        self.count += 1;
+        let byte = self.shift as u8;
+        let level = match count {
+            0...7 => byte & (1 << count) != 0,
+            8 => parity(byte),
+            // The stop bit
+            9 => true,
+            _ => {
+                // The device pulls data low to acknowledge
+                if self.data.is_high() {
+                    self.errors += 1;
+                }
+                self.state = State::Receiving;
+                self.count = 0;
+                return;
+            }
+        };
+        if level {
+            release(self.data);
+        } else {
+            drive_low(self.data);
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! Random numbers, seeded from whatever noise the chip can find.
+//!
+//! The generator is xoshiro128** - four words of state, and only shifts,
+//! rotates and a multiply, so it's quick on a Cortex-M4 - and there's one
+//! of it for everyone, behind `rand_u32`. Until it's seeded it gives the
+//! same numbers after every reset, so call `gather` once at start-up:
+//!
+//! ``` ignore
+//! let mut adc = Adc::adc0(p.ADC0);
+//! random::gather(|| adc.read(adc::TEMPERATURE));
+//! ```
+//!
+//! `gather` takes the bottom bits of a few hundred ADC readings. A pin
+//! with nothing connected to it is the noisiest input, but the temperature
+//! sensor does well enough and needs no pin. After each reading it also
+//! takes SysTick's count: the ADC runs from its own oscillator, so how
+//! many CPU clocks a conversion takes wanders about. If SysTick isn't
+//! counting, we run it while we gather and stop it again afterwards.
+//!
+//! `stir` adds more entropy later on - the time of a key press, say. None
+//! of this is good enough for cryptography.
+
+use cortex_m::interrupt;
+use cortex_m::peripheral::SYST;
+
+/// Readings `gather` takes.
+const SAMPLES: usize = 256;
+
+/// SYST_CSR.ENABLE
+const CSR_ENABLE: u32 = 1 << 0;
+
+/// Where we start, before any seeding. Anything but all zeroes will do.
+const INITIAL: [u32; 4] = [0x2545_F491, 0x9E37_79B9, 0x6A09_E667, 0xBB67_AE85];
+
+static mut STATE: [u32; 4] = INITIAL;
+
+/// A random number, using all 32 bits. Safe to call from an interrupt.
+pub fn rand_u32() -> u32 {
+    interrupt::free(|_| unsafe { next(&mut STATE) })
+}
+
+/// A random number from 0 to `limit - 1` (or 0, if `limit` is 0).
+pub fn below(limit: u32) -> u32 {
+    if limit == 0 {
+        return 0;
+    }
+    // Multiply and keep the top half, which is fairer than `%`
+    ((u64::from(rand_u32()) * u64::from(limit)) >> 32) as u32
+}
+
+/// Mix some entropy into the generator.
+pub fn stir(entropy: u32) {
+    interrupt::free(|_| unsafe {
+        STATE[0] ^= entropy;
+        STATE[2] ^= entropy.rotate_left(16);
+        next(&mut STATE);
+        if STATE == [0; 4] {
+            STATE = INITIAL;
+        }
+    });
+}
+
+/// Take `SAMPLES` ADC readings (from `read`) and the SysTick count after
+/// each one, and stir them all in.
+pub fn gather<F>(mut read: F)
+where
+    F: FnMut() -> u16,
+{
+    let syst = unsafe { &*SYST::ptr() };
+    let csr = syst.csr.read();
+    if csr & CSR_ENABLE == 0 {
+        unsafe {
+            syst.rvr.write(0x00FF_FFFF);
+            syst.csr.write(csr | CSR_ENABLE);
+        }
+    }
+    let mut pool = 0u32;
+    for i in 0..SAMPLES {
+        let sample = u32::from(read());
+        let ticks = syst.cvr.read();
+        // Each reading has a bit or two of noise at the bottom; smear it
+        // over the whole word
+        pool = (pool.rotate_left(7) ^ sample ^ ticks.rotate_left(12)).wrapping_mul(0x9E37_79B1);
+        if i % 32 == 31 {
+            stir(pool);
+        }
+    }
+    unsafe { syst.csr.write(csr) };
+}
+
+/// xoshiro128**, by David Blackman and Sebastiano Vigna.
+fn next(s: &mut [u32; 4]) -> u32 {
+    let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+    let t = s[1] << 9;
+    s[2] ^= s[0];
+    s[3] ^= s[1];
+    s[1] ^= s[2];
+    s[0] ^= s[3];
+    s[2] ^= t;
+    s[3] = s[3].rotate_left(11);
+    result
+}
@@ -0,0 +1,266 @@
+//! Hobby radio control receivers.
+//!
+//! Whatever the wire format, a receiver gives us a `Frame`: one value per
+//! channel, as the length of the pulse a servo wants, in microseconds.
+//! 1000 is one end of the stick, 1500 the middle and 2000 the other end,
+//! although plenty of transmitters go a bit past either end.
+//!
+//! There are three decoders here:
+//!
+//! * `Ppm` - all the channels on one wire, one after another. Each
+//!   channel is the time from one rising edge to the next, and the frame
+//!   ends with a gap longer than any channel. Many receivers have a PPM
+//!   (or "CPPM") pin, sometimes instead of the servo pins.
+//! * `Pwm` - one wire per channel, just as a servo would see it. This
+//!   needs a capture timer per channel.
+//! * `Sbus` - Futaba's serial format, also used by FrSky and others: 16
+//!   channels, 100000 baud, 8 data bits, even parity and two stop bits,
+//!   with the line inverted (idle low). No microcontroller UART here can
+//!   invert its input, so it needs an inverter in front of U1Rx - see the
+//!   `rc_receiver` example.
+//!
+//! The first two are fed from a timer capture interrupt, and `Sbus` from
+//! a UART. Each returns a `Frame` once it has a complete one, so the rest
+//! of the program doesn't care which is fitted. `mix` turns channels into servo
+//! outputs.
+
+/// The most channels a `Frame` holds.
+pub const MAX_CHANNELS: usize = 16;
+
+/// The ends and middle of a channel, in microseconds.
+pub const MIN_US: u16 = 1000;
+pub const CENTRE_US: u16 = 1500;
+pub const MAX_US: u16 = 2000;
+
+/// How far outside `MIN_US..MAX_US` a pulse can be before we call it
+/// noise.
+pub const SLACK_US: u32 = 250;
+
+/// A gap at least this long ends a PPM frame.
+pub const PPM_SYNC_US: u32 = 3000;
+
+/// Fewer channels than this in a PPM frame means we missed some.
+pub const PPM_MIN_CHANNELS: usize = 4;
+
+/// The bytes in an SBUS frame: a header, 22 bytes holding 16 channels of
+/// 11 bits, a byte of flags and a footer.
+pub const SBUS_FRAME_LEN: usize = 25;
+
+const SBUS_HEADER: u8 = 0x0F;
+/// The footer is zero, except for SBUS2 which sends 0x04, 0x14, 0x24 and
+/// 0x34 in turn.
+const SBUS_FOOTER: u8 = 0x00;
+const SBUS2_FOOTER: u8 = 0x04;
+/// In the flags byte. The receiver sets `FRAME_LOST` when it misses a
+/// frame from the transmitter and `FAILSAFE` when it has given up.
+const SBUS_FRAME_LOST: u8 = 1 << 2;
+const SBUS_FAILSAFE: u8 = 1 << 3;
+/// Where the channels are in a frame.
+const SBUS_DATA: usize = 1;
+const SBUS_DATA_LEN: usize = 22;
+
+/// One reading of every channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    pub channels: [u16; MAX_CHANNELS],
+    /// How many of `channels` the receiver sent
+    pub count: usize,
+    /// The receiver has lost the transmitter, and these are the positions
+    /// it was told to use instead. Only some formats say so.
+    pub failsafe: bool,
+}
+
+impl Frame {
+    pub fn new() -> Frame {
+        Frame {
+            channels: [CENTRE_US; MAX_CHANNELS],
+            count: 0,
+            failsafe: false,
+        }
+    }
+
+    /// A channel, counting from 0. Channels the receiver didn't send are
+    /// in the middle.
+    pub fn get(&self, channel: usize) -> u16 {
+        if channel < self.count {
+            self.channels[channel]
+        } else {
+            CENTRE_US
+        }
+    }
+
+    /// The channels the receiver sent.
+    pub fn channels(&self) -> &[u16] {
+        &self.channels[0..self.count]
+    }
+}
+
+/// Decodes a PPM stream.
+pub struct Ppm {
+    frame: Frame,
+    /// Something went wrong, so ignore everything until the next gap
+    lost: bool,
+}
+
+impl Ppm {
+    pub fn new() -> Ppm {
+        Ppm {
+            frame: Frame::new(),
+            // We don't know where we are until we've seen a gap
+            lost: true,
+        }
+    }
+
+    /// Feed in the time between two rising edges. Returns the frame when
+    /// its closing gap arrives.
+    pub fn pulse(&mut self, us: u32) -> Option<Frame> {
+        if us >= PPM_SYNC_US {
+            let complete = !self.lost && self.frame.count >= PPM_MIN_CHANNELS;
+            let frame = self.frame;
+            self.frame.count = 0;
+            self.lost = false;
+            return if complete { Some(frame) } else { None };
+        }
+        if self.lost {
+            return None;
+        }
+        if !valid(us) || self.frame.count == MAX_CHANNELS {
+            self.lost = true;
+            return None;
+        }
+        self.frame.channels[self.frame.count] = us as u16;
+        self.frame.count += 1;
+        None
+    }
+}
+
+/// Decodes a receiver's separate servo outputs, one pulse at a time.
+pub struct Pwm {
+    frame: Frame,
+    /// A bit for each channel we've had since the last frame
+    seen: u16,
+}
+
+impl Pwm {
+    /// Expect `count` channels (up to `MAX_CHANNELS`).
+    pub fn new(count: usize) -> Pwm {
+        let mut frame = Frame::new();
+        frame.count = count.min(MAX_CHANNELS);
+        Pwm { frame, seen: 0 }
+    }
+
+    /// Feed in the length of a channel's high pulse. Returns a frame once
+    /// every channel has had a new one. Receivers send them one after
+    /// another or all at once, so we don't rely on the order.
+    pub fn pulse(&mut self, channel: usize, us: u32) -> Option<Frame> {
+        if channel >= self.frame.count || !valid(us) {
+            return None;
+        }
+        self.frame.channels[channel] = us as u16;
+        self.seen |= 1 << channel;
+        let all = ((1u32 << self.frame.count) - 1) as u16;
+        if self.seen == all {
+            self.seen = 0;
+            Some(self.frame)
+        } else {
+            None
+        }
+    }
+}
+
+/// Decodes SBUS, a byte at a time.
+pub struct Sbus {
+    buffer: [u8; SBUS_FRAME_LEN],
+    count: usize,
+    lost_frames: u32,
+}
+
+impl Sbus {
+    pub fn new() -> Sbus {
+        Sbus {
+            buffer: [0; SBUS_FRAME_LEN],
+            count: 0,
+            lost_frames: 0,
+        }
+    }
+
+    /// Feed in a byte from the UART. Returns a frame once it has all 25
+    /// bytes and the footer is right.
+    ///
+    /// A frame is only found by its header byte, which can also turn up
+    /// in the channels. If we start in the wrong place the footer won't
+    /// match, and we look for another header. Call `reset` if the UART
+    /// reports an error, and we'll do the same.
+    pub fn input(&mut self, byte: u8) -> Option<Frame> {
+        if self.count == 0 && byte != SBUS_HEADER {
+            return None;
+        }
+        self.buffer[self.count] = byte;
+        self.count += 1;
+        if self.count < SBUS_FRAME_LEN {
+            return None;
+        }
+        self.count = 0;
+        if byte != SBUS_FOOTER && byte & 0x0F != SBUS2_FOOTER {
+            return None;
+        }
+        let flags = self.buffer[SBUS_FRAME_LEN - 2];
+        if flags & SBUS_FRAME_LOST != 0 {
+            self.lost_frames = self.lost_frames.wrapping_add(1);
+        }
+        let mut frame = Frame::new();
+        frame.count = MAX_CHANNELS;
+        frame.failsafe = flags & SBUS_FAILSAFE != 0;
+        // Each channel is 11 bits, least significant bit first
+        let mut bits = 0u32;
+        let mut held = 0;
+        let mut channel = 0;
+        for &byte in &self.buffer[SBUS_DATA..SBUS_DATA + SBUS_DATA_LEN] {
+            bits |= u32::from(byte) << held;
+            held += 8;
+            if held >= 11 {
+                frame.channels[channel] = sbus_to_us(bits & 0x7FF);
+                bits >>= 11;
+                held -= 11;
+                channel += 1;
+            }
+        }
+        Some(frame)
+    }
+
+    /// Forget any partial frame, and wait for the next header.
+    pub fn reset(&mut self) {
+        self.count = 0;
+    }
+
+    /// How many times the receiver has said it missed a frame from the
+    /// transmitter. Worth watching as the range gets longer.
+    pub fn lost_frames(&self) -> u32 {
+        self.lost_frames
+    }
+}
+
+/// SBUS sends 172 to 1811 for the ends of the stick, with 992 in the
+/// middle, which is 0.625 us a step.
+fn sbus_to_us(value: u32) -> u16 {
+    (880 + ((value * 5) / 8)) as u16
+}
+
+/// Could this be a channel?
+fn valid(us: u32) -> bool {
+    us + SLACK_US >= u32::from(MIN_US) && us <= u32::from(MAX_US) + SLACK_US
+}
+
+/// Work out one output of a mixer. `weights` says how much of each
+/// channel (in percent, either way) moves the output away from the
+/// middle. `[0, 100]` passes channel 1 straight through, and `[50, -50]`
+/// is half of channel 0 less half of channel 1. The result stays within
+/// `MIN_US..MAX_US`, so a servo is never driven past its ends.
+pub fn mix(frame: &Frame, weights: &[i16]) -> u16 {
+    let mut us = i32::from(CENTRE_US);
+    for (channel, &weight) in weights.iter().enumerate() {
+        let offset = i32::from(frame.get(channel)) - i32::from(CENTRE_US);
+        us += (offset * i32::from(weight)) / 100;
+    }
+    us.max(i32::from(MIN_US)).min(i32::from(MAX_US)) as u16
+}
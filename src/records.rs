@@ -0,0 +1,404 @@
+//! Small records - high score tables, saved games - kept in the EEPROM, or
+//! a file on an SD card, so that a reset half way through saving can't
+//! spoil them.
+//!
+//! Each `Record` keeps two copies. A copy is a header word (a sequence
+//! number, and the length in bytes), the data, and a CRC-32 of both in the
+//! last word. `save` writes over the older copy, leaving the newer one
+//! alone, and `load` takes the newest copy whose CRC checks out. So if the
+//! power goes mid-save, you get the last good save back, never a mixture.
+//!
+//! Records go in the bottom of the EEPROM (see `eeprom`), one after the
+//! other. `Record::words` says how much room one takes:
+//!
+//! ``` ignore
+//! // 2 * (2 + 64 / 4) = 36 words
+//! const SCORES: Record = Record { address: 0, capacity: 64 };
+//! const SAVE_GAME: Record = Record { address: 36, capacity: 200 };
+//! ```
+//!
+//! Or they go in a file, through `FileStorage`, with the address counting
+//! words from the start of the file. Nothing reaches the card until a save
+//! is finished. A card writes a 512 byte block at a time, and a block it
+//! was writing when the power went can come back as rubbish. So on a card,
+//! start each record on a `BLOCK_WORDS` boundary and give it a capacity of
+//! `BLOCK_CAPACITY`, and each copy has a block to itself:
+//!
+//! ``` ignore
+//! let mut storage = FileStorage::open(&mut volume, Dir::root(), "SAVES.DAT")?;
+//! const SAVE_GAME: Record = Record { address: 0, capacity: BLOCK_CAPACITY };
+//! SAVE_GAME.save(&mut storage, &game)?;
+//! ```
+//!
+//! `HighScores` is a table of the best few scores, ready to go in a
+//! `Record`.
+
+use eeprom::{self, Eeprom};
+use fat::{self, BlockDevice, Dir, File, Volume};
+
+/// Words in a block on a card.
+pub const BLOCK_WORDS: usize = fat::BLOCK_LEN / 4;
+
+/// The capacity that makes each copy of a record one block long.
+pub const BLOCK_CAPACITY: usize = (BLOCK_WORDS - 2) * 4;
+
+/// Something went wrong with a record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// Nothing's been saved, or neither copy is any good
+    Empty,
+    /// More data than the record has room for
+    TooBig,
+    Eeprom(eeprom::Error),
+    /// The file (or the card) didn't work, and why
+    File(&'static str),
+}
+
+impl From<eeprom::Error> for Error {
+    fn from(e: eeprom::Error) -> Error {
+        Error::Eeprom(e)
+    }
+}
+
+/// Somewhere to keep records, a 32-bit word at a time.
+pub trait Storage {
+    fn read(&mut self, address: usize, data: &mut [u32]) -> Result<(), Error>;
+
+    fn write(&mut self, address: usize, data: &[u32]) -> Result<(), Error>;
+
+    /// Make sure everything written so far has really gone.
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl Storage for Eeprom {
+    fn read(&mut self, address: usize, data: &mut [u32]) -> Result<(), Error> {
+        Eeprom::read(self, address, data).map_err(Error::from)
+    }
+
+    fn write(&mut self, address: usize, data: &[u32]) -> Result<(), Error> {
+        Eeprom::write(self, address, data).map_err(Error::from)
+    }
+}
+
+/// Records in a file. Past the end of the file reads as all ones, like
+/// erased EEPROM, and writing there makes the file longer.
+pub struct FileStorage<'a, D>
+where
+    D: BlockDevice + 'a,
+{
+    volume: &'a mut Volume<D>,
+    file: File,
+}
+
+impl<'a, D> FileStorage<'a, D>
+where
+    D: BlockDevice,
+{
+    /// Open the file at `path`, making it if it isn't there.
+    pub fn open(
+        volume: &'a mut Volume<D>,
+        cwd: Dir,
+        path: &str,
+    ) -> Result<FileStorage<'a, D>, Error> {
+        let file = match volume.open(cwd, path) {
+            Err(fat::Error::NotFound) => volume.create(cwd, path),
+            result => result,
+        };
+        match file {
+            Ok(file) => Ok(FileStorage { volume, file }),
+            Err(e) => Err(Error::File(e.message())),
+        }
+    }
+}
+
+impl<'a, D> Storage for FileStorage<'a, D>
+where
+    D: BlockDevice,
+{
+    fn read(&mut self, address: usize, data: &mut [u32]) -> Result<(), Error> {
+        self.file.seek((address * 4) as u32);
+        for word in data.iter_mut() {
+            let mut bytes = [0xFFu8; 4];
+            self.volume
+                .read(&mut self.file, &mut bytes)
+                .map_err(|e| Error::File(e.message()))?;
+            *word = bytes
+                .iter()
+                .enumerate()
+                .fold(0, |n, (i, &byte)| n | (u32::from(byte) << (i * 8)));
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, address: usize, data: &[u32]) -> Result<(), Error> {
+        let offset = (address * 4) as u32;
+        // Fill any gap as if it were erased
+        self.file.seek(offset);
+        while self.file.position() < offset {
+            self.volume
+                .write(&mut self.file, &[0xFF])
+                .map_err(|e| Error::File(e.message()))?;
+        }
+        for &word in data {
+            let bytes = [word as u8, (word >> 8) as u8, (word >> 16) as u8, (word >> 24) as u8];
+            self.volume
+                .write(&mut self.file, &bytes)
+                .map_err(|e| Error::File(e.message()))?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.volume
+            .sync(&mut self.file)
+            .map_err(|e| Error::File(e.message()))
+    }
+}
+
+/// Where a record lives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Record {
+    /// The word address of the first copy
+    pub address: usize,
+    /// The most data it holds, in bytes
+    pub capacity: usize,
+}
+
+/// A good copy: its sequence number and length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Good {
+    sequence: u16,
+    len: usize,
+}
+
+impl Record {
+    /// How many EEPROM words the record takes, both copies together.
+    pub fn words(&self) -> usize {
+        2 * self.copy_words()
+    }
+
+    /// Read the newest good copy into `data`, and say how long it is. If
+    /// `data` is too short, the rest is left off.
+    pub fn load<S>(&self, storage: &mut S, data: &mut [u8]) -> Result<usize, Error>
+    where
+        S: Storage,
+    {
+        let (copy, good) = match (self.check(storage, 0)?, self.check(storage, 1)?) {
+            (Some(a), Some(b)) if newer(b.sequence, a.sequence) => (1, b),
+            (Some(a), _) => (0, a),
+            (None, Some(b)) => (1, b),
+            (None, None) => return Err(Error::Empty),
+        };
+        let base = self.copy_address(copy);
+        let len = good.len;
+        for i in 0..words_for(len) {
+            let word = read_word(storage, base + 1 + i)?;
+            for (j, byte) in data.iter_mut().skip(i * 4).take(4).enumerate() {
+                if (i * 4) + j < len {
+                    *byte = (word >> (j * 8)) as u8;
+                }
+            }
+        }
+        Ok(len)
+    }
+
+    /// Save `data` over the older copy.
+    pub fn save<S>(&self, storage: &mut S, data: &[u8]) -> Result<(), Error>
+    where
+        S: Storage,
+    {
+        if data.len() > self.capacity {
+            return Err(Error::TooBig);
+        }
+        // Keep the newest good copy, and write over the other
+        let (copy, sequence) = match (self.check(storage, 0)?, self.check(storage, 1)?) {
+            (Some(a), Some(b)) if newer(b.sequence, a.sequence) => (0, b.sequence),
+            (Some(a), _) => (1, a.sequence),
+            (None, Some(b)) => (0, b.sequence),
+            (None, None) => (0, 0),
+        };
+        let base = self.copy_address(copy);
+        let header = u32::from(sequence.wrapping_add(1)) | ((data.len() as u32) << 16);
+        let mut crc = crc32_word(0xFFFF_FFFF, header);
+        storage.write(base, &[header])?;
+        for i in 0..words_for(data.len()) {
+            let mut word = 0u32;
+            for (j, byte) in data.iter().skip(i * 4).take(4).enumerate() {
+                word |= u32::from(*byte) << (j * 8);
+            }
+            crc = crc32_word(crc, word);
+            storage.write(base + 1 + i, &[word])?;
+        }
+        storage.write(base + self.copy_words() - 1, &[!crc])?;
+        storage.flush()
+    }
+
+    /// Forget what's saved, so `load` gives `Error::Empty`.
+    pub fn erase<S>(&self, storage: &mut S) -> Result<(), Error>
+    where
+        S: Storage,
+    {
+        // A length longer than the record never checks out
+        storage.write(self.copy_address(0), &[0xFFFF_FFFF])?;
+        storage.write(self.copy_address(1), &[0xFFFF_FFFF])?;
+        storage.flush()
+    }
+
+    /// The header, the data and the CRC.
+    fn copy_words(&self) -> usize {
+        2 + words_for(self.capacity)
+    }
+
+    fn copy_address(&self, copy: usize) -> usize {
+        self.address + (copy * self.copy_words())
+    }
+
+    /// Is this copy any good?
+    fn check<S>(&self, storage: &mut S, copy: usize) -> Result<Option<Good>, Error>
+    where
+        S: Storage,
+    {
+        let base = self.copy_address(copy);
+        let header = read_word(storage, base)?;
+        let len = (header >> 16) as usize;
+        if len > self.capacity {
+            return Ok(None);
+        }
+        let mut crc = crc32_word(0xFFFF_FFFF, header);
+        for i in 0..words_for(len) {
+            crc = crc32_word(crc, read_word(storage, base + 1 + i)?);
+        }
+        if read_word(storage, base + self.copy_words() - 1)? != !crc {
+            return Ok(None);
+        }
+        Ok(Some(Good {
+            sequence: header as u16,
+            len,
+        }))
+    }
+}
+
+/// How many initials go with a high score.
+pub const INITIALS: usize = 3;
+
+/// How many scores a `HighScores` keeps.
+pub const SCORES: usize = 8;
+
+/// How many bytes a `HighScores` needs in a `Record`.
+pub const HIGH_SCORES_LEN: usize = SCORES * (INITIALS + 4);
+
+/// One line of a high score table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Score {
+    pub initials: [u8; INITIALS],
+    pub score: u32,
+}
+
+/// The best `SCORES` scores, best first. Blank lines score zero, and have
+/// spaces for initials.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HighScores {
+    pub scores: [Score; SCORES],
+}
+
+impl HighScores {
+    pub const EMPTY: HighScores = HighScores {
+        scores: [Score {
+            initials: [b' '; INITIALS],
+            score: 0,
+        }; SCORES],
+    };
+
+    /// Load the table, or give an empty one if there isn't one.
+    pub fn load<S>(record: &Record, storage: &mut S) -> HighScores
+    where
+        S: Storage,
+    {
+        let mut bytes = [0u8; HIGH_SCORES_LEN];
+        match record.load(storage, &mut bytes) {
+            Ok(HIGH_SCORES_LEN) => HighScores::from_bytes(&bytes),
+            _ => HighScores::EMPTY,
+        }
+    }
+
+    pub fn save<S>(&self, record: &Record, storage: &mut S) -> Result<(), Error>
+    where
+        S: Storage,
+    {
+        record.save(storage, &self.to_bytes())
+    }
+
+    /// Would this score get in the table?
+    pub fn qualifies(&self, score: u32) -> bool {
+        score > self.scores[SCORES - 1].score
+    }
+
+    /// Put a score in the table, and say where it went (0 is the top), if
+    /// it was good enough. Ties go under the older score.
+    pub fn insert(&mut self, initials: [u8; INITIALS], score: u32) -> Option<usize> {
+        let place = self.scores.iter().position(|s| score > s.score)?;
+        for i in (place + 1..SCORES).rev() {
+            self.scores[i] = self.scores[i - 1];
+        }
+        self.scores[place] = Score { initials, score };
+        Some(place)
+    }
+
+    fn to_bytes(&self) -> [u8; HIGH_SCORES_LEN] {
+        let mut bytes = [0u8; HIGH_SCORES_LEN];
+        for (score, chunk) in self.scores.iter().zip(bytes.chunks_mut(INITIALS + 4)) {
+            chunk[0..INITIALS].copy_from_slice(&score.initials);
+            for i in 0..4 {
+                chunk[INITIALS + i] = (score.score >> (i * 8)) as u8;
+            }
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8; HIGH_SCORES_LEN]) -> HighScores {
+        let mut table = HighScores::EMPTY;
+        for (score, chunk) in table.scores.iter_mut().zip(bytes.chunks(INITIALS + 4)) {
+            score.initials.copy_from_slice(&chunk[0..INITIALS]);
+            score.score = (0..4).fold(0, |n, i| n | (u32::from(chunk[INITIALS + i]) << (i * 8)));
+        }
+        table
+    }
+}
+
+fn read_word<S>(storage: &mut S, address: usize) -> Result<u32, Error>
+where
+    S: Storage,
+{
+    let mut word = [0u32];
+    storage.read(address, &mut word)?;
+    Ok(word[0])
+}
+
+/// Whole words for `len` bytes.
+fn words_for(len: usize) -> usize {
+    (len + 3) / 4
+}
+
+/// Is sequence number `a` after `b`? They wrap, so it's whichever is
+/// less than half way round ahead.
+fn newer(a: u16, b: u16) -> bool {
+    (a.wrapping_sub(b) as i16) > 0
+}
+
+/// Carry on a CRC-32 (as in `flash::crc32`) over the four bytes of a
+/// word, least significant first.
+fn crc32_word(mut crc: u32, word: u32) -> u32 {
+    for i in 0..4 {
+        crc ^= (word >> (i * 8)) & 0xFF;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
@@ -0,0 +1,161 @@
+//! A request/response protocol, for scripts on the host to drive the board
+//! with instead of typing at the menu.
+//!
+//! Packets go in `cobs` frames. Every packet is a command byte, a tag
+//! byte, some arguments and a CRC-16 of all that (as `xmodem::crc16`, or
+//! Python's `binascii.crc_hqx(data, 0)`), low byte first. Numbers are
+//! little-endian. The reply has the command with `REPLY` set, the same
+//! tag, a status byte and then anything the command gives back:
+//!
+//! | Command       | Arguments                           | Gives back  |
+//! |---------------|-------------------------------------|-------------|
+//! | `PING`        | -                                   | `VERSION`   |
+//! | `READ_MEMORY` | address: u32, length: u8            | the bytes   |
+//! | `SET_GPIO`    | port: u8 (0 is A), pin: u8, on: u8  | -           |
+//! | `READ_ADC`    | channel: u8 (`0xFF` is temperature) | sample: u16 |
+//! | `FILL_RECT`   | x, y, width, height, colour: u16    | -           |
+//! | `DRAW_TEXT`   | x: u16, y: u16, text                | -           |
+//!
+//! Requests with a bad CRC get no reply at all, just as with Modbus, so
+//! the host should give up waiting after a while and try again. The tag is
+//! the host's to choose; a new one for each request means a late reply
+//! can't be taken for the answer to the next question.
+//!
+//! What the commands actually do is up to a `Target`, which can refuse
+//! (say, memory that isn't there).
+
+use xmodem::crc16;
+
+/// Which version of the protocol this is.
+pub const VERSION: u8 = 1;
+
+pub const PING: u8 = 0x00;
+pub const READ_MEMORY: u8 = 0x01;
+pub const SET_GPIO: u8 = 0x02;
+pub const READ_ADC: u8 = 0x03;
+pub const FILL_RECT: u8 = 0x04;
+pub const DRAW_TEXT: u8 = 0x05;
+
+/// Set in the command byte of a reply.
+pub const REPLY: u8 = 0x80;
+
+/// Status codes.
+pub const OK: u8 = 0x00;
+pub const UNKNOWN_COMMAND: u8 = 0x01;
+pub const BAD_LENGTH: u8 = 0x02;
+pub const REFUSED: u8 = 0x03;
+
+/// The most `READ_MEMORY` reads at once.
+pub const MAX_READ: usize = 64;
+
+/// The longest text `DRAW_TEXT` takes.
+pub const MAX_TEXT: usize = 64;
+
+/// Big enough for any request or reply. The longest is a `DRAW_TEXT`
+/// request: command, tag, x, y, text and CRC.
+pub const MAX_PACKET: usize = 2 + 4 + MAX_TEXT + 2;
+
+/// The things a host can ask for.
+pub trait Target {
+    /// Fill `data` with the memory at `address`. Returns false if it
+    /// shouldn't be read.
+    fn read_memory(&mut self, address: u32, data: &mut [u8]) -> bool;
+    /// Drive a pin high or low. Returns false if it isn't one we let the
+    /// host change.
+    fn set_gpio(&mut self, port: u8, pin: u8, high: bool) -> bool;
+    /// Take one sample of an analog input, or `None` if there's no such
+    /// channel.
+    fn read_adc(&mut self, channel: u8) -> Option<u16>;
+    /// Fill a rectangle with an RGB565 colour. Returns false if it's off
+    /// the screen.
+    fn fill_rect(&mut self, x: u16, y: u16, width: u16, height: u16, colour: u16) -> bool;
+    /// Write some text. Returns false if it's off the screen.
+    fn draw_text(&mut self, x: u16, y: u16, text: &[u8]) -> bool;
+}
+
+/// Act on a request (including its CRC), and build the reply. Returns the
+/// length of the reply, which is zero if the request was corrupt and we
+/// shouldn't send one.
+pub fn serve<T>(request: &[u8], target: &mut T, reply: &mut [u8; MAX_PACKET]) -> usize
+where
+    T: Target,
+{
+    if request.len() < 4 {
+        return 0;
+    }
+    let (body, tail) = request.split_at(request.len() - 2);
+    if crc16(body) != u16::from(tail[0]) | (u16::from(tail[1]) << 8) {
+        return 0;
+    }
+    let command = body[0];
+    let args = &body[2..];
+    reply[0] = command | REPLY;
+    reply[1] = body[1];
+    let result = match command {
+        PING if args.is_empty() => {
+            reply[3] = VERSION;
+            Ok(1)
+        }
+        READ_MEMORY if args.len() == 5 => {
+            let len = args[4] as usize;
+            if len > MAX_READ {
+                Err(REFUSED)
+            } else if target.read_memory(word(&args[0..4]), &mut reply[3..3 + len]) {
+                Ok(len)
+            } else {
+                Err(REFUSED)
+            }
+        }
+        SET_GPIO if args.len() == 3 => check(target.set_gpio(args[0], args[1], args[2] != 0)),
+        READ_ADC if args.len() == 1 => match target.read_adc(args[0]) {
+            Some(sample) => {
+                reply[3] = sample as u8;
+                reply[4] = (sample >> 8) as u8;
+                Ok(2)
+            }
+            None => Err(REFUSED),
+        },
+        FILL_RECT if args.len() == 10 => check(target.fill_rect(
+            half(&args[0..2]),
+            half(&args[2..4]),
+            half(&args[4..6]),
+            half(&args[6..8]),
+            half(&args[8..10]),
+        )),
+        DRAW_TEXT if args.len() >= 4 && args.len() <= 4 + MAX_TEXT => {
+            check(target.draw_text(half(&args[0..2]), half(&args[2..4]), &args[4..]))
+        }
+        PING | READ_MEMORY | SET_GPIO | READ_ADC | FILL_RECT | DRAW_TEXT => Err(BAD_LENGTH),
+        _ => Err(UNKNOWN_COMMAND),
+    };
+    let len = match result {
+        Ok(len) => {
+            reply[2] = OK;
+            3 + len
+        }
+        Err(status) => {
+            reply[2] = status;
+            3
+        }
+    };
+    let crc = crc16(&reply[0..len]);
+    reply[len] = crc as u8;
+    reply[len + 1] = (crc >> 8) as u8;
+    len + 2
+}
+
+fn check(done: bool) -> Result<usize, u8> {
+    if done {
+        Ok(0)
+    } else {
+        Err(REFUSED)
+    }
+}
+
+fn half(bytes: &[u8]) -> u16 {
+    u16::from(bytes[0]) | (u16::from(bytes[1]) << 8)
+}
+
+fn word(bytes: &[u8]) -> u32 {
+    u32::from(half(&bytes[0..2])) | (u32::from(half(&bytes[2..4])) << 16)
+}
@@ -0,0 +1,161 @@
+//! Why did we reset?
+//!
+//! The RESC register in the system control block has a bit for each thing
+//! that can reset the chip. They're sticky - they collect every reset since
+//! power on - so `cause` reads them and then clears them, leaving only the
+//! next reset's cause for next time. Call it once, early on:
+//!
+//! ``` ignore
+//! writeln!(board.tx, "Reset by {}", demo::reset::cause()).unwrap();
+//! ```
+//!
+//! A brown-out (the supply dipping too low) can either reset the chip or
+//! just raise an interrupt. Resetting is the default, and is what you want
+//! on battery power - running on with a sagging supply gets you corrupted
+//! flash writes. `set_brown_out_action` changes it.
+
+use core::fmt;
+use pac;
+
+/// RESC: the RST pin
+const RESC_EXT: u32 = 1 << 0;
+/// RESC: power on
+const RESC_POR: u32 = 1 << 1;
+/// RESC: brown out
+const RESC_BOR: u32 = 1 << 2;
+/// RESC: watchdog 0
+const RESC_WDT0: u32 = 1 << 3;
+/// RESC: software (`SCB::system_reset`)
+const RESC_SW: u32 = 1 << 4;
+/// RESC: watchdog 1
+const RESC_WDT1: u32 = 1 << 5;
+/// RESC: the main oscillator failed
+const RESC_MOSCFAIL: u32 = 1 << 16;
+
+/// PBORCTL: reset (rather than interrupt) on a BOR1 event
+#[cfg(feature = "tm4c123")]
+const PBORCTL_BOR1: u32 = 1 << 1;
+/// PBORCTL: reset (rather than interrupt) on a BOR0 event
+#[cfg(feature = "tm4c123")]
+const PBORCTL_BOR0: u32 = 1 << 2;
+
+/// PTBOCTL.VDD_UBOR: what a VDD brown-out does on the TM4C1294
+#[cfg(feature = "tm4c129")]
+const PTBOCTL_VDD_UBOR_MASK: u32 = 0x3;
+#[cfg(feature = "tm4c129")]
+const PTBOCTL_VDD_UBOR_INTERRUPT: u32 = 0x1;
+#[cfg(feature = "tm4c129")]
+const PTBOCTL_VDD_UBOR_RESET: u32 = 0x3;
+
+const NAMES: [(u32, &str); 7] = [
+    (RESC_POR, "power on"),
+    (RESC_BOR, "brown out"),
+    (RESC_EXT, "reset pin"),
+    (RESC_WDT0, "watchdog 0"),
+    (RESC_WDT1, "watchdog 1"),
+    (RESC_SW, "software"),
+    (RESC_MOSCFAIL, "oscillator failure"),
+];
+
+/// The reset causes, as read from RESC. Displays as a list, like
+/// `power on, reset pin`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cause(pub u32);
+
+/// What to do when the supply drops too low.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrownOutAction {
+    /// Reset the chip
+    Reset,
+    /// Raise the system control interrupt and carry on
+    Interrupt,
+}
+
+/// Read the reset cause, and clear it.
+pub fn cause() -> Cause {
+    let sysctl = unsafe { &*pac::SYSCTL::ptr() };
+    let resc = sysctl.resc.read().bits();
+    sysctl.resc.write(|w| unsafe { w.bits(0) });
+    Cause(resc)
+}
+
+/// Choose what a brown-out does, for both brown-out levels.
+#[cfg(feature = "tm4c123")]
+pub fn set_brown_out_action(action: BrownOutAction) {
+    let sysctl = unsafe { &*pac::SYSCTL::ptr() };
+    let mask = PBORCTL_BOR0 | PBORCTL_BOR1;
+    sysctl.pborctl.modify(|r, w| unsafe {
+        match action {
+            BrownOutAction::Reset => w.bits(r.bits() | mask),
+            BrownOutAction::Interrupt => w.bits(r.bits() & !mask),
+        }
+    });
+}
+
+/// Choose what a brown-out does. The TM4C1294 has one level for VDD, set
+/// in PTBOCTL.
+#[cfg(feature = "tm4c129")]
+pub fn set_brown_out_action(action: BrownOutAction) {
+    let sysctl = unsafe { &*pac::SYSCTL::ptr() };
+    let value = match action {
+        BrownOutAction::Reset => PTBOCTL_VDD_UBOR_RESET,
+        BrownOutAction::Interrupt => PTBOCTL_VDD_UBOR_INTERRUPT,
+    };
+    sysctl.ptboctl.modify(|r, w| unsafe {
+        w.bits((r.bits() & !PTBOCTL_VDD_UBOR_MASK) | value)
+    });
+}
+
+/// What a brown-out does now.
+#[cfg(feature = "tm4c123")]
+pub fn brown_out_action() -> BrownOutAction {
+    let sysctl = unsafe { &*pac::SYSCTL::ptr() };
+    if sysctl.pborctl.read().bits() & PBORCTL_BOR0 != 0 {
+        BrownOutAction::Reset
+    } else {
+        BrownOutAction::Interrupt
+    }
+}
+
+/// What a brown-out does now.
+#[cfg(feature = "tm4c129")]
+pub fn brown_out_action() -> BrownOutAction {
+    let sysctl = unsafe { &*pac::SYSCTL::ptr() };
+    if sysctl.ptboctl.read().bits() & PTBOCTL_VDD_UBOR_MASK == PTBOCTL_VDD_UBOR_RESET {
+        BrownOutAction::Reset
+    } else {
+        BrownOutAction::Interrupt
+    }
+}
+
+impl Cause {
+    /// Did the watchdog do it? Worth shouting about.
+    pub fn is_watchdog(&self) -> bool {
+        self.0 & (RESC_WDT0 | RESC_WDT1) != 0
+    }
+
+    /// Did the power supply do it?
+    pub fn is_brown_out(&self) -> bool {
+        self.0 & RESC_BOR != 0
+    }
+}
+
+impl fmt::Display for Cause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut first = true;
+        for &(bit, name) in NAMES.iter() {
+            if self.0 & bit != 0 {
+                if !first {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", name)?;
+                first = false;
+            }
+        }
+        if first {
+            // Someone got here first, or a debugger cleared it
+            write!(f, "unknown (0x{:08x})", self.0)?;
+        }
+        Ok(())
+    }
+}
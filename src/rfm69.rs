@@ -0,0 +1,275 @@
+//! Drives a HopeRF RFM69HW sub-GHz FSK transceiver over SPI.
+//!
+//! We use packet mode with variable length packets, a CRC and the two byte
+//! sync word, at 4.8 kbps with 5 kHz deviation - slow, but it goes a long
+//! way. Packets can optionally be AES-128 encrypted by the radio itself.
+//!
+//! The HW variant has the +20 dBm power amplifier, which needs some test
+//! registers poking whenever we go in and out of transmit. We do that for
+//! you, so don't use this with the non-HW module.
+//!
+//! We poll the IRQ flags rather than using the DIO pins.
+
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::blocking::spi;
+use embedded_hal::digital::OutputPin;
+
+/// Longest payload we'll send. The FIFO is 66 bytes, but with AES on the
+/// limit is 64 including the length byte and address.
+pub const MAX_PAYLOAD: usize = 61;
+
+/// Frequency step is 32 MHz / 2^19, or 61.03515625 Hz.
+const FSTEP_MILLIHZ: u64 = 61_035;
+
+const REG_FIFO: u8 = 0x00;
+const REG_OPMODE: u8 = 0x01;
+const REG_DATAMODUL: u8 = 0x02;
+const REG_BITRATEMSB: u8 = 0x03;
+const REG_FDEVMSB: u8 = 0x05;
+const REG_FRFMSB: u8 = 0x07;
+const REG_VERSION: u8 = 0x10;
+const REG_PALEVEL: u8 = 0x11;
+const REG_OCP: u8 = 0x13;
+const REG_RXBW: u8 = 0x19;
+const REG_RSSIVALUE: u8 = 0x24;
+const REG_IRQFLAGS1: u8 = 0x27;
+const REG_IRQFLAGS2: u8 = 0x28;
+const REG_RSSITHRESH: u8 = 0x29;
+const REG_SYNCCONFIG: u8 = 0x2E;
+const REG_SYNCVALUE1: u8 = 0x2F;
+const REG_PACKETCONFIG1: u8 = 0x37;
+const REG_PAYLOADLENGTH: u8 = 0x38;
+const REG_FIFOTHRESH: u8 = 0x3C;
+const REG_PACKETCONFIG2: u8 = 0x3D;
+const REG_AESKEY1: u8 = 0x3E;
+const REG_TESTPA1: u8 = 0x5A;
+const REG_TESTPA2: u8 = 0x5C;
+const REG_TESTDAGC: u8 = 0x6F;
+
+const OPMODE_SLEEP: u8 = 0x00;
+const OPMODE_STANDBY: u8 = 0x04;
+const OPMODE_TX: u8 = 0x0C;
+const OPMODE_RX: u8 = 0x10;
+
+const IRQFLAGS1_MODEREADY: u8 = 0x80;
+const IRQFLAGS2_PAYLOADREADY: u8 = 0x04;
+const IRQFLAGS2_PACKETSENT: u8 = 0x08;
+
+/// Variable length, CRC on, no address filtering.
+const PACKETCONFIG1_VARIABLE_CRC: u8 = 0x90;
+
+/// Auto RX restart after each packet.
+const PACKETCONFIG2_AUTORXRESTART: u8 = 0x02;
+const PACKETCONFIG2_AESON: u8 = 0x01;
+
+/// PA1 and PA2 on, full power.
+const PALEVEL_PA1_PA2_MAX: u8 = 0x7F;
+
+/// Over current protection off, which the +20 dBm mode needs.
+const OCP_OFF: u8 = 0x0F;
+
+/// The sync word. Radios with different sync words won't hear each other.
+const SYNC_WORD: [u8; 2] = [0x2D, 0xD4];
+
+/// Something went wrong talking to the RFM69.
+#[derive(Debug)]
+pub enum Error<E> {
+    /// The SPI bus reported an error
+    Spi(E),
+    /// The version register didn't read back as 0x24, so there's probably
+    /// no module fitted
+    NotFound(u8),
+    /// The payload was too long
+    TooLong,
+}
+
+/// A packet we received.
+pub struct Packet {
+    pub len: usize,
+    pub data: [u8; MAX_PAYLOAD],
+    /// Signal strength in dBm (always negative)
+    pub rssi: i16,
+}
+
+impl Packet {
+    pub fn payload(&self) -> &[u8] {
+        &self.data[0..self.len]
+    }
+}
+
+pub struct Rfm69<SPI, CS> {
+    spi: SPI,
+    cs: CS,
+    mode: u8,
+}
+
+impl<SPI, CS, E> Rfm69<SPI, CS>
+where
+    SPI: spi::Transfer<u8, Error = E>,
+    CS: OutputPin,
+{
+    /// Reset the module and set it up for packet mode on the given
+    /// frequency (e.g. 433, 868 or 915 MHz, depending on the module). RST
+    /// is active high. The bus should be SPI mode 0 at up to 10 MHz.
+    pub fn new<RST, D>(
+        spi: SPI,
+        cs: CS,
+        rst: &mut RST,
+        delay: &mut D,
+        frequency_hz: u32,
+    ) -> Result<Self, Error<E>>
+    where
+        RST: OutputPin,
+        D: DelayMs<u32>,
+    {
+        let mut radio = Rfm69 {
+            spi,
+            cs,
+            mode: OPMODE_SLEEP,
+        };
+        radio.cs.set_high();
+        rst.set_high();
+        delay.delay_ms(1);
+        rst.set_low();
+        delay.delay_ms(10);
+
+        let version = radio.read_register(REG_VERSION)?;
+        if version != 0x24 {
+            return Err(Error::NotFound(version));
+        }
+
+        radio.set_mode(OPMODE_STANDBY)?;
+        // Packet mode, FSK, no shaping
+        radio.write_register(REG_DATAMODUL, 0x00)?;
+        // 4.8 kbps, 5 kHz deviation, 10.4 kHz receive bandwidth
+        radio.write_registers(REG_BITRATEMSB, &[0x1A, 0x0B])?;
+        radio.write_registers(REG_FDEVMSB, &[0x00, 0x52])?;
+        radio.write_register(REG_RXBW, 0x55)?;
+        radio.set_frequency(frequency_hz)?;
+        radio.write_register(REG_PALEVEL, PALEVEL_PA1_PA2_MAX)?;
+        radio.write_register(REG_OCP, OCP_OFF)?;
+        // -110 dBm
+        radio.write_register(REG_RSSITHRESH, 220)?;
+        // Sync word on, two bytes
+        radio.write_register(REG_SYNCCONFIG, 0x88)?;
+        radio.write_registers(REG_SYNCVALUE1, &SYNC_WORD)?;
+        radio.write_register(REG_PACKETCONFIG1, PACKETCONFIG1_VARIABLE_CRC)?;
+        radio.write_register(REG_PAYLOADLENGTH, 66)?;
+        // Start sending as soon as there's a byte in the FIFO
+        radio.write_register(REG_FIFOTHRESH, 0x8F)?;
+        radio.write_register(REG_PACKETCONFIG2, PACKETCONFIG2_AUTORXRESTART)?;
+        // Improved fading margin, as recommended for AfcLowBetaOn = 0
+        radio.write_register(REG_TESTDAGC, 0x30)?;
+        Ok(radio)
+    }
+
+    /// Set the carrier frequency.
+    pub fn set_frequency(&mut self, frequency_hz: u32) -> Result<(), Error<E>> {
+        let frf = (u64::from(frequency_hz) * 1000) / FSTEP_MILLIHZ;
+        self.write_registers(
+            REG_FRFMSB,
+            &[(frf >> 16) as u8, (frf >> 8) as u8, frf as u8],
+        )
+    }
+
+    /// Turn AES-128 encryption on with the given key, or off with `None`.
+    /// Both ends need the same key.
+    pub fn set_aes_key(&mut self, key: Option<&[u8; 16]>) -> Result<(), Error<E>> {
+        self.set_mode(OPMODE_STANDBY)?;
+        match key {
+            Some(key) => {
+                self.write_registers(REG_AESKEY1, key)?;
+                self.write_register(
+                    REG_PACKETCONFIG2,
+                    PACKETCONFIG2_AUTORXRESTART | PACKETCONFIG2_AESON,
+                )
+            }
+            None => self.write_register(REG_PACKETCONFIG2, PACKETCONFIG2_AUTORXRESTART),
+        }
+    }
+
+    /// Send a packet and wait for it to go. Leaves the radio in standby.
+    pub fn send(&mut self, data: &[u8]) -> Result<(), Error<E>> {
+        if data.len() > MAX_PAYLOAD {
+            return Err(Error::TooLong);
+        }
+        self.set_mode(OPMODE_STANDBY)?;
+        let mut buffer = [0u8; MAX_PAYLOAD + 2];
+        buffer[0] = REG_FIFO | 0x80;
+        buffer[1] = data.len() as u8;
+        buffer[2..data.len() + 2].copy_from_slice(data);
+        self.transfer(&mut buffer[0..data.len() + 2])?;
+        self.set_mode(OPMODE_TX)?;
+        while self.read_register(REG_IRQFLAGS2)? & IRQFLAGS2_PACKETSENT == 0 {}
+        self.set_mode(OPMODE_STANDBY)
+    }
+
+    /// Check for a received packet, putting the radio in receive mode if it
+    /// isn't already.
+    pub fn receive(&mut self) -> Result<Option<Packet>, Error<E>> {
+        if self.mode != OPMODE_RX {
+            self.set_mode(OPMODE_RX)?;
+            return Ok(None);
+        }
+        if self.read_register(REG_IRQFLAGS2)? & IRQFLAGS2_PAYLOADREADY == 0 {
+            return Ok(None);
+        }
+        // RSSI is only valid until we leave receive mode
+        let rssi = -i16::from(self.read_register(REG_RSSIVALUE)?) / 2;
+        self.set_mode(OPMODE_STANDBY)?;
+        let len = (self.read_register(REG_FIFO)? as usize).min(MAX_PAYLOAD);
+        let mut buffer = [0u8; MAX_PAYLOAD + 1];
+        buffer[0] = REG_FIFO;
+        self.transfer(&mut buffer[0..len + 1])?;
+        let mut packet = Packet {
+            len,
+            data: [0u8; MAX_PAYLOAD],
+            rssi,
+        };
+        packet.data[0..len].copy_from_slice(&buffer[1..len + 1]);
+        self.set_mode(OPMODE_RX)?;
+        Ok(Some(packet))
+    }
+
+    fn set_mode(&mut self, mode: u8) -> Result<(), Error<E>> {
+        if mode == self.mode {
+            return Ok(());
+        }
+        // The +20 dBm PA settings must only be on while transmitting
+        if mode == OPMODE_TX {
+            self.write_register(REG_TESTPA1, 0x5D)?;
+            self.write_register(REG_TESTPA2, 0x7C)?;
+        } else if self.mode == OPMODE_TX {
+            self.write_register(REG_TESTPA1, 0x55)?;
+            self.write_register(REG_TESTPA2, 0x70)?;
+        }
+        self.write_register(REG_OPMODE, mode)?;
+        while self.read_register(REG_IRQFLAGS1)? & IRQFLAGS1_MODEREADY == 0 {}
+        self.mode = mode;
+        Ok(())
+    }
+
+    fn read_register(&mut self, reg: u8) -> Result<u8, Error<E>> {
+        let mut buffer = [reg & 0x7F, 0];
+        self.transfer(&mut buffer)?;
+        Ok(buffer[1])
+    }
+
+    fn write_register(&mut self, reg: u8, value: u8) -> Result<(), Error<E>> {
+        self.write_registers(reg, &[value])
+    }
+
+    fn write_registers(&mut self, reg: u8, values: &[u8]) -> Result<(), Error<E>> {
+        let mut buffer = [0u8; 17];
+        buffer[0] = reg | 0x80;
+        buffer[1..values.len() + 1].copy_from_slice(values);
+        self.transfer(&mut buffer[0..values.len() + 1])
+    }
+
+    fn transfer(&mut self, buffer: &mut [u8]) -> Result<(), Error<E>> {
+        self.cs.set_low();
+        let result = self.spi.transfer(buffer).map(|_| ());
+        self.cs.set_high();
+        result.map_err(Error::Spi)
+    }
+}
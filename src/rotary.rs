@@ -0,0 +1,151 @@
+//! A rotary encoder with a push button, like the KY-040 boards, read with
+//! QEI0.
+//!
+//! The two switches go to PD6 (PhA0) and PD7 (PhB0), and the button is a
+//! switch to ground on PE5. The pins use the internal pull-ups, so the
+//! encoder's common pin goes to ground (a KY-040's own pull-ups are fine
+//! too). If it counts the wrong way, swap A and B. PD6 is also the
+//! `analog_joystick`'s button, and PD7 is one of the two locked pins -
+//! `config::Pin` unlocks it for us. On the TM4C1294 PhA0 and PhB0 are on
+//! PL1 and PL2 instead.
+//!
+//! The QEI counts every edge of both switches, which is four counts for
+//! each click of a detented encoder. `poll` turns that into one `Event` per
+//! click, plus one for each press of the button. The button bounces, so
+//! call `poll` every millisecond or so, like `joystick::sample`.
+
+use config::{Pin, Port};
+use pac::QEI0;
+
+/// QEI counts for each click.
+pub const COUNTS_PER_DETENT: i32 = 4;
+
+/// How many polls in a row the button must stay the same.
+const DEBOUNCE_POLLS: u8 = 5;
+
+#[cfg(feature = "tm4c123")]
+const PHASE_A: Pin = Pin {
+    port: Port::D,
+    bit: 6,
+};
+#[cfg(feature = "tm4c123")]
+const PHASE_B: Pin = Pin {
+    port: Port::D,
+    bit: 7,
+};
+#[cfg(feature = "tm4c129")]
+const PHASE_A: Pin = Pin {
+    port: Port::L,
+    bit: 1,
+};
+#[cfg(feature = "tm4c129")]
+const PHASE_B: Pin = Pin {
+    port: Port::L,
+    bit: 2,
+};
+/// PhA0 and PhB0 in the pin mux table
+const PHASE_FUNCTION: u32 = 6;
+const BUTTON: Pin = Pin {
+    port: Port::E,
+    bit: 5,
+};
+
+/// QEICTL: enable, count both edges of both phases, and filter the inputs
+const CTL_ENABLE: u32 = 1 << 0;
+const CTL_CAPMODE: u32 = 1 << 3;
+const CTL_FILTEN: u32 = 1 << 13;
+const CTL_FILTCNT_MAX: u32 = 0xF << 16;
+
+/// Something happened to the encoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Clockwise,
+    Anticlockwise,
+    Press,
+}
+
+pub struct Rotary {
+    qei: QEI0,
+    /// The count at the last click we reported
+    last_count: u32,
+    /// Clicks since `new`, clockwise positive
+    position: i32,
+    /// What we've decided the button is doing
+    pressed: bool,
+    /// What the button said last time, and for how many polls
+    last_button: bool,
+    same_for: u8,
+}
+
+impl Rotary {
+    /// Set up the pins and start counting. The caller must power up QEI0.
+    pub fn new(qei: QEI0) -> Rotary {
+        for pin in [PHASE_A, PHASE_B].iter() {
+            pin.into_af(PHASE_FUNCTION);
+            let port = pin.port.registers();
+            port.pur
+                .modify(|r, w| unsafe { w.bits(r.bits() | (1 << pin.bit)) });
+        }
+        BUTTON.into_input();
+        let port = BUTTON.port.registers();
+        port.pur
+            .modify(|r, w| unsafe { w.bits(r.bits() | (1 << BUTTON.bit)) });
+
+        qei.ctl.write(|w| unsafe { w.bits(0) });
+        qei.maxpos.write(|w| unsafe { w.bits(0xFFFF_FFFF) });
+        qei.pos.write(|w| unsafe { w.bits(0) });
+        qei.ctl
+            .write(|w| unsafe { w.bits(CTL_FILTCNT_MAX | CTL_FILTEN | CTL_CAPMODE | CTL_ENABLE) });
+        Rotary {
+            qei,
+            last_count: 0,
+            position: 0,
+            pressed: false,
+            last_button: false,
+            same_for: 0,
+        }
+    }
+
+    /// Clicks since `new`, clockwise positive.
+    pub fn position(&self) -> i32 {
+        self.position
+    }
+
+    /// Is the button held down (after debouncing)?
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+
+    /// Look for a click or a press. A quick turn is several clicks, which
+    /// come out of several polls.
+    pub fn poll(&mut self) -> Option<Event> {
+        // Active low, and bouncy
+        let button = !BUTTON.is_high();
+        if button == self.last_button {
+            self.same_for = self.same_for.saturating_add(1);
+        } else {
+            self.last_button = button;
+            self.same_for = 1;
+        }
+        if self.same_for == DEBOUNCE_POLLS && button != self.pressed {
+            self.pressed = button;
+            if button {
+                return Some(Event::Press);
+            }
+        }
+
+        let count = self.qei.pos.read().bits();
+        let moved = count.wrapping_sub(self.last_count) as i32;
+        if moved >= COUNTS_PER_DETENT {
+            self.last_count = self.last_count.wrapping_add(COUNTS_PER_DETENT as u32);
+            self.position += 1;
+            Some(Event::Clockwise)
+        } else if moved <= -COUNTS_PER_DETENT {
+            self.last_count = self.last_count.wrapping_sub(COUNTS_PER_DETENT as u32);
+            self.position -= 1;
+            Some(Event::Anticlockwise)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! Half-duplex RS-485 on top of a UART and a transceiver like the MAX3485.
+//!
+//! Only one end of the bus may drive it at a time, so the transceiver has a
+//! Driver Enable (DE) pin, and usually an active-low Receiver Enable (/RE)
+//! pin too. Tie DE and /RE together and give them to us as one GPIO. We
+//! raise it before the first byte goes out and drop it again once the last
+//! stop bit has left the UART - not just the FIFO - so the bus is freed as
+//! soon as possible without chopping off the end of the frame. With /RE
+//! tied to DE you won't hear your own transmissions.
+
+use embedded_hal::digital::OutputPin;
+use embedded_hal::serial;
+use pac::uart0;
+
+/// UARTFR.BUSY
+const FR_BUSY: u32 = 1 << 3;
+
+pub struct Rs485<TX, DE> {
+    tx: TX,
+    de: DE,
+    uart: &'static uart0::RegisterBlock,
+    driving: bool,
+}
+
+impl<TX, DE, E> Rs485<TX, DE>
+where
+    TX: serial::Write<u8, Error = E>,
+    DE: OutputPin,
+{
+    /// Wrap the transmit half of a UART. `uart` must be the same UART's
+    /// registers, so we can see when it has really finished.
+    pub fn new(tx: TX, mut de: DE, uart: &'static uart0::RegisterBlock) -> Rs485<TX, DE> {
+        de.set_low();
+        Rs485 {
+            tx,
+            de,
+            uart,
+            driving: false,
+        }
+    }
+
+    /// Send a whole frame and let go of the bus.
+    pub fn send(&mut self, bytes: &[u8]) -> Result<(), E> {
+        for byte in bytes {
+            block!(serial::Write::write(self, *byte))?;
+        }
+        block!(serial::Write::flush(self))
+    }
+
+    /// Are we driving the bus at the moment?
+    pub fn is_driving(&self) -> bool {
+        self.driving
+    }
+
+    /// Give back the UART and the pin.
+    pub fn free(self) -> (TX, DE) {
+        (self.tx, self.de)
+    }
+}
+
+impl<TX, DE, E> serial::Write<u8> for Rs485<TX, DE>
+where
+    TX: serial::Write<u8, Error = E>,
+    DE: OutputPin,
+{
+    type Error = E;
+
+    /// Take the bus (if we haven't already) and queue a byte.
+    fn write(&mut self, byte: u8) -> ::nb::Result<(), E> {
+        if !self.driving {
+            self.de.set_high();
+            self.driving = true;
+        }
+        self.tx.write(byte)
+    }
+
+    /// Wait for the UART to go idle, then let go of the bus.
+    fn flush(&mut self) -> ::nb::Result<(), E> {
+        self.tx.flush()?;
+        if self.uart.fr.read().bits() & FR_BUSY != 0 {
+            return Err(::nb::Error::WouldBlock);
+        }
+        if self.driving {
+            self.de.set_low();
+            self.driving = false;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+//! A tiny cooperative scheduler.
+//!
+//! Each `Task` is a function and how often (in milliseconds) it wants to
+//! run. SysTick counts milliseconds, and `Scheduler::run` calls whichever
+//! tasks are due, then sleeps until the next tick. Tasks get a `&mut` to
+//! whatever state the example keeps its peripherals in, so there are no
+//! statics to share.
+//!
+//! Tasks must not block - if one needs to wait, it should return and check
+//! again next time. A task that overruns just delays the others; a period
+//! is never run twice to catch up.
+//!
+//! SysTick is ours once `start` has it, so use `Scheduler::sleep_ms` rather
+//! than `Delay`. The example must send the SysTick exception here:
+//!
+//! ``` ignore
+//! exception!(SysTick, demo::scheduler::tick);
+//! ```
+
+use cortex_m::asm;
+use cortex_m::peripheral::{SystClkSource, SYST};
+use hal::sysctl::Clocks;
+
+/// How many tasks a `Scheduler` can hold.
+pub const MAX_TASKS: usize = 8;
+
+/// Milliseconds since `start`, counted by `tick`.
+static mut TICKS: u32 = 0;
+
+/// Something to run every so often. `C` is the state the tasks share.
+pub struct Task<C> {
+    /// For debugging
+    pub name: &'static str,
+    /// How often to run, in milliseconds. Zero means every time round.
+    pub period_ms: u32,
+    /// What to run
+    pub run: fn(&mut C),
+}
+
+/// Runs `Task`s when they're due.
+pub struct Scheduler<'a, C: 'a> {
+    tasks: &'a [Task<C>],
+    next: [u32; MAX_TASKS],
+    _syst: SYST,
+}
+
+/// Start SysTick interrupting every millisecond and make a scheduler for
+/// the given tasks. They all run the first time round.
+pub fn start<'a, C>(mut syst: SYST, clocks: &Clocks, tasks: &'a [Task<C>]) -> Scheduler<'a, C> {
+    assert!(tasks.len() <= MAX_TASKS);
+    syst.set_clock_source(SystClkSource::Core);
+    syst.set_reload((clocks.sysclk.0 / 1000) - 1);
+    syst.clear_current();
+    unsafe { TICKS = 0 };
+    syst.enable_counter();
+    syst.enable_interrupt();
+    let now = now();
+    Scheduler {
+        tasks,
+        next: [now; MAX_TASKS],
+        _syst: syst,
+    }
+}
+
+/// The SysTick handler.
+pub fn tick() {
+    unsafe { TICKS = TICKS.wrapping_add(1) };
+}
+
+/// Milliseconds since `start`. Wraps after about 49 days.
+pub fn now() -> u32 {
+    unsafe { ::core::ptr::read_volatile(&TICKS) }
+}
+
+impl<'a, C> Scheduler<'a, C> {
+    /// Run every task that's due, once.
+    pub fn poll(&mut self, context: &mut C) {
+        for (task, next) in self.tasks.iter().zip(self.next.iter_mut()) {
+            let now = now();
+            // Wrapping compare, so this survives the counter rolling over
+            if (now.wrapping_sub(*next) as i32) >= 0 {
+                (task.run)(context);
+                *next = now.wrapping_add(task.period_ms);
+            }
+        }
+    }
+
+    /// Run the tasks forever, sleeping between ticks.
+    pub fn run(&mut self, context: &mut C) -> ! {
+        loop {
+            self.poll(context);
+            asm::wfi();
+        }
+    }
+
+    /// Wait, running the tasks, for the given number of milliseconds. For
+    /// code outside a task that would otherwise use `Delay`.
+    pub fn sleep_ms(&mut self, context: &mut C, ms: u32) {
+        let start = now();
+        while now().wrapping_sub(start) < ms {
+            self.poll(context);
+            asm::wfi();
+        }
+    }
+}
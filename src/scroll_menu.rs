@@ -0,0 +1,275 @@
+//! A menu you scroll through, for when there's no keyboard.
+//!
+//! It takes the same `menu::Menu` a `Runner` would, and draws it on a
+//! `Canvas` as a list with the current item highlighted. `next` and
+//! `previous` move the highlight (a `rotary` encoder's clicks, say) and
+//! `select` runs the item (its button): a callback is called, and a
+//! sub-menu is entered, calling its `entry` and `exit` as the `Runner`
+//! would. Sub-menus get a `..` item at the top to go back up.
+//!
+//! A callback is called with just its command, as if it had been typed
+//! with no arguments, so anything that needs an argument should get a
+//! sub-menu of choices instead. Whatever it writes to the `Console` is
+//! captured and shown under the list.
+//!
+//! Everything goes below the `status_bar`, which is left to the caller:
+//!
+//! ``` ignore
+//! let mut view = ScrollMenu::new(&ROOT_MENU);
+//! view.draw(fb);
+//! loop {
+//!     match rotary.poll() {
+//!         Some(rotary::Event::Clockwise) => view.next(),
+//!         Some(rotary::Event::Anticlockwise) => view.previous(),
+//!         Some(rotary::Event::Press) => view.select(),
+//!         None => continue,
+//!     }
+//!     view.draw(fb);
+//! }
+//! ```
+
+use console::{self, Captured};
+use font;
+use graphics::{Canvas, Colour};
+use menu::{Item, ItemType, Menu};
+use status_bar;
+
+/// How many sub-menus deep we can go.
+const DEPTH: usize = 4;
+
+/// Text rows for the list, and for the captured output under it. With the
+/// heading and the status bar, that's a 300 line screen.
+const LIST_ROWS: usize = 10;
+const OUTPUT_ROWS: usize = 6;
+
+/// Columns for the command, before its help text.
+const COMMAND_COLS: usize = 12;
+
+/// The item that goes back up a level.
+const BACK: &str = "..";
+
+pub struct ScrollMenu<'a> {
+    /// The menus we're in, outermost first
+    menus: [&'a Menu<'a>; DEPTH],
+    depth: usize,
+    /// The highlighted entry, counting `..`
+    selected: usize,
+    /// The entry on the top row of the list
+    top: usize,
+    /// What the last callback said
+    output: Option<Captured>,
+}
+
+impl<'a> ScrollMenu<'a> {
+    pub fn new(root: &'a Menu<'a>) -> ScrollMenu<'a> {
+        ScrollMenu {
+            menus: [root; DEPTH],
+            depth: 0,
+            selected: 0,
+            top: 0,
+            output: None,
+        }
+    }
+
+    /// The menu we're in.
+    pub fn menu(&self) -> &'a Menu<'a> {
+        self.menus[self.depth]
+    }
+
+    /// Move the highlight down, unless it's at the bottom.
+    pub fn next(&mut self) {
+        if self.selected + 1 < self.entries() {
+            self.selected += 1;
+            if self.selected >= self.top + LIST_ROWS {
+                self.top = self.selected + 1 - LIST_ROWS;
+            }
+        }
+    }
+
+    /// Move the highlight up, unless it's at the top.
+    pub fn previous(&mut self) {
+        if self.selected > 0 {
+            self.selected -= 1;
+            if self.selected < self.top {
+                self.top = self.selected;
+            }
+        }
+    }
+
+    /// Run the highlighted item.
+    pub fn select(&mut self) {
+        if self.selected >= self.entries() {
+            return;
+        }
+        let menu = self.menu();
+        let item = match self.item(self.selected) {
+            Some(item) => item,
+            None => {
+                self.output = menu.exit.map(|exit| console::capture(|| exit(menu)));
+                self.depth -= 1;
+                self.selected = 0;
+                self.top = 0;
+                return;
+            }
+        };
+        match item.item_type {
+            ItemType::Callback(callback) => {
+                self.output = Some(console::capture(|| callback(menu, item, item.command)));
+            }
+            ItemType::Menu(sub) if self.depth + 1 < DEPTH => {
+                self.depth += 1;
+                self.menus[self.depth] = sub;
+                self.selected = 0;
+                self.top = 0;
+                self.output = sub.entry.map(|entry| console::capture(|| entry(sub)));
+            }
+            ItemType::Menu(_) => {}
+        }
+    }
+
+    /// Draw the heading, the list and the output, under the status bar.
+    pub fn draw<C>(&self, canvas: &mut C)
+    where
+        C: Canvas,
+    {
+        let cols = canvas.width() / font::WIDTH;
+        let mut line = [b' '; 64];
+        let cols = cols.min(line.len());
+
+        // The heading is the path to here
+        let mut len = 0;
+        for menu in self.menus[0..=self.depth].iter() {
+            len = put(&mut line[0..cols], len, menu.label.as_bytes());
+            len = put(&mut line[0..cols], len, b"/");
+        }
+        draw_row(canvas, 0, &line[0..cols], true);
+
+        for row in 0..LIST_ROWS {
+            let entry = self.top + row;
+            clear(&mut line);
+            if entry < self.entries() {
+                let mut len = 1;
+                match self.item(entry) {
+                    Some(item) => {
+                        len = put(&mut line[0..cols], len, item.command.as_bytes());
+                        if let ItemType::Menu(_) = item.item_type {
+                            len = put(&mut line[0..cols], len, b"/");
+                        }
+                        len = len.max(1 + COMMAND_COLS);
+                        if let Some(help) = item.help {
+                            put(&mut line[0..cols], len, help.as_bytes());
+                        }
+                    }
+                    None => {
+                        len = put(&mut line[0..cols], len, BACK.as_bytes());
+                        put(&mut line[0..cols], len.max(1 + COMMAND_COLS), b"back up");
+                    }
+                }
+            }
+            draw_row(canvas, 1 + row, &line[0..cols], entry == self.selected);
+        }
+
+        // The output, or as much of the end of it as fits
+        let bytes = self.output.as_ref().map(|o| o.as_bytes()).unwrap_or(&[]);
+        let mut lines = 0;
+        for_each_line(bytes, cols, |_, _| lines += 1);
+        let skip = lines.saturating_sub(OUTPUT_ROWS);
+        let first = 1 + LIST_ROWS;
+        for row in 0..OUTPUT_ROWS {
+            clear(&mut line);
+            draw_row(canvas, first + row, &line[0..cols], false);
+        }
+        for_each_line(bytes, cols, |n, text| {
+            if n >= skip {
+                clear(&mut line);
+                put(&mut line[0..cols], 0, text);
+                draw_row(canvas, first + n - skip, &line[0..cols], false);
+            }
+        });
+    }
+
+    /// How many entries in the list, counting `..`.
+    fn entries(&self) -> usize {
+        self.menu().items.len() + if self.depth > 0 { 1 } else { 0 }
+    }
+
+    /// The item for an entry, or `None` for `..`.
+    fn item(&self, entry: usize) -> Option<&'a Item<'a>> {
+        if self.depth == 0 {
+            Some(self.menu().items[entry])
+        } else if entry == 0 {
+            None
+        } else {
+            Some(self.menu().items[entry - 1])
+        }
+    }
+}
+
+/// Copy `text` into `line` at `at`, as far as it fits. Gives where the
+/// text ended.
+fn put(line: &mut [u8], at: usize, text: &[u8]) -> usize {
+    let mut at = at;
+    for &byte in text {
+        if at == line.len() {
+            break;
+        }
+        line[at] = byte;
+        at += 1;
+    }
+    at
+}
+
+fn clear(line: &mut [u8]) {
+    for byte in line.iter_mut() {
+        *byte = b' ';
+    }
+}
+
+/// Draw a text row, counting from the one under the status bar.
+fn draw_row<C>(canvas: &mut C, row: usize, line: &[u8], highlight: bool)
+where
+    C: Canvas,
+{
+    let (fg, bg) = if highlight {
+        (Colour::BLACK, Colour::WHITE)
+    } else {
+        (Colour::WHITE, Colour::BLACK)
+    };
+    let y = status_bar::HEIGHT + (row * font::HEIGHT);
+    for (col, &byte) in line.iter().enumerate() {
+        // The font only does ASCII for certain
+        let byte = if byte.is_ascii() { byte } else { b'?' };
+        canvas.draw_char(col * font::WIDTH, y, byte, fg, bg);
+    }
+}
+
+/// Split console output into numbered lines, wrapping at `cols`.
+fn for_each_line<F>(bytes: &[u8], cols: usize, mut f: F)
+where
+    F: FnMut(usize, &[u8]),
+{
+    let mut n = 0;
+    let mut start = 0;
+    for (i, &byte) in bytes.iter().enumerate() {
+        if byte == b'\n' {
+            f(n, strip(&bytes[start..i]));
+            n += 1;
+            start = i + 1;
+        } else if i - start == cols {
+            f(n, &bytes[start..i]);
+            n += 1;
+            start = i;
+        }
+    }
+    if start < bytes.len() {
+        f(n, strip(&bytes[start..]));
+    }
+}
+
+/// Drop the `\r` the `Console` puts before a `\n`.
+fn strip(line: &[u8]) -> &[u8] {
+    match line.last() {
+        Some(&b'\r') => &line[0..line.len() - 1],
+        _ => line,
+    }
+}
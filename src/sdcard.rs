@@ -0,0 +1,359 @@
+//! SD and SDHC cards, in SPI mode.
+//!
+//! Any SD card will talk SPI, which saves needing the SD bus. Wire the
+//! card (or a breakout board - most have a 3.3V regulator and nothing else
+//! on them) to SSI0 and a spare GPIO for chip select, as for the other SPI
+//! examples. The bus must be SPI mode 0, at 400 kHz or less until `init`
+//! has woken the card up. After that, most cards cope with 20 MHz - use
+//! `spi::Spi::set_frequency` through `spi()`.
+//!
+//! We only read and write single 512 byte blocks, which is all the FAT
+//! layer (`demo::fat`) needs. Version 1 cards address in bytes and SDHC
+//! (and SDXC) cards in blocks, and we hide that. Cards can be pulled out
+//! at any time: the next command times out, and after the card's back
+//! `init` brings it up again.
+
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::blocking::spi;
+use embedded_hal::digital::OutputPin;
+use fat::{BlockDevice, BLOCK_LEN};
+
+/// How many times to try CMD0 before deciding there's no card.
+const IDLE_TRIES: u32 = 32;
+
+/// How long the card has to finish starting up (ACMD41), in 10ms steps.
+const READY_TRIES: u32 = 100;
+
+/// How many bytes to wait for a read to start, or a write to finish. A
+/// write can take 250ms, so at 20 MHz this is about half a second.
+const BUSY_TRIES: u32 = 1_000_000;
+
+const CMD_GO_IDLE_STATE: u8 = 0;
+const CMD_SEND_IF_COND: u8 = 8;
+const CMD_SET_BLOCKLEN: u8 = 16;
+const CMD_READ_SINGLE_BLOCK: u8 = 17;
+const CMD_WRITE_BLOCK: u8 = 24;
+const CMD_APP_CMD: u8 = 55;
+const CMD_READ_OCR: u8 = 58;
+const ACMD_SD_SEND_OP_COND: u8 = 41;
+
+/// The bits of the R1 response that come first in every reply.
+const R1_IDLE: u8 = 0x01;
+const R1_ILLEGAL_COMMAND: u8 = 0x04;
+
+/// 2.7-3.6V, and the check pattern that CMD8 echoes back.
+const IF_COND: u32 = 0x1AA;
+
+/// ACMD41's 'we can do SDHC'.
+const OP_COND_HCS: u32 = 1 << 30;
+
+/// Card Capacity Status in the OCR - set for SDHC and SDXC.
+const OCR_CCS: u32 = 1 << 30;
+
+const TOKEN_START_BLOCK: u8 = 0xFE;
+
+/// The data response to a write, with the don't-care bits masked off.
+const DATA_RESPONSE_MASK: u8 = 0x1F;
+const DATA_ACCEPTED: u8 = 0x05;
+
+/// Something went wrong talking to the card.
+#[derive(Debug)]
+pub enum Error<E> {
+    /// The SPI bus reported an error
+    Spi(E),
+    /// No card answered, or it stopped answering
+    Timeout,
+    /// The card turned a command down - this is its R1 response
+    Command(u8),
+    /// An MMC card, or something else we don't handle
+    Unsupported,
+    /// A read came back with this error token instead of the data
+    Read(u8),
+    /// The card didn't take a block we wrote - this is its data response
+    Write(u8),
+}
+
+/// Which sort of card `init` found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardType {
+    /// An original SD card, up to 2 GB
+    Sd1,
+    /// A version 2 SD card, up to 2 GB
+    Sd2,
+    /// SDHC or SDXC
+    Sdhc,
+}
+
+pub struct SdCard<SPI, CS> {
+    spi: SPI,
+    cs: CS,
+    card_type: Option<CardType>,
+}
+
+impl<SPI, CS, E> SdCard<SPI, CS>
+where
+    SPI: spi::Transfer<u8, Error = E>,
+    CS: OutputPin,
+{
+    /// Take the bus and chip select. Nothing is said to the card until
+    /// `init`.
+    pub fn new(spi: SPI, mut cs: CS) -> SdCard<SPI, CS> {
+        cs.set_high();
+        SdCard {
+            spi,
+            cs,
+            card_type: None,
+        }
+    }
+
+    /// Give the bus and chip select back.
+    pub fn free(self) -> (SPI, CS) {
+        (self.spi, self.cs)
+    }
+
+    /// The bus, to speed it up after `init`.
+    pub fn spi(&mut self) -> &mut SPI {
+        &mut self.spi
+    }
+
+    /// What `init` found, if it worked.
+    pub fn card_type(&self) -> Option<CardType> {
+        self.card_type
+    }
+
+    /// Wake the card up and put it into SPI mode. Call this again after a
+    /// card's been pulled out and put back (or swapped).
+    pub fn init<D>(&mut self, delay: &mut D) -> Result<CardType, Error<E>>
+    where
+        D: DelayMs<u32>,
+    {
+        self.card_type = None;
+        // At least 74 clocks with chip select high
+        self.cs.set_high();
+        for _ in 0..10 {
+            self.transfer_byte(0xFF)?;
+        }
+
+        let result = self.start_up(delay);
+        self.deselect()?;
+        let card_type = result?;
+        self.card_type = Some(card_type);
+        Ok(card_type)
+    }
+
+    fn start_up<D>(&mut self, delay: &mut D) -> Result<CardType, Error<E>>
+    where
+        D: DelayMs<u32>,
+    {
+        // CMD0 with chip select low puts the card in SPI mode. The CRC only
+        // matters for this and CMD8 - after that, SPI mode doesn't check.
+        let mut tries = 0;
+        loop {
+            match self.command(CMD_GO_IDLE_STATE, 0) {
+                Ok(R1_IDLE) => break,
+                Ok(_) | Err(Error::Timeout) if tries < IDLE_TRIES => tries += 1,
+                Ok(r1) => return Err(Error::Command(r1)),
+                Err(e) => return Err(e),
+            }
+        }
+
+        // Version 1 cards don't know CMD8
+        let r1 = self.command(CMD_SEND_IF_COND, IF_COND)?;
+        let version2 = if r1 & R1_ILLEGAL_COMMAND != 0 {
+            false
+        } else {
+            if self.read_u32()? & 0xFFF != IF_COND {
+                return Err(Error::Unsupported);
+            }
+            true
+        };
+
+        let arg = if version2 { OP_COND_HCS } else { 0 };
+        let mut tries = 0;
+        loop {
+            self.command(CMD_APP_CMD, 0)?;
+            match self.command(ACMD_SD_SEND_OP_COND, arg)? {
+                0 => break,
+                R1_IDLE if tries < READY_TRIES => {
+                    tries += 1;
+                    delay.delay_ms(10);
+                }
+                R1_IDLE => return Err(Error::Timeout),
+                // MMC cards don't know ACMD41
+                _ => return Err(Error::Unsupported),
+            }
+        }
+
+        let card_type = if version2 {
+            match self.command(CMD_READ_OCR, 0)? {
+                0 => {}
+                r1 => return Err(Error::Command(r1)),
+            }
+            if self.read_u32()? & OCR_CCS != 0 {
+                CardType::Sdhc
+            } else {
+                CardType::Sd2
+            }
+        } else {
+            CardType::Sd1
+        };
+
+        // SDHC blocks are always 512 bytes. The others start that way,
+        // but it's not promised.
+        if card_type != CardType::Sdhc {
+            match self.command(CMD_SET_BLOCKLEN, BLOCK_LEN as u32)? {
+                0 => {}
+                r1 => return Err(Error::Command(r1)),
+            }
+        }
+        Ok(card_type)
+    }
+
+    /// Read one block.
+    pub fn read_block(&mut self, block: u32, data: &mut [u8; BLOCK_LEN]) -> Result<(), Error<E>> {
+        let address = self.address(block)?;
+        let result = self.read_data(address, data);
+        self.deselect()?;
+        result
+    }
+
+    fn read_data(&mut self, address: u32, data: &mut [u8; BLOCK_LEN]) -> Result<(), Error<E>> {
+        match self.command(CMD_READ_SINGLE_BLOCK, address)? {
+            0 => {}
+            r1 => return Err(Error::Command(r1)),
+        }
+        let mut tries = 0;
+        loop {
+            match self.transfer_byte(0xFF)? {
+                0xFF if tries < BUSY_TRIES => tries += 1,
+                0xFF => return Err(Error::Timeout),
+                TOKEN_START_BLOCK => break,
+                token => return Err(Error::Read(token)),
+            }
+        }
+        for byte in data.iter_mut() {
+            *byte = 0xFF;
+        }
+        self.spi.transfer(data).map_err(Error::Spi)?;
+        // The CRC, which we don't check
+        self.transfer_byte(0xFF)?;
+        self.transfer_byte(0xFF)?;
+        Ok(())
+    }
+
+    /// Write one block, and wait until the card has finished with it.
+    pub fn write_block(&mut self, block: u32, data: &[u8; BLOCK_LEN]) -> Result<(), Error<E>> {
+        let address = self.address(block)?;
+        let result = self.write_data(address, data);
+        self.deselect()?;
+        result
+    }
+
+    fn write_data(&mut self, address: u32, data: &[u8; BLOCK_LEN]) -> Result<(), Error<E>> {
+        match self.command(CMD_WRITE_BLOCK, address)? {
+            0 => {}
+            r1 => return Err(Error::Command(r1)),
+        }
+        self.transfer_byte(0xFF)?;
+        self.transfer_byte(TOKEN_START_BLOCK)?;
+        for &byte in data.iter() {
+            self.transfer_byte(byte)?;
+        }
+        // A CRC, which isn't checked
+        self.transfer_byte(0xFF)?;
+        self.transfer_byte(0xFF)?;
+        let response = self.transfer_byte(0xFF)? & DATA_RESPONSE_MASK;
+        if response != DATA_ACCEPTED {
+            return Err(Error::Write(response));
+        }
+        self.wait_ready()
+    }
+
+    /// Block number to what the card wants in a read or write command.
+    fn address(&self, block: u32) -> Result<u32, Error<E>> {
+        match self.card_type {
+            Some(CardType::Sdhc) => Ok(block),
+            Some(_) => Ok(block * BLOCK_LEN as u32),
+            None => Err(Error::Timeout),
+        }
+    }
+
+    /// Select the card and send it a command, returning its R1 response.
+    /// Chip select is left low for whatever comes next.
+    fn command(&mut self, command: u8, arg: u32) -> Result<u8, Error<E>> {
+        self.cs.set_low();
+        // Anything but CMD0 waits for the last write to finish
+        if command != CMD_GO_IDLE_STATE {
+            self.wait_ready()?;
+        }
+        let crc = match command {
+            CMD_GO_IDLE_STATE => 0x95,
+            CMD_SEND_IF_COND => 0x87,
+            _ => 0x01,
+        };
+        let mut frame = [
+            0x40 | command,
+            (arg >> 24) as u8,
+            (arg >> 16) as u8,
+            (arg >> 8) as u8,
+            arg as u8,
+            crc,
+        ];
+        self.spi.transfer(&mut frame).map_err(Error::Spi)?;
+        // The response comes within eight bytes, and has its top bit clear
+        for _ in 0..8 {
+            let r1 = self.transfer_byte(0xFF)?;
+            if r1 & 0x80 == 0 {
+                return Ok(r1);
+            }
+        }
+        Err(Error::Timeout)
+    }
+
+    /// The four bytes after an R3 or R7 response.
+    fn read_u32(&mut self) -> Result<u32, Error<E>> {
+        let mut bytes = [0xFF; 4];
+        self.spi.transfer(&mut bytes).map_err(Error::Spi)?;
+        Ok((u32::from(bytes[0]) << 24) | (u32::from(bytes[1]) << 16)
+            | (u32::from(bytes[2]) << 8) | u32::from(bytes[3]))
+    }
+
+    /// The card holds MISO low while it's busy.
+    fn wait_ready(&mut self) -> Result<(), Error<E>> {
+        for _ in 0..BUSY_TRIES {
+            if self.transfer_byte(0xFF)? == 0xFF {
+                return Ok(());
+            }
+        }
+        Err(Error::Timeout)
+    }
+
+    /// Chip select high, and one more byte so the card lets go of MISO.
+    fn deselect(&mut self) -> Result<(), Error<E>> {
+        self.cs.set_high();
+        self.transfer_byte(0xFF)?;
+        Ok(())
+    }
+
+    fn transfer_byte(&mut self, byte: u8) -> Result<u8, Error<E>> {
+        let mut buffer = [byte];
+        self.spi.transfer(&mut buffer).map_err(Error::Spi)?;
+        Ok(buffer[0])
+    }
+}
+
+impl<SPI, CS, E> BlockDevice for SdCard<SPI, CS>
+where
+    SPI: spi::Transfer<u8, Error = E>,
+    CS: OutputPin,
+{
+    type Error = Error<E>;
+
+    fn read_block(&mut self, block: u32, data: &mut [u8; BLOCK_LEN]) -> Result<(), Error<E>> {
+        SdCard::read_block(self, block, data)
+    }
+
+    fn write_block(&mut self, block: u32, data: &[u8; BLOCK_LEN]) -> Result<(), Error<E>> {
+        SdCard::write_block(self, block, data)
+    }
+}
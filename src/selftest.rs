@@ -0,0 +1,312 @@
+//! Loopback tests for the on-chip peripherals, for checking a home-made
+//! board.
+//!
+//! Each test sends something out and checks it comes back:
+//!
+//! * `uart1` - bytes out of PB1 (U1Tx) and into PB0 (U1Rx). Needs a jumper.
+//! * `ssi1` - bytes through SSI1 in internal loopback mode.
+//! * `can0` - a frame through CAN0 in internal loopback mode. The CAN pins
+//!   aren't touched, so no transceiver is needed.
+//! * `adc0` - the temperature sensor, which should read something a person
+//!   could stand (5 to 60 degrees C) if the ADC and its reference are OK.
+//! * `eeprom` - a pattern written to the last block of the EEPROM and read
+//!   back. The old contents are put back afterwards.
+//! * `gpio` - PE2 driven high and low, and read on PE3. Needs a jumper.
+//!
+//! `run` prints a table:
+//!
+//! ```text
+//! Test    Result
+//! uart1   PASS
+//! ssi1    PASS
+//! can0    FAIL (no frame received)
+//! ...
+//! ```
+//!
+//! The caller must power up SSI1, CAN0, ADC0 and the EEPROM, set UART1 up
+//! on PB0 and PB1, and build a `Rig`. The GPIO pins are set up here.
+
+use adc::{self, Adc};
+use config::{Pin, Port};
+use core::fmt;
+use eeprom::{self, Eeprom};
+use embedded_hal::blocking::spi::Transfer;
+use embedded_hal::serial::{Read, Write};
+use hal::serial::{Rx, Tx};
+use pac::{self, ADC0, CAN0, SSI1, UART1};
+use spi::Spi;
+
+/// How long to wait for a byte or a frame, in trips round a polling loop.
+const TIMEOUT: u32 = 100_000;
+
+/// What the loopback tests send.
+const PATTERN: [u8; 8] = [0x00, 0xFF, 0x55, 0xAA, 0x01, 0x80, 0x3C, 0xC3];
+
+/// Driven by the `gpio` test.
+const GPIO_OUT: Pin = Pin {
+    port: Port::E,
+    bit: 2,
+};
+/// Read by the `gpio` test.
+const GPIO_IN: Pin = Pin {
+    port: Port::E,
+    bit: 3,
+};
+
+/// CANCTL: initialisation
+const CAN_CTL_INIT: u32 = 1 << 0;
+/// CANCTL: configuration change enable
+const CAN_CTL_CCE: u32 = 1 << 6;
+/// CANCTL: test mode
+const CAN_CTL_TEST: u32 = 1 << 7;
+/// CANTST: loopback
+const CAN_TST_LBACK: u32 = 1 << 4;
+/// CANBIT for 500 kbit/s at 80 MHz: BRP = 10, TSEG1 = 12, TSEG2 = 3
+const CAN_BIT_500K: u32 = 9 | (11 << 8) | (2 << 12);
+/// CANIFnCMSK: write to the message object, rather than read
+const CAN_CMSK_WRNRD: u32 = 1 << 7;
+/// CANIFnCMSK: transfer the arbitration bits
+const CAN_CMSK_ARB: u32 = 1 << 5;
+/// CANIFnCMSK: transfer the control bits
+const CAN_CMSK_CONTROL: u32 = 1 << 4;
+/// CANIFnCMSK: clear NEWDAT on a read
+const CAN_CMSK_NEWDAT: u32 = 1 << 2;
+/// CANIFnCMSK: transfer data bytes 0 to 3
+const CAN_CMSK_DATAA: u32 = 1 << 1;
+/// CANIFnCMSK: transfer data bytes 4 to 7
+const CAN_CMSK_DATAB: u32 = 1 << 0;
+/// CANIFnCRQ: the transfer is still going
+const CAN_CRQ_BUSY: u32 = 1 << 15;
+/// CANIFnARB2: the message object is in use
+const CAN_ARB2_MSGVAL: u32 = 1 << 15;
+/// CANIFnARB2: transmit, rather than receive
+const CAN_ARB2_DIR: u32 = 1 << 13;
+/// CANIFnMCTL: send it
+const CAN_MCTL_TXRQST: u32 = 1 << 8;
+/// CANIFnMCTL: end of buffer (we don't use FIFOs)
+const CAN_MCTL_EOB: u32 = 1 << 7;
+/// The frame's (standard) identifier
+const CAN_ID: u32 = 0x123;
+/// The message object that sends
+const CAN_TX_OBJECT: u32 = 1;
+/// The message object that receives
+const CAN_RX_OBJECT: u32 = 2;
+
+/// The peripherals under test.
+pub struct Rig {
+    pub uart1_tx: Tx<UART1>,
+    pub uart1_rx: Rx<UART1>,
+    pub ssi1: Spi<SSI1>,
+    pub can0: CAN0,
+    pub adc0: Adc<ADC0>,
+    /// `None` if the EEPROM didn't start up, which fails the test
+    pub eeprom: Option<Eeprom>,
+}
+
+/// A test.
+pub struct Test {
+    pub name: &'static str,
+    pub run: fn(&mut Rig) -> Result<(), &'static str>,
+}
+
+/// Everything `run` runs, in order.
+pub static TESTS: [Test; 6] = [
+    Test {
+        name: "uart1",
+        run: uart1,
+    },
+    Test {
+        name: "ssi1",
+        run: ssi1,
+    },
+    Test {
+        name: "can0",
+        run: can0,
+    },
+    Test {
+        name: "adc0",
+        run: adc0,
+    },
+    Test {
+        name: "eeprom",
+        run: eeprom,
+    },
+    Test {
+        name: "gpio",
+        run: gpio,
+    },
+];
+
+/// Run every test, printing the results as we go. Returns how many failed.
+pub fn run<W>(rig: &mut Rig, out: &mut W) -> Result<usize, fmt::Error>
+where
+    W: fmt::Write,
+{
+    let mut failed = 0;
+    writeln!(out, "Test    Result")?;
+    for test in TESTS.iter() {
+        match (test.run)(rig) {
+            Ok(()) => writeln!(out, "{:<8}PASS", test.name)?,
+            Err(reason) => {
+                writeln!(out, "{:<8}FAIL ({})", test.name, reason)?;
+                failed += 1;
+            }
+        }
+    }
+    Ok(failed)
+}
+
+fn uart1(rig: &mut Rig) -> Result<(), &'static str> {
+    // Throw away anything left over
+    while rig.uart1_rx.read().is_ok() {}
+    for &byte in PATTERN.iter() {
+        block!(rig.uart1_tx.write(byte)).map_err(|_| "can't send")?;
+        let mut tries = 0;
+        let got = loop {
+            match rig.uart1_rx.read() {
+                Ok(got) => break got,
+                Err(::nb::Error::WouldBlock) if tries < TIMEOUT => tries += 1,
+                Err(::nb::Error::WouldBlock) => return Err("nothing received"),
+                Err(::nb::Error::Other(_)) => return Err("receive error"),
+            }
+        };
+        if got != byte {
+            return Err("wrong byte received");
+        }
+    }
+    Ok(())
+}
+
+fn ssi1(rig: &mut Rig) -> Result<(), &'static str> {
+    let ssi = unsafe { &*pac::SSI1::ptr() };
+    ssi.cr1.modify(|_, w| w.sse().clear_bit());
+    ssi.cr1.modify(|_, w| w.lbm().set_bit());
+    ssi.cr1.modify(|_, w| w.sse().set_bit());
+    let mut buffer = PATTERN;
+    let result = rig.ssi1.transfer(&mut buffer).map(|got| got == PATTERN);
+    ssi.cr1.modify(|_, w| w.sse().clear_bit());
+    ssi.cr1.modify(|_, w| w.lbm().clear_bit());
+    ssi.cr1.modify(|_, w| w.sse().set_bit());
+    match result {
+        Ok(true) => Ok(()),
+        Ok(false) => Err("wrong bytes received"),
+        Err(_) => Err("overrun"),
+    }
+}
+
+fn can0(rig: &mut Rig) -> Result<(), &'static str> {
+    let can = &rig.can0;
+    let word = |i: usize| u32::from(PATTERN[i]) | (u32::from(PATTERN[i + 1]) << 8);
+
+    can.ctl
+        .write(|w| unsafe { w.bits(CAN_CTL_INIT | CAN_CTL_CCE | CAN_CTL_TEST) });
+    can.bit_.write(|w| unsafe { w.bits(CAN_BIT_500K) });
+    can.tst.write(|w| unsafe { w.bits(CAN_TST_LBACK) });
+
+    // Something to receive it, matching the whole identifier
+    can.if2cmsk.write(|w| unsafe {
+        w.bits(CAN_CMSK_WRNRD | CAN_CMSK_ARB | CAN_CMSK_CONTROL)
+    });
+    can.if2arb1.write(|w| unsafe { w.bits(0) });
+    can.if2arb2
+        .write(|w| unsafe { w.bits(CAN_ARB2_MSGVAL | (CAN_ID << 2)) });
+    can.if2mctl.write(|w| unsafe { w.bits(CAN_MCTL_EOB) });
+    can.if2crq.write(|w| unsafe { w.bits(CAN_RX_OBJECT) });
+    while can.if2crq.read().bits() & CAN_CRQ_BUSY != 0 {}
+
+    // Leaving INIT (but not TEST) puts us on the (looped back) bus
+    can.ctl.write(|w| unsafe { w.bits(CAN_CTL_TEST) });
+
+    // Something to send it
+    can.if1cmsk.write(|w| unsafe {
+        w.bits(
+            CAN_CMSK_WRNRD | CAN_CMSK_ARB | CAN_CMSK_CONTROL | CAN_CMSK_DATAA
+                | CAN_CMSK_DATAB,
+        )
+    });
+    can.if1arb1.write(|w| unsafe { w.bits(0) });
+    can.if1arb2.write(|w| unsafe {
+        w.bits(CAN_ARB2_MSGVAL | CAN_ARB2_DIR | (CAN_ID << 2))
+    });
+    can.if1mctl.write(|w| unsafe {
+        w.bits(CAN_MCTL_TXRQST | CAN_MCTL_EOB | PATTERN.len() as u32)
+    });
+    can.if1da1.write(|w| unsafe { w.bits(word(0)) });
+    can.if1da2.write(|w| unsafe { w.bits(word(2)) });
+    can.if1db1.write(|w| unsafe { w.bits(word(4)) });
+    can.if1db2.write(|w| unsafe { w.bits(word(6)) });
+    can.if1crq.write(|w| unsafe { w.bits(CAN_TX_OBJECT) });
+    while can.if1crq.read().bits() & CAN_CRQ_BUSY != 0 {}
+
+    let mut tries = 0;
+    while can.nwda1.read().bits() & (1 << (CAN_RX_OBJECT - 1)) == 0 {
+        tries += 1;
+        if tries == TIMEOUT {
+            can.ctl.write(|w| unsafe { w.bits(CAN_CTL_INIT) });
+            return Err("no frame received");
+        }
+    }
+
+    can.if2cmsk.write(|w| unsafe {
+        w.bits(CAN_CMSK_CONTROL | CAN_CMSK_NEWDAT | CAN_CMSK_DATAA | CAN_CMSK_DATAB)
+    });
+    can.if2crq.write(|w| unsafe { w.bits(CAN_RX_OBJECT) });
+    while can.if2crq.read().bits() & CAN_CRQ_BUSY != 0 {}
+    let good = can.if2mctl.read().bits() & 0xF == PATTERN.len() as u32
+        && can.if2da1.read().bits() == word(0)
+        && can.if2da2.read().bits() == word(2)
+        && can.if2db1.read().bits() == word(4)
+        && can.if2db2.read().bits() == word(6);
+
+    can.ctl.write(|w| unsafe { w.bits(CAN_CTL_INIT) });
+    if good {
+        Ok(())
+    } else {
+        Err("wrong frame received")
+    }
+}
+
+fn adc0(rig: &mut Rig) -> Result<(), &'static str> {
+    let sample = rig.adc0.read(adc::TEMPERATURE);
+    match adc::temperature(sample) {
+        50...600 => Ok(()),
+        _ => Err("temperature out of range"),
+    }
+}
+
+fn eeprom(rig: &mut Rig) -> Result<(), &'static str> {
+    let eeprom = rig.eeprom.as_mut().ok_or("didn't start up")?;
+    let address = eeprom::WORDS - 16;
+    let mut saved = [0u32; 16];
+    let mut pattern = [0u32; 16];
+    for (i, word) in pattern.iter_mut().enumerate() {
+        let base = if i % 2 == 0 { 0x5555_AAAA } else { 0xAAAA_5555 };
+        *word = base ^ i as u32;
+    }
+    let mut check = [0u32; 16];
+    eeprom.read(address, &mut saved).map_err(|_| "can't read")?;
+    eeprom.write(address, &pattern).map_err(|_| "can't write")?;
+    eeprom.read(address, &mut check).map_err(|_| "can't read")?;
+    eeprom.write(address, &saved).map_err(|_| "can't restore")?;
+    if check == pattern {
+        Ok(())
+    } else {
+        Err("wrong data read back")
+    }
+}
+
+fn gpio(_rig: &mut Rig) -> Result<(), &'static str> {
+    GPIO_IN.into_input();
+    GPIO_OUT.into_output();
+    for &level in [true, false, true, false].iter() {
+        GPIO_OUT.set(level);
+        // Give it a moment to get there
+        for _ in 0..100 {
+            ::cortex_m::asm::nop();
+        }
+        if GPIO_IN.is_high() != level {
+            return Err(if level { "PE3 stuck low" } else { "PE3 stuck high" });
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,205 @@
+//! Settings every demo can share, kept in the EEPROM.
+//!
+//! There's the console's baud rate, the keyboard layout, whether the
+//! screen is colour or monochrome, and the name of a program for a
+//! launcher to start on power-up (empty for none). `load` gives the
+//! defaults if nothing was ever saved.
+//!
+//! The record starts with a `VERSION`. Settings added later go on the end
+//! and bump it; loading an older record keeps what it has and uses the
+//! defaults for the rest. A record from newer firmware than ours is
+//! ignored, as we can't know what it means.
+//!
+//! `get` and `set` take the settings by name, as text, for a command line.
+//! The keyboard's repeat rate stays in `keyboard::Settings`.
+
+use core::fmt::Write;
+use eeprom::{self, Eeprom};
+use keyboard::Layout;
+
+/// Where the `Settings` go in the EEPROM. Eight words.
+pub const EEPROM_ADDRESS: usize = eeprom::WORDS - 48;
+
+/// "SETS"
+const MAGIC: u32 = 0x5354_4553;
+
+/// Which layout `save` writes.
+pub const VERSION: u8 = 1;
+
+/// How long the startup program's name can be.
+pub const STARTUP_LEN: usize = 16;
+
+/// The words in a record: the magic, the version and some small things,
+/// the baud rate and the startup program.
+const WORDS: usize = 3 + (STARTUP_LEN / 4);
+
+/// The baud rates we offer.
+pub const BAUDS: [u32; 8] = [
+    9_600, 19_200, 38_400, 57_600, 115_200, 230_400, 460_800, 921_600,
+];
+
+/// The names `get` and `set` know.
+pub const NAMES: [&str; 4] = ["baud", "layout", "display", "startup"];
+
+/// What sort of screen is plugged in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Display {
+    Colour,
+    Mono,
+}
+
+impl Display {
+    pub const ALL: [Display; 2] = [Display::Colour, Display::Mono];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Display::Colour => "colour",
+            Display::Mono => "mono",
+        }
+    }
+
+    /// The display called `name` (as in `name()`).
+    pub fn from_name(name: &str) -> Option<Display> {
+        Display::ALL.iter().cloned().find(|d| d.name() == name)
+    }
+}
+
+/// Something went wrong with a setting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// There's no setting with that name
+    UnknownName,
+    /// That's not a value the setting can have
+    BadValue,
+    /// The EEPROM didn't take it
+    Eeprom(eeprom::Error),
+}
+
+impl From<eeprom::Error> for Error {
+    fn from(e: eeprom::Error) -> Error {
+        Error::Eeprom(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+    /// UART0's baud rate, one of `BAUDS`
+    pub baud: u32,
+    pub layout: Layout,
+    pub display: Display,
+    startup: [u8; STARTUP_LEN],
+    startup_len: u8,
+}
+
+impl Settings {
+    pub const DEFAULT: Settings = Settings {
+        baud: 115_200,
+        layout: Layout::Us,
+        display: Display::Mono,
+        startup: [0u8; STARTUP_LEN],
+        startup_len: 0,
+    };
+
+    /// Read the settings from the EEPROM, or give the defaults if there
+    /// aren't any we understand.
+    pub fn load(eeprom: &mut Eeprom) -> Settings {
+        let mut words = [0u32; WORDS];
+        if eeprom.read(EEPROM_ADDRESS, &mut words).is_err() || words[0] != MAGIC {
+            return Settings::DEFAULT;
+        }
+        let version = words[1] as u8;
+        if version == 0 || version > VERSION {
+            return Settings::DEFAULT;
+        }
+        // Everything so far came in version 1
+        let mut settings = Settings::DEFAULT;
+        settings.layout = match (words[1] >> 8) as u8 {
+            1 => Layout::Uk,
+            2 => Layout::De,
+            _ => Layout::Us,
+        };
+        settings.display = match (words[1] >> 16) as u8 {
+            0 => Display::Colour,
+            _ => Display::Mono,
+        };
+        if BAUDS.contains(&words[2]) {
+            settings.baud = words[2];
+        }
+        let len = ((words[1] >> 24) as usize).min(STARTUP_LEN);
+        for (i, byte) in settings.startup.iter_mut().enumerate() {
+            *byte = (words[3 + (i / 4)] >> ((i % 4) * 8)) as u8;
+        }
+        // Anything we couldn't read back as text is no name at all
+        settings.startup_len = len as u8;
+        if ::core::str::from_utf8(&settings.startup[0..len]).is_err() {
+            settings.startup_len = 0;
+        }
+        settings
+    }
+
+    /// Write the settings to the EEPROM.
+    pub fn save(&self, eeprom: &mut Eeprom) -> Result<(), Error> {
+        let mut words = [0u32; WORDS];
+        words[0] = MAGIC;
+        words[1] = u32::from(VERSION)
+            | ((self.layout as u32) << 8)
+            | ((self.display as u32) << 16)
+            | (u32::from(self.startup_len) << 24);
+        words[2] = self.baud;
+        for (i, byte) in self.startup.iter().enumerate() {
+            words[3 + (i / 4)] |= u32::from(*byte) << ((i % 4) * 8);
+        }
+        eeprom.write(EEPROM_ADDRESS, &words)?;
+        Ok(())
+    }
+
+    /// The program to start on power-up, or "" for none.
+    pub fn startup(&self) -> &str {
+        let len = usize::from(self.startup_len);
+        ::core::str::from_utf8(&self.startup[0..len]).unwrap_or("")
+    }
+
+    /// Change the startup program. The name must fit in `STARTUP_LEN`
+    /// bytes, and have no spaces in.
+    pub fn set_startup(&mut self, name: &str) -> Result<(), Error> {
+        if name.len() > STARTUP_LEN || name.contains(char::is_whitespace) {
+            return Err(Error::BadValue);
+        }
+        self.startup = [0u8; STARTUP_LEN];
+        self.startup[0..name.len()].copy_from_slice(name.as_bytes());
+        self.startup_len = name.len() as u8;
+        Ok(())
+    }
+
+    /// Write the setting called `name` as text.
+    pub fn get<W: Write>(&self, name: &str, out: &mut W) -> Result<(), Error> {
+        // If the writer fills up, that's its problem, not the setting's
+        let _ = match name {
+            "baud" => write!(out, "{}", self.baud),
+            "layout" => out.write_str(self.layout.name()),
+            "display" => out.write_str(self.display.name()),
+            "startup" => out.write_str(self.startup()),
+            _ => return Err(Error::UnknownName),
+        };
+        Ok(())
+    }
+
+    /// Change the setting called `name`, given as text. This doesn't save
+    /// it.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), Error> {
+        match name {
+            "baud" => {
+                self.baud = value
+                    .parse()
+                    .ok()
+                    .filter(|baud| BAUDS.contains(baud))
+                    .ok_or(Error::BadValue)?
+            }
+            "layout" => self.layout = Layout::from_name(value).ok_or(Error::BadValue)?,
+            "display" => self.display = Display::from_name(value).ok_or(Error::BadValue)?,
+            "startup" => self.set_startup(value)?,
+            _ => return Err(Error::UnknownName),
+        }
+        Ok(())
+    }
+}
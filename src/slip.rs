@@ -0,0 +1,284 @@
+//! Serial Line IP (RFC 1055), and a smoltcp `Device` that uses it.
+//!
+//! SLIP just sends IP packets down a serial line with a `0xC0` byte after
+//! each one. Any `0xC0` or `0xDB` in the packet is escaped. There are no
+//! addresses, no checksums and no negotiation, so Linux's `slattach` will
+//! talk to us with no extra hardware at all.
+//!
+//! smoltcp only knows how to drive Ethernet, so `Slip` pretends to be an
+//! Ethernet card with a single neighbour (the host at the other end of the
+//! wire). IP packets we receive get a made-up Ethernet header, and the
+//! header is stripped off again when we send. When smoltcp asks who has an
+//! IP address with ARP, we answer on the host's behalf.
+
+use embedded_hal::serial;
+use smoltcp;
+use smoltcp::phy::{self, DeviceCapabilities};
+use smoltcp::time::Instant;
+
+/// The largest packet we handle, which is what Linux uses for SLIP by
+/// default.
+pub const MTU: usize = 296;
+
+/// Our pretend Ethernet address, and the host's.
+pub const LOCAL_MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+pub const REMOTE_MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x02];
+
+const END: u8 = 0xC0;
+const ESC: u8 = 0xDB;
+const ESC_END: u8 = 0xDC;
+const ESC_ESC: u8 = 0xDD;
+
+const ETHERNET_HEADER_LEN: usize = 14;
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_ARP: u16 = 0x0806;
+
+/// An Ethernet header and an ARP packet for IPv4.
+const ARP_FRAME_LEN: usize = ETHERNET_HEADER_LEN + 28;
+const ARP_REQUEST: u16 = 1;
+const ARP_REPLY: u16 = 2;
+
+/// Collects bytes from the serial line into packets.
+pub struct Decoder {
+    buffer: [u8; MTU],
+    len: usize,
+    escape: bool,
+    /// Set when the packet was too long, so we throw it away.
+    overflow: bool,
+}
+
+impl Decoder {
+    pub fn new() -> Decoder {
+        Decoder {
+            buffer: [0u8; MTU],
+            len: 0,
+            escape: false,
+            overflow: false,
+        }
+    }
+
+    /// Feed in the next byte. Returns the packet when one is complete.
+    pub fn input(&mut self, byte: u8) -> Option<&[u8]> {
+        let byte = match (self.escape, byte) {
+            (_, END) => {
+                let len = self.len;
+                let overflow = self.overflow;
+                self.len = 0;
+                self.escape = false;
+                self.overflow = false;
+                // Senders put an END before each packet as well as after,
+                // so empty packets are normal
+                return if len > 0 && !overflow {
+                    Some(&self.buffer[0..len])
+                } else {
+                    None
+                };
+            }
+            (false, ESC) => {
+                self.escape = true;
+                return None;
+            }
+            (true, ESC_END) => END,
+            (true, ESC_ESC) => ESC,
+            (_, b) => b,
+        };
+        self.escape = false;
+        if self.len == MTU {
+            self.overflow = true;
+        } else {
+            self.buffer[self.len] = byte;
+            self.len += 1;
+        }
+        None
+    }
+}
+
+/// Send one packet, with an END either side.
+pub fn encode<TX>(tx: &mut TX, packet: &[u8]) -> Result<(), TX::Error>
+where
+    TX: serial::Write<u8>,
+{
+    block!(tx.write(END))?;
+    for &byte in packet {
+        match byte {
+            END => {
+                block!(tx.write(ESC))?;
+                block!(tx.write(ESC_END))?;
+            }
+            ESC => {
+                block!(tx.write(ESC))?;
+                block!(tx.write(ESC_ESC))?;
+            }
+            _ => block!(tx.write(byte))?,
+        }
+    }
+    block!(tx.write(END))
+}
+
+/// A SLIP link that smoltcp thinks is an Ethernet card.
+pub struct Slip<TX, RX> {
+    tx: TX,
+    rx: RX,
+    decoder: Decoder,
+    /// An answer to an ARP request, waiting to be 'received'.
+    arp_reply: Option<[u8; ARP_FRAME_LEN]>,
+}
+
+impl<TX, RX> Slip<TX, RX>
+where
+    TX: serial::Write<u8>,
+    RX: serial::Read<u8>,
+{
+    pub fn new(tx: TX, rx: RX) -> Slip<TX, RX> {
+        Slip {
+            tx,
+            rx,
+            decoder: Decoder::new(),
+            arp_reply: None,
+        }
+    }
+
+    /// Give back the UART.
+    pub fn free(self) -> (TX, RX) {
+        (self.tx, self.rx)
+    }
+
+    /// Deal with a frame smoltcp wants to send.
+    fn send_frame(&mut self, frame: &[u8]) {
+        if frame.len() < ETHERNET_HEADER_LEN {
+            return;
+        }
+        let ethertype = (u16::from(frame[12]) << 8) | u16::from(frame[13]);
+        let payload = &frame[ETHERNET_HEADER_LEN..];
+        match ethertype {
+            ETHERTYPE_IPV4 => {
+                // Nothing sensible to do if the UART fails
+                let _ = encode(&mut self.tx, payload);
+            }
+            ETHERTYPE_ARP => self.arp_reply = arp_reply(payload),
+            _ => {}
+        }
+    }
+}
+
+/// Build the frame the host would send in answer to an ARP request, if
+/// this is one.
+fn arp_reply(request: &[u8]) -> Option<[u8; ARP_FRAME_LEN]> {
+    if request.len() < 28 {
+        return None;
+    }
+    let operation = (u16::from(request[6]) << 8) | u16::from(request[7]);
+    if operation != ARP_REQUEST {
+        return None;
+    }
+    let mut frame = [0u8; ARP_FRAME_LEN];
+    frame[0..6].copy_from_slice(&LOCAL_MAC);
+    frame[6..12].copy_from_slice(&REMOTE_MAC);
+    frame[12] = (ETHERTYPE_ARP >> 8) as u8;
+    frame[13] = ETHERTYPE_ARP as u8;
+    let reply = &mut frame[ETHERNET_HEADER_LEN..];
+    // Same hardware and protocol types and lengths as the request
+    reply[0..6].copy_from_slice(&request[0..6]);
+    reply[6] = (ARP_REPLY >> 8) as u8;
+    reply[7] = ARP_REPLY as u8;
+    // The host 'has' whatever address was asked for
+    reply[8..14].copy_from_slice(&REMOTE_MAC);
+    reply[14..18].copy_from_slice(&request[24..28]);
+    // Sent back to whoever asked
+    reply[18..24].copy_from_slice(&request[8..14]);
+    reply[24..28].copy_from_slice(&request[14..18]);
+    Some(frame)
+}
+
+/// A packet we've received, with a pretend Ethernet header on the front.
+pub struct RxToken {
+    buffer: [u8; ETHERNET_HEADER_LEN + MTU],
+    len: usize,
+}
+
+/// Permission to send one packet.
+pub struct TxToken<'a, TX: 'a, RX: 'a> {
+    slip: &'a mut Slip<TX, RX>,
+}
+
+impl<'a, TX, RX> phy::Device<'a> for Slip<TX, RX>
+where
+    TX: serial::Write<u8> + 'a,
+    RX: serial::Read<u8> + 'a,
+{
+    type RxToken = RxToken;
+    type TxToken = TxToken<'a, TX, RX>;
+
+    fn receive(&'a mut self) -> Option<(RxToken, TxToken<'a, TX, RX>)> {
+        let mut token = RxToken {
+            buffer: [0u8; ETHERNET_HEADER_LEN + MTU],
+            len: 0,
+        };
+        if let Some(frame) = self.arp_reply.take() {
+            token.buffer[0..ARP_FRAME_LEN].copy_from_slice(&frame);
+            token.len = ARP_FRAME_LEN;
+            return Some((token, TxToken { slip: self }));
+        }
+        loop {
+            let byte = match self.rx.read() {
+                Ok(byte) => byte,
+                Err(::nb::Error::WouldBlock) => return None,
+                // Most likely an overrun, which the IP checksum will catch
+                Err(::nb::Error::Other(_)) => continue,
+            };
+            if let Some(packet) = self.decoder.input(byte) {
+                // IPv4 only
+                if packet[0] >> 4 != 4 {
+                    continue;
+                }
+                token.buffer[0..6].copy_from_slice(&LOCAL_MAC);
+                token.buffer[6..12].copy_from_slice(&REMOTE_MAC);
+                token.buffer[12] = (ETHERTYPE_IPV4 >> 8) as u8;
+                token.buffer[13] = ETHERTYPE_IPV4 as u8;
+                token.len = ETHERNET_HEADER_LEN + packet.len();
+                token.buffer[ETHERNET_HEADER_LEN..token.len].copy_from_slice(packet);
+                break;
+            }
+        }
+        Some((token, TxToken { slip: self }))
+    }
+
+    fn transmit(&'a mut self) -> Option<TxToken<'a, TX, RX>> {
+        Some(TxToken { slip: self })
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.max_transmission_unit = ETHERNET_HEADER_LEN + MTU;
+        caps.max_burst_size = Some(1);
+        caps
+    }
+}
+
+impl phy::RxToken for RxToken {
+    fn consume<R, F>(self, _timestamp: Instant, f: F) -> smoltcp::Result<R>
+    where
+        F: FnOnce(&[u8]) -> smoltcp::Result<R>,
+    {
+        f(&self.buffer[0..self.len])
+    }
+}
+
+impl<'a, TX, RX> phy::TxToken for TxToken<'a, TX, RX>
+where
+    TX: serial::Write<u8>,
+    RX: serial::Read<u8>,
+{
+    fn consume<R, F>(self, _timestamp: Instant, len: usize, f: F) -> smoltcp::Result<R>
+    where
+        F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
+    {
+        if len > ETHERNET_HEADER_LEN + MTU {
+            return Err(smoltcp::Error::Truncated);
+        }
+        let mut buffer = [0u8; ETHERNET_HEADER_LEN + MTU];
+        let result = f(&mut buffer[0..len])?;
+        self.slip.send_frame(&buffer[0..len]);
+        Ok(result)
+    }
+}
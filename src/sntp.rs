@@ -0,0 +1,79 @@
+//! Simple Network Time Protocol (RFC 4330) clients.
+//!
+//! We send a 48 byte UDP packet to port 123 of a time server, and the
+//! reply has the time the server sent it, in seconds and 1/2^32 fractions
+//! of a second since 1900. That's all we need to within the round trip
+//! time, which is a few tens of milliseconds on most networks. The transport
+//! is up to you.
+
+/// The port time servers listen on.
+pub const PORT: u16 = 123;
+
+/// Requests and replies are this long (ignoring the optional extras).
+pub const PACKET_LEN: usize = 48;
+
+/// NTP counts from 1900-01-01, Unix from 1970-01-01.
+const UNIX_OFFSET: u32 = 2_208_988_800;
+
+const VERSION_4: u8 = 4 << 3;
+const MODE_CLIENT: u8 = 3;
+const MODE_SERVER: u8 = 4;
+const MODE_MASK: u8 = 0x07;
+/// A leap indicator of 3 means the server's clock isn't set.
+const LI_UNSYNCHRONISED: u8 = 3 << 6;
+
+/// Where the transmit timestamp is.
+const TRANSMIT_TIMESTAMP: usize = 40;
+
+/// What the server told us.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reply {
+    /// Seconds since 1970-01-01 (UTC)
+    pub unix: u32,
+    /// Fractions of a second, in 1/2^32ths
+    pub fraction: u32,
+    /// How far the server is from a reference clock (1 = attached to one)
+    pub stratum: u8,
+}
+
+impl Reply {
+    /// The fraction in milliseconds.
+    pub fn millis(&self) -> u32 {
+        ((u64::from(self.fraction) * 1000) >> 32) as u32
+    }
+}
+
+/// Build a request.
+pub fn request() -> [u8; PACKET_LEN] {
+    let mut packet = [0u8; PACKET_LEN];
+    packet[0] = VERSION_4 | MODE_CLIENT;
+    packet
+}
+
+/// Check a reply from the server and pull out the time. Servers that
+/// aren't synchronised, and 'kiss of death' replies (stratum 0, which
+/// means go away), give `None`.
+pub fn parse(packet: &[u8]) -> Option<Reply> {
+    if packet.len() < PACKET_LEN {
+        return None;
+    }
+    if packet[0] & MODE_MASK != MODE_SERVER || packet[0] & LI_UNSYNCHRONISED == LI_UNSYNCHRONISED
+    {
+        return None;
+    }
+    let stratum = packet[1];
+    if stratum == 0 || stratum > 15 {
+        return None;
+    }
+    let seconds = be32(&packet[TRANSMIT_TIMESTAMP..]);
+    Some(Reply {
+        unix: seconds.wrapping_sub(UNIX_OFFSET),
+        fraction: be32(&packet[TRANSMIT_TIMESTAMP + 4..]),
+        stratum,
+    })
+}
+
+fn be32(bytes: &[u8]) -> u32 {
+    (u32::from(bytes[0]) << 24) | (u32::from(bytes[1]) << 16) | (u32::from(bytes[2]) << 8)
+        | u32::from(bytes[3])
+}
@@ -0,0 +1,194 @@
+//! Blocking SPI master using the Synchronous Serial Interface (SSI)
+//! peripherals.
+//!
+//! The caller is responsible for powering up the SSI and putting the pins
+//! into the right alternate function. Chip select is left to the driver
+//! using the bus, as most of them need to hold it across several bytes.
+
+use embedded_hal::blocking;
+use embedded_hal::spi::{FullDuplex, Mode, Phase, Polarity};
+use hal::sysctl::Clocks;
+use hal::time::Hertz;
+use pac::{SSI0, SSI1, SSI2, SSI3};
+
+/// Something went wrong on the SPI bus.
+#[derive(Debug)]
+pub enum Error {
+    /// The receive FIFO overflowed
+    Overrun,
+}
+
+/// An SSI in SPI master mode with 8-bit frames.
+pub struct Spi<SSI> {
+    ssi: SSI,
+}
+
+/// The extra bits a driver needs to push data out with uDMA.
+pub trait SpiDma {
+    /// The uDMA channel and encoding for this SSI's TX requests.
+    fn tx_dma_channel(&self) -> (u8, u8);
+
+    /// Turn TX DMA requests on or off.
+    fn set_tx_dma(&mut self, enabled: bool);
+
+    /// Switch between 8-bit and 16-bit frames.
+    fn set_16bit(&mut self, enabled: bool);
+
+    /// Address of the data register, for use as a DMA destination.
+    fn data_register(&self) -> *mut u8;
+
+    /// Wait for the SSI to go idle, then throw away anything in the RX FIFO
+    /// and clear any overrun. Call this after a TX-only DMA transfer.
+    fn wait_idle(&mut self);
+}
+
+macro_rules! hal {
+    ($($SSI:ident: ($ssiX:ident, $channel:expr, $encoding:expr),)+) => {
+        $(
+            impl Spi<$SSI> {
+                /// Configure the SSI as an SPI master at (approximately)
+                /// the given frequency.
+                pub fn $ssiX(ssi: $SSI, mode: Mode, freq: Hertz, clocks: &Clocks) -> Self {
+                    ssi.cr1.modify(|_, w| w.sse().clear_bit());
+                    let (cpsdvsr, scr) = dividers(freq, clocks);
+                    ssi.cpsr.write(|w| unsafe { w.cpsdvsr().bits(cpsdvsr as u8) });
+                    ssi.cr0.write(|w| {
+                        w.dss()._8();
+                        w.frf().moto();
+                        match mode.polarity {
+                            Polarity::IdleLow => w.spo().clear_bit(),
+                            Polarity::IdleHigh => w.spo().set_bit(),
+                        };
+                        match mode.phase {
+                            Phase::CaptureOnFirstTransition => w.sph().clear_bit(),
+                            Phase::CaptureOnSecondTransition => w.sph().set_bit(),
+                        };
+                        unsafe { w.scr().bits(scr as u8) };
+                        w
+                    });
+                    ssi.cc.modify(|_, w| w.cs().syspll());
+                    ssi.cr1.modify(|_, w| w.sse().set_bit());
+                    Spi { ssi }
+                }
+
+                /// Change the clock, keeping the mode. Some devices (SD
+                /// cards, for one) have to start slowly.
+                pub fn set_frequency(&mut self, freq: Hertz, clocks: &Clocks) {
+                    let (cpsdvsr, scr) = dividers(freq, clocks);
+                    self.ssi.cr1.modify(|_, w| w.sse().clear_bit());
+                    self.ssi
+                        .cpsr
+                        .write(|w| unsafe { w.cpsdvsr().bits(cpsdvsr as u8) });
+                    self.ssi.cr0.modify(|_, w| unsafe { w.scr().bits(scr as u8) });
+                    self.ssi.cr1.modify(|_, w| w.sse().set_bit());
+                }
+
+                /// Give the SSI back.
+                pub fn free(self) -> $SSI {
+                    self.ssi
+                }
+            }
+
+            impl SpiDma for Spi<$SSI> {
+                fn tx_dma_channel(&self) -> (u8, u8) {
+                    ($channel, $encoding)
+                }
+
+                fn set_tx_dma(&mut self, enabled: bool) {
+                    self.ssi.dmactl.modify(|_, w| w.txdmae().bit(enabled));
+                }
+
+                fn set_16bit(&mut self, enabled: bool) {
+                    self.ssi.cr0.modify(|_, w| {
+                        if enabled {
+                            w.dss()._16()
+                        } else {
+                            w.dss()._8()
+                        }
+                    });
+                }
+
+                fn data_register(&self) -> *mut u8 {
+                    &self.ssi.dr as *const _ as *mut u8
+                }
+
+                fn wait_idle(&mut self) {
+                    while self.ssi.sr.read().bsy().bit_is_set() {}
+                    while self.ssi.sr.read().rne().bit_is_set() {
+                        let _ = self.ssi.dr.read();
+                    }
+                    self.ssi.icr.write(|w| w.roric().set_bit());
+                }
+            }
+
+            impl FullDuplex<u8> for Spi<$SSI> {
+                type Error = Error;
+
+                fn read(&mut self) -> ::nb::Result<u8, Error> {
+                    if self.ssi.ris.read().rorris().bit_is_set() {
+                        self.ssi.icr.write(|w| w.roric().set_bit());
+                        Err(::nb::Error::Other(Error::Overrun))
+                    } else if self.ssi.sr.read().rne().bit_is_set() {
+                        Ok(self.ssi.dr.read().data().bits() as u8)
+                    } else {
+                        Err(::nb::Error::WouldBlock)
+                    }
+                }
+
+                fn send(&mut self, byte: u8) -> ::nb::Result<(), Error> {
+                    if self.ssi.sr.read().tnf().bit_is_set() {
+                        self.ssi.dr.write(|w| unsafe { w.data().bits(u16::from(byte)) });
+                        Ok(())
+                    } else {
+                        Err(::nb::Error::WouldBlock)
+                    }
+                }
+            }
+
+            impl blocking::spi::Transfer<u8> for Spi<$SSI> {
+                type Error = Error;
+
+                fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Error> {
+                    for word in words.iter_mut() {
+                        block!(self.send(*word))?;
+                        *word = block!(self.read())?;
+                    }
+                    Ok(words)
+                }
+            }
+
+            impl blocking::spi::Write<u8> for Spi<$SSI> {
+                type Error = Error;
+
+                fn write(&mut self, words: &[u8]) -> Result<(), Error> {
+                    for word in words {
+                        block!(self.send(*word))?;
+                        // Throw away what comes back so the RX FIFO doesn't
+                        // overflow.
+                        block!(self.read())?;
+                    }
+                    Ok(())
+                }
+            }
+        )+
+    }
+}
+
+/// SSIClk = SysClk / (CPSDVSR * (1 + SCR)). CPSDVSR must be even, so find
+/// the smallest one that lets SCR fit in a byte.
+fn dividers(freq: Hertz, clocks: &Clocks) -> (u32, u32) {
+    let ratio = clocks.sysclk.0 / freq.0;
+    let mut cpsdvsr = 2;
+    while ratio / cpsdvsr > 256 {
+        cpsdvsr += 2;
+    }
+    (cpsdvsr, (ratio / cpsdvsr).max(1) - 1)
+}
+
+// See table 9-1 in the datasheet for the uDMA channel assignments
+hal! {
+    SSI0: (ssi0, 11, 0),
+    SSI1: (ssi1, 25, 0),
+    SSI2: (ssi2, 13, 2),
+    SSI3: (ssi3, 15, 2),
+}
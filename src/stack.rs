@@ -0,0 +1,89 @@
+//! Measuring how much stack we've used.
+//!
+//! The stack runs from the top of RAM down towards the statics (and the
+//! heap, if there is one). Nothing stops it going too far, and when it does
+//! it quietly scribbles over whatever's below - usually the frame buffer.
+//! To see how close we've come, `paint` fills the unused stack with a
+//! pattern as early as possible in `main`:
+//!
+//! ``` ignore
+//! fn main() -> ! {
+//!     demo::stack::paint();
+//!     ...
+//! }
+//! ```
+//!
+//! Anything that uses the stack later (interrupt handlers included) wipes
+//! the pattern out, so `used` can find the deepest the stack has ever been
+//! by looking for where the pattern stops. That's the worst case seen so
+//! far, not the worst case possible - exercise everything before you
+//! believe it.
+//!
+//! `used` reads the whole painted area, so it's not something to call from
+//! an interrupt handler.
+
+use core::ptr;
+use cortex_m::register::msp;
+use cortex_m_rt;
+
+/// What unused stack looks like.
+const PAINT: u32 = 0xCAFE_F00D;
+
+/// How far below the current stack pointer to leave alone when painting,
+/// for `paint`'s own frame and anything an interrupt pushes meanwhile.
+const MARGIN: usize = 256;
+
+/// Where `paint` stopped. Zero until it's called.
+static mut FLOOR: usize = 0;
+
+extern "C" {
+    /// The top of the stack, from the `cortex-m-rt` linker script.
+    static _stack_start: u32;
+}
+
+/// The lowest address the stack can reach without hitting something: the
+/// end of the statics, or whatever `paint_above` was given.
+fn bottom() -> usize {
+    match unsafe { FLOOR } {
+        0 => (cortex_m_rt::heap_start() as usize + 3) & !3,
+        floor => floor,
+    }
+}
+
+/// The address the stack starts from.
+fn top() -> usize {
+    unsafe { &_stack_start as *const u32 as usize }
+}
+
+/// Paint the unused stack, down to the end of the statics.
+pub fn paint() {
+    paint_above(bottom());
+}
+
+/// Paint the unused stack down to `limit`, for when there's a heap between
+/// the statics and the stack.
+pub fn paint_above(limit: usize) {
+    let limit = (limit + 3) & !3;
+    unsafe { FLOOR = limit };
+    let sp = (msp::read() as usize - MARGIN) & !3;
+    let mut addr = limit;
+    while addr < sp {
+        unsafe { ptr::write_volatile(addr as *mut u32, PAINT) };
+        addr += 4;
+    }
+}
+
+/// How many bytes of stack there are.
+pub fn size() -> usize {
+    top() - bottom()
+}
+
+/// The most stack ever used, in bytes, since `paint`. If it's `size()`, the
+/// stack has overflowed (or `paint` was never called).
+pub fn used() -> usize {
+    let mut addr = bottom();
+    while addr < top() && unsafe { ptr::read_volatile(addr as *const u32) } == PAINT {
+        addr += 4;
+    }
+    top() - addr
+}
@@ -0,0 +1,52 @@
+//! A one line status bar across the top of the VGA screen.
+//!
+//! It's drawn in inverse video with the graphics primitives, so it sits on
+//! top of whatever the text console is doing. Anything the text console
+//! writes on its top row will get overwritten the next time the bar is
+//! drawn, so demos that use it should start their text on the second row.
+
+use font;
+use graphics::{Canvas, Colour};
+
+/// Height of the bar in pixels.
+pub const HEIGHT: usize = font::HEIGHT;
+
+/// Draw the bar, with `left` at the left hand end and `right` at the right
+/// hand end. Text that doesn't fit is cut off.
+pub fn draw<C>(canvas: &mut C, left: &str, right: &str)
+where
+    C: Canvas,
+{
+    let width = canvas.width();
+    canvas.fill_rect(0, 0, width, HEIGHT, Colour::WHITE);
+    let cols = width / font::WIDTH;
+    let right = tail(right, cols);
+    let left = head(left, cols - right.len());
+    canvas.draw_str(0, 0, left, Colour::BLACK, Colour::WHITE);
+    canvas.draw_str(
+        (cols - right.len()) * font::WIDTH,
+        0,
+        right,
+        Colour::BLACK,
+        Colour::WHITE,
+    );
+}
+
+/// The start of `s`, no more than `len` bytes long. We draw a glyph per
+/// byte, but mustn't cut a UTF-8 character in half, so it may be shorter.
+fn head(s: &str, len: usize) -> &str {
+    let mut end = len.min(s.len());
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[0..end]
+}
+
+/// The end of `s`, no more than `len` bytes long.
+fn tail(s: &str, len: usize) -> &str {
+    let mut start = s.len() - len.min(s.len());
+    while !s.is_char_boundary(start) {
+        start += 1;
+    }
+    &s[start..]
+}
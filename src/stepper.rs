@@ -0,0 +1,143 @@
+//! Step generation for a stepper motor, with trapezoidal speed ramps.
+//!
+//! Call `tick()` at a fixed rate (e.g. from a timer interrupt). It works out
+//! whether it's time for the next step, speeding up towards the maximum
+//! speed and slowing down in time to stop exactly on the target.
+//!
+//! Speeds are in steps per second, accelerations in steps per second per
+//! second. Internally speed is held in milli-steps per second so slow ramps
+//! still make progress on every tick.
+
+/// Coil patterns for half-stepping a 4-wire (unipolar, or bipolar through an
+/// H-bridge) motor. Bit 0 is IN1 / coil A+.
+pub const HALF_STEP: [u8; 8] = [
+    0b0001, 0b0011, 0b0010, 0b0110, 0b0100, 0b1100, 0b1000, 0b1001,
+];
+
+/// The slowest we ever go while moving, so we always reach the target.
+const MIN_SPEED_MSPS: u32 = 20_000;
+
+/// The fastest acceleration we take, in steps/s/s. Far more than any motor
+/// can manage, and small enough to work out in milli-steps without
+/// overflowing.
+pub const MAX_ACCEL: u32 = 1_000_000;
+
+pub struct Stepper {
+    tick_hz: u32,
+    max_speed: u32,
+    accel: u32,
+    position: i32,
+    target: i32,
+    /// +1 or -1 - the way we're currently moving.
+    direction: i32,
+    /// Current speed in milli-steps per second.
+    speed: u32,
+    /// Accumulates speed every tick; we step when it overflows.
+    phase: u32,
+}
+
+impl Stepper {
+    /// Create a stepper at position zero, ticked `tick_hz` times a second.
+    /// The speed and acceleration are limited as by `set_max_speed` and
+    /// `set_accel`.
+    pub fn new(tick_hz: u32, max_speed: u32, accel: u32) -> Stepper {
+        let mut stepper = Stepper {
+            tick_hz,
+            max_speed: 0,
+            accel: 1,
+            position: 0,
+            target: 0,
+            direction: 1,
+            speed: 0,
+            phase: 0,
+        };
+        stepper.set_max_speed(max_speed);
+        stepper.set_accel(accel);
+        stepper
+    }
+
+    /// Set the top speed in steps per second. It can't usefully be more than
+    /// the tick rate.
+    pub fn set_max_speed(&mut self, max_speed: u32) {
+        self.max_speed = max_speed.min(self.tick_hz).max(MIN_SPEED_MSPS / 1000);
+    }
+
+    /// Set the acceleration (and deceleration) in steps/s/s, from 1 up to
+    /// `MAX_ACCEL`.
+    pub fn set_accel(&mut self, accel: u32) {
+        self.accel = accel.max(1).min(MAX_ACCEL);
+    }
+
+    /// Move to an absolute position.
+    pub fn move_to(&mut self, target: i32) {
+        self.target = target;
+    }
+
+    /// Move relative to the current target.
+    pub fn move_by(&mut self, delta: i32) {
+        self.target = self.target.wrapping_add(delta);
+    }
+
+    pub fn position(&self) -> i32 {
+        self.position
+    }
+
+    pub fn target(&self) -> i32 {
+        self.target
+    }
+
+    /// Current speed in steps per second.
+    pub fn speed(&self) -> u32 {
+        self.speed / 1000
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.speed != 0 || self.position != self.target
+    }
+
+    /// The coil pattern for the current position.
+    pub fn coils(&self) -> u8 {
+        HALF_STEP[(self.position & 7) as usize]
+    }
+
+    /// Advance time by one tick. Returns `Some(direction)` if we took a
+    /// step, in which case the coils need updating.
+    pub fn tick(&mut self) -> Option<i32> {
+        let remaining = self.target - self.position;
+        if remaining == 0 && self.speed <= MIN_SPEED_MSPS {
+            self.speed = 0;
+            self.phase = 0;
+            return None;
+        }
+
+        // How far it takes to stop from here: v^2 / 2a
+        let v = u64::from(self.speed / 1000);
+        let stopping = (v * v) / (2 * u64::from(self.accel));
+        let wrong_way = remaining.signum() != self.direction;
+        let dv = ((self.accel * 1000) / self.tick_hz).max(1);
+
+        if wrong_way || (remaining.abs() as u64) <= stopping {
+            self.speed = self.speed.saturating_sub(dv).max(MIN_SPEED_MSPS);
+            if wrong_way && self.speed == MIN_SPEED_MSPS {
+                // Slow enough to turn around
+                self.direction = -self.direction;
+            }
+        } else if self.speed < self.max_speed * 1000 {
+            self.speed = (self.speed + dv).min(self.max_speed * 1000).max(MIN_SPEED_MSPS);
+        }
+
+        if remaining == 0 {
+            // Arrived, but still slowing down
+            return None;
+        }
+
+        self.phase += self.speed;
+        if self.phase >= self.tick_hz * 1000 {
+            self.phase -= self.tick_hz * 1000;
+            self.position += self.direction;
+            Some(self.direction)
+        } else {
+            None
+        }
+    }
+}
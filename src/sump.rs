@@ -0,0 +1,161 @@
+//! The SUMP protocol, as spoken by logic analysers like the Open Bench
+//! Logic Sniffer, and understood by sigrok (PulseView) and OLS.
+//!
+//! The host sends one byte commands, or five byte ones (the command, then
+//! a little-endian 32-bit argument) if the top bit of the command is set.
+//! `Parser` turns those bytes into `Command`s. After a `Run`, the device
+//! sends back its samples - one byte each, for eight channels - newest
+//! first. `Metadata` answers the host's 'what are you?' question.
+//!
+//! Only the parts a simple analyser needs are here: one trigger stage, in
+//! parallel mode, and one group of eight channels.
+
+/// What we say to `Command::Id`.
+pub const ID: &[u8; 4] = b"1ALS";
+
+/// The SUMP clock. Dividers are relative to this, whatever the real
+/// hardware runs at.
+pub const CLOCK_HZ: u32 = 100_000_000;
+
+const CMD_RESET: u8 = 0x00;
+const CMD_RUN: u8 = 0x01;
+const CMD_ID: u8 = 0x02;
+const CMD_METADATA: u8 = 0x04;
+const CMD_DIVIDER: u8 = 0x80;
+const CMD_COUNTS: u8 = 0x81;
+const CMD_FLAGS: u8 = 0x82;
+/// The trigger commands are 0xC0 + (4 * stage) + what.
+const CMD_TRIGGER: u8 = 0xC0;
+
+/// Metadata keys.
+const META_END: u8 = 0x00;
+const META_NAME: u8 = 0x01;
+const META_PROBES: u8 = 0x20;
+const META_SAMPLE_MEMORY: u8 = 0x21;
+const META_MAX_SAMPLE_RATE: u8 = 0x23;
+const META_PROTOCOL_VERSION: u8 = 0x24;
+
+/// Something the host asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// Stop whatever we're doing. Sent five times in a row, in case we
+    /// were half way through a long command.
+    Reset,
+    /// Arm the trigger, capture, and send the samples back
+    Run,
+    /// Send `ID`
+    Id,
+    /// Send our `Metadata`
+    Metadata,
+    /// Only sample when `(sample & mask) == value`, for one trigger stage
+    TriggerMask { stage: u8, mask: u32 },
+    /// See `TriggerMask`
+    TriggerValue { stage: u8, value: u32 },
+    /// Delay, level, channel and start bits for one trigger stage
+    TriggerConfig { stage: u8, config: u32 },
+    /// Sample at `CLOCK_HZ / (divider + 1)`
+    Divider(u32),
+    /// Send `read` samples, `delay` of which come after the trigger
+    Counts { read: usize, delay: usize },
+    /// Channel groups, filters, RLE and so on
+    Flags(u32),
+    /// Something we don't know (or care) about, including XON and XOFF
+    Other(u8),
+}
+
+/// Splits the bytes from the host into commands.
+pub struct Parser {
+    buffer: [u8; 5],
+    len: usize,
+}
+
+/// Everything we tell the host about ourselves.
+pub struct Metadata {
+    pub name: &'static str,
+    pub probes: u32,
+    /// How many samples we can take
+    pub sample_memory: u32,
+    pub max_sample_rate: u32,
+}
+
+impl Parser {
+    pub fn new() -> Parser {
+        Parser {
+            buffer: [0; 5],
+            len: 0,
+        }
+    }
+
+    /// Handle a byte from the host. Returns a command, if it's finished
+    /// one.
+    pub fn input(&mut self, byte: u8) -> Option<Command> {
+        if self.len == 0 && byte & 0x80 == 0 {
+            return Some(match byte {
+                CMD_RESET => Command::Reset,
+                CMD_RUN => Command::Run,
+                CMD_ID => Command::Id,
+                CMD_METADATA => Command::Metadata,
+                _ => Command::Other(byte),
+            });
+        }
+        self.buffer[self.len] = byte;
+        self.len += 1;
+        if self.len < self.buffer.len() {
+            return None;
+        }
+        self.len = 0;
+        let arg = u32::from(self.buffer[1]) | (u32::from(self.buffer[2]) << 8)
+            | (u32::from(self.buffer[3]) << 16) | (u32::from(self.buffer[4]) << 24);
+        let command = self.buffer[0];
+        Some(match command {
+            CMD_DIVIDER => Command::Divider(arg & 0x00FF_FFFF),
+            CMD_COUNTS => Command::Counts {
+                read: ((arg & 0xFFFF) as usize + 1) * 4,
+                delay: ((arg >> 16) as usize + 1) * 4,
+            },
+            CMD_FLAGS => Command::Flags(arg),
+            0xC0...0xCF => {
+                let stage = (command - CMD_TRIGGER) / 4;
+                match command & 0x03 {
+                    0 => Command::TriggerMask { stage, mask: arg },
+                    1 => Command::TriggerValue { stage, value: arg },
+                    _ => Command::TriggerConfig { stage, config: arg },
+                }
+            }
+            _ => Command::Other(command),
+        })
+    }
+}
+
+impl Metadata {
+    /// Send the metadata, a byte at a time.
+    pub fn send<F>(&self, mut send: F)
+    where
+        F: FnMut(u8),
+    {
+        send(META_NAME);
+        for &byte in self.name.as_bytes() {
+            send(byte);
+        }
+        send(0);
+        let numbers = [
+            (META_PROBES, self.probes),
+            (META_SAMPLE_MEMORY, self.sample_memory),
+            (META_MAX_SAMPLE_RATE, self.max_sample_rate),
+            (META_PROTOCOL_VERSION, 2),
+        ];
+        for &(key, value) in numbers.iter() {
+            send(key);
+            // Big-endian, unlike the commands
+            for shift in [24, 16, 8, 0].iter() {
+                send((value >> shift) as u8);
+            }
+        }
+        send(META_END);
+    }
+}
+
+/// The sample rate a divider asks for.
+pub fn sample_rate(divider: u32) -> u32 {
+    CLOCK_HZ / (divider + 1)
+}
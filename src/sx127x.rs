@@ -0,0 +1,272 @@
+//! Drives a Semtech SX1276/77/78/79 (e.g. HopeRF RFM95W) in LoRa mode over
+//! SPI.
+//!
+//! We use explicit header mode with a CRC, 125 kHz bandwidth and 4/5 coding
+//! rate, with a configurable spreading factor. Higher spreading factors go
+//! further but take much longer to send - a 20 byte packet is about 60ms at
+//! SF7 and over a second at SF12.
+//!
+//! Transmit power comes out of the PA_BOOST pin, which is the one wired to
+//! the antenna on all the common modules. We poll the IRQ flags rather than
+//! using DIO0.
+
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::blocking::spi;
+use embedded_hal::digital::OutputPin;
+
+/// Longest packet we'll handle. The chip can do 255 but it takes forever.
+pub const MAX_PAYLOAD: usize = 64;
+
+/// The slowest and fastest spreading factors we support. SF6 needs implicit
+/// header mode, so we don't do that.
+pub const MIN_SPREADING_FACTOR: u8 = 7;
+pub const MAX_SPREADING_FACTOR: u8 = 12;
+
+/// Frequency step is 32 MHz / 2^19, or 61.03515625 Hz.
+const FSTEP_MILLIHZ: u64 = 61_035;
+
+const REG_FIFO: u8 = 0x00;
+const REG_OPMODE: u8 = 0x01;
+const REG_FRFMSB: u8 = 0x06;
+const REG_PACONFIG: u8 = 0x09;
+const REG_FIFOADDRPTR: u8 = 0x0D;
+const REG_FIFOTXBASEADDR: u8 = 0x0E;
+const REG_FIFORXBASEADDR: u8 = 0x0F;
+const REG_FIFORXCURRENTADDR: u8 = 0x10;
+const REG_IRQFLAGS: u8 = 0x12;
+const REG_RXNBBYTES: u8 = 0x13;
+const REG_PKTSNRVALUE: u8 = 0x19;
+const REG_PKTRSSIVALUE: u8 = 0x1A;
+const REG_MODEMCONFIG1: u8 = 0x1D;
+const REG_MODEMCONFIG2: u8 = 0x1E;
+const REG_PAYLOADLENGTH: u8 = 0x22;
+const REG_MODEMCONFIG3: u8 = 0x26;
+const REG_VERSION: u8 = 0x42;
+
+const OPMODE_LORA: u8 = 0x80;
+const OPMODE_SLEEP: u8 = 0x00;
+const OPMODE_STANDBY: u8 = 0x01;
+const OPMODE_TX: u8 = 0x03;
+const OPMODE_RX_CONTINUOUS: u8 = 0x05;
+
+const IRQ_RX_DONE: u8 = 0x40;
+const IRQ_CRC_ERROR: u8 = 0x20;
+const IRQ_TX_DONE: u8 = 0x08;
+
+/// 125 kHz bandwidth, 4/5 coding rate, explicit header.
+const MODEMCONFIG1_125K_4_5: u8 = 0x72;
+
+/// CRC on received packets.
+const MODEMCONFIG2_RX_CRC: u8 = 0x04;
+
+/// AGC on.
+const MODEMCONFIG3_AGC: u8 = 0x04;
+
+/// Needed when a symbol takes more than 16ms (SF11 and SF12 at 125 kHz).
+const MODEMCONFIG3_LOW_DATA_RATE: u8 = 0x08;
+
+/// PA_BOOST, +17 dBm.
+const PACONFIG_BOOST_17DBM: u8 = 0x8F;
+
+/// Something went wrong talking to the SX127x.
+#[derive(Debug)]
+pub enum Error<E> {
+    /// The SPI bus reported an error
+    Spi(E),
+    /// The version register didn't read back as 0x12, so there's probably
+    /// no module fitted
+    NotFound(u8),
+    /// The payload was too long
+    TooLong,
+    /// A packet arrived but its CRC was wrong
+    BadCrc,
+}
+
+/// A packet we received.
+pub struct Packet {
+    pub len: usize,
+    pub data: [u8; MAX_PAYLOAD],
+    /// Signal strength in dBm
+    pub rssi: i16,
+    /// Signal to noise ratio in quarter dB steps
+    pub snr_quarter_db: i8,
+}
+
+impl Packet {
+    pub fn payload(&self) -> &[u8] {
+        &self.data[0..self.len]
+    }
+}
+
+pub struct Sx127x<SPI, CS> {
+    spi: SPI,
+    cs: CS,
+    frequency_hz: u32,
+    spreading_factor: u8,
+    receiving: bool,
+}
+
+impl<SPI, CS, E> Sx127x<SPI, CS>
+where
+    SPI: spi::Transfer<u8, Error = E>,
+    CS: OutputPin,
+{
+    /// Reset the module and put it into LoRa mode on the given frequency
+    /// at SF7. RST is active low. The bus should be SPI mode 0 at up to
+    /// 10 MHz.
+    pub fn new<RST, D>(
+        spi: SPI,
+        cs: CS,
+        rst: &mut RST,
+        delay: &mut D,
+        frequency_hz: u32,
+    ) -> Result<Self, Error<E>>
+    where
+        RST: OutputPin,
+        D: DelayMs<u32>,
+    {
+        let mut radio = Sx127x {
+            spi,
+            cs,
+            frequency_hz,
+            spreading_factor: MIN_SPREADING_FACTOR,
+            receiving: false,
+        };
+        radio.cs.set_high();
+        rst.set_low();
+        delay.delay_ms(1);
+        rst.set_high();
+        delay.delay_ms(10);
+
+        let version = radio.read_register(REG_VERSION)?;
+        if version != 0x12 {
+            return Err(Error::NotFound(version));
+        }
+
+        // We can only switch to LoRa mode from sleep
+        radio.write_register(REG_OPMODE, OPMODE_SLEEP)?;
+        radio.write_register(REG_OPMODE, OPMODE_LORA | OPMODE_SLEEP)?;
+        let frf = (u64::from(frequency_hz) * 1000) / FSTEP_MILLIHZ;
+        radio.write_registers(
+            REG_FRFMSB,
+            &[(frf >> 16) as u8, (frf >> 8) as u8, frf as u8],
+        )?;
+        // Use the whole FIFO for both directions, as we're half duplex
+        radio.write_register(REG_FIFOTXBASEADDR, 0)?;
+        radio.write_register(REG_FIFORXBASEADDR, 0)?;
+        radio.write_register(REG_PACONFIG, PACONFIG_BOOST_17DBM)?;
+        radio.write_register(REG_MODEMCONFIG1, MODEMCONFIG1_125K_4_5)?;
+        radio.set_spreading_factor(MIN_SPREADING_FACTOR)?;
+        radio.write_register(REG_OPMODE, OPMODE_LORA | OPMODE_STANDBY)?;
+        Ok(radio)
+    }
+
+    pub fn spreading_factor(&self) -> u8 {
+        self.spreading_factor
+    }
+
+    /// Change the spreading factor. Out of range values are clamped. Both
+    /// ends must use the same value.
+    pub fn set_spreading_factor(&mut self, sf: u8) -> Result<(), Error<E>> {
+        let sf = sf.max(MIN_SPREADING_FACTOR).min(MAX_SPREADING_FACTOR);
+        self.write_register(REG_MODEMCONFIG2, (sf << 4) | MODEMCONFIG2_RX_CRC)?;
+        let config3 = if sf >= 11 {
+            MODEMCONFIG3_AGC | MODEMCONFIG3_LOW_DATA_RATE
+        } else {
+            MODEMCONFIG3_AGC
+        };
+        self.write_register(REG_MODEMCONFIG3, config3)?;
+        self.spreading_factor = sf;
+        Ok(())
+    }
+
+    /// Send a packet and wait for it to go. Leaves the radio in standby.
+    pub fn send(&mut self, data: &[u8]) -> Result<(), Error<E>> {
+        if data.len() > MAX_PAYLOAD {
+            return Err(Error::TooLong);
+        }
+        self.write_register(REG_OPMODE, OPMODE_LORA | OPMODE_STANDBY)?;
+        self.receiving = false;
+        self.write_register(REG_FIFOADDRPTR, 0)?;
+        let mut buffer = [0u8; MAX_PAYLOAD + 1];
+        buffer[0] = REG_FIFO | 0x80;
+        buffer[1..data.len() + 1].copy_from_slice(data);
+        self.transfer(&mut buffer[0..data.len() + 1])?;
+        self.write_register(REG_PAYLOADLENGTH, data.len() as u8)?;
+        self.write_register(REG_OPMODE, OPMODE_LORA | OPMODE_TX)?;
+        while self.read_register(REG_IRQFLAGS)? & IRQ_TX_DONE == 0 {}
+        self.write_register(REG_IRQFLAGS, IRQ_TX_DONE)
+    }
+
+    /// Check for a received packet, putting the radio in continuous
+    /// receive mode if it isn't already.
+    pub fn receive(&mut self) -> Result<Option<Packet>, Error<E>> {
+        if !self.receiving {
+            self.write_register(REG_OPMODE, OPMODE_LORA | OPMODE_RX_CONTINUOUS)?;
+            self.receiving = true;
+            return Ok(None);
+        }
+        let flags = self.read_register(REG_IRQFLAGS)?;
+        if flags & IRQ_RX_DONE == 0 {
+            return Ok(None);
+        }
+        self.write_register(REG_IRQFLAGS, IRQ_RX_DONE | IRQ_CRC_ERROR)?;
+        if flags & IRQ_CRC_ERROR != 0 {
+            return Err(Error::BadCrc);
+        }
+
+        let len = (self.read_register(REG_RXNBBYTES)? as usize).min(MAX_PAYLOAD);
+        let start = self.read_register(REG_FIFORXCURRENTADDR)?;
+        self.write_register(REG_FIFOADDRPTR, start)?;
+        let mut buffer = [0u8; MAX_PAYLOAD + 1];
+        buffer[0] = REG_FIFO;
+        self.transfer(&mut buffer[0..len + 1])?;
+
+        let snr = self.read_register(REG_PKTSNRVALUE)? as i8;
+        let raw_rssi = i16::from(self.read_register(REG_PKTRSSIVALUE)?);
+        // The offset is different for the low frequency port
+        let offset = if self.frequency_hz < 525_000_000 {
+            -164
+        } else {
+            -157
+        };
+        let rssi = if snr < 0 {
+            offset + raw_rssi + (i16::from(snr) / 4)
+        } else {
+            offset + raw_rssi
+        };
+
+        let mut packet = Packet {
+            len,
+            data: [0u8; MAX_PAYLOAD],
+            rssi,
+            snr_quarter_db: snr,
+        };
+        packet.data[0..len].copy_from_slice(&buffer[1..len + 1]);
+        Ok(Some(packet))
+    }
+
+    fn read_register(&mut self, reg: u8) -> Result<u8, Error<E>> {
+        let mut buffer = [reg & 0x7F, 0];
+        self.transfer(&mut buffer)?;
+        Ok(buffer[1])
+    }
+
+    fn write_register(&mut self, reg: u8, value: u8) -> Result<(), Error<E>> {
+        self.write_registers(reg, &[value])
+    }
+
+    fn write_registers(&mut self, reg: u8, values: &[u8]) -> Result<(), Error<E>> {
+        let mut buffer = [0u8; 4];
+        buffer[0] = reg | 0x80;
+        buffer[1..values.len() + 1].copy_from_slice(values);
+        self.transfer(&mut buffer[0..values.len() + 1])
+    }
+
+    fn transfer(&mut self, buffer: &mut [u8]) -> Result<(), Error<E>> {
+        self.cs.set_low();
+        let result = self.spi.transfer(buffer).map(|_| ());
+        self.cs.set_high();
+        result.map_err(Error::Spi)
+    }
+}
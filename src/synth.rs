@@ -0,0 +1,301 @@
+//! A three channel synthesiser, mixed in a timer interrupt.
+//!
+//! Each channel is a `wavetable::Oscillator` with a volume and a simple
+//! ADSR envelope. `Synth` mixes them down to one sample at a time; `start`
+//! sets up the hardware to ask for one 37,500 times a second - about once
+//! a VGA line - and play it.
+//!
+//! The output is PWM on PB4 (T1CCP0), with one PWM cycle per sample, so
+//! there are about 2,000 levels and the carrier is well above anything you
+//! can hear. Filter it (1k and 10nF) and feed an amplifier. Timer2A
+//! interrupts at the same rate to work out the next sample. The caller must
+//! power up Timer1 and Timer2, enable the TIMER2A interrupt (at a lower
+//! priority than the VGA timers, so the picture doesn't wobble) and hook up
+//! the handler:
+//!
+//! ``` ignore
+//! interrupt!(TIMER2A, synth::timer2a_isr);
+//! ```
+//!
+//! Then use `with` to play notes. PB4 is also the `audio` beeper's pin, so
+//! it's one or the other.
+//!
+//! Or, for an R-2R ladder, call `init` rather than `start` and give `fill`
+//! to `dac::start`. Then there's no interrupt per sample at all.
+
+use config::{Pin, Port};
+use cortex_m::interrupt;
+use hal::sysctl::Clocks;
+use pac::{self, TIMER1, TIMER2};
+use wavetable::{Oscillator, Waveform};
+
+/// How many notes at once.
+pub const CHANNELS: usize = 3;
+
+/// Samples a second. A 800x600 VGA line is 26.4us, so this is close.
+pub const SAMPLE_RATE_HZ: u32 = 37_500;
+
+/// The top octave of MIDI notes (C9 to B9), in millihertz. Each octave
+/// down halves them.
+const TOP_OCTAVE_MILLIHERTZ: [u32; 12] = [
+    8_372_018, 8_869_844, 9_397_273, 9_956_063, 10_548_082, 11_175_303, 11_839_822,
+    12_543_854, 13_289_750, 14_080_000, 14_917_240, 15_804_266,
+];
+/// The MIDI note at the start of `TOP_OCTAVE_MILLIHERTZ`.
+const TOP_OCTAVE_NOTE: u8 = 120;
+
+/// Where the sound comes out.
+const PIN: Pin = Pin {
+    port: Port::B,
+    bit: 4,
+};
+/// T1CCP0's alternate function.
+const PIN_FUNCTION: u32 = 7;
+
+// GPTMCFG, GPTMTAMR
+const CFG_16_BIT: u32 = 0x4;
+const CFG_32_BIT: u32 = 0x0;
+/// Periodic, PWM, and only change the match value at the end of a cycle
+const TAMR_PWM: u32 = 0x2 | (1 << 3) | (1 << 10);
+
+/// How the volume of a note changes over time. Times are in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Envelope {
+    /// From nothing to full volume
+    pub attack_ms: u32,
+    /// From full volume to `sustain`
+    pub decay_ms: u32,
+    /// The level (0..255) held until the note is released
+    pub sustain: u8,
+    /// From `sustain` to nothing, after the release
+    pub release_ms: u32,
+}
+
+/// Where a channel's envelope is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+    Off,
+}
+
+/// One voice.
+struct Channel {
+    oscillator: Oscillator,
+    envelope: Envelope,
+    stage: Stage,
+    /// The envelope's output, 0..255, with 16 fractional bits
+    level: u32,
+}
+
+/// All the channels, and how to mix them.
+pub struct Synth {
+    channels: [Channel; CHANNELS],
+}
+
+static mut SYNTH: Option<Synth> = None;
+
+/// The envelope for a plain organ-like note: straight on, straight off.
+pub const ORGAN: Envelope = Envelope {
+    attack_ms: 5,
+    decay_ms: 0,
+    sustain: 255,
+    release_ms: 5,
+};
+
+/// The top of the envelope.
+const FULL: u32 = 255 << 16;
+
+/// The frequency of a MIDI note, in millihertz.
+pub fn note_millihertz(note: u8) -> u32 {
+    let note = note.min(TOP_OCTAVE_NOTE + 11);
+    let octaves_down = (TOP_OCTAVE_NOTE + 11 - note) / 12;
+    let index = (note + (octaves_down * 12) - TOP_OCTAVE_NOTE) as usize;
+    TOP_OCTAVE_MILLIHERTZ[index] >> octaves_down
+}
+
+/// How much the envelope moves per sample to go from 0 to `FULL` in `ms`.
+fn rate(ms: u32) -> u32 {
+    let samples = (ms * SAMPLE_RATE_HZ) / 1000;
+    FULL / samples.max(1)
+}
+
+impl Channel {
+    fn new() -> Channel {
+        let mut oscillator = Oscillator::new(Waveform::Square, SAMPLE_RATE_HZ);
+        oscillator.volume = 255;
+        Channel {
+            oscillator,
+            envelope: ORGAN,
+            stage: Stage::Off,
+            level: 0,
+        }
+    }
+
+    /// Move the envelope on a sample.
+    fn step_envelope(&mut self) {
+        let sustain = u32::from(self.envelope.sustain) << 16;
+        match self.stage {
+            Stage::Attack => {
+                self.level = (self.level + rate(self.envelope.attack_ms)).min(FULL);
+                if self.level == FULL {
+                    self.stage = Stage::Decay;
+                }
+            }
+            Stage::Decay => {
+                self.level = self
+                    .level
+                    .saturating_sub(rate(self.envelope.decay_ms))
+                    .max(sustain);
+                if self.level == sustain {
+                    self.stage = Stage::Sustain;
+                }
+            }
+            Stage::Sustain => self.level = sustain,
+            Stage::Release => {
+                self.level = self.level.saturating_sub(rate(self.envelope.release_ms));
+                if self.level == 0 {
+                    self.stage = Stage::Off;
+                }
+            }
+            Stage::Off => {}
+        }
+    }
+
+    fn next_sample(&mut self) -> i32 {
+        if self.stage == Stage::Off {
+            return 0;
+        }
+        self.step_envelope();
+        let sample = i32::from(self.oscillator.next_sample());
+        (sample * (self.level >> 16) as i32) / 255
+    }
+}
+
+impl Synth {
+    /// Three silent square wave channels, with the `ORGAN` envelope.
+    pub fn new() -> Synth {
+        Synth {
+            channels: [Channel::new(), Channel::new(), Channel::new()],
+        }
+    }
+
+    /// Start a note, from the beginning of its envelope.
+    pub fn note_on(&mut self, channel: usize, millihertz: u32) {
+        let c = &mut self.channels[channel];
+        c.oscillator.set_frequency_millihertz(millihertz);
+        c.oscillator.reset();
+        c.stage = Stage::Attack;
+    }
+
+    /// Change the pitch of a note that's playing, without restarting it.
+    pub fn set_pitch(&mut self, channel: usize, millihertz: u32) {
+        self.channels[channel]
+            .oscillator
+            .set_frequency_millihertz(millihertz);
+    }
+
+    /// Let a note go. It fades out over the envelope's release time.
+    pub fn note_off(&mut self, channel: usize) {
+        let c = &mut self.channels[channel];
+        if c.stage != Stage::Off {
+            c.stage = Stage::Release;
+        }
+    }
+
+    /// Is the channel making any noise?
+    pub fn is_playing(&self, channel: usize) -> bool {
+        self.channels[channel].stage != Stage::Off
+    }
+
+    pub fn set_waveform(&mut self, channel: usize, waveform: Waveform) {
+        self.channels[channel].oscillator.waveform = waveform;
+    }
+
+    /// 0 (silent) to 255 (full scale).
+    pub fn set_volume(&mut self, channel: usize, volume: u8) {
+        self.channels[channel].oscillator.volume = volume;
+    }
+
+    pub fn set_envelope(&mut self, channel: usize, envelope: Envelope) {
+        self.channels[channel].envelope = envelope;
+    }
+
+    /// Mix the next sample. All three at full volume is full scale.
+    pub fn next_sample(&mut self) -> i16 {
+        let mut total = 0;
+        for channel in self.channels.iter_mut() {
+            total += channel.next_sample();
+        }
+        (total / CHANNELS as i32) as i16
+    }
+}
+
+/// Set up the synth, without any hardware to play it on.
+pub fn init() {
+    interrupt::free(|_| unsafe { SYNTH = Some(Synth::new()) });
+}
+
+/// Set up the pin and both timers, and start playing silence.
+pub fn start(pwm: TIMER1, timer: TIMER2, clocks: &Clocks) {
+    let period = clocks.sysclk.0 / SAMPLE_RATE_HZ;
+    init();
+
+    // Timer1A as PWM, one cycle per sample. The counter reloads at the
+    // top, taking the output high, and it goes low again at the match
+    // value. The prescaler registers hold bits 23:16.
+    PIN.into_af(PIN_FUNCTION);
+    pwm.ctl.write(|w| unsafe { w.bits(0) });
+    pwm.cfg.write(|w| unsafe { w.bits(CFG_16_BIT) });
+    pwm.tamr.write(|w| unsafe { w.bits(TAMR_PWM) });
+    pwm.tailr.write(|w| unsafe { w.bits((period - 1) & 0xFFFF) });
+    pwm.tapr.write(|w| unsafe { w.bits((period - 1) >> 16) });
+    pwm.tamatchr.write(|w| unsafe { w.bits((period / 2) & 0xFFFF) });
+    pwm.tapmr.write(|w| unsafe { w.bits((period / 2) >> 16) });
+    pwm.ctl.modify(|_, w| w.taen().set_bit());
+
+    // Timer2A, 32-bit periodic, interrupting on timeout
+    timer.ctl.modify(|_, w| w.taen().clear_bit());
+    timer.cfg.write(|w| unsafe { w.bits(CFG_32_BIT) });
+    timer.tamr.modify(|_, w| w.tamr().period());
+    timer.tailr.write(|w| unsafe { w.bits(period - 1) });
+    timer.imr.modify(|_, w| w.tatoim().set_bit());
+    timer.icr.write(|w| w.tatocint().set_bit());
+    timer.ctl.modify(|_, w| w.taen().set_bit());
+}
+
+/// Run a closure against the synth with the sample interrupt masked.
+/// Panics if `start` hasn't been called.
+pub fn with<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut Synth) -> R,
+{
+    interrupt::free(|_| f(unsafe { SYNTH.as_mut().unwrap() }))
+}
+
+/// Mix a buffer's worth of unsigned 8-bit samples. A `dac::Source`.
+pub fn fill(buffer: &mut [u8]) {
+    if let Some(synth) = unsafe { SYNTH.as_mut() } {
+        for sample in buffer.iter_mut() {
+            *sample = ((i32::from(synth.next_sample()) + 32768) >> 8) as u8;
+        }
+    }
+}
+
+/// Play the next sample. The TIMER2A handler.
+pub fn timer2a_isr() {
+    let timer = unsafe { &*pac::TIMER2::ptr() };
+    timer.icr.write(|w| w.tatocint().set_bit());
+    if let Some(synth) = unsafe { SYNTH.as_mut() } {
+        let pwm = unsafe { &*pac::TIMER1::ptr() };
+        let top = pwm.tailr.read().bits() | (pwm.tapr.read().bits() << 16);
+        // -32768..32767 to 0..top. The output is high for top - match
+        // clocks.
+        let level = ((i32::from(synth.next_sample()) + 32768) as u32 * top) >> 16;
+        let matched = top - level;
+        pwm.tamatchr.write(|w| unsafe { w.bits(matched & 0xFFFF) });
+        pwm.tapmr.write(|w| unsafe { w.bits(matched >> 16) });
+    }
+}
@@ -0,0 +1,234 @@
+//! Streams measurements down a serial port, for plotting on the host.
+//!
+//! Each measurement is a channel with a name and an `i32` value. Every
+//! `period_ms` the selected channels go out in one of two formats:
+//!
+//! * `Csv` - a line of comma-separated numbers. A line of channel names
+//!   goes first, and again whenever the selection changes. The Arduino
+//!   IDE's serial plotter takes this as it is, and so will a spreadsheet.
+//! * `Binary` - `0xAA 0x55`, a byte saying how many bytes of values
+//!   follow, the values as little-endian `i32`s, and the bottom eight bits
+//!   of the sum of the value bytes. That's SerialPlot's "Custom Frame"
+//!   format (with "int32", "little endian" and "checksum" ticked), and it
+//!   keeps up at rates the text can't.
+//!
+//! The caller keeps its own names and values, in the same order:
+//!
+//! ``` ignore
+//! let mut telemetry = Telemetry::new(Format::Csv, 100);
+//! loop {
+//!     if telemetry.due(scheduler::now()) {
+//!         let values = [adc.read(0) as i32, adc.read(1) as i32];
+//!         telemetry.send(&mut board.tx, &["ain0", "ain1"], &values).unwrap();
+//!     }
+//! }
+//! ```
+//!
+//! Bytes go out with `serial::Write`, not `fmt::Write`, so `Board`'s
+//! newline swapping doesn't mangle binary frames.
+
+use embedded_hal::serial;
+
+/// The most channels a `Telemetry` can pick from.
+pub const MAX_CHANNELS: usize = 16;
+
+/// The start of a binary frame.
+pub const SYNC: [u8; 2] = [0xAA, 0x55];
+
+/// How the measurements are sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Binary,
+}
+
+impl Format {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Format::Csv => "csv",
+            Format::Binary => "binary",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "csv" => Some(Format::Csv),
+            "binary" => Some(Format::Binary),
+            _ => None,
+        }
+    }
+}
+
+/// Which channels go out, how, and how often.
+pub struct Telemetry {
+    format: Format,
+    period_ms: u32,
+    /// One bit per channel
+    selected: u16,
+    /// When the next lot is due
+    next_ms: u32,
+    /// Send the CSV names before the next values
+    header_due: bool,
+}
+
+impl Telemetry {
+    /// Send every channel in `format`, every `period_ms` milliseconds.
+    pub fn new(format: Format, period_ms: u32) -> Telemetry {
+        Telemetry {
+            format,
+            period_ms,
+            selected: 0xFFFF,
+            next_ms: 0,
+            header_due: true,
+        }
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    pub fn set_format(&mut self, format: Format) {
+        self.format = format;
+        self.header_due = true;
+    }
+
+    pub fn period_ms(&self) -> u32 {
+        self.period_ms
+    }
+
+    pub fn set_period_ms(&mut self, period_ms: u32) {
+        self.period_ms = period_ms;
+    }
+
+    pub fn is_selected(&self, channel: usize) -> bool {
+        channel < MAX_CHANNELS && self.selected & (1 << channel) != 0
+    }
+
+    /// Turn a channel on or off.
+    pub fn select(&mut self, channel: usize, on: bool) {
+        if channel >= MAX_CHANNELS {
+            return;
+        }
+        if on {
+            self.selected |= 1 << channel;
+        } else {
+            self.selected &= !(1 << channel);
+        }
+        self.header_due = true;
+    }
+
+    /// Send the CSV names again before the next values - say, after the
+    /// host has reconnected.
+    pub fn restart(&mut self) {
+        self.header_due = true;
+    }
+
+    /// Is it time to send? Says true once a period, given the time in
+    /// milliseconds. If we've fallen behind, the missed ones are dropped.
+    pub fn due(&mut self, now_ms: u32) -> bool {
+        // Wrapping compare, so this survives the counter rolling over
+        if (now_ms.wrapping_sub(self.next_ms) as i32) < 0 {
+            return false;
+        }
+        self.next_ms = now_ms.wrapping_add(self.period_ms);
+        true
+    }
+
+    /// Send the selected channels. `names` and `values` go together; any
+    /// past `MAX_CHANNELS` are left off.
+    pub fn send<TX>(
+        &mut self,
+        tx: &mut TX,
+        names: &[&str],
+        values: &[i32],
+    ) -> Result<(), TX::Error>
+    where
+        TX: serial::Write<u8>,
+    {
+        match self.format {
+            Format::Csv => {
+                if self.header_due {
+                    self.header_due = false;
+                    let mut first = true;
+                    for (i, name) in names.iter().enumerate() {
+                        if self.is_selected(i) {
+                            if !first {
+                                block!(tx.write(b','))?;
+                            }
+                            first = false;
+                            write_bytes(tx, name.as_bytes())?;
+                        }
+                    }
+                    write_bytes(tx, b"\r\n")?;
+                }
+                let mut first = true;
+                for (i, &value) in values.iter().enumerate() {
+                    if self.is_selected(i) {
+                        if !first {
+                            block!(tx.write(b','))?;
+                        }
+                        first = false;
+                        write_decimal(tx, value)?;
+                    }
+                }
+                write_bytes(tx, b"\r\n")
+            }
+            Format::Binary => {
+                let count = (0..values.len()).filter(|&i| self.is_selected(i)).count();
+                write_bytes(tx, &SYNC)?;
+                block!(tx.write((count * 4) as u8))?;
+                let mut sum = 0u8;
+                for (i, &value) in values.iter().enumerate() {
+                    if self.is_selected(i) {
+                        for shift in 0..4 {
+                            let byte = (value >> (shift * 8)) as u8;
+                            sum = sum.wrapping_add(byte);
+                            block!(tx.write(byte))?;
+                        }
+                    }
+                }
+                block!(tx.write(sum))
+            }
+        }
+    }
+}
+
+fn write_bytes<TX>(tx: &mut TX, bytes: &[u8]) -> Result<(), TX::Error>
+where
+    TX: serial::Write<u8>,
+{
+    for &byte in bytes {
+        block!(tx.write(byte))?;
+    }
+    Ok(())
+}
+
+/// A number in decimal, with a `-` if it needs one.
+fn write_decimal<TX>(tx: &mut TX, value: i32) -> Result<(), TX::Error>
+where
+    TX: serial::Write<u8>,
+{
+    let mut digits = [0u8; 11];
+    let mut len = 0;
+    // i32::MIN has no positive i32, but it does as a u32
+    let mut n = if value < 0 {
+        (value as u32).wrapping_neg()
+    } else {
+        value as u32
+    };
+    loop {
+        digits[len] = b'0' + (n % 10) as u8;
+        len += 1;
+        n /= 10;
+        if n == 0 {
+            break;
+        }
+    }
+    if value < 0 {
+        block!(tx.write(b'-'))?;
+    }
+    for &digit in digits[0..len].iter().rev() {
+        block!(tx.write(digit))?;
+    }
+    Ok(())
+}
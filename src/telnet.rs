@@ -0,0 +1,183 @@
+//! Just enough Telnet to put a menu `Runner` on a TCP connection.
+//!
+//! Send `NEGOTIATION` when someone connects, so their client stops echoing
+//! locally and sends each key as it's pressed, like a serial terminal.
+//! Then push what they send through a `Decoder`, which strips out the
+//! option negotiation and turns their line endings into the plain `\r` the
+//! `Runner` expects, and give the `Runner` an `Output` to write into. Send
+//! whatever is `pending` whenever the socket has room.
+
+use core::fmt;
+
+/// The port Telnet servers listen on.
+pub const PORT: u16 = 23;
+
+/// How much an `Output` buffers before it starts dropping things.
+pub const OUTPUT_LEN: usize = 1024;
+
+// Commands
+const SE: u8 = 240;
+const SB: u8 = 250;
+const WILL: u8 = 251;
+const WONT: u8 = 252;
+const DO: u8 = 253;
+const DONT: u8 = 254;
+const IAC: u8 = 255;
+
+// Options
+const OPTION_ECHO: u8 = 1;
+const OPTION_SUPPRESS_GO_AHEAD: u8 = 3;
+
+/// We'll do the echoing, and we won't send go-aheads. Between them, that
+/// puts most clients into character-at-a-time mode.
+pub const NEGOTIATION: [u8; 6] = [
+    IAC,
+    WILL,
+    OPTION_ECHO,
+    IAC,
+    WILL,
+    OPTION_SUPPRESS_GO_AHEAD,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Data,
+    /// Just had a carriage return, which may be followed by a LF or NUL
+    Return,
+    /// Just had an IAC
+    Command,
+    /// Waiting for the option after a WILL, WONT, DO or DONT
+    Option,
+    /// In a sub-negotiation, which we ignore
+    Sub,
+    /// Had an IAC in a sub-negotiation
+    SubCommand,
+}
+
+/// Pulls the typing out of what a Telnet client sends.
+pub struct Decoder {
+    state: State,
+}
+
+impl Decoder {
+    pub fn new() -> Decoder {
+        Decoder { state: State::Data }
+    }
+
+    /// Feed in a byte from the client. Returns a byte for the `Runner`, if
+    /// this was one.
+    pub fn input(&mut self, byte: u8) -> Option<u8> {
+        match (self.state, byte) {
+            (State::Data, IAC) | (State::Return, IAC) => {
+                self.state = State::Command;
+                None
+            }
+            (State::Data, b'\r') | (State::Return, b'\r') => {
+                self.state = State::Return;
+                Some(b'\r')
+            }
+            (State::Return, b'\n') | (State::Return, 0) => {
+                self.state = State::Data;
+                None
+            }
+            (State::Data, _) | (State::Return, _) => {
+                self.state = State::Data;
+                Some(byte)
+            }
+            // An escaped 0xFF
+            (State::Command, IAC) => {
+                self.state = State::Data;
+                Some(IAC)
+            }
+            (State::Command, WILL) | (State::Command, WONT) | (State::Command, DO)
+            | (State::Command, DONT) => {
+                self.state = State::Option;
+                None
+            }
+            (State::Command, SB) => {
+                self.state = State::Sub;
+                None
+            }
+            // Anything else is a two byte command we don't care about
+            (State::Command, _) | (State::Option, _) => {
+                self.state = State::Data;
+                None
+            }
+            (State::Sub, IAC) => {
+                self.state = State::SubCommand;
+                None
+            }
+            (State::Sub, _) => None,
+            (State::SubCommand, SE) => {
+                self.state = State::Data;
+                None
+            }
+            (State::SubCommand, _) => {
+                self.state = State::Sub;
+                None
+            }
+        }
+    }
+}
+
+/// Collects the `Runner`'s output until the socket can take it. Newlines
+/// become `\r\n` on the way in.
+pub struct Output {
+    data: [u8; OUTPUT_LEN],
+    len: usize,
+}
+
+impl Output {
+    pub fn new() -> Output {
+        Output {
+            data: [0u8; OUTPUT_LEN],
+            len: 0,
+        }
+    }
+
+    /// Add bytes that are already in network form (e.g. `NEGOTIATION`, or
+    /// text captured from the `Console`).
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.push(byte);
+        }
+    }
+
+    /// What's waiting to be sent.
+    pub fn pending(&self) -> &[u8] {
+        &self.data[0..self.len]
+    }
+
+    /// Forget the first `count` bytes, because they've been sent.
+    pub fn consume(&mut self, count: usize) {
+        let count = count.min(self.len);
+        for i in count..self.len {
+            self.data[i - count] = self.data[i];
+        }
+        self.len -= count;
+    }
+
+    /// Forget everything, e.g. when the client goes away.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    fn push(&mut self, byte: u8) {
+        if self.len < OUTPUT_LEN {
+            self.data[self.len] = byte;
+            self.len += 1;
+        }
+    }
+}
+
+impl fmt::Write for Output {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            if byte == b'\n' {
+                self.push(b'\r');
+            }
+            self.push(byte);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+//! Formatting text without a heap.
+//!
+//! `Buffer` collects whatever you `write!` into it, up to a fixed size, so
+//! it can go on the screen or out in a packet. `Buffer::new()` holds one
+//! line of the VGA screen, which is plenty for a label or a reading. For
+//! anything bigger, hand `Buffer::from_storage` an array (or a slice) to
+//! keep it in:
+//!
+//! ``` ignore
+//! let mut page = Buffer::from_storage([0u8; 512]);
+//! write!(page, "Uptime: {} ms", uptime).unwrap();
+//! socket.send_slice(page.as_bytes());
+//! ```
+//!
+//! Writing past the end makes `write!` return an error. Whatever fitted is
+//! kept, and it's always whole characters, so callers that don't mind the
+//! text being cut short can ignore the error.
+
+use core::fmt;
+use core::str;
+
+/// How many bytes `Buffer::new` holds - one line of the 640 pixel wide
+/// VGA screen.
+pub const LINE: usize = 80;
+
+/// Somewhere for a `Buffer` to keep its bytes.
+pub trait Storage {
+    fn bytes(&self) -> &[u8];
+    fn bytes_mut(&mut self) -> &mut [u8];
+}
+
+macro_rules! array_storage {
+    ($($len:expr),*) => {
+        $(
+            impl Storage for [u8; $len] {
+                fn bytes(&self) -> &[u8] {
+                    &self[..]
+                }
+
+                fn bytes_mut(&mut self) -> &mut [u8] {
+                    &mut self[..]
+                }
+            }
+        )*
+    };
+}
+
+array_storage!(16, 32, 48, 64, LINE, 128, 256, 512, 1024);
+
+impl<'a> Storage for &'a mut [u8] {
+    fn bytes(&self) -> &[u8] {
+        self
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+/// Lets us `write!` into a fixed buffer.
+pub struct Buffer<S = [u8; LINE]> {
+    data: S,
+    len: usize,
+}
+
+impl Buffer {
+    /// An empty buffer, `LINE` bytes long.
+    pub fn new() -> Buffer {
+        Buffer {
+            data: [0u8; LINE],
+            len: 0,
+        }
+    }
+}
+
+impl<S> Buffer<S>
+where
+    S: Storage,
+{
+    /// An empty buffer, keeping its bytes in `data`.
+    pub fn from_storage(data: S) -> Buffer<S> {
+        Buffer { data, len: 0 }
+    }
+
+    pub fn as_str(&self) -> &str {
+        str::from_utf8(self.as_bytes()).unwrap_or("")
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data.bytes()[0..self.len]
+    }
+
+    /// How many bytes have been written.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// How many bytes it can hold.
+    pub fn capacity(&self) -> usize {
+        self.data.bytes().len()
+    }
+
+    /// Empty it, ready to write something else.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Take the last character off the end, if there is one.
+    pub fn pop(&mut self) {
+        let len = self.as_str().chars().next_back().map_or(0, |ch| ch.len_utf8());
+        self.len -= len;
+    }
+}
+
+impl<S> fmt::Write for Buffer<S>
+where
+    S: Storage,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let space = self.capacity() - self.len;
+        let mut count = s.len().min(space);
+        // Only whole characters, so `as_str` always has something to show
+        while !s.is_char_boundary(count) {
+            count -= 1;
+        }
+        self.data.bytes_mut()[self.len..self.len + count]
+            .copy_from_slice(&s.as_bytes()[0..count]);
+        self.len += count;
+        if count == s.len() {
+            Ok(())
+        } else {
+            Err(fmt::Error)
+        }
+    }
+}
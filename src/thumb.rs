@@ -0,0 +1,306 @@
+//! A minimal Thumb-2 disassembler.
+//!
+//! It knows all the 16-bit Thumb instructions an ARMv7-M can run, and the
+//! common 32-bit ones - branches, `bl`, `push.w`/`pop.w` and word loads
+//! and stores with a 12-bit offset. Anything else is shown as raw
+//! halfwords. Branch targets are worked out, so you can follow the code.
+//!
+//! Instructions are one or two halfwords long; `length` tells you which
+//! from the first one.
+
+use core::fmt::{self, Write};
+
+const CONDITIONS: [&str; 16] = [
+    "eq", "ne", "cs", "cc", "mi", "pl", "vs", "vc", "hi", "ls", "ge", "lt", "gt", "le", "", "",
+];
+
+const SHIFTS: [&str; 3] = ["lsls", "lsrs", "asrs"];
+
+const DATA_PROCESSING: [&str; 16] = [
+    "ands", "eors", "lsls", "lsrs", "asrs", "adcs", "sbcs", "rors", "tst", "rsbs", "cmp", "cmn",
+    "orrs", "muls", "bics", "mvns",
+];
+
+const IMMEDIATE_OPS: [&str; 4] = ["movs", "cmp", "adds", "subs"];
+
+const LOAD_STORE_REG: [&str; 8] = [
+    "str", "strh", "strb", "ldrsb", "ldr", "ldrh", "ldrb", "ldrsh",
+];
+
+const EXTENDS: [&str; 4] = ["sxth", "sxtb", "uxth", "uxtb"];
+
+const HINTS: [&str; 5] = ["nop", "yield", "wfe", "wfi", "sev"];
+
+/// How long the instruction starting with `first` is, in bytes.
+pub fn length(first: u16) -> u32 {
+    match first >> 11 {
+        0b11101 | 0b11110 | 0b11111 => 4,
+        _ => 2,
+    }
+}
+
+/// Write out the instruction at `address`. `second` is only looked at for
+/// 32-bit instructions.
+pub fn disassemble<W>(out: &mut W, address: u32, first: u16, second: u16) -> fmt::Result
+where
+    W: Write,
+{
+    if length(first) == 4 {
+        wide(out, address, first, second)
+    } else {
+        narrow(out, address, first)
+    }
+}
+
+fn narrow<W: Write>(out: &mut W, address: u32, h: u16) -> fmt::Result {
+    let r0 = h & 7;
+    let r3 = (h >> 3) & 7;
+    let r6 = (h >> 6) & 7;
+    let r8 = (h >> 8) & 7;
+    let imm8 = u32::from(h & 0xFF);
+    // The PC reads as the instruction's address plus four
+    let pc = address.wrapping_add(4);
+    match h >> 11 {
+        0b00000...0b00010 => {
+            let op = usize::from((h >> 11) & 3);
+            write!(out, "{} r{}, r{}, #{}", SHIFTS[op], r0, r3, (h >> 6) & 31)
+        }
+        0b00011 => {
+            let op = if h & (1 << 9) != 0 { "subs" } else { "adds" };
+            if h & (1 << 10) != 0 {
+                write!(out, "{} r{}, r{}, #{}", op, r0, r3, r6)
+            } else {
+                write!(out, "{} r{}, r{}, r{}", op, r0, r3, r6)
+            }
+        }
+        0b00100...0b00111 => {
+            let op = usize::from((h >> 11) & 3);
+            write!(out, "{} r{}, #{}", IMMEDIATE_OPS[op], r8, imm8)
+        }
+        0b01000 if h & (1 << 10) == 0 => {
+            let op = usize::from((h >> 6) & 15);
+            write!(out, "{} r{}, r{}", DATA_PROCESSING[op], r0, r3)
+        }
+        0b01000 => {
+            let rd = ((h >> 4) & 8) | r0;
+            let rm = (h >> 3) & 15;
+            match (h >> 8) & 3 {
+                0 => write!(out, "add {}, {}", Reg(rd), Reg(rm)),
+                1 => write!(out, "cmp {}, {}", Reg(rd), Reg(rm)),
+                2 => write!(out, "mov {}, {}", Reg(rd), Reg(rm)),
+                _ if h & (1 << 7) != 0 => write!(out, "blx {}", Reg(rm)),
+                _ => write!(out, "bx {}", Reg(rm)),
+            }
+        }
+        0b01001 => {
+            let target = (pc & !3).wrapping_add(imm8 * 4);
+            write!(out, "ldr r{}, [pc, #{}] ; 0x{:08x}", r8, imm8 * 4, target)
+        }
+        0b01010 | 0b01011 => {
+            let op = usize::from((h >> 9) & 7);
+            write!(out, "{} r{}, [r{}, r{}]", LOAD_STORE_REG[op], r0, r3, r6)
+        }
+        0b01100...0b10001 => {
+            let (op, scale) = match h >> 11 {
+                0b01100 => ("str", 4),
+                0b01101 => ("ldr", 4),
+                0b01110 => ("strb", 1),
+                0b01111 => ("ldrb", 1),
+                0b10000 => ("strh", 2),
+                _ => ("ldrh", 2),
+            };
+            write!(out, "{} r{}, [r{}, #{}]", op, r0, r3, ((h >> 6) & 31) * scale)
+        }
+        0b10010 => write!(out, "str r{}, [sp, #{}]", r8, imm8 * 4),
+        0b10011 => write!(out, "ldr r{}, [sp, #{}]", r8, imm8 * 4),
+        0b10100 => write!(out, "adr r{}, 0x{:08x}", r8, (pc & !3).wrapping_add(imm8 * 4)),
+        0b10101 => write!(out, "add r{}, sp, #{}", r8, imm8 * 4),
+        0b10110 | 0b10111 => misc(out, pc, h),
+        0b11000 => {
+            out.write_str("stmia ")?;
+            write!(out, "r{}!, ", r8)?;
+            reg_list(out, h & 0xFF, None)
+        }
+        0b11001 => {
+            out.write_str("ldmia ")?;
+            write!(out, "r{}!, ", r8)?;
+            reg_list(out, h & 0xFF, None)
+        }
+        0b11010 | 0b11011 => match (h >> 8) & 15 {
+            0b1110 => write!(out, "udf #{}", imm8),
+            0b1111 => write!(out, "svc #{}", imm8),
+            cond => {
+                let offset = (i32::from(h as u8 as i8) * 2) as u32;
+                write!(
+                    out,
+                    "b{} 0x{:08x}",
+                    CONDITIONS[usize::from(cond)],
+                    pc.wrapping_add(offset)
+                )
+            }
+        },
+        _ => {
+            // An unconditional branch, with an 11-bit offset
+            let offset = ((i32::from(h << 5) << 16) >> 20) as u32;
+            write!(out, "b 0x{:08x}", pc.wrapping_add(offset))
+        }
+    }
+}
+
+/// The 1011 group - stack, extends, hints and so on.
+fn misc<W: Write>(out: &mut W, pc: u32, h: u16) -> fmt::Result {
+    let r0 = h & 7;
+    let r3 = (h >> 3) & 7;
+    match (h >> 8) & 15 {
+        0b0000 if h & (1 << 7) == 0 => write!(out, "add sp, #{}", (h & 0x7F) * 4),
+        0b0000 => write!(out, "sub sp, #{}", (h & 0x7F) * 4),
+        0b0001 | 0b0011 | 0b1001 | 0b1011 => {
+            let op = if h & (1 << 11) != 0 { "cbnz" } else { "cbz" };
+            let offset = u32::from(((h >> 3) & 31) | ((h >> 4) & 32)) * 2;
+            write!(out, "{} r{}, 0x{:08x}", op, r0, pc.wrapping_add(offset))
+        }
+        0b0010 => {
+            let op = usize::from((h >> 6) & 3);
+            write!(out, "{} r{}, r{}", EXTENDS[op], r0, r3)
+        }
+        0b0100 | 0b0101 => {
+            out.write_str("push ")?;
+            reg_list(out, h & 0x1FF, Some("lr"))
+        }
+        0b1100 | 0b1101 => {
+            out.write_str("pop ")?;
+            reg_list(out, h & 0x1FF, Some("pc"))
+        }
+        0b0110 if (h >> 5) & 7 == 0b011 => {
+            let op = if h & (1 << 4) != 0 { "cpsid" } else { "cpsie" };
+            let flags = match h & 3 {
+                1 => "f",
+                2 => "i",
+                _ => "if",
+            };
+            write!(out, "{} {}", op, flags)
+        }
+        0b1010 => match (h >> 6) & 3 {
+            0 => write!(out, "rev r{}, r{}", r0, r3),
+            1 => write!(out, "rev16 r{}, r{}", r0, r3),
+            3 => write!(out, "revsh r{}, r{}", r0, r3),
+            _ => write!(out, ".short 0x{:04x}", h),
+        },
+        0b1110 => write!(out, "bkpt #{}", h & 0xFF),
+        0b1111 if h & 15 != 0 => {
+            // The mask says how many instructions follow, and whether each
+            // is 'then' or 'else'
+            let cond = (h >> 4) & 15;
+            let mask = h & 15;
+            out.write_str("it")?;
+            let mut bit = 3;
+            while mask & ((1 << bit) - 1) != 0 {
+                let same = ((mask >> bit) & 1) == (cond & 1);
+                out.write_str(if same { "t" } else { "e" })?;
+                bit -= 1;
+            }
+            write!(out, " {}", CONDITIONS[usize::from(cond)])
+        }
+        0b1111 => match HINTS.get(usize::from((h >> 4) & 15)) {
+            Some(hint) => out.write_str(hint),
+            None => write!(out, ".short 0x{:04x}", h),
+        },
+        _ => write!(out, ".short 0x{:04x}", h),
+    }
+}
+
+fn wide<W: Write>(out: &mut W, address: u32, h1: u16, h2: u16) -> fmt::Result {
+    let pc = address.wrapping_add(4);
+    let s = u32::from((h1 >> 10) & 1);
+    let j1 = u32::from((h2 >> 13) & 1);
+    let j2 = u32::from((h2 >> 11) & 1);
+    let imm11 = u32::from(h2 & 0x7FF);
+    if h1 >> 11 == 0b11110 && h2 & 0x8000 != 0 {
+        if h2 & 0x1000 != 0 {
+            // BL and B.W have a 25-bit offset
+            let i1 = !(j1 ^ s) & 1;
+            let i2 = !(j2 ^ s) & 1;
+            let imm10 = u32::from(h1 & 0x3FF);
+            let offset = (s << 24) | (i1 << 23) | (i2 << 22) | (imm10 << 12) | (imm11 << 1);
+            let offset = ((offset << 7) as i32 >> 7) as u32;
+            let op = if h2 & 0x4000 != 0 { "bl" } else { "b.w" };
+            return write!(out, "{} 0x{:08x}", op, pc.wrapping_add(offset));
+        }
+        let cond = usize::from((h1 >> 6) & 15);
+        if h2 & 0x4000 == 0 && cond < 14 {
+            // Conditional B.W has a 21-bit offset
+            let imm6 = u32::from(h1 & 0x3F);
+            let offset = (s << 20) | (j2 << 19) | (j1 << 18) | (imm6 << 12) | (imm11 << 1);
+            let offset = ((offset << 11) as i32 >> 11) as u32;
+            return write!(out, "b{}.w 0x{:08x}", CONDITIONS[cond], pc.wrapping_add(offset));
+        }
+    }
+    let rn = h1 & 15;
+    let rt = h2 >> 12;
+    match h1 & 0xFFF0 {
+        0xF8D0 => write!(out, "ldr.w {}, [{}, #{}]", Reg(rt), Reg(rn), h2 & 0xFFF),
+        0xF8C0 => write!(out, "str.w {}, [{}, #{}]", Reg(rt), Reg(rn), h2 & 0xFFF),
+        _ if h1 == 0xE92D => {
+            out.write_str("push.w ")?;
+            wide_reg_list(out, h2)
+        }
+        _ if h1 == 0xE8BD => {
+            out.write_str("pop.w ")?;
+            wide_reg_list(out, h2)
+        }
+        _ => write!(out, ".word 0x{:04x}{:04x}", h1, h2),
+    }
+}
+
+/// `{r0, r4, lr}` and so on. Bit 8 is `extra`, if there is one.
+fn reg_list<W: Write>(out: &mut W, list: u16, extra: Option<&str>) -> fmt::Result {
+    out.write_char('{')?;
+    let mut first = true;
+    for reg in 0..8 {
+        if list & (1 << reg) != 0 {
+            if !first {
+                out.write_str(", ")?;
+            }
+            write!(out, "r{}", reg)?;
+            first = false;
+        }
+    }
+    if let Some(extra) = extra {
+        if list & (1 << 8) != 0 {
+            if !first {
+                out.write_str(", ")?;
+            }
+            out.write_str(extra)?;
+        }
+    }
+    out.write_char('}')
+}
+
+fn wide_reg_list<W: Write>(out: &mut W, list: u16) -> fmt::Result {
+    out.write_char('{')?;
+    let mut first = true;
+    for reg in 0..16 {
+        if list & (1 << reg) != 0 {
+            if !first {
+                out.write_str(", ")?;
+            }
+            write!(out, "{}", Reg(reg))?;
+            first = false;
+        }
+    }
+    out.write_char('}')
+}
+
+/// Shows the high registers by name.
+struct Reg(u16);
+
+impl fmt::Display for Reg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            13 => f.write_str("sp"),
+            14 => f.write_str("lr"),
+            15 => f.write_str("pc"),
+            n => write!(f, "r{}", n),
+        }
+    }
+}
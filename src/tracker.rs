@@ -0,0 +1,573 @@
+//! Plays tracker-style songs on the `synth`.
+//!
+//! A song is a list of patterns and the order to play them in. A pattern is
+//! rows of text, in flash, that look like a MOD tracker's - one cell for
+//! each synth channel, separated by `|`:
+//!
+//! ```text
+//! "C-5 1 047|A-2 2 ...|--- . ..."
+//! ```
+//!
+//! Each cell is a note (`C-5`, `F#3`, `---` for nothing and `===` to let go
+//! of the last one), an instrument (`1` to `9`, or `.` to keep the last
+//! one) and an effect:
+//!
+//! * `0xy` - arpeggio: cycle through the note, `x` and `y` semitones above
+//! * `1xx` - slide up `xx` 1024ths of the pitch every tick
+//! * `2xx` - slide down
+//! * `Cxx` - set the channel's volume
+//! * `Fxx` - set the number of ticks per row
+//! * `...` - nothing
+//!
+//! Effects only last for their own row. There are `TICK_HZ` ticks a
+//! second. Timer4A provides them: the caller must power up Timer4, call
+//! `start`, enable the TIMER4A interrupt (below the VGA timers, like the
+//! synth's) and hook up the handler:
+//!
+//! ``` ignore
+//! interrupt!(TIMER4A, tracker::timer4a_isr);
+//! ```
+//!
+//! Then `play` a `Song`. `DEMO` is one to be going on with.
+
+use cortex_m::interrupt;
+use hal::sysctl::Clocks;
+use pac::{self, TIMER4};
+use synth::{self, Envelope, Synth, CHANNELS};
+use wavetable::Waveform;
+
+/// How often the player moves on, as in a MOD player.
+pub const TICK_HZ: u32 = 50;
+
+// GPTMCFG
+const CFG_32_BIT: u32 = 0x0;
+
+/// What a channel plays a note with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instrument {
+    pub waveform: Waveform,
+    pub envelope: Envelope,
+    pub volume: u8,
+}
+
+/// Something to play.
+pub struct Song {
+    pub name: &'static str,
+    /// Ticks per row, until an `Fxx` says otherwise
+    pub speed: u8,
+    /// Numbered from 1 in the patterns
+    pub instruments: &'static [Instrument],
+    pub patterns: &'static [&'static [&'static str]],
+    /// Which pattern to play when
+    pub order: &'static [usize],
+    /// Where in `order` to go back to at the end, or `None` to stop
+    pub restart: Option<usize>,
+}
+
+/// The note part of a cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Note {
+    None,
+    /// A MIDI note number
+    On(u8),
+    Off,
+}
+
+/// The effect part of a cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    None,
+    Arpeggio(u8, u8),
+    SlideUp(u8),
+    SlideDown(u8),
+    Volume(u8),
+    Speed(u8),
+}
+
+/// One channel's part of a row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub note: Note,
+    /// Counting from 1, as it's written
+    pub instrument: Option<u8>,
+    pub effect: Effect,
+}
+
+impl Cell {
+    /// Parse a cell like `C#4 1 047`. Returns `None` if it doesn't make
+    /// sense.
+    pub fn parse(text: &str) -> Option<Cell> {
+        let mut fields = text.split_whitespace();
+        let note = parse_note(fields.next()?)?;
+        let instrument = match fields.next()? {
+            "." => None,
+            s => Some(s.parse::<u8>().ok().filter(|&i| i > 0)?),
+        };
+        let effect = parse_effect(fields.next()?)?;
+        if fields.next().is_some() {
+            return None;
+        }
+        Some(Cell {
+            note,
+            instrument,
+            effect,
+        })
+    }
+}
+
+fn parse_note(text: &str) -> Option<Note> {
+    const NAMES: [&str; 12] = [
+        "C-", "C#", "D-", "D#", "E-", "F-", "F#", "G-", "G#", "A-", "A#", "B-",
+    ];
+    match text {
+        "---" => return Some(Note::None),
+        "===" => return Some(Note::Off),
+        _ => {}
+    }
+    if text.len() != 3 {
+        return None;
+    }
+    let name = text.get(0..2)?;
+    let semitone = NAMES.iter().position(|&n| n == name)? as u8;
+    let octave = text.get(2..3)?.parse::<u8>().ok()?;
+    // Middle C is C-4, which is MIDI note 60
+    Some(Note::On(((octave + 1) * 12) + semitone))
+}
+
+fn parse_effect(text: &str) -> Option<Effect> {
+    if text == "..." {
+        return Some(Effect::None);
+    }
+    let command = text.get(0..1)?;
+    let x = u8::from_str_radix(text.get(1..2)?, 16).ok()?;
+    let y = u8::from_str_radix(text.get(2..3)?, 16).ok()?;
+    let xx = (x << 4) | y;
+    match command {
+        "0" => Some(Effect::Arpeggio(x, y)),
+        "1" => Some(Effect::SlideUp(xx)),
+        "2" => Some(Effect::SlideDown(xx)),
+        "C" => Some(Effect::Volume(xx)),
+        "F" => Some(Effect::Speed(xx.max(1))),
+        _ => None,
+    }
+}
+
+/// What the player remembers about each channel.
+#[derive(Clone, Copy)]
+struct Voice {
+    note: u8,
+    millihertz: u32,
+    effect: Effect,
+}
+
+/// Works through a song, a tick at a time.
+pub struct Player {
+    song: &'static Song,
+    position: usize,
+    row: usize,
+    tick: u8,
+    speed: u8,
+    voices: [Voice; CHANNELS],
+    finished: bool,
+}
+
+impl Player {
+    pub fn new(song: &'static Song) -> Player {
+        Player {
+            song,
+            position: 0,
+            row: 0,
+            tick: 0,
+            speed: song.speed.max(1),
+            voices: [Voice {
+                note: 0,
+                millihertz: 0,
+                effect: Effect::None,
+            }; CHANNELS],
+            finished: song.order.is_empty(),
+        }
+    }
+
+    /// Where we are in the song's order, and the row in that pattern.
+    pub fn position(&self) -> (usize, usize) {
+        (self.position, self.row)
+    }
+
+    /// Has the song ended? Songs that loop never do.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Move on a tick, playing any new notes and effects on `synth`.
+    pub fn tick(&mut self, synth: &mut Synth) {
+        if self.finished {
+            return;
+        }
+        if self.tick == 0 {
+            self.start_row(synth);
+        }
+        let tick = self.tick;
+        for (channel, voice) in self.voices.iter_mut().enumerate() {
+            apply_effect(synth, channel, voice, tick);
+        }
+        self.tick += 1;
+        if self.tick >= self.speed {
+            self.tick = 0;
+            self.next_row(synth);
+        }
+    }
+
+    fn start_row(&mut self, synth: &mut Synth) {
+        let song = self.song;
+        let pattern = song.patterns[song.order[self.position]];
+        let line = pattern[self.row];
+        for (channel, voice) in self.voices.iter_mut().enumerate() {
+            // Put the pitch back after an arpeggio
+            if let Effect::Arpeggio(_, _) = voice.effect {
+                synth.set_pitch(channel, voice.millihertz);
+            }
+            voice.effect = Effect::None;
+        }
+        for (channel, text) in line.split('|').take(CHANNELS).enumerate() {
+            let cell = match Cell::parse(text) {
+                Some(cell) => cell,
+                None => continue,
+            };
+            if let Some(instrument) = cell.instrument {
+                if let Some(i) = song.instruments.get(usize::from(instrument) - 1) {
+                    synth.set_waveform(channel, i.waveform);
+                    synth.set_envelope(channel, i.envelope);
+                    synth.set_volume(channel, i.volume);
+                }
+            }
+            match cell.note {
+                Note::On(note) => {
+                    let millihertz = synth::note_millihertz(note);
+                    self.voices[channel].note = note;
+                    self.voices[channel].millihertz = millihertz;
+                    synth.note_on(channel, millihertz);
+                }
+                Note::Off => synth.note_off(channel),
+                Note::None => {}
+            }
+            match cell.effect {
+                Effect::Volume(volume) => synth.set_volume(channel, volume),
+                Effect::Speed(speed) => self.speed = speed,
+                effect => self.voices[channel].effect = effect,
+            }
+        }
+    }
+
+    fn next_row(&mut self, synth: &mut Synth) {
+        let song = self.song;
+        self.row += 1;
+        if self.row < song.patterns[song.order[self.position]].len() {
+            return;
+        }
+        self.row = 0;
+        self.position += 1;
+        if self.position < song.order.len() {
+            return;
+        }
+        match song.restart {
+            Some(position) if position < song.order.len() => self.position = position,
+            _ => {
+                self.finished = true;
+                for channel in 0..CHANNELS {
+                    synth.note_off(channel);
+                }
+            }
+        }
+    }
+}
+
+/// Do this tick's part of a channel's effect.
+fn apply_effect(synth: &mut Synth, channel: usize, voice: &mut Voice, tick: u8) {
+    match voice.effect {
+        Effect::Arpeggio(x, y) => {
+            let offset = match tick % 3 {
+                0 => 0,
+                1 => x,
+                _ => y,
+            };
+            let note = voice.note.saturating_add(offset);
+            synth.set_pitch(channel, synth::note_millihertz(note));
+        }
+        // Slides start on the tick after the row, as in a MOD
+        Effect::SlideUp(amount) if tick > 0 => {
+            voice.millihertz += (voice.millihertz * u32::from(amount)) / 1024;
+            synth.set_pitch(channel, voice.millihertz);
+        }
+        Effect::SlideDown(amount) if tick > 0 => {
+            voice.millihertz -= (voice.millihertz * u32::from(amount)) / 1024;
+            synth.set_pitch(channel, voice.millihertz);
+        }
+        _ => {}
+    }
+}
+
+static mut PLAYER: Option<Player> = None;
+
+/// Set Timer4A ticking. Nothing plays until `play`.
+pub fn start(timer: TIMER4, clocks: &Clocks) {
+    timer.ctl.modify(|_, w| w.taen().clear_bit());
+    timer.cfg.write(|w| unsafe { w.bits(CFG_32_BIT) });
+    timer.tamr.modify(|_, w| w.tamr().period());
+    timer.tailr.write(|w| unsafe { w.bits((clocks.sysclk.0 / TICK_HZ) - 1) });
+    timer.imr.modify(|_, w| w.tatoim().set_bit());
+    timer.icr.write(|w| w.tatocint().set_bit());
+    timer.ctl.modify(|_, w| w.taen().set_bit());
+}
+
+/// Play a song from the top, instead of whatever was playing.
+pub fn play(song: &'static Song) {
+    interrupt::free(|_| unsafe { PLAYER = Some(Player::new(song)) });
+}
+
+/// Stop the song, letting go of any notes.
+pub fn stop() {
+    interrupt::free(|_| unsafe { PLAYER = None });
+    synth::with(|s| {
+        for channel in 0..CHANNELS {
+            s.note_off(channel);
+        }
+    });
+}
+
+/// Where the song has got to (see `Player::position`), or `None` if
+/// nothing is playing.
+pub fn position() -> Option<(usize, usize)> {
+    interrupt::free(|_| unsafe {
+        match PLAYER {
+            Some(ref player) if !player.is_finished() => Some(player.position()),
+            _ => None,
+        }
+    })
+}
+
+/// Move the song on a tick. The TIMER4A handler.
+pub fn timer4a_isr() {
+    let timer = unsafe { &*pac::TIMER4::ptr() };
+    timer.icr.write(|w| w.tatocint().set_bit());
+    if let Some(player) = unsafe { PLAYER.as_mut() } {
+        synth::with(|s| player.tick(s));
+    }
+}
+
+/// A little tune in C, that loops.
+pub static DEMO: Song = Song {
+    name: "Demo",
+    speed: 6,
+    instruments: &[
+        // 1: lead
+        Instrument {
+            waveform: Waveform::Square,
+            envelope: Envelope {
+                attack_ms: 2,
+                decay_ms: 200,
+                sustain: 160,
+                release_ms: 50,
+            },
+            volume: 200,
+        },
+        // 2: bass
+        Instrument {
+            waveform: Waveform::Sawtooth,
+            envelope: Envelope {
+                attack_ms: 2,
+                decay_ms: 100,
+                sustain: 128,
+                release_ms: 30,
+            },
+            volume: 255,
+        },
+        // 3: hi-hat
+        Instrument {
+            waveform: Waveform::Noise,
+            envelope: Envelope {
+                attack_ms: 1,
+                decay_ms: 40,
+                sustain: 0,
+                release_ms: 5,
+            },
+            volume: 120,
+        },
+        // 4: drum
+        Instrument {
+            waveform: Waveform::Noise,
+            envelope: Envelope {
+                attack_ms: 1,
+                decay_ms: 120,
+                sustain: 0,
+                release_ms: 20,
+            },
+            volume: 200,
+        },
+        // 5: chords
+        Instrument {
+            waveform: Waveform::Square,
+            envelope: synth::ORGAN,
+            volume: 140,
+        },
+    ],
+    patterns: &[
+        &PATTERN_0, &PATTERN_1, &PATTERN_2, &PATTERN_3, &PATTERN_4, &PATTERN_5, &PATTERN_6,
+        &PATTERN_7,
+    ],
+    order: &[0, 1, 2, 3, 4, 5, 6, 7],
+    restart: Some(0),
+};
+
+// C, A minor, F and G, as chords
+const PATTERN_0: [&str; 16] = [
+    "C-5 5 047|C-2 2 ...|C-3 4 ...",
+    "--- . 047|--- . ...|--- . ...",
+    "--- . 047|--- . ...|C-8 3 ...",
+    "--- . 047|--- . ...|--- . ...",
+    "--- . 047|C-2 2 ...|C-6 4 ...",
+    "--- . 047|--- . ...|--- . ...",
+    "--- . 047|C-3 2 ...|C-8 3 ...",
+    "--- . 047|--- . ...|--- . ...",
+    "--- . 047|C-2 2 ...|C-3 4 ...",
+    "--- . 047|--- . ...|--- . ...",
+    "--- . 047|--- . ...|C-8 3 ...",
+    "--- . 047|--- . ...|--- . ...",
+    "--- . 047|G-2 2 ...|C-6 4 ...",
+    "--- . 047|--- . ...|--- . ...",
+    "=== . ...|C-3 2 ...|C-8 3 ...",
+    "--- . ...|--- . ...|--- . ...",
+];
+
+const PATTERN_1: [&str; 16] = [
+    "A-4 5 037|A-1 2 ...|C-3 4 ...",
+    "--- . 037|--- . ...|--- . ...",
+    "--- . 037|--- . ...|C-8 3 ...",
+    "--- . 037|--- . ...|--- . ...",
+    "--- . 037|A-1 2 ...|C-6 4 ...",
+    "--- . 037|--- . ...|--- . ...",
+    "--- . 037|A-2 2 ...|C-8 3 ...",
+    "--- . 037|--- . ...|--- . ...",
+    "--- . 037|A-1 2 ...|C-3 4 ...",
+    "--- . 037|--- . ...|--- . ...",
+    "--- . 037|--- . ...|C-8 3 ...",
+    "--- . 037|--- . ...|--- . ...",
+    "--- . 037|E-2 2 ...|C-6 4 ...",
+    "--- . 037|--- . ...|--- . ...",
+    "=== . ...|A-2 2 ...|C-8 3 ...",
+    "--- . ...|--- . ...|--- . ...",
+];
+
+const PATTERN_2: [&str; 16] = [
+    "F-4 5 047|F-1 2 ...|C-3 4 ...",
+    "--- . 047|--- . ...|--- . ...",
+    "--- . 047|--- . ...|C-8 3 ...",
+    "--- . 047|--- . ...|--- . ...",
+    "--- . 047|F-1 2 ...|C-6 4 ...",
+    "--- . 047|--- . ...|--- . ...",
+    "--- . 047|F-2 2 ...|C-8 3 ...",
+    "--- . 047|--- . ...|--- . ...",
+    "--- . 047|F-1 2 ...|C-3 4 ...",
+    "--- . 047|--- . ...|--- . ...",
+    "--- . 047|--- . ...|C-8 3 ...",
+    "--- . 047|--- . ...|--- . ...",
+    "--- . 047|C-2 2 ...|C-6 4 ...",
+    "--- . 047|--- . ...|--- . ...",
+    "=== . ...|F-2 2 ...|C-8 3 ...",
+    "--- . ...|--- . ...|--- . ...",
+];
+
+const PATTERN_3: [&str; 16] = [
+    "G-4 5 047|G-1 2 ...|C-3 4 ...",
+    "--- . 047|--- . ...|--- . ...",
+    "--- . 047|--- . ...|C-8 3 ...",
+    "--- . 047|--- . ...|--- . ...",
+    "--- . 047|G-1 2 ...|C-6 4 ...",
+    "--- . 047|--- . ...|--- . ...",
+    "--- . 047|G-2 2 ...|C-8 3 ...",
+    "--- . 047|--- . ...|--- . ...",
+    "--- . 047|G-1 2 ...|C-3 4 ...",
+    "--- . 047|--- . ...|--- . ...",
+    "--- . 047|--- . ...|C-8 3 ...",
+    "--- . 047|--- . ...|--- . ...",
+    "--- . 047|D-2 2 ...|C-6 4 ...",
+    "--- . 047|--- . ...|--- . ...",
+    "=== . ...|G-2 2 ...|C-8 3 ...",
+    "--- . ...|--- . ...|--- . ...",
+];
+
+// The same again, with a tune
+const PATTERN_4: [&str; 16] = [
+    "E-5 1 ...|C-2 2 ...|C-3 4 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "--- . ...|--- . ...|C-8 3 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "G-5 1 ...|C-2 2 ...|C-6 4 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "E-5 1 ...|C-3 2 ...|C-8 3 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "C-5 1 ...|C-2 2 ...|C-3 4 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "--- . ...|--- . ...|C-8 3 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "D-5 1 ...|G-2 2 ...|C-6 4 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "E-5 1 ...|C-3 2 ...|C-8 3 ...",
+    "--- . ...|--- . ...|--- . ...",
+];
+
+const PATTERN_5: [&str; 16] = [
+    "C-5 1 ...|A-1 2 ...|C-3 4 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "--- . ...|--- . ...|C-8 3 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "A-4 1 ...|A-1 2 ...|C-6 4 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "--- . ...|A-2 2 ...|C-8 3 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "C-5 1 ...|A-1 2 ...|C-3 4 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "E-5 1 ...|--- . ...|C-8 3 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "D-5 1 ...|E-2 2 ...|C-6 4 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "C-5 1 ...|A-2 2 ...|C-8 3 ...",
+    "--- . ...|--- . ...|--- . ...",
+];
+
+const PATTERN_6: [&str; 16] = [
+    "A-4 1 ...|F-1 2 ...|C-3 4 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "--- . ...|--- . ...|C-8 3 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "C-5 1 ...|F-1 2 ...|C-6 4 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "F-5 1 ...|F-2 2 ...|C-8 3 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "E-5 1 ...|F-1 2 ...|C-3 4 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "C-5 1 ...|--- . ...|C-8 3 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "A-4 1 ...|C-2 2 ...|C-6 4 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "C-5 1 ...|F-2 2 ...|C-8 3 ...",
+    "--- . ...|--- . ...|--- . ...",
+];
+
+const PATTERN_7: [&str; 16] = [
+    "B-4 1 ...|G-1 2 ...|C-3 4 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "--- . ...|--- . ...|C-8 3 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "D-5 1 ...|G-1 2 ...|C-6 4 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "G-5 1 ...|G-2 2 ...|C-8 3 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "F-5 1 ...|G-1 2 ...|C-3 4 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "D-5 1 ...|--- . ...|C-8 3 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "B-4 1 ...|D-2 2 ...|C-6 4 ...",
+    "--- . ...|--- . ...|--- . ...",
+    "=== . ...|G-2 2 ...|C-8 3 ...",
+    "--- . 108|--- . ...|--- . ...",
+];
@@ -0,0 +1,100 @@
+//! Integer trigonometry, for code that wants angles without dragging in
+//! floating point.
+//!
+//! Angles are 'binary degrees': a full circle is 65536, so they wrap
+//! naturally in a `u16`. Results are Q15 fixed point, where 32767 is 1.0.
+
+/// One cycle of a sine wave, in 256 steps, Q15.
+pub const SINE: [i16; 256] = [
+    0, 804, 1608, 2410, 3212, 4011, 4808, 5602,
+    6393, 7179, 7962, 8739, 9512, 10278, 11039, 11793,
+    12539, 13279, 14010, 14732, 15446, 16151, 16846, 17530,
+    18204, 18868, 19519, 20159, 20787, 21403, 22005, 22594,
+    23170, 23731, 24279, 24811, 25329, 25832, 26319, 26790,
+    27245, 27683, 28105, 28510, 28898, 29268, 29621, 29956,
+    30273, 30571, 30852, 31113, 31356, 31580, 31785, 31971,
+    32137, 32285, 32412, 32521, 32609, 32678, 32728, 32757,
+    32767, 32757, 32728, 32678, 32609, 32521, 32412, 32285,
+    32137, 31971, 31785, 31580, 31356, 31113, 30852, 30571,
+    30273, 29956, 29621, 29268, 28898, 28510, 28105, 27683,
+    27245, 26790, 26319, 25832, 25329, 24811, 24279, 23731,
+    23170, 22594, 22005, 21403, 20787, 20159, 19519, 18868,
+    18204, 17530, 16846, 16151, 15446, 14732, 14010, 13279,
+    12539, 11793, 11039, 10278, 9512, 8739, 7962, 7179,
+    6393, 5602, 4808, 4011, 3212, 2410, 1608, 804,
+    0, -804, -1608, -2410, -3212, -4011, -4808, -5602,
+    -6393, -7179, -7962, -8739, -9512, -10278, -11039, -11793,
+    -12539, -13279, -14010, -14732, -15446, -16151, -16846, -17530,
+    -18204, -18868, -19519, -20159, -20787, -21403, -22005, -22594,
+    -23170, -23731, -24279, -24811, -25329, -25832, -26319, -26790,
+    -27245, -27683, -28105, -28510, -28898, -29268, -29621, -29956,
+    -30273, -30571, -30852, -31113, -31356, -31580, -31785, -31971,
+    -32137, -32285, -32412, -32521, -32609, -32678, -32728, -32757,
+    -32767, -32757, -32728, -32678, -32609, -32521, -32412, -32285,
+    -32137, -31971, -31785, -31580, -31356, -31113, -30852, -30571,
+    -30273, -29956, -29621, -29268, -28898, -28510, -28105, -27683,
+    -27245, -26790, -26319, -25832, -25329, -24811, -24279, -23731,
+    -23170, -22594, -22005, -21403, -20787, -20159, -19519, -18868,
+    -18204, -17530, -16846, -16151, -15446, -14732, -14010, -13279,
+    -12539, -11793, -11039, -10278, -9512, -8739, -7962, -7179,
+    -6393, -5602, -4808, -4011, -3212, -2410, -1608, -804,
+];
+
+/// A quarter turn in binary degrees.
+pub const QUARTER_TURN: u16 = 16384;
+
+/// Sine of a binary angle, in Q15. We interpolate between table entries.
+pub fn sin(angle: u16) -> i16 {
+    let idx = (angle >> 8) as usize;
+    let frac = i32::from(angle & 0xFF);
+    let a = i32::from(SINE[idx]);
+    let b = i32::from(SINE[(idx + 1) & 0xFF]);
+    (a + (((b - a) * frac) >> 8)) as i16
+}
+
+/// Cosine of a binary angle, in Q15.
+pub fn cos(angle: u16) -> i16 {
+    sin(angle.wrapping_add(QUARTER_TURN))
+}
+
+/// The angle of the vector (`x`, `y`), in thousandths of a degree
+/// (-180000..180000). Good to about a quarter of a degree.
+pub fn atan2(y: i32, x: i32) -> i32 {
+    if x == 0 && y == 0 {
+        return 0;
+    }
+    let (ax, ay) = (i64::from(x).abs(), i64::from(y).abs());
+    // Work out the angle in the first octant, where the ratio is <= 1
+    let (num, den) = if ax >= ay { (ay, ax) } else { (ax, ay) };
+    let z = (num << 15) / den;
+    // atan(z) ~= 45z + 15.64z(1 - z) degrees, for 0 <= z <= 1
+    let octant = ((45_000 * z) + ((15_640 * z * (32_768 - z)) >> 15)) >> 15;
+    let first_quadrant = if ax >= ay { octant } else { 90_000 - octant };
+    let angle = match (x >= 0, y >= 0) {
+        (true, true) => first_quadrant,
+        (false, true) => 180_000 - first_quadrant,
+        (false, false) => first_quadrant - 180_000,
+        (true, false) => -first_quadrant,
+    };
+    angle as i32
+}
+
+/// Integer square root, rounded down.
+pub fn isqrt(n: u32) -> u32 {
+    let mut result = 0u32;
+    let mut bit = 1u32 << 30;
+    let mut n = n;
+    while bit > n {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if n >= result + bit {
+            n -= result + bit;
+            result = (result >> 1) + bit;
+        } else {
+            result >>= 1;
+        }
+        bit >>= 2;
+    }
+    result
+}
@@ -0,0 +1,595 @@
+//! Text mode widgets - frames, labels, progress bars, list boxes, buttons
+//! and dialogs - drawn on any `Canvas`.
+//!
+//! Everything is placed in character cells (`font::WIDTH` by
+//! `font::HEIGHT` pixels), counting from the top left of the canvas, so
+//! row 0 is where the `status_bar` goes. Lines and boxes use the IBM PC
+//! line drawing characters, which Code Page 850 kept from 437. There's no
+//! colour on the VGA output, so `Attr::Highlight` is inverse video.
+//!
+//! Each widget can `draw` itself and, if it can take the focus, handle a
+//! `keyboard::Key`. The framebuffer can't be read back, so nothing is
+//! remembered about what was on the screen - after a `Dialog` goes away,
+//! draw whatever was under it again. A `Focus` moves between a screen's
+//! widgets: Tab, Up and Down go to the next or previous one, unless the
+//! focused widget wants them (a `ListBox` wants Up and Down). The widgets
+//! are borrowed only for each call, so the caller can look at them in
+//! between:
+//!
+//! ``` ignore
+//! let mut focus = Focus::new();
+//! loop {
+//!     let key = ...;
+//!     let (index, response) = {
+//!         let mut widgets: [&mut Widget; 2] = [&mut list, &mut ok];
+//!         let result = focus.key(&mut widgets, key);
+//!         focus.draw(fb, &mut widgets);
+//!         result
+//!     };
+//!     if index == 1 && response == Response::Activated {
+//!         use(list.selected());
+//!     }
+//! }
+//! ```
+
+use font;
+use graphics::{Canvas, Colour};
+use keyboard::Key;
+
+/// How to draw some text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attr {
+    Normal,
+    /// Inverse video, for whatever has the focus
+    Highlight,
+}
+
+impl Attr {
+    /// Foreground and background.
+    fn colours(self) -> (Colour, Colour) {
+        match self {
+            Attr::Normal => (Colour::WHITE, Colour::BLACK),
+            Attr::Highlight => (Colour::BLACK, Colour::WHITE),
+        }
+    }
+}
+
+/// A box of character cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub col: usize,
+    pub row: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    /// The cells inside a frame drawn around this.
+    pub fn inside(&self) -> Rect {
+        Rect {
+            col: self.col + 1,
+            row: self.row + 1,
+            width: self.width.saturating_sub(2),
+            height: self.height.saturating_sub(2),
+        }
+    }
+}
+
+/// The characters for a frame.
+pub struct Lines {
+    pub horizontal: u8,
+    pub vertical: u8,
+    pub top_left: u8,
+    pub top_right: u8,
+    pub bottom_left: u8,
+    pub bottom_right: u8,
+}
+
+impl Lines {
+    pub const SINGLE: Lines = Lines {
+        horizontal: 0xC4,
+        vertical: 0xB3,
+        top_left: 0xDA,
+        top_right: 0xBF,
+        bottom_left: 0xC0,
+        bottom_right: 0xD9,
+    };
+
+    pub const DOUBLE: Lines = Lines {
+        horizontal: 0xCD,
+        vertical: 0xBA,
+        top_left: 0xC9,
+        top_right: 0xBB,
+        bottom_left: 0xC8,
+        bottom_right: 0xBC,
+    };
+}
+
+/// A solid block, for the filled part of a `ProgressBar`.
+const FULL_BLOCK: u8 = 0xDB;
+/// A light shade, for the empty part.
+const LIGHT_SHADE: u8 = 0xB0;
+
+/// Draw one character in a cell.
+pub fn put(canvas: &mut Canvas, col: usize, row: usize, ch: u8, attr: Attr) {
+    let (fg, bg) = attr.colours();
+    canvas.draw_char(col * font::WIDTH, row * font::HEIGHT, ch, fg, bg);
+}
+
+/// Draw `s` in `width` cells, cut short or padded with spaces to fit.
+pub fn text(canvas: &mut Canvas, col: usize, row: usize, width: usize, s: &str, attr: Attr) {
+    let mut bytes = s.bytes();
+    for i in 0..width {
+        put(canvas, col + i, row, bytes.next().unwrap_or(b' '), attr);
+    }
+}
+
+/// Fill a box with spaces.
+pub fn clear(canvas: &mut Canvas, rect: Rect) {
+    let (_, bg) = Attr::Normal.colours();
+    canvas.fill_rect(
+        rect.col * font::WIDTH,
+        rect.row * font::HEIGHT,
+        rect.width * font::WIDTH,
+        rect.height * font::HEIGHT,
+        bg,
+    );
+}
+
+/// Draw a frame around the edge of `rect`, with a title in the top edge.
+pub fn frame(canvas: &mut Canvas, rect: Rect, lines: &Lines, title: Option<&str>) {
+    if rect.width < 2 || rect.height < 2 {
+        return;
+    }
+    let right = rect.col + rect.width - 1;
+    let bottom = rect.row + rect.height - 1;
+    for col in rect.col + 1..right {
+        put(canvas, col, rect.row, lines.horizontal, Attr::Normal);
+        put(canvas, col, bottom, lines.horizontal, Attr::Normal);
+    }
+    for row in rect.row + 1..bottom {
+        put(canvas, rect.col, row, lines.vertical, Attr::Normal);
+        put(canvas, right, row, lines.vertical, Attr::Normal);
+    }
+    put(canvas, rect.col, rect.row, lines.top_left, Attr::Normal);
+    put(canvas, right, rect.row, lines.top_right, Attr::Normal);
+    put(canvas, rect.col, bottom, lines.bottom_left, Attr::Normal);
+    put(canvas, right, bottom, lines.bottom_right, Attr::Normal);
+    if let Some(title) = title {
+        // Leave a corner and a line at each end
+        let width = title.len().min(rect.width.saturating_sub(4));
+        text(canvas, rect.col + 2, rect.row, width, title, Attr::Normal);
+    }
+}
+
+/// What a widget did with a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Response {
+    /// Not for us - `Focus` may use it to move
+    Ignored,
+    /// Used it, but nothing the caller needs to know about
+    Handled,
+    /// The widget's value changed
+    Changed,
+    /// Enter (or Space) on a button, or a list item
+    Activated,
+    /// Escape, in a `Dialog`
+    Cancelled,
+}
+
+pub trait Widget {
+    fn draw(&self, canvas: &mut Canvas, focused: bool);
+
+    /// Can this have the focus?
+    fn focusable(&self) -> bool {
+        false
+    }
+
+    /// Handle a key, while this has the focus.
+    fn key(&mut self, _key: Key) -> Response {
+        Response::Ignored
+    }
+}
+
+/// A line of text.
+pub struct Label<'a> {
+    pub col: usize,
+    pub row: usize,
+    pub width: usize,
+    pub text: &'a str,
+}
+
+impl<'a> Widget for Label<'a> {
+    fn draw(&self, canvas: &mut Canvas, _focused: bool) {
+        text(canvas, self.col, self.row, self.width, self.text, Attr::Normal);
+    }
+}
+
+/// A bar that fills up from the left. With a `step`, it's a slider too:
+/// Left and Right move it, and leave a cell at each end for arrows.
+pub struct ProgressBar {
+    pub col: usize,
+    pub row: usize,
+    pub width: usize,
+    pub value: u32,
+    pub max: u32,
+    /// How far Left and Right move it, or 0 to leave it alone
+    pub step: u32,
+}
+
+impl ProgressBar {
+    /// A bar that only shows things.
+    pub fn new(col: usize, row: usize, width: usize, max: u32) -> ProgressBar {
+        ProgressBar {
+            col,
+            row,
+            width,
+            value: 0,
+            max,
+            step: 0,
+        }
+    }
+}
+
+impl Widget for ProgressBar {
+    fn draw(&self, canvas: &mut Canvas, focused: bool) {
+        let max = self.max.max(1);
+        let filled = (self.value.min(max) as usize * self.width) / max as usize;
+        for i in 0..self.width {
+            let ch = if i < filled { FULL_BLOCK } else { LIGHT_SHADE };
+            put(canvas, self.col + i, self.row, ch, Attr::Normal);
+        }
+        // Inverse video would turn the bar inside out, so a slider with
+        // the focus gets arrows at each end instead
+        if self.step != 0 {
+            let (left, right) = if focused { (b'<', b'>') } else { (b' ', b' ') };
+            if self.col > 0 {
+                put(canvas, self.col - 1, self.row, left, Attr::Normal);
+            }
+            put(canvas, self.col + self.width, self.row, right, Attr::Normal);
+        }
+    }
+
+    fn focusable(&self) -> bool {
+        self.step != 0
+    }
+
+    fn key(&mut self, key: Key) -> Response {
+        let value = match key {
+            Key::Left => self.value.saturating_sub(self.step),
+            Key::Right => (self.value + self.step).min(self.max),
+            Key::Home => 0,
+            Key::End => self.max,
+            _ => return Response::Ignored,
+        };
+        if value == self.value {
+            Response::Handled
+        } else {
+            self.value = value;
+            Response::Changed
+        }
+    }
+}
+
+/// Something to press.
+pub struct Button<'a> {
+    pub col: usize,
+    pub row: usize,
+    pub label: &'a str,
+}
+
+impl<'a> Widget for Button<'a> {
+    fn draw(&self, canvas: &mut Canvas, focused: bool) {
+        let attr = if focused { Attr::Highlight } else { Attr::Normal };
+        put(canvas, self.col, self.row, b'[', Attr::Normal);
+        text(canvas, self.col + 1, self.row, self.label.len(), self.label, attr);
+        put(canvas, self.col + 1 + self.label.len(), self.row, b']', Attr::Normal);
+    }
+
+    fn focusable(&self) -> bool {
+        true
+    }
+
+    fn key(&mut self, key: Key) -> Response {
+        match key {
+            Key::Enter | Key::Char(' ') => Response::Activated,
+            _ => Response::Ignored,
+        }
+    }
+}
+
+/// On or off.
+pub struct CheckBox<'a> {
+    pub col: usize,
+    pub row: usize,
+    pub label: &'a str,
+    pub checked: bool,
+}
+
+impl<'a> Widget for CheckBox<'a> {
+    fn draw(&self, canvas: &mut Canvas, focused: bool) {
+        let attr = if focused { Attr::Highlight } else { Attr::Normal };
+        let mark = if self.checked { b'X' } else { b' ' };
+        put(canvas, self.col, self.row, b'[', attr);
+        put(canvas, self.col + 1, self.row, mark, attr);
+        put(canvas, self.col + 2, self.row, b']', attr);
+        text(canvas, self.col + 4, self.row, self.label.len(), self.label, Attr::Normal);
+    }
+
+    fn focusable(&self) -> bool {
+        true
+    }
+
+    fn key(&mut self, key: Key) -> Response {
+        match key {
+            Key::Enter | Key::Char(' ') => {
+                self.checked = !self.checked;
+                Response::Changed
+            }
+            _ => Response::Ignored,
+        }
+    }
+}
+
+/// A framed list, scrolling if it has to, with one item selected.
+pub struct ListBox<'a> {
+    /// Including the frame
+    pub rect: Rect,
+    pub title: Option<&'a str>,
+    pub items: &'a [&'a str],
+    selected: usize,
+    /// The item at the top of the frame
+    top: usize,
+}
+
+impl<'a> ListBox<'a> {
+    pub fn new(rect: Rect, title: Option<&'a str>, items: &'a [&'a str]) -> ListBox<'a> {
+        ListBox {
+            rect,
+            title,
+            items,
+            selected: 0,
+            top: 0,
+        }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Select an item, scrolling to it.
+    pub fn select(&mut self, index: usize) {
+        if index >= self.items.len() {
+            return;
+        }
+        let rows = self.rect.inside().height.max(1);
+        self.selected = index;
+        if index < self.top {
+            self.top = index;
+        } else if index >= self.top + rows {
+            self.top = index + 1 - rows;
+        }
+    }
+}
+
+impl<'a> Widget for ListBox<'a> {
+    fn draw(&self, canvas: &mut Canvas, focused: bool) {
+        let lines = if focused {
+            &Lines::DOUBLE
+        } else {
+            &Lines::SINGLE
+        };
+        frame(canvas, self.rect, lines, self.title);
+        let inside = self.rect.inside();
+        for row in 0..inside.height {
+            let index = self.top + row;
+            let item = self.items.get(index).cloned().unwrap_or("");
+            let attr = if index == self.selected && !item.is_empty() {
+                Attr::Highlight
+            } else {
+                Attr::Normal
+            };
+            text(canvas, inside.col, inside.row + row, inside.width, item, attr);
+        }
+    }
+
+    fn focusable(&self) -> bool {
+        !self.items.is_empty()
+    }
+
+    fn key(&mut self, key: Key) -> Response {
+        let last = self.items.len().saturating_sub(1);
+        let page = self.rect.inside().height.max(1);
+        let index = match key {
+            Key::Enter => return Response::Activated,
+            Key::Up if self.selected > 0 => self.selected - 1,
+            Key::Down if self.selected < last => self.selected + 1,
+            Key::PageUp => self.selected.saturating_sub(page),
+            Key::PageDown => (self.selected + page).min(last),
+            Key::Home => 0,
+            Key::End => last,
+            // Off the ends, `Focus` can have them
+            _ => return Response::Ignored,
+        };
+        if index == self.selected {
+            Response::Handled
+        } else {
+            self.select(index);
+            Response::Changed
+        }
+    }
+}
+
+/// A box in the middle of the screen, with a message and some buttons.
+/// While it's up, give it all the keys; when it says `Activated`, the
+/// `selected` button was pressed.
+pub struct Dialog<'a> {
+    pub title: &'a str,
+    /// Split into lines at `\n`
+    pub message: &'a str,
+    pub buttons: &'a [&'a str],
+    selected: usize,
+}
+
+impl<'a> Dialog<'a> {
+    pub fn new(title: &'a str, message: &'a str, buttons: &'a [&'a str]) -> Dialog<'a> {
+        Dialog {
+            title,
+            message,
+            buttons,
+            selected: 0,
+        }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Where it goes on a canvas of `cols` by `rows` cells.
+    pub fn rect(&self, cols: usize, rows: usize) -> Rect {
+        let buttons: usize = self.buttons.iter().map(|b| b.len() + 3).sum();
+        let widest = self
+            .message
+            .lines()
+            .map(|l| l.len())
+            .chain(Some(buttons))
+            .chain(Some(self.title.len() + 2))
+            .max()
+            .unwrap_or(0);
+        let width = (widest + 4).min(cols);
+        // A frame, a gap round the message, and the buttons
+        let height = (self.message.lines().count() + 5).min(rows);
+        Rect {
+            col: (cols - width) / 2,
+            row: (rows - height) / 2,
+            width,
+            height,
+        }
+    }
+}
+
+impl<'a> Widget for Dialog<'a> {
+    fn draw(&self, canvas: &mut Canvas, _focused: bool) {
+        let cols = canvas.width() / font::WIDTH;
+        let rows = canvas.height() / font::HEIGHT;
+        let rect = self.rect(cols, rows);
+        clear(canvas, rect);
+        frame(canvas, rect, &Lines::DOUBLE, Some(self.title));
+        let inside = rect.inside();
+        for (i, line) in self.message.lines().enumerate() {
+            if 1 + i < inside.height {
+                let width = inside.width.saturating_sub(1);
+                text(canvas, inside.col + 1, inside.row + 1 + i, width, line, Attr::Normal);
+            }
+        }
+        let mut col = inside.col + 1;
+        let row = inside.row + inside.height - 1;
+        for (i, &label) in self.buttons.iter().enumerate() {
+            let button = Button { col, row, label };
+            button.draw(canvas, i == self.selected);
+            col += label.len() + 3;
+        }
+    }
+
+    fn focusable(&self) -> bool {
+        true
+    }
+
+    fn key(&mut self, key: Key) -> Response {
+        let count = self.buttons.len();
+        match key {
+            Key::Enter | Key::Char(' ') if count > 0 => Response::Activated,
+            Key::Escape => Response::Cancelled,
+            Key::Left | Key::Up if self.selected > 0 => {
+                self.selected -= 1;
+                Response::Handled
+            }
+            Key::Right | Key::Down | Key::Tab if self.selected + 1 < count => {
+                self.selected += 1;
+                Response::Handled
+            }
+            _ => Response::Handled,
+        }
+    }
+}
+
+/// Which of a screen's widgets has the focus.
+pub struct Focus {
+    index: usize,
+}
+
+impl Focus {
+    pub fn new() -> Focus {
+        Focus { index: 0 }
+    }
+
+    /// The widget with the focus, as an index into the widgets.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Give the focus to a widget, if it'll take it.
+    pub fn set(&mut self, widgets: &mut [&mut Widget], index: usize) {
+        if index < widgets.len() && widgets[index].focusable() {
+            self.index = index;
+        }
+    }
+
+    /// Draw all of the widgets.
+    pub fn draw(&mut self, canvas: &mut Canvas, widgets: &mut [&mut Widget]) {
+        self.settle(widgets);
+        for (i, widget) in widgets.iter().enumerate() {
+            widget.draw(canvas, i == self.index);
+        }
+    }
+
+    /// Give a key to the focused widget, or move the focus with it. Says
+    /// which widget had the key, and what it made of it.
+    pub fn key(&mut self, widgets: &mut [&mut Widget], key: Key) -> (usize, Response) {
+        self.settle(widgets);
+        let index = self.index;
+        if index >= widgets.len() {
+            return (index, Response::Ignored);
+        }
+        let response = widgets[index].key(key);
+        if response != Response::Ignored {
+            return (index, response);
+        }
+        match key {
+            Key::Tab | Key::Down => self.step(widgets, true),
+            Key::Up => self.step(widgets, false),
+            _ => return (index, Response::Ignored),
+        }
+        (index, Response::Handled)
+    }
+
+    /// Move to the next (or previous) widget that takes the focus,
+    /// wrapping round.
+    fn step(&mut self, widgets: &mut [&mut Widget], forwards: bool) {
+        let count = widgets.len();
+        for n in 1..count {
+            let i = if forwards {
+                (self.index + n) % count
+            } else {
+                (self.index + count - n) % count
+            };
+            if widgets[i].focusable() {
+                self.index = i;
+                return;
+            }
+        }
+    }
+
+    /// Make sure the focus is on something that can have it.
+    fn settle(&mut self, widgets: &mut [&mut Widget]) {
+        let ok = widgets
+            .get(self.index)
+            .map(|w| w.focusable())
+            .unwrap_or(false);
+        if !ok {
+            if let Some(i) = widgets.iter().position(|w| w.focusable()) {
+                self.index = i;
+            }
+        }
+    }
+}
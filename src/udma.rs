@@ -0,0 +1,225 @@
+//! Helpers for the micro Direct Memory Access (uDMA) controller.
+//!
+//! The uDMA controller reads its channel configuration from a control table
+//! in SRAM which must be aligned to 1024 bytes. There are 32 primary
+//! structures, followed by 32 alternate structures (used in ping-pong mode).
+//!
+//! See the `ti_dma_example.c` example for the TivaWare equivalent.
+
+use pac;
+
+/// One entry in the channel control table.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct ChannelControl {
+    src_end: u32,
+    dst_end: u32,
+    control: u32,
+    _reserved: u32,
+}
+
+/// The channel control table. The hardware demands 1 KiB alignment.
+#[repr(C, align(1024))]
+pub struct ControlTable {
+    channels: [ChannelControl; 64],
+}
+
+static mut CONTROL_TABLE: ControlTable = ControlTable {
+    channels: [ChannelControl {
+        src_end: 0,
+        dst_end: 0,
+        control: 0,
+        _reserved: 0,
+    }; 64],
+};
+
+/// Which of the two control structures for a channel to use.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Select {
+    Primary,
+    Alternate,
+}
+
+/// Size of each item transferred.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Size {
+    Byte = 0,
+    HalfWord = 1,
+    Word = 2,
+}
+
+/// How far to move the address after each item.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Increment {
+    Byte = 0,
+    HalfWord = 1,
+    Word = 2,
+    None = 3,
+}
+
+/// How many items to move before re-arbitrating.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Arbitrate {
+    _1 = 0,
+    _2 = 1,
+    _4 = 2,
+    _8 = 3,
+    _16 = 4,
+    _32 = 5,
+    _64 = 6,
+    _128 = 7,
+    _256 = 8,
+    _512 = 9,
+    _1024 = 10,
+}
+
+/// The transfer mode.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Stop = 0,
+    Basic = 1,
+    Auto = 2,
+    PingPong = 3,
+}
+
+/// The most items a single transfer can move.
+pub const MAX_TRANSFER: usize = 1024;
+
+/// Everything needed to build a channel control word.
+#[derive(Clone, Copy)]
+pub struct Transfer {
+    pub size: Size,
+    pub src_inc: Increment,
+    pub dst_inc: Increment,
+    pub arbitrate: Arbitrate,
+    pub mode: Mode,
+}
+
+impl Transfer {
+    fn control_word(&self, count: usize) -> u32 {
+        assert!(count > 0 && count <= MAX_TRANSFER);
+        ((self.dst_inc as u32) << 30) | ((self.size as u32) << 28)
+            | ((self.src_inc as u32) << 26) | ((self.size as u32) << 24)
+            | ((self.arbitrate as u32) << 14) | (((count - 1) as u32) << 4)
+            | (self.mode as u32)
+    }
+}
+
+/// Switch on the controller and point it at our control table. The caller
+/// must have already powered up the `MicroDma` domain.
+pub fn init() {
+    let udma = unsafe { &*pac::UDMA::ptr() };
+    udma.cfg.write(|w| w.masten().set_bit());
+    // The ADDR field starts at bit 10, so its accessor wants the address
+    // shifted down. Writing the whole register does the same, as the
+    // table's alignment keeps the bottom ten bits zero.
+    let addr = unsafe { &CONTROL_TABLE as *const ControlTable as u32 };
+    udma.ctlbase.write(|w| unsafe { w.bits(addr) });
+}
+
+/// Connect `channel` to one of its (up to five) peripheral sources. See
+/// table 9-1 in the datasheet for the encodings.
+pub fn assign(channel: u8, encoding: u8) {
+    let udma = unsafe { &*pac::UDMA::ptr() };
+    let shift = u32::from(channel % 8) * 4;
+    let mask = !(0xF << shift);
+    let value = u32::from(encoding) << shift;
+    match channel / 8 {
+        0 => udma.chmap0.modify(|r, w| unsafe { w.bits((r.bits() & mask) | value) }),
+        1 => udma.chmap1.modify(|r, w| unsafe { w.bits((r.bits() & mask) | value) }),
+        2 => udma.chmap2.modify(|r, w| unsafe { w.bits((r.bits() & mask) | value) }),
+        _ => udma.chmap3.modify(|r, w| unsafe { w.bits((r.bits() & mask) | value) }),
+    }
+    // Clear down any leftover attributes
+    udma.altclr.write(|w| unsafe { w.bits(1 << channel) });
+    udma.prioclr.write(|w| unsafe { w.bits(1 << channel) });
+    udma.reqmaskclr.write(|w| unsafe { w.bits(1 << channel) });
+    udma.useburstclr.write(|w| unsafe { w.bits(1 << channel) });
+}
+
+/// Only respond to burst requests from the peripheral on this channel.
+pub fn use_burst(channel: u8) {
+    let udma = unsafe { &*pac::UDMA::ptr() };
+    udma.useburstset.write(|w| unsafe { w.bits(1 << channel) });
+}
+
+/// Set up a transfer of `count` items from `src` to `dst`. Each pointer
+/// is the start of its buffer (or the register, if it doesn't increment).
+pub fn configure(
+    channel: u8,
+    select: Select,
+    transfer: &Transfer,
+    src: *const u8,
+    dst: *mut u8,
+    count: usize,
+) {
+    let index = match select {
+        Select::Primary => channel as usize,
+        Select::Alternate => channel as usize + 32,
+    };
+    let end = |start: u32, inc: Increment| match inc {
+        Increment::None => start,
+        _ => start + (((count - 1) as u32) << (inc as u32)),
+    };
+    unsafe {
+        let entry = &mut CONTROL_TABLE.channels[index];
+        entry.src_end = end(src as u32, transfer.src_inc);
+        entry.dst_end = end(dst as u32, transfer.dst_inc);
+        entry.control = transfer.control_word(count);
+    }
+}
+
+/// Returns the mode currently in the control word. The hardware sets this
+/// back to `Stop` when it has finished with a structure.
+pub fn mode(channel: u8, select: Select) -> Mode {
+    let index = match select {
+        Select::Primary => channel as usize,
+        Select::Alternate => channel as usize + 32,
+    };
+    let control = unsafe {
+        ::core::ptr::read_volatile(&CONTROL_TABLE.channels[index].control)
+    };
+    match control & 0x7 {
+        1 => Mode::Basic,
+        2 => Mode::Auto,
+        3 => Mode::PingPong,
+        _ => Mode::Stop,
+    }
+}
+
+/// Enable a channel. The hardware disables it again when the transfer is
+/// complete.
+pub fn enable(channel: u8) {
+    let udma = unsafe { &*pac::UDMA::ptr() };
+    udma.enaset.write(|w| unsafe { w.bits(1 << channel) });
+}
+
+/// Disable a channel, abandoning any transfer in progress.
+pub fn disable(channel: u8) {
+    let udma = unsafe { &*pac::UDMA::ptr() };
+    udma.enaclr.write(|w| unsafe { w.bits(1 << channel) });
+}
+
+/// Is the channel still busy?
+pub fn is_enabled(channel: u8) -> bool {
+    let udma = unsafe { &*pac::UDMA::ptr() };
+    (udma.enaset.read().bits() & (1 << channel)) != 0
+}
+
+/// Kick off a transfer from software (for memory-to-memory transfers).
+pub fn request(channel: u8) {
+    let udma = unsafe { &*pac::UDMA::ptr() };
+    udma.swreq.write(|w| unsafe { w.bits(1 << channel) });
+}
+
+/// Has the channel finished a transfer since we last asked? Peripheral
+/// channels report this on the peripheral's own interrupt, so its handler
+/// should call this to tell the two apart. Clears the flag.
+pub fn take_interrupt(channel: u8) -> bool {
+    let udma = unsafe { &*pac::UDMA::ptr() };
+    let pending = (udma.chis.read().bits() & (1 << channel)) != 0;
+    if pending {
+        udma.chis.write(|w| unsafe { w.bits(1 << channel) });
+    }
+    pending
+}
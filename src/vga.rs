@@ -0,0 +1,168 @@
+//! The glue between the VGA framebuffer crate and the TM4C123 hardware, for
+//! examples that want a screen as well as doing something else.
+//!
+//! By default this is the same arrangement as the `hello_vga` example:
+//! HSYNC is PB6 (T0CCP0), VSYNC is PC4 and the green pixel data is PB7
+//! (SSI2Tx). See `demo::config` to move them. `init` sets the pins up; the
+//! caller must power up Timer0 and SSI2, enable the TIMER0A and TIMER0B
+//! interrupts and hook up the handlers:
+//!
+//! ``` ignore
+//! interrupt!(TIMER0A, vga::timer0a_isr);
+//! interrupt!(TIMER0B, vga::timer0b_isr);
+//! ```
+//!
+//! Nothing in `vga-framebuffer` is TI-specific - it only needs the four
+//! methods of `fb::Hardware`, and two interrupts a line. `demo::vga_stm32f4`
+//! does the same on an STM32F4 Discovery, with a timer and SPI fed by DMA.
+
+use config;
+use cortex_m::asm;
+use fb;
+use pac::{self, SSI2, TIMER0};
+
+pub struct Hardware {
+    h_timer: Option<TIMER0>,
+}
+
+static mut HARDWARE: Hardware = Hardware { h_timer: None };
+
+static mut FRAMEBUFFER: fb::FrameBuffer<&'static mut Hardware> = fb::FrameBuffer::new();
+
+/// Set up the pins, and SSI2 to send pixels at 20 MHz, then start the
+/// framebuffer running. Give the monitor a few seconds to sync before drawing anything
+/// important.
+pub fn init(timer: TIMER0, ssi: SSI2) {
+    // T0CCP0
+    config::VGA_HSYNC.into_af(7);
+    // GPIO controlled V-Sync
+    config::VGA_VSYNC.into_output();
+    // Ssi2Tx
+    config::VGA_PIXELS.into_af(2);
+
+    ssi.cr1.modify(|_, w| w.sse().clear_bit());
+    // 20 MHz = 80 MHz / (4 * (1 + 0))
+    ssi.cpsr.write(|w| unsafe { w.cpsdvsr().bits(4) });
+    ssi.cr0.write(|w| {
+        w.dss()._16();
+        w.frf().moto();
+        w.spo().clear_bit();
+        w.sph().set_bit();
+        w
+    });
+    ssi.cc.modify(|_, w| w.cs().syspll());
+    ssi.cr1.modify(|_, w| w.sse().set_bit());
+
+    unsafe {
+        HARDWARE.h_timer = Some(timer);
+        FRAMEBUFFER.init(&mut HARDWARE);
+    }
+}
+
+/// Get the framebuffer. There is only one, so don't hang on to this while
+/// calling something else that gets it too.
+pub fn framebuffer() -> &'static mut fb::FrameBuffer<&'static mut Hardware> {
+    unsafe { &mut FRAMEBUFFER }
+}
+
+impl fb::Hardware for &'static mut Hardware {
+    fn configure(&mut self, width: u32, sync_end: u32, line_start: u32, _clock_rate: u32) {
+        if let Some(ref h_timer) = self.h_timer {
+            // Configure Timer0A for h-sync and Timer0B for line trigger
+            h_timer.ctl.modify(|_, w| {
+                w.taen().clear_bit();
+                w.tben().clear_bit();
+                w
+            });
+            h_timer.cfg.modify(|_, w| w.cfg()._16_bit());
+            h_timer.tamr.modify(|_, w| {
+                w.taams().set_bit();
+                w.tacmr().clear_bit();
+                w.tapwmie().set_bit();
+                w.tamr().period();
+                w
+            });
+            h_timer.tbmr.modify(|_, w| {
+                w.tbams().set_bit();
+                w.tbcmr().clear_bit();
+                w.tbmr().period();
+                w.tbpwmie().set_bit();
+                w
+            });
+            h_timer.ctl.modify(|_, w| {
+                // Trigger Timer A capture on rising edge (i.e. line start)
+                w.tapwml().clear_bit();
+                // Trigger Timer B capture on falling edge (i.e. data start)
+                w.tbpwml().set_bit();
+                w
+            });
+            // We're counting down in PWM mode, so start at the end. The
+            // timings are for 40 MHz, and we're at 80 MHz.
+            h_timer
+                .tailr
+                .modify(|_, w| unsafe { w.bits(width * 2 - 1) });
+            h_timer
+                .tbilr
+                .modify(|_, w| unsafe { w.bits(width * 2 - 1) });
+            h_timer
+                .tamatchr
+                .modify(|_, w| unsafe { w.bits(2 * (width - sync_end) - 1) });
+            h_timer
+                .tbmatchr
+                .modify(|_, w| unsafe { w.bits(2 * (width - line_start) - 1) });
+            h_timer.imr.modify(|_, w| {
+                w.caeim().set_bit(); // Timer0A fires at start of line
+                w.cbeim().set_bit(); // Timer0B fires at start of data
+                w
+            });
+
+            // Clear interrupts
+            h_timer.icr.write(|w| {
+                w.tbmcint().set_bit();
+                w.tbtocint().set_bit();
+                w
+            });
+
+            h_timer.ctl.modify(|_, w| {
+                w.taen().set_bit();
+                w.tben().set_bit();
+                w
+            });
+        }
+    }
+
+    /// Called when V-Sync needs to be high.
+    fn vsync_on(&mut self) {
+        config::VGA_VSYNC.set(true);
+    }
+
+    /// Called when V-Sync needs to be low.
+    fn vsync_off(&mut self) {
+        config::VGA_VSYNC.set(false);
+    }
+
+    /// Called when pixels need to be written to the output pin.
+    fn write_pixels(&mut self, pixels: &fb::VideoLine) {
+        let ssi = unsafe { &*pac::SSI2::ptr() };
+        for word in &pixels.words {
+            ssi.dr.write(|w| unsafe { w.data().bits(*word) });
+            while ssi.sr.read().tnf().bit_is_clear() {
+                asm::nop();
+            }
+        }
+    }
+}
+
+/// Start of line. The TIMER0A handler.
+pub fn timer0a_isr() {
+    let timer = unsafe { &*pac::TIMER0::ptr() };
+    unsafe { FRAMEBUFFER.isr_sol() };
+    timer.icr.write(|w| w.caecint().set_bit());
+}
+
+/// Start of pixel data. The TIMER0B handler.
+pub fn timer0b_isr() {
+    let timer = unsafe { &*pac::TIMER0::ptr() };
+    unsafe { FRAMEBUFFER.isr_data() };
+    timer.icr.write(|w| w.cbecint().set_bit());
+}
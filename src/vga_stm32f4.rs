@@ -0,0 +1,244 @@
+//! The glue between the VGA framebuffer crate and an STM32F4 Discovery.
+//!
+//! It's the same 800 x 600 picture as `demo::vga`, on different pins:
+//! HSYNC is PB4 (TIM3_CH1), VSYNC is PB5 and the green pixel data is PB15
+//! (SPI2_MOSI), wired to the monitor the same way. All three are free on
+//! the Discovery's headers.
+//!
+//! TIM3 does what Timer0 does on the LaunchPad. Channel 1 is PWM for
+//! HSYNC, the update event is the start of a line, and a compare on
+//! channel 2 is the start of the pixels. Rather than feed SPI2 a word at a
+//! time from the interrupt, each line goes to it with DMA (DMA1 stream 4,
+//! channel 0), so the CPU is free while the pixels go out. The line is
+//! only drawn again a line later, by when the transfer has finished.
+//!
+//! Call `set_up_clock` first - the timings want a 160 MHz system clock -
+//! then `init`. Both timer events share one interrupt, so enable TIM3 and
+//! hook up the handler:
+//!
+//! ``` ignore
+//! interrupt!(TIM3, vga_stm32f4::tim3_isr);
+//! ```
+
+use fb;
+use pac::{DMA1, FLASH, GPIOB, RCC, SPI2, TIM3};
+
+/// The system clock `set_up_clock` gives us. The timers on APB1 run at
+/// half of it.
+pub const SYSCLK_HZ: u32 = 160_000_000;
+
+/// The Discovery's crystal.
+const HSE_HZ: u32 = 8_000_000;
+
+const RCC_CR_HSEON: u32 = 1 << 16;
+const RCC_CR_HSERDY: u32 = 1 << 17;
+const RCC_CR_PLLON: u32 = 1 << 24;
+const RCC_CR_PLLRDY: u32 = 1 << 25;
+
+/// 8 MHz / 8 x 320 / 2 = 160 MHz, from the crystal. PLLQ only matters for
+/// USB, which we don't use.
+const RCC_PLLCFGR: u32 = (7 << 24) | (1 << 22) | (320 << 6) | (HSE_HZ / 1_000_000);
+
+/// SYSCLK from the PLL, AHB at 160 MHz, APB1 at 40 MHz and APB2 at 80 MHz.
+const RCC_CFGR: u32 = (0b100 << 13) | (0b101 << 10) | 0b10;
+const RCC_CFGR_SWS_MASK: u32 = 0b11 << 2;
+const RCC_CFGR_SWS_PLL: u32 = 0b10 << 2;
+
+const RCC_AHB1ENR_GPIOBEN: u32 = 1 << 1;
+const RCC_AHB1ENR_DMA1EN: u32 = 1 << 21;
+const RCC_APB1ENR_TIM3EN: u32 = 1 << 1;
+const RCC_APB1ENR_SPI2EN: u32 = 1 << 14;
+
+/// Five wait states for 160 MHz at 3.3V, with the caches and prefetch on.
+const FLASH_ACR: u32 = (1 << 10) | (1 << 9) | (1 << 8) | 5;
+
+const HSYNC_PIN: u32 = 4;
+const VSYNC_PIN: u32 = 5;
+const PIXELS_PIN: u32 = 15;
+
+const TIM_CR1_CEN: u32 = 1 << 0;
+const TIM_CCMR1_OC1M_PWM1: u32 = 0b110 << 4;
+const TIM_CCMR1_OC1PE: u32 = 1 << 3;
+const TIM_CCER_CC1E: u32 = 1 << 0;
+const TIM_EGR_UG: u32 = 1 << 0;
+/// The update event (start of line) and channel 2 (start of data), which
+/// are the same bits in DIER and SR
+const TIM_UPDATE: u32 = 1 << 0;
+const TIM_CC2: u32 = 1 << 2;
+
+const SPI_CR1_CPHA: u32 = 1 << 0;
+const SPI_CR1_MSTR: u32 = 1 << 2;
+/// 40 MHz / 2 = 20 MHz, the same as SSI2 on the LaunchPad
+const SPI_CR1_BR_DIV2: u32 = 0b000 << 3;
+const SPI_CR1_SPE: u32 = 1 << 6;
+const SPI_CR1_SSI: u32 = 1 << 8;
+const SPI_CR1_SSM: u32 = 1 << 9;
+const SPI_CR1_DFF_16: u32 = 1 << 11;
+const SPI_CR2_TXDMAEN: u32 = 1 << 1;
+
+const DMA_SCR_EN: u32 = 1 << 0;
+/// Channel 0 (SPI2_TX), memory to peripheral in half-words, stepping
+/// through memory, at very high priority.
+const DMA_SCR: u32 = (0b11 << 16) | (0b01 << 13) | (0b01 << 11) | (1 << 10) | (0b01 << 6);
+/// All of stream 4's flags in HIFCR
+const DMA_HIFCR_STREAM4: u32 = 0b11_1101;
+
+pub struct Hardware {
+    h_timer: Option<TIM3>,
+}
+
+static mut HARDWARE: Hardware = Hardware { h_timer: None };
+
+static mut FRAMEBUFFER: fb::FrameBuffer<&'static mut Hardware> = fb::FrameBuffer::new();
+
+/// Run from the crystal through the PLL, at `SYSCLK_HZ`. The chip starts
+/// on its 16 MHz internal oscillator.
+pub fn set_up_clock(rcc: &RCC, flash: &FLASH) {
+    rcc.cr
+        .modify(|r, w| unsafe { w.bits(r.bits() | RCC_CR_HSEON) });
+    while rcc.cr.read().bits() & RCC_CR_HSERDY == 0 {}
+
+    rcc.pllcfgr.write(|w| unsafe { w.bits(RCC_PLLCFGR) });
+    rcc.cr
+        .modify(|r, w| unsafe { w.bits(r.bits() | RCC_CR_PLLON) });
+    while rcc.cr.read().bits() & RCC_CR_PLLRDY == 0 {}
+
+    // The flash must be slowed down before the clock speeds up
+    flash.acr.write(|w| unsafe { w.bits(FLASH_ACR) });
+    rcc.cfgr.write(|w| unsafe { w.bits(RCC_CFGR) });
+    while rcc.cfgr.read().bits() & RCC_CFGR_SWS_MASK != RCC_CFGR_SWS_PLL {}
+}
+
+/// Power up and set up the pins, TIM3, SPI2 and the DMA, then start the
+/// framebuffer running. Give the monitor a few seconds to sync before
+/// drawing anything important.
+pub fn init(rcc: &RCC, gpio: GPIOB, timer: TIM3, spi: SPI2, dma: DMA1) {
+    rcc.ahb1enr.modify(|r, w| unsafe {
+        w.bits(r.bits() | RCC_AHB1ENR_GPIOBEN | RCC_AHB1ENR_DMA1EN)
+    });
+    rcc.apb1enr.modify(|r, w| unsafe {
+        w.bits(r.bits() | RCC_APB1ENR_TIM3EN | RCC_APB1ENR_SPI2EN)
+    });
+
+    // HSYNC is AF2 and the pixels AF5. VSYNC is a plain output.
+    gpio.afrl.modify(|r, w| unsafe {
+        w.bits((r.bits() & !(0xF << (HSYNC_PIN * 4))) | (2 << (HSYNC_PIN * 4)))
+    });
+    gpio.afrh.modify(|r, w| unsafe {
+        let shift = (PIXELS_PIN - 8) * 4;
+        w.bits((r.bits() & !(0xF << shift)) | (5 << shift))
+    });
+    gpio.moder.modify(|r, w| unsafe {
+        let mask = (0b11 << (HSYNC_PIN * 2)) | (0b11 << (VSYNC_PIN * 2))
+            | (0b11 << (PIXELS_PIN * 2));
+        let mode = (0b10 << (HSYNC_PIN * 2)) | (0b01 << (VSYNC_PIN * 2))
+            | (0b10 << (PIXELS_PIN * 2));
+        w.bits((r.bits() & !mask) | mode)
+    });
+    // Very high speed, so the edges stay sharp
+    gpio.ospeedr.modify(|r, w| unsafe {
+        w.bits(
+            r.bits() | (0b11 << (HSYNC_PIN * 2)) | (0b11 << (VSYNC_PIN * 2))
+                | (0b11 << (PIXELS_PIN * 2)),
+        )
+    });
+
+    // Transmit only, so the slave select is software and always high
+    spi.cr1.write(|w| unsafe {
+        w.bits(
+            SPI_CR1_CPHA | SPI_CR1_MSTR | SPI_CR1_BR_DIV2 | SPI_CR1_SSI | SPI_CR1_SSM
+                | SPI_CR1_DFF_16,
+        )
+    });
+    spi.cr2.write(|w| unsafe { w.bits(SPI_CR2_TXDMAEN) });
+    spi.cr1
+        .modify(|r, w| unsafe { w.bits(r.bits() | SPI_CR1_SPE) });
+
+    // The stream is set up properly for each line
+    dma.s4cr.write(|w| unsafe { w.bits(0) });
+    dma.s4par
+        .write(|w| unsafe { w.bits(&spi.dr as *const _ as u32) });
+
+    unsafe {
+        HARDWARE.h_timer = Some(timer);
+        FRAMEBUFFER.init(&mut HARDWARE);
+    }
+}
+
+/// Get the framebuffer. There is only one, so don't hang on to this while
+/// calling something else that gets it too.
+pub fn framebuffer() -> &'static mut fb::FrameBuffer<&'static mut Hardware> {
+    unsafe { &mut FRAMEBUFFER }
+}
+
+impl fb::Hardware for &'static mut Hardware {
+    fn configure(&mut self, width: u32, sync_end: u32, line_start: u32, _clock_rate: u32) {
+        if let Some(ref h_timer) = self.h_timer {
+            h_timer.cr1.write(|w| unsafe { w.bits(0) });
+            // Counting up from zero, at 80 MHz. The timings are for 40 MHz.
+            h_timer.psc.write(|w| unsafe { w.bits(0) });
+            h_timer.arr.write(|w| unsafe { w.bits(width * 2 - 1) });
+            // HSYNC is high from the start of the line until the sync ends
+            h_timer
+                .ccr1
+                .write(|w| unsafe { w.bits(sync_end * 2) });
+            h_timer
+                .ccr2
+                .write(|w| unsafe { w.bits(line_start * 2) });
+            h_timer
+                .ccmr1_output
+                .write(|w| unsafe { w.bits(TIM_CCMR1_OC1M_PWM1 | TIM_CCMR1_OC1PE) });
+            h_timer.ccer.write(|w| unsafe { w.bits(TIM_CCER_CC1E) });
+            // Load the registers, then clear the update that caused
+            h_timer.egr.write(|w| unsafe { w.bits(TIM_EGR_UG) });
+            h_timer.sr.write(|w| unsafe { w.bits(0) });
+            h_timer
+                .dier
+                .write(|w| unsafe { w.bits(TIM_UPDATE | TIM_CC2) });
+            h_timer.cr1.write(|w| unsafe { w.bits(TIM_CR1_CEN) });
+        }
+    }
+
+    /// Called when V-Sync needs to be high.
+    fn vsync_on(&mut self) {
+        let gpio = unsafe { &*GPIOB::ptr() };
+        gpio.bsrr.write(|w| unsafe { w.bits(1 << VSYNC_PIN) });
+    }
+
+    /// Called when V-Sync needs to be low.
+    fn vsync_off(&mut self) {
+        let gpio = unsafe { &*GPIOB::ptr() };
+        gpio.bsrr.write(|w| unsafe { w.bits(1 << (VSYNC_PIN + 16)) });
+    }
+
+    /// Called when pixels need to be written to the output pin. The DMA
+    /// carries on sending them after we return.
+    fn write_pixels(&mut self, pixels: &fb::VideoLine) {
+        let dma = unsafe { &*DMA1::ptr() };
+        // The last line finished long ago, but the stream has to be off
+        // before it can be changed
+        dma.s4cr.write(|w| unsafe { w.bits(DMA_SCR) });
+        while dma.s4cr.read().bits() & DMA_SCR_EN != 0 {}
+        dma.hifcr.write(|w| unsafe { w.bits(DMA_HIFCR_STREAM4) });
+        dma.s4m0ar
+            .write(|w| unsafe { w.bits(pixels.words.as_ptr() as u32) });
+        dma.s4ndtr
+            .write(|w| unsafe { w.bits(pixels.words.len() as u32) });
+        dma.s4cr
+            .write(|w| unsafe { w.bits(DMA_SCR | DMA_SCR_EN) });
+    }
+}
+
+/// Start of line and start of pixel data. The TIM3 handler.
+pub fn tim3_isr() {
+    let timer = unsafe { &*TIM3::ptr() };
+    let flags = timer.sr.read().bits();
+    // The flags are cleared by writing zero
+    timer.sr.write(|w| unsafe { w.bits(!flags) });
+    if flags & TIM_UPDATE != 0 {
+        unsafe { FRAMEBUFFER.isr_sol() };
+    }
+    if flags & TIM_CC2 != 0 {
+        unsafe { FRAMEBUFFER.isr_data() };
+    }
+}
@@ -0,0 +1,416 @@
+//! Enough of a VT100 to be a dumb terminal, drawn on any `Canvas`.
+//!
+//! Feed it bytes with `input` and it keeps a grid of characters, moving
+//! the cursor and scrolling as it goes. It understands:
+//!
+//! * CR, LF (and VT and FF, as LF), BS, TAB and BEL (`audio::bell`)
+//! * `ESC 7` / `ESC 8` (save and restore the cursor), `ESC D` (index),
+//!   `ESC E` (next line), `ESC M` (reverse index) and `ESC c` (reset)
+//! * `CSI n A/B/C/D` (cursor up/down/forward/back), `CSI r;c H` or `f`
+//!   (cursor position), `CSI n J` (erase in display), `CSI n K` (erase in
+//!   line), `CSI s` / `CSI u` (save and restore) and `CSI ... m`, of which
+//!   only 0 (normal), 7 (inverse) and 27 (not inverse) do anything
+//!
+//! Anything else is swallowed, so programs that use colours or scrolling
+//! regions come out messy but readable. The cursor is the cell under it in
+//! inverse video.
+//!
+//! The grid is `MAX_COLS` by `MAX_ROWS`, which is all of the VGA screen.
+//! Only cells that change get drawn, but a scroll still changes most of the
+//! screen and takes a few milliseconds - at high baud rates, the sender
+//! needs flow control or the UART's FIFO will overflow.
+
+use audio;
+use font;
+use graphics::{Canvas, Colour};
+
+/// The most columns we'll use.
+pub const MAX_COLS: usize = 50;
+/// The most rows we'll use.
+pub const MAX_ROWS: usize = 18;
+
+/// The most parameters a control sequence can have. Any more go in the
+/// last one.
+const MAX_PARAMS: usize = 4;
+
+/// One character on the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cell {
+    ch: u8,
+    inverse: bool,
+}
+
+const BLANK: Cell = Cell {
+    ch: b' ',
+    inverse: false,
+};
+
+/// Where we are in an escape sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Plain text
+    Ground,
+    /// Just had an ESC
+    Escape,
+    /// In an `ESC [` (CSI) sequence
+    Csi,
+}
+
+pub struct Terminal {
+    cells: [[Cell; MAX_COLS]; MAX_ROWS],
+    cols: usize,
+    rows: usize,
+    row: usize,
+    col: usize,
+    /// We've written in the last column, and wrap on the next character
+    wrap_pending: bool,
+    inverse: bool,
+    saved: (usize, usize),
+    state: State,
+    params: [u16; MAX_PARAMS],
+    param_count: usize,
+}
+
+impl Terminal {
+    /// Clear the canvas, and fill as much of it as we can.
+    pub fn new<C>(canvas: &mut C) -> Terminal
+    where
+        C: Canvas,
+    {
+        let mut terminal = Terminal {
+            cells: [[BLANK; MAX_COLS]; MAX_ROWS],
+            cols: (canvas.width() / font::WIDTH).min(MAX_COLS),
+            rows: (canvas.height() / font::HEIGHT).min(MAX_ROWS),
+            row: 0,
+            col: 0,
+            wrap_pending: false,
+            inverse: false,
+            saved: (0, 0),
+            state: State::Ground,
+            params: [0; MAX_PARAMS],
+            param_count: 0,
+        };
+        terminal.reset(canvas);
+        terminal
+    }
+
+    /// The size of the screen, in characters, as (columns, rows).
+    pub fn size(&self) -> (usize, usize) {
+        (self.cols, self.rows)
+    }
+
+    /// Clear the screen and put everything back as it was at the start.
+    pub fn reset<C>(&mut self, canvas: &mut C)
+    where
+        C: Canvas,
+    {
+        self.cells = [[BLANK; MAX_COLS]; MAX_ROWS];
+        self.row = 0;
+        self.col = 0;
+        self.wrap_pending = false;
+        self.inverse = false;
+        self.saved = (0, 0);
+        self.state = State::Ground;
+        canvas.clear(Colour::BLACK);
+        self.draw_cursor(canvas, true);
+    }
+
+    /// Handle one byte from the host.
+    pub fn input<C>(&mut self, canvas: &mut C, byte: u8)
+    where
+        C: Canvas,
+    {
+        self.draw_cursor(canvas, false);
+        match self.state {
+            State::Ground => self.ground(canvas, byte),
+            State::Escape => self.escape(canvas, byte),
+            State::Csi => self.csi(canvas, byte),
+        }
+        self.draw_cursor(canvas, true);
+    }
+
+    fn ground<C>(&mut self, canvas: &mut C, byte: u8)
+    where
+        C: Canvas,
+    {
+        match byte {
+            0x1B => self.state = State::Escape,
+            b'\r' => {
+                let row = self.row;
+                self.move_to(row, 0);
+            }
+            b'\n' | 0x0B | 0x0C => self.line_feed(canvas),
+            0x07 => audio::bell(),
+            0x08 => {
+                let (row, col) = (self.row, self.col);
+                self.move_to(row, col.saturating_sub(1));
+            }
+            b'\t' => {
+                let (row, col) = (self.row, self.col);
+                self.move_to(row, (col / 8 + 1) * 8);
+            }
+            0x00...0x1F | 0x7F => {}
+            _ => self.put(canvas, byte),
+        }
+    }
+
+    fn escape<C>(&mut self, canvas: &mut C, byte: u8)
+    where
+        C: Canvas,
+    {
+        self.state = State::Ground;
+        match byte {
+            b'[' => {
+                self.state = State::Csi;
+                self.params = [0; MAX_PARAMS];
+                self.param_count = 0;
+            }
+            b'7' => self.saved = (self.row, self.col),
+            b'8' => {
+                let (row, col) = self.saved;
+                self.move_to(row, col);
+            }
+            b'D' => self.line_feed(canvas),
+            b'E' => {
+                let row = self.row;
+                self.move_to(row, 0);
+                self.line_feed(canvas);
+            }
+            b'M' => self.reverse_index(canvas),
+            b'c' => self.reset(canvas),
+            _ => {}
+        }
+    }
+
+    fn csi<C>(&mut self, canvas: &mut C, byte: u8)
+    where
+        C: Canvas,
+    {
+        match byte {
+            b'0'...b'9' => {
+                if self.param_count == 0 {
+                    self.param_count = 1;
+                }
+                let i = self.param_count - 1;
+                let digit = u16::from(byte - b'0');
+                self.params[i] = self.params[i].saturating_mul(10).saturating_add(digit);
+            }
+            b';' => {
+                if self.param_count == 0 {
+                    self.param_count = 1;
+                }
+                if self.param_count < MAX_PARAMS {
+                    self.param_count += 1;
+                }
+            }
+            // The final byte
+            0x40...0x7E => {
+                self.state = State::Ground;
+                self.dispatch(canvas, byte);
+            }
+            // Private markers and intermediates - we don't do any of those
+            _ => {}
+        }
+    }
+
+    /// Act on a complete control sequence.
+    fn dispatch<C>(&mut self, canvas: &mut C, byte: u8)
+    where
+        C: Canvas,
+    {
+        let (row, col) = (self.row, self.col);
+        let (cols, rows) = (self.cols, self.rows);
+        let n = self.param(0, 1);
+        match byte {
+            b'A' => self.move_to(row.saturating_sub(n), col),
+            b'B' => self.move_to(row + n, col),
+            b'C' => self.move_to(row, col + n),
+            b'D' => self.move_to(row, col.saturating_sub(n)),
+            b'H' | b'f' => {
+                let new_row = self.param(0, 1) - 1;
+                let new_col = self.param(1, 1) - 1;
+                self.move_to(new_row, new_col);
+            }
+            b'J' => match self.param(0, 0) {
+                0 => {
+                    self.erase(canvas, row, col, cols);
+                    for r in row + 1..rows {
+                        self.erase(canvas, r, 0, cols);
+                    }
+                }
+                1 => {
+                    for r in 0..row {
+                        self.erase(canvas, r, 0, cols);
+                    }
+                    self.erase(canvas, row, 0, col + 1);
+                }
+                2 => {
+                    for r in 0..rows {
+                        self.erase(canvas, r, 0, cols);
+                    }
+                }
+                _ => {}
+            },
+            b'K' => match self.param(0, 0) {
+                0 => self.erase(canvas, row, col, cols),
+                1 => self.erase(canvas, row, 0, col + 1),
+                2 => self.erase(canvas, row, 0, cols),
+                _ => {}
+            },
+            b'm' => {
+                if self.param_count == 0 {
+                    self.inverse = false;
+                }
+                for i in 0..self.param_count {
+                    match self.params[i] {
+                        0 | 27 => self.inverse = false,
+                        7 => self.inverse = true,
+                        _ => {}
+                    }
+                }
+            }
+            b's' => self.saved = (row, col),
+            b'u' => {
+                let (saved_row, saved_col) = self.saved;
+                self.move_to(saved_row, saved_col);
+            }
+            _ => {}
+        }
+    }
+
+    /// Parameter `i` of the current control sequence, or `default` if it's
+    /// missing or zero. Missing ones are zero, as they're cleared at the
+    /// start of each sequence.
+    fn param(&self, i: usize, default: usize) -> usize {
+        match self.params[i] {
+            0 => default,
+            p => usize::from(p),
+        }
+    }
+
+    /// Draw a character at the cursor, and move on.
+    fn put<C>(&mut self, canvas: &mut C, byte: u8)
+    where
+        C: Canvas,
+    {
+        if self.wrap_pending {
+            let row = self.row;
+            self.move_to(row, 0);
+            self.line_feed(canvas);
+        }
+        let (row, col) = (self.row, self.col);
+        let cell = Cell {
+            ch: byte,
+            inverse: self.inverse,
+        };
+        self.set_cell(canvas, row, col, cell);
+        if col + 1 == self.cols {
+            self.wrap_pending = true;
+        } else {
+            self.col += 1;
+        }
+    }
+
+    /// Move the cursor, keeping it on the screen.
+    fn move_to(&mut self, row: usize, col: usize) {
+        self.row = row.min(self.rows - 1);
+        self.col = col.min(self.cols - 1);
+        self.wrap_pending = false;
+    }
+
+    /// Down a line, scrolling if we're at the bottom.
+    fn line_feed<C>(&mut self, canvas: &mut C)
+    where
+        C: Canvas,
+    {
+        self.wrap_pending = false;
+        if self.row + 1 == self.rows {
+            self.scroll_up(canvas);
+        } else {
+            self.row += 1;
+        }
+    }
+
+    /// Up a line, scrolling if we're at the top.
+    fn reverse_index<C>(&mut self, canvas: &mut C)
+    where
+        C: Canvas,
+    {
+        self.wrap_pending = false;
+        if self.row == 0 {
+            self.scroll_down(canvas);
+        } else {
+            self.row -= 1;
+        }
+    }
+
+    fn scroll_up<C>(&mut self, canvas: &mut C)
+    where
+        C: Canvas,
+    {
+        let (cols, rows) = (self.cols, self.rows);
+        for row in 0..rows - 1 {
+            for col in 0..cols {
+                let cell = self.cells[row + 1][col];
+                self.set_cell(canvas, row, col, cell);
+            }
+        }
+        self.erase(canvas, rows - 1, 0, cols);
+    }
+
+    fn scroll_down<C>(&mut self, canvas: &mut C)
+    where
+        C: Canvas,
+    {
+        let (cols, rows) = (self.cols, self.rows);
+        for row in (1..rows).rev() {
+            for col in 0..cols {
+                let cell = self.cells[row - 1][col];
+                self.set_cell(canvas, row, col, cell);
+            }
+        }
+        self.erase(canvas, 0, 0, cols);
+    }
+
+    /// Blank columns `from..to` of `row`.
+    fn erase<C>(&mut self, canvas: &mut C, row: usize, from: usize, to: usize)
+    where
+        C: Canvas,
+    {
+        for col in from..to.min(self.cols) {
+            self.set_cell(canvas, row, col, BLANK);
+        }
+    }
+
+    /// Change a cell, drawing it only if it's different.
+    fn set_cell<C>(&mut self, canvas: &mut C, row: usize, col: usize, cell: Cell)
+    where
+        C: Canvas,
+    {
+        if self.cells[row][col] != cell {
+            self.cells[row][col] = cell;
+            draw_cell(canvas, row, col, cell, false);
+        }
+    }
+
+    fn draw_cursor<C>(&self, canvas: &mut C, visible: bool)
+    where
+        C: Canvas,
+    {
+        let cell = self.cells[self.row][self.col];
+        draw_cell(canvas, self.row, self.col, cell, visible);
+    }
+}
+
+/// Draw a cell, inverted if it's under the cursor.
+fn draw_cell<C>(canvas: &mut C, row: usize, col: usize, cell: Cell, cursor: bool)
+where
+    C: Canvas,
+{
+    let (fg, bg) = if cell.inverse != cursor {
+        (Colour::BLACK, Colour::WHITE)
+    } else {
+        (Colour::WHITE, Colour::BLACK)
+    };
+    canvas.draw_char(col * font::WIDTH, row * font::HEIGHT, cell.ch, fg, bg);
+}
@@ -0,0 +1,174 @@
+//! Plays 8-bit mono WAV files through the `dac`.
+//!
+//! `parse_header` works out where the samples are from the first few
+//! hundred bytes of a file. After that, whoever is reading the file (a
+//! main loop, say) hands the samples to `push` as they arrive, and `fill`
+//! - the `dac::Source` - takes them out again in the TIMER3A interrupt.
+//! There's a `BUFFER_LEN` ring buffer in between, so the reader can be
+//! quite lumpy. If it can't keep up the output holds its last level, which
+//! is much kinder on the ears than a click, and `underruns` goes up by one.
+//!
+//! The `wav_player` example's `play <file.wav>` reads the file from an SD
+//! card (see `demo::fat`), or takes it over the console if you don't give
+//! a name.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use cortex_m::interrupt;
+
+/// Samples held between `push` and `fill`.
+pub const BUFFER_LEN: usize = 4096;
+
+/// Something wrong with a WAV file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// Need more of the file to find the samples
+    Incomplete,
+    /// Not a RIFF file
+    NotRiff,
+    /// A RIFF file, but not a WAV
+    NotWave,
+    /// The samples came before the format
+    NoFormat,
+    /// Not 8-bit mono PCM
+    Unsupported,
+}
+
+/// What `parse_header` found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub sample_rate: u32,
+    /// Where the samples start, from the start of the file
+    pub data_start: usize,
+    /// How many bytes of samples there are
+    pub data_len: u32,
+}
+
+// The format chunk's values we can play
+const FORMAT_PCM: u16 = 1;
+const MONO: u16 = 1;
+const BITS_PER_SAMPLE: u16 = 8;
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from(data[offset]) | (u16::from(data[offset + 1]) << 8)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from(read_u16(data, offset)) | (u32::from(read_u16(data, offset + 2)) << 16)
+}
+
+/// Find the format and the samples in the start of a WAV file. Gives
+/// `Error::Incomplete` if `data` stops before the samples start.
+pub fn parse_header(data: &[u8]) -> Result<Header, Error> {
+    if data.len() < 12 {
+        return Err(Error::Incomplete);
+    }
+    if &data[0..4] != b"RIFF" {
+        return Err(Error::NotRiff);
+    }
+    if &data[8..12] != b"WAVE" {
+        return Err(Error::NotWave);
+    }
+    let mut sample_rate = None;
+    let mut offset = 12;
+    loop {
+        if data.len() < offset + 8 {
+            return Err(Error::Incomplete);
+        }
+        let id = &data[offset..offset + 4];
+        let len = read_u32(data, offset + 4) as usize;
+        let body = offset + 8;
+        if id == b"data" {
+            return match sample_rate {
+                Some(sample_rate) => Ok(Header {
+                    sample_rate,
+                    data_start: body,
+                    data_len: len as u32,
+                }),
+                None => Err(Error::NoFormat),
+            };
+        }
+        if id == b"fmt " {
+            if data.len() < body + 16 {
+                return Err(Error::Incomplete);
+            }
+            if read_u16(data, body) != FORMAT_PCM || read_u16(data, body + 2) != MONO
+                || read_u16(data, body + 14) != BITS_PER_SAMPLE
+            {
+                return Err(Error::Unsupported);
+            }
+            sample_rate = Some(read_u32(data, body + 4));
+        }
+        // Chunks are padded to an even length
+        offset = body + len + (len & 1);
+    }
+}
+
+static mut BUFFER: [u8; BUFFER_LEN] = [128; BUFFER_LEN];
+/// Where `push` writes next. Only `push` moves it.
+static WRITE: AtomicUsize = AtomicUsize::new(0);
+/// Where `fill` reads next. Only `fill` moves it.
+static READ: AtomicUsize = AtomicUsize::new(0);
+static UNDERRUNS: AtomicUsize = AtomicUsize::new(0);
+static LAST: AtomicUsize = AtomicUsize::new(128);
+
+/// Queue up samples to be played. Returns how many there was room for.
+pub fn push(samples: &[u8]) -> usize {
+    let read = READ.load(Ordering::Acquire);
+    let mut write = WRITE.load(Ordering::Relaxed);
+    let mut count = 0;
+    for &sample in samples {
+        let next = (write + 1) % BUFFER_LEN;
+        if next == read {
+            break;
+        }
+        unsafe { BUFFER[write] = sample };
+        write = next;
+        count += 1;
+    }
+    WRITE.store(write, Ordering::Release);
+    count
+}
+
+/// How many samples are waiting to be played.
+pub fn queued() -> usize {
+    let read = READ.load(Ordering::Relaxed);
+    let write = WRITE.load(Ordering::Relaxed);
+    (write + BUFFER_LEN - read) % BUFFER_LEN
+}
+
+/// How many times the buffer has run dry. Counts once per `fill`, not
+/// once per missing sample.
+pub fn underruns() -> usize {
+    UNDERRUNS.load(Ordering::Relaxed)
+}
+
+/// Throw away anything queued and start counting underruns again. Call
+/// this between files.
+pub fn reset() {
+    interrupt::free(|_| {
+        READ.store(WRITE.load(Ordering::Relaxed), Ordering::Relaxed);
+        UNDERRUNS.store(0, Ordering::Relaxed);
+    });
+}
+
+/// Take queued samples for the DAC. A `dac::Source`.
+pub fn fill(buffer: &mut [u8]) {
+    let write = WRITE.load(Ordering::Acquire);
+    let mut read = READ.load(Ordering::Relaxed);
+    let mut last = LAST.load(Ordering::Relaxed) as u8;
+    let mut ran_dry = false;
+    for sample in buffer.iter_mut() {
+        if read == write {
+            ran_dry = true;
+        } else {
+            last = unsafe { BUFFER[read] };
+            read = (read + 1) % BUFFER_LEN;
+        }
+        *sample = last;
+    }
+    READ.store(read, Ordering::Release);
+    LAST.store(usize::from(last), Ordering::Relaxed);
+    if ran_dry {
+        UNDERRUNS.fetch_add(1, Ordering::Relaxed);
+    }
+}
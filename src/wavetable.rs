@@ -0,0 +1,118 @@
+//! Oscillators for sound and signal generation.
+//!
+//! An `Oscillator` is a 32-bit phase accumulator: every sample, it adds
+//! `step` to `phase`, and the top bits of the phase pick a point on one
+//! cycle of the waveform. The frequency resolution is the sample rate over
+//! 2^32 - well under a millihertz at audio rates. Sines come from the table
+//! in `trig`, and the other shapes are worked out from the phase directly.
+//! Noise comes from a shift register, clocked sixteen times a cycle, so a
+//! higher frequency gives a brighter hiss.
+//!
+//! Samples are signed, 16-bit, centred on zero. Turning them into whatever
+//! a PWM or a DAC wants is up to the caller.
+
+use trig;
+
+/// The shape of one cycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    Sine,
+    Square,
+    Triangle,
+    /// Rising ramp
+    Sawtooth,
+    /// Random levels. Needs an `Oscillator`, as it has state.
+    Noise,
+}
+
+pub struct Oscillator {
+    pub waveform: Waveform,
+    /// 0 (silent) to 255 (full scale)
+    pub volume: u8,
+    phase: u32,
+    step: u32,
+    sample_rate: u32,
+    /// A 16-bit Galois LFSR, for `Waveform::Noise`
+    noise: u16,
+}
+
+/// The taps for a maximal length 16-bit LFSR.
+const NOISE_TAPS: u16 = 0xB400;
+
+impl Waveform {
+    /// One point on a full scale cycle. A phase of 2^32 is a whole turn.
+    /// `Noise` is silent here - use an `Oscillator`.
+    pub fn sample(&self, phase: u32) -> i16 {
+        match *self {
+            Waveform::Sine => trig::sin((phase >> 16) as u16),
+            Waveform::Square => {
+                if phase < 0x8000_0000 {
+                    i16::max_value()
+                } else {
+                    -i16::max_value()
+                }
+            }
+            Waveform::Triangle => {
+                // Fold the second half back down, then centre it
+                let rising = if phase < 0x8000_0000 { phase } else { !phase };
+                ((rising >> 15) as i32 - 32768).max(-32767) as i16
+            }
+            Waveform::Sawtooth => ((phase >> 16) as i32 - 32768).max(-32767) as i16,
+            Waveform::Noise => 0,
+        }
+    }
+}
+
+impl Oscillator {
+    /// A silent oscillator, which will be called `sample_rate` times a
+    /// second.
+    pub fn new(waveform: Waveform, sample_rate: u32) -> Oscillator {
+        Oscillator {
+            waveform,
+            volume: 0,
+            phase: 0,
+            step: 0,
+            sample_rate,
+            noise: 0xACE1,
+        }
+    }
+
+    /// Set the frequency. Anything over half the sample rate will alias.
+    pub fn set_frequency(&mut self, hz: u32) {
+        self.step = ((u64::from(hz) << 32) / u64::from(self.sample_rate)) as u32;
+    }
+
+    /// Set the frequency in thousandths of a Hz, for low notes that fall
+    /// between whole numbers.
+    pub fn set_frequency_millihertz(&mut self, millihertz: u32) {
+        self.step = ((u64::from(millihertz) << 32) / (u64::from(self.sample_rate) * 1000)) as u32;
+    }
+
+    /// The frequency, rounded down to the nearest Hz.
+    pub fn frequency(&self) -> u32 {
+        ((u64::from(self.step) * u64::from(self.sample_rate)) >> 32) as u32
+    }
+
+    /// Start the next cycle from the beginning.
+    pub fn reset(&mut self) {
+        self.phase = 0;
+    }
+
+    /// The next sample.
+    pub fn next_sample(&mut self) -> i16 {
+        let sample = match self.waveform {
+            Waveform::Noise => i32::from(self.noise as i16),
+            waveform => i32::from(waveform.sample(self.phase)),
+        };
+        let next = self.phase.wrapping_add(self.step);
+        if (next >> 28) != (self.phase >> 28) {
+            let carry = self.noise & 1 != 0;
+            self.noise >>= 1;
+            if carry {
+                self.noise ^= NOISE_TAPS;
+            }
+        }
+        self.phase = next;
+        ((sample * i32::from(self.volume)) / 255) as i16
+    }
+}
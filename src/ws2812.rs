@@ -0,0 +1,174 @@
+//! Drives a string of WS2812 ("NeoPixel") LEDs from SSI0.
+//!
+//! The WS2812 wants a bit every 1.25us, where a 0 is a short high pulse and
+//! a 1 is a long high pulse. We fake this by running SSI0 at ~2.35 MHz and
+//! sending three SPI bits per LED bit - `100` for a 0 and `110` for a 1.
+//! Four LED bits fit in one 12-bit SSI frame, so each LED takes six frames.
+//!
+//! The frames are fed to the SSI by uDMA, so once `show()` is called the CPU
+//! is free to get on with something else.
+//!
+//! SSI0Tx is PA5 (PA4 on the TM4C1294). You'll probably want a 3.3V to 5V
+//! level shifter on it.
+
+use pac;
+use udma;
+
+/// SSIClk = SysClk / (CPSDVSR * (1 + SCR)): 2.35 MHz = 80 MHz / (2 * (1 + 16))
+#[cfg(feature = "tm4c123")]
+const SCR: u8 = 16;
+/// SSIClk = SysClk / (CPSDVSR * (1 + SCR)): 2.31 MHz = 120 MHz / (2 * (1 + 25)),
+/// which is well within the WS2812's tolerance
+#[cfg(feature = "tm4c129")]
+const SCR: u8 = 25;
+
+/// uDMA channel 11, encoding 0 is SSI0 TX.
+const DMA_CHANNEL: u8 = 11;
+
+/// Number of SSI frames needed per LED.
+pub const WORDS_PER_LED: usize = 6;
+
+/// The biggest string we can update in one uDMA transfer.
+pub const MAX_LEDS: usize = udma::MAX_TRANSFER / WORDS_PER_LED;
+
+/// A colour, in the usual order. The WS2812 wants GRB on the wire but we
+/// take care of that.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct Rgb {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+impl Rgb {
+    pub fn new(red: u8, green: u8, blue: u8) -> Rgb {
+        Rgb { red, green, blue }
+    }
+
+    /// Map 0..255 around the colour wheel: red to green to blue and back.
+    pub fn wheel(pos: u8) -> Rgb {
+        match pos {
+            0...84 => Rgb::new(255 - pos * 3, pos * 3, 0),
+            85...169 => {
+                let pos = pos - 85;
+                Rgb::new(0, 255 - pos * 3, pos * 3)
+            }
+            _ => {
+                let pos = pos - 170;
+                Rgb::new(pos * 3, 0, 255 - pos * 3)
+            }
+        }
+    }
+
+    /// Scale all three channels by `level / 256`.
+    pub fn scale(&self, level: u8) -> Rgb {
+        let s = |c: u8| ((u16::from(c) * (u16::from(level) + 1)) >> 8) as u8;
+        Rgb::new(s(self.red), s(self.green), s(self.blue))
+    }
+}
+
+/// Owns SSI0 and a buffer of encoded frames.
+pub struct Ws2812 {
+    _ssi: pac::SSI0,
+    buffer: &'static mut [u16],
+}
+
+impl Ws2812 {
+    /// Set up SSI0 and the uDMA channel. The caller must have powered up
+    /// SSI0 and the uDMA controller, called `udma::init()`, and put SSI0Tx
+    /// on its pin (`config::SSI0.connect()` will do). The system clock must
+    /// be what `Board` sets (80 MHz, or 120 MHz on the TM4C1294).
+    ///
+    /// The buffer must be `WORDS_PER_LED` words per LED.
+    pub fn new(ssi: pac::SSI0, buffer: &'static mut [u16]) -> Ws2812 {
+        assert!(buffer.len() % WORDS_PER_LED == 0);
+        assert!(buffer.len() <= udma::MAX_TRANSFER);
+        ssi.cr1.modify(|_, w| w.sse().clear_bit());
+        ssi.cpsr.write(|w| unsafe { w.cpsdvsr().bits(2) });
+        // Send 12 bits at a time in Freescale format. SPH=1 gives us
+        // back-to-back frames with no gap.
+        ssi.cr0.write(|w| {
+            w.dss()._12();
+            w.frf().moto();
+            w.spo().clear_bit();
+            w.sph().set_bit();
+            unsafe { w.scr().bits(SCR) };
+            w
+        });
+        ssi.cc.modify(|_, w| w.cs().syspll());
+        ssi.dmactl.write(|w| w.txdmae().set_bit());
+        ssi.cr1.modify(|_, w| w.sse().set_bit());
+
+        udma::assign(DMA_CHANNEL, 0);
+
+        let mut ws = Ws2812 { _ssi: ssi, buffer };
+        ws.clear();
+        ws
+    }
+
+    /// How many LEDs we're driving.
+    pub fn len(&self) -> usize {
+        self.buffer.len() / WORDS_PER_LED
+    }
+
+    /// Set every LED to black. Call `show()` to see the result.
+    pub fn clear(&mut self) {
+        for i in 0..self.len() {
+            self.set(i, Rgb::default());
+        }
+    }
+
+    /// Set the colour of one LED. Out of range LEDs are ignored. Call
+    /// `show()` to see the result.
+    pub fn set(&mut self, index: usize, colour: Rgb) {
+        if index >= self.len() {
+            return;
+        }
+        let words = &mut self.buffer[index * WORDS_PER_LED..(index + 1) * WORDS_PER_LED];
+        for (pair, byte) in words
+            .chunks_mut(2)
+            .zip([colour.green, colour.red, colour.blue].iter())
+        {
+            pair[0] = encode_nibble(byte >> 4);
+            pair[1] = encode_nibble(byte & 0x0F);
+        }
+    }
+
+    /// Is a transfer still in progress?
+    pub fn is_busy(&self) -> bool {
+        udma::is_enabled(DMA_CHANNEL)
+    }
+
+    /// Send the buffer to the LEDs. If a transfer is already in progress,
+    /// we wait for it to finish first. The LEDs latch when the line has been
+    /// idle for 50us, so don't call this any faster than that.
+    pub fn show(&mut self) {
+        while self.is_busy() {}
+        let ssi = unsafe { &*pac::SSI0::ptr() };
+        udma::configure(
+            DMA_CHANNEL,
+            udma::Select::Primary,
+            &udma::Transfer {
+                size: udma::Size::HalfWord,
+                src_inc: udma::Increment::HalfWord,
+                dst_inc: udma::Increment::None,
+                arbitrate: udma::Arbitrate::_4,
+                mode: udma::Mode::Basic,
+            },
+            self.buffer.as_ptr() as *const u8,
+            &ssi.dr as *const _ as *mut u8,
+            self.buffer.len(),
+        );
+        udma::enable(DMA_CHANNEL);
+    }
+}
+
+/// Turn four LED bits into twelve SPI bits, MSB first.
+fn encode_nibble(nibble: u8) -> u16 {
+    let mut word = 0;
+    for bit in (0..4).rev() {
+        word <<= 3;
+        word |= if nibble & (1 << bit) != 0 { 0b110 } else { 0b100 };
+    }
+    word
+}
@@ -0,0 +1,461 @@
+//! Transfers files with XMODEM and YMODEM.
+//!
+//! XMODEM sends a file in numbered 128 byte blocks (or 1024 byte ones, for
+//! XMODEM-1K), each of which we ACK, or NAK to have it sent again. We ask
+//! for the CRC-16 flavour first and fall back to the original one byte
+//! checksum if the sender doesn't answer. There's no length anywhere, so the
+//! last block is padded out, usually with `SUB` (Ctrl-Z).
+//!
+//! YMODEM (batch mode, as in `sb` and `rb`) is XMODEM-1K with CRCs plus a
+//! block zero before each file giving its name and length, so several files
+//! can go in one session and the padding can be removed. An empty block
+//! zero ends the batch. We can send as well as receive YMODEM.
+//!
+//! Nothing else can use the UART while a transfer is running.
+
+use embedded_hal::blocking::delay::DelayUs;
+use embedded_hal::serial;
+
+/// The usual padding at the end of a file.
+pub const SUB: u8 = 0x1A;
+
+/// Block sizes.
+pub const BLOCK_LEN: usize = 128;
+pub const LONG_BLOCK_LEN: usize = 1024;
+
+const SOH: u8 = 0x01;
+const STX: u8 = 0x02;
+const EOT: u8 = 0x04;
+const ACK: u8 = 0x06;
+const NAK: u8 = 0x15;
+const CAN: u8 = 0x18;
+/// Sent instead of a NAK to ask for CRCs.
+const CRC_REQUEST: u8 = b'C';
+
+/// How long to wait for the sender to start, each time we ask.
+const START_TIMEOUT_MS: u32 = 3_000;
+/// How long to wait for the next block.
+const PACKET_TIMEOUT_MS: u32 = 10_000;
+/// How long to wait for each byte in a block.
+const BYTE_TIMEOUT_MS: u32 = 1_000;
+
+/// How many times we ask for CRCs before trying checksums.
+const CRC_ATTEMPTS: u32 = 3;
+/// How many times we ask at all.
+const START_ATTEMPTS: u32 = 10;
+/// How many bad blocks in a row before we give up.
+const MAX_ERRORS: u32 = 10;
+
+/// Something went wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The UART reported an error
+    Serial,
+    /// The sender went quiet
+    Timeout,
+    /// A block was damaged
+    Corrupt,
+    /// The sender cancelled the transfer
+    Cancelled,
+    /// Too many damaged blocks in a row
+    TooManyErrors,
+    /// A block turned up out of order
+    OutOfSequence,
+    /// The caller didn't want the data (e.g. it was full)
+    Refused,
+}
+
+/// Where the files in a YMODEM batch go.
+pub trait Sink {
+    /// A file is starting. We get its size, if the sender told us. Return
+    /// false to cancel the batch.
+    fn open(&mut self, name: &str, size: Option<usize>) -> bool;
+
+    /// The next piece of the file, with any padding removed. Return false
+    /// to cancel the batch.
+    fn write(&mut self, data: &[u8]) -> bool;
+
+    /// The file is complete. If the batch fails part way through a file,
+    /// this isn't called.
+    fn close(&mut self);
+}
+
+/// What the sender sent.
+enum Packet {
+    /// A block, with its number and length
+    Block(u8, usize),
+    /// End of file
+    End,
+    /// The sender gave up
+    Cancel,
+}
+
+pub struct Xmodem<'a, TX: 'a, RX: 'a, D: 'a> {
+    tx: &'a mut TX,
+    rx: &'a mut RX,
+    delay: &'a mut D,
+    crc: bool,
+}
+
+impl<'a, TX, RX, D> Xmodem<'a, TX, RX, D>
+where
+    TX: serial::Write<u8>,
+    RX: serial::Read<u8>,
+    D: DelayUs<u32>,
+{
+    pub fn new(tx: &'a mut TX, rx: &'a mut RX, delay: &'a mut D) -> Xmodem<'a, TX, RX, D> {
+        Xmodem {
+            tx,
+            rx,
+            delay,
+            crc: true,
+        }
+    }
+
+    /// Receive a file, passing each block to `sink` as it arrives. If
+    /// `sink` returns false, we cancel the transfer. Returns how many bytes
+    /// we got, padding and all.
+    pub fn receive<F>(&mut self, sink: F) -> Result<usize, Error>
+    where
+        F: FnMut(&[u8]) -> bool,
+    {
+        let mut buffer = [0u8; LONG_BLOCK_LEN];
+        self.crc = true;
+        let first = self.start(&mut buffer, true)?;
+        self.blocks(&mut buffer, first, false, sink)
+    }
+
+    /// Receive a YMODEM batch into `sink`. Returns how many files we got.
+    pub fn receive_batch<S>(&mut self, sink: &mut S) -> Result<usize, Error>
+    where
+        S: Sink,
+    {
+        let mut buffer = [0u8; LONG_BLOCK_LEN];
+        let mut files = 0;
+        self.crc = true;
+        loop {
+            // Block zero has the file's name and size
+            let len = match self.start(&mut buffer, false)? {
+                Packet::Block(0, len) => len,
+                Packet::Cancel => return Err(Error::Cancelled),
+                _ => {
+                    self.cancel();
+                    return Err(Error::OutOfSequence);
+                }
+            };
+            // No name means that's the lot
+            if buffer[0] == 0 {
+                self.send(ACK)?;
+                return Ok(files);
+            }
+            let mut remaining = {
+                let (name, size) = parse_header(&buffer[0..len]);
+                if !sink.open(name, size) {
+                    self.cancel();
+                    return Err(Error::Refused);
+                }
+                size
+            };
+            self.send(ACK)?;
+
+            // Then it's just like XMODEM, except we know where the padding
+            // starts
+            let first = self.start(&mut buffer, false)?;
+            self.blocks(&mut buffer, first, true, |block| {
+                let len = remaining.map_or(block.len(), |r| r.min(block.len()));
+                if let Some(ref mut r) = remaining {
+                    *r -= len;
+                }
+                sink.write(&block[0..len])
+            })?;
+            sink.close();
+            files += 1;
+        }
+    }
+
+    /// Send one file of a YMODEM batch. Call `end_batch` after the last
+    /// one.
+    pub fn send_file(&mut self, name: &str, data: &[u8]) -> Result<(), Error> {
+        let mut buffer = [0u8; LONG_BLOCK_LEN];
+        self.wait_for_request()?;
+        write_header(&mut buffer[0..BLOCK_LEN], name, data.len());
+        self.send_block(0, &buffer[0..BLOCK_LEN])?;
+
+        self.wait_for_request()?;
+        let mut number: u8 = 1;
+        for chunk in data.chunks(LONG_BLOCK_LEN) {
+            // Don't pad a little bit out to a whole 1K
+            let len = if chunk.len() <= BLOCK_LEN {
+                BLOCK_LEN
+            } else {
+                LONG_BLOCK_LEN
+            };
+            buffer[0..chunk.len()].copy_from_slice(chunk);
+            for byte in buffer[chunk.len()..len].iter_mut() {
+                *byte = SUB;
+            }
+            self.send_block(number, &buffer[0..len])?;
+            number = number.wrapping_add(1);
+        }
+
+        for _ in 0..MAX_ERRORS {
+            self.send(EOT)?;
+            // Receivers usually NAK the first one, to make sure
+            if self.read_byte(PACKET_TIMEOUT_MS) == Ok(ACK) {
+                return Ok(());
+            }
+        }
+        Err(Error::TooManyErrors)
+    }
+
+    /// Finish a YMODEM batch.
+    pub fn end_batch(&mut self) -> Result<(), Error> {
+        self.wait_for_request()?;
+        self.send_block(0, &[0u8; BLOCK_LEN])
+    }
+
+    /// Ask the sender to start (or carry on, in YMODEM), until we get
+    /// something. If `fallback`, we'll give up on CRCs after a while.
+    fn start(&mut self, buffer: &mut [u8], fallback: bool) -> Result<Packet, Error> {
+        let mut attempts = 0;
+        loop {
+            let request = if self.crc { CRC_REQUEST } else { NAK };
+            self.send(request)?;
+            match self.read_packet(buffer, START_TIMEOUT_MS) {
+                Ok(packet) => return Ok(packet),
+                Err(Error::Timeout) => {}
+                Err(_) => self.purge(),
+            }
+            attempts += 1;
+            if attempts == START_ATTEMPTS {
+                return Err(Error::Timeout);
+            }
+            // No answer to 'C', so maybe they only do checksums
+            if fallback && attempts == CRC_ATTEMPTS {
+                self.crc = false;
+            }
+        }
+    }
+
+    /// Receive blocks from 1 onwards, starting with `packet`, until the end
+    /// of the file. YMODEM senders get a NAK for their first EOT.
+    fn blocks<F>(
+        &mut self,
+        buffer: &mut [u8],
+        mut packet: Packet,
+        ymodem: bool,
+        mut sink: F,
+    ) -> Result<usize, Error>
+    where
+        F: FnMut(&[u8]) -> bool,
+    {
+        let mut expected: u8 = 1;
+        let mut total = 0;
+        let mut errors = 0;
+        let mut eot_seen = false;
+        loop {
+            match packet {
+                Packet::Block(number, len) => {
+                    if number == expected {
+                        if !sink(&buffer[0..len]) {
+                            self.cancel();
+                            return Err(Error::Refused);
+                        }
+                        total += len;
+                        expected = expected.wrapping_add(1);
+                        errors = 0;
+                    } else if number != expected.wrapping_sub(1) {
+                        self.cancel();
+                        return Err(Error::OutOfSequence);
+                    }
+                    // A repeat of the last block means they missed our ACK
+                    self.send(ACK)?;
+                }
+                Packet::End if ymodem && !eot_seen => {
+                    eot_seen = true;
+                    self.send(NAK)?;
+                }
+                Packet::End => {
+                    self.send(ACK)?;
+                    return Ok(total);
+                }
+                Packet::Cancel => return Err(Error::Cancelled),
+            }
+            packet = loop {
+                match self.read_packet(buffer, PACKET_TIMEOUT_MS) {
+                    Ok(packet) => break packet,
+                    Err(_) => {
+                        errors += 1;
+                        if errors == MAX_ERRORS {
+                            self.cancel();
+                            return Err(Error::TooManyErrors);
+                        }
+                        self.purge();
+                        self.send(NAK)?;
+                    }
+                }
+            };
+        }
+    }
+
+    /// Send a block with a CRC, until it's ACKed.
+    fn send_block(&mut self, number: u8, data: &[u8]) -> Result<(), Error> {
+        let header = if data.len() == BLOCK_LEN { SOH } else { STX };
+        let crc = crc16(data);
+        for _ in 0..MAX_ERRORS {
+            self.send(header)?;
+            self.send(number)?;
+            self.send(!number)?;
+            for &byte in data {
+                self.send(byte)?;
+            }
+            self.send((crc >> 8) as u8)?;
+            self.send(crc as u8)?;
+            match self.read_byte(PACKET_TIMEOUT_MS) {
+                Ok(ACK) => return Ok(()),
+                Ok(CAN) if self.read_byte(BYTE_TIMEOUT_MS) == Ok(CAN) => {
+                    return Err(Error::Cancelled)
+                }
+                // Anything else and we try again
+                _ => {}
+            }
+        }
+        self.cancel();
+        Err(Error::TooManyErrors)
+    }
+
+    /// Wait for a receiver to ask for a block with a 'C'.
+    fn wait_for_request(&mut self) -> Result<(), Error> {
+        for _ in 0..START_ATTEMPTS {
+            match self.read_byte(START_TIMEOUT_MS) {
+                Ok(CRC_REQUEST) => return Ok(()),
+                Ok(CAN) if self.read_byte(BYTE_TIMEOUT_MS) == Ok(CAN) => {
+                    return Err(Error::Cancelled)
+                }
+                _ => {}
+            }
+        }
+        Err(Error::Timeout)
+    }
+
+    fn read_packet(&mut self, buffer: &mut [u8], timeout_ms: u32) -> Result<Packet, Error> {
+        let len = match self.read_byte(timeout_ms)? {
+            SOH => BLOCK_LEN,
+            STX => LONG_BLOCK_LEN,
+            EOT => return Ok(Packet::End),
+            // It takes two, so line noise can't cancel a transfer
+            CAN if self.read_byte(BYTE_TIMEOUT_MS)? == CAN => return Ok(Packet::Cancel),
+            _ => return Err(Error::Corrupt),
+        };
+        let number = self.read_byte(BYTE_TIMEOUT_MS)?;
+        let complement = self.read_byte(BYTE_TIMEOUT_MS)?;
+        for byte in buffer[0..len].iter_mut() {
+            *byte = self.read_byte(BYTE_TIMEOUT_MS)?;
+        }
+        let good = if self.crc {
+            let high = self.read_byte(BYTE_TIMEOUT_MS)?;
+            let low = self.read_byte(BYTE_TIMEOUT_MS)?;
+            crc16(&buffer[0..len]) == (u16::from(high) << 8) | u16::from(low)
+        } else {
+            checksum(&buffer[0..len]) == self.read_byte(BYTE_TIMEOUT_MS)?
+        };
+        if !good || number != !complement {
+            return Err(Error::Corrupt);
+        }
+        Ok(Packet::Block(number, len))
+    }
+
+    fn read_byte(&mut self, timeout_ms: u32) -> Result<u8, Error> {
+        let mut ticks = timeout_ms * 10;
+        loop {
+            match self.rx.read() {
+                Ok(byte) => return Ok(byte),
+                Err(::nb::Error::WouldBlock) => {}
+                Err(::nb::Error::Other(_)) => return Err(Error::Serial),
+            }
+            if ticks == 0 {
+                return Err(Error::Timeout);
+            }
+            ticks -= 1;
+            self.delay.delay_us(100);
+        }
+    }
+
+    fn send(&mut self, byte: u8) -> Result<(), Error> {
+        block!(self.tx.write(byte)).map_err(|_| Error::Serial)
+    }
+
+    /// Throw away whatever's still coming, so we're in step again.
+    fn purge(&mut self) {
+        while self.read_byte(BYTE_TIMEOUT_MS) != Err(Error::Timeout) {}
+    }
+
+    fn cancel(&mut self) {
+        for _ in 0..3 {
+            let _ = self.send(CAN);
+        }
+    }
+}
+
+/// Pull the name and (optional) size out of a YMODEM block zero. The name
+/// ends with a NUL, and the size follows in decimal, ending with a space
+/// (if the modification time and so on follow) or another NUL.
+fn parse_header(block: &[u8]) -> (&str, Option<usize>) {
+    let name_len = block.iter().position(|&b| b == 0).unwrap_or(block.len());
+    let name = ::core::str::from_utf8(&block[0..name_len]).unwrap_or("?");
+    let mut size = None;
+    for &byte in block.iter().skip(name_len + 1) {
+        match byte {
+            b'0'...b'9' => {
+                let digit = usize::from(byte - b'0');
+                size = Some(size.unwrap_or(0) * 10 + digit);
+            }
+            _ => break,
+        }
+    }
+    (name, size)
+}
+
+/// Build a YMODEM block zero. Names too long for it are cut short.
+fn write_header(block: &mut [u8], name: &str, size: usize) {
+    for byte in block.iter_mut() {
+        *byte = 0;
+    }
+    // Leave room for the NUL and ten digits of size
+    let name_len = name.len().min(block.len() - 12);
+    block[0..name_len].copy_from_slice(&name.as_bytes()[0..name_len]);
+    let mut digits = [0u8; 10];
+    let mut count = 0;
+    let mut remaining = size;
+    loop {
+        digits[count] = b'0' + (remaining % 10) as u8;
+        count += 1;
+        remaining /= 10;
+        if remaining == 0 {
+            break;
+        }
+    }
+    for i in 0..count {
+        block[name_len + 1 + i] = digits[count - 1 - i];
+    }
+}
+
+/// The CRC-16 (CCITT polynomial, starting from zero) that XMODEM uses.
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for &byte in data {
+        crc ^= u16::from(byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+}
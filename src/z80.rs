@@ -0,0 +1,1040 @@
+//! A Z80 processor.
+//!
+//! All the documented instructions are here, with the `CB`, `DD`, `ED` and
+//! `FD` prefixes, plus the undocumented ones that real software leans on -
+//! the halves of IX and IY, `SLL`, and the `DDCB` forms that copy their
+//! result into a register. Timing is to the instruction, not the cycle, and
+//! the undocumented flag bits 3 and 5 are only roughly right.
+//!
+//! As with `mos6502`, the processor sees the rest of the machine through a
+//! `Bus`, which on the Z80 has a separate I/O space as well as memory.
+
+use core::mem;
+
+/// Everything outside the processor.
+pub trait Bus {
+    /// Read a byte of memory.
+    fn read(&mut self, address: u16) -> u8;
+
+    /// Write a byte of memory. Writes to ROM should be ignored.
+    fn write(&mut self, address: u16, value: u8);
+
+    /// Read an I/O port. The high byte is whatever was on the top half of
+    /// the address bus - usually A or B.
+    fn input(&mut self, port: u16) -> u8;
+
+    /// Write an I/O port.
+    fn output(&mut self, port: u16, value: u8);
+}
+
+/// Carry
+pub const FLAG_C: u8 = 1 << 0;
+/// Subtract
+pub const FLAG_N: u8 = 1 << 1;
+/// Parity or overflow
+pub const FLAG_PV: u8 = 1 << 2;
+/// Undocumented copy of bit 3 of the result
+pub const FLAG_X: u8 = 1 << 3;
+/// Half carry
+pub const FLAG_H: u8 = 1 << 4;
+/// Undocumented copy of bit 5 of the result
+pub const FLAG_Y: u8 = 1 << 5;
+/// Zero
+pub const FLAG_Z: u8 = 1 << 6;
+/// Sign
+pub const FLAG_S: u8 = 1 << 7;
+
+/// Where a non-maskable interrupt goes.
+const NMI_ADDRESS: u16 = 0x0066;
+
+/// T-states for the unprefixed instructions, before any index prefix, and
+/// for the conditional ones, not taking the jump.
+const CYCLES: [u8; 256] = [
+    4, 10, 7, 6, 4, 4, 7, 4, 4, 11, 7, 6, 4, 4, 7, 4, // 00
+    8, 10, 7, 6, 4, 4, 7, 4, 12, 11, 7, 6, 4, 4, 7, 4, // 10
+    7, 10, 16, 6, 4, 4, 7, 4, 7, 11, 16, 6, 4, 4, 7, 4, // 20
+    7, 10, 13, 6, 11, 11, 10, 4, 7, 11, 13, 6, 4, 4, 7, 4, // 30
+    4, 4, 4, 4, 4, 4, 7, 4, 4, 4, 4, 4, 4, 4, 7, 4, // 40
+    4, 4, 4, 4, 4, 4, 7, 4, 4, 4, 4, 4, 4, 4, 7, 4, // 50
+    4, 4, 4, 4, 4, 4, 7, 4, 4, 4, 4, 4, 4, 4, 7, 4, // 60
+    7, 7, 7, 7, 7, 7, 4, 7, 4, 4, 4, 4, 4, 4, 7, 4, // 70
+    4, 4, 4, 4, 4, 4, 7, 4, 4, 4, 4, 4, 4, 4, 7, 4, // 80
+    4, 4, 4, 4, 4, 4, 7, 4, 4, 4, 4, 4, 4, 4, 7, 4, // 90
+    4, 4, 4, 4, 4, 4, 7, 4, 4, 4, 4, 4, 4, 4, 7, 4, // A0
+    4, 4, 4, 4, 4, 4, 7, 4, 4, 4, 4, 4, 4, 4, 7, 4, // B0
+    5, 10, 10, 10, 10, 11, 7, 11, 5, 10, 10, 0, 10, 17, 7, 11, // C0
+    5, 10, 10, 11, 10, 11, 7, 11, 5, 4, 10, 11, 10, 0, 7, 11, // D0
+    5, 10, 10, 19, 10, 11, 7, 11, 5, 4, 10, 4, 10, 0, 7, 11, // E0
+    5, 10, 10, 4, 10, 11, 7, 11, 5, 6, 10, 4, 10, 0, 7, 11, // F0
+];
+
+/// Which register an instruction means by HL, which the `DD` and `FD`
+/// prefixes change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Index {
+    HL,
+    IX,
+    IY,
+}
+
+/// The registers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cpu {
+    pub a: u8,
+    pub f: u8,
+    pub bc: u16,
+    pub de: u16,
+    pub hl: u16,
+    pub ix: u16,
+    pub iy: u16,
+    pub sp: u16,
+    pub pc: u16,
+    /// The other register set, for `EX AF,AF'` and `EXX`
+    pub af_alt: u16,
+    pub bc_alt: u16,
+    pub de_alt: u16,
+    pub hl_alt: u16,
+    pub i: u8,
+    pub r: u8,
+    pub iff1: bool,
+    pub iff2: bool,
+    /// Interrupt mode, 0 to 2
+    pub im: u8,
+    /// Set by `HALT` until an interrupt comes along
+    pub halted: bool,
+    /// How many T-states we've run, which wraps round
+    pub cycles: u32,
+    index: Index,
+    /// `EI` waits an instruction before letting interrupts in.
+    ei_pending: bool,
+}
+
+impl Cpu {
+    pub fn new() -> Cpu {
+        Cpu {
+            a: 0xFF,
+            f: 0xFF,
+            bc: 0,
+            de: 0,
+            hl: 0,
+            ix: 0,
+            iy: 0,
+            sp: 0xFFFF,
+            pc: 0,
+            af_alt: 0,
+            bc_alt: 0,
+            de_alt: 0,
+            hl_alt: 0,
+            i: 0,
+            r: 0,
+            iff1: false,
+            iff2: false,
+            im: 0,
+            halted: false,
+            cycles: 0,
+            index: Index::HL,
+            ei_pending: false,
+        }
+    }
+
+    /// What the reset line does: start again at 0000 with interrupts off.
+    pub fn reset(&mut self) {
+        self.pc = 0;
+        self.i = 0;
+        self.r = 0;
+        self.iff1 = false;
+        self.iff2 = false;
+        self.im = 0;
+        self.halted = false;
+        self.ei_pending = false;
+    }
+
+    /// A maskable interrupt, ignored unless interrupts are enabled. In mode
+    /// 0, `data` must be an `RST`; in mode 2 it's the low half of the vector
+    /// address. Returns whether the interrupt was taken.
+    pub fn interrupt<B: Bus>(&mut self, bus: &mut B, data: u8) -> bool {
+        if !self.iff1 || self.ei_pending {
+            return false;
+        }
+        self.iff1 = false;
+        self.iff2 = false;
+        self.halted = false;
+        self.bump_r();
+        let pc = self.pc;
+        self.push(bus, pc);
+        match self.im {
+            0 => {
+                self.pc = (data & 0x38) as u16;
+                self.add_cycles(13);
+            }
+            1 => {
+                self.pc = 0x0038;
+                self.add_cycles(13);
+            }
+            _ => {
+                let vector = ((self.i as u16) << 8) | data as u16;
+                self.pc = read_word(bus, vector);
+                self.add_cycles(19);
+            }
+        }
+        true
+    }
+
+    /// The non-maskable interrupt.
+    pub fn nmi<B: Bus>(&mut self, bus: &mut B) {
+        self.iff2 = self.iff1;
+        self.iff1 = false;
+        self.halted = false;
+        self.bump_r();
+        let pc = self.pc;
+        self.push(bus, pc);
+        self.pc = NMI_ADDRESS;
+        self.add_cycles(11);
+    }
+
+    /// Run one instruction (or while halted, four T-states of nothing).
+    /// Returns how many T-states it took.
+    pub fn step<B: Bus>(&mut self, bus: &mut B) -> u32 {
+        self.ei_pending = false;
+        let start = self.cycles;
+        if self.halted {
+            self.bump_r();
+            self.add_cycles(4);
+            return 4;
+        }
+        self.index = Index::HL;
+        let mut opcode = self.fetch_opcode(bus);
+        // A string of prefixes is allowed; the last one wins
+        while opcode == 0xDD || opcode == 0xFD {
+            self.index = if opcode == 0xDD { Index::IX } else { Index::IY };
+            self.add_cycles(4);
+            opcode = self.fetch_opcode(bus);
+        }
+        match opcode {
+            0xCB => {
+                if self.index == Index::HL {
+                    self.cb_prefix(bus)
+                } else {
+                    self.index_cb_prefix(bus)
+                }
+            }
+            0xED => {
+                self.index = Index::HL;
+                self.ed_prefix(bus)
+            }
+            _ => self.unprefixed(bus, opcode),
+        }
+        self.cycles.wrapping_sub(start)
+    }
+
+    fn unprefixed<B: Bus>(&mut self, bus: &mut B, opcode: u8) {
+        self.add_cycles(CYCLES[opcode as usize] as u32);
+        let x = opcode >> 6;
+        let y = (opcode >> 3) & 7;
+        let z = opcode & 7;
+        let p = y >> 1;
+        let q = y & 1;
+
+        match (x, z) {
+            (0, 0) => match y {
+                0 => {}
+                1 => {
+                    let af = self.af();
+                    let alt = self.af_alt;
+                    self.set_af(alt);
+                    self.af_alt = af;
+                }
+                2 => {
+                    let offset = self.fetch(bus) as i8;
+                    let b = self.b().wrapping_sub(1);
+                    self.set_b(b);
+                    if b != 0 {
+                        self.jump_relative(offset);
+                        self.add_cycles(5);
+                    }
+                }
+                3 => {
+                    let offset = self.fetch(bus) as i8;
+                    self.jump_relative(offset);
+                }
+                _ => {
+                    let offset = self.fetch(bus) as i8;
+                    if self.condition(y - 4) {
+                        self.jump_relative(offset);
+                        self.add_cycles(5);
+                    }
+                }
+            },
+            (0, 1) => {
+                if q == 0 {
+                    let value = self.fetch_word(bus);
+                    self.set_rp(p, value);
+                } else {
+                    let (hl, value) = (self.index_reg(), self.rp(p));
+                    let result = self.add16(hl, value);
+                    self.set_index_reg(result);
+                }
+            }
+            (0, 2) => match (q, p) {
+                (0, 0) => bus.write(self.bc, self.a),
+                (0, 1) => bus.write(self.de, self.a),
+                (0, 2) => {
+                    let address = self.fetch_word(bus);
+                    let value = self.index_reg();
+                    write_word(bus, address, value);
+                }
+                (0, _) => {
+                    let address = self.fetch_word(bus);
+                    bus.write(address, self.a);
+                }
+                (_, 0) => self.a = bus.read(self.bc),
+                (_, 1) => self.a = bus.read(self.de),
+                (_, 2) => {
+                    let address = self.fetch_word(bus);
+                    let value = read_word(bus, address);
+                    self.set_index_reg(value);
+                }
+                (_, _) => {
+                    let address = self.fetch_word(bus);
+                    self.a = bus.read(address);
+                }
+            },
+            (0, 3) => {
+                let value = self.rp(p);
+                let value = if q == 0 {
+                    value.wrapping_add(1)
+                } else {
+                    value.wrapping_sub(1)
+                };
+                self.set_rp(p, value);
+            }
+            (0, 4) => {
+                let address = self.operand_address(bus, y);
+                let value = self.get_reg(bus, y, address).wrapping_add(1);
+                self.set_reg(bus, y, address, value);
+                self.f = (self.f & FLAG_C) | sz(value) | if value & 0x0F == 0 { FLAG_H } else { 0 }
+                    | if value == 0x80 { FLAG_PV } else { 0 };
+            }
+            (0, 5) => {
+                let address = self.operand_address(bus, y);
+                let value = self.get_reg(bus, y, address).wrapping_sub(1);
+                self.set_reg(bus, y, address, value);
+                self.f = (self.f & FLAG_C) | sz(value) | FLAG_N
+                    | if value & 0x0F == 0x0F { FLAG_H } else { 0 }
+                    | if value == 0x7F { FLAG_PV } else { 0 };
+            }
+            (0, 6) => {
+                let address = self.operand_address(bus, y);
+                let value = self.fetch(bus);
+                self.set_reg(bus, y, address, value);
+            }
+            (0, _) => self.accumulator_op(y),
+            (1, _) => {
+                if y == 6 && z == 6 {
+                    self.halted = true;
+                } else if y == 6 || z == 6 {
+                    // With (IX+d), the other register really is H or L
+                    let address = self.operand_address(bus, 6);
+                    if y == 6 {
+                        let value = self.get_plain_reg(bus, z, address);
+                        bus.write(address, value);
+                    } else {
+                        let value = bus.read(address);
+                        self.set_plain_reg(bus, y, address, value);
+                    }
+                } else {
+                    let value = self.get_reg(bus, z, 0);
+                    self.set_reg(bus, y, 0, value);
+                }
+            }
+            (2, _) => {
+                let address = self.operand_address(bus, z);
+                let value = self.get_reg(bus, z, address);
+                self.alu(y, value);
+            }
+            (_, 0) => {
+                if self.condition(y) {
+                    self.pc = self.pop(bus);
+                    self.add_cycles(6);
+                }
+            }
+            (_, 1) => match (q, p) {
+                (0, _) => {
+                    let value = self.pop(bus);
+                    self.set_rp2(p, value);
+                }
+                (_, 0) => self.pc = self.pop(bus),
+                (_, 1) => {
+                    mem::swap(&mut self.bc, &mut self.bc_alt);
+                    mem::swap(&mut self.de, &mut self.de_alt);
+                    mem::swap(&mut self.hl, &mut self.hl_alt);
+                }
+                (_, 2) => self.pc = self.index_reg(),
+                (_, _) => self.sp = self.index_reg(),
+            },
+            (_, 2) => {
+                let address = self.fetch_word(bus);
+                if self.condition(y) {
+                    self.pc = address;
+                }
+            }
+            (_, 3) => match y {
+                0 => self.pc = self.fetch_word(bus),
+                2 => {
+                    let port = self.fetch(bus) as u16 | ((self.a as u16) << 8);
+                    bus.output(port, self.a);
+                }
+                3 => {
+                    let port = self.fetch(bus) as u16 | ((self.a as u16) << 8);
+                    self.a = bus.input(port);
+                }
+                4 => {
+                    let sp = self.sp;
+                    let value = read_word(bus, sp);
+                    let hl = self.index_reg();
+                    write_word(bus, sp, hl);
+                    self.set_index_reg(value);
+                }
+                5 => mem::swap(&mut self.de, &mut self.hl),
+                6 => {
+                    self.iff1 = false;
+                    self.iff2 = false;
+                }
+                7 => {
+                    self.iff1 = true;
+                    self.iff2 = true;
+                    self.ei_pending = true;
+                }
+                // CB is a prefix, handled in `step`
+                _ => {}
+            },
+            (_, 4) => {
+                let address = self.fetch_word(bus);
+                if self.condition(y) {
+                    self.call(bus, address);
+                    self.add_cycles(7);
+                }
+            }
+            (_, 5) => {
+                if q == 0 {
+                    let value = self.rp2(p);
+                    self.push(bus, value);
+                } else {
+                    // The others (p = 1, 2 and 3) are prefixes
+                    let address = self.fetch_word(bus);
+                    self.call(bus, address);
+                }
+            }
+            (_, 6) => {
+                let value = self.fetch(bus);
+                self.alu(y, value);
+            }
+            (_, _) => self.call(bus, (y as u16) * 8),
+        }
+    }
+
+    /// The rotates, shifts and bit instructions on registers and (HL).
+    fn cb_prefix<B: Bus>(&mut self, bus: &mut B) {
+        let opcode = self.fetch_opcode(bus);
+        let x = opcode >> 6;
+        let y = (opcode >> 3) & 7;
+        let z = opcode & 7;
+        let address = self.hl;
+        let value = self.get_reg(bus, z, address);
+        if z == 6 {
+            self.add_cycles(if x == 1 { 12 } else { 15 });
+        } else {
+            self.add_cycles(8);
+        }
+        match x {
+            0 => {
+                let result = self.rotate(y, value);
+                self.set_reg(bus, z, address, result);
+            }
+            1 => self.bit(y, value),
+            2 => self.set_reg(bus, z, address, value & !(1 << y)),
+            _ => self.set_reg(bus, z, address, value | (1 << y)),
+        }
+    }
+
+    /// `DDCB` and `FDCB`: the displacement comes before the opcode, and
+    /// everything works on (IX+d), with the result also copied into a
+    /// register unless that register is (HL).
+    fn index_cb_prefix<B: Bus>(&mut self, bus: &mut B) {
+        let offset = self.fetch(bus) as i8;
+        let address = self.index_reg().wrapping_add(offset as u16);
+        // Not an M1 cycle, so R doesn't count it
+        let opcode = self.fetch(bus);
+        let x = opcode >> 6;
+        let y = (opcode >> 3) & 7;
+        let z = opcode & 7;
+        let value = bus.read(address);
+        self.add_cycles(if x == 1 { 16 } else { 19 });
+        let result = match x {
+            0 => self.rotate(y, value),
+            1 => {
+                self.bit(y, value);
+                return;
+            }
+            2 => value & !(1 << y),
+            _ => value | (1 << y),
+        };
+        bus.write(address, result);
+        if z != 6 {
+            self.set_plain_reg(bus, z, address, result);
+        }
+    }
+
+    /// The extended instructions. Anything undefined is a long NOP.
+    fn ed_prefix<B: Bus>(&mut self, bus: &mut B) {
+        let opcode = self.fetch_opcode(bus);
+        let x = opcode >> 6;
+        let y = (opcode >> 3) & 7;
+        let z = opcode & 7;
+        let p = y >> 1;
+        let q = y & 1;
+        self.add_cycles(8);
+
+        if x == 2 && z <= 3 && y >= 4 {
+            self.block(bus, y, z);
+            return;
+        }
+        if x != 1 {
+            return;
+        }
+
+        match z {
+            0 => {
+                let value = bus.input(self.bc);
+                // y = 6 only sets the flags
+                if y != 6 {
+                    self.set_plain_reg(bus, y, 0, value);
+                }
+                self.f = (self.f & FLAG_C) | szp(value);
+                self.add_cycles(4);
+            }
+            1 => {
+                let value = if y == 6 {
+                    0
+                } else {
+                    self.get_plain_reg(bus, y, 0)
+                };
+                bus.output(self.bc, value);
+                self.add_cycles(4);
+            }
+            2 => {
+                let (hl, value) = (self.hl, self.rp(p));
+                let carry = (self.f & FLAG_C) as u16;
+                self.hl = if q == 0 {
+                    self.sbc16(hl, value, carry)
+                } else {
+                    self.adc16(hl, value, carry)
+                };
+                self.add_cycles(7);
+            }
+            3 => {
+                let address = self.fetch_word(bus);
+                if q == 0 {
+                    let value = self.rp(p);
+                    write_word(bus, address, value);
+                } else {
+                    let value = read_word(bus, address);
+                    self.set_rp(p, value);
+                }
+                self.add_cycles(12);
+            }
+            4 => {
+                let a = self.a;
+                self.a = 0;
+                self.alu(2, a);
+            }
+            5 => {
+                // RETI and RETN both do this
+                self.iff1 = self.iff2;
+                self.pc = self.pop(bus);
+                self.add_cycles(6);
+            }
+            6 => {
+                self.im = match y & 3 {
+                    0 | 1 => 0,
+                    2 => 1,
+                    _ => 2,
+                };
+            }
+            _ => match y {
+                0 => {
+                    self.i = self.a;
+                    self.add_cycles(1);
+                }
+                1 => {
+                    self.r = self.a;
+                    self.add_cycles(1);
+                }
+                2 | 3 => {
+                    let value = if y == 2 { self.i } else { self.r };
+                    self.a = value;
+                    self.f = (self.f & FLAG_C) | sz(value)
+                        | if self.iff2 { FLAG_PV } else { 0 };
+                    self.add_cycles(1);
+                }
+                4 | 5 => {
+                    // RRD and RLD rotate a digit at a time through A and (HL)
+                    let value = bus.read(self.hl);
+                    let a = self.a;
+                    let (a, value) = if y == 4 {
+                        ((a & 0xF0) | (value & 0x0F), (a << 4) | (value >> 4))
+                    } else {
+                        ((a & 0xF0) | (value >> 4), (value << 4) | (a & 0x0F))
+                    };
+                    bus.write(self.hl, value);
+                    self.a = a;
+                    self.f = (self.f & FLAG_C) | szp(a);
+                    self.add_cycles(10);
+                }
+                _ => {}
+            },
+        }
+    }
+
+    /// LDI, CPI, INI, OUTI and their decrementing and repeating versions.
+    /// The repeating ones go round again by winding the PC back, as the
+    /// real thing does, so interrupts can get in.
+    fn block<B: Bus>(&mut self, bus: &mut B, y: u8, z: u8) {
+        let decrement = y & 1 != 0;
+        let repeat = y >= 6;
+        let step = |value: u16| {
+            if decrement {
+                value.wrapping_sub(1)
+            } else {
+                value.wrapping_add(1)
+            }
+        };
+        let again = match z {
+            0 => {
+                let value = bus.read(self.hl);
+                bus.write(self.de, value);
+                self.hl = step(self.hl);
+                self.de = step(self.de);
+                self.bc = self.bc.wrapping_sub(1);
+                let n = value.wrapping_add(self.a);
+                self.f = (self.f & (FLAG_S | FLAG_Z | FLAG_C)) | (n & FLAG_X)
+                    | ((n << 4) & FLAG_Y) | if self.bc != 0 { FLAG_PV } else { 0 };
+                self.bc != 0
+            }
+            1 => {
+                let value = bus.read(self.hl);
+                let result = self.a.wrapping_sub(value);
+                self.hl = step(self.hl);
+                self.bc = self.bc.wrapping_sub(1);
+                let half = (self.a ^ value ^ result) & FLAG_H;
+                self.f = (self.f & FLAG_C) | (sz(result) & !(FLAG_X | FLAG_Y)) | half | FLAG_N
+                    | if self.bc != 0 { FLAG_PV } else { 0 };
+                self.bc != 0 && result != 0
+            }
+            2 => {
+                let value = bus.input(self.bc);
+                bus.write(self.hl, value);
+                self.hl = step(self.hl);
+                let b = self.b().wrapping_sub(1);
+                self.set_b(b);
+                self.f = (self.f & FLAG_C) | sz(b) | FLAG_N;
+                b != 0
+            }
+            _ => {
+                let value = bus.read(self.hl);
+                let b = self.b().wrapping_sub(1);
+                self.set_b(b);
+                bus.output(self.bc, value);
+                self.hl = step(self.hl);
+                self.f = (self.f & FLAG_C) | sz(b) | FLAG_N;
+                b != 0
+            }
+        };
+        self.add_cycles(8);
+        if repeat && again {
+            self.pc = self.pc.wrapping_sub(2);
+            self.add_cycles(5);
+        }
+    }
+
+    /// RLCA, RRCA, RLA, RRA, DAA, CPL, SCF and CCF.
+    fn accumulator_op(&mut self, y: u8) {
+        let a = self.a;
+        let keep = self.f & (FLAG_S | FLAG_Z | FLAG_PV);
+        match y {
+            0 => {
+                self.a = a.rotate_left(1);
+                self.f = keep | (a >> 7);
+            }
+            1 => {
+                self.a = a.rotate_right(1);
+                self.f = keep | (a & FLAG_C);
+            }
+            2 => {
+                self.a = (a << 1) | (self.f & FLAG_C);
+                self.f = keep | (a >> 7);
+            }
+            3 => {
+                self.a = (a >> 1) | ((self.f & FLAG_C) << 7);
+                self.f = keep | (a & FLAG_C);
+            }
+            4 => self.daa(),
+            5 => {
+                self.a = !a;
+                self.f = (self.f & !(FLAG_X | FLAG_Y)) | FLAG_H | FLAG_N | (!a & (FLAG_X | FLAG_Y));
+            }
+            6 => self.f = keep | FLAG_C | (a & (FLAG_X | FLAG_Y)),
+            _ => {
+                let half = (self.f & FLAG_C) << 4;
+                self.f = keep | half | ((self.f & FLAG_C) ^ FLAG_C) | (a & (FLAG_X | FLAG_Y));
+            }
+        }
+    }
+
+    fn daa(&mut self) {
+        let a = self.a;
+        let subtract = self.f & FLAG_N != 0;
+        let mut correction = 0;
+        let mut carry = self.f & FLAG_C != 0;
+        if self.f & FLAG_H != 0 || a & 0x0F > 9 {
+            correction |= 0x06;
+        }
+        if carry || a > 0x99 {
+            correction |= 0x60;
+            carry = true;
+        }
+        let result = if subtract {
+            a.wrapping_sub(correction)
+        } else {
+            a.wrapping_add(correction)
+        };
+        let half = if subtract {
+            self.f & FLAG_H != 0 && a & 0x0F < 6
+        } else {
+            a & 0x0F > 9
+        };
+        self.a = result;
+        self.f = szp(result) | if half { FLAG_H } else { 0 } | if subtract { FLAG_N } else { 0 }
+            | if carry { FLAG_C } else { 0 };
+    }
+
+    /// ADD, ADC, SUB, SBC, AND, XOR, OR and CP, on A.
+    fn alu(&mut self, op: u8, value: u8) {
+        let a = self.a;
+        let carry = self.f & FLAG_C;
+        match op {
+            0 | 1 => {
+                let carry = if op == 1 { carry } else { 0 };
+                let sum = a as u16 + value as u16 + carry as u16;
+                let result = sum as u8;
+                self.a = result;
+                self.f = sz(result) | ((a ^ value ^ result) & FLAG_H)
+                    | ((!(a ^ value) & (a ^ result) & 0x80) >> 5)
+                    | if sum > 0xFF { FLAG_C } else { 0 };
+            }
+            2 | 3 | 7 => {
+                let carry = if op == 3 { carry } else { 0 };
+                let result = self.subtract(a, value, carry);
+                if op == 7 {
+                    // CP takes bits 3 and 5 from the operand
+                    self.f = (self.f & !(FLAG_X | FLAG_Y)) | (value & (FLAG_X | FLAG_Y));
+                } else {
+                    self.a = result;
+                }
+            }
+            4 => {
+                self.a = a & value;
+                self.f = szp(self.a) | FLAG_H;
+            }
+            5 => {
+                self.a = a ^ value;
+                self.f = szp(self.a);
+            }
+            _ => {
+                self.a = a | value;
+                self.f = szp(self.a);
+            }
+        }
+    }
+
+    /// An 8-bit subtraction, setting the flags.
+    fn subtract(&mut self, a: u8, value: u8, carry: u8) -> u8 {
+        let difference = a as i16 - value as i16 - carry as i16;
+        let result = difference as u8;
+        self.f = sz(result) | ((a ^ value ^ result) & FLAG_H)
+            | (((a ^ value) & (a ^ result) & 0x80) >> 5) | FLAG_N
+            | if difference < 0 { FLAG_C } else { 0 };
+        result
+    }
+
+    /// ADD HL,rr - which only touches H, N and C.
+    fn add16(&mut self, a: u16, b: u16) -> u16 {
+        let sum = a as u32 + b as u32;
+        let result = sum as u16;
+        self.f = (self.f & (FLAG_S | FLAG_Z | FLAG_PV)) | (((a ^ b ^ result) >> 8) as u8 & FLAG_H)
+            | ((result >> 8) as u8 & (FLAG_X | FLAG_Y))
+            | if sum > 0xFFFF { FLAG_C } else { 0 };
+        result
+    }
+
+    fn adc16(&mut self, a: u16, b: u16, carry: u16) -> u16 {
+        let sum = a as u32 + b as u32 + carry as u32;
+        let result = sum as u16;
+        let high = (result >> 8) as u8;
+        self.f = (high & (FLAG_S | FLAG_X | FLAG_Y)) | if result == 0 { FLAG_Z } else { 0 }
+            | (((a ^ b ^ result) >> 8) as u8 & FLAG_H)
+            | (((!(a ^ b) & (a ^ result)) >> 13) as u8 & FLAG_PV)
+            | if sum > 0xFFFF { FLAG_C } else { 0 };
+        result
+    }
+
+    fn sbc16(&mut self, a: u16, b: u16, carry: u16) -> u16 {
+        let difference = a as i32 - b as i32 - carry as i32;
+        let result = difference as u16;
+        let high = (result >> 8) as u8;
+        self.f = (high & (FLAG_S | FLAG_X | FLAG_Y)) | if result == 0 { FLAG_Z } else { 0 }
+            | (((a ^ b ^ result) >> 8) as u8 & FLAG_H)
+            | ((((a ^ b) & (a ^ result)) >> 13) as u8 & FLAG_PV) | FLAG_N
+            | if difference < 0 { FLAG_C } else { 0 };
+        result
+    }
+
+    /// RLC, RRC, RL, RR, SLA, SRA, SLL and SRL.
+    fn rotate(&mut self, op: u8, value: u8) -> u8 {
+        let carry = self.f & FLAG_C;
+        let (result, out) = match op {
+            0 => (value.rotate_left(1), value >> 7),
+            1 => (value.rotate_right(1), value & 1),
+            2 => ((value << 1) | carry, value >> 7),
+            3 => ((value >> 1) | (carry << 7), value & 1),
+            4 => (value << 1, value >> 7),
+            5 => ((value >> 1) | (value & 0x80), value & 1),
+            6 => ((value << 1) | 1, value >> 7),
+            _ => (value >> 1, value & 1),
+        };
+        self.f = szp(result) | out;
+        result
+    }
+
+    fn bit(&mut self, bit: u8, value: u8) {
+        let tested = value & (1 << bit);
+        self.f = (self.f & FLAG_C) | FLAG_H | (value & (FLAG_X | FLAG_Y))
+            | if tested == 0 { FLAG_Z | FLAG_PV } else { 0 }
+            | (tested & FLAG_S);
+    }
+
+    /// NZ, Z, NC, C, PO, PE, P and M.
+    fn condition(&self, cc: u8) -> bool {
+        let flag = match cc >> 1 {
+            0 => FLAG_Z,
+            1 => FLAG_C,
+            2 => FLAG_PV,
+            _ => FLAG_S,
+        };
+        (self.f & flag != 0) == (cc & 1 != 0)
+    }
+
+    /// Where register `r` is, if it's (HL) or (IX+d). This fetches the
+    /// displacement, so call it once per instruction.
+    fn operand_address<B: Bus>(&mut self, bus: &mut B, r: u8) -> u16 {
+        if r != 6 {
+            return 0;
+        }
+        match self.index {
+            Index::HL => self.hl,
+            _ => {
+                let offset = self.fetch(bus) as i8;
+                self.add_cycles(8);
+                self.index_reg().wrapping_add(offset as u16)
+            }
+        }
+    }
+
+    /// Get register `r` (B, C, D, E, H, L, (HL), A) with H and L swapped
+    /// for the halves of IX or IY if there's a prefix. `address` is where
+    /// (HL) is.
+    fn get_reg<B: Bus>(&mut self, bus: &mut B, r: u8, address: u16) -> u8 {
+        match (r, self.index) {
+            (4, Index::IX) => (self.ix >> 8) as u8,
+            (5, Index::IX) => self.ix as u8,
+            (4, Index::IY) => (self.iy >> 8) as u8,
+            (5, Index::IY) => self.iy as u8,
+            _ => self.get_plain_reg(bus, r, address),
+        }
+    }
+
+    fn set_reg<B: Bus>(&mut self, bus: &mut B, r: u8, address: u16, value: u8) {
+        match (r, self.index) {
+            (4, Index::IX) => self.ix = (self.ix & 0x00FF) | ((value as u16) << 8),
+            (5, Index::IX) => self.ix = (self.ix & 0xFF00) | value as u16,
+            (4, Index::IY) => self.iy = (self.iy & 0x00FF) | ((value as u16) << 8),
+            (5, Index::IY) => self.iy = (self.iy & 0xFF00) | value as u16,
+            _ => self.set_plain_reg(bus, r, address, value),
+        }
+    }
+
+    /// Get register `r`, ignoring any prefix.
+    fn get_plain_reg<B: Bus>(&mut self, bus: &mut B, r: u8, address: u16) -> u8 {
+        match r {
+            0 => (self.bc >> 8) as u8,
+            1 => self.bc as u8,
+            2 => (self.de >> 8) as u8,
+            3 => self.de as u8,
+            4 => (self.hl >> 8) as u8,
+            5 => self.hl as u8,
+            6 => bus.read(address),
+            _ => self.a,
+        }
+    }
+
+    fn set_plain_reg<B: Bus>(&mut self, bus: &mut B, r: u8, address: u16, value: u8) {
+        let value16 = value as u16;
+        match r {
+            0 => self.bc = (self.bc & 0x00FF) | (value16 << 8),
+            1 => self.bc = (self.bc & 0xFF00) | value16,
+            2 => self.de = (self.de & 0x00FF) | (value16 << 8),
+            3 => self.de = (self.de & 0xFF00) | value16,
+            4 => self.hl = (self.hl & 0x00FF) | (value16 << 8),
+            5 => self.hl = (self.hl & 0xFF00) | value16,
+            6 => bus.write(address, value),
+            _ => self.a = value,
+        }
+    }
+
+    /// BC, DE, HL (or IX or IY) and SP.
+    fn rp(&self, p: u8) -> u16 {
+        match p {
+            0 => self.bc,
+            1 => self.de,
+            2 => self.index_reg(),
+            _ => self.sp,
+        }
+    }
+
+    fn set_rp(&mut self, p: u8, value: u16) {
+        match p {
+            0 => self.bc = value,
+            1 => self.de = value,
+            2 => self.set_index_reg(value),
+            _ => self.sp = value,
+        }
+    }
+
+    /// As `rp`, but with AF instead of SP, for PUSH and POP.
+    fn rp2(&self, p: u8) -> u16 {
+        match p {
+            3 => self.af(),
+            _ => self.rp(p),
+        }
+    }
+
+    fn set_rp2(&mut self, p: u8, value: u16) {
+        match p {
+            3 => self.set_af(value),
+            _ => self.set_rp(p, value),
+        }
+    }
+
+    /// HL, IX or IY, depending on the prefix.
+    fn index_reg(&self) -> u16 {
+        match self.index {
+            Index::HL => self.hl,
+            Index::IX => self.ix,
+            Index::IY => self.iy,
+        }
+    }
+
+    fn set_index_reg(&mut self, value: u16) {
+        match self.index {
+            Index::HL => self.hl = value,
+            Index::IX => self.ix = value,
+            Index::IY => self.iy = value,
+        }
+    }
+
+    fn af(&self) -> u16 {
+        ((self.a as u16) << 8) | self.f as u16
+    }
+
+    fn set_af(&mut self, value: u16) {
+        self.a = (value >> 8) as u8;
+        self.f = value as u8;
+    }
+
+    fn b(&self) -> u8 {
+        (self.bc >> 8) as u8
+    }
+
+    fn set_b(&mut self, value: u8) {
+        self.bc = (self.bc & 0x00FF) | ((value as u16) << 8);
+    }
+
+    fn jump_relative(&mut self, offset: i8) {
+        self.pc = self.pc.wrapping_add(offset as u16);
+    }
+
+    fn call<B: Bus>(&mut self, bus: &mut B, address: u16) {
+        let pc = self.pc;
+        self.push(bus, pc);
+        self.pc = address;
+    }
+
+    /// Fetch an opcode, which counts towards the refresh register.
+    fn fetch_opcode<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        self.bump_r();
+        self.fetch(bus)
+    }
+
+    fn fetch<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        let value = bus.read(self.pc);
+        self.pc = self.pc.wrapping_add(1);
+        value
+    }
+
+    fn fetch_word<B: Bus>(&mut self, bus: &mut B) -> u16 {
+        let low = self.fetch(bus) as u16;
+        let high = self.fetch(bus) as u16;
+        (high << 8) | low
+    }
+
+    fn push<B: Bus>(&mut self, bus: &mut B, value: u16) {
+        self.sp = self.sp.wrapping_sub(2);
+        write_word(bus, self.sp, value);
+    }
+
+    fn pop<B: Bus>(&mut self, bus: &mut B) -> u16 {
+        let value = read_word(bus, self.sp);
+        self.sp = self.sp.wrapping_add(2);
+        value
+    }
+
+    /// The bottom seven bits of R count opcode fetches.
+    fn bump_r(&mut self) {
+        self.r = (self.r & 0x80) | (self.r.wrapping_add(1) & 0x7F);
+    }
+
+    fn add_cycles(&mut self, cycles: u32) {
+        self.cycles = self.cycles.wrapping_add(cycles);
+    }
+}
+
+/// Sign, zero and the undocumented bits, from a result.
+fn sz(value: u8) -> u8 {
+    (value & (FLAG_S | FLAG_X | FLAG_Y)) | if value == 0 { FLAG_Z } else { 0 }
+}
+
+/// As `sz`, plus parity.
+fn szp(value: u8) -> u8 {
+    sz(value) | if value.count_ones() & 1 == 0 { FLAG_PV } else { 0 }
+}
+
+fn read_word<B: Bus>(bus: &mut B, address: u16) -> u16 {
+    let low = bus.read(address) as u16;
+    let high = bus.read(address.wrapping_add(1)) as u16;
+    (high << 8) | low
+}
+
+fn write_word<B: Bus>(bus: &mut B, address: u16, value: u16) {
+    bus.write(address, value as u8);
+    bus.write(address.wrapping_add(1), (value >> 8) as u8);
+}